@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE http_trigger ADD COLUMN validate_request BOOLEAN NOT NULL DEFAULT FALSE;
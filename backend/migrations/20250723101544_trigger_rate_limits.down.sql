@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE workspace_settings DROP COLUMN webhook_rate_limits;
+ALTER TABLE http_trigger DROP COLUMN rate_limits;
+DROP TABLE rate_limit_counter;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE rate_limit_counter (
+    workspace_id VARCHAR(50) NOT NULL,
+    key TEXT NOT NULL,
+    window_start TIMESTAMPTZ NOT NULL,
+    window_end TIMESTAMPTZ NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (workspace_id, key, window_start)
+);
+
+CREATE INDEX idx_rate_limit_counter_window_end ON rate_limit_counter (window_end);
+
+GRANT ALL ON rate_limit_counter TO windmill_user;
+GRANT ALL ON rate_limit_counter TO windmill_admin;
+
+ALTER TABLE http_trigger ADD COLUMN rate_limits JSONB NULL;
+ALTER TABLE workspace_settings ADD COLUMN webhook_rate_limits JSONB NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE workspace_settings DROP COLUMN idempotency_window_s;
+DROP TABLE job_idempotency_key;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE job_idempotency_key (
+    workspace_id VARCHAR(50) NOT NULL,
+    scope VARCHAR(600) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    job_id UUID NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (workspace_id, scope, key)
+);
+
+CREATE INDEX idx_job_idempotency_key_expires_at ON job_idempotency_key (expires_at);
+
+GRANT ALL ON job_idempotency_key TO windmill_user;
+GRANT ALL ON job_idempotency_key TO windmill_admin;
+
+ALTER TABLE workspace_settings ADD COLUMN idempotency_window_s INTEGER NULL;
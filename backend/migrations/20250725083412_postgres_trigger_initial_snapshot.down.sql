@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE postgres_trigger
+    DROP COLUMN initial_snapshot,
+    DROP COLUMN snapshot_completed_at;
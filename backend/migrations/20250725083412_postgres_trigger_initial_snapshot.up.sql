@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE postgres_trigger
+    ADD COLUMN initial_snapshot BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN snapshot_completed_at TIMESTAMPTZ NULL;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE postgres_trigger
+    DROP COLUMN batching,
+    DROP COLUMN ack_after_success;
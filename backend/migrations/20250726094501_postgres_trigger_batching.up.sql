@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE postgres_trigger
+    ADD COLUMN batching JSONB NULL,
+    ADD COLUMN ack_after_success BOOLEAN NOT NULL DEFAULT FALSE;
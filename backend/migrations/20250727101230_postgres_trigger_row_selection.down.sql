@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE postgres_trigger
+    DROP COLUMN row_filters,
+    DROP COLUMN column_projection,
+    DROP COLUMN include_old_row;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE postgres_trigger
+    ADD COLUMN row_filters JSONB NULL,
+    ADD COLUMN column_projection JSONB NULL,
+    ADD COLUMN include_old_row BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TYPE TRIGGER_KIND ADD VALUE IF NOT EXISTS 'mysql';
+ALTER TYPE JOB_TRIGGER_KIND ADD VALUE IF NOT EXISTS 'mysql';
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DELETE FROM resource_type WHERE workspace_id = 'admins' AND name = 'amqp' AND created_by = 'system';
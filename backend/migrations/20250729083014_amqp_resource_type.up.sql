@@ -0,0 +1,34 @@
+-- Add up migration script here
+INSERT INTO resource_type(workspace_id, name, schema, description, created_by) VALUES
+	('admins', 'amqp', '{
+    "type": "object",
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "required": ["host"],
+    "properties": {
+        "host": {
+            "type": "string",
+            "description": "Hostname of the AMQP 0-9-1 broker"
+        },
+        "port": {
+            "type": "integer",
+            "description": "Port of the broker, 5672 or 5671 with tls by default"
+        },
+        "username": {
+            "type": "string",
+            "default": "guest"
+        },
+        "password": {
+            "type": "string",
+            "default": "guest"
+        },
+        "vhost": {
+            "type": "string",
+            "default": "/"
+        },
+        "tls": {
+            "type": "boolean",
+            "default": false
+        }
+    }
+}', 'Connection to an AMQP 0-9-1 broker such as RabbitMQ', 'system')
+ON CONFLICT DO NOTHING;
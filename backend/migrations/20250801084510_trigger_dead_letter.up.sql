@@ -0,0 +1,42 @@
+-- Add up migration script here
+CREATE TABLE trigger_dead_letter (
+    id BIGINT PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    workspace_id VARCHAR(50) NOT NULL,
+    trigger_kind TRIGGER_KIND NOT NULL,
+    trigger_path VARCHAR(255) NOT NULL,
+    runnable_path VARCHAR(255) NOT NULL,
+    is_flow BOOLEAN NOT NULL,
+    args JSONB NOT NULL,
+    failure_kind VARCHAR(10) NOT NULL CHECK (failure_kind IN ('push', 'job')),
+    error TEXT NOT NULL,
+    job_id UUID NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    edited_by VARCHAR(50) NULL,
+    edited_at TIMESTAMPTZ NULL,
+    replayed_by VARCHAR(50) NULL,
+    replayed_at TIMESTAMPTZ NULL,
+    replayed_job_id UUID NULL,
+    FOREIGN KEY (workspace_id) REFERENCES workspace(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX trigger_dead_letter_job_id_idx ON trigger_dead_letter (job_id) WHERE job_id IS NOT NULL;
+CREATE INDEX trigger_dead_letter_workspace_created_at_idx ON trigger_dead_letter (workspace_id, created_at DESC);
+
+GRANT ALL ON trigger_dead_letter TO windmill_user;
+GRANT ALL ON trigger_dead_letter TO windmill_admin;
+
+ALTER TABLE trigger_dead_letter ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY admin_policy ON trigger_dead_letter FOR ALL TO windmill_admin USING (true);
+CREATE POLICY see_folder_extra_perms_user_select ON trigger_dead_letter FOR SELECT TO windmill_user
+USING (SPLIT_PART(trigger_dead_letter.trigger_path, '/', 1) = 'f' AND SPLIT_PART(trigger_dead_letter.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_read'), ',')::text[]));
+CREATE POLICY see_folder_extra_perms_user_insert ON trigger_dead_letter FOR INSERT TO windmill_user
+WITH CHECK (SPLIT_PART(trigger_dead_letter.trigger_path, '/', 1) = 'f' AND SPLIT_PART(trigger_dead_letter.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_write'), ',')::text[]));
+CREATE POLICY see_folder_extra_perms_user_update ON trigger_dead_letter FOR UPDATE TO windmill_user
+USING (SPLIT_PART(trigger_dead_letter.trigger_path, '/', 1) = 'f' AND SPLIT_PART(trigger_dead_letter.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_write'), ',')::text[]));
+CREATE POLICY see_folder_extra_perms_user_delete ON trigger_dead_letter FOR DELETE TO windmill_user
+USING (SPLIT_PART(trigger_dead_letter.trigger_path, '/', 1) = 'f' AND SPLIT_PART(trigger_dead_letter.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.folders_write'), ',')::text[]));
+CREATE POLICY see_own ON trigger_dead_letter FOR ALL TO windmill_user
+USING (SPLIT_PART(trigger_dead_letter.trigger_path, '/', 1) = 'u' AND SPLIT_PART(trigger_dead_letter.trigger_path, '/', 2) = current_setting('session.user'));
+CREATE POLICY see_member ON trigger_dead_letter FOR ALL TO windmill_user
+USING (SPLIT_PART(trigger_dead_letter.trigger_path, '/', 1) = 'g' AND SPLIT_PART(trigger_dead_letter.trigger_path, '/', 2) = any(regexp_split_to_array(current_setting('session.groups'), ',')::text[]));
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE websocket_trigger DROP COLUMN args_transform, DROP COLUMN correlation;
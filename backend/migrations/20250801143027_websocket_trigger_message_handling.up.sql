@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE websocket_trigger ADD COLUMN args_transform JSONB NULL, ADD COLUMN correlation JSONB NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS promotion;
+DROP TYPE IF EXISTS PROMOTION_STATUS;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+CREATE TYPE PROMOTION_STATUS AS ENUM ('pending', 'approved', 'rejected', 'applied');
+
+CREATE TABLE promotion (
+    id BIGINT PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    workspace_id VARCHAR(50) NOT NULL,
+    target_workspace_id VARCHAR(50) NOT NULL,
+    description TEXT NULL,
+    items JSONB NOT NULL,
+    remappings JSONB NOT NULL DEFAULT '[]'::jsonb,
+    diff JSONB NOT NULL,
+    approver_group VARCHAR(50) NULL,
+    status PROMOTION_STATUS NOT NULL DEFAULT 'pending',
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reviewed_by VARCHAR(255) NULL,
+    reviewed_at TIMESTAMPTZ NULL,
+    applied_by VARCHAR(255) NULL,
+    applied_at TIMESTAMPTZ NULL,
+    FOREIGN KEY (workspace_id) REFERENCES workspace(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_workspace_id) REFERENCES workspace(id) ON DELETE CASCADE
+);
+
+CREATE INDEX promotion_workspace_id_idx ON promotion (workspace_id, id DESC);
+CREATE INDEX promotion_target_workspace_id_idx ON promotion (target_workspace_id, id DESC);
+
+GRANT ALL ON promotion TO windmill_user;
+GRANT ALL ON promotion TO windmill_admin;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE git_sync_pull_state;
+ALTER TABLE deployment_metadata DROP COLUMN commit_sha;
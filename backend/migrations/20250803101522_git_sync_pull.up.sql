@@ -0,0 +1,16 @@
+-- Add up migration script here
+ALTER TABLE deployment_metadata ADD COLUMN commit_sha VARCHAR(64) NULL;
+
+CREATE TABLE git_sync_pull_state (
+    workspace_id VARCHAR(50) NOT NULL,
+    git_repo_resource_path VARCHAR(255) NOT NULL,
+    commit_sha VARCHAR(64) NULL,
+    items JSONB NOT NULL DEFAULT '{}'::jsonb,
+    pulled_at TIMESTAMPTZ NULL,
+    error TEXT NULL,
+    PRIMARY KEY (workspace_id, git_repo_resource_path),
+    FOREIGN KEY (workspace_id) REFERENCES workspace(id) ON DELETE CASCADE
+);
+
+GRANT ALL ON git_sync_pull_state TO windmill_user;
+GRANT ALL ON git_sync_pull_state TO windmill_admin;
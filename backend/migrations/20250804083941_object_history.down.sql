@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE object_history;
+DROP TYPE OBJECT_HISTORY_ACTION;
@@ -0,0 +1,63 @@
+-- Add up migration script here
+CREATE TYPE OBJECT_HISTORY_ACTION AS ENUM ('baseline', 'create', 'update', 'delete');
+
+CREATE TABLE object_history (
+    id BIGINT PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    workspace_id VARCHAR(50) NOT NULL,
+    kind VARCHAR(50) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    action OBJECT_HISTORY_ACTION NOT NULL,
+    value JSONB NULL,
+    previous_value JSONB NULL,
+    edited_by VARCHAR(255) NOT NULL,
+    edited_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    FOREIGN KEY (workspace_id) REFERENCES workspace(id) ON DELETE CASCADE
+);
+
+CREATE INDEX object_history_path_idx ON object_history (workspace_id, kind, path, id DESC);
+CREATE INDEX object_history_edited_at_idx ON object_history (workspace_id, edited_at);
+
+-- the current state of existing objects is the baseline their first change is compared to
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'resource', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM resource t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'variable', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM variable t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'schedule', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM schedule t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'folder', name, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM folder t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'resource_type', name, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM resource_type t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'user', email, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM usr t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'group', name, 'baseline', to_jsonb(t) || jsonb_build_object('members', (SELECT COALESCE(jsonb_agg(m.usr ORDER BY m.usr), '[]'::jsonb) FROM usr_to_group m WHERE m.workspace_id = t.workspace_id AND m.group_ = t.name)) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM group_ t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'settings', 'settings', 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM workspace_settings t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'http_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM http_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'websocket_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM websocket_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'kafka_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM kafka_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'nats_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM nats_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'postgres_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM postgres_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'mqtt_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM mqtt_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'sqs_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM sqs_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'gcp_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM gcp_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'mysql_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM mysql_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'amqp_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM amqp_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 'redis_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM redis_trigger t;
+INSERT INTO object_history (workspace_id, kind, path, action, value, edited_by)
+SELECT workspace_id, 's3_trigger', path, 'baseline', to_jsonb(t) - 'server_id' - 'last_server_ping' - 'error', 'system' FROM s3_trigger t;
+
+GRANT ALL ON object_history TO windmill_user;
+GRANT ALL ON object_history TO windmill_admin;
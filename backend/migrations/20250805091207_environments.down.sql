@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS environment_binding;
+DROP TABLE IF EXISTS resource_override;
+DROP TABLE IF EXISTS variable_override;
+DROP TABLE IF EXISTS environment;
@@ -0,0 +1,52 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS environment (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    description TEXT,
+    worker_tags TEXT[] NOT NULL DEFAULT '{}',
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, name)
+);
+
+-- the value is encrypted with the workspace key when the overridden variable is secret
+CREATE TABLE IF NOT EXISTS variable_override (
+    workspace_id VARCHAR(50) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    environment VARCHAR(50) NOT NULL,
+    value VARCHAR(15000) NOT NULL,
+    edited_by VARCHAR(255) NOT NULL,
+    edited_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, path, environment),
+    FOREIGN KEY (workspace_id, environment) REFERENCES environment(workspace_id, name) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS resource_override (
+    workspace_id VARCHAR(50) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    environment VARCHAR(50) NOT NULL,
+    value JSONB NOT NULL,
+    edited_by VARCHAR(255) NOT NULL,
+    edited_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, path, environment),
+    FOREIGN KEY (workspace_id, environment) REFERENCES environment(workspace_id, name) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- kind is the trigger kind of the jobs started by the bound schedule or trigger
+CREATE TABLE IF NOT EXISTS environment_binding (
+    workspace_id VARCHAR(50) NOT NULL,
+    kind JOB_TRIGGER_KIND NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    environment VARCHAR(50) NOT NULL,
+    PRIMARY KEY (workspace_id, kind, path),
+    FOREIGN KEY (workspace_id, environment) REFERENCES environment(workspace_id, name) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+GRANT ALL ON environment TO windmill_user;
+GRANT ALL ON environment TO windmill_admin;
+GRANT ALL ON variable_override TO windmill_user;
+GRANT ALL ON variable_override TO windmill_admin;
+GRANT ALL ON resource_override TO windmill_user;
+GRANT ALL ON resource_override TO windmill_admin;
+GRANT ALL ON environment_binding TO windmill_user;
+GRANT ALL ON environment_binding TO windmill_admin;
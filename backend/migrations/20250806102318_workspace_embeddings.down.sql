@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS workspace_embedding_queue;
+DROP TABLE IF EXISTS workspace_embedding;
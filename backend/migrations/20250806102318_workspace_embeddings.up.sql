@@ -0,0 +1,27 @@
+-- Add up migration script here
+-- embeddings of the workspace items, computed once by one of the servers running the embedding model
+CREATE TABLE IF NOT EXISTS workspace_embedding (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    model VARCHAR(255) NOT NULL,
+    summary TEXT NOT NULL DEFAULT '',
+    text_hash VARCHAR(64) NOT NULL,
+    embedding REAL[] NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, kind, path, model)
+);
+
+-- workspace items deployed since they were last embedded
+CREATE TABLE IF NOT EXISTS workspace_embedding_queue (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    queued_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, kind, path)
+);
+
+GRANT ALL ON workspace_embedding TO windmill_user;
+GRANT ALL ON workspace_embedding TO windmill_admin;
+GRANT ALL ON workspace_embedding_queue TO windmill_user;
+GRANT ALL ON workspace_embedding_queue TO windmill_admin;
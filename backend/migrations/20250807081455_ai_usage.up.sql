@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- tokens used through the AI proxy, aggregated per day (UTC)
+CREATE TABLE IF NOT EXISTS ai_usage (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    provider VARCHAR(50) NOT NULL,
+    model VARCHAR(255) NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    input_tokens BIGINT NOT NULL DEFAULT 0,
+    output_tokens BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (workspace_id, day, email, provider, model)
+);
+
+GRANT ALL ON ai_usage TO windmill_user;
+GRANT ALL ON ai_usage TO windmill_admin;
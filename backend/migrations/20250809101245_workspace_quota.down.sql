@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS workspace_quota_log_bytes_trigger ON job_logs;
+DROP TRIGGER IF EXISTS workspace_quota_result_bytes_trigger ON v2_job_completed;
+DROP FUNCTION IF EXISTS workspace_quota_count_log_bytes();
+DROP FUNCTION IF EXISTS workspace_quota_count_result_bytes();
+DROP TABLE IF EXISTS workspace_quota_warning;
+DROP TABLE IF EXISTS workspace_quota;
+DROP FUNCTION IF EXISTS workspace_quota_init_usage();
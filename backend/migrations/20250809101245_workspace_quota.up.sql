@@ -0,0 +1,92 @@
+-- Add up migration script here
+-- quotas of the workspace set by the superadmins, and the last measure of its object storage
+-- usage which is too expensive to compute when jobs are pushed
+CREATE TABLE IF NOT EXISTS workspace_quota (
+    workspace_id VARCHAR(50) PRIMARY KEY REFERENCES workspace(id) ON DELETE CASCADE,
+    quotas JSONB NOT NULL DEFAULT '{}'::jsonb,
+    object_storage_bytes BIGINT,
+    object_storage_measured_at TIMESTAMP WITH TIME ZONE,
+    -- size of the results and logs of the workspace, kept up to date by the triggers below so
+    -- that checking the quotas does not scan the jobs of the workspace
+    result_bytes BIGINT NOT NULL DEFAULT 0,
+    log_bytes BIGINT NOT NULL DEFAULT 0,
+    updated_by VARCHAR(255),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+-- soft quota warnings already sent to the workspace, at most one per quota and day
+CREATE TABLE IF NOT EXISTS workspace_quota_warning (
+    workspace_id VARCHAR(50) NOT NULL REFERENCES workspace(id) ON DELETE CASCADE,
+    quota VARCHAR(50) NOT NULL,
+    day DATE NOT NULL,
+    PRIMARY KEY (workspace_id, quota, day)
+);
+
+GRANT ALL ON workspace_quota TO windmill_user;
+GRANT ALL ON workspace_quota TO windmill_admin;
+GRANT ALL ON workspace_quota_warning TO windmill_user;
+GRANT ALL ON workspace_quota_warning TO windmill_admin;
+
+-- the counters start from what the workspace already stores, only the workspaces that have a
+-- workspace_quota row are counted
+CREATE OR REPLACE FUNCTION workspace_quota_init_usage()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.result_bytes := (SELECT COALESCE(SUM(pg_column_size(result)), 0) FROM v2_job_completed
+        WHERE workspace_id = NEW.workspace_id);
+    NEW.log_bytes := (SELECT COALESCE(SUM(octet_length(logs)), 0) FROM job_logs
+        WHERE workspace_id = NEW.workspace_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER workspace_quota_init_usage_trigger
+BEFORE INSERT ON workspace_quota
+FOR EACH ROW
+EXECUTE FUNCTION workspace_quota_init_usage();
+
+CREATE OR REPLACE FUNCTION workspace_quota_count_result_bytes()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE workspace_quota SET result_bytes = result_bytes - COALESCE(pg_column_size(OLD.result), 0)
+        WHERE workspace_id = OLD.workspace_id;
+    ELSIF TG_OP = 'UPDATE' THEN
+        UPDATE workspace_quota SET result_bytes = result_bytes
+            + COALESCE(pg_column_size(NEW.result), 0) - COALESCE(pg_column_size(OLD.result), 0)
+        WHERE workspace_id = NEW.workspace_id;
+    ELSE
+        UPDATE workspace_quota SET result_bytes = result_bytes + COALESCE(pg_column_size(NEW.result), 0)
+        WHERE workspace_id = NEW.workspace_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER workspace_quota_result_bytes_trigger
+AFTER INSERT OR UPDATE OF result OR DELETE ON v2_job_completed
+FOR EACH ROW
+EXECUTE FUNCTION workspace_quota_count_result_bytes();
+
+CREATE OR REPLACE FUNCTION workspace_quota_count_log_bytes()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE workspace_quota SET log_bytes = log_bytes - COALESCE(octet_length(OLD.logs), 0)
+        WHERE workspace_id = OLD.workspace_id;
+    ELSIF TG_OP = 'UPDATE' THEN
+        UPDATE workspace_quota SET log_bytes = log_bytes
+            + COALESCE(octet_length(NEW.logs), 0) - COALESCE(octet_length(OLD.logs), 0)
+        WHERE workspace_id = NEW.workspace_id;
+    ELSE
+        UPDATE workspace_quota SET log_bytes = log_bytes + COALESCE(octet_length(NEW.logs), 0)
+        WHERE workspace_id = NEW.workspace_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER workspace_quota_log_bytes_trigger
+AFTER INSERT OR UPDATE OF logs OR DELETE ON job_logs
+FOR EACH ROW
+EXECUTE FUNCTION workspace_quota_count_log_bytes();
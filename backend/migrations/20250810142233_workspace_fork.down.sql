@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS workspace_fork_item;
+DROP TABLE IF EXISTS workspace_fork;
+DROP TYPE IF EXISTS FORK_SECRETS;
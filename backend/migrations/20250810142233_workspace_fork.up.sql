@@ -0,0 +1,33 @@
+-- Add up migration script here
+CREATE TYPE FORK_SECRETS AS ENUM ('reference', 'mask');
+
+CREATE TABLE workspace_fork (
+    workspace_id VARCHAR(50) PRIMARY KEY,
+    parent_workspace_id VARCHAR(50) NOT NULL,
+    secrets FORK_SECRETS NOT NULL,
+    created_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    merged_by VARCHAR(255) NULL,
+    merged_at TIMESTAMPTZ NULL,
+    FOREIGN KEY (workspace_id) REFERENCES workspace(id) ON DELETE CASCADE,
+    FOREIGN KEY (parent_workspace_id) REFERENCES workspace(id) ON DELETE CASCADE
+);
+
+CREATE INDEX workspace_fork_parent_workspace_id_idx ON workspace_fork (parent_workspace_id);
+
+-- fingerprints of the items copied into the fork as of their copy or last merge, in the fork and
+-- in its parent
+CREATE TABLE workspace_fork_item (
+    workspace_id VARCHAR(50) NOT NULL,
+    kind VARCHAR(20) NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    fork_fingerprint CHAR(64) NOT NULL,
+    parent_fingerprint CHAR(64) NOT NULL,
+    PRIMARY KEY (workspace_id, kind, path),
+    FOREIGN KEY (workspace_id) REFERENCES workspace_fork(workspace_id) ON DELETE CASCADE
+);
+
+GRANT ALL ON workspace_fork TO windmill_user;
+GRANT ALL ON workspace_fork TO windmill_admin;
+GRANT ALL ON workspace_fork_item TO windmill_user;
+GRANT ALL ON workspace_fork_item TO windmill_admin;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE git_sync_pull_state DROP COLUMN IF EXISTS variable_hashes;
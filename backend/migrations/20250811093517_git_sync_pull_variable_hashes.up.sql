@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- hashes of the variables as written by the last pull, variables have no edit time to tell
+-- whether they were edited in the workspace since
+ALTER TABLE git_sync_pull_state ADD COLUMN IF NOT EXISTS variable_hashes JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE postgres_trigger DROP COLUMN last_window_batch;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE postgres_trigger ADD COLUMN last_window_batch JSONB NULL;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TYPE JOB_TRIGGER_KIND ADD VALUE IF NOT EXISTS 'mqtt';
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- conversation of the AI agent jobs suspended while their tool jobs run
+CREATE TABLE IF NOT EXISTS ai_agent_state (
+    id UUID REFERENCES v2_job_queue (id) ON DELETE CASCADE PRIMARY KEY NOT NULL,
+    state JSONB NOT NULL
+);
+
+GRANT ALL ON ai_agent_state TO windmill_user;
+GRANT ALL ON ai_agent_state TO windmill_admin;
@@ -793,6 +793,34 @@ pub async fn delete_expired_items(db: &DB) -> () {
         Err(e) => tracing::error!("Error deleting cache resource {}", e.to_string()),
     }
 
+    let deleted_rate_limit_counters =
+        sqlx::query!("DELETE FROM rate_limit_counter WHERE window_end < now()",)
+            .execute(db)
+            .await;
+
+    match deleted_rate_limit_counters {
+        Ok(res) => {
+            if res.rows_affected() > 0 {
+                tracing::debug!("deleted {} expired rate limit counters", res.rows_affected())
+            }
+        }
+        Err(e) => tracing::error!("Error deleting rate limit counters {}", e.to_string()),
+    }
+
+    let deleted_idempotency_keys =
+        sqlx::query!("DELETE FROM job_idempotency_key WHERE expires_at <= now()",)
+            .execute(db)
+            .await;
+
+    match deleted_idempotency_keys {
+        Ok(res) => {
+            if res.rows_affected() > 0 {
+                tracing::debug!("deleted {} expired idempotency keys", res.rows_affected())
+            }
+        }
+        Err(e) => tracing::error!("Error deleting idempotency keys {}", e.to_string()),
+    }
+
     let deleted_expired_variables = sqlx::query_scalar!(
         "DELETE FROM variable WHERE expires_at IS NOT NULL AND expires_at < now() RETURNING path",
     )
@@ -1691,6 +1719,7 @@ async fn handle_zombie_jobs(db: &Pool<Postgres>, base_internal_url: &str, worker
                     AND running = true
                     AND kind NOT IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow')
                     AND same_worker = false
+                    AND suspend_until IS NULL
                     AND (zjc.counter IS NULL OR zjc.counter <= $2)
                 FOR UPDATE of q SKIP LOCKED
             ),
@@ -1865,7 +1894,7 @@ async fn handle_zombie_jobs(db: &Pool<Postgres>, base_internal_url: &str, worker
         vec![]
     } else {
         sqlx::query_as::<_, QueuedJob>("SELECT *, null as workflow_as_code_status FROM v2_as_queue WHERE last_ping < now() - ($1 || ' seconds')::interval
-    AND running = true  AND job_kind NOT IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow') AND same_worker = false")
+    AND running = true  AND job_kind NOT IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow') AND same_worker = false AND suspend_until IS NULL")
         .bind(ZOMBIE_JOB_TIMEOUT.as_str())
         .fetch_all(db)
         .await
@@ -0,0 +1,170 @@
+use serde_json::json;
+use sqlx::{
+    types::{Json, Uuid},
+    Pool, Postgres,
+};
+use windmill_common::worker::{make_pull_query, make_suspended_pull_query};
+use windmill_queue::{add_completed_job, PulledJob};
+
+async fn queue_job(db: &Pool<Postgres>, kind: &str, tag: &str, parent_job: Option<Uuid>) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO v2_job (id, workspace_id, kind, tag, parent_job, created_by, permissioned_as, permissioned_as_email)
+        VALUES ($1, 'test-workspace', $2::job_kind, $3, $4, 'test-user', 'u/test-user', 'test@windmill.dev')",
+    )
+    .bind(id)
+    .bind(kind)
+    .bind(tag)
+    .bind(parent_job)
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO v2_job_queue (id, workspace_id, scheduled_for, tag) VALUES ($1, 'test-workspace', now(), $2)",
+    )
+    .bind(id)
+    .bind(tag)
+    .execute(db)
+    .await
+    .unwrap();
+    id
+}
+
+/// An agent job pulled and suspended on its tool jobs, the way the worker leaves it
+async fn suspend_agent(db: &Pool<Postgres>, tool_jobs: i32) -> Uuid {
+    let id = queue_job(db, "aiagent", "flow", None).await;
+    sqlx::query(
+        "UPDATE v2_job_queue SET running = true, suspend = $2, suspend_until = now() + interval '1 hour'
+        WHERE id = $1",
+    )
+    .bind(id)
+    .bind(tool_jobs)
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO ai_agent_state (id, state) VALUES ($1, '{}'::jsonb)")
+        .bind(id)
+        .execute(db)
+        .await
+        .unwrap();
+    id
+}
+
+async fn pull(db: &Pool<Postgres>, query: &str) -> Option<PulledJob> {
+    sqlx::query_as::<_, PulledJob>(query)
+        .bind("test-worker")
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn complete(db: &Pool<Postgres>, job: &PulledJob, success: bool) {
+    add_completed_job(
+        db,
+        &job.job,
+        success,
+        false,
+        Json(&json!({ "result": 42 })),
+        None,
+        0,
+        None,
+        false,
+        None,
+    )
+    .await
+    .unwrap();
+}
+
+async fn suspend(db: &Pool<Postgres>, id: Uuid) -> i32 {
+    sqlx::query_scalar("SELECT suspend FROM v2_job_queue WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_agent_resumed_when_its_tool_jobs_complete(db: Pool<Postgres>) {
+    let agent = suspend_agent(&db, 2).await;
+    queue_job(&db, "script", "tool", Some(agent)).await;
+    queue_job(&db, "script", "tool", Some(agent)).await;
+
+    let suspended_pull_query = make_suspended_pull_query(&["flow".to_string()]);
+    let tool_pull_query = make_pull_query(&["tool".to_string()]);
+
+    // suspended agents are not pulled as new jobs
+    assert!(pull(&db, &make_pull_query(&["flow".to_string()]))
+        .await
+        .is_none());
+
+    let first = pull(&db, &tool_pull_query).await.unwrap();
+    complete(&db, &first, true).await;
+    assert_eq!(suspend(&db, agent).await, 1);
+    assert!(pull(&db, &suspended_pull_query).await.is_none());
+
+    // failed tools resume the agent too, their error is passed back to the model
+    let second = pull(&db, &tool_pull_query).await.unwrap();
+    complete(&db, &second, false).await;
+    assert_eq!(suspend(&db, agent).await, 0);
+
+    let resumed = pull(&db, &suspended_pull_query).await.unwrap();
+    assert_eq!(resumed.id, agent);
+    let state_kept =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM ai_agent_state WHERE id = $1)")
+            .bind(agent)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert!(state_kept);
+
+    // the state is dropped along with the queued agent job
+    complete(&db, &resumed, true).await;
+    let state_kept =
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM ai_agent_state WHERE id = $1)")
+            .bind(agent)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert!(!state_kept);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_agent_resumed_after_tool_timeout(db: Pool<Postgres>) {
+    let agent = suspend_agent(&db, 1).await;
+    queue_job(&db, "script", "tool", Some(agent)).await;
+
+    let suspended_pull_query = make_suspended_pull_query(&["flow".to_string()]);
+    assert!(pull(&db, &suspended_pull_query).await.is_none());
+
+    sqlx::query(
+        "UPDATE v2_job_queue SET suspend_until = now() - interval '1 second' WHERE id = $1",
+    )
+    .bind(agent)
+    .execute(&db)
+    .await
+    .unwrap();
+    assert_eq!(
+        pull(&db, &suspended_pull_query).await.map(|job| job.id),
+        Some(agent)
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_children_of_other_jobs_do_not_resume(db: Pool<Postgres>) {
+    let parent = queue_job(&db, "script", "parent", None).await;
+    sqlx::query(
+        "UPDATE v2_job_queue SET running = true, suspend = 1, suspend_until = now() + interval '1 hour'
+        WHERE id = $1",
+    )
+    .bind(parent)
+    .execute(&db)
+    .await
+    .unwrap();
+    queue_job(&db, "script", "tool", Some(parent)).await;
+
+    let child = pull(&db, &make_pull_query(&["tool".to_string()]))
+        .await
+        .unwrap();
+    complete(&db, &child, true).await;
+    assert_eq!(suspend(&db, parent).await, 1);
+}
@@ -0,0 +1,299 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+pub struct ApiServer {
+    pub addr: std::net::SocketAddr,
+    tx: tokio::sync::broadcast::Sender<()>,
+    task: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl ApiServer {
+    pub async fn start(db: Pool<Postgres>) -> Self {
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+
+        let sock = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = sock.local_addr().unwrap();
+        drop(sock);
+        let (port_tx, port_rx) = tokio::sync::oneshot::channel::<String>();
+
+        let task = tokio::task::spawn(windmill_api::run_server(
+            db.clone(),
+            None,
+            None,
+            addr,
+            rx,
+            port_tx,
+            false,
+            false,
+            format!("http://localhost:{}", addr.port()),
+        ));
+
+        port_rx.await.expect("failed to receive port");
+
+        windmill_common::cache::clear();
+
+        Self { addr, tx, task }
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        let Self { tx, task, .. } = self;
+        drop(tx);
+        task.await.unwrap()
+    }
+
+    /// Chat completion through the proxy, with the resource of the mock provider
+    async fn proxy(&self, provider: &str, path: &str, body: Value) -> (u16, String) {
+        let response = reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{}/api/w/test-workspace/ai/proxy/{path}",
+                self.addr.port()
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .header("X-Provider", provider)
+            .header("X-Resource-Path", "u/test-user/ai")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status().as_u16();
+        (status, response.text().await.unwrap())
+    }
+}
+
+fn sse(events: Vec<Value>) -> Response {
+    let body = events
+        .into_iter()
+        .map(|event| format!("data: {event}\n\n"))
+        .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+        .collect::<String>();
+    ([(header::CONTENT_TYPE, "text/event-stream")], body).into_response()
+}
+
+/// OpenAI chat completions, the usage is only streamed when asked for
+async fn chat_completions(Json(body): Json<Value>) -> Response {
+    let model = body["model"].clone();
+    if body["stream"] != true {
+        return Json(json!({
+            "model": model,
+            "choices": [{"message": {"role": "assistant", "content": "hello"}}],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 20, "total_tokens": 30}
+        }))
+        .into_response();
+    }
+    let mut events = vec![
+        json!({"model": model, "choices": [{"delta": {"content": "hel"}}]}),
+        json!({"model": model, "choices": [{"delta": {"content": "lo"}}]}),
+    ];
+    if body["stream_options"]["include_usage"] == true {
+        events.push(json!({
+            "model": model,
+            "choices": [],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 30, "total_tokens": 42}
+        }));
+    }
+    sse(events)
+}
+
+/// Anthropic messages, streamed with the output tokens counted cumulatively
+async fn messages(Json(body): Json<Value>) -> Response {
+    sse(vec![
+        json!({
+            "type": "message_start",
+            "message": {
+                "model": body["model"],
+                "usage": {"input_tokens": 20, "cache_read_input_tokens": 5, "output_tokens": 1}
+            }
+        }),
+        json!({"type": "content_block_delta", "delta": {"text": "hello"}}),
+        json!({"type": "message_delta", "usage": {"output_tokens": 15}}),
+    ])
+}
+
+/// Mock provider and the resource pointing to it
+async fn setup(db: &Pool<Postgres>) {
+    let app = Router::new()
+        .route("/chat/completions", post(chat_completions))
+        .route("/messages", post(messages));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    sqlx::query(
+        "INSERT INTO resource (workspace_id, path, value, resource_type, created_by)
+        VALUES ('test-workspace', 'u/test-user/ai', $1, 'openai', 'test-user')",
+    )
+    .bind(json!({"base_url": format!("http://{addr}"), "api_key": "key"}))
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+async fn set_budgets(db: &Pool<Postgres>, budgets: Value) {
+    sqlx::query(
+        "UPDATE workspace_settings SET ai_config = $1 WHERE workspace_id = 'test-workspace'",
+    )
+    .bind(json!({ "budgets": budgets }))
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+/// Usage is recorded once the response has been forwarded, in the background
+async fn wait_for_usage(
+    db: &Pool<Postgres>,
+    requests: i64,
+) -> Vec<(String, String, i64, i64, i64)> {
+    for _ in 0..50 {
+        let usage: Vec<(String, String, i64, i64, i64)> = sqlx::query_as(
+            "SELECT provider, model, requests, input_tokens, output_tokens FROM ai_usage
+            WHERE workspace_id = 'test-workspace' AND email = 'test@windmill.dev'
+            ORDER BY provider, model",
+        )
+        .fetch_all(db)
+        .await
+        .unwrap();
+        if usage.iter().map(|u| u.2).sum::<i64>() >= requests {
+            return usage;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("the usage of {requests} requests was not recorded");
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_usage_of_responses(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+
+    let (status, response) = server
+        .proxy(
+            "azure_openai",
+            "chat/completions",
+            json!({"model": "gpt-4o", "messages": []}),
+        )
+        .await;
+    assert_eq!(status, 200, "{response}");
+    assert_eq!(
+        wait_for_usage(&db, 1).await,
+        vec![("azure_openai".to_string(), "gpt-4o".to_string(), 1, 10, 20)]
+    );
+
+    // the proxy asks for the usage of the streamed response
+    let (status, response) = server
+        .proxy(
+            "azure_openai",
+            "chat/completions",
+            json!({"model": "gpt-4o", "messages": [], "stream": true}),
+        )
+        .await;
+    assert_eq!(status, 200, "{response}");
+    assert!(response.contains("\"total_tokens\":42"), "{response}");
+
+    let (status, response) = server
+        .proxy(
+            "customai",
+            "messages",
+            json!({"model": "claude-sonnet", "messages": [], "stream": true}),
+        )
+        .await;
+    assert_eq!(status, 200, "{response}");
+    assert_eq!(
+        wait_for_usage(&db, 3).await,
+        vec![
+            ("azure_openai".to_string(), "gpt-4o".to_string(), 2, 22, 50),
+            (
+                "customai".to_string(),
+                "claude-sonnet".to_string(),
+                1,
+                25,
+                15
+            ),
+        ]
+    );
+
+    let usage: Value = reqwest::Client::new()
+        .get(format!(
+            "http://localhost:{}/api/w/test-workspace/ai/usage?group_by=provider",
+            server.addr.port()
+        ))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        usage,
+        json!([
+            {"key": "azure_openai", "requests": 2, "input_tokens": 22, "output_tokens": 50},
+            {"key": "customai", "requests": 1, "input_tokens": 25, "output_tokens": 15}
+        ])
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_budgets(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    sqlx::query(
+        "INSERT INTO ai_usage (workspace_id, day, email, provider, model, requests, input_tokens, output_tokens)
+        VALUES ('test-workspace', (now() AT TIME ZONE 'UTC')::date, 'other@windmill.dev', 'openai', 'gpt-4o', 1, 60, 40)",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+    let request = json!({"model": "gpt-4o", "messages": []});
+
+    set_budgets(&db, json!([{"period": "daily", "max_tokens": 100}])).await;
+    let (status, response) = server
+        .proxy("azure_openai", "chat/completions", request.clone())
+        .await;
+    assert_eq!(status, 429, "{response}");
+    assert!(
+        response.contains("daily AI budget of 100 tokens exhausted for workspace test-workspace"),
+        "{response}"
+    );
+
+    // the tokens of the other users do not count against a budget per user
+    set_budgets(
+        &db,
+        json!([
+            {"period": "daily", "max_tokens": 1000},
+            {"period": "monthly", "max_tokens": 100, "per_user": true}
+        ]),
+    )
+    .await;
+    let (status, response) = server
+        .proxy("azure_openai", "chat/completions", request.clone())
+        .await;
+    assert_eq!(status, 200, "{response}");
+    wait_for_usage(&db, 1).await;
+
+    set_budgets(
+        &db,
+        json!([{"period": "monthly", "max_tokens": 30, "per_user": true}]),
+    )
+    .await;
+    let (status, response) = server
+        .proxy("azure_openai", "chat/completions", request)
+        .await;
+    assert_eq!(status, 429, "{response}");
+    assert!(
+        response.contains(
+            "monthly AI budget of 30 tokens exhausted for user test@windmill.dev (30 tokens used)"
+        ),
+        "{response}"
+    );
+
+    server.close().await.unwrap();
+}
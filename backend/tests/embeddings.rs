@@ -0,0 +1,173 @@
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+pub struct ApiServer {
+    pub addr: std::net::SocketAddr,
+    tx: tokio::sync::broadcast::Sender<()>,
+    task: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl ApiServer {
+    pub async fn start(db: Pool<Postgres>) -> Self {
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+
+        let sock = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = sock.local_addr().unwrap();
+        drop(sock);
+        let (port_tx, port_rx) = tokio::sync::oneshot::channel::<String>();
+
+        let task = tokio::task::spawn(windmill_api::run_server(
+            db.clone(),
+            None,
+            None,
+            addr,
+            rx,
+            port_tx,
+            false,
+            false,
+            format!("http://localhost:{}", addr.port()),
+        ));
+
+        port_rx.await.expect("failed to receive port");
+
+        windmill_common::cache::clear();
+
+        Self { addr, tx, task }
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        let Self { tx, task, .. } = self;
+        drop(tx);
+        task.await.unwrap()
+    }
+
+    async fn request(&self, method: reqwest::Method, path: &str, body: Option<Value>) {
+        let request = reqwest::Client::new()
+            .request(
+                method,
+                format!(
+                    "http://localhost:{}/api/w/test-workspace{path}",
+                    self.addr.port()
+                ),
+            )
+            .bearer_auth("SECRET_TOKEN");
+        let request = match body {
+            Some(body) => request.json(&body),
+            None => request,
+        };
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let text = response.text().await.unwrap();
+        assert!(status.is_success(), "{path}: {status} {text}");
+    }
+
+    async fn post(&self, path: &str, body: Value) {
+        self.request(reqwest::Method::POST, path, Some(body)).await
+    }
+
+    async fn delete(&self, path: &str) {
+        self.request(reqwest::Method::DELETE, path, None).await
+    }
+}
+
+/// Queued items with their queue date
+async fn queue(db: &Pool<Postgres>) -> Vec<(String, String, chrono::DateTime<chrono::Utc>)> {
+    sqlx::query_as(
+        "SELECT kind, path, queued_at FROM workspace_embedding_queue
+        WHERE workspace_id = 'test-workspace' ORDER BY path",
+    )
+    .fetch_all(db)
+    .await
+    .unwrap()
+}
+
+async fn queued_paths(db: &Pool<Postgres>) -> Vec<(String, String)> {
+    queue(db)
+        .await
+        .into_iter()
+        .map(|(kind, path, _)| (kind, path))
+        .collect()
+}
+
+fn item(kind: &str, path: &str) -> (String, String) {
+    (kind.to_string(), path.to_string())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_deployed_items_are_queued_for_embedding(db: Pool<Postgres>) {
+    let server = ApiServer::start(db.clone()).await;
+
+    server
+        .post(
+            "/resources/create",
+            json!({
+                "path": "u/test-user/db",
+                "value": {"host": "localhost"},
+                "resource_type": "postgresql",
+                "description": "orders database"
+            }),
+        )
+        .await;
+    // variables are not searchable by embedding
+    server
+        .post(
+            "/variables/create",
+            json!({
+                "path": "u/test-user/db_password",
+                "value": "secret",
+                "is_secret": true,
+                "description": ""
+            }),
+        )
+        .await;
+    assert_eq!(
+        queued_paths(&db).await,
+        [item("resource", "u/test-user/db")]
+    );
+    let first_queued_at = queue(&db).await[0].2;
+
+    // a change queues the item again, it stays queued once
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    server
+        .post(
+            "/resources/update/u/test-user/db",
+            json!({"description": "orders database in production"}),
+        )
+        .await;
+    let queued = queue(&db).await;
+    assert_eq!(queued.len(), 1);
+    assert!(queued[0].2 > first_queued_at);
+
+    // once embedded, a renamed item is queued under both paths so that the embedding of the
+    // previous path is removed
+    sqlx::query("DELETE FROM workspace_embedding_queue")
+        .execute(&db)
+        .await
+        .unwrap();
+    server
+        .post(
+            "/resources/update/u/test-user/db",
+            json!({"path": "u/test-user/orders_db"}),
+        )
+        .await;
+    assert_eq!(
+        queued_paths(&db).await,
+        [
+            item("resource", "u/test-user/db"),
+            item("resource", "u/test-user/orders_db")
+        ]
+    );
+
+    sqlx::query("DELETE FROM workspace_embedding_queue")
+        .execute(&db)
+        .await
+        .unwrap();
+    server.delete("/resources/delete/u/test-user/orders_db").await;
+    assert_eq!(
+        queued_paths(&db).await,
+        [item("resource", "u/test-user/orders_db")]
+    );
+
+    server.close().await.unwrap();
+}
@@ -0,0 +1,121 @@
+use sqlx::{types::Uuid, Pool, Postgres};
+use windmill_common::environments::get_job_environment;
+
+async fn setup(db: &Pool<Postgres>) {
+    sqlx::query(
+        "INSERT INTO environment (workspace_id, name, worker_tags, created_by) VALUES
+            ('test-workspace', 'prod', '{prod}', 'test-user'),
+            ('test-workspace', 'staging', '{}', 'test-user'),
+            ('test-workspace', 'dev', '{}', 'test-user')",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO environment_binding (workspace_id, kind, path, environment) VALUES
+            ('test-workspace', 'schedule', 'f/flows/nightly', 'staging')",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+async fn insert_job(
+    db: &Pool<Postgres>,
+    args: serde_json::Value,
+    tag: &str,
+    schedule: Option<&str>,
+    root_job: Option<Uuid>,
+) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO v2_job (id, workspace_id, args, tag, trigger_kind, trigger, root_job)
+        VALUES ($1, 'test-workspace', $2, $3, CASE WHEN $4::text IS NULL THEN NULL ELSE 'schedule'::job_trigger_kind END, $4, $5)",
+    )
+    .bind(id)
+    .bind(args)
+    .bind(tag)
+    .bind(schedule)
+    .bind(root_job)
+    .execute(db)
+    .await
+    .unwrap();
+    id
+}
+
+async fn environment(db: &Pool<Postgres>, job_id: Uuid) -> Option<String> {
+    get_job_environment(db, "test-workspace", &job_id)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_job_environment_precedence(db: Pool<Postgres>) {
+    setup(&db).await;
+    let arg = |name: &str| serde_json::json!({ "_ENVIRONMENT": name });
+
+    // the arg of the job comes before its schedule and its tag
+    let job = insert_job(&db, arg("dev"), "prod", Some("f/flows/nightly"), None).await;
+    assert_eq!(environment(&db, job).await.as_deref(), Some("dev"));
+
+    // then the schedule, then the tag
+    let job = insert_job(
+        &db,
+        serde_json::json!({}),
+        "prod",
+        Some("f/flows/nightly"),
+        None,
+    )
+    .await;
+    assert_eq!(environment(&db, job).await.as_deref(), Some("staging"));
+
+    let job = insert_job(&db, serde_json::json!({}), "prod", None, None).await;
+    assert_eq!(environment(&db, job).await.as_deref(), Some("prod"));
+
+    let job = insert_job(&db, serde_json::json!({}), "other", None, None).await;
+    assert_eq!(environment(&db, job).await, None);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_job_environment_inherited_from_root_flow(db: Pool<Postgres>) {
+    setup(&db).await;
+    let arg = |name: &str| serde_json::json!({ "_ENVIRONMENT": name });
+
+    let root = insert_job(&db, arg("dev"), "flow", Some("f/flows/nightly"), None).await;
+    let step = insert_job(&db, serde_json::json!({}), "prod", None, Some(root)).await;
+    assert_eq!(environment(&db, step).await.as_deref(), Some("dev"));
+
+    // the schedule of the root flow comes after the arg of the step, but before its tag
+    let root = insert_job(
+        &db,
+        serde_json::json!({}),
+        "flow",
+        Some("f/flows/nightly"),
+        None,
+    )
+    .await;
+    let step = insert_job(&db, arg("prod"), "other", None, Some(root)).await;
+    assert_eq!(environment(&db, step).await.as_deref(), Some("prod"));
+    let step = insert_job(&db, serde_json::json!({}), "prod", None, Some(root)).await;
+    assert_eq!(environment(&db, step).await.as_deref(), Some("staging"));
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_job_environment_skips_unknown_names(db: Pool<Postgres>) {
+    setup(&db).await;
+    let arg = |name: &str| serde_json::json!({ "_ENVIRONMENT": name });
+
+    // an unknown name falls back to the next source instead of to no environment at all
+    let job = insert_job(&db, arg("unknown"), "prod", Some("f/flows/nightly"), None).await;
+    assert_eq!(environment(&db, job).await.as_deref(), Some("staging"));
+
+    let job = insert_job(&db, arg("unknown"), "prod", None, None).await;
+    assert_eq!(environment(&db, job).await.as_deref(), Some("prod"));
+
+    let root = insert_job(&db, arg("unknown"), "flow", None, None).await;
+    let step = insert_job(&db, arg("unknown"), "prod", None, Some(root)).await;
+    assert_eq!(environment(&db, step).await.as_deref(), Some("prod"));
+
+    let job = insert_job(&db, arg("unknown"), "other", None, None).await;
+    assert_eq!(environment(&db, job).await, None);
+}
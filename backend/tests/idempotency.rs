@@ -0,0 +1,89 @@
+use sqlx::{types::Uuid, Pool, Postgres};
+use windmill_queue::{
+    idempotency::{begin_idempotent_push, commit_idempotent_push, IdempotencyKey, IdempotentPush},
+    PushIsolationLevel,
+};
+
+const EMAIL: &str = "test@windmill.dev";
+
+fn key(is_flow: bool, path: &str, key: &str) -> IdempotencyKey {
+    IdempotencyKey::new(
+        "test-workspace",
+        IdempotencyKey::user_scope(EMAIL, is_flow, path),
+        key,
+    )
+    .unwrap()
+    .unwrap()
+}
+
+/// Goes through the idempotent push of a job with the key, returns the id of the job and whether
+/// it was pushed or the one already bound to the key
+async fn push(db: &Pool<Postgres>, key: &IdempotencyKey) -> (Uuid, bool) {
+    let tx = PushIsolationLevel::IsolatedRoot(db.clone());
+    match begin_idempotent_push(tx, Some(key)).await.unwrap() {
+        IdempotentPush::Existing(job_id) => (job_id, false),
+        IdempotentPush::Push(PushIsolationLevel::Transaction(tx)) => {
+            let job_id = Uuid::new_v4();
+            commit_idempotent_push(tx, Some(key), job_id).await.unwrap();
+            (job_id, true)
+        }
+        IdempotentPush::Push(_) => panic!("a claimed key must be pushed in its transaction"),
+    }
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_idempotency_key_replays_same_runnable(db: Pool<Postgres>) {
+    let (job_id, pushed) = push(&db, &key(false, "u/test-user/a", "order-1")).await;
+    assert!(pushed);
+
+    let (replayed_id, pushed) = push(&db, &key(false, "u/test-user/a", "order-1")).await;
+    assert!(!pushed);
+    assert_eq!(replayed_id, job_id);
+
+    let (other_id, pushed) = push(&db, &key(false, "u/test-user/a", "order-2")).await;
+    assert!(pushed);
+    assert_ne!(other_id, job_id);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_idempotency_key_reused_for_other_runnable(db: Pool<Postgres>) {
+    let (job_id, _) = push(&db, &key(false, "u/test-user/a", "order-1")).await;
+
+    // the same key for another script, or for a flow at the same path, pushes a new job
+    let (script_id, pushed) = push(&db, &key(false, "u/test-user/b", "order-1")).await;
+    assert!(pushed);
+    assert_ne!(script_id, job_id);
+
+    let (flow_id, pushed) = push(&db, &key(true, "u/test-user/a", "order-1")).await;
+    assert!(pushed);
+    assert_ne!(flow_id, job_id);
+    assert_ne!(flow_id, script_id);
+
+    // and each of them still replays its own job
+    assert_eq!(
+        push(&db, &key(false, "u/test-user/a", "order-1")).await,
+        (job_id, false)
+    );
+    assert_eq!(
+        push(&db, &key(false, "u/test-user/b", "order-1")).await,
+        (script_id, false)
+    );
+    assert_eq!(
+        push(&db, &key(true, "u/test-user/a", "order-1")).await,
+        (flow_id, false)
+    );
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_idempotency_key_expires_with_window(db: Pool<Postgres>) {
+    let (job_id, _) = push(&db, &key(true, "f/flows/a", "order-1")).await;
+
+    sqlx::query("UPDATE job_idempotency_key SET expires_at = now() - interval '1 second'")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let (new_id, pushed) = push(&db, &key(true, "f/flows/a", "order-1")).await;
+    assert!(pushed);
+    assert_ne!(new_id, job_id);
+}
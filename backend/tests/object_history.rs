@@ -0,0 +1,375 @@
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+pub struct ApiServer {
+    pub addr: std::net::SocketAddr,
+    tx: tokio::sync::broadcast::Sender<()>,
+    task: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl ApiServer {
+    pub async fn start(db: Pool<Postgres>) -> Self {
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+
+        let sock = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = sock.local_addr().unwrap();
+        drop(sock);
+        let (port_tx, port_rx) = tokio::sync::oneshot::channel::<String>();
+
+        let task = tokio::task::spawn(windmill_api::run_server(
+            db.clone(),
+            None,
+            None,
+            addr,
+            rx,
+            port_tx,
+            false,
+            false,
+            format!("http://localhost:{}", addr.port()),
+        ));
+
+        port_rx.await.expect("failed to receive port");
+
+        windmill_common::cache::clear();
+
+        Self { addr, tx, task }
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        let Self { tx, task, .. } = self;
+        drop(tx);
+        task.await.unwrap()
+    }
+
+    async fn request(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> String {
+        let request = reqwest::Client::new()
+            .request(
+                method,
+                format!(
+                    "http://localhost:{}/api/w/test-workspace{path}",
+                    self.addr.port()
+                ),
+            )
+            .bearer_auth("SECRET_TOKEN");
+        let request = match body {
+            Some(body) => request.json(&body),
+            None => request,
+        };
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let text = response.text().await.unwrap();
+        assert!(status.is_success(), "{path}: {status} {text}");
+        text
+    }
+
+    async fn post(&self, path: &str, body: Value) -> String {
+        self.request(reqwest::Method::POST, path, Some(body)).await
+    }
+
+    async fn get(&self, path: &str) -> Value {
+        serde_json::from_str(&self.request(reqwest::Method::GET, path, None).await).unwrap()
+    }
+
+    async fn delete(&self, path: &str) {
+        self.request(reqwest::Method::DELETE, path, None).await;
+    }
+}
+
+async fn setup(db: &Pool<Postgres>) {
+    sqlx::query(
+        "INSERT INTO password (email, password_hash, login_type, super_admin, name, username)
+        VALUES ('test@windmill.dev', 'hash', 'password', true, 'Test', 'test-user')",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+/// Time of the database, to restore to the state in between two changes
+async fn now(db: &Pool<Postgres>) -> Value {
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    let now: chrono::DateTime<chrono::Utc> = sqlx::query_scalar("SELECT now()")
+        .fetch_one(db)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    json!(now)
+}
+
+async fn variable(db: &Pool<Postgres>, path: &str) -> Option<(String, String)> {
+    sqlx::query_as(
+        "SELECT value, description FROM variable WHERE workspace_id = 'test-workspace' AND path = $1",
+    )
+    .bind(path)
+    .fetch_optional(db)
+    .await
+    .unwrap()
+}
+
+fn actions(history: &Value) -> Vec<&str> {
+    history
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_record_history(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+
+    server
+        .post(
+            "/variables/create",
+            json!({"path": "u/test-user/db_host", "value": "dev.local", "is_secret": false, "description": "host"}),
+        )
+        .await;
+    server
+        .post(
+            "/variables/update/u/test-user/db_host",
+            json!({"value": "prod.local"}),
+        )
+        .await;
+    // an update that changes nothing is not recorded
+    server
+        .post(
+            "/variables/update/u/test-user/db_host",
+            json!({"value": "prod.local"}),
+        )
+        .await;
+    server.delete("/variables/delete/u/test-user/db_host").await;
+
+    let history = server
+        .get("/object_history/list/variable/u/test-user/db_host")
+        .await;
+    assert_eq!(actions(&history), vec!["delete", "update", "create"]);
+    assert!(history
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|entry| entry["edited_by"] == "test-user"));
+
+    let (delete_id, update_id, create_id) = (
+        history[0]["id"].as_i64().unwrap(),
+        history[1]["id"].as_i64().unwrap(),
+        history[2]["id"].as_i64().unwrap(),
+    );
+    let update = server
+        .get(&format!("/object_history/get/{update_id}"))
+        .await;
+    assert_eq!(update["value"]["value"], "prod.local");
+    assert_eq!(update["previous_value"]["value"], "dev.local");
+    let delete = server
+        .get(&format!("/object_history/get/{delete_id}"))
+        .await;
+    assert_eq!(delete["value"], Value::Null);
+    assert_eq!(delete["previous_value"]["value"], "prod.local");
+
+    let diff = server
+        .get(&format!("/object_history/diff/{create_id}/{update_id}"))
+        .await;
+    assert_eq!(
+        diff["changes"],
+        json!([{"pointer": "/value", "from": "dev.local", "to": "prod.local"}])
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_restore_object(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+
+    let before_creation = now(&db).await;
+    server
+        .post(
+            "/variables/create",
+            json!({"path": "u/test-user/db_host", "value": "dev.local", "is_secret": false, "description": "host"}),
+        )
+        .await;
+    let created = now(&db).await;
+    server
+        .post(
+            "/variables/update/u/test-user/db_host",
+            json!({"value": "oops", "description": "edited by mistake"}),
+        )
+        .await;
+
+    let restore = |at: &Value, dry_run: bool| json!({"kind": "variable", "path": "u/test-user/db_host", "at": at, "dry_run": dry_run});
+
+    let report: Value = serde_json::from_str(
+        &server
+            .post("/object_history/restore", restore(&created, true))
+            .await,
+    )
+    .unwrap();
+    assert_eq!(report["items"][0]["action"], "restore");
+    assert_eq!(
+        variable(&db, "u/test-user/db_host").await,
+        Some(("oops".to_string(), "edited by mistake".to_string()))
+    );
+
+    server
+        .post("/object_history/restore", restore(&created, false))
+        .await;
+    assert_eq!(
+        variable(&db, "u/test-user/db_host").await,
+        Some(("dev.local".to_string(), "host".to_string()))
+    );
+    // the restore is recorded like any other change
+    let history = server
+        .get("/object_history/list/variable/u/test-user/db_host")
+        .await;
+    assert_eq!(actions(&history), vec!["update", "update", "create"]);
+
+    let report: Value = serde_json::from_str(
+        &server
+            .post("/object_history/restore", restore(&created, false))
+            .await,
+    )
+    .unwrap();
+    assert_eq!(report["items"][0]["action"], "unchanged");
+
+    // before it was created, the variable did not exist
+    let report: Value = serde_json::from_str(
+        &server
+            .post("/object_history/restore", restore(&before_creation, false))
+            .await,
+    )
+    .unwrap();
+    assert_eq!(report["items"][0]["action"], "delete");
+    assert_eq!(variable(&db, "u/test-user/db_host").await, None);
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_restore_folder(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+
+    server
+        .post("/folders/create", json!({"name": "prod"}))
+        .await;
+    server
+        .post(
+            "/variables/create",
+            json!({"path": "f/prod/db_host", "value": "prod.local", "is_secret": false, "description": ""}),
+        )
+        .await;
+    let at = now(&db).await;
+    server
+        .post("/variables/update/f/prod/db_host", json!({"value": "oops"}))
+        .await;
+    server
+        .post(
+            "/variables/create",
+            json!({"path": "f/prod/created_since", "value": "x", "is_secret": false, "description": ""}),
+        )
+        .await;
+    server
+        .post(
+            "/variables/create",
+            json!({"path": "f/other/untouched", "value": "y", "is_secret": false, "description": ""}),
+        )
+        .await;
+
+    let report: Value = serde_json::from_str(
+        &server
+            .post(
+                "/object_history/restore_folder",
+                json!({"folder": "prod", "at": at}),
+            )
+            .await,
+    )
+    .unwrap();
+    let items = report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            (
+                item["kind"].as_str().unwrap(),
+                item["path"].as_str().unwrap(),
+                item["action"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        items,
+        vec![
+            ("folder", "prod", "unchanged"),
+            ("variable", "f/prod/created_since", "delete"),
+            ("variable", "f/prod/db_host", "restore"),
+        ]
+    );
+    assert_eq!(
+        variable(&db, "f/prod/db_host").await.map(|v| v.0),
+        Some("prod.local".to_string())
+    );
+    assert_eq!(variable(&db, "f/prod/created_since").await, None);
+    assert_eq!(
+        variable(&db, "f/other/untouched").await.map(|v| v.0),
+        Some("y".to_string())
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_restore_schedule(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+
+    server
+        .post(
+            "/schedules/create",
+            json!({
+                "path": "u/test-user/nightly",
+                "schedule": "0 0 2 * * *",
+                "timezone": "UTC",
+                "script_path": "u/test-user/etl",
+                "is_flow": false,
+                "args": {"full": false},
+                "enabled": false
+            }),
+        )
+        .await;
+    let at = now(&db).await;
+    server
+        .post(
+            "/schedules/update/u/test-user/nightly",
+            json!({"schedule": "0 */5 * * * *", "timezone": "UTC", "args": {"full": true}}),
+        )
+        .await;
+
+    let history = server
+        .get("/object_history/list/schedule/u/test-user/nightly")
+        .await;
+    assert_eq!(actions(&history), vec!["update", "create"]);
+
+    let report: Value = serde_json::from_str(
+        &server
+            .post(
+                "/object_history/restore",
+                json!({"kind": "schedule", "path": "u/test-user/nightly", "at": at}),
+            )
+            .await,
+    )
+    .unwrap();
+    assert_eq!(report["items"][0]["action"], "restore");
+    let (schedule, args): (String, Value) = sqlx::query_as(
+        "SELECT schedule, args FROM schedule WHERE workspace_id = 'test-workspace' AND path = 'u/test-user/nightly'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(schedule, "0 0 2 * * *");
+    assert_eq!(args, json!({"full": false}));
+
+    server.close().await.unwrap();
+}
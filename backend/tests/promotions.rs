@@ -0,0 +1,313 @@
+use serde_json::{json, Value};
+use sqlx::{Pool, Postgres};
+
+pub struct ApiServer {
+    pub addr: std::net::SocketAddr,
+    tx: tokio::sync::broadcast::Sender<()>,
+    task: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl ApiServer {
+    pub async fn start(db: Pool<Postgres>) -> Self {
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+
+        let sock = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = sock.local_addr().unwrap();
+        drop(sock);
+        let (port_tx, port_rx) = tokio::sync::oneshot::channel::<String>();
+
+        let task = tokio::task::spawn(windmill_api::run_server(
+            db.clone(),
+            None,
+            None,
+            addr,
+            rx,
+            port_tx,
+            false,
+            false,
+            format!("http://localhost:{}", addr.port()),
+        ));
+
+        port_rx.await.expect("failed to receive port");
+
+        windmill_common::cache::clear();
+
+        Self { addr, tx, task }
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        let Self { tx, task, .. } = self;
+        drop(tx);
+        task.await.unwrap()
+    }
+
+    async fn post(&self, path: &str, body: Option<Value>) -> Result<String, String> {
+        let request = reqwest::Client::new()
+            .post(format!("http://localhost:{}/api{path}", self.addr.port()))
+            .bearer_auth("SECRET_TOKEN");
+        let request = match body {
+            Some(body) => request.json(&body),
+            None => request,
+        };
+        let response = request.send().await.unwrap();
+        let success = response.status().is_success();
+        let text = response.text().await.unwrap();
+        if success {
+            Ok(text)
+        } else {
+            Err(text)
+        }
+    }
+
+    /// Promotion of the variables and the resource of test-workspace to target-workspace,
+    /// approved by the test user who is an admin of target-workspace
+    async fn approved_promotion(&self) -> i64 {
+        let id = self
+            .post(
+                "/w/test-workspace/promotions/create",
+                Some(json!({
+                    "target_workspace_id": "target-workspace",
+                    "items": [
+                        {"kind": "variable", "path": "f/shared/plain"},
+                        {"kind": "variable", "path": "f/shared/same"},
+                        {"kind": "resource", "path": "f/shared/db"}
+                    ],
+                    "remappings": [{"from": "f/shared/db", "to": "f/shared/prod_db"}]
+                })),
+            )
+            .await
+            .unwrap()
+            .parse()
+            .unwrap();
+        self.post(
+            &format!("/w/target-workspace/promotions/approve/{id}"),
+            None,
+        )
+        .await
+        .unwrap();
+        id
+    }
+}
+
+/// Variables and a resource in test-workspace, and a target-workspace where one variable has
+/// another value and the other one is the same
+async fn setup(db: &Pool<Postgres>) {
+    sqlx::raw_sql(
+        "INSERT INTO password (email, password_hash, login_type, super_admin, name, username)
+            VALUES ('test@windmill.dev', 'hash', 'password', true, 'Test', 'test-user');
+        INSERT INTO workspace (id, name, owner) VALUES ('target-workspace', 'target-workspace', 'test-user');
+        INSERT INTO workspace_settings (workspace_id) VALUES ('target-workspace');
+        INSERT INTO workspace_key (workspace_id, kind, key) VALUES ('target-workspace', 'cloud', 'target-key');
+        INSERT INTO usr (workspace_id, email, username, is_admin) VALUES
+            ('target-workspace', 'test@windmill.dev', 'test-user', true);
+        INSERT INTO group_ (workspace_id, name, summary) VALUES ('target-workspace', 'all', '');
+        INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, created_by, edited_at) VALUES
+            ('test-workspace', 'shared', 'Shared', ARRAY[]::TEXT[], '{}', 'test-user', now()),
+            ('target-workspace', 'shared', 'Shared', ARRAY[]::TEXT[], '{}', 'test-user', now());
+        INSERT INTO variable (workspace_id, path, value, is_secret, description) VALUES
+            ('test-workspace', 'f/shared/plain', 'hello', false, 'plain variable'),
+            ('test-workspace', 'f/shared/same', 'same', false, 'same variable'),
+            ('target-workspace', 'f/shared/plain', 'bye', false, 'plain variable'),
+            ('target-workspace', 'f/shared/same', 'same', false, 'same variable');
+        INSERT INTO resource (workspace_id, path, value, description, resource_type, created_by) VALUES
+            ('test-workspace', 'f/shared/db', '{\"password\": \"$var:f/shared/plain\"}', 'database', 'postgresql', 'test-user');",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+async fn variable(db: &Pool<Postgres>, path: &str) -> Option<String> {
+    sqlx::query_scalar(
+        "SELECT value FROM variable WHERE workspace_id = 'target-workspace' AND path = $1",
+    )
+    .bind(path)
+    .fetch_optional(db)
+    .await
+    .unwrap()
+}
+
+async fn resource_exists(db: &Pool<Postgres>, path: &str) -> bool {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM resource WHERE workspace_id = 'target-workspace' AND path = $1)",
+    )
+    .bind(path)
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
+
+async fn status(db: &Pool<Postgres>, id: i64) -> String {
+    sqlx::query_scalar("SELECT status::TEXT FROM promotion WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+async fn diff(db: &Pool<Postgres>, id: i64) -> Value {
+    sqlx::query_scalar("SELECT diff FROM promotion WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_promotion_diff_and_apply(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+
+    let id = server.approved_promotion().await;
+    assert_eq!(
+        diff(&db, id).await,
+        json!([
+            {
+                "kind": "variable",
+                "path": "f/shared/plain",
+                "target_path": "f/shared/plain",
+                "status": "modified",
+                "changes": ["/variable.json/value"]
+            },
+            {
+                "kind": "variable",
+                "path": "f/shared/same",
+                "target_path": "f/shared/same",
+                "status": "unchanged"
+            },
+            {
+                "kind": "resource",
+                "path": "f/shared/db",
+                "target_path": "f/shared/prod_db",
+                "status": "added"
+            }
+        ])
+    );
+    assert_eq!(status(&db, id).await, "approved");
+
+    server
+        .post(&format!("/w/target-workspace/promotions/apply/{id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(status(&db, id).await, "applied");
+    assert_eq!(
+        variable(&db, "f/shared/plain").await,
+        Some("hello".to_string())
+    );
+    assert!(resource_exists(&db, "f/shared/prod_db").await);
+    assert!(!resource_exists(&db, "f/shared/db").await);
+
+    // an applied promotion cannot be applied again
+    assert!(server
+        .post(&format!("/w/target-workspace/promotions/apply/{id}"), None)
+        .await
+        .is_err());
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_failed_apply_is_rolled_back(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    let id = server.approved_promotion().await;
+
+    // resources are imported after variables, so the variable is overwritten before the failure
+    sqlx::raw_sql(
+        "CREATE FUNCTION fail_resource_insert() RETURNS TRIGGER AS $$
+        BEGIN
+            RAISE EXCEPTION 'resource insert failed';
+        END;
+        $$ LANGUAGE plpgsql;
+        CREATE TRIGGER fail_resource_insert BEFORE INSERT ON resource
+            FOR EACH ROW WHEN (NEW.workspace_id = 'target-workspace')
+            EXECUTE FUNCTION fail_resource_insert();",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let error = server
+        .post(&format!("/w/target-workspace/promotions/apply/{id}"), None)
+        .await
+        .unwrap_err();
+    assert!(error.contains("resource insert failed"), "{error}");
+    assert_eq!(status(&db, id).await, "approved");
+    assert_eq!(
+        variable(&db, "f/shared/plain").await,
+        Some("bye".to_string())
+    );
+    assert!(!resource_exists(&db, "f/shared/prod_db").await);
+
+    // the promotion can still be applied once the cause of the failure is gone
+    sqlx::query("DROP TRIGGER fail_resource_insert ON resource")
+        .execute(&db)
+        .await
+        .unwrap();
+    server
+        .post(&format!("/w/target-workspace/promotions/apply/{id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(status(&db, id).await, "applied");
+    assert_eq!(
+        variable(&db, "f/shared/plain").await,
+        Some("hello".to_string())
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_changed_diff_resets_to_pending(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    let id = server.approved_promotion().await;
+
+    sqlx::query(
+        "UPDATE variable SET value = 'changed' WHERE workspace_id = 'test-workspace' AND path = 'f/shared/same'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let error = server
+        .post(&format!("/w/target-workspace/promotions/apply/{id}"), None)
+        .await
+        .unwrap_err();
+    assert!(error.contains("needs to be reviewed again"), "{error}");
+    assert_eq!(status(&db, id).await, "pending");
+    assert_eq!(
+        diff(&db, id).await[1],
+        json!({
+            "kind": "variable",
+            "path": "f/shared/same",
+            "target_path": "f/shared/same",
+            "status": "modified",
+            "changes": ["/variable.json/value"]
+        })
+    );
+    assert_eq!(
+        variable(&db, "f/shared/same").await,
+        Some("same".to_string())
+    );
+
+    // once approved again, the new diff is applied
+    server
+        .post(
+            &format!("/w/target-workspace/promotions/approve/{id}"),
+            None,
+        )
+        .await
+        .unwrap();
+    server
+        .post(&format!("/w/target-workspace/promotions/apply/{id}"), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        variable(&db, "f/shared/same").await,
+        Some("changed".to_string())
+    );
+
+    server.close().await.unwrap();
+}
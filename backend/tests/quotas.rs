@@ -0,0 +1,116 @@
+use sqlx::{types::Uuid, Pool, Postgres};
+use windmill_common::{quotas::invalidate_workspace_quotas, worker::make_pull_query};
+use windmill_queue::{
+    quotas::is_over_concurrent_jobs_quota, requeue_if_over_concurrent_jobs_quota, PulledJob,
+};
+
+async fn set_concurrent_jobs_quota(db: &Pool<Postgres>, hard: i64) {
+    sqlx::query(
+        "INSERT INTO workspace_quota (workspace_id, quotas)
+        VALUES ('test-workspace', jsonb_build_object('concurrent_jobs', jsonb_build_object('hard', $1::bigint)))",
+    )
+    .bind(hard)
+    .execute(db)
+    .await
+    .unwrap();
+    invalidate_workspace_quotas("test-workspace");
+}
+
+async fn queue_job(db: &Pool<Postgres>, kind: &str) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO v2_job (id, workspace_id, kind, tag) VALUES ($1, 'test-workspace', $2::job_kind, 'quota')",
+    )
+    .bind(id)
+    .bind(kind)
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO v2_job_queue (id, workspace_id, scheduled_for, tag) VALUES ($1, 'test-workspace', now(), 'quota')",
+    )
+    .bind(id)
+    .execute(db)
+    .await
+    .unwrap();
+    id
+}
+
+async fn pull(db: &Pool<Postgres>) -> Option<PulledJob> {
+    sqlx::query_as::<_, PulledJob>(&make_pull_query(&["quota".to_string()]))
+        .bind("test-worker")
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn running_jobs(db: &Pool<Postgres>) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM v2_job_queue WHERE running = true")
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_pull_skips_workspaces_over_concurrent_jobs_quota(db: Pool<Postgres>) {
+    set_concurrent_jobs_quota(&db, 1).await;
+    queue_job(&db, "script").await;
+    queue_job(&db, "script").await;
+
+    let job = pull(&db).await.expect("the first job is under the quota");
+    // the count includes the pulled job, which is already marked as running
+    assert!(!is_over_concurrent_jobs_quota(&db, "test-workspace")
+        .await
+        .unwrap());
+    assert!(!requeue_if_over_concurrent_jobs_quota(&db, &job)
+        .await
+        .unwrap());
+
+    assert!(pull(&db).await.is_none());
+    assert_eq!(running_jobs(&db).await, 1);
+
+    // flows do not count and are still pulled, their steps are counted instead
+    let flow_id = queue_job(&db, "flow").await;
+    assert_eq!(pull(&db).await.map(|job| job.id), Some(flow_id));
+
+    sqlx::query("DELETE FROM v2_job_queue WHERE id = $1")
+        .bind(job.id)
+        .execute(&db)
+        .await
+        .unwrap();
+    assert!(pull(&db).await.is_some());
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_jobs_pulled_past_concurrent_jobs_quota_are_requeued(db: Pool<Postgres>) {
+    queue_job(&db, "script").await;
+    queue_job(&db, "script").await;
+
+    // two workers pulling at the same time, before the quota is reached
+    let first = pull(&db).await.unwrap();
+    let second = pull(&db).await.unwrap();
+    set_concurrent_jobs_quota(&db, 1).await;
+
+    assert!(is_over_concurrent_jobs_quota(&db, "test-workspace")
+        .await
+        .unwrap());
+    assert!(requeue_if_over_concurrent_jobs_quota(&db, &second)
+        .await
+        .unwrap());
+
+    let (running, scheduled_later) = sqlx::query_as::<_, (bool, bool)>(
+        "SELECT running, scheduled_for > now() FROM v2_job_queue WHERE id = $1",
+    )
+    .bind(second.id)
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert!(!running);
+    assert!(scheduled_later);
+
+    // back under the quota, the job left running is not requeued
+    assert!(!requeue_if_over_concurrent_jobs_quota(&db, &first)
+        .await
+        .unwrap());
+    assert_eq!(running_jobs(&db).await, 1);
+}
@@ -0,0 +1,396 @@
+use serde_json::{json, Value};
+use sqlx::{types::Uuid, Pool, Postgres};
+use windmill_common::quotas::invalidate_workspace_quotas;
+
+pub struct ApiServer {
+    pub addr: std::net::SocketAddr,
+    tx: tokio::sync::broadcast::Sender<()>,
+    task: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl ApiServer {
+    /// In server mode, the dead letter sweeper runs along with the server
+    pub async fn start(db: Pool<Postgres>, server_mode: bool) -> Self {
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+
+        let sock = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = sock.local_addr().unwrap();
+        drop(sock);
+        let (port_tx, port_rx) = tokio::sync::oneshot::channel::<String>();
+
+        let task = tokio::task::spawn(windmill_api::run_server(
+            db.clone(),
+            None,
+            None,
+            addr,
+            rx,
+            port_tx,
+            server_mode,
+            false,
+            format!("http://localhost:{}", addr.port()),
+        ));
+
+        port_rx.await.expect("failed to receive port");
+
+        windmill_common::cache::clear();
+
+        Self { addr, tx, task }
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        let Self { tx, task, .. } = self;
+        drop(tx);
+        task.await.unwrap()
+    }
+
+    async fn get(&self, token: &str, path: &str) -> Result<Value, String> {
+        let response = reqwest::Client::new()
+            .get(format!("http://localhost:{}/api{path}", self.addr.port()))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap();
+        let success = response.status().is_success();
+        let text = response.text().await.unwrap();
+        if success {
+            Ok(serde_json::from_str(&text).unwrap())
+        } else {
+            Err(text)
+        }
+    }
+
+    async fn post(&self, token: &str, path: &str, body: Value) -> Result<String, String> {
+        let response = reqwest::Client::new()
+            .post(format!("http://localhost:{}/api{path}", self.addr.port()))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        let success = response.status().is_success();
+        let text = response.text().await.unwrap();
+        if success {
+            Ok(text)
+        } else {
+            Err(text)
+        }
+    }
+
+    async fn list(&self, token: &str) -> Vec<Value> {
+        serde_json::from_value(
+            self.get(token, "/w/test-workspace/trigger_dead_letters/list")
+                .await
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    async fn replay(&self, token: &str, body: Value) -> Result<Vec<Value>, String> {
+        self.post(token, "/w/test-workspace/trigger_dead_letters/replay", body)
+            .await
+            .map(|text| serde_json::from_str(&text).unwrap())
+    }
+}
+
+/// The orders folder, readable by dev and not by outsider, with scripts and an asynchronous
+/// http route authenticated by windmill running f/orders/process
+async fn setup(db: &Pool<Postgres>) {
+    sqlx::raw_sql(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, role) VALUES
+            ('test-workspace', 'dev@windmill.dev', 'dev', false, 'Developer'),
+            ('test-workspace', 'outsider@windmill.dev', 'outsider', false, 'Developer');
+        INSERT INTO token (token, email, label, super_admin) VALUES
+            ('DEV_USER_TOKEN', 'dev@windmill.dev', 'dev token', false),
+            ('OUTSIDER_USER_TOKEN', 'outsider@windmill.dev', 'outsider token', false);
+        INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, created_by, edited_at) VALUES
+            ('test-workspace', 'orders', 'Orders', ARRAY[]::TEXT[], '{\"u/dev\": false}', 'test-user', now());
+        INSERT INTO script (workspace_id, created_by, content, schema, summary, description, path, hash, language, lock) VALUES
+            ('test-workspace', 'test-user', 'export function main(order: number) { return order }', NULL, '', '', 'f/orders/process', 1001, 'deno', ''),
+            ('test-workspace', 'test-user', 'export function main(order: number) { return order }', NULL, '', '', 'f/orders/other', 1002, 'deno', ''),
+            ('test-workspace', 'test-user', 'export function main(order: number) { return order }', NULL, '', '', 'u/test-user/private', 1003, 'deno', '');
+        INSERT INTO http_trigger (path, route_path, route_path_key, script_path, is_flow, workspace_id, edited_by, email, is_async, authentication_method, http_method) VALUES
+            ('f/orders/hook', 'orders', 'orders', 'f/orders/process', false, 'test-workspace', 'test-user', 'test@windmill.dev', true, 'windmill', 'post');",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+/// A hard limit of 0 job seconds per day rejects every job pushed in the workspace
+async fn set_job_quota(db: &Pool<Postgres>, exceeded: bool) {
+    if exceeded {
+        sqlx::query(
+            "INSERT INTO workspace_quota (workspace_id, quotas)
+            VALUES ('test-workspace', '{\"job_seconds_per_day\": {\"hard\": 0}}')",
+        )
+        .execute(db)
+        .await
+        .unwrap();
+    } else {
+        sqlx::query("DELETE FROM workspace_quota WHERE workspace_id = 'test-workspace'")
+            .execute(db)
+            .await
+            .unwrap();
+    }
+    invalidate_workspace_quotas("test-workspace");
+}
+
+async fn insert_dead_letter(db: &Pool<Postgres>, trigger_path: &str) -> i64 {
+    sqlx::query_scalar(
+        "INSERT INTO trigger_dead_letter
+            (workspace_id, trigger_kind, trigger_path, runnable_path, is_flow, args, failure_kind, error)
+        VALUES ('test-workspace', 'mqtt', $1, 'f/orders/process', false, '{\"order\": 1}', 'push', 'failed')
+        RETURNING id",
+    )
+    .bind(trigger_path)
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
+
+async fn job(db: &Pool<Postgres>, id: Uuid) -> (String, String, String, Value) {
+    sqlx::query_as(
+        "SELECT runnable_path, trigger, trigger_kind::TEXT, args FROM v2_job WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(db)
+    .await
+    .unwrap()
+}
+
+fn replayed_job_id(result: &[Value], id: i64) -> Uuid {
+    assert_eq!(result.len(), 1, "{result:?}");
+    assert_eq!(result[0]["id"], id);
+    assert!(result[0].get("error").is_none(), "{result:?}");
+    result[0]["job_id"].as_str().unwrap().parse().unwrap()
+}
+
+#[cfg(feature = "http_trigger")]
+#[sqlx::test(fixtures("base"))]
+async fn test_push_failure_is_replayed(db: Pool<Postgres>) {
+    setup(&db).await;
+    set_job_quota(&db, true).await;
+    let server = ApiServer::start(db.clone(), false).await;
+
+    let error = server
+        .post("SECRET_TOKEN", "/r/orders", json!({"order": 1}))
+        .await
+        .unwrap_err();
+    assert!(error.contains("quota"), "{error}");
+
+    let dead_letters = server.list("SECRET_TOKEN").await;
+    assert_eq!(dead_letters.len(), 1);
+    let dead_letter = &dead_letters[0];
+    assert_eq!(dead_letter["trigger_kind"], "http");
+    assert_eq!(dead_letter["trigger_path"], "f/orders/hook");
+    assert_eq!(dead_letter["runnable_path"], "f/orders/process");
+    assert_eq!(dead_letter["is_flow"], false);
+    assert_eq!(dead_letter["failure_kind"], "push");
+    assert_eq!(dead_letter["args"]["order"], 1);
+    assert!(dead_letter["error"].as_str().unwrap().contains("quota"));
+    let id = dead_letter["id"].as_i64().unwrap();
+
+    // still rejected, the dead letter stays unreplayed
+    let result = server
+        .replay("SECRET_TOKEN", json!({"ids": [id]}))
+        .await
+        .unwrap();
+    assert!(result[0]["error"].as_str().unwrap().contains("quota"));
+    assert_eq!(
+        server
+            .get(
+                "SECRET_TOKEN",
+                &format!("/w/test-workspace/trigger_dead_letters/get/{id}")
+            )
+            .await
+            .unwrap()["replayed_at"],
+        Value::Null
+    );
+
+    set_job_quota(&db, false).await;
+    let result = server
+        .replay("SECRET_TOKEN", json!({"ids": [id]}))
+        .await
+        .unwrap();
+    let job_id = replayed_job_id(&result, id);
+    let (runnable_path, trigger, trigger_kind, args) = job(&db, job_id).await;
+    assert_eq!(runnable_path, "f/orders/process");
+    assert_eq!(trigger, "f/orders/hook");
+    assert_eq!(trigger_kind, "http");
+    assert_eq!(args["order"], 1);
+
+    let dead_letter = server
+        .get(
+            "SECRET_TOKEN",
+            &format!("/w/test-workspace/trigger_dead_letters/get/{id}"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(dead_letter["replayed_by"], "test-user");
+    assert_eq!(dead_letter["replayed_job_id"], job_id.to_string());
+
+    // edited args replayed into another runnable
+    assert!(server
+        .post(
+            "SECRET_TOKEN",
+            &format!("/w/test-workspace/trigger_dead_letters/update/{id}"),
+            json!({"args": [2]}),
+        )
+        .await
+        .is_err());
+    server
+        .post(
+            "SECRET_TOKEN",
+            &format!("/w/test-workspace/trigger_dead_letters/update/{id}"),
+            json!({"args": {"order": 2}}),
+        )
+        .await
+        .unwrap();
+    assert!(server
+        .replay(
+            "SECRET_TOKEN",
+            json!({"ids": [id], "runnable_path": "f/orders/other"})
+        )
+        .await
+        .is_err());
+    let result = server
+        .replay(
+            "SECRET_TOKEN",
+            json!({"ids": [id], "runnable_path": "f/orders/other", "is_flow": false}),
+        )
+        .await
+        .unwrap();
+    let job_id = replayed_job_id(&result, id);
+    let (runnable_path, trigger, _, args) = job(&db, job_id).await;
+    assert_eq!(runnable_path, "f/orders/other");
+    assert_eq!(trigger, "f/orders/hook");
+    assert_eq!(args, json!({"order": 2}));
+
+    assert!(server
+        .replay("SECRET_TOKEN", json!({"ids": []}))
+        .await
+        .is_err());
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_replay_permissions(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone(), false).await;
+    let id = insert_dead_letter(&db, "f/orders/mq").await;
+    let own_id = insert_dead_letter(&db, "u/outsider/mq").await;
+
+    // outsider only sees the dead letters of their own triggers
+    let dead_letters = server.list("OUTSIDER_USER_TOKEN").await;
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0]["id"], own_id);
+    let error = server
+        .replay("OUTSIDER_USER_TOKEN", json!({"ids": [own_id, id]}))
+        .await
+        .unwrap_err();
+    assert!(
+        error.contains(&format!("Dead letter {id} not found")),
+        "{error}"
+    );
+
+    // dev can read the orders folder but not write to it
+    assert_eq!(server.list("DEV_USER_TOKEN").await.len(), 1);
+    assert!(server
+        .post(
+            "DEV_USER_TOKEN",
+            &format!("/w/test-workspace/trigger_dead_letters/update/{id}"),
+            json!({"args": {"order": 2}}),
+        )
+        .await
+        .is_err());
+    let client = reqwest::Client::new();
+    let response = client
+        .delete(format!(
+            "http://localhost:{}/api/w/test-workspace/trigger_dead_letters/delete/{id}",
+            server.addr.port()
+        ))
+        .bearer_auth("DEV_USER_TOKEN")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // the replay runs as the caller, who can neither see the private script nor push jobs to
+    // the orders folder without write access
+    for body in [
+        json!({"ids": [id], "runnable_path": "u/test-user/private", "is_flow": false}),
+        json!({"ids": [id]}),
+    ] {
+        let result = server.replay("DEV_USER_TOKEN", body).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result[0]["job_id"].is_null());
+        assert!(result[0]["error"].as_str().is_some(), "{result:?}");
+    }
+    let replayed: bool =
+        sqlx::query_scalar("SELECT replayed_at IS NOT NULL FROM trigger_dead_letter WHERE id = $1")
+            .bind(id)
+            .fetch_one(&db)
+            .await
+            .unwrap();
+    assert!(!replayed);
+
+    let result = server
+        .replay("SECRET_TOKEN", json!({"ids": [id]}))
+        .await
+        .unwrap();
+    let job_id = replayed_job_id(&result, id);
+    let (_, trigger, trigger_kind, _) = job(&db, job_id).await;
+    assert_eq!(trigger, "f/orders/mq");
+    assert_eq!(trigger_kind, "mqtt");
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_failed_trigger_jobs_are_swept(db: Pool<Postgres>) {
+    setup(&db).await;
+    let failed = Uuid::new_v4();
+    let manual = Uuid::new_v4();
+    for (id, trigger_kind) in [(failed, Some("mqtt")), (manual, None)] {
+        sqlx::query(
+            "INSERT INTO v2_job (id, workspace_id, kind, runnable_path, trigger, trigger_kind, args)
+            VALUES ($1, 'test-workspace', 'script', 'f/orders/process', 'f/orders/mq', $2::job_trigger_kind, '{\"order\": 3}')",
+        )
+        .bind(id)
+        .bind(trigger_kind)
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO v2_job_completed (id, workspace_id, duration_ms, status, result)
+            VALUES ($1, 'test-workspace', 10, 'failure', '{\"error\": {\"message\": \"broker down\"}}')",
+        )
+        .bind(id)
+        .execute(&db)
+        .await
+        .unwrap();
+    }
+
+    // the sweeper runs as soon as the server starts
+    let server = ApiServer::start(db.clone(), true).await;
+    let mut dead_letters = vec![];
+    for _ in 0..50 {
+        dead_letters = server.list("SECRET_TOKEN").await;
+        if !dead_letters.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(dead_letters.len(), 1, "{dead_letters:?}");
+    let dead_letter = &dead_letters[0];
+    assert_eq!(dead_letter["failure_kind"], "job");
+    assert_eq!(dead_letter["trigger_kind"], "mqtt");
+    assert_eq!(dead_letter["trigger_path"], "f/orders/mq");
+    assert_eq!(dead_letter["job_id"], failed.to_string());
+    assert_eq!(dead_letter["error"], "broker down");
+    assert_eq!(dead_letter["args"], json!({"order": 3}));
+
+    server.close().await.unwrap();
+}
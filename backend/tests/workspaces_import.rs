@@ -0,0 +1,481 @@
+use futures::StreamExt;
+use serde_json::Value;
+use sqlx::{Pool, Postgres};
+use tokio::io::AsyncReadExt;
+use windmill_common::variables::{build_crypt_from_key, decrypt, encrypt};
+
+pub struct ApiServer {
+    pub addr: std::net::SocketAddr,
+    tx: tokio::sync::broadcast::Sender<()>,
+    task: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+impl ApiServer {
+    pub async fn start(db: Pool<Postgres>) -> Self {
+        let (tx, rx) = tokio::sync::broadcast::channel::<()>(1);
+
+        let sock = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = sock.local_addr().unwrap();
+        drop(sock);
+        let (port_tx, port_rx) = tokio::sync::oneshot::channel::<String>();
+
+        let task = tokio::task::spawn(windmill_api::run_server(
+            db.clone(),
+            None,
+            None,
+            addr,
+            rx,
+            port_tx,
+            false,
+            false,
+            format!("http://localhost:{}", addr.port()),
+        ));
+
+        port_rx.await.expect("failed to receive port");
+
+        windmill_common::cache::clear();
+
+        Self { addr, tx, task }
+    }
+
+    async fn close(self) -> anyhow::Result<()> {
+        let Self { tx, task, .. } = self;
+        drop(tx);
+        task.await.unwrap()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://localhost:{}/api{path}", self.addr.port())
+    }
+
+    /// Archive of test-workspace, with its encryption key, users and groups
+    async fn export(&self) -> Vec<u8> {
+        std::fs::create_dir_all("/tmp/windmill").unwrap();
+        let response = reqwest::Client::new()
+            .get(self.url(
+                "/w/test-workspace/workspaces/tarball?include_key=true&include_users=true&include_groups=true",
+            ))
+            .bearer_auth("SECRET_TOKEN")
+            .send()
+            .await
+            .unwrap();
+        assert!(
+            response.status().is_success(),
+            "{}",
+            response.text().await.unwrap()
+        );
+        response.bytes().await.unwrap().to_vec()
+    }
+
+    async fn import(&self, path: &str, archive: Vec<u8>) -> Result<Value, String> {
+        let response = reqwest::Client::new()
+            .post(self.url(path))
+            .bearer_auth("SECRET_TOKEN")
+            .body(archive)
+            .send()
+            .await
+            .unwrap();
+        if response.status().is_success() {
+            Ok(response.json().await.unwrap())
+        } else {
+            Err(response.text().await.unwrap())
+        }
+    }
+}
+
+/// Content of test-workspace exported by the tests, along with a second workspace to import into
+async fn setup(db: &Pool<Postgres>) {
+    let crypt = build_crypt_from_key("test-key");
+    sqlx::query(
+        "INSERT INTO password (email, password_hash, login_type, super_admin, name, username)
+        VALUES ('test@windmill.dev', 'hash', 'password', true, 'Test', 'test-user'),
+            ('dev@windmill.dev', 'hash', 'password', false, 'Dev', 'dev')",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, operator) VALUES
+            ('test-workspace', 'dev@windmill.dev', 'dev', false, false),
+            ('test-workspace', 'gone@windmill.dev', 'gone', false, true)",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO variable (workspace_id, path, value, is_secret, description) VALUES
+            ('test-workspace', 'f/shared/plain', 'hello', false, 'plain variable'),
+            ('test-workspace', 'f/shared/secret', $1, true, 'secret variable')",
+    )
+    .bind(encrypt(&crypt, "s3cr3t"))
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, created_by, edited_at)
+        VALUES ('test-workspace', 'shared', 'Shared', ARRAY[]::TEXT[], '{}', 'test-user', now())",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "INSERT INTO script (workspace_id, hash, path, summary, description, content, created_by, language)
+        VALUES ('test-workspace', 1234, 'f/shared/hello', 'Hello', '', 'export function main() { return 1 }', 'test-user', 'deno')",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+
+    sqlx::raw_sql(
+        "INSERT INTO workspace (id, name, owner) VALUES ('target-workspace', 'target-workspace', 'test-user');
+        INSERT INTO workspace_settings (workspace_id) VALUES ('target-workspace');
+        INSERT INTO workspace_key (workspace_id, kind, key) VALUES ('target-workspace', 'cloud', 'target-key');
+        INSERT INTO usr (workspace_id, email, username, is_admin) VALUES
+            ('target-workspace', 'test@windmill.dev', 'test-user', true);
+        INSERT INTO group_ (workspace_id, name, summary) VALUES ('target-workspace', 'all', '');",
+    )
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+fn item<'a>(report: &'a Value, kind: &str, path: &str) -> &'a Value {
+    report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["kind"] == kind && item["path"] == path)
+        .unwrap_or_else(|| panic!("no {kind} {path} in {report}"))
+}
+
+async fn variable(db: &Pool<Postgres>, w_id: &str, path: &str) -> Option<String> {
+    sqlx::query_scalar("SELECT value FROM variable WHERE workspace_id = $1 AND path = $2")
+        .bind(w_id)
+        .bind(path)
+        .fetch_optional(db)
+        .await
+        .unwrap()
+}
+
+async fn count(db: &Pool<Postgres>, query: &str) -> i64 {
+    sqlx::query_scalar(query).fetch_one(db).await.unwrap()
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_import_into_existing_workspace(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    let archive = server.export().await;
+
+    let report = server
+        .import("/w/target-workspace/workspaces/import_tarball", archive)
+        .await
+        .unwrap();
+    assert_eq!(report["dry_run"], false);
+    assert_eq!(item(&report, "folder", "shared")["action"], "create");
+    assert_eq!(
+        item(&report, "variable", "f/shared/plain")["action"],
+        "create"
+    );
+    assert_eq!(
+        item(&report, "script", "f/shared/hello")["action"],
+        "create"
+    );
+
+    // secrets are encrypted with the key of the target workspace
+    let secret = variable(&db, "target-workspace", "f/shared/secret")
+        .await
+        .unwrap();
+    assert_eq!(
+        decrypt(&build_crypt_from_key("target-key"), secret).unwrap(),
+        "s3cr3t"
+    );
+    assert_eq!(
+        variable(&db, "target-workspace", "f/shared/plain").await,
+        Some("hello".to_string())
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM script WHERE workspace_id = 'target-workspace'
+            AND path = 'f/shared/hello' AND content = 'export function main() { return 1 }'"
+        )
+        .await,
+        1
+    );
+
+    // users are restored with their role, only if they have an account on the instance
+    assert_eq!(item(&report, "user", "test@windmill.dev")["action"], "skip");
+    assert_eq!(
+        item(&report, "user", "dev@windmill.dev")["action"],
+        "create"
+    );
+    let gone = item(&report, "user", "gone@windmill.dev");
+    assert_eq!(gone["action"], "skip");
+    assert_eq!(gone["reason"], "no account on this instance");
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM usr WHERE workspace_id = 'target-workspace'
+            AND username = 'dev' AND is_admin = false AND operator = false"
+        )
+        .await,
+        1
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_import_conflicts(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    let archive = server.export().await;
+    server
+        .import(
+            "/w/target-workspace/workspaces/import_tarball",
+            archive.clone(),
+        )
+        .await
+        .unwrap();
+    sqlx::query(
+        "UPDATE variable SET value = 'changed' WHERE workspace_id = 'target-workspace' AND path = 'f/shared/plain'",
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let report = server
+        .import(
+            "/w/target-workspace/workspaces/import_tarball",
+            archive.clone(),
+        )
+        .await
+        .unwrap();
+    let plain = item(&report, "variable", "f/shared/plain");
+    assert_eq!(plain["action"], "skip");
+    assert_eq!(plain["reason"], "already exists");
+    assert_eq!(
+        variable(&db, "target-workspace", "f/shared/plain").await,
+        Some("changed".to_string())
+    );
+
+    let report = server
+        .import(
+            "/w/target-workspace/workspaces/import_tarball?on_conflict=rename",
+            archive.clone(),
+        )
+        .await
+        .unwrap();
+    let plain = item(&report, "variable", "f/shared/plain");
+    assert_eq!(plain["action"], "rename");
+    assert_eq!(plain["renamed_to"], "f/shared/plain_imported");
+    assert_eq!(
+        variable(&db, "target-workspace", "f/shared/plain_imported").await,
+        Some("hello".to_string())
+    );
+    // folders cannot be renamed
+    assert_eq!(item(&report, "folder", "shared")["action"], "skip");
+    assert_eq!(
+        item(&report, "script", "f/shared/hello")["renamed_to"],
+        "f/shared/hello_imported"
+    );
+
+    let report = server
+        .import(
+            "/w/target-workspace/workspaces/import_tarball?on_conflict=overwrite",
+            archive,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        item(&report, "variable", "f/shared/plain")["action"],
+        "overwrite"
+    );
+    assert_eq!(
+        variable(&db, "target-workspace", "f/shared/plain").await,
+        Some("hello".to_string())
+    );
+    // the overwritten script gets a new version on top of the imported one
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM script WHERE workspace_id = 'target-workspace'
+            AND path = 'f/shared/hello' AND archived = false"
+        )
+        .await,
+        1
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM script WHERE workspace_id = 'target-workspace'
+            AND path = 'f/shared/hello'"
+        )
+        .await,
+        2
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_import_dry_run(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    let archive = server.export().await;
+
+    let report = server
+        .import(
+            "/w/target-workspace/workspaces/import_tarball?dry_run=true",
+            archive,
+        )
+        .await
+        .unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(
+        item(&report, "variable", "f/shared/plain")["action"],
+        "create"
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM variable WHERE workspace_id = 'target-workspace'"
+        )
+        .await,
+        0
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM usr WHERE workspace_id = 'target-workspace'"
+        )
+        .await,
+        1
+    );
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_create_workspace_from_tarball(db: Pool<Postgres>) {
+    setup(&db).await;
+    // only two workspaces can be created without a license
+    sqlx::query("UPDATE workspace SET deleted = true WHERE id = 'target-workspace'")
+        .execute(&db)
+        .await
+        .unwrap();
+    let server = ApiServer::start(db.clone()).await;
+    let archive = server.export().await;
+
+    // nothing is left behind by a dry run, not even the workspace
+    let report = server
+        .import(
+            "/workspaces/create_from_tarball?id=restored&name=Restored&dry_run=true",
+            archive.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(
+        count(&db, "SELECT COUNT(*) FROM workspace WHERE id = 'restored'").await,
+        0
+    );
+
+    let report = server
+        .import(
+            "/workspaces/create_from_tarball?id=restored&name=Restored",
+            archive.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        item(&report, "variable", "f/shared/plain")["action"],
+        "create"
+    );
+    assert_eq!(item(&report, "user", "test@windmill.dev")["action"], "skip");
+
+    let key = sqlx::query_scalar::<_, String>(
+        "SELECT key FROM workspace_key WHERE workspace_id = 'restored'",
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    let secret = variable(&db, "restored", "f/shared/secret").await.unwrap();
+    assert_eq!(
+        decrypt(&build_crypt_from_key(&key), secret).unwrap(),
+        "s3cr3t"
+    );
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM usr WHERE workspace_id = 'restored' AND username = 'test-user' AND is_admin = true"
+        )
+        .await,
+        1
+    );
+
+    // an existing workspace is not imported into
+    sqlx::query("UPDATE workspace SET deleted = true WHERE id = 'test-workspace'")
+        .execute(&db)
+        .await
+        .unwrap();
+    let error = server
+        .import(
+            "/workspaces/create_from_tarball?id=restored&name=Restored",
+            archive,
+        )
+        .await
+        .unwrap_err();
+    assert!(error.contains("restored"), "{error}");
+
+    server.close().await.unwrap();
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_import_secrets_without_key(db: Pool<Postgres>) {
+    setup(&db).await;
+    let server = ApiServer::start(db.clone()).await;
+    let archive = server.export().await;
+
+    // an archive without its key is decrypted with the key of the target workspace
+    let mut builder = tokio_tar::Builder::new(vec![]);
+    let mut entries = tokio_tar::Archive::new(archive.as_slice());
+    let mut entries = entries.entries().unwrap();
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().to_string();
+        if path.ends_with("encryption_key.json") {
+            continue;
+        }
+        let mut content = vec![];
+        entry.read_to_end(&mut content).await.unwrap();
+        let mut header = entry.header().clone();
+        builder
+            .append_data(&mut header, path, content.as_slice())
+            .await
+            .unwrap();
+    }
+    let archive = builder.into_inner().await.unwrap();
+
+    let error = server
+        .import("/w/target-workspace/workspaces/import_tarball", archive)
+        .await
+        .unwrap_err();
+    assert!(
+        error.contains("Could not decrypt secret variable f/shared/secret"),
+        "{error}"
+    );
+    // the failed import is rolled back as a whole
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM variable WHERE workspace_id = 'target-workspace'"
+        )
+        .await,
+        0
+    );
+
+    server.close().await.unwrap();
+}
@@ -1,9 +1,17 @@
 use crate::{
+    ai_usage::{check_budgets, get_usage, include_usage_in_stream, UsageTracker},
     db::{ApiAuthed, DB},
     variables::get_variable_or_self,
 };
 
-use axum::{body::Bytes, extract::Path, response::IntoResponse, routing::post, Extension, Router};
+use axum::{
+    body::Bytes,
+    extract::Path,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Router,
+};
+use futures::StreamExt;
 use http::{HeaderMap, Method};
 use quick_cache::sync::Cache;
 use reqwest::{Client, RequestBuilder};
@@ -341,6 +349,23 @@ pub struct ProviderModel {
     pub provider: AIProvider,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum AIBudgetPeriod {
+    Daily,
+    Monthly,
+}
+
+/// Maximum number of tokens, input and output, used through the proxy in a period (UTC)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AIBudget {
+    pub period: AIBudgetPeriod,
+    pub max_tokens: i64,
+    /// the budget applies to each user instead of the whole workspace
+    #[serde(default)]
+    pub per_user: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AIConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -349,6 +374,8 @@ pub struct AIConfig {
     pub default_model: Option<ProviderModel>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code_completion_model: Option<ProviderModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budgets: Option<Vec<AIBudget>>,
 }
 
 pub fn global_service() -> Router {
@@ -356,7 +383,9 @@ pub fn global_service() -> Router {
 }
 
 pub fn workspaced_service() -> Router {
-    Router::new().route("/proxy/*ai", post(proxy).get(proxy))
+    Router::new()
+        .route("/proxy/*ai", post(proxy).get(proxy))
+        .route("/usage", get(get_usage))
 }
 
 async fn global_proxy(
@@ -507,6 +536,10 @@ async fn proxy(
         }
     };
 
+    check_budgets(&db, &authed, &w_id).await?;
+
+    let body = include_usage_in_stream(&provider, &ai_path, body)?;
+    let request_body = body.clone();
     let request = request_config.prepare_request(&provider, &ai_path, method, headers, body)?;
 
     let response = request.send().await.map_err(to_anyhow)?;
@@ -532,6 +565,22 @@ async fn proxy(
 
     let status_code = response.status();
     let headers = response.headers().clone();
-    let stream = response.bytes_stream();
+    let is_sse = headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"));
+    let mut usage_tracker = UsageTracker::new(
+        db.clone(),
+        w_id.clone(),
+        authed.email.clone(),
+        &provider,
+        &request_body,
+        is_sse,
+    );
+    let stream = response.bytes_stream().inspect(move |chunk| {
+        if let Ok(chunk) = chunk {
+            usage_tracker.feed(chunk);
+        }
+    });
     Ok((status_code, headers, axum::body::Body::from_stream(stream)))
 }
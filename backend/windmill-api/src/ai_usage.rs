@@ -0,0 +1,480 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Tokens used through the AI proxy, per workspace, user, provider and model, and the budgets
+//! limiting them. Usage is read from the responses of the providers, streamed or not, and is
+//! recorded once the response has been forwarded. Budgets are checked before each request, so
+//! requests running concurrently can exceed a budget by their own usage.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    Extension, Json,
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, value::RawValue, Value};
+use std::collections::HashMap;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::error::{Error, JsonResult, Result};
+
+use crate::{
+    ai::{AIBudget, AIBudgetPeriod, AIProvider},
+    db::{ApiAuthed, DB},
+};
+
+/// Responses that are not streamed are parsed whole up to this size. Only the end of the larger
+/// ones is kept, the providers sending the usage after the content.
+const MAX_BUFFERED_RESPONSE_SIZE: usize = 10 * 1024 * 1024;
+const USAGE_TAIL_SIZE: usize = 64 * 1024;
+
+fn period_start(period: &AIBudgetPeriod, today: NaiveDate) -> NaiveDate {
+    match period {
+        AIBudgetPeriod::Daily => today,
+        AIBudgetPeriod::Monthly => today.with_day(1).unwrap_or(today),
+    }
+}
+
+fn seconds_until_period_end(period: &AIBudgetPeriod) -> u64 {
+    let now = Utc::now();
+    let today = now.date_naive();
+    let end = match period {
+        AIBudgetPeriod::Daily => today.succ_opt(),
+        AIBudgetPeriod::Monthly => {
+            let (year, month) = if today.month() == 12 {
+                (today.year() + 1, 1)
+            } else {
+                (today.year(), today.month() + 1)
+            };
+            NaiveDate::from_ymd_opt(year, month, 1)
+        }
+    };
+    end.and_then(|end| end.and_hms_opt(0, 0, 0))
+        .map(|end| (end.and_utc() - now).num_seconds().max(0) as u64)
+        .unwrap_or(0)
+}
+
+/// Rejects the request if the tokens already used in the period of one of the budgets of the
+/// workspace reached it
+pub async fn check_budgets(db: &DB, authed: &ApiAuthed, w_id: &str) -> Result<()> {
+    let budgets = sqlx::query_scalar!(
+        "SELECT ai_config->'budgets' FROM workspace_settings WHERE workspace_id = $1",
+        w_id
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten();
+    let Some(budgets) = budgets else {
+        return Ok(());
+    };
+    let budgets = serde_json::from_value::<Vec<AIBudget>>(budgets)
+        .map_err(|e| Error::BadConfig(format!("Invalid AI budgets: {e}")))?;
+
+    let today = Utc::now().date_naive();
+    for budget in budgets {
+        let used = sqlx::query_scalar!(
+            "SELECT COALESCE(SUM(input_tokens + output_tokens), 0)::BIGINT FROM ai_usage
+            WHERE workspace_id = $1 AND day >= $2 AND ($3::text IS NULL OR email = $3)",
+            w_id,
+            period_start(&budget.period, today),
+            budget.per_user.then(|| authed.email.as_str())
+        )
+        .fetch_one(db)
+        .await?
+        .unwrap_or(0);
+
+        if used >= budget.max_tokens {
+            let scope = if budget.per_user {
+                format!("user {}", authed.email)
+            } else {
+                format!("workspace {w_id}")
+            };
+            let period = match budget.period {
+                AIBudgetPeriod::Daily => "daily",
+                AIBudgetPeriod::Monthly => "monthly",
+            };
+            let mut tx = db.begin().await?;
+            audit_log(
+                &mut *tx,
+                authed,
+                "ai.budget_exceeded",
+                ActionKind::Execute,
+                w_id,
+                Some(&authed.email),
+                Some([("period", period)].into()),
+            )
+            .await?;
+            tx.commit().await?;
+            return Err(Error::TooManyRequests {
+                message: format!(
+                    "{period} AI budget of {} tokens exhausted for {scope} ({used} tokens used), \
+                    update the budgets in the AI settings of the workspace to increase it",
+                    budget.max_tokens
+                ),
+                retry_after_s: seconds_until_period_end(&budget.period),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Asks the providers of the OpenAI chat completions API to send the usage in the last chunk of
+/// the streamed responses, which they do not do by default
+pub fn include_usage_in_stream(provider: &AIProvider, path: &str, body: Bytes) -> Result<Bytes> {
+    let supports_stream_options = matches!(
+        provider,
+        AIProvider::OpenAI
+            | AIProvider::AzureOpenAI
+            | AIProvider::DeepSeek
+            | AIProvider::GoogleAI
+            | AIProvider::Groq
+            | AIProvider::OpenRouter
+            | AIProvider::TogetherAI
+    );
+    if !supports_stream_options || !path.ends_with("chat/completions") {
+        return Ok(body);
+    }
+    let Ok(mut json_body) = serde_json::from_slice::<HashMap<String, Box<RawValue>>>(&body) else {
+        return Ok(body);
+    };
+    let is_streamed = json_body.get("stream").is_some_and(|s| s.get() == "true");
+    if !is_streamed || json_body.contains_key("stream_options") {
+        return Ok(body);
+    }
+    json_body.insert(
+        "stream_options".to_string(),
+        RawValue::from_string(r#"{"include_usage":true}"#.to_string())
+            .map_err(|e| Error::internal_err(format!("Failed to build stream options: {}", e)))?,
+    );
+    Ok(serde_json::to_vec(&json_body)
+        .map_err(|e| Error::internal_err(format!("Failed to reserialize request body: {}", e)))?
+        .into())
+}
+
+/// Value of the first or last occurrence of the key in a part of a JSON document
+fn value_of_key(json: &[u8], key: &str, last: bool) -> Option<Value> {
+    let pattern = format!("\"{key}\"");
+    let mut starts = json
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(i, w)| *w == pattern.as_bytes() && (*i == 0 || json[i - 1] != b'\\'))
+        .map(|(i, _)| i + pattern.len());
+    let start = if last { starts.last() } else { starts.next() }?;
+    let rest = &json[start..];
+    let rest = rest[rest.iter().position(|b| !b.is_ascii_whitespace())?..].strip_prefix(b":")?;
+    serde_json::Deserializer::from_slice(rest)
+        .into_iter::<Value>()
+        .next()?
+        .ok()
+}
+
+/// Reads the usage from the response as it is forwarded, and records it when dropped, i.e. once
+/// the response has been fully forwarded or the client went away
+pub struct UsageTracker {
+    db: DB,
+    w_id: String,
+    email: String,
+    provider: String,
+    model: String,
+    is_sse: bool,
+    buffer: Vec<u8>,
+    /// whether the start of the response that is not streamed was dropped from the buffer
+    truncated: bool,
+    input_tokens: i64,
+    output_tokens: i64,
+}
+
+impl UsageTracker {
+    pub fn new(
+        db: DB,
+        w_id: String,
+        email: String,
+        provider: &AIProvider,
+        request_body: &Bytes,
+        is_sse: bool,
+    ) -> Self {
+        #[derive(Deserialize)]
+        struct RequestModel {
+            model: Option<String>,
+        }
+        let model = serde_json::from_slice::<RequestModel>(request_body)
+            .ok()
+            .and_then(|r| r.model)
+            .unwrap_or_default();
+        let provider = serde_json::to_value(provider)
+            .ok()
+            .and_then(|p| p.as_str().map(str::to_string))
+            .unwrap_or_default();
+        Self {
+            db,
+            w_id,
+            email,
+            provider,
+            model,
+            is_sse,
+            buffer: vec![],
+            truncated: false,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+        if !self.is_sse {
+            if self.buffer.len() > MAX_BUFFERED_RESPONSE_SIZE {
+                // the model is sent before the content
+                if !self.truncated && self.model.is_empty() {
+                    if let Some(Value::String(model)) = value_of_key(&self.buffer, "model", false) {
+                        self.model = model;
+                    }
+                }
+                self.truncated = true;
+                let dropped = self.buffer.len() - USAGE_TAIL_SIZE;
+                self.buffer.drain(..dropped);
+            }
+            return;
+        }
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(data) = line.trim().strip_prefix("data:") {
+                if let Ok(event) = serde_json::from_str::<Value>(data.trim()) {
+                    self.read_usage(&event);
+                }
+            }
+        }
+        // events with the usage are small, the rest of a larger one is ignored as it is not
+        // prefixed with `data:`
+        if self.buffer.len() > MAX_BUFFERED_RESPONSE_SIZE {
+            self.buffer.clear();
+        }
+    }
+
+    /// Usage of the OpenAI compatible APIs (chat completions and responses) and of Anthropic.
+    /// Anthropic streams the output tokens cumulatively, hence only the largest count is kept.
+    fn read_usage(&mut self, value: &Value) {
+        let usages = [
+            value.get("usage"),
+            value.pointer("/message/usage"),
+            value.pointer("/response/usage"),
+            value.pointer("/x_groq/usage"),
+        ];
+        for usage in usages.into_iter().flatten() {
+            let count = |keys: &[&str]| {
+                keys.iter()
+                    .filter_map(|k| usage.get(*k).and_then(Value::as_i64))
+                    .sum::<i64>()
+            };
+            let input_tokens = count(&[
+                "prompt_tokens",
+                "input_tokens",
+                "cache_creation_input_tokens",
+                "cache_read_input_tokens",
+            ]);
+            let output_tokens = count(&["completion_tokens", "output_tokens"]);
+            self.input_tokens = self.input_tokens.max(input_tokens);
+            self.output_tokens = self.output_tokens.max(output_tokens);
+        }
+        if self.model.is_empty() {
+            if let Some(model) = value
+                .get("model")
+                .or_else(|| value.pointer("/message/model"))
+                .and_then(Value::as_str)
+            {
+                self.model = model.to_string();
+            }
+        }
+    }
+}
+
+impl Drop for UsageTracker {
+    fn drop(&mut self) {
+        if !self.is_sse {
+            let response = if self.truncated {
+                value_of_key(&self.buffer, "usage", true).map(|usage| json!({ "usage": usage }))
+            } else {
+                serde_json::from_slice::<Value>(&self.buffer).ok()
+            };
+            if let Some(response) = response {
+                self.read_usage(&response);
+            }
+        }
+        if self.model.is_empty() && self.input_tokens == 0 && self.output_tokens == 0 {
+            return;
+        }
+        let db = self.db.clone();
+        let w_id = std::mem::take(&mut self.w_id);
+        let email = std::mem::take(&mut self.email);
+        let provider = std::mem::take(&mut self.provider);
+        let model = std::mem::take(&mut self.model);
+        let (input_tokens, output_tokens) = (self.input_tokens, self.output_tokens);
+        tokio::spawn(async move {
+            let recorded = sqlx::query!(
+                "INSERT INTO ai_usage
+                    (workspace_id, day, email, provider, model, requests, input_tokens, output_tokens)
+                VALUES ($1, $2, $3, $4, $5, 1, $6, $7)
+                ON CONFLICT (workspace_id, day, email, provider, model) DO UPDATE SET
+                    requests = ai_usage.requests + 1,
+                    input_tokens = ai_usage.input_tokens + $6,
+                    output_tokens = ai_usage.output_tokens + $7",
+                w_id,
+                Utc::now().date_naive(),
+                email,
+                provider,
+                model,
+                input_tokens,
+                output_tokens
+            )
+            .execute(&db)
+            .await;
+            if let Err(e) = recorded {
+                tracing::error!("Could not record AI usage of {email} in {w_id}: {e:#}");
+            }
+        });
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum UsageGroupBy {
+    User,
+    Group,
+    Provider,
+    Model,
+    Day,
+}
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    /// first day included, defaults to the first day of the current month
+    from: Option<NaiveDate>,
+    /// last day included, defaults to today
+    to: Option<NaiveDate>,
+    group_by: Option<UsageGroupBy>,
+    email: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct UsageRow {
+    key: Option<String>,
+    requests: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+}
+
+/// Usage of the workspace, by user, group, provider, model or day. Users in several groups are
+/// counted in each of them. Non admins only see their own usage.
+pub async fn get_usage(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(q): Query<UsageQuery>,
+) -> JsonResult<Vec<UsageRow>> {
+    let email = if authed.is_admin {
+        q.email
+    } else {
+        Some(authed.email.clone())
+    };
+    let today = Utc::now().date_naive();
+    let from = q
+        .from
+        .unwrap_or_else(|| period_start(&AIBudgetPeriod::Monthly, today));
+    let to = q.to.unwrap_or(today);
+
+    let (key, join) = match q.group_by.unwrap_or(UsageGroupBy::User) {
+        UsageGroupBy::User => ("u.email", ""),
+        UsageGroupBy::Provider => ("u.provider", ""),
+        UsageGroupBy::Model => ("u.model", ""),
+        UsageGroupBy::Day => ("u.day::text", ""),
+        UsageGroupBy::Group => (
+            "g.group_",
+            "LEFT JOIN usr ON usr.workspace_id = u.workspace_id AND usr.email = u.email
+            LEFT JOIN usr_to_group g ON g.workspace_id = u.workspace_id AND g.usr = usr.username",
+        ),
+    };
+    let rows = sqlx::query_as::<_, UsageRow>(&format!(
+        "SELECT {key} AS key, SUM(u.requests)::BIGINT AS requests,
+            SUM(u.input_tokens)::BIGINT AS input_tokens, SUM(u.output_tokens)::BIGINT AS output_tokens
+        FROM ai_usage u {join}
+        WHERE u.workspace_id = $1 AND u.day >= $2 AND u.day <= $3 AND ($4::text IS NULL OR u.email = $4)
+        GROUP BY 1 ORDER BY 1"
+    ))
+    .bind(&w_id)
+    .bind(from)
+    .bind(to)
+    .bind(email)
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_period_start() {
+        let today = NaiveDate::from_ymd_opt(2025, 8, 13).unwrap();
+        assert_eq!(period_start(&AIBudgetPeriod::Daily, today), today);
+        assert_eq!(
+            period_start(&AIBudgetPeriod::Monthly, today),
+            NaiveDate::from_ymd_opt(2025, 8, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_include_usage_in_stream() {
+        let streamed = Bytes::from(r#"{"model":"gpt-4o","stream":true}"#);
+        let body =
+            include_usage_in_stream(&AIProvider::OpenAI, "chat/completions", streamed.clone())
+                .unwrap();
+        let body = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(body["stream_options"], json!({"include_usage": true}));
+        assert_eq!(body["model"], "gpt-4o");
+
+        // the usage of the other APIs is always sent, and stream options are not overridden
+        for (provider, path, body) in [
+            (AIProvider::Anthropic, "messages", streamed.clone()),
+            (AIProvider::OpenAI, "responses", streamed),
+            (
+                AIProvider::OpenAI,
+                "chat/completions",
+                Bytes::from(r#"{"stream":false}"#),
+            ),
+            (
+                AIProvider::OpenAI,
+                "chat/completions",
+                Bytes::from(r#"{"stream":true,"stream_options":{"include_usage":false}}"#),
+            ),
+        ] {
+            assert_eq!(
+                include_usage_in_stream(&provider, path, body.clone()).unwrap(),
+                body
+            );
+        }
+    }
+
+    #[test]
+    fn test_value_of_key() {
+        let json = br#"{"model": "gpt-4o", "choices": [{"text": "\"usage\": 1"}], "usage": {"total_tokens": 3}}"#;
+        assert_eq!(value_of_key(json, "model", false), Some(json!("gpt-4o")));
+        assert_eq!(
+            value_of_key(json, "usage", true),
+            Some(json!({"total_tokens": 3}))
+        );
+        // the tail of a truncated response
+        let tail = br#"ntent": "end"}}], "usage" : {"prompt_tokens": 1, "completion_tokens": 2}}"#;
+        assert_eq!(
+            value_of_key(tail, "usage", true),
+            Some(json!({"prompt_tokens": 1, "completion_tokens": 2}))
+        );
+        assert_eq!(value_of_key(tail, "model", false), None);
+    }
+}
@@ -0,0 +1,978 @@
+use crate::{
+    capture::AmqpTriggerConfig,
+    db::{ApiAuthed, DB},
+    resources::try_get_resource_from_db_as,
+    trigger_helpers::TriggerJobArgs,
+    triggers::{
+        handler::{trigger_routes, PgQuery, Trigger, TriggerCrud},
+        listener::{trigger_job, Listener, ListeningConfig},
+    },
+};
+use windmill_git_sync::DeployedObject;
+
+use axum::{async_trait, extract::Path, routing::post, Extension, Json, Router};
+use base64::{engine, prelude::*};
+use futures::TryStreamExt;
+use lapin::{
+    message::Delivery,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions,
+        BasicQosOptions, ConfirmSelectOptions, ExchangeDeclareOptions, QueueBindOptions,
+        QueueDeclareOptions,
+    },
+    types::{AMQPValue, FieldArray, FieldTable, LongString, ShortString},
+    uri::{AMQPScheme, AMQPUri},
+    BasicProperties, Channel, Connection, ConnectionProperties, Consumer, ExchangeKind,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Map, Value};
+use sqlx::{types::Json as SqlxJson, FromRow};
+use std::collections::HashMap;
+use tokio::time::Duration;
+use windmill_common::{
+    db::UserDB, error, triggers::TriggerKind, utils::report_critical_error, worker::to_raw_value,
+};
+
+pub fn workspaced_service() -> Router {
+    trigger_routes::<AmqpTriggerHandler>()
+        .route("/test", post(test_amqp_connection))
+        .route("/publish", post(publish_amqp_message))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Common(#[from] windmill_common::error::Error),
+    #[error("{0}")]
+    Lapin(#[from] lapin::Error),
+}
+
+/// Header holding the number of times the delivery of a message was attempted by the trigger
+const DELIVERY_ATTEMPTS_HEADER: &str = "x-windmill-delivery-attempts";
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const DEFAULT_PREFETCH_COUNT: i32 = 10;
+const DEFAULT_MAX_DELIVERY_ATTEMPTS: i32 = 3;
+
+async fn run_job(
+    payload: &[u8],
+    trigger_info: HashMap<String, Box<RawValue>>,
+    db: &DB,
+    trigger: &AmqpTrigger,
+) -> anyhow::Result<()> {
+    let args = AmqpTriggerHandler::build_job_args(
+        &trigger.base.script_path,
+        trigger.base.is_flow,
+        &trigger.base.workspace_id,
+        db,
+        payload,
+        trigger_info,
+    )
+    .await?;
+
+    trigger_job::<AmqpTriggerHandler>(db, &trigger.base, args, None).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AmqpResource {
+    host: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    vhost: Option<String>,
+    tls: Option<bool>,
+}
+
+impl AmqpResource {
+    async fn connect(&self) -> Result<Connection, Error> {
+        let mut uri = AMQPUri::default();
+        if self.tls.unwrap_or(false) {
+            uri.scheme = AMQPScheme::AMQPS;
+        }
+        uri.authority.host = self.host.clone();
+        uri.authority.port = self.port.unwrap_or_else(|| uri.scheme.default_port());
+        if let Some(username) = &self.username {
+            uri.authority.userinfo.username = username.clone();
+        }
+        if let Some(password) = &self.password {
+            uri.authority.userinfo.password = password.clone();
+        }
+        if let Some(vhost) = self.vhost.as_ref().filter(|vhost| !vhost.is_empty()) {
+            uri.vhost = vhost.clone();
+        }
+
+        Ok(Connection::connect_uri(uri, ConnectionProperties::default()).await?)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AmqpExchangeType {
+    Direct,
+    Fanout,
+    Topic,
+    Headers,
+}
+
+impl From<&AmqpExchangeType> for ExchangeKind {
+    fn from(value: &AmqpExchangeType) -> Self {
+        match value {
+            AmqpExchangeType::Direct => ExchangeKind::Direct,
+            AmqpExchangeType::Fanout => ExchangeKind::Fanout,
+            AmqpExchangeType::Topic => ExchangeKind::Topic,
+            AmqpExchangeType::Headers => ExchangeKind::Headers,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AmqpBinding {
+    exchange: String,
+    #[serde(default)]
+    routing_key: String,
+    /// The exchange is declared as a durable exchange of this type if set, otherwise it must
+    /// already exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exchange_type: Option<AmqpExchangeType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Map<String, Value>>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AmqpQueueOptions {
+    #[serde(default = "default_true")]
+    durable: bool,
+    #[serde(default)]
+    exclusive: bool,
+    #[serde(default)]
+    auto_delete: bool,
+    /// Only checks that the queue exists instead of declaring it
+    #[serde(default)]
+    passive: bool,
+    /// Arguments of the queue declaration such as `x-queue-type` or `x-dead-letter-exchange`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Map<String, Value>>,
+}
+
+impl Default for AmqpQueueOptions {
+    fn default() -> Self {
+        Self {
+            durable: true,
+            exclusive: false,
+            auto_delete: false,
+            passive: false,
+            arguments: None,
+        }
+    }
+}
+
+fn json_to_amqp_value(value: &Value) -> AMQPValue {
+    match value {
+        Value::Null => AMQPValue::Void,
+        Value::Bool(bool) => AMQPValue::Boolean(*bool),
+        Value::Number(number) => match number.as_i64() {
+            Some(int) => AMQPValue::LongLongInt(int),
+            None => AMQPValue::Double(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(string) => AMQPValue::LongString(LongString::from(string.clone())),
+        Value::Array(array) => AMQPValue::FieldArray(FieldArray::from(
+            array.iter().map(json_to_amqp_value).collect::<Vec<_>>(),
+        )),
+        Value::Object(object) => AMQPValue::FieldTable(json_to_field_table(object)),
+    }
+}
+
+fn json_to_field_table(object: &Map<String, Value>) -> FieldTable {
+    let mut table = FieldTable::default();
+    for (key, value) in object {
+        table.insert(ShortString::from(key.clone()), json_to_amqp_value(value));
+    }
+    table
+}
+
+fn bytes_to_json(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(string) => Value::from(string),
+        Err(_) => Value::from(engine::general_purpose::STANDARD.encode(bytes)),
+    }
+}
+
+fn amqp_value_to_json(value: &AMQPValue) -> Value {
+    match value {
+        AMQPValue::Boolean(bool) => Value::from(*bool),
+        AMQPValue::ShortShortInt(int) => Value::from(*int),
+        AMQPValue::ShortShortUInt(int) => Value::from(*int),
+        AMQPValue::ShortInt(int) => Value::from(*int),
+        AMQPValue::ShortUInt(int) => Value::from(*int),
+        AMQPValue::LongInt(int) => Value::from(*int),
+        AMQPValue::LongUInt(int) => Value::from(*int),
+        AMQPValue::LongLongInt(int) => Value::from(*int),
+        AMQPValue::Float(float) => Value::from(*float as f64),
+        AMQPValue::Double(double) => Value::from(*double),
+        AMQPValue::DecimalValue(decimal) => {
+            Value::from(decimal.value as f64 / 10f64.powi(decimal.scale as i32))
+        }
+        AMQPValue::ShortString(string) => Value::from(string.as_str()),
+        AMQPValue::LongString(string) => bytes_to_json(string.as_bytes()),
+        AMQPValue::FieldArray(array) => {
+            Value::Array(array.as_slice().iter().map(amqp_value_to_json).collect())
+        }
+        AMQPValue::Timestamp(timestamp) => Value::from(*timestamp),
+        AMQPValue::FieldTable(table) => field_table_to_json(table),
+        AMQPValue::ByteArray(bytes) => bytes_to_json(bytes.as_slice()),
+        AMQPValue::Void => Value::Null,
+    }
+}
+
+fn field_table_to_json(table: &FieldTable) -> Value {
+    Value::Object(
+        table
+            .inner()
+            .iter()
+            .map(|(key, value)| (key.to_string(), amqp_value_to_json(value)))
+            .collect(),
+    )
+}
+
+fn properties_to_json(properties: &BasicProperties) -> Value {
+    serde_json::json!({
+        "content_type": properties.content_type().as_ref().map(ShortString::as_str),
+        "content_encoding": properties.content_encoding().as_ref().map(ShortString::as_str),
+        "headers": properties.headers().as_ref().map(field_table_to_json),
+        "delivery_mode": properties.delivery_mode(),
+        "priority": properties.priority(),
+        "correlation_id": properties.correlation_id().as_ref().map(ShortString::as_str),
+        "reply_to": properties.reply_to().as_ref().map(ShortString::as_str),
+        "expiration": properties.expiration().as_ref().map(ShortString::as_str),
+        "message_id": properties.message_id().as_ref().map(ShortString::as_str),
+        "timestamp": properties.timestamp(),
+        "type": properties.kind().as_ref().map(ShortString::as_str),
+        "user_id": properties.user_id().as_ref().map(ShortString::as_str),
+        "app_id": properties.app_id().as_ref().map(ShortString::as_str),
+    })
+}
+
+fn delivery_attempts(properties: &BasicProperties) -> u64 {
+    properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| {
+            headers
+                .inner()
+                .get(&ShortString::from(DELIVERY_ATTEMPTS_HEADER))
+        })
+        .and_then(|attempts| amqp_value_to_json(attempts).as_u64())
+        .unwrap_or(0)
+}
+
+/// Returns the properties of the message with additional headers
+fn with_headers(properties: &BasicProperties, headers: Vec<(&str, AMQPValue)>) -> BasicProperties {
+    let mut table = properties.headers().clone().unwrap_or_default();
+    for (key, value) in headers {
+        table.insert(ShortString::from(key), value);
+    }
+    properties.clone().with_headers(table)
+}
+
+async fn publish_confirmed(
+    channel: &Channel,
+    exchange: &str,
+    routing_key: &str,
+    payload: &[u8],
+    properties: BasicProperties,
+) -> Result<(), Error> {
+    let confirmation = channel
+        .basic_publish(
+            exchange,
+            routing_key,
+            BasicPublishOptions::default(),
+            payload,
+            properties,
+        )
+        .await?
+        .await?;
+
+    if confirmation.is_nack() {
+        return Err(error::Error::InternalErr(format!(
+            "Message published to exchange '{}' with routing key '{}' was rejected by the broker",
+            exchange, routing_key
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn default_prefetch_count() -> i32 {
+    DEFAULT_PREFETCH_COUNT
+}
+
+fn default_max_delivery_attempts() -> i32 {
+    DEFAULT_MAX_DELIVERY_ATTEMPTS
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AmqpConfig {
+    pub amqp_resource_path: String,
+    pub queue_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_options: Option<SqlxJson<AmqpQueueOptions>>,
+    #[serde(default)]
+    pub bindings: SqlxJson<Vec<AmqpBinding>>,
+    #[serde(default = "default_prefetch_count")]
+    pub prefetch_count: i32,
+    #[serde(default = "default_max_delivery_attempts")]
+    pub max_delivery_attempts: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_letter_exchange: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dead_letter_routing_key: Option<String>,
+}
+
+pub type AmqpTrigger = Trigger<AmqpConfig>;
+
+pub struct AmqpTriggerHandler;
+
+impl TriggerCrud for AmqpTriggerHandler {
+    const TABLE_NAME: &'static str = "amqp_trigger";
+    const ROUTE_PREFIX: &'static str = "amqp_triggers";
+    const DISPLAY_NAME: &'static str = "AMQP";
+    const KIND: TriggerKind = TriggerKind::Amqp;
+    const CONFIG_COLUMNS: &'static [&'static str] = &[
+        "amqp_resource_path",
+        "queue_name",
+        "queue_options",
+        "bindings",
+        "prefetch_count",
+        "max_delivery_attempts",
+        "dead_letter_exchange",
+        "dead_letter_routing_key",
+    ];
+
+    type Config = AmqpConfig;
+
+    fn deployed_object(path: String) -> DeployedObject {
+        DeployedObject::AmqpTrigger { path }
+    }
+
+    fn validate_config(config: &AmqpConfig) -> error::Result<()> {
+        if config.queue_name.trim().is_empty() {
+            return Err(error::Error::BadRequest(
+                "AMQP trigger must have a queue name".to_string(),
+            ));
+        }
+        if !(1..=u16::MAX as i32).contains(&config.prefetch_count) {
+            return Err(error::Error::BadRequest(format!(
+                "Prefetch count must be between 1 and {}",
+                u16::MAX
+            )));
+        }
+        if config.max_delivery_attempts < 1 {
+            return Err(error::Error::BadRequest(
+                "Max delivery attempts must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn bind_config<'q>(config: &'q AmqpConfig, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&config.amqp_resource_path)
+            .bind(&config.queue_name)
+            .bind(&config.queue_options)
+            .bind(&config.bindings)
+            .bind(config.prefetch_count)
+            .bind(config.max_delivery_attempts)
+            .bind(&config.dead_letter_exchange)
+            .bind(&config.dead_letter_routing_key)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestAmqpConnection {
+    amqp_resource_path: String,
+}
+
+pub async fn test_amqp_connection(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(workspace_id): Path<String>,
+    Json(test_amqp): Json<TestAmqpConnection>,
+) -> error::Result<()> {
+    let amqp_resource = try_get_resource_from_db_as::<AmqpResource>(
+        &authed,
+        Some(user_db),
+        &db,
+        &test_amqp.amqp_resource_path,
+        &workspace_id,
+    )
+    .await?;
+
+    let connect_f = async {
+        let connection = amqp_resource.connect().await?;
+        connection.close(0, "connection test").await?;
+        Ok::<_, Error>(())
+    };
+
+    tokio::time::timeout(tokio::time::Duration::from_secs(30), connect_f)
+        .await
+        .map_err(|_| {
+            error::Error::BadConfig(format!(
+                "Timeout occurred while trying to connect to amqp broker after 30 seconds"
+            ))
+        })?
+        .map_err(|err| {
+            error::Error::BadConfig(format!(
+                "Error connecting to amqp broker: {}",
+                err.to_string()
+            ))
+        })?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishAmqpMessage {
+    amqp_resource_path: String,
+    #[serde(default)]
+    exchange: String,
+    routing_key: String,
+    /// Strings are published as is, any other value as its json serialization
+    payload: Value,
+    content_type: Option<String>,
+    headers: Option<Map<String, Value>>,
+    persistent: Option<bool>,
+    message_id: Option<String>,
+    correlation_id: Option<String>,
+    reply_to: Option<String>,
+}
+
+/// Publishes a message and waits for the broker to confirm it, for scripts to send messages
+/// through the workspace amqp resources without an amqp client of their own
+pub async fn publish_amqp_message(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(workspace_id): Path<String>,
+    Json(message): Json<PublishAmqpMessage>,
+) -> error::Result<String> {
+    let amqp_resource = try_get_resource_from_db_as::<AmqpResource>(
+        &authed,
+        Some(user_db),
+        &db,
+        &message.amqp_resource_path,
+        &workspace_id,
+    )
+    .await?;
+
+    let (payload, default_content_type) = match &message.payload {
+        Value::String(string) => (string.as_bytes().to_vec(), "text/plain"),
+        payload => (serde_json::to_vec(payload)?, "application/json"),
+    };
+
+    let mut properties = BasicProperties::default()
+        .with_content_type(ShortString::from(
+            message
+                .content_type
+                .clone()
+                .unwrap_or_else(|| default_content_type.to_string()),
+        ))
+        .with_delivery_mode(if message.persistent.unwrap_or(true) {
+            2
+        } else {
+            1
+        })
+        .with_app_id(ShortString::from("windmill"));
+    if let Some(headers) = &message.headers {
+        properties = properties.with_headers(json_to_field_table(headers));
+    }
+    if let Some(message_id) = &message.message_id {
+        properties = properties.with_message_id(ShortString::from(message_id.clone()));
+    }
+    if let Some(correlation_id) = &message.correlation_id {
+        properties = properties.with_correlation_id(ShortString::from(correlation_id.clone()));
+    }
+    if let Some(reply_to) = &message.reply_to {
+        properties = properties.with_reply_to(ShortString::from(reply_to.clone()));
+    }
+
+    let publish_f = async {
+        let connection = amqp_resource.connect().await?;
+        let channel = connection.create_channel().await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+        publish_confirmed(
+            &channel,
+            &message.exchange,
+            &message.routing_key,
+            &payload,
+            properties,
+        )
+        .await?;
+        connection.close(0, "message published").await?;
+        Ok::<_, Error>(())
+    };
+
+    tokio::time::timeout(tokio::time::Duration::from_secs(30), publish_f)
+        .await
+        .map_err(|_| {
+            error::Error::BadConfig(format!(
+                "Timeout occurred while trying to publish to amqp broker after 30 seconds"
+            ))
+        })?
+        .map_err(|err| match err {
+            Error::Common(err) => err,
+            err => error::Error::BadConfig(format!(
+                "Error publishing to amqp broker: {}",
+                err.to_string()
+            )),
+        })?;
+
+    Ok(format!(
+        "Message published to exchange '{}' with routing key '{}'",
+        message.exchange, message.routing_key
+    ))
+}
+
+/// Connection to the broker and consumer of the queue of a trigger or a capture. The connection
+/// is closed when the consumption stops as it is dropped along with the channel.
+pub struct AmqpConsumer {
+    _connection: Connection,
+    channel: Channel,
+    consumer: Consumer,
+    queue_name: String,
+}
+
+#[async_trait]
+impl Listener for AmqpTriggerHandler {
+    type CaptureConfig = AmqpTriggerConfig;
+    type Connection = AmqpConsumer;
+    type Error = Error;
+
+    /// Declares the queue, the exchanges and the bindings, then starts consuming the queue.
+    ///
+    /// Captures must not take messages meant for the trigger, so they consume a temporary queue
+    /// with the same bindings when there are some.
+    async fn connect(db: &DB, config: &ListeningConfig<Self>) -> Result<AmqpConsumer, Error> {
+        let (amqp_resource_path, queue_name, queue_options, bindings, prefetch_count) = match config
+        {
+            ListeningConfig::Trigger(trigger) => (
+                &trigger.config.amqp_resource_path,
+                trigger.config.queue_name.clone(),
+                trigger
+                    .config
+                    .queue_options
+                    .as_ref()
+                    .map(|options| options.0.clone())
+                    .unwrap_or_default(),
+                trigger.config.bindings.0.as_slice(),
+                trigger.config.prefetch_count as u16,
+            ),
+            ListeningConfig::Capture(capture) => {
+                let config = &capture.trigger_config;
+                let bindings = config.bindings.as_deref().unwrap_or_default();
+                let (queue_name, queue_options) = if bindings.is_empty() {
+                    (
+                        config.queue_name.clone(),
+                        config.queue_options.clone().unwrap_or_default(),
+                    )
+                } else {
+                    (
+                        String::new(),
+                        AmqpQueueOptions {
+                            durable: false,
+                            exclusive: true,
+                            auto_delete: true,
+                            passive: false,
+                            arguments: None,
+                        },
+                    )
+                };
+                (
+                    &config.amqp_resource_path,
+                    queue_name,
+                    queue_options,
+                    bindings,
+                    DEFAULT_PREFETCH_COUNT as u16,
+                )
+            }
+        };
+
+        let amqp_resource = try_get_resource_from_db_as::<AmqpResource>(
+            &config.fetch_authed(db).await?,
+            Some(UserDB::new(db.clone())),
+            db,
+            amqp_resource_path,
+            config.workspace_id(),
+        )
+        .await?;
+
+        let connection = amqp_resource.connect().await?;
+        let channel = connection.create_channel().await?;
+        channel
+            .basic_qos(prefetch_count, BasicQosOptions::default())
+            .await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+
+        let queue = channel
+            .queue_declare(
+                &queue_name,
+                QueueDeclareOptions {
+                    passive: queue_options.passive,
+                    durable: queue_options.durable,
+                    exclusive: queue_options.exclusive,
+                    auto_delete: queue_options.auto_delete,
+                    nowait: false,
+                },
+                queue_options
+                    .arguments
+                    .as_ref()
+                    .map(json_to_field_table)
+                    .unwrap_or_default(),
+            )
+            .await?;
+        let queue_name = queue.name().to_string();
+
+        for binding in bindings {
+            if let Some(exchange_type) = &binding.exchange_type {
+                channel
+                    .exchange_declare(
+                        &binding.exchange,
+                        exchange_type.into(),
+                        ExchangeDeclareOptions { durable: true, ..Default::default() },
+                        FieldTable::default(),
+                    )
+                    .await?;
+            }
+            channel
+                .queue_bind(
+                    &queue_name,
+                    &binding.exchange,
+                    &binding.routing_key,
+                    QueueBindOptions::default(),
+                    binding
+                        .arguments
+                        .as_ref()
+                        .map(json_to_field_table)
+                        .unwrap_or_default(),
+                )
+                .await?;
+        }
+
+        let consumer = channel
+            .basic_consume(
+                &queue_name,
+                "",
+                BasicConsumeOptions {
+                    no_ack: matches!(config, ListeningConfig::Capture(_)),
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        Ok(AmqpConsumer { _connection: connection, channel, consumer, queue_name })
+    }
+
+    async fn consume(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+        amqp_consumer: AmqpConsumer,
+    ) -> Result<(), Error> {
+        let AmqpConsumer { _connection, channel, consumer, queue_name } = amqp_consumer;
+        let concurrency = match config {
+            ListeningConfig::Trigger(trigger) => trigger.config.prefetch_count as usize,
+            ListeningConfig::Capture(_) => 1,
+        };
+
+        consumer
+            .map_err(Error::from)
+            .try_for_each_concurrent(concurrency, |delivery| {
+                handle_delivery(db, config, &channel, &queue_name, delivery)
+            })
+            .await?;
+
+        Err(error::Error::InternalErr(format!(
+            "Consumer of queue '{}' was cancelled by the broker",
+            queue_name
+        ))
+        .into())
+    }
+}
+
+async fn handle_delivery(
+    db: &DB,
+    config: &ListeningConfig<AmqpTriggerHandler>,
+    channel: &Channel,
+    queue_name: &str,
+    delivery: Delivery,
+) -> Result<(), Error> {
+    let trigger_info = HashMap::from([
+        ("queue".to_string(), to_raw_value(&queue_name)),
+        (
+            "exchange".to_string(),
+            to_raw_value(&delivery.exchange.as_str()),
+        ),
+        (
+            "routing_key".to_string(),
+            to_raw_value(&delivery.routing_key.as_str()),
+        ),
+        (
+            "redelivered".to_string(),
+            to_raw_value(&delivery.redelivered),
+        ),
+        (
+            "delivery_tag".to_string(),
+            to_raw_value(&delivery.delivery_tag),
+        ),
+        (
+            "properties".to_string(),
+            to_raw_value(&properties_to_json(&delivery.properties)),
+        ),
+    ]);
+
+    match config {
+        ListeningConfig::Trigger(trigger) => {
+            match run_job(&delivery.data, trigger_info, db, trigger).await {
+                // the message is only removed from the queue once its job is queued
+                Ok(()) => delivery.acker.ack(BasicAckOptions::default()).await?,
+                Err(err) => {
+                    handle_failed_delivery(db, trigger, channel, queue_name, delivery, err).await?
+                }
+            }
+        }
+        ListeningConfig::Capture(capture) => {
+            let (main_args, preprocessor_args) =
+                AmqpTriggerHandler::build_capture_payloads(delivery.data.as_slice(), trigger_info);
+            capture
+                .insert_payload(db, &TriggerKind::Amqp, main_args, preprocessor_args)
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Publishes the message back to its queue to be delivered again, or dead-letters it once it
+/// has been attempted `max_delivery_attempts` times. The original message is only acked once
+/// the broker confirmed the new one.
+async fn handle_failed_delivery(
+    db: &DB,
+    trigger: &AmqpTrigger,
+    channel: &Channel,
+    queue_name: &str,
+    delivery: Delivery,
+    err: anyhow::Error,
+) -> Result<(), Error> {
+    let attempts = delivery_attempts(&delivery.properties) + 1;
+
+    if attempts < trigger.config.max_delivery_attempts as u64 {
+        tracing::warn!(
+            "Failed to trigger job from amqp trigger {} (attempt {}/{}), retrying: {:?}",
+            trigger.base.path,
+            attempts,
+            trigger.config.max_delivery_attempts,
+            err
+        );
+        tokio::time::sleep(RETRY_DELAY * attempts as u32).await;
+        let properties = with_headers(
+            &delivery.properties,
+            vec![(
+                DELIVERY_ATTEMPTS_HEADER,
+                AMQPValue::LongLongInt(attempts as i64),
+            )],
+        );
+        publish_confirmed(channel, "", queue_name, &delivery.data, properties).await?;
+        delivery.acker.ack(BasicAckOptions::default()).await?;
+        return Ok(());
+    }
+
+    report_critical_error(
+        format!(
+            "Failed to trigger job from amqp trigger {} after {} attempts, dead-lettering message: {:?}",
+            trigger.base.path, attempts, err
+        ),
+        db.clone(),
+        Some(&trigger.base.workspace_id),
+        None,
+    )
+    .await;
+
+    match &trigger.config.dead_letter_exchange {
+        Some(dead_letter_exchange) => {
+            let properties = with_headers(
+                &delivery.properties,
+                vec![
+                    (
+                        DELIVERY_ATTEMPTS_HEADER,
+                        AMQPValue::LongLongInt(attempts as i64),
+                    ),
+                    (
+                        "x-windmill-error",
+                        AMQPValue::LongString(LongString::from(err.to_string())),
+                    ),
+                    (
+                        "x-windmill-trigger-path",
+                        AMQPValue::LongString(LongString::from(trigger.base.path.clone())),
+                    ),
+                    (
+                        "x-windmill-original-exchange",
+                        AMQPValue::LongString(LongString::from(delivery.exchange.to_string())),
+                    ),
+                    (
+                        "x-windmill-original-routing-key",
+                        AMQPValue::LongString(LongString::from(delivery.routing_key.to_string())),
+                    ),
+                ],
+            );
+            let routing_key = trigger
+                .config
+                .dead_letter_routing_key
+                .as_deref()
+                .unwrap_or(delivery.routing_key.as_str());
+            publish_confirmed(
+                channel,
+                dead_letter_exchange,
+                routing_key,
+                &delivery.data,
+                properties,
+            )
+            .await?;
+            delivery.acker.ack(BasicAckOptions::default()).await?;
+        }
+        None => {
+            // dead-lettered by the broker if the queue has a dead letter exchange, dropped otherwise
+            delivery
+                .acker
+                .nack(BasicNackOptions { requeue: false, multiple: false })
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+impl TriggerJobArgs<&[u8]> for AmqpTriggerHandler {
+    fn v1_payload_fn(payload: &[u8]) -> HashMap<String, Box<RawValue>> {
+        HashMap::from([("payload".to_string(), to_raw_value(&payload))])
+    }
+
+    fn v2_payload_fn(payload: &[u8]) -> HashMap<String, Box<RawValue>> {
+        let base64_payload = engine::general_purpose::STANDARD.encode(payload);
+        HashMap::from([("payload".to_string(), to_raw_value(&base64_payload))])
+    }
+
+    fn trigger_kind() -> TriggerKind {
+        TriggerKind::Amqp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_amqp_values() {
+        let value = json!({
+            "x-queue-type": "quorum",
+            "x-max-length": 1000,
+            "x-ratio": 0.5,
+            "x-lazy": true,
+            "x-none": null,
+            "x-list": ["a", 1]
+        });
+        let table = json_to_field_table(value.as_object().unwrap());
+        assert!(matches!(
+            table.inner().get(&ShortString::from("x-max-length")),
+            Some(AMQPValue::LongLongInt(1000))
+        ));
+        assert_eq!(field_table_to_json(&table), value);
+
+        // strings that are not valid utf-8 are base64 encoded
+        assert_eq!(
+            amqp_value_to_json(&AMQPValue::LongString(LongString::from(vec![0xff, 0xfe]))),
+            json!("//4=")
+        );
+        assert_eq!(
+            amqp_value_to_json(&AMQPValue::ShortString(ShortString::from("direct"))),
+            json!("direct")
+        );
+    }
+
+    #[test]
+    fn test_delivery_attempts() {
+        let properties =
+            BasicProperties::default().with_content_type(ShortString::from("text/plain"));
+        assert_eq!(delivery_attempts(&properties), 0);
+
+        let properties = with_headers(
+            &properties,
+            vec![
+                (DELIVERY_ATTEMPTS_HEADER, AMQPValue::LongLongInt(2)),
+                ("x-tenant", AMQPValue::LongString(LongString::from("acme"))),
+            ],
+        );
+        assert_eq!(delivery_attempts(&properties), 2);
+
+        // the headers of the message are kept along with the attempts of the retries
+        let properties = with_headers(
+            &properties,
+            vec![(DELIVERY_ATTEMPTS_HEADER, AMQPValue::LongLongInt(3))],
+        );
+        assert_eq!(delivery_attempts(&properties), 3);
+        let properties = properties_to_json(&properties);
+        assert_eq!(properties["content_type"], "text/plain");
+        assert_eq!(
+            properties["headers"],
+            json!({DELIVERY_ATTEMPTS_HEADER: 3, "x-tenant": "acme"})
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let config: AmqpConfig = serde_json::from_value(json!({
+            "amqp_resource_path": "f/orders/amqp",
+            "queue_name": "orders",
+            "bindings": [{"exchange": "events", "routing_key": "orders.*", "exchange_type": "topic"}]
+        }))
+        .unwrap();
+        assert_eq!(config.prefetch_count, DEFAULT_PREFETCH_COUNT);
+        assert_eq!(config.max_delivery_attempts, DEFAULT_MAX_DELIVERY_ATTEMPTS);
+        assert!(config.queue_options.is_none());
+        assert!(matches!(
+            config.bindings.0[0].exchange_type,
+            Some(AmqpExchangeType::Topic)
+        ));
+        assert!(AmqpTriggerHandler::validate_config(&config).is_ok());
+
+        let options: AmqpQueueOptions = serde_json::from_value(json!({"exclusive": true})).unwrap();
+        assert!(options.durable && options.exclusive && !options.auto_delete);
+
+        for invalid in [
+            json!({"queue_name": " "}),
+            json!({"prefetch_count": 0}),
+            json!({"prefetch_count": 65536}),
+            json!({"max_delivery_attempts": 0}),
+        ] {
+            let mut config = json!({
+                "amqp_resource_path": "f/orders/amqp",
+                "queue_name": "orders"
+            });
+            config
+                .as_object_mut()
+                .unwrap()
+                .extend(invalid.as_object().unwrap().clone());
+            let config: AmqpConfig = serde_json::from_value(config).unwrap();
+            assert!(AmqpTriggerHandler::validate_config(&config).is_err());
+        }
+    }
+}
@@ -65,6 +65,7 @@ use windmill_common::{
     },
     variables::{build_crypt, build_crypt_with_key_suffix, encrypt},
     worker::{to_raw_value, CLOUD_HOSTED},
+    workspaces::{fork_item_workspace, ForkedItemKind},
     HUB_BASE_URL,
 };
 
@@ -440,11 +441,13 @@ async fn get_raw_app_data(Path((w_id, version_id)): Path<(String, String)>) -> R
 async fn get_app(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
     Path((w_id, path)): Path<(String, StripPath)>,
     Query(query): Query<WithStarredInfoQuery>,
 ) -> JsonResult<AppWithLastVersionAndStarred> {
     let path = path.to_path();
     check_scopes(&authed, || format!("apps:read:{}", path))?;
+    let w_id = fork_item_workspace(&db, &w_id, ForkedItemKind::App, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let app_o = if query.with_starred_info.unwrap_or(false) {
@@ -51,6 +51,9 @@ use crate::kafka_triggers_oss::KafkaTriggerConfigConnection;
 #[cfg(feature = "mqtt_trigger")]
 use crate::mqtt_triggers::{MqttClientVersion, MqttV3Config, MqttV5Config, SubscribeTopic};
 
+#[cfg(feature = "amqp_trigger")]
+use crate::amqp_triggers::{AmqpBinding, AmqpQueueOptions};
+
 #[cfg(all(feature = "enterprise", feature = "nats"))]
 use crate::nats_triggers_oss::NatsTriggerConfigConnection;
 
@@ -201,6 +204,41 @@ pub struct MqttTriggerConfig {
     pub client_version: Option<MqttClientVersion>,
     pub client_id: Option<String>,
 }
+
+#[cfg(feature = "mysql_trigger")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MysqlTriggerConfig {
+    pub mysql_resource_path: String,
+    pub include_tables: Option<Vec<String>>,
+    pub exclude_tables: Option<Vec<String>>,
+}
+
+#[cfg(feature = "amqp_trigger")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmqpTriggerConfig {
+    pub amqp_resource_path: String,
+    pub queue_name: String,
+    pub queue_options: Option<AmqpQueueOptions>,
+    pub bindings: Option<Vec<AmqpBinding>>,
+}
+
+#[cfg(feature = "redis_trigger")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedisTriggerConfig {
+    pub redis_resource_path: String,
+    pub streams: Vec<String>,
+    pub consumer_group: String,
+}
+
+#[cfg(feature = "s3_trigger")]
+#[derive(Debug, Serialize, Deserialize)]
+pub struct S3TriggerConfig {
+    pub storage: Option<String>,
+    pub prefix: String,
+    pub include_patterns: Option<Vec<String>>,
+    pub exclude_patterns: Option<Vec<String>>,
+}
+
 #[cfg(feature = "postgres_trigger")]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PostgresTriggerConfig {
@@ -238,8 +276,16 @@ enum TriggerConfig {
     Nats(NatsTriggerConfig),
     #[cfg(feature = "mqtt_trigger")]
     Mqtt(MqttTriggerConfig),
+    #[cfg(feature = "mysql_trigger")]
+    Mysql(MysqlTriggerConfig),
+    #[cfg(feature = "amqp_trigger")]
+    Amqp(AmqpTriggerConfig),
+    #[cfg(feature = "redis_trigger")]
+    Redis(RedisTriggerConfig),
     #[cfg(all(feature = "enterprise", feature = "gcp_trigger"))]
     Gcp(GcpTriggerConfig),
+    #[cfg(feature = "s3_trigger")]
+    S3(S3TriggerConfig),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -907,7 +953,7 @@ async fn gcp_payload(
     headers: HeaderMap,
     request: Request,
 ) -> Result<StatusCode> {
-    use crate::{gcp_triggers_oss::GcpTrigger, trigger_helpers::TriggerJobArgs};
+    use crate::{gcp_triggers_oss::GcpTriggerHandler, trigger_helpers::TriggerJobArgs};
 
     let is_flow = matches!(runnable_kind, RunnableKind::Flow);
     let (gcp_trigger_config, owner, email): (GcpTriggerConfig, _, _) =
@@ -932,7 +978,7 @@ async fn gcp_payload(
 
     let (payload, gcp) = process_google_push_request(headers, request).await?;
 
-    let (main_args, preprocessor_args) = GcpTrigger::build_capture_payloads(payload, gcp);
+    let (main_args, preprocessor_args) = GcpTriggerHandler::build_capture_payloads(payload, gcp);
 
     let _ = insert_capture_payload(
         &db,
@@ -11,7 +11,7 @@ use axum::Router;
 
 #[cfg(feature = "embedding")]
 use axum::{
-    extract::{Path, Query},
+    extract::{Extension, Path, Query},
     Json,
 };
 
@@ -44,18 +44,28 @@ use tokio::sync::RwLock;
 use windmill_common::utils::http_get_from_hub;
 
 #[cfg(feature = "embedding")]
-use windmill_common::error::JsonResult;
+use windmill_common::{db::UserDB, error::JsonResult};
 
 #[cfg(feature = "embedding")]
-use crate::{resources::ResourceType, HTTP_CLIENT};
+use crate::{db::ApiAuthed, resources::ResourceType, HTTP_CLIENT};
 
 #[cfg(feature = "embedding")]
 lazy_static::lazy_static! {
     pub static ref EMBEDDINGS_DB: Arc<RwLock<Option<EmbeddingsDb>>> = Arc::new(RwLock::new(None));
     pub static ref MODEL_INSTANCE: Arc<RwLock<Option<Arc<ModelInstance>>>> = Arc::new(RwLock::new(None));
     pub static ref HUB_EMBEDDINGS_PULLING_INTERVAL_SECS: u64 = std::env::var("HUB_EMBEDDINGS_PULLING_INTERVAL_SECS").ok().map(|x| x.parse::<u64>().ok()).flatten().unwrap_or(3600 * 24);
+    pub static ref WORKSPACE_EMBEDDINGS_QUEUE_INTERVAL_SECS: u64 = std::env::var("WORKSPACE_EMBEDDINGS_QUEUE_INTERVAL_SECS").ok().map(|x| x.parse::<u64>().ok()).flatten().unwrap_or(10);
+    /// Local directory with the `config.json`, `tokenizer.json` and `model.safetensors` of the model,
+    /// for instances that cannot reach hugging face
+    pub static ref EMBEDDING_MODEL_PATH: Option<String> = std::env::var("EMBEDDING_MODEL_PATH").ok();
+    /// Name of the model, the embeddings of the hub are only used with the model they were computed with.
+    /// A local model is named after its path unless it is named explicitly
+    pub static ref EMBEDDING_MODEL: String = std::env::var("EMBEDDING_MODEL").ok().or_else(|| EMBEDDING_MODEL_PATH.clone()).unwrap_or_else(|| DEFAULT_EMBEDDING_MODEL.to_string());
 }
 
+#[cfg(feature = "embedding")]
+const DEFAULT_EMBEDDING_MODEL: &str = "thenlper/gte-small";
+
 #[cfg(feature = "embedding")]
 #[derive(Deserialize)]
 struct HubScriptsQuery {
@@ -153,13 +163,29 @@ struct HubResourceType {
 pub struct ModelInstance {
     model: BertModel,
     tokenizer: Tokenizer,
+    dimension: usize,
 }
 
 #[cfg(feature = "embedding")]
 impl ModelInstance {
     pub async fn load_model_files() -> Result<(PathBuf, PathBuf, PathBuf)> {
+        if let Some(model_path) = EMBEDDING_MODEL_PATH.as_ref() {
+            let model_path = PathBuf::from(model_path);
+            let files = (
+                model_path.join("config.json"),
+                model_path.join("tokenizer.json"),
+                model_path.join("model.safetensors"),
+            );
+            for file in [&files.0, &files.1, &files.2] {
+                if !file.exists() {
+                    return Err(anyhow!("Embedding model file {} not found", file.display()));
+                }
+            }
+            return Ok(files);
+        }
+
         let api = Api::new()?;
-        let repo_api = api.model("thenlper/gte-small".to_string());
+        let repo_api = api.model(EMBEDDING_MODEL.clone());
 
         let (config_filename, tokenizer_filename, weights_filename) =
             (
@@ -195,32 +221,44 @@ impl ModelInstance {
         let vb =
             unsafe { VarBuilder::from_mmaped_safetensors(&[weights_filename], DTYPE, &device)? };
         let model = BertModel::load(vb, &config)?;
-        tracing::info!("Loaded embedding model");
-        Ok(Self { model, tokenizer })
+        let mut model_instance = Self { model, tokenizer, dimension: 0 };
+        model_instance.dimension = model_instance.embed("dimension")?.len();
+        tracing::info!(
+            "Loaded embedding model {} ({} dimensions)",
+            *EMBEDDING_MODEL,
+            model_instance.dimension
+        );
+        Ok(model_instance)
     }
 
-    pub async fn create_embedding(self: Arc<Self>, sentence: &str) -> Result<Vec<f32>> {
-        let sentence = sentence.to_owned();
-        tokio::task::spawn_blocking(move || {
-            let tokens = self
-                .tokenizer
-                .encode(sentence, true)
-                .map_err(Error::msg)?
-                .get_ids()
-                .to_vec();
+    /// The embeddings of the hub were computed with the default model
+    fn uses_hub_embeddings(&self) -> bool {
+        EMBEDDING_MODEL.as_str() == DEFAULT_EMBEDDING_MODEL
+    }
 
-            let token_ids = Tensor::new(&tokens[..], &Device::Cpu)?.unsqueeze(0)?;
-            let token_type_ids = token_ids.zeros_like()?;
+    fn embed(&self, sentence: &str) -> Result<Vec<f32>> {
+        let tokens = self
+            .tokenizer
+            .encode(sentence, true)
+            .map_err(Error::msg)?
+            .get_ids()
+            .to_vec();
 
-            let embedding = self.model.forward(&token_ids, &token_type_ids, None)?;
-            let embedding = (embedding.sum(1)? / embedding.dim(1)? as f64)?;
-            let embedding = normalize_l2(&embedding)?;
+        let token_ids = Tensor::new(&tokens[..], &Device::Cpu)?.unsqueeze(0)?;
+        let token_type_ids = token_ids.zeros_like()?;
 
-            let embedding = embedding.get(0)?.to_vec1()?;
+        let embedding = self.model.forward(&token_ids, &token_type_ids, None)?;
+        let embedding = (embedding.sum(1)? / embedding.dim(1)? as f64)?;
+        let embedding = normalize_l2(&embedding)?;
 
-            Ok(embedding)
-        })
-        .await?
+        let embedding = embedding.get(0)?.to_vec1()?;
+
+        Ok(embedding)
+    }
+
+    pub async fn create_embedding(self: Arc<Self>, sentence: &str) -> Result<Vec<f32>> {
+        let sentence = sentence.to_owned();
+        tokio::task::spawn_blocking(move || self.embed(&sentence)).await?
     }
 }
 
@@ -242,35 +280,29 @@ impl EmbeddingsDb {
         Ok(embeddings_db)
     }
 
-    async fn fill_db(&mut self, pg_db: &Pool<Postgres>) -> Result<()> {
-        if self.db.get_collection("scripts").is_some() {
-            self.db.delete_collection("scripts")?;
-        }
-
-        self.db
-            .create_collection("scripts".to_string(), 384, Distance::Cosine)?;
-
-        if self.db.get_collection("resource_types").is_some() {
-            self.db.delete_collection("resource_types")?;
-        }
-
-        self.db
-            .create_collection("resource_types".to_string(), 384, Distance::Cosine)?;
-
+    async fn fetch_hub_embeddings<T: serde::de::DeserializeOwned>(
+        pg_db: &Pool<Postgres>,
+        bucket_file: &str,
+        hub_route: &str,
+    ) -> Result<Vec<T>> {
         let hub_base_url = HUB_BASE_URL.read().await.clone();
 
         let response = match hub_base_url.as_str() {
             DEFAULT_HUB_BASE_URL => {
                 let response = HTTP_CLIENT
-                    .get("https://bucket.windmillhub.com/embeddings/scripts_embeddings.json")
+                    .get(format!(
+                        "https://bucket.windmillhub.com/embeddings/{bucket_file}"
+                    ))
                     .send()
                     .await;
 
                 if response.is_err() || response.as_ref().unwrap().error_for_status_ref().is_err() {
-                    tracing::warn!("Failed to get scripts embeddings from bucket, trying hub...");
+                    tracing::warn!(
+                        "Failed to get {hub_route} embeddings from bucket, trying hub..."
+                    );
                     http_get_from_hub(
                         &HTTP_CLIENT,
-                        &format!("{}/scripts/embeddings", hub_base_url),
+                        &format!("{}/{hub_route}/embeddings", hub_base_url),
                         false,
                         None,
                         Some(pg_db),
@@ -283,7 +315,7 @@ impl EmbeddingsDb {
             _ => {
                 http_get_from_hub(
                     &HTTP_CLIENT,
-                    &format!("{}/scripts/embeddings", hub_base_url),
+                    &format!("{}/{hub_route}/embeddings", hub_base_url),
                     false,
                     None,
                     Some(pg_db),
@@ -294,12 +326,42 @@ impl EmbeddingsDb {
 
         if response.error_for_status_ref().is_err() {
             return Err(anyhow!(
-                "Failed to get scripts embeddings from hub with error code: {}",
+                "Failed to get {hub_route} embeddings from hub with error code: {}",
                 response.status()
             ));
         }
 
-        let hub_scripts = response.json::<Vec<HubScript>>().await?;
+        Ok(response.json::<Vec<T>>().await?)
+    }
+
+    async fn fill_db(&mut self, pg_db: &Pool<Postgres>) -> Result<()> {
+        let dimension = self.model_instance.dimension;
+        if self.db.get_collection("scripts").is_some() {
+            self.db.delete_collection("scripts")?;
+        }
+
+        self.db
+            .create_collection("scripts".to_string(), dimension, Distance::Cosine)?;
+
+        if self.db.get_collection("resource_types").is_some() {
+            self.db.delete_collection("resource_types")?;
+        }
+
+        self.db
+            .create_collection("resource_types".to_string(), dimension, Distance::Cosine)?;
+
+        // without the hub, e.g. air-gapped, the resource types are embedded with the local model
+        let uses_hub_embeddings = self.model_instance.uses_hub_embeddings();
+        let hub_scripts = if uses_hub_embeddings {
+            Self::fetch_hub_embeddings::<HubScript>(pg_db, "scripts_embeddings.json", "scripts")
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::warn!("Hub scripts will not be searchable: {e:#}");
+                    vec![]
+                })
+        } else {
+            vec![]
+        };
 
         for script in &hub_scripts {
             let mut hm = HashMap::new();
@@ -320,48 +382,21 @@ impl EmbeddingsDb {
             self.db.insert_into_collection("scripts", embedding)?;
         }
 
-        let response = match hub_base_url.as_str() {
-            DEFAULT_HUB_BASE_URL => {
-                let response = HTTP_CLIENT
-                    .get("https://bucket.windmillhub.com/embeddings/resource_types_embeddings.json")
-                    .send()
-                    .await;
-                if response.is_err() || response.as_ref().unwrap().error_for_status_ref().is_err() {
-                    tracing::warn!(
-                        "Failed to get resource types embeddings from bucket, trying hub..."
-                    );
-                    http_get_from_hub(
-                        &HTTP_CLIENT,
-                        &format!("{}/resource_types/embeddings", hub_base_url),
-                        false,
-                        None,
-                        Some(pg_db),
-                    )
-                    .await?
-                } else {
-                    response.unwrap()
-                }
-            }
-            _ => {
-                http_get_from_hub(
-                    &HTTP_CLIENT,
-                    &format!("{}/resource_types/embeddings", hub_base_url),
-                    false,
-                    None,
-                    Some(pg_db),
-                )
-                .await?
-            }
+        let hub_resource_types = if uses_hub_embeddings {
+            Self::fetch_hub_embeddings::<HubResourceType>(
+                pg_db,
+                "resource_types_embeddings.json",
+                "resource_types",
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Resource types will be embedded locally: {e:#}");
+                vec![]
+            })
+        } else {
+            vec![]
         };
 
-        if response.error_for_status_ref().is_err() {
-            return Err(anyhow!(
-                "Failed to get resource types embeddings from hub with error code: {}",
-                response.status()
-            ));
-        }
-        let hub_resource_types = response.json::<Vec<HubResourceType>>().await?;
-
         let resource_types: Vec<ResourceType> =
             sqlx::query_as!(ResourceType, "SELECT * from resource_type ORDER BY name",)
                 .fetch_all(pg_db)
@@ -545,6 +580,281 @@ impl EmbeddingsDb {
     }
 }
 
+#[cfg(feature = "embedding")]
+struct WorkspaceItem {
+    summary: String,
+    text: String,
+}
+
+#[cfg(feature = "embedding")]
+impl WorkspaceItem {
+    fn text_hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+        hex::encode(Sha256::digest(self.text.as_bytes()))
+    }
+}
+
+/// Script, flow, app or resource with the text it is searched by, `None` if it was removed
+#[cfg(feature = "embedding")]
+async fn fetch_workspace_item(
+    db: &Pool<Postgres>,
+    w_id: &str,
+    kind: &str,
+    path: &str,
+) -> Result<Option<WorkspaceItem>> {
+    let item = match kind {
+        "script" => sqlx::query!(
+            "SELECT summary, description FROM script
+            WHERE workspace_id = $1 AND path = $2 AND archived = false AND deleted = false
+            ORDER BY created_at DESC LIMIT 1",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|s| WorkspaceItem {
+            text: format!("{};{};{}", path, s.summary, s.description),
+            summary: s.summary,
+        }),
+        "flow" => sqlx::query!(
+            "SELECT summary, description FROM flow
+            WHERE workspace_id = $1 AND path = $2 AND archived = false",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|f| WorkspaceItem {
+            text: format!("{};{};{}", path, f.summary, f.description),
+            summary: f.summary,
+        }),
+        "app" => sqlx::query!(
+            "SELECT summary FROM app WHERE workspace_id = $1 AND path = $2",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|a| WorkspaceItem { text: format!("{};{}", path, a.summary), summary: a.summary }),
+        "resource" => sqlx::query!(
+            "SELECT resource_type, description FROM resource WHERE workspace_id = $1 AND path = $2",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|r| {
+            let description = r.description.unwrap_or_default();
+            WorkspaceItem {
+                text: format!("{};{};{}", path, r.resource_type, description),
+                summary: description,
+            }
+        }),
+        _ => None,
+    };
+    Ok(item)
+}
+
+/// Queues the items that have no embedding with the current model, e.g. the ones deployed before
+/// the model was changed
+#[cfg(feature = "embedding")]
+async fn queue_missing_embeddings(db: &Pool<Postgres>) -> Result<()> {
+    let queued = sqlx::query!(
+        "INSERT INTO workspace_embedding_queue (workspace_id, kind, path)
+        SELECT DISTINCT i.workspace_id, i.kind, i.path FROM (
+            SELECT workspace_id, 'script' AS kind, path FROM script
+                WHERE archived = false AND deleted = false
+            UNION ALL SELECT workspace_id, 'flow', path FROM flow WHERE archived = false
+            UNION ALL SELECT workspace_id, 'app', path FROM app
+            UNION ALL SELECT workspace_id, 'resource', path FROM resource
+        ) i
+        WHERE NOT EXISTS (
+            SELECT 1 FROM workspace_embedding e
+            WHERE e.workspace_id = i.workspace_id AND e.kind = i.kind AND e.path = i.path
+            AND e.model = $1
+        )
+        ON CONFLICT (workspace_id, kind, path) DO NOTHING",
+        EMBEDDING_MODEL.as_str()
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    if queued > 0 {
+        tracing::info!("Queued {queued} workspace items without embedding");
+    }
+    Ok(())
+}
+
+/// Embeds a batch of the queued workspace items, and removes the embeddings of the removed ones.
+/// The batch is locked until it is embedded so that servers do not embed the same items.
+#[cfg(feature = "embedding")]
+async fn embed_queued_items(
+    db: &Pool<Postgres>,
+    model_instance: Arc<ModelInstance>,
+) -> Result<usize> {
+    let mut tx = db.begin().await?;
+    let queued = sqlx::query!(
+        "SELECT workspace_id, kind, path FROM workspace_embedding_queue
+        ORDER BY queued_at LIMIT 100 FOR UPDATE SKIP LOCKED"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for q in &queued {
+        match fetch_workspace_item(db, &q.workspace_id, &q.kind, &q.path).await? {
+            Some(item) => {
+                let text_hash = item.text_hash();
+                let stored_hash = sqlx::query_scalar!(
+                    "SELECT text_hash FROM workspace_embedding
+                    WHERE workspace_id = $1 AND kind = $2 AND path = $3 AND model = $4",
+                    q.workspace_id,
+                    q.kind,
+                    q.path,
+                    EMBEDDING_MODEL.as_str()
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+                if stored_hash.as_ref() != Some(&text_hash) {
+                    match model_instance.clone().create_embedding(&item.text).await {
+                        Ok(vector) => {
+                            sqlx::query!(
+                                "INSERT INTO workspace_embedding
+                                    (workspace_id, kind, path, model, summary, text_hash, embedding)
+                                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                                ON CONFLICT (workspace_id, kind, path, model) DO UPDATE
+                                SET summary = $5, text_hash = $6, embedding = $7, updated_at = now()",
+                                q.workspace_id,
+                                q.kind,
+                                q.path,
+                                EMBEDDING_MODEL.as_str(),
+                                item.summary,
+                                text_hash,
+                                &vector
+                            )
+                            .execute(&mut *tx)
+                            .await?;
+                        }
+                        Err(e) => tracing::error!(
+                            "Could not embed {} {} of workspace {}: {e:#}",
+                            q.kind,
+                            q.path,
+                            q.workspace_id
+                        ),
+                    }
+                }
+            }
+            None => {
+                sqlx::query!(
+                    "DELETE FROM workspace_embedding
+                    WHERE workspace_id = $1 AND kind = $2 AND path = $3",
+                    q.workspace_id,
+                    q.kind,
+                    q.path
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        sqlx::query!(
+            "DELETE FROM workspace_embedding_queue
+            WHERE workspace_id = $1 AND kind = $2 AND path = $3",
+            q.workspace_id,
+            q.kind,
+            q.path
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(queued.len())
+}
+
+#[cfg(feature = "embedding")]
+async fn embed_workspace_items(db: &Pool<Postgres>, model_instance: Arc<ModelInstance>) {
+    if let Err(e) = queue_missing_embeddings(db).await {
+        tracing::error!("Failed to queue the workspace items without embedding: {e:#}");
+    }
+    loop {
+        match embed_queued_items(db, model_instance.clone()).await {
+            // the queue may not be empty yet
+            Ok(nb_embedded) if nb_embedded > 0 => continue,
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to embed the queued workspace items: {e:#}"),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(
+            *WORKSPACE_EMBEDDINGS_QUEUE_INTERVAL_SECS,
+        ))
+        .await;
+    }
+}
+
+#[cfg(feature = "embedding")]
+#[derive(Deserialize)]
+struct WorkspaceItemsQuery {
+    text: String,
+    limit: Option<i64>,
+    kind: Option<String>,
+}
+
+#[cfg(feature = "embedding")]
+#[derive(Serialize)]
+pub struct WorkspaceItemResult {
+    kind: String,
+    path: String,
+    summary: String,
+    score: f32,
+}
+
+/// Items of the workspace closest to the text, among the ones the user can see
+#[cfg(feature = "embedding")]
+async fn query_workspace_items(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<WorkspaceItemsQuery>,
+) -> JsonResult<Vec<WorkspaceItemResult>> {
+    let model_instance = MODEL_INSTANCE.read().await.clone().ok_or_else(|| {
+        windmill_common::error::Error::internal_err("Embedding model not initialized".to_string())
+    })?;
+    let query_embedding = model_instance.create_embedding(&query.text).await?;
+
+    // the embeddings are normalized, their dot product is their cosine similarity. The items are
+    // checked against the tables they come from in the user transaction so that only the ones
+    // visible to the user are returned
+    let mut tx = user_db.begin(&authed).await?;
+    let items = sqlx::query_as!(
+        WorkspaceItemResult,
+        r#"SELECT kind AS "kind!", path AS "path!", summary AS "summary!", score AS "score!" FROM (
+            SELECT e.kind, e.path, e.summary,
+                COALESCE((SELECT SUM(a * b) FROM unnest(e.embedding, $3::real[]) AS v(a, b)), 0)::real AS score
+            FROM workspace_embedding e
+            WHERE e.workspace_id = $1 AND e.model = $2 AND ($4::text IS NULL OR e.kind = $4)
+            AND CASE e.kind
+                WHEN 'script' THEN EXISTS (SELECT 1 FROM script s
+                    WHERE s.workspace_id = $1 AND s.path = e.path AND s.archived = false AND s.deleted = false)
+                WHEN 'flow' THEN EXISTS (SELECT 1 FROM flow f
+                    WHERE f.workspace_id = $1 AND f.path = e.path AND f.archived = false)
+                WHEN 'app' THEN EXISTS (SELECT 1 FROM app a WHERE a.workspace_id = $1 AND a.path = e.path)
+                WHEN 'resource' THEN EXISTS (SELECT 1 FROM resource r
+                    WHERE r.workspace_id = $1 AND r.path = e.path)
+                ELSE false
+            END
+        ) items
+        ORDER BY score DESC
+        LIMIT $5"#,
+        w_id,
+        EMBEDDING_MODEL.as_str(),
+        &query_embedding,
+        query.kind,
+        query.limit.unwrap_or(10).min(100)
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(items))
+}
+
 #[cfg(feature = "embedding")]
 fn normalize_l2(v: &Tensor) -> Result<Tensor> {
     Ok(v.broadcast_div(&v.sqr()?.sum_keepdim(1)?.sqrt()?)?)
@@ -562,9 +872,14 @@ pub fn load_embeddings_db(db: &Pool<Postgres>) -> () {
         tokio::spawn(async move {
             let model_instance = ModelInstance::new().await;
             if let Ok(model_instance) = model_instance {
+                let model_instance = Arc::new(model_instance);
                 let mut model_instance_lock = MODEL_INSTANCE.write().await;
-                *model_instance_lock = Some(Arc::new(model_instance));
+                *model_instance_lock = Some(model_instance.clone());
                 drop(model_instance_lock);
+                let db_clone_2 = db_clone.clone();
+                tokio::spawn(async move {
+                    embed_workspace_items(&db_clone_2, model_instance).await;
+                });
                 loop {
                     update_embeddings_db(&db_clone).await;
                     tokio::time::sleep(std::time::Duration::from_secs(
@@ -601,7 +916,9 @@ pub async fn update_embeddings_db(db: &Pool<Postgres>) -> () {
 
 #[cfg(feature = "embedding")]
 pub fn workspaced_service() -> Router {
-    Router::new().route("/query_resource_types", get(query_resource_types))
+    Router::new()
+        .route("/query_resource_types", get(query_resource_types))
+        .route("/query_workspace_items", get(query_workspace_items))
 }
 
 #[cfg(feature = "embedding")]
@@ -0,0 +1,745 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Environments of a workspace and the per-environment values of its variables and resources.
+//! Reading an overridden variable or resource requires the permission to read the variable or
+//! resource itself, managing environments and overrides requires to be admin.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    auth::JWTAuthClaims,
+    environments::get_job_environment,
+    error::{Error, JsonResult, Result},
+    jwt,
+    utils::{not_found_if_none, require_admin, StripPath},
+    variables::{build_crypt, decrypt, encrypt},
+};
+use windmill_queue::JobTriggerKind;
+
+use crate::db::{ApiAuthed, DB};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/list", get(list_environments))
+        .route("/create", post(create_environment))
+        .route("/update/:name", post(update_environment))
+        .route("/delete/:name", delete(delete_environment))
+        .route("/list_overrides", get(list_overrides))
+        .route("/set_override/:kind/:name/*path", post(set_override))
+        .route(
+            "/delete_override/:kind/:name/*path",
+            delete(delete_override),
+        )
+        .route("/list_bindings", get(list_bindings))
+        .route("/bind", post(bind))
+        .route("/unbind/:kind/*path", delete(unbind))
+        .route("/job/:id", get(get_environment_of_job))
+}
+
+#[derive(Serialize)]
+struct Environment {
+    name: String,
+    description: Option<String>,
+    worker_tags: Vec<String>,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+struct NewEnvironment {
+    name: String,
+    description: Option<String>,
+    #[serde(default)]
+    worker_tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct EditEnvironment {
+    description: Option<String>,
+    worker_tags: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideKind {
+    Variable,
+    Resource,
+}
+
+#[derive(Serialize)]
+struct Override {
+    kind: OverrideKind,
+    path: String,
+    environment: String,
+    /// `None` for the values of secret variables
+    value: Option<Value>,
+    edited_by: String,
+    edited_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+struct ListOverridesQuery {
+    environment: Option<String>,
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SetOverride {
+    value: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Binding {
+    kind: JobTriggerKind,
+    path: String,
+    environment: String,
+}
+
+/// Environment of the job a request is made for: the job given explicitly, or else the job the
+/// token of the request was issued to
+pub async fn get_request_environment(
+    db: &DB,
+    w_id: &str,
+    job_id: Option<Uuid>,
+    token: &str,
+) -> Result<Option<String>> {
+    let job_id = match job_id {
+        Some(job_id) => Some(job_id),
+        None => get_token_job(db, token).await?,
+    };
+    match job_id {
+        Some(job_id) => get_job_environment(db, w_id, &job_id).await,
+        None => Ok(None),
+    }
+}
+
+async fn get_token_job(db: &DB, token: &str) -> Result<Option<Uuid>> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+    if let Some(jwt_token) = token.strip_prefix("jwt_") {
+        let claims = jwt::decode_with_internal_secret::<JWTAuthClaims>(jwt_token)
+            .await
+            .ok();
+        return Ok(claims
+            .and_then(|c| c.job_id)
+            .and_then(|job_id| Uuid::parse_str(&job_id).ok()));
+    }
+    let job = sqlx::query_scalar!("SELECT job FROM token WHERE token = $1", token)
+        .fetch_optional(db)
+        .await?
+        .flatten();
+    Ok(job)
+}
+
+/// Value of the resource in the environment, if it is overridden there
+pub async fn get_resource_override(
+    db: &DB,
+    w_id: &str,
+    path: &str,
+    environment: Option<&str>,
+) -> Result<Option<Value>> {
+    let Some(environment) = environment else {
+        return Ok(None);
+    };
+    let value = sqlx::query_scalar!(
+        "SELECT value FROM resource_override WHERE workspace_id = $1 AND path = $2 AND environment = $3",
+        w_id,
+        path,
+        environment
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(value)
+}
+
+async fn list_environments(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Vec<Environment>> {
+    if authed.is_operator {
+        return Err(Error::NotAuthorized(
+            "Operators cannot list environments".to_string(),
+        ));
+    }
+    let environments = sqlx::query_as!(
+        Environment,
+        "SELECT name, description, worker_tags, created_by, created_at FROM environment
+        WHERE workspace_id = $1 ORDER BY name",
+        w_id
+    )
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(environments))
+}
+
+fn check_environment_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.len() > 50
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(Error::BadRequest(format!(
+            "Invalid environment name `{name}`, only alphanumeric characters, `_` and `-` are allowed"
+        )));
+    }
+    Ok(())
+}
+
+async fn create_environment(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(ne): Json<NewEnvironment>,
+) -> Result<(StatusCode, String)> {
+    require_admin(authed.is_admin, &authed.username)?;
+    check_environment_name(&ne.name)?;
+
+    let mut tx = db.begin().await?;
+    let created = sqlx::query_scalar!(
+        "INSERT INTO environment (workspace_id, name, description, worker_tags, created_by)
+        VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING RETURNING name",
+        w_id,
+        ne.name,
+        ne.description,
+        &ne.worker_tags,
+        authed.username
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if created.is_none() {
+        return Err(Error::BadRequest(format!(
+            "Environment {} already exists",
+            ne.name
+        )));
+    }
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.create",
+        ActionKind::Create,
+        &w_id,
+        Some(&ne.name),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::CREATED,
+        format!("environment {} created", ne.name),
+    ))
+}
+
+async fn update_environment(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, name)): Path<(String, String)>,
+    Json(ee): Json<EditEnvironment>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+    let updated = sqlx::query_scalar!(
+        "UPDATE environment SET description = COALESCE($3, description),
+            worker_tags = COALESCE($4, worker_tags)
+        WHERE workspace_id = $1 AND name = $2 RETURNING name",
+        w_id,
+        name,
+        ee.description,
+        ee.worker_tags.as_deref()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(updated, "Environment", &name)?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.update",
+        ActionKind::Update,
+        &w_id,
+        Some(&name),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("environment {name} updated"))
+}
+
+async fn delete_environment(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, name)): Path<(String, String)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+    // the overrides and the bindings of the environment are deleted with it
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM environment WHERE workspace_id = $1 AND name = $2 RETURNING name",
+        w_id,
+        name
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(deleted, "Environment", &name)?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(&name),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("environment {name} deleted"))
+}
+
+async fn list_overrides(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(q): Query<ListOverridesQuery>,
+) -> JsonResult<Vec<Override>> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let variables = sqlx::query!(
+        "SELECT o.path, o.environment, CASE WHEN v.is_secret THEN NULL ELSE o.value END AS value,
+            o.edited_by, o.edited_at
+        FROM variable_override o
+        LEFT JOIN variable v ON v.workspace_id = o.workspace_id AND v.path = o.path
+        WHERE o.workspace_id = $1 AND ($2::text IS NULL OR o.environment = $2)
+            AND ($3::text IS NULL OR o.path = $3)
+        ORDER BY o.path, o.environment",
+        w_id,
+        q.environment,
+        q.path
+    )
+    .fetch_all(&db)
+    .await?
+    .into_iter()
+    .map(|o| Override {
+        kind: OverrideKind::Variable,
+        path: o.path,
+        environment: o.environment,
+        value: o.value.map(Value::String),
+        edited_by: o.edited_by,
+        edited_at: o.edited_at,
+    });
+
+    let resources = sqlx::query!(
+        "SELECT path, environment, value, edited_by, edited_at FROM resource_override
+        WHERE workspace_id = $1 AND ($2::text IS NULL OR environment = $2)
+            AND ($3::text IS NULL OR path = $3)
+        ORDER BY path, environment",
+        w_id,
+        q.environment,
+        q.path
+    )
+    .fetch_all(&db)
+    .await?
+    .into_iter()
+    .map(|o| Override {
+        kind: OverrideKind::Resource,
+        path: o.path,
+        environment: o.environment,
+        value: Some(o.value),
+        edited_by: o.edited_by,
+        edited_at: o.edited_at,
+    });
+
+    Ok(Json(variables.chain(resources).collect()))
+}
+
+fn parse_override_kind(kind: &str) -> Result<OverrideKind> {
+    match kind {
+        "variable" => Ok(OverrideKind::Variable),
+        "resource" => Ok(OverrideKind::Resource),
+        _ => Err(Error::BadRequest(format!(
+            "Only variables and resources can be overridden, not `{kind}`"
+        ))),
+    }
+}
+
+async fn set_override(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, name, path)): Path<(String, String, String, StripPath)>,
+    Json(so): Json<SetOverride>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let kind = parse_override_kind(&kind)?;
+    let path = path.to_path();
+
+    let mut tx = db.begin().await?;
+    let environment = sqlx::query_scalar!(
+        "SELECT name FROM environment WHERE workspace_id = $1 AND name = $2",
+        w_id,
+        name
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(environment, "Environment", &name)?;
+
+    match kind {
+        OverrideKind::Variable => {
+            let Value::String(value) = so.value else {
+                return Err(Error::BadRequest(
+                    "The value of a variable must be a string".to_string(),
+                ));
+            };
+            let is_secret = sqlx::query_scalar!(
+                "SELECT is_secret FROM variable WHERE workspace_id = $1 AND path = $2",
+                w_id,
+                path
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            let is_secret = not_found_if_none(is_secret, "Variable", path)?;
+            let value = if is_secret && !value.is_empty() {
+                let mc = build_crypt(&db, &w_id).await?;
+                encrypt(&mc, &value)
+            } else {
+                value
+            };
+            sqlx::query!(
+                "INSERT INTO variable_override (workspace_id, path, environment, value, edited_by)
+                VALUES ($1, $2, $3, $4, $5) ON CONFLICT (workspace_id, path, environment)
+                DO UPDATE SET value = $4, edited_by = $5, edited_at = now()",
+                w_id,
+                path,
+                name,
+                value,
+                authed.username
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        OverrideKind::Resource => {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM resource WHERE workspace_id = $1 AND path = $2)",
+                w_id,
+                path
+            )
+            .fetch_one(&mut *tx)
+            .await?
+            .unwrap_or(false);
+            if !exists {
+                return Err(Error::NotFound(format!("Resource {path} not found")));
+            }
+            sqlx::query!(
+                "INSERT INTO resource_override (workspace_id, path, environment, value, edited_by)
+                VALUES ($1, $2, $3, $4, $5) ON CONFLICT (workspace_id, path, environment)
+                DO UPDATE SET value = $4, edited_by = $5, edited_at = now()",
+                w_id,
+                path,
+                name,
+                so.value,
+                authed.username
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.set_override",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some(HashMap::from([("environment", name.as_str())])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("override of {path} in environment {name} set"))
+}
+
+async fn delete_override(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, name, path)): Path<(String, String, String, StripPath)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let kind = parse_override_kind(&kind)?;
+    let path = path.to_path();
+
+    let mut tx = db.begin().await?;
+    let deleted = match kind {
+        OverrideKind::Variable => sqlx::query_scalar!(
+            "DELETE FROM variable_override WHERE workspace_id = $1 AND path = $2 AND environment = $3
+            RETURNING path",
+            w_id,
+            path,
+            name
+        )
+        .fetch_optional(&mut *tx)
+        .await?,
+        OverrideKind::Resource => sqlx::query_scalar!(
+            "DELETE FROM resource_override WHERE workspace_id = $1 AND path = $2 AND environment = $3
+            RETURNING path",
+            w_id,
+            path,
+            name
+        )
+        .fetch_optional(&mut *tx)
+        .await?,
+    };
+    not_found_if_none(deleted, "Override", format!("{path} in {name}"))?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.delete_override",
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        Some(HashMap::from([("environment", name.as_str())])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("override of {path} in environment {name} deleted"))
+}
+
+async fn list_bindings(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Vec<Binding>> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let bindings = sqlx::query_as!(
+        Binding,
+        "SELECT kind AS \"kind: JobTriggerKind\", path, environment FROM environment_binding
+        WHERE workspace_id = $1 ORDER BY kind, path",
+        w_id
+    )
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(bindings))
+}
+
+/// Binds a schedule or a trigger to an environment: the jobs it starts, and all the jobs of their
+/// flows, run in that environment unless their args choose another one
+async fn bind(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(binding): Json<Binding>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+    let environment = sqlx::query_scalar!(
+        "SELECT name FROM environment WHERE workspace_id = $1 AND name = $2",
+        w_id,
+        binding.environment
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(environment, "Environment", &binding.environment)?;
+    sqlx::query!(
+        "INSERT INTO environment_binding (workspace_id, kind, path, environment)
+        VALUES ($1, $2, $3, $4) ON CONFLICT (workspace_id, kind, path)
+        DO UPDATE SET environment = $4",
+        w_id,
+        binding.kind.clone() as JobTriggerKind,
+        binding.path,
+        binding.environment
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.bind",
+        ActionKind::Update,
+        &w_id,
+        Some(&binding.path),
+        Some(HashMap::from([(
+            "environment",
+            binding.environment.as_str(),
+        )])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!(
+        "{} bound to environment {}",
+        binding.path, binding.environment
+    ))
+}
+
+async fn unbind(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, path)): Path<(String, String, StripPath)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let path = path.to_path();
+    let kind: JobTriggerKind = serde_json::from_value(Value::String(kind.clone()))
+        .map_err(|_| Error::BadRequest(format!("Unknown trigger kind `{kind}`")))?;
+
+    let mut tx = db.begin().await?;
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM environment_binding WHERE workspace_id = $1 AND kind = $2 AND path = $3
+        RETURNING path",
+        w_id,
+        kind as JobTriggerKind,
+        path
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(deleted, "Binding", path)?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "environments.unbind",
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("{path} unbound"))
+}
+
+async fn get_environment_of_job(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> JsonResult<Option<String>> {
+    if authed.is_operator {
+        return Err(Error::NotAuthorized(
+            "Operators cannot read the environment of a job".to_string(),
+        ));
+    }
+    Ok(Json(get_job_environment(&db, &w_id, &id).await?))
+}
+
+/// Drops the overrides of a deleted variable or resource
+pub async fn delete_overrides<'c>(
+    tx: &mut sqlx::Transaction<'c, sqlx::Postgres>,
+    w_id: &str,
+    kind: OverrideKind,
+    path: &str,
+) -> Result<()> {
+    match kind {
+        OverrideKind::Variable => {
+            sqlx::query!(
+                "DELETE FROM variable_override WHERE workspace_id = $1 AND path = $2",
+                w_id,
+                path
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        OverrideKind::Resource => {
+            sqlx::query!(
+                "DELETE FROM resource_override WHERE workspace_id = $1 AND path = $2",
+                w_id,
+                path
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves the overrides of a renamed variable or resource to its new path
+pub async fn rename_overrides<'c>(
+    tx: &mut sqlx::Transaction<'c, sqlx::Postgres>,
+    w_id: &str,
+    kind: OverrideKind,
+    path: &str,
+    new_path: &str,
+) -> Result<()> {
+    match kind {
+        OverrideKind::Variable => {
+            sqlx::query!(
+                "UPDATE variable_override SET path = $3 WHERE workspace_id = $1 AND path = $2",
+                w_id,
+                path,
+                new_path
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        OverrideKind::Resource => {
+            sqlx::query!(
+                "UPDATE resource_override SET path = $3 WHERE workspace_id = $1 AND path = $2",
+                w_id,
+                path,
+                new_path
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Encrypts or decrypts the overrides of a variable whose value became secret or not secret
+pub async fn set_overrides_secret<'c>(
+    tx: &mut sqlx::Transaction<'c, sqlx::Postgres>,
+    db: &DB,
+    w_id: &str,
+    path: &str,
+    is_secret: bool,
+) -> Result<()> {
+    let overrides = sqlx::query!(
+        "SELECT environment, value FROM variable_override WHERE workspace_id = $1 AND path = $2",
+        w_id,
+        path
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    if overrides.is_empty() {
+        return Ok(());
+    }
+    let mc = build_crypt(db, w_id).await?;
+    for o in overrides {
+        let value = match (is_secret, o.value.is_empty()) {
+            (_, true) => continue,
+            (true, false) => encrypt(&mc, &o.value),
+            (false, false) => decrypt(&mc, o.value)?,
+        };
+        sqlx::query!(
+            "UPDATE variable_override SET value = $4
+            WHERE workspace_id = $1 AND path = $2 AND environment = $3",
+            w_id,
+            path,
+            o.environment,
+            value
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+    Ok(())
+}
@@ -44,6 +44,7 @@ use windmill_common::{
     schedule::Schedule,
     scripts::Schema,
     utils::{http_get_from_hub, not_found_if_none, paginate, Pagination, RunnableKind, StripPath},
+    workspaces::{fork_item_workspace, ForkedItemKind},
 };
 use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
 use windmill_queue::{push, schedule::push_scheduled_job, PushIsolationLevel};
@@ -1041,11 +1042,13 @@ async fn get_deployment_status(
 async fn get_flow_by_path(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
     Path((w_id, path)): Path<(String, StripPath)>,
     Query(query): Query<WithStarredInfoQuery>,
 ) -> JsonResult<FlowWithStarred> {
     let path = path.to_path();
     check_scopes(&authed, || format!("flows:read:{}", path))?;
+    let w_id = fork_item_workspace(&db, &w_id, ForkedItemKind::Flow, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let flow_o = if query.with_starred_info.unwrap_or(false) {
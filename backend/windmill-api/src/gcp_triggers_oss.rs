@@ -6,6 +6,7 @@ pub use crate::gcp_triggers_ee::*;
 use {
     crate::db::{ApiAuthed, DB},
     crate::trigger_helpers::TriggerJobArgs,
+    crate::triggers::handler::Trigger,
     axum::{extract::Request, Router},
     http::HeaderMap,
     serde::{Deserialize, Serialize},
@@ -135,34 +136,23 @@ pub fn gcp_push_route_handler() -> Router {
 
 #[derive(FromRow, Deserialize, Serialize, Debug)]
 #[cfg(not(feature = "private"))]
-pub struct GcpTrigger {
+pub struct GcpConfig {
     pub gcp_resource_path: String,
     pub subscription_id: String,
     pub delivery_type: DeliveryType,
     pub delivery_config: Option<SqlxJson<PushConfig>>,
     pub subscription_mode: SubscriptionMode,
     pub topic_id: String,
-    pub path: String,
-    pub script_path: String,
-    pub is_flow: bool,
-    pub workspace_id: String,
-    pub edited_by: String,
-    pub email: String,
-    pub edited_at: chrono::DateTime<chrono::Utc>,
-    pub extra_perms: Option<serde_json::Value>,
-    pub error: Option<String>,
-    pub server_id: Option<String>,
-    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub retry: Option<SqlxJson<windmill_common::flows::Retry>>,
 }
+
+#[cfg(not(feature = "private"))]
+pub type GcpTrigger = Trigger<GcpConfig>;
+
+#[cfg(not(feature = "private"))]
+pub struct GcpTriggerHandler;
+
 #[cfg(not(feature = "private"))]
-impl TriggerJobArgs<String> for GcpTrigger {
+impl TriggerJobArgs<String> for GcpTriggerHandler {
     fn v1_payload_fn(payload: String) -> HashMap<String, Box<RawValue>> {
         HashMap::from([("payload".to_string(), to_raw_value(&payload))])
     }
@@ -23,7 +23,7 @@ use windmill_common::{
     utils::{not_found_if_none, StripPath},
 };
 
-const KINDS: [&str; 14] = [
+const KINDS: &[&str] = &[
     "script",
     "group_",
     "resource",
@@ -37,7 +37,11 @@ const KINDS: [&str; 14] = [
     "websocket_trigger",
     "kafka_trigger",
     "nats_trigger",
-    "mqtt_trigger"
+    "mqtt_trigger",
+    "mysql_trigger",
+    "amqp_trigger",
+    "redis_trigger",
+    "s3_trigger"
 ];
 
 pub fn workspaced_service() -> Router {
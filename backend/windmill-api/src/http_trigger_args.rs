@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue;
 use windmill_common::{
     error::Error,
+    schema::{SchemaFieldError, SchemaValidator},
     triggers::{RunnableFormat, RunnableFormatVersion},
     worker::to_raw_value,
     DB,
@@ -108,6 +109,21 @@ struct HttpTriggerWmTrigger<'a> {
 }
 
 impl HttpTriggerArgs {
+    /// Validates the body against the schema of the runnable, as it would be passed to the main
+    /// function. Query and path parameters are not passed to the main function so they are not checked.
+    pub fn validate_request(
+        &self,
+        validator: &SchemaValidator,
+        wrap_body: bool,
+    ) -> Vec<SchemaFieldError> {
+        match &self.0.body {
+            Body::HashMap(body) if !wrap_body => validator.validate_all(body),
+            body => {
+                validator.validate_all(&HashMap::from([("body".to_string(), to_raw_value(body))]))
+            }
+        }
+    }
+
     pub fn to_main_args(self, wrap_body: bool) -> Result<PushArgsOwned, Error> {
         let mut extra = HashMap::new();
 
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::response::{IntoResponse, Response};
+use http::{
+    header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use windmill_common::{
+    error::{Error, Result},
+    DB,
+};
+
+use crate::jobs::result_to_response;
+
+const HTTP_CACHE_RESOURCE_PREFIX: &str = "g/http_cache";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpCachePolicy {
+    pub ttl_s: u32,
+    #[serde(default)]
+    pub key_params: Vec<String>,
+    #[serde(default)]
+    pub key_query: Vec<String>,
+    #[serde(default)]
+    pub key_headers: Vec<String>,
+}
+
+impl HttpCachePolicy {
+    pub fn validate(&self) -> Result<()> {
+        if self.ttl_s == 0 {
+            return Err(Error::BadRequest(
+                "Cache policy ttl must be greater than 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Values of the request that identify a cached response. Only the parts listed in the
+/// cache policy of the route are taken into account when computing the key, along with the
+/// principal so that a response is never served to another caller than the one it was run for.
+#[derive(Deserialize, Debug, Default)]
+pub struct CacheKeyParts {
+    /// email of the caller for the routes authenticated by windmill, the callers of the other
+    /// routes share the same credentials and the same responses
+    #[serde(default)]
+    pub principal: Option<String>,
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl CacheKeyParts {
+    pub fn from_request(
+        principal: Option<String>,
+        params: &HashMap<String, String>,
+        query: &HashMap<String, Box<RawValue>>,
+        headers: &HeaderMap,
+    ) -> Self {
+        let query = query
+            .iter()
+            .map(|(k, v)| {
+                let v = serde_json::from_str::<String>(v.get()).unwrap_or_else(|_| v.to_string());
+                (k.clone(), v)
+            })
+            .collect();
+        let headers = headers
+            .iter()
+            .filter_map(|(k, v)| Some((k.as_str().to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+        Self { principal, params: params.clone(), query, headers }
+    }
+
+    fn to_key(&self, policy: &HttpCachePolicy) -> String {
+        let select = |names: &[String], values: &HashMap<String, String>, lowercase: bool| {
+            names
+                .iter()
+                .map(|name| {
+                    let value = if lowercase {
+                        values
+                            .iter()
+                            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                            .map(|(_, v)| v.clone())
+                    } else {
+                        values.get(name).cloned()
+                    };
+                    (name.to_lowercase(), value)
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let key = serde_json::json!({
+            "principal": self.principal,
+            "params": select(&policy.key_params, &self.params, false),
+            "query": select(&policy.key_query, &self.query, false),
+            "headers": select(&policy.key_headers, &self.headers, true),
+        });
+
+        let mut hasher = Sha256::new();
+        hasher.update(key.to_string().as_bytes());
+        format!("{:064x}", hasher.finalize())
+    }
+}
+
+/// Same shape as the cache resources of the workers so that both can be inspected the same way
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    expire: i64,
+    value: Box<RawValue>,
+}
+
+// the trigger path is hashed to stay within the length limit of resource paths
+fn cache_resource_prefix(trigger_path: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(trigger_path.as_bytes());
+    let trigger_hash = format!("{:x}", hasher.finalize());
+    format!("{HTTP_CACHE_RESOURCE_PREFIX}/{}/", &trigger_hash[..16])
+}
+
+pub fn cache_resource_path(
+    trigger_path: &str,
+    policy: &HttpCachePolicy,
+    parts: &CacheKeyParts,
+) -> String {
+    format!(
+        "{}{}",
+        cache_resource_prefix(trigger_path),
+        parts.to_key(policy)
+    )
+}
+
+pub fn compute_etag(value: &RawValue) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(value.get().as_bytes());
+    format!("\"{:x}\"", hasher.finalize())
+}
+
+pub async fn get_cached_response(
+    db: &DB,
+    w_id: &str,
+    cached_path: &str,
+    request_headers: &HeaderMap,
+    private: bool,
+) -> Result<Option<Response>> {
+    let cached = sqlx::query_scalar!(
+        r#"SELECT value AS "value: Json<CachedResponse>" FROM resource WHERE workspace_id = $1 AND path = $2 AND resource_type = 'cache'"#,
+        w_id,
+        cached_path
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten();
+
+    let Some(Json(cached)) = cached else {
+        return Ok(None);
+    };
+
+    let remaining_s = cached.expire - chrono::Utc::now().timestamp();
+    if remaining_s <= 0 {
+        return Ok(None);
+    }
+
+    let etag = compute_etag(&cached.value);
+    let not_modified = request_headers
+        .get(IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        result_to_response(cached.value, true)?
+    };
+
+    add_cache_headers(response.headers_mut(), &etag, remaining_s, private);
+
+    Ok(Some(response))
+}
+
+pub async fn save_response_in_cache(
+    db: &DB,
+    w_id: &str,
+    cached_path: &str,
+    policy: &HttpCachePolicy,
+    value: &RawValue,
+    created_by: &str,
+) -> Result<()> {
+    let cached = CachedResponse {
+        expire: chrono::Utc::now().timestamp() + policy.ttl_s as i64,
+        value: value.to_owned(),
+    };
+
+    sqlx::query!(
+        "INSERT INTO resource
+        (workspace_id, path, value, resource_type, created_by, edited_at)
+        VALUES ($1, $2, $3, $4, $5, now()) ON CONFLICT (workspace_id, path)
+        DO UPDATE SET value = $3, edited_at = now()",
+        w_id,
+        cached_path,
+        Json(&cached) as Json<&CachedResponse>,
+        "cache",
+        created_by
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Responses of the routes that authenticate their callers are marked private so that shared
+/// caches in front of windmill do not serve them to other callers
+pub fn add_cache_headers(headers: &mut HeaderMap, etag: &str, max_age_s: i64, private: bool) {
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        headers.insert(ETAG, etag);
+    }
+    let cache_control = if private {
+        format!("private, max-age={max_age_s}")
+    } else {
+        format!("max-age={max_age_s}")
+    };
+    if let Ok(cache_control) = HeaderValue::from_str(&cache_control) {
+        headers.insert(CACHE_CONTROL, cache_control);
+    }
+}
+
+/// Deletes the cached responses of a route, either a single entry or all of them
+pub async fn invalidate_cache<'c, E: sqlx::PgExecutor<'c>>(
+    db: E,
+    w_id: &str,
+    trigger_path: &str,
+    cached_path: Option<&str>,
+) -> Result<u64> {
+    let deleted = match cached_path {
+        Some(cached_path) => sqlx::query!(
+            "DELETE FROM resource WHERE workspace_id = $1 AND path = $2 AND resource_type = 'cache'",
+            w_id,
+            cached_path
+        )
+        .execute(db)
+        .await?
+        .rows_affected(),
+        None => sqlx::query!(
+            "DELETE FROM resource WHERE workspace_id = $1 AND starts_with(path, $2) AND resource_type = 'cache'",
+            w_id,
+            cache_resource_prefix(trigger_path)
+        )
+        .execute(db)
+        .await?
+        .rows_affected(),
+    };
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_cache_headers() {
+        let mut headers = HeaderMap::new();
+        add_cache_headers(&mut headers, "\"abc\"", 60, false);
+        assert_eq!(headers.get(ETAG).unwrap(), "\"abc\"");
+        assert_eq!(headers.get(CACHE_CONTROL).unwrap(), "max-age=60");
+
+        add_cache_headers(&mut headers, "\"abc\"", 30, true);
+        assert_eq!(headers.get(CACHE_CONTROL).unwrap(), "private, max-age=30");
+    }
+}
@@ -1,13 +1,20 @@
 #[cfg(feature = "http_trigger")]
 use crate::http_trigger_args::{HttpMethod, RawHttpTriggerArgs};
+use crate::http_trigger_cache::{
+    add_cache_headers, cache_resource_path, compute_etag, get_cached_response, invalidate_cache,
+    save_response_in_cache, CacheKeyParts, HttpCachePolicy,
+};
 #[cfg(feature = "parquet")]
 use crate::job_helpers_oss::get_workspace_s3_resource;
 use crate::{
     auth::{AuthCache, OptTokened},
     db::{ApiAuthed, DB},
+    jobs::result_to_response,
+    rate_limits::{check_rate_limits, validate_rate_limits, RateLimit, RateLimitCaller},
     resources::try_get_resource_from_db_as,
     trigger_helpers::{
-        get_runnable_format, trigger_runnable, trigger_runnable_and_wait_for_result, RunnableId,
+        get_runnable_format, get_runnable_schema_validator, trigger_runnable_idempotent,
+        wait_for_job_result, RunnableId,
     },
     users::fetch_api_authed,
     utils::{check_scopes, non_empty_str, ExpiringCacheEntry},
@@ -16,7 +23,7 @@ use crate::{
 use anyhow::anyhow;
 use axum::response::Response;
 use axum::{
-    extract::{Path, Query},
+    extract::{ConnectInfo, Path, Query},
     response::IntoResponse,
     routing::{delete, get, post},
     Extension, Json, Router,
@@ -32,7 +39,7 @@ use sqlx::prelude::FromRow;
 use sqlx::PgConnection;
 use std::borrow::Cow;
 use std::collections::HashSet;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 use tokio::sync::{RwLock, RwLockReadGuard};
 use tower_http::cors::CorsLayer;
 use windmill_audit::{audit_oss::audit_log, ActionKind};
@@ -49,6 +56,7 @@ use windmill_common::{
     worker::CLOUD_HOSTED,
 };
 use windmill_git_sync::handle_deployment_metadata;
+use windmill_queue::idempotency::{IdempotencyKey, IDEMPOTENCY_KEY_HEADER};
 
 lazy_static::lazy_static! {
     static ref ROUTE_PATH_KEY_RE: regex::Regex = regex::Regex::new(r"/?:[-\w]+").unwrap();
@@ -89,6 +97,7 @@ pub fn workspaced_service() -> Router {
         .route("/delete/*path", delete(delete_trigger))
         .route("/exists/*path", get(exists_trigger))
         .route("/route_exists", post(exists_route))
+        .route("/invalidate_cache/*path", post(invalidate_trigger_cache))
 }
 
 #[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -123,6 +132,9 @@ struct NewTrigger {
     error_handler_path: Option<String>,
     error_handler_args: Option<sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     retry: Option<sqlx::types::Json<Retry>>,
+    validate_request: Option<bool>,
+    cache_policy: Option<sqlx::types::Json<HttpCachePolicy>>,
+    rate_limits: Option<sqlx::types::Json<Vec<RateLimit>>>,
 }
 
 #[derive(FromRow, Serialize)]
@@ -154,6 +166,11 @@ pub struct HttpTrigger {
     pub error_handler_args: Option<sqlx::types::Json<Box<RawValue>>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub retry: Option<sqlx::types::Json<Box<RawValue>>>,
+    pub validate_request: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_policy: Option<sqlx::types::Json<HttpCachePolicy>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<sqlx::types::Json<Vec<RateLimit>>>,
 }
 
 #[derive(Deserialize)]
@@ -177,6 +194,9 @@ struct EditTrigger {
     error_handler_path: Option<String>,
     error_handler_args: Option<sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     retry: Option<sqlx::types::Json<Retry>>,
+    validate_request: Option<bool>,
+    cache_policy: Option<sqlx::types::Json<HttpCachePolicy>>,
+    rate_limits: Option<sqlx::types::Json<Vec<RateLimit>>>,
 }
 
 #[derive(Deserialize)]
@@ -223,6 +243,9 @@ async fn list_triggers(
             "error_handler_path",
             "error_handler_args",
             "retry",
+            "validate_request",
+            "cache_policy",
+            "rate_limits",
         ])
         .order_by("edited_at", true)
         .and_where("workspace_id = ?".bind(&w_id))
@@ -284,7 +307,10 @@ async fn get_trigger(
             raw_string,
             error_handler_path,
             error_handler_args as "error_handler_args: _",
-            retry as "retry: _"
+            retry as "retry: _",
+            validate_request,
+            cache_policy as "cache_policy: _",
+            rate_limits as "rate_limits: _"
         FROM 
             http_trigger
         WHERE 
@@ -320,6 +346,31 @@ fn validate_authentication_method(
     Ok(())
 }
 
+fn validate_cache_policy(
+    cache_policy: Option<&HttpCachePolicy>,
+    http_method: HttpMethod,
+    is_async: bool,
+    authentication_method: AuthenticationMethod,
+) -> WindmillResult<()> {
+    if let Some(cache_policy) = cache_policy {
+        if is_async || http_method != HttpMethod::Get {
+            return Err(Error::BadRequest(
+                "Response caching is only available for synchronous GET routes".to_string(),
+            ));
+        }
+        // the cached responses are served without running the runnable that authenticates
+        if authentication_method == AuthenticationMethod::CustomScript {
+            return Err(Error::BadRequest(
+                "Response caching is not available for routes authenticated by a custom script"
+                    .to_string(),
+            ));
+        }
+        cache_policy.validate()?;
+    }
+
+    Ok(())
+}
+
 async fn increase_trigger_version(tx: &mut PgConnection) -> WindmillResult<()> {
     sqlx::query!("SELECT nextval('http_trigger_version_seq')",)
         .fetch_one(tx)
@@ -364,10 +415,13 @@ async fn create_trigger_inner(
             is_static_website,
             error_handler_path,
             error_handler_args,
-            retry
+            retry,
+            validate_request,
+            cache_policy,
+            rate_limits
         ) 
         VALUES (
-            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, now(), $19, $20, $21, $22
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, now(), $19, $20, $21, $22, $23, $24, $25
         )
         "#,
         w_id,
@@ -392,6 +446,9 @@ async fn create_trigger_inner(
         new_http_trigger.error_handler_path,
         new_http_trigger.error_handler_args as _,
         new_http_trigger.retry as _,
+        new_http_trigger.validate_request.unwrap_or(false),
+        new_http_trigger.cache_policy as _,
+        new_http_trigger.rate_limits as _,
     )
     .execute(&mut *tx)
     .await?;
@@ -510,6 +567,17 @@ async fn validate_http_trigger<'trigger>(
         new_http_trigger.raw_string,
     )?;
 
+    validate_cache_policy(
+        new_http_trigger.cache_policy.as_deref(),
+        new_http_trigger.http_method,
+        new_http_trigger.is_async,
+        new_http_trigger.authentication_method,
+    )?;
+
+    if let Some(rate_limits) = new_http_trigger.rate_limits.as_deref() {
+        validate_rate_limits(rate_limits)?;
+    }
+
     // route path key is extracted from the route path to check for uniqueness
     // it replaces /?:{key} with :key
     // it will also remove the leading / if present, not an issue as we only allow : after slashes
@@ -593,6 +661,17 @@ async fn update_trigger(
 
     validate_authentication_method(ct.authentication_method, ct.raw_string)?;
 
+    validate_cache_policy(
+        ct.cache_policy.as_deref(),
+        ct.http_method,
+        ct.is_async,
+        ct.authentication_method,
+    )?;
+
+    if let Some(rate_limits) = ct.rate_limits.as_deref() {
+        validate_rate_limits(rate_limits)?;
+    }
+
     let mut tx;
     if authed.is_admin {
         let Some(route_path) = ct.route_path else {
@@ -649,10 +728,13 @@ async fn update_trigger(
                 is_static_website = $18,
                 error_handler_path = $19,
                 error_handler_args = $20,
-                retry = $21
+                retry = $21,
+                validate_request = $22,
+                cache_policy = $23,
+                rate_limits = $24
             WHERE 
-                workspace_id = $22 AND 
-                path = $23
+                workspace_id = $25 AND 
+                path = $26
             "#,
             route_path,
             &route_path_key,
@@ -675,6 +757,9 @@ async fn update_trigger(
             ct.error_handler_path,
             ct.error_handler_args as _,
             ct.retry as _,
+            ct.validate_request.unwrap_or(false),
+            ct.cache_policy as _,
+            ct.rate_limits as _,
             w_id,
             path,
         )
@@ -704,10 +789,13 @@ async fn update_trigger(
                 is_static_website = $14,
                 error_handler_path = $15,
                 error_handler_args = $16,
-                retry = $17
+                retry = $17,
+                validate_request = $18,
+                cache_policy = $19,
+                rate_limits = $20
             WHERE 
-                workspace_id = $18 AND 
-                path = $19
+                workspace_id = $21 AND 
+                path = $22
             "#,
             ct.workspaced_route,
             ct.wrap_body,
@@ -726,6 +814,9 @@ async fn update_trigger(
             ct.error_handler_path,
             ct.error_handler_args as _,
             ct.retry as _,
+            ct.validate_request.unwrap_or(false),
+            ct.cache_policy as _,
+            ct.rate_limits as _,
             w_id,
             path,
         )
@@ -797,6 +888,9 @@ async fn delete_trigger(
 
     tx.commit().await?;
 
+    // only once the route is deleted, so that its responses are not cached again in between
+    invalidate_cache(&db, &w_id, path, None).await?;
+
     handle_deployment_metadata(
         &authed.email,
         &authed.username,
@@ -919,6 +1013,50 @@ async fn exists_route(
     Ok(Json(exists))
 }
 
+#[derive(Deserialize)]
+struct InvalidateCache {
+    /// if not set, all the cached responses of the route are invalidated
+    key_parts: Option<CacheKeyParts>,
+}
+
+async fn invalidate_trigger_cache(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(InvalidateCache { key_parts }): Json<InvalidateCache>,
+) -> WindmillResult<String> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("http_triggers:write:{}", path))?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    let cache_policy = sqlx::query_scalar!(
+        r#"SELECT cache_policy AS "cache_policy: sqlx::types::Json<HttpCachePolicy>" FROM http_trigger WHERE workspace_id = $1 AND path = $2"#,
+        w_id,
+        path
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let cache_policy = not_found_if_none(cache_policy, "Trigger", path)?;
+
+    let Some(sqlx::types::Json(cache_policy)) = cache_policy else {
+        return Err(Error::BadRequest(format!(
+            "HTTP route {path} has no cache policy"
+        )));
+    };
+
+    let cached_path =
+        key_parts.map(|key_parts| cache_resource_path(path, &cache_policy, &key_parts));
+
+    let deleted = invalidate_cache(&db, &w_id, path, cached_path.as_deref()).await?;
+
+    Ok(format!(
+        "Invalidated {deleted} cached response(s) of HTTP route {path}"
+    ))
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct TriggerRoute {
     path: String,
@@ -939,6 +1077,9 @@ pub struct TriggerRoute {
     error_handler_path: Option<String>,
     error_handler_args: Option<sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     retry: Option<sqlx::types::Json<Retry>>,
+    validate_request: bool,
+    cache_policy: Option<sqlx::types::Json<HttpCachePolicy>>,
+    rate_limits: Option<sqlx::types::Json<Vec<RateLimit>>>,
 }
 
 pub struct RoutersCache {
@@ -1028,7 +1169,10 @@ pub async fn refresh_routers(db: &DB) -> Result<(bool, RwLockReadGuard<'_, Route
                         is_static_website,
                         error_handler_path,
                         error_handler_args as "error_handler_args: _",
-                        retry as "retry: _"
+                        retry as "retry: _",
+                        validate_request,
+                        cache_policy as "cache_policy: _",
+                        rate_limits as "rate_limits: _"
                     FROM 
                         http_trigger 
                     WHERE 
@@ -1203,6 +1347,7 @@ async fn route_job(
     Extension(auth_cache): Extension<Arc<AuthCache>>,
     OptTokened { token }: OptTokened,
     Path(route_path): Path<StripPath>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     headers: HeaderMap,
     args: RawHttpTriggerArgs,
 ) -> Result<impl IntoResponse, Response> {
@@ -1301,6 +1446,22 @@ async fn route_job(
         }
     }
 
+    if let Some(sqlx::types::Json(rate_limits)) = trigger.rate_limits.as_ref() {
+        check_rate_limits(
+            &db,
+            &trigger.workspace_id,
+            &format!("http/{}", trigger.path),
+            rate_limits,
+            &RateLimitCaller::new(
+                &authed,
+                &headers,
+                connect_info.map(|ConnectInfo(peer)| peer),
+            ),
+        )
+        .await
+        .map_err(|e| e.into_response())?;
+    }
+
     #[cfg(not(feature = "parquet"))]
     if trigger.static_asset_config.is_some() {
         return Err(error::Error::internal_err(
@@ -1412,12 +1573,14 @@ async fn route_job(
         }
     }
 
+    let runnable_id = if trigger.is_flow {
+        RunnableId::from_flow_path(&trigger.script_path)
+    } else {
+        RunnableId::from_script_path(&trigger.script_path)
+    };
+
     let runnable_format = get_runnable_format(
-        if trigger.is_flow {
-            RunnableId::from_flow_path(&trigger.script_path)
-        } else {
-            RunnableId::from_script_path(&trigger.script_path)
-        },
+        runnable_id.clone(),
         &trigger.workspace_id,
         &db,
         &TriggerKind::Http,
@@ -1425,6 +1588,60 @@ async fn route_job(
     .await
     .map_err(|e| e.into_response())?;
 
+    // with a preprocessor, the request is reshaped before reaching the main function
+    // so the main function schema cannot be used to validate it, and a raw string body
+    // is passed as is
+    if trigger.validate_request && !trigger.raw_string && !runnable_format.has_preprocessor {
+        let validator = get_runnable_schema_validator(&runnable_id, &trigger.workspace_id, &db)
+            .await
+            .map_err(|e| e.into_response())?;
+
+        if let Some(validator) = &*validator {
+            let errors = args.validate_request(validator, trigger.wrap_body);
+            if !errors.is_empty() {
+                return Ok((
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "error": "Request does not match the schema of the runnable",
+                        "errors": errors,
+                    })),
+                )
+                    .into_response());
+            }
+        }
+    }
+
+    let private_cache = !matches!(trigger.authentication_method, AuthenticationMethod::None);
+    let cache = match trigger.cache_policy.as_ref() {
+        Some(sqlx::types::Json(cache_policy)) if !trigger.is_async => {
+            let principal = match trigger.authentication_method {
+                AuthenticationMethod::Windmill => Some(authed.email.clone()),
+                _ => None,
+            };
+            let cache_key_parts =
+                CacheKeyParts::from_request(principal, &params, &args.0.metadata.query, &headers);
+            let cached_path = cache_resource_path(&trigger.path, cache_policy, &cache_key_parts);
+
+            let cached_response = get_cached_response(
+                &db,
+                &trigger.workspace_id,
+                &cached_path,
+                &headers,
+                private_cache,
+            )
+            .await
+            .map_err(|e| e.into_response())?;
+
+            if let Some(cached_response) = cached_response {
+                tracing::debug!("HTTP response cache hit for route {}", trigger.path);
+                return Ok(cached_response);
+            }
+
+            Some((cache_policy, cached_path))
+        }
+        _ => None,
+    };
+
     let args = args
         .to_args_from_format(
             &trigger.route_path,
@@ -1435,37 +1652,79 @@ async fn route_job(
         )
         .map_err(|e| e.into_response())?;
 
-    if trigger.is_async {
-        trigger_runnable(
-            &db,
-            Some(user_db),
-            authed,
-            &trigger.workspace_id,
-            &trigger.script_path,
-            trigger.is_flow,
-            args,
-            trigger.retry.as_ref(),
-            trigger.error_handler_path.as_deref(),
-            trigger.error_handler_args.as_ref(),
-            format!("http_trigger/{}", trigger.path),
-        )
-        .await
-        .map_err(|e| e.into_response())
-    } else {
-        trigger_runnable_and_wait_for_result(
-            &db,
-            Some(user_db),
-            authed,
+    let idempotency_key = match headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|h| h.to_str().ok())
+    {
+        // the username is the one of the caller for routes authenticated by windmill
+        Some(key) => IdempotencyKey::new(
             &trigger.workspace_id,
-            &trigger.script_path,
-            trigger.is_flow,
-            args,
-            trigger.retry.as_ref(),
-            trigger.error_handler_path.as_deref(),
-            trigger.error_handler_args.as_ref(),
-            format!("http_trigger/{}", trigger.path),
+            format!("http_trigger/{}/{}", trigger.path, authed.username),
+            key,
         )
-        .await
-        .map_err(|e| e.into_response())
+        .map_err(|e| e.into_response())?,
+        None => None,
+    };
+
+    let username = authed.username.clone();
+    let (uuid, delete_after_use) = trigger_runnable_idempotent(
+        &db,
+        Some(user_db),
+        authed,
+        &trigger.workspace_id,
+        &trigger.script_path,
+        trigger.is_flow,
+        args,
+        trigger.retry.as_ref(),
+        trigger.error_handler_path.as_deref(),
+        trigger.error_handler_args.as_ref(),
+        format!("http_trigger/{}", trigger.path),
+        idempotency_key.as_ref(),
+    )
+    .await
+    .map_err(|e| e.into_response())?;
+
+    if trigger.is_async {
+        return Ok((StatusCode::CREATED, uuid.to_string()).into_response());
+    }
+
+    let (result, success) = wait_for_job_result(
+        &db,
+        uuid,
+        &trigger.workspace_id,
+        &username,
+        delete_after_use,
+    )
+    .await
+    .map_err(|e| e.into_response())?;
+
+    let Some((cache_policy, cached_path)) = cache.filter(|_| success) else {
+        return result_to_response(result, success).map_err(|e| e.into_response());
+    };
+
+    if let Err(err) = save_response_in_cache(
+        &db,
+        &trigger.workspace_id,
+        &cached_path,
+        cache_policy,
+        &result,
+        &username,
+    )
+    .await
+    {
+        tracing::error!(
+            "Error caching response of HTTP route {}: {err:#}",
+            trigger.path
+        );
     }
+
+    let etag = compute_etag(&result);
+    let mut response = result_to_response(result, success).map_err(|e| e.into_response())?;
+    add_cache_headers(
+        response.headers_mut(),
+        &etag,
+        cache_policy.ttl_s as i64,
+        private_cache,
+    );
+    Ok(response)
 }
@@ -43,6 +43,7 @@ use crate::{
     auth::{OptTokened, Tokened},
     concurrency_groups::join_concurrency_key,
     db::{ApiAuthed, DB},
+    rate_limits::webhook_rate_limit,
     trigger_helpers::RunnableId,
     users::{get_scope_tags, require_owner_of_path, OptAuthed},
     utils::{check_scopes, content_plain, require_super_admin},
@@ -88,8 +89,12 @@ use windmill_common::{
     get_script_info_for_hash, FlowVersionInfo, ScriptHashInfo, BASE_URL,
 };
 use windmill_queue::{
-    cancel_job, get_result_and_success_by_id_from_flow, job_is_complete, push, PushArgs,
-    PushArgsOwned, PushIsolationLevel,
+    cancel_job, get_result_and_success_by_id_from_flow,
+    idempotency::{
+        begin_idempotent_push, commit_idempotent_push, IdempotencyKey, IdempotentPush,
+        IDEMPOTENCY_KEY_HEADER,
+    },
+    job_is_complete, push, PushArgs, PushArgsOwned, PushIsolationLevel,
 };
 
 pub fn workspaced_service() -> Router {
@@ -102,11 +107,14 @@ pub fn workspaced_service() -> Router {
     let ce_headers =
         ServiceBuilder::new().layer(axum::middleware::from_fn(add_webhook_allowed_origin));
 
+    let rate_limit = ServiceBuilder::new().layer(axum::middleware::from_fn(webhook_rate_limit));
+
     Router::new()
         .route(
             "/run/f/*script_path",
             post(run_flow_by_path)
                 .head(|| async { "" })
+                .route_layer(rate_limit.clone())
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
@@ -136,6 +144,7 @@ pub fn workspaced_service() -> Router {
             "/run/p/*script_path",
             post(run_script_by_path)
                 .head(|| async { "" })
+                .route_layer(rate_limit.clone())
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
@@ -144,6 +153,7 @@ pub fn workspaced_service() -> Router {
             post(run_wait_result_script_by_path)
                 .get(run_wait_result_job_by_path_get)
                 .head(|| async { "" })
+                .route_layer(rate_limit.clone())
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
@@ -151,6 +161,7 @@ pub fn workspaced_service() -> Router {
             "/run_wait_result/h/:hash",
             post(run_wait_result_script_by_hash)
                 .head(|| async { "" })
+                .route_layer(rate_limit.clone())
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
@@ -159,6 +170,7 @@ pub fn workspaced_service() -> Router {
             post(run_wait_result_flow_by_path)
                 .get(run_wait_result_flow_by_path_get)
                 .head(|| async { "" })
+                .route_layer(rate_limit.clone())
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
@@ -166,6 +178,7 @@ pub fn workspaced_service() -> Router {
             "/run/h/:hash",
             post(run_job_by_hash)
                 .head(|| async { "" })
+                .route_layer(rate_limit.clone())
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
@@ -1360,9 +1373,45 @@ pub struct RunJobQuery {
     pub timeout: Option<i32>,
     pub cache_ttl: Option<i32>,
     pub skip_preprocessor: Option<bool>,
+    pub idempotency_key: Option<String>,
+    #[serde(skip)]
+    pub trigger_idempotency_key: Option<IdempotencyKey>,
 }
 
 impl RunJobQuery {
+    /// The idempotency key can be passed either as a query argument or with the `Idempotency-Key` header
+    pub fn with_idempotency_key_header(mut self, headers: &HeaderMap) -> Self {
+        if self.idempotency_key.is_none() {
+            self.idempotency_key = headers
+                .get(IDEMPOTENCY_KEY_HEADER)
+                .and_then(|h| h.to_str().ok())
+                .map(|h| h.to_string());
+        }
+        self
+    }
+
+    /// Keys of the requests are scoped to the user submitting them and to the script or flow they
+    /// run, the triggers pass their own
+    fn idempotency_key(
+        &self,
+        w_id: &str,
+        authed: &ApiAuthed,
+        is_flow: bool,
+        path: &str,
+    ) -> error::Result<Option<IdempotencyKey>> {
+        if let Some(idempotency_key) = self.trigger_idempotency_key.as_ref() {
+            return Ok(Some(idempotency_key.clone()));
+        }
+        match self.idempotency_key.as_deref() {
+            Some(key) => IdempotencyKey::new(
+                w_id,
+                IdempotencyKey::user_scope(&authed.email, is_flow, path),
+                key,
+            ),
+            None => Ok(None),
+        }
+    }
+
     async fn get_scheduled_for<'c>(
         &self,
         db: &DB,
@@ -3475,8 +3524,11 @@ pub async fn run_flow_by_path(
     Extension(user_db): Extension<UserDB>,
     Path((w_id, flow_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<(StatusCode, String)> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -3544,39 +3596,46 @@ pub async fn run_flow_by_path_inner(
             )
         };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        JobPayload::Flow {
-            path: flow_path.to_string(),
-            dedicated_worker,
-            version,
-            apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
-                && has_preprocessor.unwrap_or(false),
-        },
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        scheduled_for,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        None,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, true, &flow_path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                JobPayload::Flow {
+                    path: flow_path.to_string(),
+                    dedicated_worker,
+                    version,
+                    apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
+                        && has_preprocessor.unwrap_or(false),
+                },
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                scheduled_for,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                None,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
     Ok(uuid)
 }
 
@@ -3680,8 +3739,11 @@ pub async fn run_script_by_path(
     Extension(user_db): Extension<UserDB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<(StatusCode, String)> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -3739,33 +3801,40 @@ pub async fn run_script_by_path_inner(
             )
         };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        job_payload,
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        scheduled_for,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, false, script_path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                job_payload,
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                scheduled_for,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                timeout,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
 
     Ok((uuid, delete_after_use))
 }
@@ -4357,18 +4426,21 @@ pub async fn run_wait_result_job_by_path_get(
     Extension(db): Extension<DB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let script_path = script_path.to_path();
     check_scopes(&authed, || format!("jobs:run:scripts:{script_path}"))?;
 
     if method == http::Method::HEAD {
         return Ok(Json(serde_json::json!("")).into_response());
     }
-    let payload_r = run_query.payload.map(decode_payload).map(|x| {
+    let payload_r = run_query.payload.clone().map(decode_payload).map(|x| {
         x.map_err(|e| Error::internal_err(format!("Impossible to decode query payload: {e:#?}")))
     });
 
@@ -4417,33 +4489,40 @@ pub async fn run_wait_result_job_by_path_get(
             )
         };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        job_payload,
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        None,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, false, script_path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                job_payload,
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                None,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                timeout,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
     if delete_after_use.unwrap_or(false) {
@@ -4459,11 +4538,14 @@ pub async fn run_wait_result_flow_by_path_get(
     Extension(db): Extension<DB>,
     Path((w_id, flow_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     if method == http::Method::HEAD {
         return Ok(Json(serde_json::json!("")).into_response());
     }
@@ -4501,11 +4583,14 @@ pub async fn run_wait_result_script_by_path(
     Extension(db): Extension<DB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -4559,33 +4644,40 @@ pub async fn run_wait_result_script_by_path_internal(
             )
         };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        job_payload,
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        None,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, false, script_path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                job_payload,
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                None,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                timeout,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
     if delete_after_use.unwrap_or(false) {
@@ -4600,11 +4692,14 @@ pub async fn run_wait_result_script_by_hash(
     Extension(db): Extension<DB>,
     Path((w_id, script_hash)): Path<(String, ScriptHash)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -4661,45 +4756,52 @@ pub async fn run_wait_result_script_by_hash(
         )
     };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        JobPayload::ScriptHash {
-            hash: ScriptHash(hash),
-            path: path,
-            custom_concurrency_key: concurrency_key,
-            concurrent_limit: concurrent_limit,
-            concurrency_time_window_s: concurrency_time_window_s,
-            cache_ttl,
-            language,
-            dedicated_worker,
-            priority,
-            apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
-                && has_preprocessor.unwrap_or(false),
-        },
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        None,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, false, &path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                JobPayload::ScriptHash {
+                    hash: ScriptHash(hash),
+                    path: path,
+                    custom_concurrency_key: concurrency_key,
+                    concurrent_limit: concurrent_limit,
+                    concurrency_time_window_s: concurrency_time_window_s,
+                    cache_ttl,
+                    language,
+                    dedicated_worker,
+                    priority,
+                    apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
+                        && has_preprocessor.unwrap_or(false),
+                },
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                None,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                timeout,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
     if delete_after_use.unwrap_or(false) {
@@ -4714,11 +4816,14 @@ pub async fn run_wait_result_flow_by_path(
     Extension(db): Extension<DB>,
     Path((w_id, flow_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -4782,39 +4887,46 @@ pub async fn run_wait_result_flow_by_path_internal(
             )
         };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        JobPayload::Flow {
-            path: flow_path.to_string(),
-            dedicated_worker,
-            version,
-            apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
-                && has_preprocessor.unwrap_or(false),
-        },
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        scheduled_for,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        None,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, true, &flow_path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                JobPayload::Flow {
+                    path: flow_path.to_string(),
+                    dedicated_worker,
+                    version,
+                    apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
+                        && has_preprocessor.unwrap_or(false),
+                },
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                scheduled_for,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                None,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
 
     run_wait_result(&db, uuid, w_id, early_return, &authed.username).await
 }
@@ -5552,8 +5664,11 @@ pub async fn run_job_by_hash(
     Extension(user_db): Extension<UserDB>,
     Path((w_id, script_hash)): Path<(String, ScriptHash)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<(StatusCode, String)> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
+
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -5628,45 +5743,52 @@ pub async fn run_job_by_hash_inner(
         )
     };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &w_id,
-        JobPayload::ScriptHash {
-            hash: ScriptHash(hash),
-            path: path,
-            custom_concurrency_key: concurrency_key,
-            concurrent_limit: concurrent_limit,
-            concurrency_time_window_s: concurrency_time_window_s,
-            cache_ttl,
-            language,
-            dedicated_worker,
-            priority,
-            apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
-                && has_preprocessor.unwrap_or(false),
-        },
-        PushArgs { args: &args.args, extra: args.extra },
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        scheduled_for,
-        None,
-        run_query.parent_job,
-        run_query.root_job,
-        run_query.job_id,
-        false,
-        false,
-        None,
-        !run_query.invisible_to_owner.unwrap_or(false),
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let idempotency_key = run_query.idempotency_key(&w_id, &authed, false, &path)?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key.as_ref()).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &w_id,
+                JobPayload::ScriptHash {
+                    hash: ScriptHash(hash),
+                    path: path,
+                    custom_concurrency_key: concurrency_key,
+                    concurrent_limit: concurrent_limit,
+                    concurrency_time_window_s: concurrency_time_window_s,
+                    cache_ttl,
+                    language,
+                    dedicated_worker,
+                    priority,
+                    apply_preprocessor: !run_query.skip_preprocessor.unwrap_or(false)
+                        && has_preprocessor.unwrap_or(false),
+                },
+                PushArgs { args: &args.args, extra: args.extra },
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                scheduled_for,
+                None,
+                run_query.parent_job,
+                run_query.root_job,
+                run_query.job_id,
+                false,
+                false,
+                None,
+                !run_query.invisible_to_owner.unwrap_or(false),
+                tag,
+                timeout,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key.as_ref(), uuid).await?;
+            uuid
+        }
+    };
 
     Ok((uuid, delete_after_use))
 }
@@ -3,11 +3,13 @@
 pub use crate::kafka_triggers_ee::*;
 
 #[cfg(not(feature = "private"))]
-use crate::db::DB;
+use crate::{db::DB, triggers::handler::Trigger};
 #[cfg(not(feature = "private"))]
 use axum::Router;
 #[cfg(not(feature = "private"))]
 use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "private"))]
+use sqlx::FromRow;
 
 #[derive(Serialize, Deserialize)]
 #[cfg(not(feature = "private"))]
@@ -30,31 +32,13 @@ pub fn start_kafka_consumers(
 #[cfg(not(feature = "private"))]
 pub enum KafkaTriggerConfigConnection {}
 
-#[derive(Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, FromRow)]
 #[cfg(not(feature = "private"))]
-pub struct KafkaTrigger {
-    pub workspace_id: String,
-    pub path: String,
+pub struct KafkaConfig {
     pub kafka_resource_path: String,
     pub group_id: String,
     pub topics: Vec<String>,
-    pub script_path: String,
-    pub is_flow: bool,
-    pub edited_by: String,
-    pub email: String,
-    pub edited_at: chrono::DateTime<chrono::Utc>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub server_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
-    pub extra_perms: serde_json::Value,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_args: Option<sqlx::types::Json<std::collections::HashMap<String, Box<serde_json::value::RawValue>>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub retry: Option<sqlx::types::Json<windmill_common::flows::Retry>>,
 }
+
+#[cfg(not(feature = "private"))]
+pub type KafkaTrigger = Trigger<KafkaConfig>;
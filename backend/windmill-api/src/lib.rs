@@ -70,6 +70,7 @@ pub mod agent_workers_ee;
 #[cfg(feature = "agent_worker_server")]
 mod agent_workers_oss;
 mod ai;
+mod ai_usage;
 mod apps;
 pub mod args;
 mod assets;
@@ -84,6 +85,7 @@ mod drafts;
 pub mod ee;
 pub mod ee_oss;
 pub mod embeddings;
+mod environments;
 mod favorite;
 mod flows;
 mod folders;
@@ -94,6 +96,8 @@ mod http_trigger_args;
 #[cfg(feature = "http_trigger")]
 mod http_trigger_auth;
 #[cfg(feature = "http_trigger")]
+mod http_trigger_cache;
+#[cfg(feature = "http_trigger")]
 pub mod http_triggers;
 #[cfg(feature = "private")]
 pub mod indexer_ee;
@@ -121,6 +125,7 @@ mod gcp_triggers_oss;
 pub mod git_sync_ee;
 #[cfg(feature = "enterprise")]
 mod git_sync_oss;
+mod git_sync_pull;
 #[cfg(all(feature = "parquet", feature = "private"))]
 pub mod job_helpers_ee;
 #[cfg(feature = "parquet")]
@@ -133,6 +138,14 @@ pub mod kafka_triggers_ee;
 mod kafka_triggers_oss;
 #[cfg(feature = "mqtt_trigger")]
 mod mqtt_triggers;
+#[cfg(feature = "mysql_trigger")]
+mod mysql_triggers;
+#[cfg(feature = "amqp_trigger")]
+mod amqp_triggers;
+#[cfg(feature = "redis_trigger")]
+mod redis_triggers;
+#[cfg(feature = "s3_trigger")]
+mod s3_triggers;
 #[cfg(all(feature = "enterprise", feature = "nats", feature = "private"))]
 pub mod nats_triggers_ee;
 #[cfg(all(feature = "enterprise", feature = "nats"))]
@@ -143,8 +156,12 @@ pub mod oauth2_ee;
 pub mod oauth2_oss;
 #[cfg(feature = "private")]
 pub mod oidc_ee;
+mod object_history;
 mod oidc_oss;
+mod promotions;
+mod quotas;
 mod raw_apps;
+mod rate_limits;
 mod resources;
 #[cfg(feature = "private")]
 pub mod saml_ee;
@@ -169,6 +186,7 @@ mod sqs_triggers_oss;
 #[cfg(feature = "private")]
 pub mod teams_approvals_ee;
 mod teams_approvals_oss;
+mod trigger_dead_letters;
 mod trigger_helpers;
 
 mod static_assets;
@@ -192,15 +210,19 @@ pub mod webhook_util;
 #[cfg(feature = "websocket")]
 mod websocket_triggers;
 mod workers;
+mod workspace_forks;
 mod workspaces;
 #[cfg(feature = "private")]
 pub mod workspaces_ee;
 mod workspaces_export;
 mod workspaces_extra;
+mod workspaces_import;
 mod workspaces_oss;
 
 #[cfg(feature = "mcp")]
 mod mcp;
+#[cfg(feature = "mcp")]
+mod mcp_resources;
 
 pub const DEFAULT_BODY_LIMIT: usize = 2097152 * 100; // 200MB
 
@@ -414,6 +436,54 @@ pub async fn run_server(
         }
     };
 
+    let mysql_triggers_service = {
+        #[cfg(feature = "mysql_trigger")]
+        {
+            mysql_triggers::workspaced_service()
+        }
+
+        #[cfg(not(feature = "mysql_trigger"))]
+        {
+            Router::new()
+        }
+    };
+
+    let amqp_triggers_service = {
+        #[cfg(feature = "amqp_trigger")]
+        {
+            amqp_triggers::workspaced_service()
+        }
+
+        #[cfg(not(feature = "amqp_trigger"))]
+        {
+            Router::new()
+        }
+    };
+
+    let redis_triggers_service = {
+        #[cfg(feature = "redis_trigger")]
+        {
+            redis_triggers::workspaced_service()
+        }
+
+        #[cfg(not(feature = "redis_trigger"))]
+        {
+            Router::new()
+        }
+    };
+
+    let s3_triggers_service = {
+        #[cfg(feature = "s3_trigger")]
+        {
+            s3_triggers::workspaced_service()
+        }
+
+        #[cfg(not(feature = "s3_trigger"))]
+        {
+            Router::new()
+        }
+    };
+
     let gcp_triggers_service = {
         #[cfg(all(feature = "enterprise", feature = "gcp_trigger"))]
         {
@@ -478,7 +548,10 @@ pub async fn run_server(
         #[cfg(feature = "websocket")]
         {
             let ws_killpill_rx = killpill_rx.resubscribe();
-            websocket_triggers::start_websockets(db.clone(), ws_killpill_rx);
+            triggers::listener::start_listener::<websocket_triggers::WebsocketTriggerHandler>(
+                db.clone(),
+                ws_killpill_rx,
+            );
         }
 
         #[cfg(all(feature = "enterprise", feature = "kafka"))]
@@ -496,13 +569,52 @@ pub async fn run_server(
         #[cfg(feature = "postgres_trigger")]
         {
             let db_killpill_rx = killpill_rx.resubscribe();
-            postgres_triggers::start_database(db.clone(), db_killpill_rx);
+            triggers::listener::start_listener::<postgres_triggers::PostgresTriggerHandler>(
+                db.clone(),
+                db_killpill_rx,
+            );
         }
 
         #[cfg(feature = "mqtt_trigger")]
         {
             let mqtt_killpill_rx = killpill_rx.resubscribe();
-            mqtt_triggers::start_mqtt_consumer(db.clone(), mqtt_killpill_rx);
+            triggers::listener::start_listener::<mqtt_triggers::MqttTriggerHandler>(
+                db.clone(),
+                mqtt_killpill_rx,
+            );
+        }
+
+        #[cfg(feature = "mysql_trigger")]
+        {
+            let mysql_killpill_rx = killpill_rx.resubscribe();
+            mysql_triggers::start_mysql_binlog_consumer(db.clone(), mysql_killpill_rx);
+        }
+
+        #[cfg(feature = "amqp_trigger")]
+        {
+            let amqp_killpill_rx = killpill_rx.resubscribe();
+            triggers::listener::start_listener::<amqp_triggers::AmqpTriggerHandler>(
+                db.clone(),
+                amqp_killpill_rx,
+            );
+        }
+
+        #[cfg(feature = "redis_trigger")]
+        {
+            let redis_killpill_rx = killpill_rx.resubscribe();
+            triggers::listener::start_listener::<redis_triggers::RedisTriggerHandler>(
+                db.clone(),
+                redis_killpill_rx,
+            );
+        }
+
+        #[cfg(feature = "s3_trigger")]
+        {
+            let s3_killpill_rx = killpill_rx.resubscribe();
+            triggers::listener::start_listener::<s3_triggers::S3TriggerHandler>(
+                db.clone(),
+                s3_killpill_rx,
+            );
         }
 
         #[cfg(all(feature = "enterprise", feature = "sqs_trigger"))]
@@ -518,6 +630,14 @@ pub async fn run_server(
         }
     }
 
+    if server_mode && !mcp_mode {
+        let dead_letter_killpill_rx = killpill_rx.resubscribe();
+        trigger_dead_letters::start_dead_letter_sweeper(db.clone(), dead_letter_killpill_rx);
+
+        let git_sync_pull_killpill_rx = killpill_rx.resubscribe();
+        git_sync_pull::start_git_sync_puller(db.clone(), git_sync_pull_killpill_rx);
+    }
+
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .context("binding main windmill server")?;
@@ -609,8 +729,22 @@ pub async fn run_server(
                         .nest("/kafka_triggers", kafka_triggers_service)
                         .nest("/nats_triggers", nats_triggers_service)
                         .nest("/mqtt_triggers", mqtt_triggers_service)
+                        .nest("/mysql_triggers", mysql_triggers_service)
+                        .nest("/amqp_triggers", amqp_triggers_service)
+                        .nest("/redis_triggers", redis_triggers_service)
+                        .nest("/s3_triggers", s3_triggers_service)
                         .nest("/sqs_triggers", sqs_triggers_service)
                         .nest("/gcp_triggers", gcp_triggers_service)
+                        .nest(
+                            "/trigger_dead_letters",
+                            trigger_dead_letters::workspaced_service(),
+                        )
+                        .nest("/promotions", promotions::workspaced_service())
+                        .nest("/git_sync", git_sync_pull::workspaced_service())
+                        .nest("/object_history", object_history::workspaced_service())
+                        .nest("/environments", environments::workspaced_service())
+                        .nest("/quotas", quotas::workspaced_service())
+                        .nest("/forks", workspace_forks::workspaced_service())
                     .nest("/postgres_triggers", postgres_triggers_service),
                 )
                 .nest("/workspaces", workspaces::global_service())
@@ -629,6 +763,7 @@ pub async fn run_server(
                 .nest("/apps", apps::global_service().layer(cors.clone()))
                 .nest("/schedules", schedule::global_service())
                 .nest("/embeddings", embeddings::global_service())
+                .nest("/quotas", quotas::global_service())
                 .nest("/ai", ai::global_service())
                 .nest("/inkeep", inkeep_oss::global_service())
                 .route_layer(from_extractor::<ApiAuthed>())
@@ -804,7 +939,11 @@ pub async fn run_server(
         )
     };
 
-    let server = axum::serve(listener, app.into_make_service());
+    // the address of the peer is the client ip of the rate limits when there is no trusted proxy
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    );
 
     tracing::info!(
         instance = %*INSTANCE_NAME,
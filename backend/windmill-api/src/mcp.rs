@@ -16,60 +16,28 @@ use serde_json::Value;
 use sql_builder::prelude::*;
 use sqlx::FromRow;
 use tokio::try_join;
+use windmill_common::ai_tools::{
+    convert_schema_to_schema_type, schema_resource_types, tool_args_to_runnable_args,
+    tool_description, tool_input_schema, transform_path, transform_schema_for_resources,
+    ResourceInfo, ResourceType, SchemaType,
+};
 use windmill_common::db::UserDB;
-use windmill_common::worker::to_raw_value;
 use windmill_common::{DB, HUB_BASE_URL};
 
 use windmill_common::scripts::{get_full_hub_script_by_path, Schema};
 
 use crate::db::ApiAuthed;
 use crate::jobs::{
-    run_wait_result_flow_by_path_internal, run_wait_result_script_by_path_internal, RunJobQuery,
+    run_flow_by_path_inner, run_script_by_path_inner, run_wait_result_flow_by_path_internal,
+    run_wait_result_internal, run_wait_result_script_by_path_internal, RunJobQuery,
 };
+use crate::mcp_resources::{self, McpContext};
 use crate::HTTP_CLIENT;
 use rmcp::transport::streamable_http_server::{
     session::local::LocalSessionManager, SessionManager, StreamableHttpService,
 };
 use windmill_common::utils::{query_elems_from_hub, StripPath};
 
-/// Transforms the path for workspace scripts/flows.
-///
-/// This function takes a path and a type string.
-/// It then formats the transformed path with the type prefix.
-/// This is used when listing, because we can't have names with slashes.
-/// Because we replace slashes with underscores, we also need to escape underscores.
-///
-/// # Parameters
-/// - `path`: The path to transform.
-/// - `type_str`: The type of the item (script or flow).
-///
-/// # Returns
-/// - `String`: The transformed path.
-fn transform_path(path: &str, type_str: &str) -> String {
-    // Only apply special underscore escaping for paths starting with "f/"
-    let transformed = if path.starts_with("f/") {
-        let escaped_path = path.replace('_', "__");
-        escaped_path.replace('/', "_")
-    } else {
-        path.replace('/', "_")
-    };
-
-    // first letter of type_str is used as prefix, only one letter to avoid reaching 60 char name limit
-    format!("{}-{}", &type_str[..1], transformed)
-}
-
-fn convert_schema_to_schema_type(schema: Option<Schema>) -> SchemaType {
-    let schema_obj = if let Some(ref s) = schema {
-        match serde_json::from_str::<SchemaType>(s.0.get()) {
-            Ok(val) => val,
-            Err(_) => SchemaType::default(),
-        }
-    } else {
-        SchemaType::default()
-    };
-    schema_obj
-}
-
 trait ToolableItem {
     fn get_path_or_id(&self) -> String;
     fn get_summary(&self) -> &str;
@@ -174,23 +142,6 @@ struct HubScriptInfo {
     app: Option<String>,
 }
 
-#[derive(Serialize, FromRow, Deserialize, Debug, Clone)]
-struct SchemaType {
-    r#type: String,
-    properties: std::collections::HashMap<String, serde_json::Value>,
-    required: Vec<String>,
-}
-
-impl Default for SchemaType {
-    fn default() -> Self {
-        Self {
-            r#type: "object".to_string(),
-            properties: std::collections::HashMap::new(),
-            required: vec![],
-        }
-    }
-}
-
 #[derive(Serialize, FromRow, Debug)]
 struct ScriptInfo {
     path: String,
@@ -212,19 +163,6 @@ struct FlowInfo {
     schema: Option<Schema>,
 }
 
-#[derive(Serialize, FromRow, Debug)]
-struct ResourceInfo {
-    path: String,
-    description: Option<String>,
-    resource_type: String,
-}
-
-#[derive(Serialize, FromRow, Debug, Clone)]
-struct ResourceType {
-    name: String,
-    description: Option<String>,
-}
-
 impl Runner {
     pub fn new() -> Self {
         Self {}
@@ -466,109 +404,10 @@ impl Runner {
         Ok(hub_response.asks)
     }
 
-    /// Transforms a value if it's an object.
-    ///
-    /// This function takes a key and a value, and a schema object.
-    /// If the value is a string that starts with "$res:", it returns the value as is.
-    /// Otherwise, it checks if the key is defined in the schema and if it's an object type.
-    /// If it is, it transforms the value to a string. This is because some clients do not support object types.
-    /// # Parameters
-    /// - `key`: The key of the value to transform.
-    /// - `value`: The value to transform.
-    /// - `schema_obj`: The schema object.
-    ///
-    /// # Returns
-    /// - `Value`: The transformed value.
-    fn transform_value_if_object(
-        key: &str,
-        value: &Value,
-        schema_obj: &Option<SchemaType>,
-    ) -> Value {
-        if value.is_string() && value.as_str().unwrap().starts_with("$res:") {
-            return value.clone();
-        }
-
-        let schema_obj = match schema_obj {
-            Some(s) => s,
-            None => return value.clone(),
-        };
-
-        // Check if property is defined in schema and is an object type
-        let is_obj_type = match schema_obj.properties.get(key) {
-            Some(property) => {
-                let prop_type = property.get("type").and_then(|t| t.as_str());
-                prop_type == Some("object")
-            }
-            None => false,
-        };
-
-        // If it's an object type and we received a string, try to parse it
-        if is_obj_type && value.is_string() {
-            if let Some(str_val) = value.as_str() {
-                if let Ok(obj_val) = serde_json::from_str::<serde_json::Value>(str_val) {
-                    return obj_val;
-                }
-            }
-        }
-
-        value.clone()
-    }
-
-    /// Reverses the transformation of a key.
-    ///
-    /// This function takes a transformed key and a schema object.
-    /// It then reverses the transformation applied by `apply_key_transformation`. This can be subject to collisions, but it's unlikely and is ok for our use case.
-    /// # Parameters
-    /// - `transformed_key`: The transformed key to reverse.
-    /// - `schema_obj`: The schema object.
-    ///
-    /// # Returns
-    /// - `String`: The original key.
-    fn reverse_transform_key(transformed_key: &str, schema_obj: &Option<SchemaType>) -> String {
-        let schema_obj = match schema_obj {
-            Some(s) => s,
-            None => {
-                // No schema available, return the key as is (best guess)
-                return transformed_key.to_string();
-            }
-        };
-
-        for original_key_in_schema in schema_obj.properties.keys() {
-            // Apply the SAME forward transformation to the schema key
-            let potential_transformed_key =
-                Runner::apply_key_transformation(original_key_in_schema);
-
-            // If it matches the key we received, we found the likely original
-            if potential_transformed_key == transformed_key {
-                return original_key_in_schema.clone();
-            }
-        }
-
-        transformed_key.to_string()
-    }
-
-    /// Applies a key transformation to a key.
-    ///
-    /// This function takes a key and replaces spaces with underscores.
-    /// It also removes any characters that are not alphanumeric or underscores.
-    /// This is used when listing, because we can't have names with spaces or special characters in the schema properties.
-    /// # Parameters
-    /// - `key`: The key to transform.
-    ///
-    /// # Returns
-    /// - `String`: The transformed key.
-    fn apply_key_transformation(key: &str) -> String {
-        key.replace(' ', "_")
-            .chars()
-            .filter(|c| c.is_alphanumeric() || *c == '_')
-            .collect::<String>()
-    }
-
     /// Transforms the schema for resources.
     ///
-    /// This function takes a schema and a database connection, and attempts to transform the schema for resources.
-    /// It replaces invalid characters in property keys with underscores and converts object properties to strings.
-    /// It also fetches resource type information and adds it to the description of resource properties.
+    /// This function fetches the available resources of the resource types used by the schema
+    /// into the cache, then transforms the schema with `transform_schema_for_resources`.
     ///
     /// # Parameters
     /// - `schema`: The schema to transform.
@@ -589,138 +428,28 @@ impl Runner {
         resources_cache: &mut HashMap<String, Vec<ResourceInfo>>,
         resources_types: &Vec<ResourceType>,
     ) -> Result<SchemaType, Error> {
-        let mut schema_obj: SchemaType = schema.clone();
-
-        // replace invalid char in property key with underscore
-        let replacements: Vec<(String, String, serde_json::Value)> = schema_obj
-            .properties
-            .iter()
-            .filter_map(|(key, value)| {
-                if key.chars().any(|c| !c.is_alphanumeric() && c != '_') {
-                    let new_key = Runner::apply_key_transformation(key);
-                    Some((key.clone(), new_key, value.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        for (old_key, new_key, value) in replacements {
-            schema_obj.properties.remove(&old_key);
-            schema_obj.properties.insert(new_key, value);
-        }
-
-        for (_key, prop_value) in schema_obj.properties.iter_mut() {
-            if let serde_json::Value::Object(prop_map) = prop_value {
-                // transform object properties to string because some client does not support object, might change in the future
-                if let Some(type_value) = prop_map.get("type") {
-                    if let serde_json::Value::String(type_str) = type_value {
-                        if type_str == "object" {
-                            prop_map.insert(
-                                "type".to_string(),
-                                serde_json::Value::String("string".to_string()),
-                            );
-                        }
+        for resource_type_key in schema_resource_types(schema) {
+            if !resources_cache.contains_key(&resource_type_key) {
+                let available_resources =
+                    Runner::inner_get_resources(user_db, authed, &w_id, &resource_type_key).await;
+
+                match available_resources {
+                    Ok(cache_data) => {
+                        resources_cache.insert(resource_type_key, cache_data);
                     }
-                }
-                // if property is a resource, fetch the resource type infos, and add each available resource to the description
-                if let Some(format_value) = prop_map.get("format") {
-                    if let serde_json::Value::String(format_str) = format_value {
-                        if format_str.starts_with("resource-") {
-                            let resource_type_key =
-                                format_str.split("-").last().unwrap_or_default().to_string();
-                            let resource_type = resources_types
-                                .iter()
-                                .find(|rt| rt.name == resource_type_key);
-                            let resource_type_obj = resource_type.cloned().unwrap_or_else(|| {
-                                tracing::info!("Resource type not found: {}", resource_type_key);
-                                ResourceType { name: resource_type_key.clone(), description: None }
-                            });
-
-                            if !resources_cache.contains_key(&resource_type_key) {
-                                let available_resources = Runner::inner_get_resources(
-                                    user_db,
-                                    authed,
-                                    &w_id,
-                                    &resource_type_key,
-                                )
-                                .await;
-
-                                match available_resources {
-                                    Ok(cache_data) => {
-                                        resources_cache
-                                            .insert(resource_type_key.clone(), cache_data);
-                                    }
-                                    Err(e) => {
-                                        tracing::error!(
-                                            "Failed to fetch resource cache data: {}",
-                                            e
-                                        );
-                                        continue; // Skip this property if fetching failed
-                                    }
-                                }
-                            }
-
-                            if let Some(resource_cache) = resources_cache.get(&resource_type_key) {
-                                let resources_count = resource_cache.len();
-                                let description = format!(
-                                    "This is a resource named `{}` with the following description: `{}`.\nThe path of the resource should be used to specify the resource.\n{}",
-                                    resource_type_obj.name,
-                                    resource_type_obj.description.as_deref().unwrap_or("No description"),
-                                    if resources_count == 0 {
-                                        "This resource does not have any available instances, you should create one from your windmill workspace."
-                                    } else if resources_count > 1 {
-                                        "This resource has multiple available instances, you should precisely select the one you want to use."
-                                    } else {
-                                        "There is 1 resource available."
-                                    }
-                                );
-                                prop_map.insert(
-                                    "type".to_string(),
-                                    serde_json::Value::String("string".to_string()),
-                                );
-                                prop_map.insert(
-                                    "description".to_string(),
-                                    serde_json::Value::String(description),
-                                );
-                                if resources_count > 0 {
-                                    let resources_description = resource_cache
-                                        .iter()
-                                        .map(|resource| {
-                                            format!(
-                                                "{}: $res:{}",
-                                                resource
-                                                    .description
-                                                    .as_deref()
-                                                    .unwrap_or("No title"),
-                                                resource.path
-                                            )
-                                        })
-                                        .collect::<Vec<String>>()
-                                        .join("\n");
-
-                                    prop_map.insert(
-                                        "description".to_string(),
-                                        serde_json::Value::String(format!(
-                                            "{}\nHere are the available resources, in the format title:path. Title can be empty. Path should be used to specify the resource:\n{}",
-                                            prop_map.get("description").unwrap_or(&serde_json::Value::String("No description".to_string())),
-                                            resources_description
-                                        )),
-                                    );
-                                }
-                            }
-                        }
+                    Err(e) => {
+                        // the property is left as is
+                        tracing::error!("Failed to fetch resource cache data: {}", e);
                     }
                 }
-            } else {
-                tracing::warn!(
-                    "Schema property value is not a JSON object: {:?}",
-                    prop_value
-                );
             }
         }
 
-        Ok(schema_obj)
+        Ok(transform_schema_for_resources(
+            schema,
+            resources_cache,
+            resources_types,
+        ))
     }
 
     /// Fetches the schema for a Hub script.
@@ -780,20 +509,19 @@ impl Runner {
         let is_hub = item.is_hub();
         let path = item.get_path_or_id();
         let item_type = item.item_type();
-        let description = format!(
-            "This is a {} named `{}` with the following description: `{}`.{}",
+        let hub_app = if is_hub {
+            Some(
+                item.get_integration_type()
+                    .unwrap_or("No integration type".to_string()),
+            )
+        } else {
+            None
+        };
+        let description = tool_description(
             item_type,
             item.get_summary(),
             item.get_description(),
-            if is_hub {
-                format!(
-                    " It is a tool used for the following app: {}",
-                    item.get_integration_type()
-                        .unwrap_or("No integration type".to_string())
-                )
-            } else {
-                "".to_string()
-            }
+            hub_app.as_deref(),
         );
         let schema_obj = Runner::transform_schema_for_resources(
             &item.get_schema(),
@@ -804,21 +532,7 @@ impl Runner {
             &resources_types,
         )
         .await?;
-        let input_schema_map = match serde_json::to_value(schema_obj) {
-            Ok(Value::Object(map)) => map,
-            Ok(_) => {
-                tracing::warn!("Schema object for tool '{}' did not serialize to a JSON object, using empty schema.", path);
-                serde_json::Map::new()
-            }
-            Err(e) => {
-                tracing::error!(
-                    "Failed to serialize schema object for tool '{}': {}. Using empty schema.",
-                    path,
-                    e
-                );
-                serde_json::Map::new()
-            }
-        };
+        let input_schema_map = tool_input_schema(schema_obj, &path);
         Ok(Tool {
             name: Cow::Owned(path),
             description: Some(Cow::Owned(description)),
@@ -826,6 +540,80 @@ impl Runner {
             annotations: None,
         })
     }
+
+    /// Waits for the result of a job pushed by a tool call, sending a progress notification
+    /// every few seconds with the current flow step or the progress reported by the script.
+    /// The job is cancelled if the client cancels the request.
+    async fn wait_job_with_progress(
+        db: &DB,
+        workspace_id: &str,
+        uuid: uuid::Uuid,
+        username: &str,
+        progress_token: ProgressToken,
+        context: &RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, Error> {
+        let wait_result =
+            run_wait_result_internal(db, uuid, workspace_id.to_string(), None, username);
+        tokio::pin!(wait_result);
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(2));
+
+        loop {
+            tokio::select! {
+                result = &mut wait_result => {
+                    let (result, success) = result.map_err(|e| {
+                        Error::internal_error(format!("Failed to wait for job {uuid}: {e}"), None)
+                    })?;
+                    let content = vec![Content::text(result.get().to_string())];
+                    return Ok(if success {
+                        CallToolResult::success(content)
+                    } else {
+                        CallToolResult::error(content)
+                    });
+                }
+                _ = context.ct.cancelled() => {
+                    let cancel = async {
+                        let (tx, _) = windmill_queue::cancel_job(
+                            username,
+                            Some("cancelled by the MCP client".to_string()),
+                            uuid,
+                            workspace_id,
+                            db.begin().await?,
+                            db,
+                            false,
+                            false,
+                        )
+                        .await?;
+                        tx.commit().await?;
+                        Ok::<_, windmill_common::error::Error>(())
+                    };
+                    if let Err(e) = cancel.await {
+                        tracing::error!("Failed to cancel job {uuid} after MCP request cancellation: {e}");
+                    }
+                    return Err(Error::internal_error(format!("Job {uuid} was cancelled"), None));
+                }
+                _ = interval.tick() => {
+                    match mcp_resources::job_progress(db, workspace_id, uuid).await {
+                        Ok(Some((progress, total, message))) => {
+                            if let Err(e) = context
+                                .peer
+                                .notify_progress(ProgressNotificationParam {
+                                    progress_token: progress_token.clone(),
+                                    progress,
+                                    total,
+                                    message: Some(message),
+                                })
+                                .await
+                            {
+                                tracing::warn!("Failed to send MCP progress for job {uuid}: {e}");
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => tracing::warn!("Failed to fetch progress of job {uuid}: {e}"),
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl ServerHandler for Runner {
@@ -916,16 +704,10 @@ impl ServerHandler for Runner {
         };
 
         let push_args = if let Value::Object(map) = args.clone() {
-            let mut args_hash = HashMap::new();
-            for (k, v) in map {
-                // need to transform back the key without invalid characters to the original key
-                let original_key = Runner::reverse_transform_key(&k, &schema_obj);
-
-                // object properties are transformed to string because some client does not support object, might change in the future
-                let transformed_v = Runner::transform_value_if_object(&k, &v, &schema_obj);
-                args_hash.insert(original_key, to_raw_value(&transformed_v));
+            windmill_queue::PushArgsOwned {
+                extra: None,
+                args: tool_args_to_runnable_args(map, &schema_obj),
             }
-            windmill_queue::PushArgsOwned { extra: None, args: args_hash }
         } else {
             windmill_queue::PushArgsOwned::default()
         };
@@ -936,6 +718,46 @@ impl ServerHandler for Runner {
         };
         let run_query = RunJobQuery::default();
 
+        // when the client asks for progress, the job is pushed asynchronously and its progress is
+        // reported until it completes or the request is cancelled
+        if let Some(progress_token) = context.meta.get_progress_token() {
+            let uuid = if tool_type == "script" {
+                run_script_by_path_inner(
+                    authed.clone(),
+                    db.clone(),
+                    user_db.clone(),
+                    workspace_id.clone(),
+                    script_or_flow_path,
+                    run_query,
+                    push_args,
+                )
+                .await
+                .map(|(uuid, _)| uuid)
+            } else {
+                run_flow_by_path_inner(
+                    authed.clone(),
+                    db.clone(),
+                    user_db.clone(),
+                    workspace_id.clone(),
+                    script_or_flow_path,
+                    run_query,
+                    push_args,
+                )
+                .await
+            }
+            .map_err(|e| Error::internal_error(format!("Failed to run {tool_type}: {e}"), None))?;
+
+            return Runner::wait_job_with_progress(
+                db,
+                &workspace_id,
+                uuid,
+                &authed.username,
+                progress_token,
+                &context,
+            )
+            .await;
+        }
+
         let result = if tool_type == "script" {
             run_wait_result_script_by_path_internal(
                 db.clone(),
@@ -1128,9 +950,11 @@ impl ServerHandler for Runner {
             capabilities: ServerCapabilities::builder()
                 .enable_tools()
                 .enable_tool_list_changed()
+                .enable_resources()
+                .enable_prompts()
                 .build(),
             server_info: Implementation::from_build_env(),
-            instructions: Some("This server provides a list of scripts and flows the user can run on Windmill. Each flow and script is a tool callable with their respective arguments.".to_string()),
+            instructions: Some("This server provides a list of scripts and flows the user can run on Windmill. Each flow and script is a tool callable with their respective arguments. Non-secret variables, resource metadata, flow definitions and the results and logs of previous jobs can be read as resources, and scripts with a description are exposed as prompts.".to_string()),
         }
     }
 
@@ -1145,17 +969,37 @@ impl ServerHandler for Runner {
     async fn list_resources(
         &self,
         _request: Option<PaginatedRequestParam>,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> Result<ListResourcesResult, Error> {
-        Ok(ListResourcesResult { resources: vec![], next_cursor: None })
+        let ctx = McpContext::from_request(&context)?;
+        mcp_resources::list_resources(&ctx).await
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, Error> {
+        let ctx = McpContext::from_request(&context)?;
+        mcp_resources::read_resource(&ctx, &request.uri).await
     }
 
     async fn list_prompts(
         &self,
         _request: Option<PaginatedRequestParam>,
-        _context: RequestContext<RoleServer>,
+        context: RequestContext<RoleServer>,
     ) -> Result<ListPromptsResult, Error> {
-        Ok(ListPromptsResult::default())
+        let ctx = McpContext::from_request(&context)?;
+        mcp_resources::list_prompts(&ctx).await
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, Error> {
+        let ctx = McpContext::from_request(&context)?;
+        mcp_resources::get_prompt(&ctx, request).await
     }
 
     async fn list_resource_templates(
@@ -1163,7 +1007,7 @@ impl ServerHandler for Runner {
         _request: Option<PaginatedRequestParam>,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListResourceTemplatesResult, Error> {
-        Ok(ListResourceTemplatesResult::default())
+        Ok(mcp_resources::list_resource_templates())
     }
 }
 
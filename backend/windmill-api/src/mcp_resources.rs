@@ -0,0 +1,710 @@
+//! Read-only MCP resources and prompts of a workspace.
+//!
+//! Resources are addressed with `windmill://` uris:
+//! - `windmill://variables/{path}`: value of a non-secret variable
+//! - `windmill://resources/{path}`: metadata of a resource, its value is never exposed
+//! - `windmill://flows/{path}`: definition of a flow, including its steps
+//! - `windmill://jobs/{id}`: status and result of a job, with the status of each step for flows
+//! - `windmill://jobs/{id}/logs`: logs of a job
+//!
+//! Prompts are built from the description of the scripts of the workspace.
+
+use rmcp::{
+    model::*,
+    service::{RequestContext, RoleServer},
+    Error,
+};
+use serde_json::{json, Value};
+use sqlx::FromRow;
+use uuid::Uuid;
+use windmill_common::{ai_tools::transform_path, db::UserDB, DB};
+
+use crate::db::ApiAuthed;
+use crate::mcp::WorkspaceId;
+
+const URI_SCHEME: &str = "windmill://";
+const LIST_LIMIT: i64 = 100;
+const JOB_HISTORY_LIMIT: i64 = 20;
+
+pub(crate) struct McpContext<'a> {
+    pub user_db: &'a UserDB,
+    pub authed: &'a ApiAuthed,
+    pub workspace_id: String,
+}
+
+impl<'a> McpContext<'a> {
+    pub fn from_request(context: &'a RequestContext<RoleServer>) -> Result<Self, Error> {
+        let http_parts = context
+            .extensions
+            .get::<axum::http::request::Parts>()
+            .ok_or_else(|| {
+                tracing::error!("http::request::Parts not found");
+                Error::internal_error("http::request::Parts not found", None)
+            })?;
+        let user_db = http_parts.extensions.get::<UserDB>().ok_or_else(|| {
+            tracing::error!("UserDB Axum extension not found");
+            Error::internal_error("UserDB Axum extension not found", None)
+        })?;
+        let authed = http_parts.extensions.get::<ApiAuthed>().ok_or_else(|| {
+            tracing::error!("ApiAuthed Axum extension not found");
+            Error::internal_error("ApiAuthed Axum extension not found", None)
+        })?;
+        let workspace_id = http_parts
+            .extensions
+            .get::<WorkspaceId>()
+            .ok_or_else(|| {
+                tracing::error!("WorkspaceId not found");
+                Error::internal_error("WorkspaceId not found", None)
+            })?
+            .0
+            .clone();
+        Ok(Self { user_db, authed, workspace_id })
+    }
+
+    /// Runs a query with the permissions of the user so that only the items they can see are returned
+    async fn fetch_all<T>(
+        &self,
+        query: sqlx::query::QueryAs<'_, sqlx::Postgres, T, sqlx::postgres::PgArguments>,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: for<'r> FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+    {
+        let mut tx = self
+            .user_db
+            .clone()
+            .begin(self.authed)
+            .await
+            .map_err(|_e| Error::internal_error("failed to begin transaction", None))?;
+        let rows = query.fetch_all(&mut *tx).await.map_err(|e| {
+            tracing::error!("Failed to fetch MCP resources: {}", e);
+            Error::internal_error("failed to fetch resources", None)
+        })?;
+        tx.commit()
+            .await
+            .map_err(|_e| Error::internal_error("failed to commit transaction", None))?;
+        Ok(rows)
+    }
+}
+
+enum ResourceUri {
+    Variable(String),
+    Resource(String),
+    Flow(String),
+    Job(Uuid),
+    JobLogs(Uuid),
+}
+
+impl ResourceUri {
+    fn parse(uri: &str) -> Option<Self> {
+        let (kind, rest) = uri.strip_prefix(URI_SCHEME)?.split_once('/')?;
+        match kind {
+            "variables" => Some(Self::Variable(rest.to_string())),
+            "resources" => Some(Self::Resource(rest.to_string())),
+            "flows" => Some(Self::Flow(rest.to_string())),
+            "jobs" => match rest.split_once('/') {
+                Some((id, "logs")) => Uuid::parse_str(id).ok().map(Self::JobLogs),
+                None => Uuid::parse_str(rest).ok().map(Self::Job),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn resource(uri: String, name: String, description: Option<String>, mime_type: &str) -> Resource {
+    RawResource {
+        uri,
+        name,
+        description: description.filter(|d| !d.is_empty()),
+        mime_type: Some(mime_type.to_string()),
+        size: None,
+    }
+    .no_annotation()
+}
+
+fn resource_template(uri_template: &str, name: &str, description: &str) -> ResourceTemplate {
+    RawResourceTemplate {
+        uri_template: uri_template.to_string(),
+        name: name.to_string(),
+        description: Some(description.to_string()),
+        mime_type: Some("application/json".to_string()),
+    }
+    .no_annotation()
+}
+
+fn json_contents(uri: &str, value: &Value) -> Result<ReadResourceResult, Error> {
+    let text = serde_json::to_string_pretty(value)
+        .map_err(|e| Error::internal_error(format!("failed to serialize resource: {e}"), None))?;
+    Ok(ReadResourceResult {
+        contents: vec![ResourceContents::TextResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some("application/json".to_string()),
+            text,
+        }],
+    })
+}
+
+fn not_found(uri: &str) -> Error {
+    Error::resource_not_found(format!("resource not found: {uri}"), None)
+}
+
+#[derive(FromRow)]
+struct VariableRow {
+    path: String,
+    description: String,
+    value: String,
+}
+
+#[derive(FromRow)]
+struct ResourceRow {
+    path: String,
+    description: Option<String>,
+    resource_type: String,
+    created_by: String,
+    edited_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(FromRow)]
+struct FlowRow {
+    path: String,
+    summary: String,
+    description: String,
+    value: Option<Value>,
+    schema: Option<Value>,
+}
+
+#[derive(FromRow)]
+struct JobHistoryRow {
+    id: Uuid,
+    script_path: Option<String>,
+    success: bool,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(FromRow)]
+struct CompletedJobRow {
+    id: Uuid,
+    script_path: Option<String>,
+    job_kind: String,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    duration_ms: i64,
+    success: bool,
+    result: Option<Value>,
+    flow_status: Option<Value>,
+}
+
+#[derive(FromRow)]
+struct QueuedJobRow {
+    id: Uuid,
+    script_path: Option<String>,
+    job_kind: String,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    started_at: Option<chrono::DateTime<chrono::Utc>>,
+    running: bool,
+    flow_status: Option<Value>,
+}
+
+#[derive(FromRow)]
+struct ScriptPromptRow {
+    path: String,
+    summary: String,
+    description: String,
+    schema: Option<Value>,
+}
+
+pub(crate) async fn list_resources(ctx: &McpContext<'_>) -> Result<ListResourcesResult, Error> {
+    let w_id = ctx.workspace_id.as_str();
+
+    let variables = ctx
+        .fetch_all(
+            sqlx::query_as::<_, VariableRow>(
+                "SELECT path, description, value FROM variable
+                WHERE workspace_id = $1 AND is_secret IS FALSE
+                ORDER BY path LIMIT $2",
+            )
+            .bind(w_id)
+            .bind(LIST_LIMIT),
+        )
+        .await?;
+    let resources = ctx
+        .fetch_all(
+            sqlx::query_as::<_, ResourceRow>(
+                "SELECT path, description, resource_type, created_by, edited_at FROM resource
+                WHERE workspace_id = $1 AND resource_type != 'cache'
+                ORDER BY path LIMIT $2",
+            )
+            .bind(w_id)
+            .bind(LIST_LIMIT),
+        )
+        .await?;
+    let flows = ctx
+        .fetch_all(
+            sqlx::query_as::<_, FlowRow>(
+                "SELECT path, summary, description, NULL::jsonb AS value, NULL::jsonb AS schema FROM flow
+                WHERE workspace_id = $1 AND archived = false AND draft_only IS NOT TRUE
+                ORDER BY edited_at DESC LIMIT $2",
+            )
+            .bind(w_id)
+            .bind(LIST_LIMIT),
+        )
+        .await?;
+    let jobs = ctx
+        .fetch_all(
+            sqlx::query_as::<_, JobHistoryRow>(
+                "SELECT id, script_path, success, created_at FROM v2_as_completed_job
+                WHERE workspace_id = $1 AND created_by = $2
+                ORDER BY created_at DESC LIMIT $3",
+            )
+            .bind(w_id)
+            .bind(&ctx.authed.username)
+            .bind(JOB_HISTORY_LIMIT),
+        )
+        .await?;
+
+    let mut list = Vec::with_capacity(variables.len() + resources.len() + flows.len() + jobs.len());
+    list.extend(variables.into_iter().map(|v| {
+        resource(
+            format!("{URI_SCHEME}variables/{}", v.path),
+            format!("variable {}", v.path),
+            Some(v.description),
+            "text/plain",
+        )
+    }));
+    list.extend(resources.into_iter().map(|r| {
+        resource(
+            format!("{URI_SCHEME}resources/{}", r.path),
+            format!("{} resource {}", r.resource_type, r.path),
+            r.description,
+            "application/json",
+        )
+    }));
+    list.extend(flows.into_iter().map(|f| {
+        resource(
+            format!("{URI_SCHEME}flows/{}", f.path),
+            format!("flow {}", f.path),
+            Some(if f.summary.is_empty() { f.description } else { f.summary }),
+            "application/json",
+        )
+    }));
+    list.extend(jobs.into_iter().map(|j| {
+        resource(
+            format!("{URI_SCHEME}jobs/{}", j.id),
+            format!(
+                "job {} of {}",
+                j.id,
+                j.script_path.as_deref().unwrap_or("preview")
+            ),
+            Some(format!(
+                "{} run started at {}",
+                if j.success { "successful" } else { "failed" },
+                j.created_at.to_rfc3339()
+            )),
+            "application/json",
+        )
+    }));
+
+    Ok(ListResourcesResult { resources: list, next_cursor: None })
+}
+
+pub(crate) fn list_resource_templates() -> ListResourceTemplatesResult {
+    ListResourceTemplatesResult {
+        resource_templates: vec![
+            resource_template(
+                "windmill://jobs/{job_id}",
+                "job",
+                "Status and result of a job, with the status of each step for flows",
+            ),
+            resource_template("windmill://jobs/{job_id}/logs", "job logs", "Logs of a job"),
+            resource_template(
+                "windmill://variables/{path}",
+                "variable",
+                "Value of a non-secret variable",
+            ),
+            resource_template(
+                "windmill://resources/{path}",
+                "resource",
+                "Metadata of a resource, without its value",
+            ),
+            resource_template(
+                "windmill://flows/{path}",
+                "flow",
+                "Definition of a flow, including its steps",
+            ),
+        ],
+        next_cursor: None,
+    }
+}
+
+pub(crate) async fn read_resource(
+    ctx: &McpContext<'_>,
+    uri: &str,
+) -> Result<ReadResourceResult, Error> {
+    let w_id = ctx.workspace_id.as_str();
+    let parsed = ResourceUri::parse(uri).ok_or_else(|| {
+        Error::invalid_params(format!("invalid resource uri: {uri}"), None)
+    })?;
+
+    match parsed {
+        ResourceUri::Variable(path) => {
+            let variable = ctx
+                .fetch_all(
+                    sqlx::query_as::<_, VariableRow>(
+                        "SELECT path, description, value FROM variable
+                        WHERE workspace_id = $1 AND path = $2 AND is_secret IS FALSE",
+                    )
+                    .bind(w_id)
+                    .bind(&path),
+                )
+                .await?
+                .pop()
+                .ok_or_else(|| not_found(uri))?;
+            Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: uri.to_string(),
+                    mime_type: Some("text/plain".to_string()),
+                    text: variable.value,
+                }],
+            })
+        }
+        ResourceUri::Resource(path) => {
+            let resource = ctx
+                .fetch_all(
+                    sqlx::query_as::<_, ResourceRow>(
+                        "SELECT path, description, resource_type, created_by, edited_at FROM resource
+                        WHERE workspace_id = $1 AND path = $2",
+                    )
+                    .bind(w_id)
+                    .bind(&path),
+                )
+                .await?
+                .pop()
+                .ok_or_else(|| not_found(uri))?;
+            json_contents(
+                uri,
+                &json!({
+                    "path": resource.path,
+                    "resource_type": resource.resource_type,
+                    "description": resource.description,
+                    "created_by": resource.created_by,
+                    "edited_at": resource.edited_at,
+                }),
+            )
+        }
+        ResourceUri::Flow(path) => {
+            let flow = ctx
+                .fetch_all(
+                    sqlx::query_as::<_, FlowRow>(
+                        "SELECT flow.path, flow.summary, flow.description, flow_version.value, flow_version.schema
+                        FROM flow
+                        LEFT JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+                        WHERE flow.workspace_id = $1 AND flow.path = $2 AND flow.archived = false",
+                    )
+                    .bind(w_id)
+                    .bind(&path),
+                )
+                .await?
+                .pop()
+                .ok_or_else(|| not_found(uri))?;
+            json_contents(
+                uri,
+                &json!({
+                    "path": flow.path,
+                    "summary": flow.summary,
+                    "description": flow.description,
+                    "schema": flow.schema,
+                    "value": flow.value,
+                }),
+            )
+        }
+        ResourceUri::Job(id) => {
+            let completed = ctx
+                .fetch_all(
+                    sqlx::query_as::<_, CompletedJobRow>(
+                        "SELECT id, script_path, job_kind::text AS job_kind, created_by, created_at, started_at,
+                            duration_ms, success, result, flow_status
+                        FROM v2_as_completed_job WHERE workspace_id = $1 AND id = $2",
+                    )
+                    .bind(w_id)
+                    .bind(id),
+                )
+                .await?
+                .pop();
+            if let Some(job) = completed {
+                return json_contents(
+                    uri,
+                    &json!({
+                        "id": job.id,
+                        "script_path": job.script_path,
+                        "job_kind": job.job_kind,
+                        "created_by": job.created_by,
+                        "created_at": job.created_at,
+                        "started_at": job.started_at,
+                        "duration_ms": job.duration_ms,
+                        "status": if job.success { "success" } else { "failure" },
+                        "result": job.result,
+                        "flow_status": job.flow_status,
+                    }),
+                );
+            }
+
+            let queued = ctx
+                .fetch_all(
+                    sqlx::query_as::<_, QueuedJobRow>(
+                        "SELECT id, script_path, job_kind::text AS job_kind, created_by, created_at, started_at,
+                            running, flow_status
+                        FROM v2_as_queue WHERE workspace_id = $1 AND id = $2",
+                    )
+                    .bind(w_id)
+                    .bind(id),
+                )
+                .await?
+                .pop()
+                .ok_or_else(|| not_found(uri))?;
+            json_contents(
+                uri,
+                &json!({
+                    "id": queued.id,
+                    "script_path": queued.script_path,
+                    "job_kind": queued.job_kind,
+                    "created_by": queued.created_by,
+                    "created_at": queued.created_at,
+                    "started_at": queued.started_at,
+                    "status": if queued.running { "running" } else { "queued" },
+                    "flow_status": queued.flow_status,
+                }),
+            )
+        }
+        ResourceUri::JobLogs(id) => {
+            let logs = ctx
+                .fetch_all(
+                    sqlx::query_as::<_, (Option<String>,)>(
+                        "SELECT job_logs.logs FROM v2_job
+                        LEFT JOIN job_logs ON job_logs.job_id = v2_job.id
+                        WHERE v2_job.workspace_id = $1 AND v2_job.id = $2",
+                    )
+                    .bind(w_id)
+                    .bind(id),
+                )
+                .await?
+                .pop()
+                .ok_or_else(|| not_found(uri))?
+                .0;
+            Ok(ReadResourceResult {
+                contents: vec![ResourceContents::TextResourceContents {
+                    uri: uri.to_string(),
+                    mime_type: Some("text/plain".to_string()),
+                    text: logs.unwrap_or_default(),
+                }],
+            })
+        }
+    }
+}
+
+fn prompt_arguments(schema: Option<&Value>) -> Option<Vec<PromptArgument>> {
+    let schema = schema?;
+    let required = schema
+        .get("required")
+        .and_then(|r| r.as_array())
+        .map(|r| r.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let properties = schema.get("properties")?.as_object()?;
+    Some(
+        properties
+            .iter()
+            .map(|(name, property)| PromptArgument {
+                name: name.clone(),
+                description: property
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .filter(|d| !d.is_empty())
+                    .map(|d| d.to_string()),
+                required: Some(required.contains(&name.as_str())),
+            })
+            .collect(),
+    )
+}
+
+async fn get_script_prompts(ctx: &McpContext<'_>) -> Result<Vec<ScriptPromptRow>, Error> {
+    ctx.fetch_all(
+        sqlx::query_as::<_, ScriptPromptRow>(
+            "SELECT DISTINCT ON (path) path, summary, description, schema FROM script
+            WHERE workspace_id = $1 AND archived = false AND draft_only IS NOT TRUE
+                AND description != ''
+            ORDER BY path, created_at DESC LIMIT $2",
+        )
+        .bind(&ctx.workspace_id)
+        .bind(LIST_LIMIT),
+    )
+    .await
+}
+
+pub(crate) async fn list_prompts(ctx: &McpContext<'_>) -> Result<ListPromptsResult, Error> {
+    let prompts = get_script_prompts(ctx)
+        .await?
+        .into_iter()
+        .map(|script| Prompt {
+            name: transform_path(&script.path, "script"),
+            description: Some(if script.summary.is_empty() {
+                script.description
+            } else {
+                script.summary
+            }),
+            arguments: prompt_arguments(script.schema.as_ref()),
+        })
+        .collect();
+
+    Ok(ListPromptsResult { prompts, next_cursor: None })
+}
+
+pub(crate) async fn get_prompt(
+    ctx: &McpContext<'_>,
+    request: GetPromptRequestParam,
+) -> Result<GetPromptResult, Error> {
+    let script = get_script_prompts(ctx)
+        .await?
+        .into_iter()
+        .find(|script| transform_path(&script.path, "script") == request.name)
+        .ok_or_else(|| Error::invalid_params(format!("prompt not found: {}", request.name), None))?;
+
+    let mut text = format!(
+        "Use the tool `{}` ({}).\n\n{}",
+        request.name, script.summary, script.description
+    );
+    if let Some(arguments) = request.arguments.filter(|a| !a.is_empty()) {
+        text.push_str(&format!(
+            "\n\nCall it with the following arguments:\n{}",
+            serde_json::to_string_pretty(&arguments).unwrap_or_default()
+        ));
+    }
+
+    Ok(GetPromptResult {
+        description: Some(script.summary).filter(|s| !s.is_empty()),
+        messages: vec![PromptMessage::new_text(
+            PromptMessageRole::User,
+            text,
+        )],
+    })
+}
+
+/// Progress of a running job as reported to MCP clients: the current step out of the number of
+/// steps for flows, the percentage set by the script for scripts.
+pub(crate) async fn job_progress(
+    db: &DB,
+    w_id: &str,
+    job_id: Uuid,
+) -> windmill_common::error::Result<Option<(u32, Option<u32>, String)>> {
+    let row = sqlx::query!(
+        r#"SELECT
+            v2_job_status.flow_status AS "flow_status: sqlx::types::Json<Value>",
+            job_stats.scalar_int AS "progress_perc?"
+        FROM v2_job
+        LEFT JOIN v2_job_status ON v2_job_status.id = v2_job.id
+        LEFT JOIN job_stats ON job_stats.job_id = v2_job.id AND job_stats.metric_id = 'progress_perc'
+        WHERE v2_job.id = $1 AND v2_job.workspace_id = $2"#,
+        job_id,
+        w_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    if let Some(flow_status) = row.flow_status {
+        let step = flow_status.get("step").and_then(|s| s.as_u64()).unwrap_or(0) as u32;
+        let total = flow_status
+            .get("modules")
+            .and_then(|m| m.as_array())
+            .map(|m| m.len() as u32);
+        let current = flow_status
+            .get("modules")
+            .and_then(|m| m.get(step as usize))
+            .and_then(|m| m.get("id"))
+            .and_then(|id| id.as_str())
+            .map(|id| format!("running step {id}"))
+            .unwrap_or_else(|| "running".to_string());
+        return Ok(Some((step, total, current)));
+    }
+
+    Ok(row
+        .progress_perc
+        .map(|perc| (perc.clamp(0, 100) as u32, Some(100), format!("{perc}%"))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resource_uri() {
+        assert!(matches!(
+            ResourceUri::parse("windmill://variables/f/orders/api_url"),
+            Some(ResourceUri::Variable(path)) if path == "f/orders/api_url"
+        ));
+        assert!(matches!(
+            ResourceUri::parse("windmill://resources/u/alice/db"),
+            Some(ResourceUri::Resource(path)) if path == "u/alice/db"
+        ));
+        assert!(matches!(
+            ResourceUri::parse("windmill://flows/f/orders/sync"),
+            Some(ResourceUri::Flow(path)) if path == "f/orders/sync"
+        ));
+
+        let id = Uuid::new_v4();
+        assert!(matches!(
+            ResourceUri::parse(&format!("windmill://jobs/{id}")),
+            Some(ResourceUri::Job(job_id)) if job_id == id
+        ));
+        assert!(matches!(
+            ResourceUri::parse(&format!("windmill://jobs/{id}/logs")),
+            Some(ResourceUri::JobLogs(job_id)) if job_id == id
+        ));
+
+        assert!(ResourceUri::parse(&format!("windmill://jobs/{id}/result")).is_none());
+        assert!(ResourceUri::parse("windmill://jobs/not-a-uuid").is_none());
+        assert!(ResourceUri::parse("windmill://schedules/f/orders/daily").is_none());
+        assert!(ResourceUri::parse("windmill://variables").is_none());
+        assert!(ResourceUri::parse("file://variables/f/orders/api_url").is_none());
+    }
+
+    #[test]
+    fn test_prompt_arguments() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "order_id": {"type": "integer", "description": "Id of the order"},
+                "notify": {"type": "boolean", "description": ""}
+            },
+            "required": ["order_id"]
+        });
+        let arguments = prompt_arguments(Some(&schema)).unwrap();
+        let argument = |name: &str| arguments.iter().find(|a| a.name == name).unwrap();
+        assert_eq!(arguments.len(), 2);
+        assert_eq!(
+            argument("order_id").description.as_deref(),
+            Some("Id of the order")
+        );
+        assert_eq!(argument("order_id").required, Some(true));
+        assert_eq!(argument("notify").description, None);
+        assert_eq!(argument("notify").required, Some(false));
+
+        assert!(prompt_arguments(None).is_none());
+        assert!(prompt_arguments(Some(&json!({"type": "object"}))).is_none());
+    }
+
+    #[test]
+    fn test_resource_description() {
+        let listed = resource(
+            "windmill://variables/f/orders/api_url".to_string(),
+            "variable f/orders/api_url".to_string(),
+            Some(String::new()),
+            "text/plain",
+        );
+        assert_eq!(listed.description, None);
+        assert_eq!(listed.mime_type.as_deref(), Some("text/plain"));
+    }
+}
@@ -1,25 +1,18 @@
 use crate::{
-    capture::{insert_capture_payload, MqttTriggerConfig},
+    capture::MqttTriggerConfig,
     db::{ApiAuthed, DB},
     resources::try_get_resource_from_db_as,
-    trigger_helpers::{trigger_runnable, TriggerJobArgs},
-    users::fetch_api_authed,
-    utils::check_scopes,
+    trigger_helpers::TriggerJobArgs,
+    triggers::{
+        handler::{trigger_routes, PgQuery, Trigger, TriggerCrud},
+        listener::{trigger_job, Listener, ListeningConfig},
+    },
 };
-use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
+use windmill_git_sync::DeployedObject;
 
-use axum::{
-    async_trait,
-    extract::{Path, Query},
-    Extension, Json,
-};
-use axum::{
-    routing::{delete, get, post},
-    Router,
-};
+use axum::{async_trait, extract::Path, routing::post, Extension, Json, Router};
 use base64::{engine, prelude::*};
 use bytes::Bytes;
-use http::StatusCode;
 use itertools::Itertools;
 use rumqttc::{
     v5::{
@@ -35,38 +28,22 @@ use rumqttc::{
     TlsConfiguration, Transport,
 };
 use serde::{Deserialize, Serialize};
-use sql_builder::{bind::Bind, SqlBuilder};
 use sqlx::{FromRow, Type};
 use std::collections::HashMap;
 use std::time::Duration;
-use windmill_audit::{audit_oss::audit_log, ActionKind};
 use windmill_common::{
-    db::UserDB,
-    error::{self, JsonResult},
-    triggers::TriggerKind,
-    utils::{not_found_if_none, paginate, report_critical_error, Pagination, StripPath},
-    worker::{to_raw_value, CLOUD_HOSTED},
-    INSTANCE_NAME,
+    db::UserDB, error, triggers::TriggerKind, utils::report_critical_error, worker::to_raw_value,
 };
 
-use rand::seq::SliceRandom;
 use serde_json::value::RawValue;
 use sqlx::types::Json as SqlxJson;
 
 pub fn workspaced_service() -> Router {
-    Router::new()
-        .route("/create", post(create_mqtt_trigger))
-        .route("/list", get(list_mqtt_triggers))
-        .route("/get/*path", get(get_mqtt_trigger))
-        .route("/update/*path", post(update_mqtt_trigger))
-        .route("/delete/*path", delete(delete_mqtt_trigger))
-        .route("/exists/*path", get(exists_mqtt_trigger))
-        .route("/setenabled/*path", post(set_enabled))
-        .route("/test", post(test_mqtt_connection))
+    trigger_routes::<MqttTriggerHandler>().route("/test", post(test_mqtt_connection))
 }
 
 #[derive(Debug, thiserror::Error)]
-enum Error {
+pub enum Error {
     #[error("{0}")]
     Common(#[from] windmill_common::error::Error),
     #[error("{0}")]
@@ -87,39 +64,17 @@ async fn run_job(
     db: &DB,
     trigger: &MqttTrigger,
 ) -> anyhow::Result<()> {
-    let args = MqttTrigger::build_job_args(
-        &trigger.script_path,
-        trigger.is_flow,
-        &trigger.workspace_id,
+    let args = MqttTriggerHandler::build_job_args(
+        &trigger.base.script_path,
+        trigger.base.is_flow,
+        &trigger.base.workspace_id,
         db,
         payload,
         trigger_info,
     )
     .await?;
 
-    let authed = fetch_api_authed(
-        trigger.edited_by.clone(),
-        trigger.email.clone(),
-        &trigger.workspace_id,
-        db,
-        Some(format!("mqtt-{}", trigger.path)),
-    )
-    .await?;
-
-    trigger_runnable(
-        db,
-        None,
-        authed,
-        &trigger.workspace_id,
-        &trigger.script_path,
-        trigger.is_flow,
-        args,
-        trigger.retry.as_ref(),
-        trigger.error_handler_path.as_deref(),
-        trigger.error_handler_args.as_ref(),
-        format!("mqtt_trigger/{}", trigger.path),
-    )
-    .await?;
+    trigger_job::<MqttTriggerHandler>(db, &trigger.base, args, None).await?;
 
     Ok(())
 }
@@ -201,43 +156,8 @@ pub struct SubscribeTopic {
     topic: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct NewMqttTrigger {
-    mqtt_resource_path: String,
-    subscribe_topics: Vec<SubscribeTopic>,
-    v3_config: Option<MqttV3Config>,
-    v5_config: Option<MqttV5Config>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    client_version: Option<MqttClientVersion>,
-    client_id: Option<String>,
-    path: String,
-    script_path: String,
-    is_flow: bool,
-    enabled: bool,
-    error_handler_path: Option<String>,
-    error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
-    retry: Option<SqlxJson<windmill_common::flows::Retry>>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EditMqttTrigger {
-    mqtt_resource_path: String,
-    subscribe_topics: Vec<SubscribeTopic>,
-    v3_config: Option<MqttV3Config>,
-    v5_config: Option<MqttV5Config>,
-    client_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    client_version: Option<MqttClientVersion>,
-    path: String,
-    script_path: String,
-    is_flow: bool,
-    error_handler_path: Option<String>,
-    error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
-    retry: Option<SqlxJson<windmill_common::flows::Retry>>,
-}
-
 #[derive(Debug, Serialize, Deserialize, FromRow)]
-pub struct MqttTrigger {
+pub struct MqttConfig {
     pub mqtt_resource_path: String,
     pub subscribe_topics: Vec<SqlxJson<SubscribeTopic>>,
     pub v3_config: Option<SqlxJson<MqttV3Config>>,
@@ -245,38 +165,45 @@ pub struct MqttTrigger {
     pub client_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_version: Option<MqttClientVersion>,
-    pub path: String,
-    pub script_path: String,
-    pub is_flow: bool,
-    pub workspace_id: String,
-    pub edited_by: String,
-    pub email: String,
-    pub edited_at: chrono::DateTime<chrono::Utc>,
-    pub extra_perms: Option<serde_json::Value>,
-    pub error: Option<String>,
-    pub server_id: Option<String>,
-    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub retry: Option<SqlxJson<windmill_common::flows::Retry>>,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ListMqttTriggerQuery {
-    page: Option<usize>,
-    per_page: Option<usize>,
-    path: Option<String>,
-    is_flow: Option<bool>,
-    path_start: Option<String>,
-}
+pub type MqttTrigger = Trigger<MqttConfig>;
 
-#[derive(Deserialize)]
-pub struct SetEnabled {
-    enabled: bool,
+pub struct MqttTriggerHandler;
+
+impl TriggerCrud for MqttTriggerHandler {
+    const TABLE_NAME: &'static str = "mqtt_trigger";
+    const ROUTE_PREFIX: &'static str = "mqtt_triggers";
+    const DISPLAY_NAME: &'static str = "MQTT";
+    const KIND: TriggerKind = TriggerKind::Mqtt;
+    const CONFIG_COLUMNS: &'static [&'static str] = &[
+        "mqtt_resource_path",
+        "subscribe_topics",
+        "v3_config",
+        "v5_config",
+        "client_id",
+        "client_version",
+    ];
+
+    type Config = MqttConfig;
+
+    fn deployed_object(path: String) -> DeployedObject {
+        DeployedObject::MqttTrigger { path }
+    }
+
+    fn validate_config(_config: &MqttConfig) -> error::Result<()> {
+        Ok(())
+    }
+
+    fn bind_config<'q>(config: &'q MqttConfig, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&config.mqtt_resource_path)
+            .bind(&config.subscribe_topics)
+            .bind(&config.v3_config)
+            .bind(&config.v5_config)
+            .bind(&config.client_id)
+            .bind(&config.client_version)
+    }
 }
 
 const KEEP_ALIVE: u64 = 60;
@@ -513,508 +440,7 @@ pub async fn test_mqtt_connection(
     Ok(())
 }
 
-pub async fn create_mqtt_trigger(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path(w_id): Path<String>,
-    Json(new_mqtt_trigger): Json<NewMqttTrigger>,
-) -> error::Result<(StatusCode, String)> {
-    check_scopes(&authed, || {
-        format!("mqtt_triggers:write:{}", &new_mqtt_trigger.path)
-    })?;
-    if *CLOUD_HOSTED {
-        return Err(error::Error::BadRequest(
-            "MQTT triggers are not supported on multi-tenant cloud, use dedicated cloud or self-host".to_string(),
-        ));
-    }
-
-    let NewMqttTrigger {
-        mqtt_resource_path,
-        subscribe_topics,
-        path,
-        script_path,
-        enabled,
-        is_flow,
-        v3_config,
-        v5_config,
-        client_version,
-        client_id,
-        error_handler_path,
-        error_handler_args,
-        retry,
-    } = new_mqtt_trigger;
-
-    let mut tx = user_db.begin(&authed).await?;
-
-    let subscribe_topics = subscribe_topics.into_iter().map(SqlxJson).collect_vec();
-    let v3_config = v3_config.map(SqlxJson);
-    let v5_config = v5_config.map(SqlxJson);
-
-    sqlx::query!(
-        r#"
-        INSERT INTO mqtt_trigger (
-            mqtt_resource_path,
-            subscribe_topics,
-            client_version,
-            client_id,
-            v3_config,
-            v5_config,
-            workspace_id,
-            path, 
-            script_path, 
-            is_flow, 
-            email, 
-            enabled, 
-            edited_by,
-            error_handler_path,
-            error_handler_args,
-            retry
-        ) 
-        VALUES (
-            $1, 
-            $2, 
-            $3, 
-            $4, 
-            $5, 
-            $6, 
-            $7,
-            $8,
-            $9,
-            $10,
-            $11,
-            $12,
-            $13,
-            $14,
-            $15,
-            $16
-        )"#,
-        mqtt_resource_path,
-        subscribe_topics.as_slice() as &[SqlxJson<SubscribeTopic>],
-        client_version as Option<MqttClientVersion>,
-        client_id,
-        v3_config as Option<SqlxJson<MqttV3Config>>,
-        v5_config as Option<SqlxJson<MqttV5Config>>,
-        &w_id,
-        &path,
-        script_path,
-        is_flow,
-        &authed.email,
-        enabled,
-        &authed.username,
-        error_handler_path,
-        error_handler_args as _,
-        retry as _
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "mqtt_triggers.create",
-        ActionKind::Create,
-        &w_id,
-        Some(path.as_str()),
-        None,
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::MqttTrigger { path: path.to_string() },
-        Some(format!("MQTT trigger '{}' created", path)),
-        true,
-    )
-    .await?;
-
-    Ok((StatusCode::CREATED, format!("{}", path.to_string())))
-}
-
-pub async fn list_mqtt_triggers(
-    authed: ApiAuthed,
-    Extension(user_db): Extension<UserDB>,
-    Path(w_id): Path<String>,
-    Query(lst): Query<ListMqttTriggerQuery>,
-) -> error::JsonResult<Vec<MqttTrigger>> {
-    let mut tx = user_db.begin(&authed).await?;
-    let (per_page, offset) = paginate(Pagination { per_page: lst.per_page, page: lst.page });
-    let mut sqlb = SqlBuilder::select_from("mqtt_trigger")
-        .fields(&[
-            "mqtt_resource_path",
-            "subscribe_topics",
-            "v3_config",
-            "v5_config",
-            "client_version",
-            "client_id",
-            "workspace_id",
-            "path",
-            "script_path",
-            "is_flow",
-            "edited_by",
-            "email",
-            "edited_at",
-            "server_id",
-            "last_server_ping",
-            "extra_perms",
-            "error",
-            "enabled",
-            "error_handler_path",
-            "error_handler_args",
-            "retry",
-        ])
-        .order_by("edited_at", true)
-        .and_where("workspace_id = ?".bind(&w_id))
-        .offset(offset)
-        .limit(per_page)
-        .clone();
-    if let Some(path) = lst.path {
-        sqlb.and_where_eq("script_path", "?".bind(&path));
-    }
-    if let Some(is_flow) = lst.is_flow {
-        sqlb.and_where_eq("is_flow", "?".bind(&is_flow));
-    }
-    if let Some(path_start) = &lst.path_start {
-        sqlb.and_where_like_left("path", path_start);
-    }
-    let sql = sqlb
-        .sql()
-        .map_err(|e| error::Error::InternalErr(e.to_string()))?;
-    let rows = sqlx::query_as::<_, MqttTrigger>(&sql)
-        .fetch_all(&mut *tx)
-        .await
-        .map_err(|e| {
-            tracing::debug!("Error fetching mqtt_trigger: {:#?}", e);
-            windmill_common::error::Error::InternalErr("server error".to_string())
-        })?;
-    tx.commit().await.map_err(|e| {
-        tracing::debug!("Error committing mqtt_trigger: {:#?}", e);
-        windmill_common::error::Error::InternalErr("server error".to_string())
-    })?;
-
-    Ok(Json(rows))
-}
-
-pub async fn get_mqtt_trigger(
-    authed: ApiAuthed,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-) -> JsonResult<MqttTrigger> {
-    let path = path.to_path();
-    check_scopes(&authed, || format!("mqtt_triggers:read:{}", path))?;
-
-    let mut tx = user_db.begin(&authed).await?;
-    let trigger = sqlx::query_as!(
-        MqttTrigger,
-        r#"
-        SELECT
-            mqtt_resource_path,
-            subscribe_topics as "subscribe_topics!: Vec<SqlxJson<SubscribeTopic>>",
-            v3_config as "v3_config!: Option<SqlxJson<MqttV3Config>>",
-            v5_config as "v5_config!: Option<SqlxJson<MqttV5Config>>",
-            client_version AS "client_version: _",
-            client_id,
-            workspace_id,
-            path,
-            script_path,
-            is_flow,
-            edited_by,
-            email,
-            edited_at,
-            server_id,
-            last_server_ping,
-            extra_perms,
-            error,
-            enabled,
-            error_handler_path,
-            error_handler_args as "error_handler_args: _",
-            retry as "retry: _"
-        FROM 
-            mqtt_trigger
-        WHERE 
-            workspace_id = $1 AND 
-            path = $2
-        "#,
-        w_id,
-        &path
-    )
-    .fetch_optional(&mut *tx)
-    .await?;
-    tx.commit().await?;
-
-    let trigger = not_found_if_none(trigger, "Mqtt Trigger", path)?;
-
-    Ok(Json(trigger))
-}
-
-pub async fn update_mqtt_trigger(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-    Json(mqtt_trigger): Json<EditMqttTrigger>,
-) -> error::Result<String> {
-    let workspace_path = path.to_path();
-    check_scopes(&authed, || format!("mqtt_triggers:write:{}", workspace_path))?;
-
-    let EditMqttTrigger {
-        mqtt_resource_path,
-        subscribe_topics,
-        script_path,
-        path,
-        is_flow,
-        v3_config,
-        v5_config,
-        client_version,
-        client_id,
-        error_handler_path,
-        error_handler_args,
-        retry,
-    } = mqtt_trigger;
-
-    let mut tx = user_db.begin(&authed).await?;
-
-    let subscribe_topics = subscribe_topics.into_iter().map(SqlxJson).collect_vec();
-
-    let v3_config = v3_config.map(SqlxJson);
-    let v5_config = v5_config.map(SqlxJson);
-
-    sqlx::query!(
-        r#"
-            UPDATE 
-                mqtt_trigger 
-            SET
-                mqtt_resource_path =  $1,
-                subscribe_topics = $2,
-                client_version = $3,
-                client_id = $4,
-                v3_config = $5,
-                v5_config = $6,
-                is_flow = $7, 
-                edited_by = $8, 
-                email = $9,
-                script_path = $10,
-                path = $11,
-                edited_at = now(), 
-                error = NULL,
-                server_id = NULL,
-                error_handler_path = $14,
-                error_handler_args = $15,
-                retry = $16
-            WHERE 
-                workspace_id = $12 AND 
-                path = $13
-            "#,
-        mqtt_resource_path,
-        subscribe_topics.as_slice() as &[SqlxJson<SubscribeTopic>],
-        client_version as Option<MqttClientVersion>,
-        client_id,
-        v3_config as Option<SqlxJson<MqttV3Config>>,
-        v5_config as Option<SqlxJson<MqttV5Config>>,
-        is_flow,
-        &authed.username,
-        &authed.email,
-        script_path,
-        path,
-        w_id,
-        workspace_path,
-        error_handler_path,
-        error_handler_args as _,
-        retry as _
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "mqtt_triggers.update",
-        ActionKind::Update,
-        &w_id,
-        Some(&path),
-        None,
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::MqttTrigger { path: path.clone() },
-        Some(format!("MQTT trigger '{}' updated", path)),
-        true,
-    )
-    .await?;
-
-    Ok(path.to_string())
-}
-
-pub async fn delete_mqtt_trigger(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-) -> error::Result<String> {
-    let path = path.to_path();
-    check_scopes(&authed, || format!("mqtt_triggers:write:{}", path))?;
-
-    let mut tx = user_db.begin(&authed).await?;
-    sqlx::query!(
-        r#"
-        DELETE 
-        FROM 
-            mqtt_trigger 
-        WHERE 
-            workspace_id = $1 AND 
-            path = $2
-        "#,
-        w_id,
-        path,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "mqtt_triggers.delete",
-        ActionKind::Delete,
-        &w_id,
-        Some(path),
-        None,
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::MqttTrigger { path: path.to_string() },
-        Some(format!("MQTT trigger '{}' deleted", path)),
-        true,
-    )
-    .await?;
-
-    Ok(format!("MQTT trigger {path} deleted"))
-}
-
-pub async fn exists_mqtt_trigger(
-    Extension(db): Extension<DB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-) -> JsonResult<bool> {
-    let path = path.to_path();
-    let exists = sqlx::query_scalar!(
-        r#"
-        SELECT EXISTS(
-            SELECT 
-                1 
-            FROM 
-                mqtt_trigger 
-            WHERE 
-                path = $1 AND 
-                workspace_id = $2
-        )"#,
-        path,
-        w_id,
-    )
-    .fetch_one(&db)
-    .await?
-    .unwrap_or(false);
-    Ok(Json(exists))
-}
-
-pub async fn set_enabled(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-    Json(payload): Json<SetEnabled>,
-) -> error::Result<String> {
-    let path = path.to_path();
-    check_scopes(&authed, || format!("mqtt_triggers:write:{}", path))?;
-    let mut tx = user_db.begin(&authed).await?;
-
-    // important to set server_id, last_server_ping and error to NULL to stop current mqtt listener
-    let one_o = sqlx::query_scalar!(
-        r#"
-        UPDATE 
-            mqtt_trigger 
-        SET 
-            enabled = $1, 
-            email = $2, 
-            edited_by = $3, 
-            edited_at = now(), 
-            server_id = NULL, 
-            error = NULL
-        WHERE 
-            path = $4 AND 
-            workspace_id = $5 
-        RETURNING 1
-        "#,
-        payload.enabled,
-        &authed.email,
-        &authed.username,
-        path,
-        w_id,
-    )
-    .fetch_optional(&mut *tx)
-    .await?
-    .flatten();
-
-    not_found_if_none(one_o, "Mqtt trigger", path)?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "mqtt_triggers.setenabled",
-        ActionKind::Update,
-        &w_id,
-        Some(path),
-        Some([("enabled", payload.enabled.to_string().as_ref())].into()),
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::MqttTrigger { path: path.to_string() },
-        Some(format!("MQTT trigger '{}' updated", path)),
-        true,
-    )
-    .await?;
-
-    Ok(format!(
-        "successfully updated mqtt trigger at path {} to status {}",
-        path, payload.enabled
-    ))
-}
-
-async fn loop_ping(db: &DB, mqtt: &MqttConfig, error: Option<&str>) {
-    loop {
-        if mqtt.update_ping(db, error).await.is_none() {
-            return;
-        }
-
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-    }
-}
-
-enum MqttClientResult {
+pub enum MqttClientResult {
     V3((V3MqttHandler, V3EventLoop)),
     V5((V5MqttHandler, V5EventLoop)),
 }
@@ -1028,7 +454,7 @@ trait MqttEvent {
     fn handle_event(&self, event: Self::Event) -> Result<Option<(Bytes, PublishData)>, String>;
 }
 
-struct V5MqttHandler;
+pub struct V5MqttHandler;
 
 impl MqttEvent for V5MqttHandler {
     type IncomingPacket = V5Incoming;
@@ -1071,7 +497,7 @@ impl MqttEvent for V5MqttHandler {
     }
 }
 
-struct V3MqttHandler;
+pub struct V3MqttHandler;
 
 impl MqttEvent for V3MqttHandler {
     type IncomingPacket = V3Incoming;
@@ -1179,7 +605,12 @@ impl EventLoop for V3EventLoop {
     }
 }
 
-async fn handle_publish_packet(db: &DB, mqtt: &MqttConfig, payload: Bytes, publish: PublishData) {
+async fn handle_publish_packet(
+    db: &DB,
+    config: &ListeningConfig<MqttTriggerHandler>,
+    payload: Bytes,
+    publish: PublishData,
+) {
     let trigger_info = HashMap::from([
         ("topic".to_string(), to_raw_value(&publish.topic)),
         ("retain".to_string(), to_raw_value(&publish.retain)),
@@ -1200,100 +631,100 @@ async fn handle_publish_packet(db: &DB, mqtt: &MqttConfig, payload: Bytes, publi
             })),
         ),
     ]);
-    mqtt.handle(&db, payload.as_ref(), trigger_info).await;
+    match config {
+        ListeningConfig::Trigger(trigger) => {
+            if let Err(err) = run_job(payload.as_ref(), trigger_info, db, trigger).await {
+                report_critical_error(
+                    format!(
+                        "Failed to trigger job from mqtt {}: {:?}",
+                        trigger.base.path, err
+                    ),
+                    db.clone(),
+                    Some(&trigger.base.workspace_id),
+                    None,
+                )
+                .await;
+            }
+        }
+        ListeningConfig::Capture(capture) => {
+            let (main_args, preprocessor_args) =
+                MqttTriggerHandler::build_capture_payloads(&payload[..], trigger_info);
+            capture
+                .insert_payload(db, &TriggerKind::Mqtt, main_args, preprocessor_args)
+                .await;
+        }
+    }
 }
 
-async fn handle_event<E, H>(db: &DB, mqtt: &MqttConfig, handler: H, mut event_loop: E) -> ()
+async fn handle_event<E, H>(
+    db: &DB,
+    config: &ListeningConfig<MqttTriggerHandler>,
+    handler: H,
+    mut event_loop: E,
+) -> Result<(), Error>
 where
     H: MqttEvent,
     E: EventLoop<Event = H::Event>,
-    E::Error: ToString,
+    Error: From<E::Error>,
 {
     loop {
-        let event = event_loop.poll().await;
-
-        match event {
-            Ok(event) => {
-                let publish_data = handler.handle_event(event);
-                if let Ok(Some((payload, publish_data))) = publish_data {
-                    handle_publish_packet(db, mqtt, payload, publish_data).await;
-                }
-            }
-            Err(err) => {
-                let err = err.to_string();
-                tracing::debug!("Error: {}", &err);
-                mqtt.disable_with_error(&db, err).await;
-                return;
-            }
+        let event = event_loop.poll().await?;
+        if let Ok(Some((payload, publish_data))) = handler.handle_event(event) {
+            handle_publish_packet(db, config, payload, publish_data).await;
         }
     }
 }
 
-#[derive(Debug)]
-enum MqttConfig {
-    Trigger(MqttTrigger),
-    Capture(CaptureConfigForMqttTrigger),
-}
-
-impl MqttConfig {
-    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
-        match self {
-            MqttConfig::Trigger(trigger) => trigger.update_ping(db, error).await,
-            MqttConfig::Capture(capture) => capture.update_ping(db, error).await,
-        }
-    }
-
-    async fn disable_with_error(&self, db: &DB, error: String) -> () {
-        match self {
-            MqttConfig::Trigger(trigger) => trigger.disable_with_error(&db, error).await,
-            MqttConfig::Capture(capture) => capture.disable_with_error(db, error).await,
-        }
-    }
+#[async_trait]
+impl Listener for MqttTriggerHandler {
+    type CaptureConfig = MqttTriggerConfig;
+    type Connection = MqttClientResult;
+    type Error = Error;
 
-    async fn start_consuming_messages(
-        &self,
-        db: &DB,
-    ) -> std::result::Result<MqttClientResult, Error> {
+    async fn connect(db: &DB, config: &ListeningConfig<Self>) -> Result<MqttClientResult, Error> {
         let mqtt_resource_path;
         let subscribe_topics;
-        let workspace_id;
-        let authed;
         let client_version;
         let client_id;
         let v3_config;
         let v5_config;
-        match self {
-            MqttConfig::Capture(capture) => {
-                mqtt_resource_path = &capture.trigger_config.0.mqtt_resource_path;
-                subscribe_topics = capture.trigger_config.0.subscribe_topics.clone();
-                workspace_id = &capture.workspace_id;
-                authed = capture.fetch_authed(&db).await?;
-                client_version = capture.trigger_config.0.client_version.as_ref();
-                client_id = capture.trigger_config.0.client_id.as_deref();
-                v3_config = capture.trigger_config.0.v3_config.as_ref();
-                v5_config = capture.trigger_config.0.v5_config.as_ref();
+        match config {
+            ListeningConfig::Capture(capture) => {
+                mqtt_resource_path = &capture.trigger_config.mqtt_resource_path;
+                subscribe_topics = capture.trigger_config.subscribe_topics.clone();
+                client_version = capture.trigger_config.client_version.as_ref();
+                client_id = capture.trigger_config.client_id.as_deref();
+                v3_config = capture.trigger_config.v3_config.as_ref();
+                v5_config = capture.trigger_config.v5_config.as_ref();
             }
-            MqttConfig::Trigger(trigger) => {
-                mqtt_resource_path = &trigger.mqtt_resource_path;
+            ListeningConfig::Trigger(trigger) => {
+                mqtt_resource_path = &trigger.config.mqtt_resource_path;
                 subscribe_topics = trigger
+                    .config
                     .subscribe_topics
                     .iter()
                     .map(|topic| topic.0.clone())
                     .collect_vec();
-                workspace_id = &trigger.workspace_id;
-                client_version = trigger.client_version.as_ref();
-                authed = trigger.fetch_authed(&db).await?;
-                client_id = trigger.client_id.as_deref();
-                v3_config = trigger.v3_config.as_ref().map(|v3_config| &v3_config.0);
-                v5_config = trigger.v5_config.as_ref().map(|v5_config| &v5_config.0);
+                client_version = trigger.config.client_version.as_ref();
+                client_id = trigger.config.client_id.as_deref();
+                v3_config = trigger
+                    .config
+                    .v3_config
+                    .as_ref()
+                    .map(|v3_config| &v3_config.0);
+                v5_config = trigger
+                    .config
+                    .v5_config
+                    .as_ref()
+                    .map(|v5_config| &v5_config.0);
             }
         }
         let mqtt_resource = try_get_resource_from_db_as::<MqttResource>(
-            &authed,
+            &config.fetch_authed(db).await?,
             Some(UserDB::new(db.clone())),
             db,
             mqtt_resource_path,
-            workspace_id,
+            config.workspace_id(),
         )
         .await?;
         let client_builder = MqttClientBuilder::new(
@@ -1308,206 +739,23 @@ impl MqttConfig {
         client_builder.build_client().await
     }
 
-    async fn handle(
-        &self,
+    async fn consume(
         db: &DB,
-        payload: &[u8],
-        trigger_info: HashMap<String, Box<RawValue>>,
-    ) -> () {
-        match self {
-            MqttConfig::Trigger(trigger) => trigger.handle(&db, payload, trigger_info).await,
-            MqttConfig::Capture(capture) => capture.handle(&db, payload, trigger_info).await,
-        }
-    }
-}
-
-impl MqttTrigger {
-    async fn try_to_listen_to_mqtt_messages(
-        self,
-        db: DB,
-        killpill_rx: tokio::sync::broadcast::Receiver<()>,
-    ) -> () {
-        let mqtt_trigger = sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                mqtt_trigger 
-            SET 
-                server_id = $1, 
-                last_server_ping = now(),
-                error = 'Connecting...'
-            WHERE 
-                enabled IS TRUE 
-                AND workspace_id = $2 
-                AND path = $3 
-                AND (last_server_ping IS NULL 
-                    OR last_server_ping < now() - INTERVAL '15 seconds'
-                ) 
-            RETURNING true
-            "#,
-            *INSTANCE_NAME,
-            self.workspace_id,
-            self.path,
-        )
-        .fetch_optional(&db)
-        .await;
-        match mqtt_trigger {
-            Ok(has_lock) => {
-                if has_lock.flatten().unwrap_or(false) {
-                    tracing::info!("Spawning new task to listen to mqtt notifications");
-                    tokio::spawn(async move {
-                        listen_to_messages(MqttConfig::Trigger(self), db.clone(), killpill_rx)
-                            .await;
-                    });
-                } else {
-                    tracing::info!("Mqtt trigger {} already being listened to", self.path);
-                }
+        config: &ListeningConfig<Self>,
+        connection: MqttClientResult,
+    ) -> Result<(), Error> {
+        match connection {
+            MqttClientResult::V3((v3_handler, event_loop)) => {
+                handle_event(db, config, v3_handler, event_loop).await
             }
-            Err(err) => {
-                tracing::error!(
-                    "Error acquiring lock for mqtt trigger {}: {:?}",
-                    self.path,
-                    err
-                );
-            }
-        };
-    }
-
-    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
-        let updated = sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                mqtt_trigger
-            SET 
-                last_server_ping = now(),
-                error = $1
-            WHERE
-                workspace_id = $2
-                AND path = $3
-                AND server_id = $4 
-                AND enabled IS TRUE
-            RETURNING 1
-            "#,
-            error,
-            &self.workspace_id,
-            &self.path,
-            *INSTANCE_NAME
-        )
-        .fetch_optional(db)
-        .await;
-
-        match updated {
-            Ok(updated) => {
-                if updated.flatten().is_none() {
-                    // allow faster restart of mqtt trigger
-                    sqlx::query!(
-                        r#"
-                    UPDATE 
-                        mqtt_trigger 
-                    SET
-                        last_server_ping = NULL 
-                    WHERE 
-                        workspace_id = $1 
-                        AND path = $2 
-                        AND server_id IS NULL"#,
-                        &self.workspace_id,
-                        &self.path,
-                    )
-                    .execute(db)
-                    .await
-                    .ok();
-                    tracing::info!(
-                        "Mqtt trigger {} changed, disabled, or deleted, stopping...",
-                        self.path
-                    );
-                    return None;
-                }
-            }
-            Err(err) => {
-                tracing::warn!(
-                    "Error updating ping of mqtt trigger {}: {:?}",
-                    self.path,
-                    err
-                );
-            }
-        };
-
-        Some(())
-    }
-
-    async fn disable_with_error(&self, db: &DB, error: String) -> () {
-        match sqlx::query!(
-            r#"
-                UPDATE 
-                    mqtt_trigger 
-                SET 
-                    enabled = FALSE, 
-                    error = $1, 
-                    server_id = NULL, 
-                    last_server_ping = NULL 
-                WHERE 
-                    workspace_id = $2 AND 
-                    path = $3
-            "#,
-            error,
-            self.workspace_id,
-            self.path,
-        )
-        .execute(db)
-        .await
-        {
-            Ok(_) => {
-                report_critical_error(
-                    format!(
-                        "Disabling mqtt trigger {} because of error: {}",
-                        self.path, error
-                    ),
-                    db.clone(),
-                    Some(&self.workspace_id),
-                    None,
-                )
-                .await;
-            }
-            Err(disable_err) => {
-                report_critical_error(
-                    format!("Could not disable mqtt trigger {} with err {}, disabling because of error {}", self.path, disable_err, error), 
-                    db.clone(),
-                    Some(&self.workspace_id),
-                    None,
-                ).await;
+            MqttClientResult::V5((v5_handler, event_loop)) => {
+                handle_event(db, config, v5_handler, event_loop).await
             }
         }
     }
-
-    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
-        fetch_api_authed(
-            self.edited_by.clone(),
-            self.email.clone(),
-            &self.workspace_id,
-            db,
-            Some(format!("mqtt-{}", self.path)),
-        )
-        .await
-    }
-
-    async fn handle(
-        &self,
-        db: &DB,
-        payload: &[u8],
-        trigger_info: HashMap<String, Box<RawValue>>,
-    ) -> () {
-        if let Err(err) = run_job(payload, trigger_info, db, self).await {
-            report_critical_error(
-                format!("Failed to trigger job from mqtt {}: {:?}", self.path, err),
-                db.clone(),
-                Some(&self.workspace_id),
-                None,
-            )
-            .await;
-        };
-    }
 }
 
-impl TriggerJobArgs<&[u8]> for MqttTrigger {
+impl TriggerJobArgs<&[u8]> for MqttTriggerHandler {
     fn v1_payload_fn(payload: &[u8]) -> HashMap<String, Box<RawValue>> {
         HashMap::from([("payload".to_string(), to_raw_value(&payload))])
     }
@@ -1541,368 +789,3 @@ impl PublishData {
         PublishData { topic, retain, pkid, v5, qos }
     }
 }
-
-async fn listen_to_messages(
-    mqtt: MqttConfig,
-    db: DB,
-    mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
-) {
-    tokio::select! {
-        biased;
-
-        _ = killpill_rx.recv() => {
-            return;
-        }
-
-        _ = loop_ping(&db, &mqtt, Some("Connecting...")) => {
-            return;
-        }
-
-        result = mqtt.start_consuming_messages(&db) => {
-            tokio::select! {
-                biased;
-
-                _ = killpill_rx.recv() => {
-                    return;
-                }
-
-                _ = loop_ping(&db, &mqtt, None) => {
-                    return;
-                }
-
-                _ = async {
-                    match result {
-                        Ok(connection) => {
-                            match connection {
-                                MqttClientResult::V3((v3_handler, event_loop)) => handle_event(&db, &mqtt, v3_handler, event_loop).await,
-                                MqttClientResult::V5((v5_handler, event_loop)) => handle_event(&db, &mqtt, v5_handler, event_loop).await,
-                            }
-                        }
-                        Err(err) => {
-                            tracing::error!(
-                                "Mqtt trigger error while trying to start listening to notifications: {}",
-                                &err
-                            );
-                            mqtt.disable_with_error(&db, err.to_string()).await
-                        }
-                    }
-                } => {}
-            }
-        }
-    }
-}
-
-#[derive(Debug, Deserialize)]
-struct CaptureConfigForMqttTrigger {
-    trigger_config: SqlxJson<MqttTriggerConfig>,
-    path: String,
-    is_flow: bool,
-    workspace_id: String,
-    owner: String,
-    email: String,
-}
-
-impl CaptureConfigForMqttTrigger {
-    async fn try_to_listen_to_mqtt_messages(
-        self,
-        db: DB,
-        killpill_rx: tokio::sync::broadcast::Receiver<()>,
-    ) -> () {
-        match sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                capture_config 
-            SET 
-                server_id = $1,
-                last_server_ping = now(), 
-                error = 'Connecting...' 
-            WHERE 
-                last_client_ping > NOW() - INTERVAL '10 seconds' AND 
-                workspace_id = $2 AND 
-                path = $3 AND 
-                is_flow = $4 AND 
-                trigger_kind = 'mqtt' AND 
-                (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds') 
-            RETURNING true
-            "#,
-            *INSTANCE_NAME,
-            self.workspace_id,
-            self.path,
-            self.is_flow,
-        )
-        .fetch_optional(&db)
-        .await
-        {
-            Ok(has_lock) => {
-                if has_lock.flatten().unwrap_or(false) {
-                    tokio::spawn(listen_to_messages(
-                        MqttConfig::Capture(self),
-                        db,
-                        killpill_rx,
-                    ));
-                } else {
-                    tracing::info!("Mqtt {} already being listened to", self.path);
-                }
-            }
-            Err(err) => {
-                tracing::error!(
-                    "Error acquiring lock for capture mqtt {}: {:?}",
-                    self.path,
-                    err
-                );
-            }
-        };
-    }
-
-    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
-        match sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                capture_config 
-            SET 
-                last_server_ping = now(), 
-                error = $1 
-            WHERE 
-                workspace_id = $2 AND 
-                path = $3 AND 
-                is_flow = $4 AND 
-                trigger_kind = 'mqtt' AND 
-                server_id = $5 AND 
-                last_client_ping > NOW() - INTERVAL '10 seconds' 
-            RETURNING 1
-        "#,
-            error,
-            self.workspace_id,
-            self.path,
-            self.is_flow,
-            *INSTANCE_NAME
-        )
-        .fetch_optional(db)
-        .await
-        {
-            Ok(updated) => {
-                if updated.flatten().is_none() {
-                    // allow faster restart of mqtt capture
-                    sqlx::query!(
-                        r#"UPDATE 
-                        capture_config 
-                    SET 
-                        last_server_ping = NULL 
-                    WHERE 
-                        workspace_id = $1 AND 
-                        path = $2 AND 
-                        is_flow = $3 AND 
-                        trigger_kind = 'mqtt' AND 
-                        server_id IS NULL
-                    "#,
-                        self.workspace_id,
-                        self.path,
-                        self.is_flow,
-                    )
-                    .execute(db)
-                    .await
-                    .ok();
-                    tracing::info!(
-                        "Mqtt capture {} changed, disabled, or deleted, stopping...",
-                        self.path
-                    );
-                    return None;
-                }
-            }
-            Err(err) => {
-                tracing::warn!(
-                    "Error updating ping of capture mqtt {}: {:?}",
-                    self.path,
-                    err
-                );
-            }
-        };
-
-        Some(())
-    }
-
-    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
-        fetch_api_authed(
-            self.owner.clone(),
-            self.email.clone(),
-            &self.workspace_id,
-            db,
-            Some(format!("mqtt-{}", self.get_trigger_path())),
-        )
-        .await
-    }
-
-    fn get_trigger_path(&self) -> String {
-        format!(
-            "{}-{}",
-            if self.is_flow { "flow" } else { "script" },
-            self.path
-        )
-    }
-
-    async fn disable_with_error(&self, db: &DB, error: String) -> () {
-        if let Err(err) = sqlx::query!(
-            r#"
-                UPDATE 
-                    capture_config 
-                SET 
-                    error = $1, 
-                    server_id = NULL, 
-                    last_server_ping = NULL 
-                WHERE 
-                    workspace_id = $2 AND 
-                    path = $3 AND 
-                    is_flow = $4 AND 
-                    trigger_kind = 'mqtt'
-            "#,
-            error,
-            self.workspace_id,
-            self.path,
-            self.is_flow,
-        )
-        .execute(db)
-        .await
-        {
-            tracing::error!(
-                "Could not disable mqtt capture {} ({}) with err {}, disabling because of error {}",
-                self.path,
-                self.workspace_id,
-                err,
-                error
-            );
-        }
-    }
-
-    async fn handle(
-        &self,
-        db: &DB,
-        payload: &[u8],
-        trigger_info: HashMap<String, Box<RawValue>>,
-    ) -> () {
-        let (main_args, preprocessor_args) =
-            MqttTrigger::build_capture_payloads(payload, trigger_info);
-        if let Err(err) = insert_capture_payload(
-            db,
-            &self.workspace_id,
-            &self.path,
-            self.is_flow,
-            &TriggerKind::Mqtt,
-            main_args,
-            preprocessor_args,
-            &self.owner,
-        )
-        .await
-        {
-            tracing::error!("Error inserting capture payload: {:?}", err);
-        }
-    }
-}
-
-async fn listen_to_unlistened_mqtt_events(
-    db: &DB,
-    killpill_rx: &tokio::sync::broadcast::Receiver<()>,
-) {
-    let mqtt_triggers = sqlx::query_as!(
-        MqttTrigger,
-        r#"
-            SELECT
-                mqtt_resource_path,
-                subscribe_topics as "subscribe_topics!: Vec<SqlxJson<SubscribeTopic>>",
-                v3_config as "v3_config!: Option<SqlxJson<MqttV3Config>>",
-                v5_config as "v5_config!: Option<SqlxJson<MqttV5Config>>",
-                client_version as "client_version: _",
-                client_id,
-                workspace_id,
-                path,
-                script_path,
-                is_flow,
-                edited_by,
-                email,
-                edited_at,
-                server_id,
-                last_server_ping,
-                extra_perms,
-                error,
-                enabled,
-                error_handler_path,
-                error_handler_args as "error_handler_args: _",
-                retry as "retry: _"
-            FROM
-                mqtt_trigger
-            WHERE
-                enabled IS TRUE
-                AND (last_server_ping IS NULL OR
-                    last_server_ping < now() - interval '15 seconds'
-                )
-            "#
-    )
-    .fetch_all(db)
-    .await;
-
-    match mqtt_triggers {
-        Ok(mut triggers) => {
-            triggers.shuffle(&mut rand::rng());
-            for trigger in triggers {
-                trigger
-                    .try_to_listen_to_mqtt_messages(db.clone(), killpill_rx.resubscribe())
-                    .await;
-            }
-        }
-        Err(err) => {
-            tracing::error!("Error fetching mqtt triggers: {:?}", err);
-        }
-    };
-
-    let mqtt_triggers_capture = sqlx::query_as!(
-        CaptureConfigForMqttTrigger,
-        r#"
-            SELECT
-                path,
-                is_flow,
-                workspace_id,
-                owner,
-                email,
-                trigger_config as "trigger_config!: _"
-            FROM
-                capture_config
-            WHERE
-                trigger_kind = 'mqtt' AND
-                last_client_ping > NOW() - INTERVAL '10 seconds' AND
-                trigger_config IS NOT NULL AND
-                (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
-            "#
-    )
-    .fetch_all(db)
-    .await;
-
-    match mqtt_triggers_capture {
-        Ok(mut captures) => {
-            captures.shuffle(&mut rand::rng());
-            for capture in captures {
-                capture
-                    .try_to_listen_to_mqtt_messages(db.clone(), killpill_rx.resubscribe())
-                    .await;
-            }
-        }
-        Err(err) => {
-            tracing::error!("Error fetching captures mqtt triggers: {:?}", err);
-        }
-    };
-}
-
-pub fn start_mqtt_consumer(db: DB, mut killpill_rx: tokio::sync::broadcast::Receiver<()>) {
-    tokio::spawn(async move {
-        listen_to_unlistened_mqtt_events(&db, &killpill_rx).await;
-        loop {
-            tokio::select! {
-                biased;
-                _ = killpill_rx.recv() => {
-                    return;
-                }
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(15)) => {
-                    listen_to_unlistened_mqtt_events(&db,  &killpill_rx).await
-                }
-            }
-        }
-    });
-}
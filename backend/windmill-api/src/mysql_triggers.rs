@@ -0,0 +1,1946 @@
+use crate::{
+    capture::{insert_capture_payload, MysqlTriggerConfig},
+    db::{ApiAuthed, DB},
+    resources::try_get_resource_from_db_as,
+    trigger_helpers::{trigger_runnable_idempotent, TriggerJobArgs},
+    users::fetch_api_authed,
+    utils::check_scopes,
+};
+use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
+
+use axum::{
+    extract::{Path, Query},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use base64::{engine, prelude::*};
+use futures::StreamExt;
+use http::StatusCode;
+use mysql_async::{
+    binlog::{
+        events::{EventData, RowsEventData},
+        row::BinlogRow,
+        value::BinlogValue,
+    },
+    prelude::Queryable,
+    BinlogStreamRequest, Conn, GnoInterval, OptsBuilder, Sid, SslOpts, Value as MysqlValue,
+};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Map, Value};
+use sql_builder::{bind::Bind, SqlBuilder};
+use sqlx::{types::Json as SqlxJson, FromRow};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{self, JsonResult},
+    triggers::TriggerKind,
+    utils::{not_found_if_none, paginate, report_critical_error, Pagination, StripPath},
+    worker::{to_raw_value, CLOUD_HOSTED},
+    INSTANCE_NAME,
+};
+use windmill_queue::idempotency::IdempotencyKey;
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/create", post(create_mysql_trigger))
+        .route("/list", get(list_mysql_triggers))
+        .route("/get/*path", get(get_mysql_trigger))
+        .route("/update/*path", post(update_mysql_trigger))
+        .route("/delete/*path", delete(delete_mysql_trigger))
+        .route("/exists/*path", get(exists_mysql_trigger))
+        .route("/setenabled/*path", post(set_enabled))
+        .route("/test", post(test_mysql_connection))
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error("{0}")]
+    Common(#[from] windmill_common::error::Error),
+    #[error("{0}")]
+    Mysql(#[from] mysql_async::Error),
+    #[error("Error reading binlog event: {0}")]
+    Binlog(#[from] std::io::Error),
+    #[error("{0}")]
+    Delivery(String),
+}
+
+/// Interval at which the binlog position of a trigger is saved, and at which the server sends
+/// heartbeats when there is no activity so that the position of idle triggers is saved as well
+const POSITION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+pub struct MysqlResource {
+    host: String,
+    user: Option<String>,
+    password: Option<String>,
+    port: Option<u16>,
+    database: String,
+    ssl: Option<bool>,
+    /// the certificate of the server is verified unless skipped explicitly, e.g. for servers
+    /// with a self-signed certificate
+    #[serde(default)]
+    ssl_skip_verification: bool,
+}
+
+impl MysqlResource {
+    async fn connect(&self) -> Result<Conn, Error> {
+        let opts = OptsBuilder::default()
+            .db_name(Some(&self.database))
+            .user(self.user.as_ref())
+            .pass(self.password.as_ref())
+            .ip_or_hostname(&self.host)
+            .tcp_port(self.port.unwrap_or(3306));
+
+        let opts = if self.ssl.unwrap_or(false) {
+            opts.ssl_opts(
+                SslOpts::default()
+                    .with_danger_skip_domain_validation(self.ssl_skip_verification)
+                    .with_danger_accept_invalid_certs(self.ssl_skip_verification),
+            )
+        } else {
+            opts
+        };
+
+        Ok(Conn::new(opts).await?)
+    }
+}
+
+/// Checks that the server writes a row based binlog with full row images and gtids, which is
+/// what the trigger needs to decode the changes and resume from the last delivered transaction
+async fn check_binlog_settings(conn: &mut Conn) -> Result<(), Error> {
+    let (log_bin, binlog_format, binlog_row_image, gtid_mode) = conn
+        .query_first::<(u8, String, String, String), _>(
+            "SELECT @@GLOBAL.log_bin, @@GLOBAL.binlog_format, @@GLOBAL.binlog_row_image, @@GLOBAL.gtid_mode",
+        )
+        .await?
+        .ok_or_else(|| error::Error::InternalErr("Could not read binlog settings".to_string()))?;
+
+    let mut errors = vec![];
+    if log_bin != 1 {
+        errors.push("the binary log must be enabled (log_bin)".to_string());
+    }
+    if !binlog_format.eq_ignore_ascii_case("ROW") {
+        errors.push(format!("binlog_format must be ROW, got {binlog_format}"));
+    }
+    if !binlog_row_image.eq_ignore_ascii_case("FULL") {
+        errors.push(format!("binlog_row_image must be FULL, got {binlog_row_image}"));
+    }
+    if !gtid_mode.eq_ignore_ascii_case("ON") {
+        errors.push(format!("gtid_mode must be ON, got {gtid_mode}"));
+    }
+
+    if !errors.is_empty() {
+        return Err(error::Error::BadConfig(format!(
+            "MySQL server is not configured for change data capture: {}",
+            errors.join(", ")
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+async fn get_gtid_executed(conn: &mut Conn) -> Result<GtidSet, Error> {
+    let gtid_executed = conn
+        .query_first::<String, _>("SELECT @@GLOBAL.gtid_executed")
+        .await?
+        .unwrap_or_default();
+    Ok(gtid_executed.parse()?)
+}
+
+/// Set of transactions identified by the uuid of their source server and their sequence number,
+/// in the `uuid:1-5:7,uuid:1-3` format used by MySQL
+#[derive(Debug, Clone, Default, PartialEq)]
+struct GtidSet(BTreeMap<Uuid, Vec<(u64, u64)>>);
+
+impl GtidSet {
+    fn add(&mut self, sid: Uuid, gno: u64) {
+        let intervals = self.0.entry(sid).or_default();
+        let position = intervals.partition_point(|(_, end)| *end + 1 < gno);
+        match intervals.get_mut(position) {
+            Some((start, end)) if *start <= gno + 1 => {
+                *start = (*start).min(gno);
+                *end = (*end).max(gno);
+                if let Some(&(next_start, next_end)) = intervals.get(position + 1) {
+                    if next_start <= intervals[position].1 + 1 {
+                        intervals[position].1 = intervals[position].1.max(next_end);
+                        intervals.remove(position + 1);
+                    }
+                }
+            }
+            _ => intervals.insert(position, (gno, gno)),
+        }
+    }
+
+    fn sids(&self) -> Vec<Sid<'static>> {
+        self.0
+            .iter()
+            .map(|(sid, intervals)| {
+                intervals.iter().fold(Sid::new(*sid.as_bytes()), |sid, (start, end)| {
+                    sid.with_interval(GnoInterval::new(*start, *end + 1))
+                })
+            })
+            .collect()
+    }
+}
+
+impl FromStr for GtidSet {
+    type Err = error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || error::Error::BadConfig(format!("Invalid gtid set: {s}"));
+        let mut gtid_set = GtidSet::default();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let mut elements = part.split(':');
+            let sid = elements
+                .next()
+                .and_then(|sid| Uuid::parse_str(sid).ok())
+                .ok_or_else(invalid)?;
+            let intervals = gtid_set.0.entry(sid).or_default();
+            for interval in elements {
+                let (start, end) = interval.split_once('-').unwrap_or((interval, interval));
+                let start = start.parse::<u64>().map_err(|_| invalid())?;
+                let end = end.parse::<u64>().map_err(|_| invalid())?;
+                intervals.push((start, end));
+            }
+        }
+        Ok(gtid_set)
+    }
+}
+
+impl fmt::Display for GtidSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sids = self
+            .0
+            .iter()
+            .map(|(sid, intervals)| {
+                let mut sid = sid.to_string();
+                for (start, end) in intervals {
+                    if start == end {
+                        sid.push_str(&format!(":{start}"));
+                    } else {
+                        sid.push_str(&format!(":{start}-{end}"));
+                    }
+                }
+                sid
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", sids.join(","))
+    }
+}
+
+/// Matches `database.table` patterns where each part can contain `*` wildcards
+fn table_matches(pattern: &str, database: &str, table: &str) -> bool {
+    fn wildcard_match(pattern: &str, value: &str) -> bool {
+        match pattern.split_once('*') {
+            None => pattern == value,
+            Some((prefix, rest)) => {
+                value.starts_with(prefix)
+                    && (0..=value.len() - prefix.len()).any(|i| {
+                        value.is_char_boundary(prefix.len() + i)
+                            && wildcard_match(rest, &value[prefix.len() + i..])
+                    })
+            }
+        }
+    }
+
+    match pattern.split_once('.') {
+        Some((database_pattern, table_pattern)) => {
+            wildcard_match(database_pattern, database) && wildcard_match(table_pattern, table)
+        }
+        None => wildcard_match(pattern, table),
+    }
+}
+
+fn is_table_tracked(
+    include_tables: Option<&[String]>,
+    exclude_tables: Option<&[String]>,
+    database: &str,
+    table: &str,
+) -> bool {
+    let included = match include_tables {
+        Some(include_tables) if !include_tables.is_empty() => include_tables
+            .iter()
+            .any(|pattern| table_matches(pattern, database, table)),
+        _ => true,
+    };
+    included
+        && !exclude_tables
+            .unwrap_or_default()
+            .iter()
+            .any(|pattern| table_matches(pattern, database, table))
+}
+
+fn mysql_value_to_json(value: &MysqlValue) -> Value {
+    match value {
+        MysqlValue::NULL => Value::Null,
+        MysqlValue::Bytes(bytes) => match std::str::from_utf8(bytes) {
+            Ok(string) => Value::String(string.to_string()),
+            Err(_) => Value::String(engine::general_purpose::STANDARD.encode(bytes)),
+        },
+        MysqlValue::Int(int) => Value::from(*int),
+        MysqlValue::UInt(uint) => Value::from(*uint),
+        MysqlValue::Float(float) => Value::from(*float as f64),
+        MysqlValue::Double(double) => Value::from(*double),
+        MysqlValue::Date(year, month, day, hour, minute, second, micro_second) => {
+            let mut date = format!(
+                "{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}"
+            );
+            if *micro_second > 0 {
+                date.push_str(&format!(".{micro_second:06}"));
+            }
+            Value::String(date)
+        }
+        MysqlValue::Time(is_negative, days, hours, minutes, seconds, micro_seconds) => {
+            let mut time = format!(
+                "{}{:02}:{minutes:02}:{seconds:02}",
+                if *is_negative { "-" } else { "" },
+                *days * 24 + *hours as u32
+            );
+            if *micro_seconds > 0 {
+                time.push_str(&format!(".{micro_seconds:06}"));
+            }
+            Value::String(time)
+        }
+    }
+}
+
+fn binlog_value_to_json(value: &BinlogValue) -> Value {
+    match value {
+        BinlogValue::Value(value) => mysql_value_to_json(value),
+        BinlogValue::Jsonb(jsonb) => Value::try_from(jsonb.clone()).unwrap_or_else(|err| {
+            tracing::warn!("Could not decode json column of mysql binlog event: {err}");
+            Value::Null
+        }),
+        // partial json updates are only sent with binlog_row_value_options=PARTIAL_JSON
+        BinlogValue::JsonDiff(_) => Value::Null,
+    }
+}
+
+/// Column names of the tracked tables. They are read from information_schema as the binlog only
+/// contains them when binlog_row_metadata is FULL.
+#[derive(Default)]
+struct ColumnNames(HashMap<(String, String), Vec<String>>);
+
+impl ColumnNames {
+    async fn get(
+        &mut self,
+        conn: &mut Conn,
+        database: &str,
+        table: &str,
+        columns_count: usize,
+    ) -> Result<&[String], Error> {
+        let key = (database.to_string(), table.to_string());
+        let outdated = self
+            .0
+            .get(&key)
+            .map_or(true, |columns| columns.len() != columns_count);
+        if outdated {
+            let columns = conn
+                .exec::<String, _, _>(
+                    "SELECT COLUMN_NAME FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ? ORDER BY ORDINAL_POSITION",
+                    (database, table),
+                )
+                .await?;
+            self.0.insert(key.clone(), columns);
+        }
+        Ok(&self.0[&key])
+    }
+}
+
+fn row_to_json(row: &BinlogRow, column_names: &[String]) -> Map<String, Value> {
+    let mut object = Map::new();
+    for (i, column) in row.columns_ref().iter().enumerate() {
+        let name = match column.name_str() {
+            name if !name.is_empty() => name.into_owned(),
+            _ => column_names
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("column_{}", i)),
+        };
+        let value = row
+            .as_ref(i)
+            .map(binlog_value_to_json)
+            .unwrap_or(Value::Null);
+        object.insert(name, value);
+    }
+    object
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewMysqlTrigger {
+    mysql_resource_path: String,
+    include_tables: Option<Vec<String>>,
+    exclude_tables: Option<Vec<String>>,
+    path: String,
+    script_path: String,
+    is_flow: bool,
+    enabled: bool,
+    error_handler_path: Option<String>,
+    error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
+    retry: Option<SqlxJson<windmill_common::flows::Retry>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditMysqlTrigger {
+    mysql_resource_path: String,
+    include_tables: Option<Vec<String>>,
+    exclude_tables: Option<Vec<String>>,
+    path: String,
+    script_path: String,
+    is_flow: bool,
+    error_handler_path: Option<String>,
+    error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
+    retry: Option<SqlxJson<windmill_common::flows::Retry>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MysqlTrigger {
+    pub mysql_resource_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_tables: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_tables: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gtid_set: Option<String>,
+    pub path: String,
+    pub script_path: String,
+    pub is_flow: bool,
+    pub workspace_id: String,
+    pub edited_by: String,
+    pub email: String,
+    pub edited_at: chrono::DateTime<chrono::Utc>,
+    pub extra_perms: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub server_id: Option<String>,
+    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_handler_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<SqlxJson<windmill_common::flows::Retry>>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ListMysqlTriggerQuery {
+    page: Option<usize>,
+    per_page: Option<usize>,
+    path: Option<String>,
+    is_flow: Option<bool>,
+    path_start: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SetEnabled {
+    enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestMysqlConnection {
+    mysql_resource_path: String,
+}
+
+pub async fn test_mysql_connection(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(workspace_id): Path<String>,
+    Json(test_mysql): Json<TestMysqlConnection>,
+) -> error::Result<()> {
+    let mysql_resource = try_get_resource_from_db_as::<MysqlResource>(
+        &authed,
+        Some(user_db),
+        &db,
+        &test_mysql.mysql_resource_path,
+        &workspace_id,
+    )
+    .await?;
+
+    let connect_f = async {
+        let mut conn = mysql_resource.connect().await?;
+        check_binlog_settings(&mut conn).await?;
+        conn.disconnect().await?;
+        Ok::<_, Error>(())
+    };
+
+    tokio::time::timeout(tokio::time::Duration::from_secs(30), connect_f)
+        .await
+        .map_err(|_| {
+            error::Error::BadConfig(format!(
+                "Timeout occurred while trying to connect to mysql server after 30 seconds"
+            ))
+        })?
+        .map_err(|err| match err {
+            Error::Common(err) => err,
+            err => error::Error::BadConfig(format!(
+                "Error connecting to mysql server: {}",
+                err.to_string()
+            )),
+        })?;
+
+    Ok(())
+}
+
+/// Position from which a new trigger receives the changes: the transactions already executed
+/// when it is created are skipped
+async fn get_initial_gtid_set(
+    authed: &ApiAuthed,
+    user_db: UserDB,
+    db: &DB,
+    mysql_resource_path: &str,
+    w_id: &str,
+) -> error::Result<String> {
+    let mysql_resource = try_get_resource_from_db_as::<MysqlResource>(
+        authed,
+        Some(user_db),
+        db,
+        mysql_resource_path,
+        w_id,
+    )
+    .await?;
+
+    let gtid_set = async {
+        let mut conn = mysql_resource.connect().await?;
+        check_binlog_settings(&mut conn).await?;
+        let gtid_set = get_gtid_executed(&mut conn).await?;
+        conn.disconnect().await?;
+        Ok::<_, Error>(gtid_set)
+    }
+    .await
+    .map_err(|err| match err {
+        Error::Common(err) => err,
+        err => error::Error::BadConfig(format!(
+            "Error connecting to mysql server: {}",
+            err.to_string()
+        )),
+    })?;
+
+    Ok(gtid_set.to_string())
+}
+
+pub async fn create_mysql_trigger(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(new_mysql_trigger): Json<NewMysqlTrigger>,
+) -> error::Result<(StatusCode, String)> {
+    check_scopes(&authed, || {
+        format!("mysql_triggers:write:{}", &new_mysql_trigger.path)
+    })?;
+    if *CLOUD_HOSTED {
+        return Err(error::Error::BadRequest(
+            "MySQL triggers are not supported on multi-tenant cloud, use dedicated cloud or self-host".to_string(),
+        ));
+    }
+
+    let NewMysqlTrigger {
+        mysql_resource_path,
+        include_tables,
+        exclude_tables,
+        path,
+        script_path,
+        enabled,
+        is_flow,
+        error_handler_path,
+        error_handler_args,
+        retry,
+    } = new_mysql_trigger;
+
+    let gtid_set =
+        get_initial_gtid_set(&authed, user_db.clone(), &db, &mysql_resource_path, &w_id).await?;
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO mysql_trigger (
+            mysql_resource_path,
+            include_tables,
+            exclude_tables,
+            gtid_set,
+            workspace_id,
+            path,
+            script_path,
+            is_flow,
+            email,
+            enabled,
+            edited_by,
+            error_handler_path,
+            error_handler_args,
+            retry
+        )
+        VALUES (
+            $1,
+            $2,
+            $3,
+            $4,
+            $5,
+            $6,
+            $7,
+            $8,
+            $9,
+            $10,
+            $11,
+            $12,
+            $13,
+            $14
+        )"#,
+        mysql_resource_path,
+        include_tables.as_deref(),
+        exclude_tables.as_deref(),
+        gtid_set,
+        &w_id,
+        &path,
+        script_path,
+        is_flow,
+        &authed.email,
+        enabled,
+        &authed.username,
+        error_handler_path,
+        error_handler_args as _,
+        retry as _
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "mysql_triggers.create",
+        ActionKind::Create,
+        &w_id,
+        Some(path.as_str()),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::MysqlTrigger { path: path.to_string() },
+        Some(format!("MySQL trigger '{}' created", path)),
+        true,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, path.to_string()))
+}
+
+pub async fn list_mysql_triggers(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(lst): Query<ListMysqlTriggerQuery>,
+) -> error::JsonResult<Vec<MysqlTrigger>> {
+    let mut tx = user_db.begin(&authed).await?;
+    let (per_page, offset) = paginate(Pagination { per_page: lst.per_page, page: lst.page });
+    let mut sqlb = SqlBuilder::select_from("mysql_trigger")
+        .fields(&[
+            "mysql_resource_path",
+            "include_tables",
+            "exclude_tables",
+            "gtid_set",
+            "workspace_id",
+            "path",
+            "script_path",
+            "is_flow",
+            "edited_by",
+            "email",
+            "edited_at",
+            "server_id",
+            "last_server_ping",
+            "extra_perms",
+            "error",
+            "enabled",
+            "error_handler_path",
+            "error_handler_args",
+            "retry",
+        ])
+        .order_by("edited_at", true)
+        .and_where("workspace_id = ?".bind(&w_id))
+        .offset(offset)
+        .limit(per_page)
+        .clone();
+    if let Some(path) = lst.path {
+        sqlb.and_where_eq("script_path", "?".bind(&path));
+    }
+    if let Some(is_flow) = lst.is_flow {
+        sqlb.and_where_eq("is_flow", "?".bind(&is_flow));
+    }
+    if let Some(path_start) = &lst.path_start {
+        sqlb.and_where_like_left("path", path_start);
+    }
+    let sql = sqlb
+        .sql()
+        .map_err(|e| error::Error::InternalErr(e.to_string()))?;
+    let rows = sqlx::query_as::<_, MysqlTrigger>(&sql)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::debug!("Error fetching mysql_trigger: {:#?}", e);
+            windmill_common::error::Error::InternalErr("server error".to_string())
+        })?;
+    tx.commit().await.map_err(|e| {
+        tracing::debug!("Error committing mysql_trigger: {:#?}", e);
+        windmill_common::error::Error::InternalErr("server error".to_string())
+    })?;
+
+    Ok(Json(rows))
+}
+
+pub async fn get_mysql_trigger(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<MysqlTrigger> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("mysql_triggers:read:{}", path))?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    let trigger = sqlx::query_as!(
+        MysqlTrigger,
+        r#"
+        SELECT
+            mysql_resource_path,
+            include_tables,
+            exclude_tables,
+            gtid_set,
+            workspace_id,
+            path,
+            script_path,
+            is_flow,
+            edited_by,
+            email,
+            edited_at,
+            server_id,
+            last_server_ping,
+            extra_perms,
+            error,
+            enabled,
+            error_handler_path,
+            error_handler_args as "error_handler_args: _",
+            retry as "retry: _"
+        FROM
+            mysql_trigger
+        WHERE
+            workspace_id = $1 AND
+            path = $2
+        "#,
+        w_id,
+        &path
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let trigger = not_found_if_none(trigger, "MySQL Trigger", path)?;
+
+    Ok(Json(trigger))
+}
+
+pub async fn update_mysql_trigger(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(mysql_trigger): Json<EditMysqlTrigger>,
+) -> error::Result<String> {
+    let workspace_path = path.to_path();
+    check_scopes(&authed, || format!("mysql_triggers:write:{}", workspace_path))?;
+
+    let EditMysqlTrigger {
+        mysql_resource_path,
+        include_tables,
+        exclude_tables,
+        script_path,
+        path,
+        is_flow,
+        error_handler_path,
+        error_handler_args,
+        retry,
+    } = mysql_trigger;
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    // the binlog position only makes sense for the server it was read from, it is set back to
+    // the current position of the new server by the listener when the resource changes
+    sqlx::query!(
+        r#"
+            UPDATE
+                mysql_trigger
+            SET
+                gtid_set = CASE WHEN mysql_resource_path = $1 THEN gtid_set ELSE NULL END,
+                mysql_resource_path = $1,
+                include_tables = $2,
+                exclude_tables = $3,
+                is_flow = $4,
+                edited_by = $5,
+                email = $6,
+                script_path = $7,
+                path = $8,
+                edited_at = now(),
+                error = NULL,
+                server_id = NULL,
+                error_handler_path = $11,
+                error_handler_args = $12,
+                retry = $13
+            WHERE
+                workspace_id = $9 AND
+                path = $10
+            "#,
+        mysql_resource_path,
+        include_tables.as_deref(),
+        exclude_tables.as_deref(),
+        is_flow,
+        &authed.username,
+        &authed.email,
+        script_path,
+        path,
+        w_id,
+        workspace_path,
+        error_handler_path,
+        error_handler_args as _,
+        retry as _
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "mysql_triggers.update",
+        ActionKind::Update,
+        &w_id,
+        Some(&path),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::MysqlTrigger { path: path.clone() },
+        Some(format!("MySQL trigger '{}' updated", path)),
+        true,
+    )
+    .await?;
+
+    Ok(path.to_string())
+}
+
+pub async fn delete_mysql_trigger(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> error::Result<String> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("mysql_triggers:write:{}", path))?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    sqlx::query!(
+        r#"
+        DELETE
+        FROM
+            mysql_trigger
+        WHERE
+            workspace_id = $1 AND
+            path = $2
+        "#,
+        w_id,
+        path,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "mysql_triggers.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::MysqlTrigger { path: path.to_string() },
+        Some(format!("MySQL trigger '{}' deleted", path)),
+        true,
+    )
+    .await?;
+
+    Ok(format!("MySQL trigger {path} deleted"))
+}
+
+pub async fn exists_mysql_trigger(
+    Extension(db): Extension<DB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<bool> {
+    let path = path.to_path();
+    let exists = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT
+                1
+            FROM
+                mysql_trigger
+            WHERE
+                path = $1 AND
+                workspace_id = $2
+        )"#,
+        path,
+        w_id,
+    )
+    .fetch_one(&db)
+    .await?
+    .unwrap_or(false);
+    Ok(Json(exists))
+}
+
+pub async fn set_enabled(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(payload): Json<SetEnabled>,
+) -> error::Result<String> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("mysql_triggers:write:{}", path))?;
+    let mut tx = user_db.begin(&authed).await?;
+
+    // important to set server_id, last_server_ping and error to NULL to stop current mysql listener
+    let one_o = sqlx::query_scalar!(
+        r#"
+        UPDATE
+            mysql_trigger
+        SET
+            enabled = $1,
+            email = $2,
+            edited_by = $3,
+            edited_at = now(),
+            server_id = NULL,
+            error = NULL
+        WHERE
+            path = $4 AND
+            workspace_id = $5
+        RETURNING 1
+        "#,
+        payload.enabled,
+        &authed.email,
+        &authed.username,
+        path,
+        w_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten();
+
+    not_found_if_none(one_o, "MySQL trigger", path)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "mysql_triggers.setenabled",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some([("enabled", payload.enabled.to_string().as_ref())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        DeployedObject::MysqlTrigger { path: path.to_string() },
+        Some(format!("MySQL trigger '{}' updated", path)),
+        true,
+    )
+    .await?;
+
+    Ok(format!(
+        "successfully updated mysql trigger at path {} to status {}",
+        path, payload.enabled
+    ))
+}
+
+async fn run_job(
+    payload: HashMap<String, Box<RawValue>>,
+    db: &DB,
+    trigger: &MysqlTrigger,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> anyhow::Result<()> {
+    let args = MysqlTrigger::build_job_args(
+        &trigger.script_path,
+        trigger.is_flow,
+        &trigger.workspace_id,
+        db,
+        payload,
+        HashMap::new(),
+    )
+    .await?;
+
+    let authed = trigger.fetch_authed(db).await?;
+
+    trigger_runnable_idempotent(
+        db,
+        None,
+        authed,
+        &trigger.workspace_id,
+        &trigger.script_path,
+        trigger.is_flow,
+        args,
+        trigger.retry.as_ref(),
+        trigger.error_handler_path.as_deref(),
+        trigger.error_handler_args.as_ref(),
+        format!("mysql_trigger/{}", trigger.path),
+        idempotency_key,
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn loop_ping(db: &DB, mysql: &MysqlConfig, error: Option<&str>) {
+    loop {
+        if mysql.update_ping(db, error).await.is_none() {
+            return;
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    }
+}
+
+/// Change of a row, in the same shape as the events of postgres triggers
+struct RowChange<'a> {
+    database: &'a str,
+    table: &'a str,
+    transaction_type: &'static str,
+    old_row: Option<Map<String, Value>>,
+    row: Map<String, Value>,
+}
+
+impl RowChange<'_> {
+    fn into_payload(self) -> HashMap<String, Box<RawValue>> {
+        HashMap::from([
+            ("schema_name".to_string(), to_raw_value(&self.database)),
+            ("table_name".to_string(), to_raw_value(&self.table)),
+            (
+                "transaction_type".to_string(),
+                to_raw_value(&self.transaction_type),
+            ),
+            ("old_row".to_string(), to_raw_value(&self.old_row)),
+            ("row".to_string(), to_raw_value(&self.row)),
+        ])
+    }
+}
+
+#[derive(Debug)]
+enum MysqlConfig {
+    Trigger(MysqlTrigger),
+    Capture(CaptureConfigForMysqlTrigger),
+}
+
+impl MysqlConfig {
+    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
+        match self {
+            MysqlConfig::Trigger(trigger) => trigger.update_ping(db, error).await,
+            MysqlConfig::Capture(capture) => capture.update_ping(db, error).await,
+        }
+    }
+
+    async fn disable_with_error(&self, db: &DB, error: String) -> () {
+        match self {
+            MysqlConfig::Trigger(trigger) => trigger.disable_with_error(&db, error).await,
+            MysqlConfig::Capture(capture) => capture.disable_with_error(db, error).await,
+        }
+    }
+
+    fn get_path(&self) -> &str {
+        match self {
+            MysqlConfig::Trigger(trigger) => &trigger.path,
+            MysqlConfig::Capture(capture) => &capture.path,
+        }
+    }
+
+    fn is_table_tracked(&self, database: &str, table: &str) -> bool {
+        let (include_tables, exclude_tables) = match self {
+            MysqlConfig::Trigger(trigger) => (
+                trigger.include_tables.as_deref(),
+                trigger.exclude_tables.as_deref(),
+            ),
+            MysqlConfig::Capture(capture) => (
+                capture.trigger_config.include_tables.as_deref(),
+                capture.trigger_config.exclude_tables.as_deref(),
+            ),
+        };
+        is_table_tracked(include_tables, exclude_tables, database, table)
+    }
+
+    async fn fetch_resource(&self, db: &DB) -> Result<MysqlResource, Error> {
+        let (authed, mysql_resource_path, workspace_id) = match self {
+            MysqlConfig::Trigger(trigger) => (
+                trigger.fetch_authed(db).await?,
+                &trigger.mysql_resource_path,
+                &trigger.workspace_id,
+            ),
+            MysqlConfig::Capture(capture) => (
+                capture.fetch_authed(db).await?,
+                &capture.trigger_config.mysql_resource_path,
+                &capture.workspace_id,
+            ),
+        };
+
+        Ok(try_get_resource_from_db_as::<MysqlResource>(
+            &authed,
+            Some(UserDB::new(db.clone())),
+            db,
+            mysql_resource_path,
+            workspace_id,
+        )
+        .await?)
+    }
+
+    /// The position to start streaming from: the saved one for triggers, the current one of the
+    /// server for captures and for triggers that never saved a position on that server
+    async fn start_position(&self, db: &DB, conn: &mut Conn) -> Result<GtidSet, Error> {
+        if let MysqlConfig::Trigger(trigger) = self {
+            if let Some(gtid_set) = trigger.gtid_set.as_deref() {
+                return Ok(gtid_set.parse()?);
+            }
+        }
+        let gtid_set = get_gtid_executed(conn).await?;
+        self.save_position(db, &gtid_set).await?;
+        Ok(gtid_set)
+    }
+
+    async fn save_position(&self, db: &DB, gtid_set: &GtidSet) -> Result<(), Error> {
+        if let MysqlConfig::Trigger(trigger) = self {
+            sqlx::query!(
+                "UPDATE mysql_trigger SET gtid_set = $1 WHERE workspace_id = $2 AND path = $3 AND server_id = $4",
+                gtid_set.to_string(),
+                &trigger.workspace_id,
+                &trigger.path,
+                *INSTANCE_NAME
+            )
+            .execute(db)
+            .await
+            .map_err(error::Error::from)?;
+        }
+        Ok(())
+    }
+
+    async fn deliver(
+        &self,
+        db: &DB,
+        change: RowChange<'_>,
+        gtid: Option<(Uuid, u64)>,
+        (event_index, row_index): (usize, usize),
+    ) -> Result<(), Error> {
+        match self {
+            MysqlConfig::Trigger(trigger) => {
+                // changes after the last saved position are streamed again when the listener
+                // restarts, the key prevents pushing their jobs twice
+                let idempotency_key = match gtid {
+                    Some((sid, gno)) => IdempotencyKey::new(
+                        &trigger.workspace_id,
+                        format!("mysql_trigger/{}", trigger.path),
+                        &format!("{}:{}/{}.{}", sid, gno, event_index, row_index),
+                    )?,
+                    None => None,
+                };
+                run_job(change.into_payload(), db, trigger, idempotency_key.as_ref())
+                    .await
+                    .map_err(|err| Error::Delivery(err.to_string()))
+            }
+            MysqlConfig::Capture(capture) => {
+                capture.handle(db, change.into_payload()).await;
+                Ok(())
+            }
+        }
+    }
+
+    async fn stream_changes(&self, db: &DB) -> Result<(), Error> {
+        let mysql_resource = self.fetch_resource(db).await?;
+
+        let mut metadata_conn = mysql_resource.connect().await?;
+        check_binlog_settings(&mut metadata_conn).await?;
+        let mut gtid_set = self.start_position(db, &mut metadata_conn).await?;
+
+        let mut binlog_conn = mysql_resource.connect().await?;
+        binlog_conn
+            .query_drop(format!(
+                "SET @master_heartbeat_period = {}",
+                POSITION_SAVE_INTERVAL.as_nanos()
+            ))
+            .await?;
+
+        // each replica connected to a server must have its own server id
+        let replica_server_id = rand::rng().random_range(1 << 24..u32::MAX);
+        let request = BinlogStreamRequest::new(replica_server_id)
+            .with_gtid()
+            .with_gtid_set(gtid_set.sids());
+        let mut binlog_stream = binlog_conn.get_binlog_stream(request).await?;
+
+        tracing::info!("Starting to listen for mysql trigger {}", self.get_path());
+
+        let mut column_names = ColumnNames::default();
+        let mut current_gtid = None;
+        let mut rows_event_index = 0;
+        let mut position_changed = false;
+        let mut last_saved_at = Instant::now();
+
+        while let Some(event) = binlog_stream.next().await {
+            let event = event?;
+            let Some(event_data) = event.read_data()? else {
+                continue;
+            };
+
+            let committed = match event_data {
+                EventData::GtidEvent(gtid_event) => {
+                    current_gtid = Some((Uuid::from_bytes(gtid_event.sid()), gtid_event.gno()));
+                    rows_event_index = 0;
+                    false
+                }
+                EventData::RowsEvent(rows_event) => {
+                    // a row is identified by the index of its event in the transaction and its
+                    // index in the event, counted over all the rows so that it does not depend on
+                    // the tracked tables
+                    let event_index = rows_event_index;
+                    rows_event_index += 1;
+                    let Some(table_map) = binlog_stream.get_tme(rows_event.table_id()) else {
+                        continue;
+                    };
+                    let database = table_map.database_name();
+                    let table = table_map.table_name();
+                    if !self.is_table_tracked(&database, &table) {
+                        continue;
+                    }
+
+                    let transaction_type = match rows_event {
+                        RowsEventData::WriteRowsEventV1(_) | RowsEventData::WriteRowsEvent(_) => {
+                            "insert"
+                        }
+                        RowsEventData::UpdateRowsEventV1(_)
+                        | RowsEventData::UpdateRowsEvent(_)
+                        | RowsEventData::PartialUpdateRowsEvent(_) => "update",
+                        RowsEventData::DeleteRowsEventV1(_) | RowsEventData::DeleteRowsEvent(_) => {
+                            "delete"
+                        }
+                    };
+
+                    let names = column_names
+                        .get(
+                            &mut metadata_conn,
+                            &database,
+                            &table,
+                            table_map.columns_count() as usize,
+                        )
+                        .await?;
+
+                    for (row_index, row) in rows_event.rows(table_map).enumerate() {
+                        let (before, after) = row?;
+                        let before = before.map(|before| row_to_json(&before, names));
+                        let after = after.map(|after| row_to_json(&after, names));
+                        let (old_row, row) = match (transaction_type, before, after) {
+                            ("delete", Some(before), _) => (None, before),
+                            (_, before, Some(after)) => (before, after),
+                            _ => continue,
+                        };
+
+                        let change =
+                            RowChange { database: &database, table: &table, transaction_type, old_row, row };
+                        self.deliver(db, change, current_gtid, (event_index, row_index)).await?;
+                    }
+                    false
+                }
+                EventData::XidEvent(_) => true,
+                EventData::QueryEvent(query_event) => query_event.query() == "COMMIT",
+                _ => false,
+            };
+
+            if committed {
+                if let Some((sid, gno)) = current_gtid.take() {
+                    gtid_set.add(sid, gno);
+                    position_changed = true;
+                }
+            }
+
+            if position_changed && last_saved_at.elapsed() >= POSITION_SAVE_INTERVAL {
+                self.save_position(db, &gtid_set).await?;
+                position_changed = false;
+                last_saved_at = Instant::now();
+            }
+        }
+
+        Err(error::Error::InternalErr("Binlog stream closed by the mysql server".to_string()).into())
+    }
+}
+
+impl MysqlTrigger {
+    async fn try_to_listen_to_binlog(
+        self,
+        db: DB,
+        killpill_rx: tokio::sync::broadcast::Receiver<()>,
+    ) -> () {
+        let mysql_trigger = sqlx::query_scalar!(
+            r#"
+            UPDATE
+                mysql_trigger
+            SET
+                server_id = $1,
+                last_server_ping = now(),
+                error = 'Connecting...'
+            WHERE
+                enabled IS TRUE
+                AND workspace_id = $2
+                AND path = $3
+                AND (last_server_ping IS NULL
+                    OR last_server_ping < now() - INTERVAL '15 seconds'
+                )
+            RETURNING true
+            "#,
+            *INSTANCE_NAME,
+            self.workspace_id,
+            self.path,
+        )
+        .fetch_optional(&db)
+        .await;
+        match mysql_trigger {
+            Ok(has_lock) => {
+                if has_lock.flatten().unwrap_or(false) {
+                    tracing::info!("Spawning new task to listen to mysql binlog");
+                    tokio::spawn(async move {
+                        listen_to_binlog(MysqlConfig::Trigger(self), db.clone(), killpill_rx)
+                            .await;
+                    });
+                } else {
+                    tracing::info!("MySQL trigger {} already being listened to", self.path);
+                }
+            }
+            Err(err) => {
+                tracing::error!(
+                    "Error acquiring lock for mysql trigger {}: {:?}",
+                    self.path,
+                    err
+                );
+            }
+        };
+    }
+
+    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
+        let updated = sqlx::query_scalar!(
+            r#"
+            UPDATE
+                mysql_trigger
+            SET
+                last_server_ping = now(),
+                error = $1
+            WHERE
+                workspace_id = $2
+                AND path = $3
+                AND server_id = $4
+                AND enabled IS TRUE
+            RETURNING 1
+            "#,
+            error,
+            &self.workspace_id,
+            &self.path,
+            *INSTANCE_NAME
+        )
+        .fetch_optional(db)
+        .await;
+
+        match updated {
+            Ok(updated) => {
+                if updated.flatten().is_none() {
+                    // allow faster restart of mysql trigger
+                    sqlx::query!(
+                        r#"
+                    UPDATE
+                        mysql_trigger
+                    SET
+                        last_server_ping = NULL
+                    WHERE
+                        workspace_id = $1
+                        AND path = $2
+                        AND server_id IS NULL"#,
+                        &self.workspace_id,
+                        &self.path,
+                    )
+                    .execute(db)
+                    .await
+                    .ok();
+                    tracing::info!(
+                        "MySQL trigger {} changed, disabled, or deleted, stopping...",
+                        self.path
+                    );
+                    return None;
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Error updating ping of mysql trigger {}: {:?}",
+                    self.path,
+                    err
+                );
+            }
+        };
+
+        Some(())
+    }
+
+    async fn disable_with_error(&self, db: &DB, error: String) -> () {
+        match sqlx::query!(
+            r#"
+                UPDATE
+                    mysql_trigger
+                SET
+                    enabled = FALSE,
+                    error = $1,
+                    server_id = NULL,
+                    last_server_ping = NULL
+                WHERE
+                    workspace_id = $2 AND
+                    path = $3
+            "#,
+            error,
+            self.workspace_id,
+            self.path,
+        )
+        .execute(db)
+        .await
+        {
+            Ok(_) => {
+                report_critical_error(
+                    format!(
+                        "Disabling mysql trigger {} because of error: {}",
+                        self.path, error
+                    ),
+                    db.clone(),
+                    Some(&self.workspace_id),
+                    None,
+                )
+                .await;
+            }
+            Err(disable_err) => {
+                report_critical_error(
+                    format!("Could not disable mysql trigger {} with err {}, disabling because of error {}", self.path, disable_err, error),
+                    db.clone(),
+                    Some(&self.workspace_id),
+                    None,
+                ).await;
+            }
+        }
+    }
+
+    /// Releases the trigger without disabling it so that it is listened to again, from the last
+    /// saved position, by the next server to pick it up
+    async fn release_with_error(&self, db: &DB, error: String) -> () {
+        report_critical_error(
+            format!(
+                "MySQL trigger {} failed to deliver changes, retrying from the last saved position: {}",
+                self.path, error
+            ),
+            db.clone(),
+            Some(&self.workspace_id),
+            None,
+        )
+        .await;
+
+        if let Err(err) = sqlx::query!(
+            r#"
+                UPDATE
+                    mysql_trigger
+                SET
+                    error = $1,
+                    server_id = NULL,
+                    last_server_ping = NULL
+                WHERE
+                    workspace_id = $2 AND
+                    path = $3 AND
+                    server_id = $4
+            "#,
+            error,
+            self.workspace_id,
+            self.path,
+            *INSTANCE_NAME
+        )
+        .execute(db)
+        .await
+        {
+            tracing::error!("Could not release mysql trigger {}: {:?}", self.path, err);
+        }
+    }
+
+    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
+        fetch_api_authed(
+            self.edited_by.clone(),
+            self.email.clone(),
+            &self.workspace_id,
+            db,
+            Some(format!("mysql-{}", self.path)),
+        )
+        .await
+    }
+}
+
+impl TriggerJobArgs<HashMap<String, Box<RawValue>>> for MysqlTrigger {
+    fn v1_payload_fn(payload: HashMap<String, Box<RawValue>>) -> HashMap<String, Box<RawValue>> {
+        payload
+    }
+
+    fn trigger_kind() -> TriggerKind {
+        TriggerKind::Mysql
+    }
+}
+
+async fn listen_to_binlog(
+    mysql: MysqlConfig,
+    db: DB,
+    mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    tokio::select! {
+        biased;
+
+        _ = killpill_rx.recv() => {
+            return;
+        }
+
+        _ = loop_ping(&db, &mysql, None) => {
+            return;
+        }
+
+        result = mysql.stream_changes(&db) => {
+            match (result, &mysql) {
+                (Ok(()), _) => {}
+                (Err(Error::Delivery(err)), MysqlConfig::Trigger(trigger)) => {
+                    trigger.release_with_error(&db, err).await
+                }
+                (Err(err), _) => {
+                    tracing::error!(
+                        "MySQL trigger {} error while listening to the binlog: {}",
+                        mysql.get_path(),
+                        &err
+                    );
+                    mysql.disable_with_error(&db, err.to_string()).await
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CaptureConfigForMysqlTrigger {
+    trigger_config: SqlxJson<MysqlTriggerConfig>,
+    path: String,
+    is_flow: bool,
+    workspace_id: String,
+    owner: String,
+    email: String,
+}
+
+impl CaptureConfigForMysqlTrigger {
+    async fn try_to_listen_to_binlog(
+        self,
+        db: DB,
+        killpill_rx: tokio::sync::broadcast::Receiver<()>,
+    ) -> () {
+        match sqlx::query_scalar!(
+            r#"
+            UPDATE
+                capture_config
+            SET
+                server_id = $1,
+                last_server_ping = now(),
+                error = 'Connecting...'
+            WHERE
+                last_client_ping > NOW() - INTERVAL '10 seconds' AND
+                workspace_id = $2 AND
+                path = $3 AND
+                is_flow = $4 AND
+                trigger_kind = 'mysql' AND
+                (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
+            RETURNING true
+            "#,
+            *INSTANCE_NAME,
+            self.workspace_id,
+            self.path,
+            self.is_flow,
+        )
+        .fetch_optional(&db)
+        .await
+        {
+            Ok(has_lock) => {
+                if has_lock.flatten().unwrap_or(false) {
+                    tokio::spawn(listen_to_binlog(MysqlConfig::Capture(self), db, killpill_rx));
+                } else {
+                    tracing::info!("MySQL {} already being listened to", self.path);
+                }
+            }
+            Err(err) => {
+                tracing::error!(
+                    "Error acquiring lock for capture mysql {}: {:?}",
+                    self.path,
+                    err
+                );
+            }
+        };
+    }
+
+    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
+        match sqlx::query_scalar!(
+            r#"
+            UPDATE
+                capture_config
+            SET
+                last_server_ping = now(),
+                error = $1
+            WHERE
+                workspace_id = $2 AND
+                path = $3 AND
+                is_flow = $4 AND
+                trigger_kind = 'mysql' AND
+                server_id = $5 AND
+                last_client_ping > NOW() - INTERVAL '10 seconds'
+            RETURNING 1
+        "#,
+            error,
+            self.workspace_id,
+            self.path,
+            self.is_flow,
+            *INSTANCE_NAME
+        )
+        .fetch_optional(db)
+        .await
+        {
+            Ok(updated) => {
+                if updated.flatten().is_none() {
+                    // allow faster restart of mysql capture
+                    sqlx::query!(
+                        r#"UPDATE
+                        capture_config
+                    SET
+                        last_server_ping = NULL
+                    WHERE
+                        workspace_id = $1 AND
+                        path = $2 AND
+                        is_flow = $3 AND
+                        trigger_kind = 'mysql' AND
+                        server_id IS NULL
+                    "#,
+                        self.workspace_id,
+                        self.path,
+                        self.is_flow,
+                    )
+                    .execute(db)
+                    .await
+                    .ok();
+                    tracing::info!(
+                        "MySQL capture {} changed, disabled, or deleted, stopping...",
+                        self.path
+                    );
+                    return None;
+                }
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "Error updating ping of capture mysql {}: {:?}",
+                    self.path,
+                    err
+                );
+            }
+        };
+
+        Some(())
+    }
+
+    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
+        fetch_api_authed(
+            self.owner.clone(),
+            self.email.clone(),
+            &self.workspace_id,
+            db,
+            Some(format!("mysql-{}", self.get_trigger_path())),
+        )
+        .await
+    }
+
+    fn get_trigger_path(&self) -> String {
+        format!(
+            "{}-{}",
+            if self.is_flow { "flow" } else { "script" },
+            self.path
+        )
+    }
+
+    async fn disable_with_error(&self, db: &DB, error: String) -> () {
+        if let Err(err) = sqlx::query!(
+            r#"
+                UPDATE
+                    capture_config
+                SET
+                    error = $1,
+                    server_id = NULL,
+                    last_server_ping = NULL
+                WHERE
+                    workspace_id = $2 AND
+                    path = $3 AND
+                    is_flow = $4 AND
+                    trigger_kind = 'mysql'
+            "#,
+            error,
+            self.workspace_id,
+            self.path,
+            self.is_flow,
+        )
+        .execute(db)
+        .await
+        {
+            tracing::error!(
+                "Could not disable mysql capture {} ({}) with err {}, disabling because of error {}",
+                self.path,
+                self.workspace_id,
+                err,
+                error
+            );
+        }
+    }
+
+    async fn handle(&self, db: &DB, payload: HashMap<String, Box<RawValue>>) -> () {
+        let (main_args, preprocessor_args) =
+            MysqlTrigger::build_capture_payloads(payload, HashMap::new());
+        if let Err(err) = insert_capture_payload(
+            db,
+            &self.workspace_id,
+            &self.path,
+            self.is_flow,
+            &TriggerKind::Mysql,
+            main_args,
+            preprocessor_args,
+            &self.owner,
+        )
+        .await
+        {
+            tracing::error!("Error inserting capture payload: {:?}", err);
+        }
+    }
+}
+
+async fn listen_to_unlistened_mysql_events(
+    db: &DB,
+    killpill_rx: &tokio::sync::broadcast::Receiver<()>,
+) {
+    let mysql_triggers = sqlx::query_as!(
+        MysqlTrigger,
+        r#"
+            SELECT
+                mysql_resource_path,
+                include_tables,
+                exclude_tables,
+                gtid_set,
+                workspace_id,
+                path,
+                script_path,
+                is_flow,
+                edited_by,
+                email,
+                edited_at,
+                server_id,
+                last_server_ping,
+                extra_perms,
+                error,
+                enabled,
+                error_handler_path,
+                error_handler_args as "error_handler_args: _",
+                retry as "retry: _"
+            FROM
+                mysql_trigger
+            WHERE
+                enabled IS TRUE
+                AND (last_server_ping IS NULL OR
+                    last_server_ping < now() - interval '15 seconds'
+                )
+            "#
+    )
+    .fetch_all(db)
+    .await;
+
+    match mysql_triggers {
+        Ok(mut triggers) => {
+            triggers.shuffle(&mut rand::rng());
+            for trigger in triggers {
+                trigger
+                    .try_to_listen_to_binlog(db.clone(), killpill_rx.resubscribe())
+                    .await;
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error fetching mysql triggers: {:?}", err);
+        }
+    };
+
+    let mysql_triggers_capture = sqlx::query_as!(
+        CaptureConfigForMysqlTrigger,
+        r#"
+            SELECT
+                path,
+                is_flow,
+                workspace_id,
+                owner,
+                email,
+                trigger_config as "trigger_config!: _"
+            FROM
+                capture_config
+            WHERE
+                trigger_kind = 'mysql' AND
+                last_client_ping > NOW() - INTERVAL '10 seconds' AND
+                trigger_config IS NOT NULL AND
+                (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
+            "#
+    )
+    .fetch_all(db)
+    .await;
+
+    match mysql_triggers_capture {
+        Ok(mut captures) => {
+            captures.shuffle(&mut rand::rng());
+            for capture in captures {
+                capture
+                    .try_to_listen_to_binlog(db.clone(), killpill_rx.resubscribe())
+                    .await;
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error fetching captures mysql triggers: {:?}", err);
+        }
+    };
+}
+
+pub fn start_mysql_binlog_consumer(
+    db: DB,
+    mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
+) {
+    tokio::spawn(async move {
+        listen_to_unlistened_mysql_events(&db, &killpill_rx).await;
+        loop {
+            tokio::select! {
+                biased;
+                _ = killpill_rx.recv() => {
+                    return;
+                }
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(15)) => {
+                    listen_to_unlistened_mysql_events(&db, &killpill_rx).await
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SID: &str = "3e11fa47-71ca-11e1-9e33-c80aa9429562";
+    const OTHER_SID: &str = "8e0a8a53-1f2b-11ef-a3b2-0242ac120002";
+
+    fn gtid_set(s: &str) -> GtidSet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_gtid_set_parse_and_display() {
+        let s = format!("{SID}:1-5:7,{OTHER_SID}:1-3");
+        assert_eq!(gtid_set(&s).to_string(), s);
+        assert_eq!(
+            gtid_set(&format!("{OTHER_SID}:2,\n{SID}:1-5")).to_string(),
+            format!("{SID}:1-5,{OTHER_SID}:2")
+        );
+        assert_eq!(gtid_set("").to_string(), "");
+
+        for invalid in [
+            "not-a-uuid:1-5",
+            &format!("{SID}:a-5"),
+            &format!("{SID}:1-"),
+        ] {
+            assert!(
+                invalid.parse::<GtidSet>().is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_gtid_set_add() {
+        let sid = Uuid::parse_str(SID).unwrap();
+        let mut gtids = gtid_set(&format!("{SID}:1-5:7:10-12"));
+
+        // already in the set
+        gtids.add(sid, 3);
+        assert_eq!(gtids.to_string(), format!("{SID}:1-5:7:10-12"));
+        // extends the end of an interval
+        gtids.add(sid, 8);
+        assert_eq!(gtids.to_string(), format!("{SID}:1-5:7-8:10-12"));
+        // fills the gap between two intervals
+        gtids.add(sid, 6);
+        assert_eq!(gtids.to_string(), format!("{SID}:1-8:10-12"));
+        // extends the start of an interval
+        gtids.add(sid, 9);
+        assert_eq!(gtids.to_string(), format!("{SID}:1-12"));
+        // new interval after the last one
+        gtids.add(sid, 20);
+        assert_eq!(gtids.to_string(), format!("{SID}:1-12:20"));
+
+        let mut gtids = gtid_set(&format!("{SID}:5-6"));
+        // new interval before the first one
+        gtids.add(sid, 2);
+        assert_eq!(gtids.to_string(), format!("{SID}:2:5-6"));
+        gtids.add(sid, 4);
+        assert_eq!(gtids.to_string(), format!("{SID}:2:4-6"));
+
+        // new source server
+        gtids.add(Uuid::parse_str(OTHER_SID).unwrap(), 1);
+        assert_eq!(gtids.to_string(), format!("{SID}:2:4-6,{OTHER_SID}:1"));
+    }
+
+    #[test]
+    fn test_table_matches() {
+        assert!(table_matches("shop.orders", "shop", "orders"));
+        assert!(!table_matches("shop.orders", "shop", "order_items"));
+        assert!(table_matches("orders", "any", "orders"));
+        assert!(table_matches("shop.order*", "shop", "order_items"));
+        assert!(table_matches("*.orders", "billing", "orders"));
+        assert!(table_matches("s*p.*_items", "shop", "order_items"));
+        assert!(!table_matches("s*p.*_items", "shops", "order_items"));
+        assert!(table_matches("*", "any", "any"));
+    }
+}
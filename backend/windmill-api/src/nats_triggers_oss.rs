@@ -3,11 +3,13 @@
 pub use crate::nats_triggers_ee::*;
 
 #[cfg(not(feature = "private"))]
-use crate::db::DB;
+use crate::{db::DB, triggers::handler::Trigger};
 #[cfg(not(feature = "private"))]
 use axum::Router;
 #[cfg(not(feature = "private"))]
 use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "private"))]
+use sqlx::FromRow;
 
 #[cfg(not(feature = "private"))]
 #[derive(Serialize, Deserialize)]
@@ -27,11 +29,9 @@ pub fn start_nats_consumers(_db: DB, mut _killpill_rx: tokio::sync::broadcast::R
 #[cfg(not(feature = "private"))]
 pub enum NatsTriggerConfigConnection {}
 
-#[derive(Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, FromRow)]
 #[cfg(not(feature = "private"))]
-pub struct NatsTrigger {
-    pub workspace_id: String,
-    pub path: String,
+pub struct NatsConfig {
     pub nats_resource_path: String,
     pub subjects: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -39,23 +39,7 @@ pub struct NatsTrigger {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub consumer_name: Option<String>,
     pub use_jetstream: bool,
-    pub script_path: String,
-    pub is_flow: bool,
-    pub edited_by: String,
-    pub email: String,
-    pub edited_at: chrono::DateTime<chrono::Utc>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub server_id: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
-    pub extra_perms: serde_json::Value,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_args: Option<serde_json::Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub retry: Option<serde_json::Value>,
 }
+
+#[cfg(not(feature = "private"))]
+pub type NatsTrigger = Trigger<NatsConfig>;
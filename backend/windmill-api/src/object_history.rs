@@ -0,0 +1,536 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! History of the deployed objects of a workspace, recorded by `handle_deployment_metadata`.
+//! Objects, or a whole folder, can be restored to their state at a given time. Scripts, flows
+//! and apps are restored by deploying one of their versions instead.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{Postgres, Transaction};
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    utils::{not_found_if_none, paginate, require_admin, Pagination, StripPath},
+};
+use windmill_git_sync::{
+    handle_deployment_metadata,
+    object_history::{deployed_object, history_table, snapshot},
+};
+use windmill_queue::schedule::{get_schedule_opt, push_scheduled_job};
+
+use crate::{
+    db::{ApiAuthed, DB},
+    promotions::diff_pointers,
+    schedule::clear_schedule,
+};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/list/:kind/*path", get(list_history))
+        .route("/get/:id", get(get_history_entry))
+        .route("/diff/:from/:to", get(diff_history_entries))
+        .route("/restore", post(restore_object))
+        .route("/restore_folder", post(restore_folder))
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "OBJECT_HISTORY_ACTION", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectHistoryAction {
+    /// State of the object when its history started to be recorded
+    Baseline,
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Serialize)]
+struct ObjectHistoryEntry {
+    id: i64,
+    kind: String,
+    path: String,
+    action: ObjectHistoryAction,
+    edited_by: String,
+    edited_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ObjectHistoryVersion {
+    id: i64,
+    kind: String,
+    path: String,
+    action: ObjectHistoryAction,
+    value: Option<Value>,
+    previous_value: Option<Value>,
+    edited_by: String,
+    edited_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct ListHistoryQuery {
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+async fn list_history(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, path)): Path<(String, String, StripPath)>,
+    Query(query): Query<ListHistoryQuery>,
+) -> JsonResult<Vec<ObjectHistoryEntry>> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let (per_page, offset) = paginate(Pagination { page: query.page, per_page: query.per_page });
+
+    let entries = sqlx::query_as!(
+        ObjectHistoryEntry,
+        r#"
+        SELECT id, kind, path, action AS "action!: _", edited_by, edited_at
+        FROM object_history
+        WHERE workspace_id = $1 AND kind = $2 AND path = $3
+        ORDER BY id DESC
+        OFFSET $4
+        LIMIT $5
+        "#,
+        w_id,
+        kind,
+        path.to_path(),
+        offset as i64,
+        per_page as i64,
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(entries))
+}
+
+async fn fetch_history_version(db: &DB, w_id: &str, id: i64) -> Result<ObjectHistoryVersion> {
+    let version = sqlx::query_as!(
+        ObjectHistoryVersion,
+        r#"
+        SELECT id, kind, path, action AS "action!: _", value, previous_value, edited_by, edited_at
+        FROM object_history
+        WHERE workspace_id = $1 AND id = $2
+        "#,
+        w_id,
+        id
+    )
+    .fetch_optional(db)
+    .await?;
+    not_found_if_none(version, "Object history entry", id.to_string())
+}
+
+async fn get_history_entry(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> JsonResult<ObjectHistoryVersion> {
+    require_admin(authed.is_admin, &authed.username)?;
+    Ok(Json(fetch_history_version(&db, &w_id, id).await?))
+}
+
+#[derive(Serialize)]
+struct HistoryChange {
+    pointer: String,
+    from: Option<Value>,
+    to: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct HistoryDiff {
+    from: i64,
+    to: i64,
+    changes: Vec<HistoryChange>,
+}
+
+async fn diff_history_entries(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, from, to)): Path<(String, i64, i64)>,
+) -> JsonResult<HistoryDiff> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let from_value = fetch_history_version(&db, &w_id, from)
+        .await?
+        .value
+        .unwrap_or(Value::Null);
+    let to_value = fetch_history_version(&db, &w_id, to)
+        .await?
+        .value
+        .unwrap_or(Value::Null);
+
+    let mut pointers = vec![];
+    diff_pointers(String::new(), &from_value, &to_value, &mut pointers);
+    let changes = pointers
+        .into_iter()
+        .map(|pointer| HistoryChange {
+            from: from_value.pointer(&pointer).cloned(),
+            to: to_value.pointer(&pointer).cloned(),
+            pointer,
+        })
+        .collect();
+
+    Ok(Json(HistoryDiff { from, to, changes }))
+}
+
+#[derive(Deserialize)]
+struct RestoreObject {
+    kind: String,
+    path: String,
+    at: DateTime<Utc>,
+    dry_run: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct RestoreFolder {
+    folder: String,
+    at: DateTime<Utc>,
+    dry_run: Option<bool>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum RestoreAction {
+    Restore,
+    Delete,
+    Unchanged,
+    Skip,
+}
+
+#[derive(Serialize)]
+struct RestoredObject {
+    kind: String,
+    path: String,
+    action: RestoreAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RestoreReport {
+    at: DateTime<Utc>,
+    dry_run: bool,
+    items: Vec<RestoredObject>,
+}
+
+/// Value of the object at the given time: `Some(None)` if it did not exist, `None` if its
+/// history does not go back that far
+async fn value_at(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    kind: &str,
+    path: &str,
+    at: DateTime<Utc>,
+) -> Result<Option<Option<Value>>> {
+    let before = sqlx::query_scalar!(
+        "SELECT value FROM object_history
+        WHERE workspace_id = $1 AND kind = $2 AND path = $3 AND edited_at <= $4
+        ORDER BY id DESC LIMIT 1",
+        w_id,
+        kind,
+        path,
+        at
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    if before.is_some() {
+        return Ok(before);
+    }
+
+    let after = sqlx::query!(
+        r#"SELECT previous_value, action AS "action!: ObjectHistoryAction" FROM object_history
+        WHERE workspace_id = $1 AND kind = $2 AND path = $3 AND edited_at > $4
+        ORDER BY id LIMIT 1"#,
+        w_id,
+        kind,
+        path,
+        at
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(after
+        .filter(|after| after.action != ObjectHistoryAction::Baseline)
+        .map(|after| after.previous_value))
+}
+
+/// Table of a restorable kind and its key column, `None` when the row is the one of the
+/// workspace
+fn restore_table(kind: &str) -> Option<(&'static str, Option<&'static str>)> {
+    match kind {
+        "group" => Some(("group_", Some("name"))),
+        "settings" => Some(("workspace_settings", None)),
+        kind => history_table(kind).map(|(table, key)| (table, Some(key))),
+    }
+}
+
+async fn delete_row(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    key: Option<&str>,
+    w_id: &str,
+    path: &str,
+) -> Result<()> {
+    let filter = key
+        .map(|key| format!(" AND {key} = $2"))
+        .unwrap_or_default();
+    let sql = format!("DELETE FROM {table} WHERE workspace_id = $1{filter}");
+    let mut query = sqlx::query(&sql).bind(w_id);
+    if key.is_some() {
+        query = query.bind(path);
+    }
+    query.execute(&mut **tx).await?;
+    Ok(())
+}
+
+/// Writes the recorded value back. Columns that are not part of the recorded value, such as
+/// the volatile ones or columns added since, keep their current value.
+async fn replace_row(
+    tx: &mut Transaction<'_, Postgres>,
+    table: &str,
+    key: Option<&str>,
+    w_id: &str,
+    path: &str,
+    value: Value,
+) -> Result<()> {
+    let filter = key
+        .map(|key| format!(" AND t.{key} = $2"))
+        .unwrap_or_default();
+    let sql = format!("SELECT to_jsonb(t) FROM {table} t WHERE t.workspace_id = $1{filter}");
+    let mut query = sqlx::query_scalar::<_, Value>(&sql).bind(w_id);
+    if key.is_some() {
+        query = query.bind(path);
+    }
+    let current = query.fetch_optional(&mut **tx).await?;
+
+    let mut row = match (current, value) {
+        (Some(Value::Object(mut current)), Value::Object(value)) => {
+            current.extend(value);
+            current
+        }
+        (_, Value::Object(value)) => value,
+        _ => {
+            return Err(Error::InternalErr(format!(
+                "Recorded value of {path} is not an object"
+            )))
+        }
+    };
+    row.insert("workspace_id".to_string(), Value::String(w_id.to_string()));
+
+    delete_row(tx, table, key, w_id, path).await?;
+    sqlx::query(&format!(
+        "INSERT INTO {table} OVERRIDING SYSTEM VALUE
+        SELECT * FROM jsonb_populate_record(NULL::{table}, $1)"
+    ))
+    .bind(Value::Object(row))
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+async fn restore_item<'c>(
+    db: &DB,
+    authed: &ApiAuthed,
+    mut tx: Transaction<'c, Postgres>,
+    w_id: &str,
+    kind: &str,
+    path: &str,
+    at: DateTime<Utc>,
+) -> Result<(Transaction<'c, Postgres>, RestoredObject)> {
+    let item = |action: RestoreAction, reason: Option<&str>| RestoredObject {
+        kind: kind.to_string(),
+        path: path.to_string(),
+        action,
+        reason: reason.map(str::to_string),
+    };
+
+    let Some((table, key)) = restore_table(kind) else {
+        let reason = match kind {
+            "script" | "flow" | "app" => "restored by deploying one of its versions",
+            _ => "not restorable",
+        };
+        return Ok((tx, item(RestoreAction::Skip, Some(reason))));
+    };
+    let Some(value) = value_at(&mut tx, w_id, kind, path, at).await? else {
+        return Ok((
+            tx,
+            item(RestoreAction::Skip, Some("no history at that time")),
+        ));
+    };
+    if snapshot(&mut *tx, w_id, kind, path).await? == value {
+        return Ok((tx, item(RestoreAction::Unchanged, None)));
+    }
+
+    if kind == "schedule" {
+        clear_schedule(&mut tx, path, w_id).await?;
+    }
+    if kind == "group" {
+        sqlx::query!(
+            "DELETE FROM usr_to_group WHERE workspace_id = $1 AND group_ = $2",
+            w_id,
+            path
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let Some(mut value) = value else {
+        if kind == "settings" {
+            return Ok((
+                tx,
+                item(RestoreAction::Skip, Some("the settings cannot be deleted")),
+            ));
+        }
+        delete_row(&mut tx, table, key, w_id, path).await?;
+        return Ok((tx, item(RestoreAction::Delete, None)));
+    };
+
+    let members = match value.as_object_mut() {
+        Some(o) if kind == "group" => o.remove("members"),
+        _ => None,
+    };
+    replace_row(&mut tx, table, key, w_id, path, value).await?;
+
+    if let Some(members) = members {
+        let members = serde_json::from_value::<Vec<String>>(members).unwrap_or_default();
+        sqlx::query!(
+            "INSERT INTO usr_to_group (workspace_id, group_, usr) SELECT $1, $2, unnest($3::text[])",
+            w_id,
+            path,
+            &members
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    if kind == "schedule" {
+        if let Some(schedule) = get_schedule_opt(&mut *tx, w_id, path).await? {
+            if schedule.enabled {
+                tx = push_scheduled_job(db, tx, &schedule, Some(&authed.clone().into())).await?;
+            }
+        }
+    }
+
+    Ok((tx, item(RestoreAction::Restore, None)))
+}
+
+async fn finish_restore(
+    db: &DB,
+    authed: &ApiAuthed,
+    mut tx: Transaction<'_, Postgres>,
+    w_id: &str,
+    resource: &str,
+    report: RestoreReport,
+) -> Result<RestoreReport> {
+    let at = report.at.to_rfc3339();
+    audit_log(
+        &mut *tx,
+        authed,
+        "object_history.restore",
+        ActionKind::Update,
+        w_id,
+        Some(resource),
+        Some(HashMap::from([("at", at.as_str())])),
+    )
+    .await?;
+
+    if report.dry_run {
+        tx.rollback().await?;
+        return Ok(report);
+    }
+    tx.commit().await?;
+
+    for item in &report.items {
+        if !matches!(item.action, RestoreAction::Restore | RestoreAction::Delete) {
+            continue;
+        }
+        if let Some(obj) = deployed_object(&item.kind, &item.path) {
+            handle_deployment_metadata(
+                &authed.email,
+                &authed.username,
+                db,
+                w_id,
+                obj,
+                Some(format!("{} restored to {at}", item.path)),
+                true,
+            )
+            .await?;
+        }
+    }
+    Ok(report)
+}
+
+async fn restore_object(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(req): Json<RestoreObject>,
+) -> JsonResult<RestoreReport> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let tx = user_db.begin(&authed).await?;
+    let (tx, item) = restore_item(&db, &authed, tx, &w_id, &req.kind, &req.path, req.at).await?;
+    let report =
+        RestoreReport { at: req.at, dry_run: req.dry_run.unwrap_or(false), items: vec![item] };
+
+    Ok(Json(
+        finish_restore(&db, &authed, tx, &w_id, &req.path, report).await?,
+    ))
+}
+
+/// Restores the folder and every object under it, objects created since are deleted
+async fn restore_folder(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(req): Json<RestoreFolder>,
+) -> JsonResult<RestoreReport> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    let objects = sqlx::query!(
+        "SELECT DISTINCT kind, path, kind = 'folder' AS is_folder FROM object_history
+        WHERE workspace_id = $1 AND (starts_with(path, $2) OR (kind = 'folder' AND path = $3))
+        ORDER BY is_folder DESC, kind, path",
+        w_id,
+        format!("f/{}/", req.folder),
+        req.folder
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut items = vec![];
+    for object in objects {
+        let (restore_tx, item) =
+            restore_item(&db, &authed, tx, &w_id, &object.kind, &object.path, req.at).await?;
+        tx = restore_tx;
+        items.push(item);
+    }
+    let report = RestoreReport { at: req.at, dry_run: req.dry_run.unwrap_or(false), items };
+
+    Ok(Json(
+        finish_restore(
+            &db,
+            &authed,
+            tx,
+            &w_id,
+            &format!("f/{}", req.folder),
+            report,
+        )
+        .await?,
+    ))
+}
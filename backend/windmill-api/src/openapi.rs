@@ -5,7 +5,12 @@ use std::{
 
 use anyhow::anyhow;
 use axum::{
-    body::Body, extract::Path, http, response::Response, routing::post, Extension, Json, Router,
+    body::Body,
+    extract::{Path, Query},
+    http,
+    response::Response,
+    routing::{get, post},
+    Extension, Json, Router,
 };
 use http::{header, HeaderValue, Method, StatusCode};
 use indexmap::IndexMap;
@@ -25,10 +30,14 @@ use crate::db::ApiAuthed;
 #[cfg(feature = "http_trigger")]
 use {
     crate::{
-        http_trigger_args::HttpMethod, http_trigger_auth::ApiKeyAuthentication,
-        http_triggers::AuthenticationMethod, resources::try_get_resource_from_db_as,
+        http_trigger_args::HttpMethod,
+        http_trigger_auth::ApiKeyAuthentication,
+        http_triggers::AuthenticationMethod,
+        resources::try_get_resource_from_db_as,
+        trigger_helpers::{get_runnable_format, get_runnable_schema, RunnableId},
     },
     itertools::Itertools,
+    windmill_common::triggers::TriggerKind,
 };
 
 lazy_static::lazy_static! {
@@ -47,11 +56,13 @@ const DEFAULT_REQUEST_KEY: &'static str = "defaultRequest";
 const DEFAULT_ASYNC_RESPONSE_KEY: &'static str = "AsyncResponse";
 const DEFAULT_SYNC_RESPONSE_KEY: &'static str = "SyncResponse";
 const DEFAULT_PAYLOAD_PARAM_KEY: &'static str = "PayloadParam";
+const VALIDATION_ERROR_RESPONSE_KEY: &'static str = "ValidationError";
 
 pub fn openapi_service() -> Router {
     Router::new()
         .route("/generate", post(generate_openapi_spec))
         .route("/download", post(download_spec))
+        .route("/http_routes", get(generate_http_routes_spec))
 }
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -167,6 +178,8 @@ pub struct FuturePath {
     summary: Option<String>,
     description: Option<String>,
     security_scheme: Option<SecurityScheme>,
+    request_schema: Option<Value>,
+    validate_request: bool,
 }
 
 impl FuturePath {
@@ -178,7 +191,26 @@ impl FuturePath {
         description: Option<String>,
         security_scheme: Option<SecurityScheme>,
     ) -> FuturePath {
-        FuturePath { route_path, kind, is_async, summary, description, security_scheme }
+        FuturePath {
+            route_path,
+            kind,
+            is_async,
+            summary,
+            description,
+            security_scheme,
+            request_schema: None,
+            validate_request: false,
+        }
+    }
+
+    pub fn with_request_schema(
+        mut self,
+        request_schema: Option<Value>,
+        validate_request: bool,
+    ) -> FuturePath {
+        self.request_schema = request_schema;
+        self.validate_request = validate_request;
+        self
     }
 }
 
@@ -279,8 +311,19 @@ fn generate_paths(
         })
     };
 
-    let generate_response = |is_async: bool| {
-        let responses = if is_async {
+    let generate_request = |schema: &Value| {
+        serde_json::json!({
+            "required": true,
+            "content": {
+                "application/json": {
+                    "schema": schema
+                }
+            }
+        })
+    };
+
+    let generate_response = |is_async: bool, validate_request: bool| {
+        let mut responses = if is_async {
             serde_json::json!({
                 "200": {
                     "$ref": format!("#/components/responses/{DEFAULT_ASYNC_RESPONSE_KEY}")
@@ -294,6 +337,12 @@ fn generate_paths(
             }))
         };
 
+        if validate_request {
+            responses["400"] = serde_json::json!({
+                "$ref": format!("#/components/responses/{VALIDATION_ERROR_RESPONSE_KEY}")
+            });
+        }
+
         responses
     };
 
@@ -388,7 +437,11 @@ fn generate_paths(
                 );
 
                 if method != Method::GET {
-                    method_map.insert("requestBody", generate_default_request());
+                    let request_body = match path.request_schema.as_ref() {
+                        Some(schema) => generate_request(schema),
+                        None => generate_default_request(),
+                    };
+                    method_map.insert("requestBody", request_body);
                 } else if is_webhook {
                     method_map.insert(
                         "parameters",
@@ -398,7 +451,10 @@ fn generate_paths(
                     );
                 }
 
-                method_map.insert("responses", generate_response(is_async));
+                method_map.insert(
+                    "responses",
+                    generate_response(is_async, path.validate_request),
+                );
 
                 path_object.insert(method.to_string().to_lowercase(), to_value(&method_map)?);
             }
@@ -582,6 +638,40 @@ fn generate_components(future_paths: &[FuturePath]) -> Map<String, Value> {
 
     }));
 
+    if future_paths.iter().any(|path| path.validate_request) {
+        if let Some(Value::Object(responses)) = components.get_mut("responses") {
+            responses.insert(
+                VALIDATION_ERROR_RESPONSE_KEY.to_owned(),
+                serde_json::json!({
+                    "description": "The request does not match the schema of the route.",
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "error": { "type": "string" },
+                                    "errors": {
+                                        "type": "array",
+                                        "items": {
+                                            "type": "object",
+                                            "properties": {
+                                                "location": { "type": "string", "enum": ["body", "query", "path"] },
+                                                "field": { "type": "string" },
+                                                "message": { "type": "string" }
+                                            },
+                                            "required": ["location", "field", "message"]
+                                        }
+                                    }
+                                },
+                                "required": ["error", "errors"]
+                            }
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
     components
 }
 
@@ -658,44 +748,127 @@ async fn http_routes_to_future_paths(
     http_route_filters: Option<&[HttpRouteFilter]>,
     w_id: &str,
 ) -> Result<Vec<FuturePath>> {
-    let mut http_routes = Vec::new();
-
-    if let Some(http_route_filters) = http_route_filters {
-        let path_regex = http_route_filters
-            .iter()
-            .map(|filter| {
-                transform_to_minified_postgres_regex(&format!(
-                    "f/{}/{}",
-                    filter.folder_regex, filter.path_regex
-                ))
-            })
-            .collect_vec();
-
-        let route_path_regex = http_route_filters
-            .iter()
-            .map(|filter| transform_to_minified_postgres_regex(&filter.route_path_regex))
-            .collect_vec();
-
-        #[derive(Debug, Deserialize)]
-        struct MinifiedHttpTrigger {
-            route_path: String,
-            http_method: HttpMethod,
-            is_async: bool,
-            workspaced_route: bool,
-            summary: Option<String>,
-            description: Option<String>,
-            authentication_method: AuthenticationMethod,
-            authentication_resource_path: Option<String>,
-        }
+    let Some(http_route_filters) = http_route_filters else {
+        return Ok(Vec::new());
+    };
+
+    let path_regex = http_route_filters
+        .iter()
+        .map(|filter| {
+            transform_to_minified_postgres_regex(&format!(
+                "f/{}/{}",
+                filter.folder_regex, filter.path_regex
+            ))
+        })
+        .collect_vec();
+
+    let route_path_regex = http_route_filters
+        .iter()
+        .map(|filter| transform_to_minified_postgres_regex(&filter.route_path_regex))
+        .collect_vec();
+
+    matching_http_routes_to_future_paths(
+        db,
+        user_db,
+        authed,
+        pg_pool,
+        &path_regex,
+        &route_path_regex,
+        w_id,
+    )
+    .await
+}
+
+/// The request body schema is the schema of the main function of the runnable,
+/// unless the request is reshaped before reaching it (preprocessor or raw body).
+#[cfg(feature = "http_trigger")]
+async fn get_http_route_request_schema(
+    db: &DB,
+    w_id: &str,
+    script_path: &str,
+    is_flow: bool,
+    wrap_body: bool,
+    raw_string: bool,
+) -> Result<Option<Value>> {
+    if script_path.is_empty() || raw_string {
+        return Ok(None);
+    }
+
+    let runnable_id = if is_flow {
+        RunnableId::from_flow_path(script_path)
+    } else {
+        RunnableId::from_script_path(script_path)
+    };
+
+    let runnable_format =
+        get_runnable_format(runnable_id.clone(), w_id, db, &TriggerKind::Http).await?;
+
+    if runnable_format.has_preprocessor {
+        return Ok(None);
+    }
+
+    let Some(schema) = get_runnable_schema(&runnable_id, w_id, db).await? else {
+        return Ok(None);
+    };
+
+    let mut schema = serde_json::from_str::<Value>(&schema)?;
+
+    if let Value::Object(schema) = &mut schema {
+        schema.remove("$schema");
+    }
+
+    let schema = if wrap_body {
+        schema
+            .get("properties")
+            .and_then(|properties| properties.get("body"))
+            .cloned()
+    } else {
+        Some(schema)
+    };
+
+    Ok(schema)
+}
+
+#[cfg(feature = "http_trigger")]
+async fn matching_http_routes_to_future_paths(
+    db: &DB,
+    user_db: UserDB,
+    authed: &ApiAuthed,
+    pg_pool: &mut PgConnection,
+    path_regex: &[String],
+    route_path_regex: &[String],
+    w_id: &str,
+) -> Result<Vec<FuturePath>> {
+    #[derive(Debug, Deserialize)]
+    struct MinifiedHttpTrigger {
+        route_path: String,
+        script_path: String,
+        is_flow: bool,
+        http_method: HttpMethod,
+        is_async: bool,
+        workspaced_route: bool,
+        wrap_body: bool,
+        raw_string: bool,
+        validate_request: bool,
+        summary: Option<String>,
+        description: Option<String>,
+        authentication_method: AuthenticationMethod,
+        authentication_resource_path: Option<String>,
+    }
 
-        http_routes = sqlx::query_as!(
-            MinifiedHttpTrigger,
-            r#"
+    let http_routes = sqlx::query_as!(
+        MinifiedHttpTrigger,
+        r#"
         SELECT
             route_path,
+            script_path,
+            is_flow,
             http_method AS "http_method: _",
             is_async,
             workspaced_route,
+            wrap_body,
+            raw_string,
+            validate_request,
             summary,
             description,
             authentication_method AS "authentication_method: _",
@@ -707,13 +880,12 @@ async fn http_routes_to_future_paths(
            route_path ~ ANY($2) AND
            workspace_id = $3
         "#,
-            &path_regex,
-            &route_path_regex,
-            &w_id
-        )
-        .fetch_all(pg_pool)
-        .await?;
-    }
+        path_regex,
+        route_path_regex,
+        &w_id
+    )
+    .fetch_all(pg_pool)
+    .await?;
 
     let mut openapi_future_paths = Vec::with_capacity(http_routes.len());
 
@@ -759,6 +931,23 @@ async fn http_routes_to_future_paths(
             HttpMethod::Delete => Method::DELETE,
         };
 
+        let request_schema = get_http_route_request_schema(
+            db,
+            w_id,
+            &http_route.script_path,
+            http_route.is_flow,
+            http_route.wrap_body,
+            http_route.raw_string,
+        )
+        .await
+        .unwrap_or_else(|err| {
+            tracing::warn!(
+                "Could not get request schema of HTTP route {}: {err:#}",
+                http_route.route_path
+            );
+            None
+        });
+
         let future_path = FuturePath::new(
             route_path,
             Kind::HttpRoute(HttpRouteConfig::new(method)),
@@ -766,7 +955,8 @@ async fn http_routes_to_future_paths(
             http_route.summary,
             http_route.description,
             auth_method,
-        );
+        )
+        .with_request_schema(request_schema, http_route.validate_request);
 
         openapi_future_paths.push(future_path);
     }
@@ -786,6 +976,19 @@ async fn http_routes_to_future_paths(
     Ok(Vec::new())
 }
 
+#[cfg(not(feature = "http_trigger"))]
+async fn matching_http_routes_to_future_paths(
+    _db: &DB,
+    _user_db: UserDB,
+    _authed: &ApiAuthed,
+    _pg_pool: &mut PgConnection,
+    _path_regex: &[String],
+    _route_path_regex: &[String],
+    _w_id: &str,
+) -> Result<Vec<FuturePath>> {
+    Ok(Vec::new())
+}
+
 async fn webhook_to_future_paths(
     pg_pool: &mut PgConnection,
     webhook_filters: Option<&[WebhookFilter]>,
@@ -980,3 +1183,48 @@ async fn download_spec(
 
     Ok(response)
 }
+
+#[derive(Debug, Deserialize)]
+struct HttpRoutesOpenAPIQuery {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    folder: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_url")]
+    url: Option<Url>,
+    #[serde(default)]
+    format: Format,
+}
+
+async fn generate_http_routes_spec(
+    Extension(authed): Extension<ApiAuthed>,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<HttpRoutesOpenAPIQuery>,
+) -> Result<String> {
+    let path_regex = match query.folder.as_deref() {
+        Some(folder) => format!("f/{}/*", folder.trim_matches('/')),
+        None => "*".to_string(),
+    };
+
+    let mut tx = user_db.clone().begin(&authed).await?;
+
+    let openapi_future_paths = matching_http_routes_to_future_paths(
+        &db,
+        user_db,
+        &authed,
+        &mut tx,
+        &[transform_to_minified_postgres_regex(&path_regex)],
+        &[transform_to_minified_postgres_regex("*")],
+        &w_id,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    generate_openapi_document(
+        None,
+        query.url.as_ref(),
+        openapi_future_paths,
+        query.format,
+    )
+}
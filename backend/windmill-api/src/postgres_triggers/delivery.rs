@@ -0,0 +1,584 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Map, Value};
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+use windmill_common::{
+    error::{Error, Result},
+    worker::to_raw_value,
+    DB,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum PostgresTriggerBatching {
+    /// one job per transaction with all the changes between its `Begin` and `Commit`
+    Transaction,
+    /// one job with up to `max_size` changes, pushed at the latest `max_wait_ms` after the first one
+    Window { max_size: u32, max_wait_ms: u64 },
+}
+
+impl PostgresTriggerBatching {
+    pub fn validate(&self) -> Result<()> {
+        if let PostgresTriggerBatching::Window { max_size, max_wait_ms } = self {
+            if *max_size == 0 || *max_wait_ms == 0 {
+                return Err(Error::BadRequest(
+                    "Batching window must have a max size and a max wait greater than 0"
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Change {
+    pub schema_name: String,
+    pub table_name: String,
+    pub transaction_type: &'static str,
+    pub old_row: Option<Map<String, Value>>,
+    pub row: Map<String, Value>,
+}
+
+impl Change {
+    pub fn into_payload(self) -> HashMap<String, Box<RawValue>> {
+        HashMap::from([
+            ("schema_name".to_string(), to_raw_value(&self.schema_name)),
+            ("table_name".to_string(), to_raw_value(&self.table_name)),
+            (
+                "transaction_type".to_string(),
+                to_raw_value(&self.transaction_type),
+            ),
+            ("old_row".to_string(), to_raw_value(&self.old_row)),
+            ("row".to_string(), to_raw_value(&self.row)),
+        ])
+    }
+}
+
+/// Number of times the job of a batch is pushed when changes are only acknowledged once their job
+/// succeeded, after which the last failed job is moved to the dead letters
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a failed job, doubled on each following retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+
+fn retry_delay(attempts: u32) -> Duration {
+    RETRY_BASE_DELAY * 2u32.saturating_pow(attempts.saturating_sub(1))
+}
+
+/// Position of a change in the replication stream: the commit lsn of its transaction and the
+/// index of the change in the transaction. Unlike the lsn of the change message, it is the same
+/// every time postgres sends the transaction again.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangePosition {
+    pub commit_lsn: u64,
+    pub index: u32,
+}
+
+impl fmt::Display for ChangePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:X}.{}", self.commit_lsn, self.index)
+    }
+}
+
+/// First and last change of a batch
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchBounds {
+    pub first: ChangePosition,
+    pub last: ChangePosition,
+}
+
+/// Payload of a job along with the position of the changes it holds, which identify it across
+/// redeliveries of the same changes
+#[derive(Clone)]
+pub struct Batch {
+    pub payload: HashMap<String, Box<RawValue>>,
+    pub bounds: BatchBounds,
+}
+
+impl Batch {
+    pub fn idempotency_key(&self) -> String {
+        format!("{}-{}", self.bounds.first, self.bounds.last)
+    }
+}
+
+/// What to do with a batch whose job failed
+pub enum Redelivery {
+    /// push the batch again, `failed_job` is to be replaced with the new job in `redelivered`
+    Retry { failed_job: Uuid, batch: Batch },
+    /// the job failed on every attempt, its changes are acknowledged and the job is to be kept
+    /// as a dead letter
+    DeadLetter(Uuid),
+}
+
+enum JobState {
+    Running,
+    RetryAt(Instant),
+    Retrying,
+    Done,
+}
+
+struct DeliveredJob {
+    job_id: Uuid,
+    batch: Batch,
+    attempts: u32,
+    state: JobState,
+}
+
+/// Groups the changes received from the replication stream into jobs and keeps track of the
+/// lsn that can be reported as flushed to postgres.
+///
+/// A transaction is acknowledged once the jobs of all its changes have been pushed, or once they
+/// have all succeeded with `wait_for_success`. Failed jobs are then pushed again with an
+/// exponential backoff, up to `MAX_DELIVERY_ATTEMPTS` times, and so are jobs deleted before
+/// completing. Changes of unacknowledged transactions are sent again by postgres when the
+/// replication restarts.
+pub struct ChangeDelivery {
+    batching: Option<PostgresTriggerBatching>,
+    wait_for_success: bool,
+    changes: Vec<Change>,
+    first_position: Option<ChangePosition>,
+    last_position: Option<ChangePosition>,
+    first_change_at: Option<Instant>,
+    in_transaction: bool,
+    /// commit lsn of the current transaction and index of its next change
+    commit_lsn: u64,
+    next_index: u32,
+    /// bounds of the last window pushed before a restart, replayed as is so that its changes get
+    /// the same idempotency key
+    replay: Option<BatchBounds>,
+    /// end lsn of the last commit whose changes are not all pushed yet
+    pending_commit_lsn: Option<u64>,
+    /// jobs pushed since the last acknowledged commit
+    pending_jobs: Vec<DeliveredJob>,
+    /// commits waiting for their jobs to succeed, in lsn order
+    in_flight: VecDeque<(u64, Vec<DeliveredJob>)>,
+    confirmed_lsn: u64,
+}
+
+impl ChangeDelivery {
+    pub fn new(batching: Option<PostgresTriggerBatching>, wait_for_success: bool) -> Self {
+        Self {
+            batching,
+            wait_for_success,
+            changes: vec![],
+            first_position: None,
+            last_position: None,
+            first_change_at: None,
+            in_transaction: false,
+            commit_lsn: 0,
+            next_index: 0,
+            replay: None,
+            pending_commit_lsn: None,
+            pending_jobs: vec![],
+            in_flight: VecDeque::new(),
+            confirmed_lsn: 0,
+        }
+    }
+
+    /// Changes before `bounds` were already pushed, and the changes within are pushed again as a
+    /// single batch, whatever the time they take to be received
+    pub fn replay_window(&mut self, bounds: BatchBounds) {
+        self.replay = Some(bounds);
+    }
+
+    pub fn on_begin(&mut self, commit_lsn: u64) {
+        self.in_transaction = true;
+        self.commit_lsn = commit_lsn;
+        self.next_index = 0;
+    }
+
+    /// Position of the next change of the transaction, to be called for every change received
+    /// even if it is filtered out so that positions do not depend on the filters
+    pub fn next_position(&mut self) -> ChangePosition {
+        let position = ChangePosition { commit_lsn: self.commit_lsn, index: self.next_index };
+        self.next_index += 1;
+        position
+    }
+
+    /// Returns the batch to push, if the change completes one
+    pub fn on_change(&mut self, position: ChangePosition, change: Change) -> Option<Batch> {
+        if let Some(replay) = self.replay {
+            if position < replay.first {
+                return None;
+            }
+            if position > replay.last {
+                self.replay = None;
+            }
+        }
+
+        match &self.batching {
+            None => {
+                return Some(Batch {
+                    payload: change.into_payload(),
+                    bounds: BatchBounds { first: position, last: position },
+                })
+            }
+            Some(batching) => {
+                if self.changes.is_empty() {
+                    self.first_position = Some(position);
+                    self.first_change_at = Some(Instant::now());
+                }
+                self.last_position = Some(position);
+                self.changes.push(change);
+
+                if let Some(replay) = self.replay {
+                    if position == replay.last {
+                        self.replay = None;
+                        return self.take();
+                    }
+                    return None;
+                }
+
+                match batching {
+                    PostgresTriggerBatching::Window { max_size, .. }
+                        if self.changes.len() >= *max_size as usize =>
+                    {
+                        self.take()
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Returns the batch to push, if the commit completes one
+    pub fn on_commit(&mut self, end_lsn: u64) -> Option<Batch> {
+        self.in_transaction = false;
+        self.pending_commit_lsn = Some(end_lsn);
+        let batch = match self.batching {
+            Some(PostgresTriggerBatching::Transaction) => self.take(),
+            _ => None,
+        };
+        if batch.is_none() {
+            self.ack_pending_commit();
+        }
+        batch
+    }
+
+    /// When the buffered changes must be pushed even if the batch is not full. A replayed window
+    /// is only pushed once all its changes are received.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.replay.is_some() {
+            return None;
+        }
+        match (&self.batching, self.first_change_at) {
+            (Some(PostgresTriggerBatching::Window { max_wait_ms, .. }), Some(first_change_at)) => {
+                Some(first_change_at + Duration::from_millis(*max_wait_ms))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn take(&mut self) -> Option<Batch> {
+        if self.changes.is_empty() {
+            return None;
+        }
+        let (Some(first), Some(last)) = (self.first_position, self.last_position) else {
+            return None;
+        };
+        self.first_change_at = None;
+        let changes = std::mem::take(&mut self.changes);
+        Some(Batch {
+            payload: HashMap::from([("changes".to_string(), to_raw_value(&changes))]),
+            bounds: BatchBounds { first, last },
+        })
+    }
+
+    /// To be called once the job of a batch has been pushed
+    pub fn delivered(&mut self, job_id: Option<Uuid>, batch: Batch) {
+        if let Some(job_id) = job_id.filter(|_| self.wait_for_success) {
+            self.pending_jobs.push(DeliveredJob {
+                job_id,
+                batch,
+                attempts: 1,
+                state: JobState::Running,
+            });
+        }
+        self.ack_pending_commit();
+    }
+
+    /// To be called once the batch of a failed job has been pushed again
+    pub fn redelivered(&mut self, failed_job: Uuid, job_id: Uuid) {
+        if let Some(job) = self.job_mut(failed_job) {
+            job.job_id = job_id;
+            job.attempts += 1;
+            job.state = JobState::Running;
+        }
+    }
+
+    fn job_mut(&mut self, job_id: Uuid) -> Option<&mut DeliveredJob> {
+        self.in_flight
+            .iter_mut()
+            .flat_map(|(_, jobs)| jobs.iter_mut())
+            .find(|job| job.job_id == job_id)
+    }
+
+    fn ack_pending_commit(&mut self) {
+        if !self.changes.is_empty() {
+            return;
+        }
+        let Some(lsn) = self.pending_commit_lsn.take() else {
+            return;
+        };
+        if self.wait_for_success && !self.pending_jobs.is_empty() {
+            self.in_flight
+                .push_back((lsn, std::mem::take(&mut self.pending_jobs)));
+        } else if self.in_flight.is_empty() {
+            self.confirmed_lsn = self.confirmed_lsn.max(lsn);
+        } else {
+            self.in_flight.push_back((lsn, vec![]));
+        }
+    }
+
+    /// Records the completion of a job, returns the dead letter to keep if it failed on its last
+    /// attempt
+    fn on_job_completed(&mut self, job_id: Uuid, success: bool) -> Option<Redelivery> {
+        let job = self.job_mut(job_id)?;
+        if !matches!(job.state, JobState::Running) {
+            return None;
+        }
+        if success {
+            job.state = JobState::Done;
+            None
+        } else if job.attempts >= MAX_DELIVERY_ATTEMPTS {
+            job.state = JobState::Done;
+            Some(Redelivery::DeadLetter(job_id))
+        } else {
+            job.state = JobState::RetryAt(Instant::now() + retry_delay(job.attempts));
+            None
+        }
+    }
+
+    /// Returns the failed batches whose backoff elapsed and acknowledges the commits whose jobs
+    /// are all done
+    fn poll(&mut self) -> Vec<Redelivery> {
+        let now = Instant::now();
+        let mut redeliveries = vec![];
+        for job in self
+            .in_flight
+            .iter_mut()
+            .flat_map(|(_, jobs)| jobs.iter_mut())
+        {
+            if matches!(job.state, JobState::RetryAt(retry_at) if retry_at <= now) {
+                job.state = JobState::Retrying;
+                redeliveries
+                    .push(Redelivery::Retry { failed_job: job.job_id, batch: job.batch.clone() });
+            }
+        }
+
+        while let Some((lsn, jobs)) = self.in_flight.front() {
+            if !jobs.iter().all(|job| matches!(job.state, JobState::Done)) {
+                break;
+            }
+            self.confirmed_lsn = self.confirmed_lsn.max(*lsn);
+            self.in_flight.pop_front();
+        }
+        redeliveries
+    }
+
+    /// Checks the jobs waiting for their success and returns the batches to push again or to
+    /// keep as dead letters
+    pub async fn refresh(&mut self, db: &DB) -> Result<Vec<Redelivery>> {
+        let job_ids = self
+            .in_flight
+            .iter()
+            .flat_map(|(_, jobs)| jobs.iter())
+            .filter(|job| matches!(job.state, JobState::Running))
+            .map(|job| job.job_id)
+            .collect::<Vec<_>>();
+
+        let mut redeliveries = vec![];
+        if !job_ids.is_empty() {
+            let jobs = sqlx::query!(
+                r#"SELECT j.id AS "id!", c.status IN ('success', 'skipped') AS "success?", q.id IS NOT NULL AS "queued!"
+                FROM unnest($1::uuid[]) AS j(id)
+                LEFT JOIN v2_job_completed c ON c.id = j.id
+                LEFT JOIN v2_job_queue q ON q.id = j.id"#,
+                &job_ids
+            )
+            .fetch_all(db)
+            .await?;
+
+            for job in jobs {
+                let success = match job.success {
+                    Some(success) => success,
+                    None if job.queued => continue,
+                    // a job that is neither queued nor completed was deleted and would hold back
+                    // the acknowledgement of the following commits forever, it is handled as failed
+                    None => {
+                        tracing::warn!(
+                            "Job {} of postgres trigger changes was deleted before completing, handling it as failed",
+                            job.id
+                        );
+                        false
+                    }
+                };
+                redeliveries.extend(self.on_job_completed(job.id, success));
+            }
+        }
+        redeliveries.extend(self.poll());
+        Ok(redeliveries)
+    }
+
+    /// The lsn to report as flushed. When there is nothing left to deliver, everything up to the
+    /// end of the wal received so far can be acknowledged.
+    pub fn flush_lsn(&mut self, wal_end: u64) -> u64 {
+        let idle = !self.in_transaction
+            && self.changes.is_empty()
+            && self.pending_commit_lsn.is_none()
+            && self.pending_jobs.is_empty()
+            && self.in_flight.is_empty();
+        if idle {
+            self.confirmed_lsn = self.confirmed_lsn.max(wal_end);
+        }
+        self.confirmed_lsn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(id: i64) -> Change {
+        let mut row = Map::new();
+        row.insert("id".to_string(), Value::from(id));
+        Change {
+            schema_name: "public".to_string(),
+            table_name: "t".to_string(),
+            transaction_type: "insert",
+            old_row: None,
+            row,
+        }
+    }
+
+    fn window(max_size: u32) -> Option<PostgresTriggerBatching> {
+        Some(PostgresTriggerBatching::Window { max_size, max_wait_ms: 1000 })
+    }
+
+    /// Sends a transaction with `n` changes, returns the batches to push
+    fn transaction(delivery: &mut ChangeDelivery, commit_lsn: u64, n: i64) -> Vec<Batch> {
+        let mut batches = vec![];
+        delivery.on_begin(commit_lsn);
+        for id in 0..n {
+            let position = delivery.next_position();
+            batches.extend(delivery.on_change(position, change(id)));
+        }
+        batches.extend(delivery.on_commit(commit_lsn + 8));
+        batches
+    }
+
+    #[test]
+    fn test_idempotency_key_only_depends_on_the_transactions() {
+        let keys = |batching| {
+            let mut delivery = ChangeDelivery::new(batching, false);
+            let mut batches = transaction(&mut delivery, 0x100, 2);
+            batches.extend(transaction(&mut delivery, 0x200, 1));
+            batches
+                .into_iter()
+                .map(|batch| batch.idempotency_key())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            keys(None),
+            vec!["100.0-100.0", "100.1-100.1", "200.0-200.0"]
+        );
+        assert_eq!(
+            keys(Some(PostgresTriggerBatching::Transaction)),
+            vec!["100.0-100.1", "200.0-200.0"]
+        );
+        assert_eq!(keys(window(2)), vec!["100.0-100.1"]);
+    }
+
+    #[test]
+    fn test_replayed_window_keeps_its_bounds() {
+        let mut delivery = ChangeDelivery::new(window(10), false);
+        let bounds = BatchBounds {
+            first: ChangePosition { commit_lsn: 0x200, index: 0 },
+            last: ChangePosition { commit_lsn: 0x300, index: 1 },
+        };
+        delivery.replay_window(bounds);
+
+        assert!(transaction(&mut delivery, 0x100, 2).is_empty());
+        assert!(delivery.take().is_none());
+        assert!(transaction(&mut delivery, 0x200, 1).is_empty());
+        assert!(delivery.deadline().is_none());
+
+        let batches = transaction(&mut delivery, 0x300, 3);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].bounds, bounds);
+        assert!(delivery.deadline().is_some());
+
+        let batch = delivery.take().unwrap();
+        assert_eq!(batch.idempotency_key(), "300.2-300.2");
+    }
+
+    #[test]
+    fn test_failed_jobs_are_retried_then_dead_lettered() {
+        let mut delivery = ChangeDelivery::new(Some(PostgresTriggerBatching::Transaction), true);
+        let batch = transaction(&mut delivery, 0x100, 1).pop().unwrap();
+        let mut job_id = Uuid::new_v4();
+        delivery.delivered(Some(job_id), batch);
+        assert_eq!(delivery.flush_lsn(0x200), 0);
+
+        for attempt in 1..MAX_DELIVERY_ATTEMPTS {
+            assert!(delivery.on_job_completed(job_id, false).is_none());
+            assert!(delivery.poll().is_empty());
+
+            delivery.job_mut(job_id).unwrap().state = JobState::RetryAt(Instant::now());
+            let redeliveries = delivery.poll();
+            let [Redelivery::Retry { failed_job, batch }] = redeliveries.as_slice() else {
+                panic!("attempt {attempt} should be retried");
+            };
+            assert_eq!(*failed_job, job_id);
+            assert_eq!(batch.idempotency_key(), "100.0-100.0");
+
+            job_id = Uuid::new_v4();
+            delivery.redelivered(*failed_job, job_id);
+            assert_eq!(delivery.flush_lsn(0x200), 0);
+        }
+
+        assert!(matches!(
+            delivery.on_job_completed(job_id, false),
+            Some(Redelivery::DeadLetter(id)) if id == job_id
+        ));
+        assert!(delivery.poll().is_empty());
+        assert_eq!(delivery.flush_lsn(0x200), 0x200);
+    }
+
+    #[test]
+    fn test_commits_are_acknowledged_in_order() {
+        let mut delivery = ChangeDelivery::new(Some(PostgresTriggerBatching::Transaction), true);
+        let mut job_ids = vec![];
+        for commit_lsn in [0x100, 0x200] {
+            delivery.on_begin(commit_lsn);
+            let position = delivery.next_position();
+            assert!(delivery.on_change(position, change(0)).is_none());
+            let batch = delivery.on_commit(commit_lsn + 8).unwrap();
+            let job_id = Uuid::new_v4();
+            delivery.delivered(Some(job_id), batch);
+            job_ids.push(job_id);
+        }
+
+        delivery.on_job_completed(job_ids[1], true);
+        delivery.poll();
+        assert_eq!(delivery.flush_lsn(0x300), 0);
+
+        delivery.on_job_completed(job_ids[0], true);
+        delivery.poll();
+        assert_eq!(delivery.flush_lsn(0x300), 0x300);
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1), RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(2), RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(4), RETRY_BASE_DELAY * 8);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use windmill_common::error::{Error, Result};
+
+use crate::trigger_helpers::is_json_superset;
+
+/// Only changes whose row has a value at `key` that is a superset of `value` are delivered.
+/// For deletes, the filter is evaluated on the deleted row.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RowFilter {
+    /// `schema.table` or `table` the filter applies to, all the tables if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    pub key: String,
+    pub value: Value,
+}
+
+fn applies_to(table_ref: &str, schema_name: &str, table_name: &str) -> bool {
+    match table_ref.split_once('.') {
+        Some((schema, table)) => schema == schema_name && table == table_name,
+        None => table_ref == table_name,
+    }
+}
+
+/// Columns to keep in the rows of each table, keyed by `schema.table` or `table`
+pub type ColumnProjection = HashMap<String, Vec<String>>;
+
+pub fn validate_row_selection(
+    row_filters: Option<&[RowFilter]>,
+    column_projection: Option<&ColumnProjection>,
+) -> Result<()> {
+    if let Some(filter) = row_filters
+        .unwrap_or_default()
+        .iter()
+        .find(|filter| filter.key.is_empty())
+    {
+        return Err(Error::BadRequest(format!(
+            "Row filter{} must have a column name as key",
+            filter
+                .table
+                .as_ref()
+                .map(|table| format!(" on {table}"))
+                .unwrap_or_default()
+        )));
+    }
+    if let Some((table, _)) = column_projection
+        .into_iter()
+        .flatten()
+        .find(|(_, columns)| columns.is_empty())
+    {
+        return Err(Error::BadRequest(format!(
+            "Column projection of {table} must keep at least one column"
+        )));
+    }
+    Ok(())
+}
+
+/// Selects the changes and the columns of their rows that are delivered to the runnable
+pub struct RowSelector {
+    row_filters: Vec<RowFilter>,
+    column_projection: ColumnProjection,
+    include_old_row: bool,
+}
+
+impl RowSelector {
+    pub fn new(
+        row_filters: Vec<RowFilter>,
+        column_projection: ColumnProjection,
+        include_old_row: bool,
+    ) -> Self {
+        Self { row_filters, column_projection, include_old_row }
+    }
+
+    fn projection(&self, schema_name: &str, table_name: &str) -> Option<&Vec<String>> {
+        self.column_projection
+            .get(&format!("{schema_name}.{table_name}"))
+            .or_else(|| self.column_projection.get(table_name))
+    }
+
+    fn filters<'a>(
+        &'a self,
+        schema_name: &'a str,
+        table_name: &'a str,
+    ) -> impl Iterator<Item = &'a RowFilter> {
+        self.row_filters.iter().filter(move |filter| {
+            filter
+                .table
+                .as_ref()
+                .map_or(true, |table| applies_to(table, schema_name, table_name))
+        })
+    }
+
+    /// The columns to decode: the projected ones and the ones the filters need, all if the
+    /// table has no projection
+    pub fn columns_to_decode(
+        &self,
+        schema_name: &str,
+        table_name: &str,
+    ) -> Option<HashSet<String>> {
+        let projection = self.projection(schema_name, table_name)?;
+        Some(
+            projection
+                .iter()
+                .cloned()
+                .chain(
+                    self.filters(schema_name, table_name)
+                        .map(|filter| filter.key.clone()),
+                )
+                .collect(),
+        )
+    }
+
+    /// The old row of an update, only delivered if requested
+    pub fn old_row<T>(&self, old_row: Option<T>) -> Option<T> {
+        old_row.filter(|_| self.include_old_row)
+    }
+
+    pub fn matches(&self, schema_name: &str, table_name: &str, row: &Map<String, Value>) -> bool {
+        self.filters(schema_name, table_name).all(|filter| {
+            row.get(&filter.key)
+                .map_or(false, |value| is_json_superset(value, &filter.value))
+        })
+    }
+
+    pub fn project(&self, schema_name: &str, table_name: &str, row: &mut Map<String, Value>) {
+        if let Some(projection) = self.projection(schema_name, table_name) {
+            row.retain(|column, _| projection.contains(column));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn row(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    fn filter(table: Option<&str>, key: &str, value: Value) -> RowFilter {
+        RowFilter { table: table.map(str::to_string), key: key.to_string(), value }
+    }
+
+    #[test]
+    fn test_filters_match_supersets() {
+        let selector = RowSelector::new(
+            vec![
+                filter(None, "status", json!("paid")),
+                filter(None, "data", json!({ "currency": "eur", "tags": ["vip"] })),
+            ],
+            HashMap::new(),
+            true,
+        );
+
+        let matching = row(json!({
+            "id": 1,
+            "status": "paid",
+            "data": { "currency": "eur", "amount": 10, "tags": ["new", "vip"] }
+        }));
+        assert!(selector.matches("public", "orders", &matching));
+
+        let other_status =
+            row(json!({ "status": "open", "data": { "currency": "eur", "tags": ["vip"] } }));
+        assert!(!selector.matches("public", "orders", &other_status));
+
+        let missing_tag =
+            row(json!({ "status": "paid", "data": { "currency": "eur", "tags": [] } }));
+        assert!(!selector.matches("public", "orders", &missing_tag));
+
+        let missing_column = row(json!({ "status": "paid" }));
+        assert!(!selector.matches("public", "orders", &missing_column));
+    }
+
+    #[test]
+    fn test_filters_only_apply_to_their_table() {
+        let selector = RowSelector::new(
+            vec![
+                filter(Some("public.orders"), "status", json!("paid")),
+                filter(Some("users"), "active", json!(true)),
+            ],
+            HashMap::new(),
+            true,
+        );
+
+        let open = row(json!({ "status": "open", "active": false }));
+        assert!(!selector.matches("public", "orders", &open));
+        assert!(selector.matches("sales", "orders", &open));
+        assert!(!selector.matches("sales", "users", &open));
+        assert!(selector.matches("public", "items", &open));
+    }
+
+    #[test]
+    fn test_projection_excludes_columns() {
+        let selector = RowSelector::new(
+            vec![filter(Some("orders"), "status", json!("paid"))],
+            HashMap::from([
+                ("orders".to_string(), vec!["id".to_string()]),
+                (
+                    "sales.orders".to_string(),
+                    vec!["id".to_string(), "amount".to_string()],
+                ),
+            ]),
+            true,
+        );
+
+        let mut public_row = row(json!({ "id": 1, "status": "paid", "amount": 10 }));
+        selector.project("public", "orders", &mut public_row);
+        assert_eq!(public_row, row(json!({ "id": 1 })));
+
+        let mut sales_row = row(json!({ "id": 1, "status": "paid", "amount": 10 }));
+        selector.project("sales", "orders", &mut sales_row);
+        assert_eq!(sales_row, row(json!({ "id": 1, "amount": 10 })));
+
+        let mut users_row = row(json!({ "id": 1, "name": "a" }));
+        selector.project("public", "users", &mut users_row);
+        assert_eq!(users_row, row(json!({ "id": 1, "name": "a" })));
+
+        // the columns the filters need are decoded even if they are excluded
+        assert_eq!(
+            selector.columns_to_decode("public", "orders"),
+            Some(HashSet::from(["id".to_string(), "status".to_string()]))
+        );
+        assert_eq!(selector.columns_to_decode("public", "users"), None);
+    }
+
+    #[test]
+    fn test_old_row_only_kept_when_included() {
+        let old_row = Some(row(json!({ "id": 1 })));
+
+        let selector = RowSelector::new(vec![], HashMap::new(), false);
+        assert_eq!(selector.old_row(old_row.clone()), None);
+
+        let selector = RowSelector::new(vec![], HashMap::new(), true);
+        assert_eq!(selector.old_row(old_row.clone()), old_row);
+    }
+
+    #[test]
+    fn test_validate_row_selection() {
+        assert!(validate_row_selection(None, None).is_ok());
+        assert!(validate_row_selection(
+            Some(&[filter(Some("orders"), "status", json!("paid"))]),
+            Some(&HashMap::from([(
+                "orders".to_string(),
+                vec!["id".to_string()]
+            )])),
+        )
+        .is_ok());
+
+        let err = validate_row_selection(Some(&[filter(Some("orders"), "", json!(1))]), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("Row filter on orders"));
+
+        let err =
+            validate_row_selection(None, Some(&HashMap::from([("orders".to_string(), vec![])])))
+                .unwrap_err();
+        assert!(err.to_string().contains("Column projection of orders"));
+    }
+}
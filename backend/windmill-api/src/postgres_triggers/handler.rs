@@ -6,32 +6,27 @@ use std::collections::{
 use crate::{
     db::{ApiAuthed, DB},
     postgres_triggers::mapper::{Mapper, MappingInfo},
-    utils::check_scopes,
+    triggers::handler::{PgQuery, Trigger, TriggerCrud},
 };
-use axum::{
-    extract::{Path, Query},
-    Extension, Json,
-};
-use http::StatusCode;
+use axum::{async_trait, extract::Path, Extension, Json};
 use itertools::Itertools;
 use pg_escape::{quote_identifier, quote_literal};
 use quick_cache::sync::Cache;
 use rust_postgres::{types::Type, Client};
 use serde::{Deserialize, Deserializer, Serialize};
-use serde_json::value::RawValue;
-use sql_builder::{bind::Bind, SqlBuilder};
 use sqlx::FromRow;
-use windmill_audit::{audit_oss::audit_log, ActionKind};
 use windmill_common::{
     db::UserDB,
-    error::{self, to_anyhow, Error, JsonResult, Result},
-    utils::{empty_as_none, not_found_if_none, paginate, Pagination, StripPath},
-    worker::CLOUD_HOSTED,
+    error::{self, to_anyhow, Error, Result},
+    triggers::TriggerKind,
+    utils::empty_as_none,
 };
-use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
+use windmill_git_sync::DeployedObject;
 
 use super::{
     check_if_valid_publication_for_postgres_version, create_logical_replication_slot,
+    delivery::PostgresTriggerBatching,
+    filter::{validate_row_selection, ColumnProjection, RowFilter},
     create_pg_publication, drop_publication, generate_random_string, get_default_pg_connection,
     ERROR_PUBLICATION_NAME_NOT_EXISTS,
 };
@@ -90,35 +85,42 @@ impl Relations {
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct EditPostgresTrigger {
-    replication_slot_name: String,
-    publication_name: String,
-    path: String,
-    script_path: String,
-    is_flow: bool,
-    postgres_resource_path: String,
-    publication: Option<PublicationData>,
-    error_handler_path: Option<String>,
-    error_handler_args: Option<sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
-    retry: Option<sqlx::types::Json<windmill_common::flows::Retry>>,
+fn default_include_old_row() -> bool {
+    true
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct NewPostgresTrigger {
-    path: String,
-    script_path: String,
-    is_flow: bool,
-    enabled: bool,
-    postgres_resource_path: String,
-    replication_slot_name: Option<String>,
-    publication_name: Option<String>,
-    publication: Option<PublicationData>,
-    error_handler_path: Option<String>,
-    error_handler_args: Option<sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
-    retry: Option<sqlx::types::Json<windmill_common::flows::Retry>>,
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PostgresConfig {
+    pub postgres_resource_path: String,
+    /// Created with the publication when neither is given on create
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub replication_slot_name: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub publication_name: Option<String>,
+    /// Tables and transactions the publication is created or altered with, not stored
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing)]
+    pub publication: Option<PublicationData>,
+    #[serde(default)]
+    pub initial_snapshot: bool,
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub snapshot_completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batching: Option<sqlx::types::Json<PostgresTriggerBatching>>,
+    #[serde(default)]
+    pub ack_after_success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row_filters: Option<sqlx::types::Json<Vec<RowFilter>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column_projection: Option<sqlx::types::Json<ColumnProjection>>,
+    #[serde(default = "default_include_old_row")]
+    pub include_old_row: bool,
 }
 
+pub type PostgresTrigger = Trigger<PostgresConfig>;
+
+pub struct PostgresTriggerHandler;
+
 #[derive(Serialize, Deserialize)]
 pub struct TestPostgres {
     pub postgres_resource_path: String,
@@ -223,49 +225,6 @@ where
     }
 }
 
-#[derive(FromRow, Deserialize, Serialize, Debug)]
-pub struct PostgresTrigger {
-    pub path: String,
-    pub script_path: String,
-    pub is_flow: bool,
-    pub workspace_id: String,
-    pub edited_by: String,
-    pub email: String,
-    pub edited_at: chrono::DateTime<chrono::Utc>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub extra_perms: Option<serde_json::Value>,
-    pub postgres_resource_path: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub server_id: Option<String>,
-    pub replication_slot_name: String,
-    pub publication_name: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_args: Option<sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub retry: Option<sqlx::types::Json<windmill_common::flows::Retry>>,
-}
-
-#[derive(Deserialize, Serialize)]
-pub struct ListPostgresTriggerQuery {
-    pub page: Option<usize>,
-    pub per_page: Option<usize>,
-    pub path: Option<String>,
-    pub is_flow: Option<bool>,
-    pub path_start: Option<String>,
-}
-
-#[derive(Deserialize)]
-pub struct SetEnabled {
-    pub enabled: bool,
-}
-
 #[derive(Serialize, Deserialize)]
 pub struct PostgresPublicationReplication {
     publication_name: String,
@@ -302,6 +261,7 @@ async fn create_custom_slot_and_publication_inner(
     postgres_resource_path: &str,
     w_id: &str,
     publication: &PublicationData,
+    create_slot: bool,
 ) -> Result<PostgresPublicationReplication> {
     let mut pg_connection = get_default_pg_connection(
         authed.clone(),
@@ -318,7 +278,9 @@ async fn create_custom_slot_and_publication_inner(
     let publication_name = format!("windmill_trigger_{}", generate_random_string());
     let replication_slot_name = publication_name.clone();
 
-    create_logical_replication_slot(tx.client(), &replication_slot_name).await?;
+    if create_slot {
+        create_logical_replication_slot(tx.client(), &replication_slot_name).await?;
+    }
     create_pg_publication(
         &tx.client(),
         &publication_name,
@@ -335,237 +297,252 @@ async fn create_custom_slot_and_publication_inner(
     ))
 }
 
-pub async fn get_postgres_version_internal(pg_connection: &Client) -> Result<String> {
-    let row = pg_connection
-        .query_one("SHOW server_version;", &[])
-        .await
-        .map_err(to_anyhow)?;
-
-    let postgres_version: String = row.get(0);
-
-    Ok(postgres_version)
-}
-
-pub async fn get_postgres_version(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, postgres_resource_path)): Path<(String, String)>,
-) -> Result<String> {
-    let pg_connection = get_default_pg_connection(
-        authed.clone(),
-        Some(user_db),
-        &db,
-        &postgres_resource_path,
-        &w_id,
-    )
-    .await
-    .map_err(to_anyhow)?;
+#[async_trait]
+impl TriggerCrud for PostgresTriggerHandler {
+    const TABLE_NAME: &'static str = "postgres_trigger";
+    const ROUTE_PREFIX: &'static str = "postgres_triggers";
+    const DISPLAY_NAME: &'static str = "Postgres";
+    const KIND: TriggerKind = TriggerKind::Postgres;
+    const CONFIG_COLUMNS: &'static [&'static str] = &[
+        "postgres_resource_path",
+        "replication_slot_name",
+        "publication_name",
+        "initial_snapshot",
+        "batching",
+        "ack_after_success",
+        "row_filters",
+        "column_projection",
+        "include_old_row",
+    ];
+    /// The last window is only replayed from the slot it was read from
+    const EXTRA_UPDATE_ASSIGNMENTS: &'static str = ", last_window_batch = CASE \
+         WHEN postgres_resource_path = $11::VARCHAR AND replication_slot_name = $12::VARCHAR \
+         THEN last_window_batch END";
+
+    type Config = PostgresConfig;
+
+    fn deployed_object(path: String) -> DeployedObject {
+        DeployedObject::PostgresTrigger { path }
+    }
 
-    let postgres_version = get_postgres_version_internal(&pg_connection).await?;
+    fn validate_config(config: &PostgresConfig) -> Result<()> {
+        if let Some(batching) = config.batching.as_ref() {
+            batching.validate()?;
+        }
 
-    Ok(postgres_version)
-}
+        validate_row_selection(
+            config
+                .row_filters
+                .as_ref()
+                .map(|row_filters| row_filters.0.as_slice()),
+            config
+                .column_projection
+                .as_ref()
+                .map(|column_projection| &column_projection.0),
+        )
+    }
 
-pub async fn create_postgres_trigger(
-    authed: ApiAuthed,
-    Extension(user_db): Extension<UserDB>,
-    Extension(db): Extension<DB>,
-    Path(w_id): Path<String>,
-    Json(new_postgres_trigger): Json<NewPostgresTrigger>,
-) -> Result<(StatusCode, String)> {
-    check_scopes(&authed, || format!("postgres_triggers:write:{}", new_postgres_trigger.path))?;
+    async fn prepare_config(
+        authed: &ApiAuthed,
+        user_db: &UserDB,
+        db: &DB,
+        w_id: &str,
+        path: Option<&str>,
+        config: &mut PostgresConfig,
+    ) -> Result<()> {
+        match path {
+            None => prepare_new_trigger(authed, user_db, db, w_id, config).await,
+            Some(path) => prepare_updated_trigger(authed, user_db, db, w_id, path, config).await,
+        }
+    }
 
-    if *CLOUD_HOSTED {
-        return Err(error::Error::BadRequest(
-            "Postgres triggers are not supported on multi-tenant cloud, use dedicated cloud or self-host".to_string(),
-        ));
+    fn bind_config<'q>(config: &'q PostgresConfig, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&config.postgres_resource_path)
+            .bind(&config.replication_slot_name)
+            .bind(&config.publication_name)
+            .bind(config.initial_snapshot)
+            .bind(&config.batching)
+            .bind(config.ack_after_success)
+            .bind(&config.row_filters)
+            .bind(&config.column_projection)
+            .bind(config.include_old_row)
     }
+}
 
-    let NewPostgresTrigger {
-        postgres_resource_path,
-        path,
-        script_path,
-        enabled,
-        is_flow,
-        publication_name,
-        replication_slot_name,
-        publication,
-        error_handler_path,
-        error_handler_args,
-        retry,
-    } = new_postgres_trigger;
-
-    if publication_name.is_none() && publication.is_none() {
-        return Err(error::Error::BadRequest(
-            "Publication data is missing".to_string(),
-        ));
+/// Creates the slot and the publication of the trigger unless both are given
+async fn prepare_new_trigger(
+    authed: &ApiAuthed,
+    user_db: &UserDB,
+    db: &DB,
+    w_id: &str,
+    config: &mut PostgresConfig,
+) -> Result<()> {
+    if config.publication_name.is_none() && config.publication.is_none() {
+        return Err(Error::BadRequest("Publication data is missing".to_string()));
     }
-    let (pub_name, slot_name) = if publication_name.is_none() && replication_slot_name.is_none() {
-        if publication.is_none() {
-            return Err(Error::BadRequest("publication must be set".to_string()));
-        }
 
+    if let (None, None, Some(publication)) = (
+        &config.publication_name,
+        &config.replication_slot_name,
+        &config.publication,
+    ) {
         let PostgresPublicationReplication { publication_name, replication_slot_name } =
             create_custom_slot_and_publication_inner(
                 authed.clone(),
                 user_db.clone(),
-                &db,
-                &postgres_resource_path,
-                &w_id,
-                &publication.unwrap(),
+                db,
+                &config.postgres_resource_path,
+                w_id,
+                publication,
+                // with an initial snapshot, the slot is created by the listener so that it can export its snapshot
+                !config.initial_snapshot,
             )
             .await?;
 
-        (publication_name, replication_slot_name)
-    } else {
-        if publication_name.is_none() {
-            return Err(Error::BadRequest("Missing publication name".to_string()));
-        } else if replication_slot_name.is_none() {
-            return Err(Error::BadRequest(
-                "Missing replication slot name".to_string(),
-            ));
-        }
-        (publication_name.unwrap(), replication_slot_name.unwrap())
+        config.publication_name = Some(publication_name);
+        config.replication_slot_name = Some(replication_slot_name);
+        return Ok(());
+    }
+
+    if config.publication_name.is_none() {
+        return Err(Error::BadRequest("Missing publication name".to_string()));
+    }
+    let Some(replication_slot_name) = config.replication_slot_name.as_ref() else {
+        return Err(Error::BadRequest(
+            "Missing replication slot name".to_string(),
+        ));
     };
 
-    let mut tx = user_db.begin(&authed).await?;
+    if config.initial_snapshot {
+        let mut pg_connection = get_default_pg_connection(
+            authed.clone(),
+            Some(user_db.clone()),
+            db,
+            &config.postgres_resource_path,
+            w_id,
+        )
+        .await?;
+
+        if check_if_logical_replication_slot_exist(&mut pg_connection, replication_slot_name)
+            .await?
+        {
+            return Err(Error::BadRequest(format!(
+                "Replication slot {} already exists, an initial snapshot can only be taken with a new replication slot",
+                replication_slot_name
+            )));
+        }
+    }
 
-    sqlx::query!(
-        r#"
-        INSERT INTO postgres_trigger (
-            publication_name,
-            replication_slot_name,
-            workspace_id, 
-            path, 
-            script_path, 
-            is_flow, 
-            email, 
-            enabled, 
-            postgres_resource_path, 
-            edited_by,
-            error_handler_path,
-            error_handler_args,
-            retry
-        ) 
-        VALUES (
-            $1, 
-            $2, 
-            $3, 
-            $4, 
-            $5, 
-            $6, 
-            $7, 
-            $8, 
-            $9, 
-            $10,
-            $11,
-            $12,
-            $13
-        )"#,
-        pub_name,
-        slot_name,
-        &w_id,
-        &path,
-        script_path,
-        is_flow,
-        &authed.email,
-        enabled,
-        postgres_resource_path,
-        &authed.username,
-        error_handler_path,
-        error_handler_args as _,
-        retry as _
-    )
-    .execute(&mut *tx)
-    .await?;
+    Ok(())
+}
 
-    audit_log(
-        &mut *tx,
-        &authed,
-        "postgres_triggers.create",
-        ActionKind::Create,
-        &w_id,
-        Some(path.as_str()),
-        None,
+/// Creates the slot if it no longer exists and alters the publication. The initial snapshot is
+/// only taken when the trigger is created, so it is kept as is.
+async fn prepare_updated_trigger(
+    authed: &ApiAuthed,
+    user_db: &UserDB,
+    db: &DB,
+    w_id: &str,
+    path: &str,
+    config: &mut PostgresConfig,
+) -> Result<()> {
+    let Some(publication_name) = config.publication_name.clone() else {
+        return Err(Error::BadRequest("Missing publication name".to_string()));
+    };
+    let Some(replication_slot_name) = config.replication_slot_name.clone() else {
+        return Err(Error::BadRequest(
+            "Missing replication slot name".to_string(),
+        ));
+    };
+
+    let mut pg_connection = get_default_pg_connection(
+        authed.clone(),
+        Some(user_db.clone()),
+        db,
+        &config.postgres_resource_path,
+        w_id,
     )
-    .await?;
+    .await
+    .map_err(to_anyhow)?;
 
-    tx.commit().await?;
+    let exists =
+        check_if_logical_replication_slot_exist(&mut pg_connection, &replication_slot_name).await?;
 
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::PostgresTrigger { path: path.to_string() },
-        Some(format!("Postgres trigger '{}' created", path)),
-        true,
+    let snapshot = sqlx::query!(
+        r#"SELECT initial_snapshot, initial_snapshot AND snapshot_completed_at IS NULL AS "snapshot_pending!" FROM postgres_trigger WHERE workspace_id = $1 AND path = $2"#,
+        w_id,
+        path
     )
+    .fetch_optional(db)
     .await?;
+    config.initial_snapshot = snapshot.as_ref().is_some_and(|s| s.initial_snapshot);
+    // the slot of a trigger still waiting for its initial snapshot is created by the listener
+    let snapshot_pending = snapshot.is_some_and(|s| s.snapshot_pending);
 
-    Ok((StatusCode::CREATED, path.to_string()))
-}
+    let tx = pg_connection.transaction().await.map_err(to_anyhow)?;
 
-pub async fn list_postgres_triggers(
-    authed: ApiAuthed,
-    Extension(user_db): Extension<UserDB>,
-    Path(w_id): Path<String>,
-    Query(lst): Query<ListPostgresTriggerQuery>,
-) -> error::JsonResult<Vec<PostgresTrigger>> {
-    let mut tx = user_db.begin(&authed).await?;
-    let (per_page, offset) = paginate(Pagination { per_page: lst.per_page, page: lst.page });
-    let mut sqlb = SqlBuilder::select_from("postgres_trigger")
-        .fields(&[
-            "workspace_id",
-            "path",
-            "script_path",
-            "is_flow",
-            "edited_by",
-            "email",
-            "edited_at",
-            "server_id",
-            "last_server_ping",
-            "extra_perms",
-            "error",
-            "enabled",
-            "postgres_resource_path",
-            "replication_slot_name",
-            "publication_name",
-            "error_handler_path",
-            "error_handler_args",
-            "retry",
-        ])
-        .order_by("edited_at", true)
-        .and_where("workspace_id = ?".bind(&w_id))
-        .offset(offset)
-        .limit(per_page)
-        .clone();
-    if let Some(path) = lst.path {
-        sqlb.and_where_eq("script_path", "?".bind(&path));
-    }
-    if let Some(is_flow) = lst.is_flow {
-        sqlb.and_where_eq("is_flow", "?".bind(&is_flow));
+    if !exists && !snapshot_pending {
+        tracing::debug!(
+            "Logical replication slot named: {} does not exists creating it...",
+            replication_slot_name
+        );
+        create_logical_replication_slot(tx.client(), &replication_slot_name)
+            .await
+            .map_err(to_anyhow)?;
     }
-    if let Some(path_start) = &lst.path_start {
-        sqlb.and_where_like_left("path", path_start);
+
+    if let Some(publication) = config.publication.take() {
+        let publication_data =
+            get_publication_scope_and_transaction(tx.client(), &publication_name)
+                .await
+                .map_err(to_anyhow)?;
+
+        update_pg_publication(
+            tx.client(),
+            &publication_name,
+            publication,
+            publication_data.map(|publication| publication.0),
+        )
+        .await
+        .map_err(to_anyhow)?;
     }
-    let sql = sqlb
-        .sql()
-        .map_err(|e| error::Error::InternalErr(e.to_string()))?;
-    let rows = sqlx::query_as::<_, PostgresTrigger>(&sql)
-        .fetch_all(&mut *tx)
+
+    tx.commit().await.map_err(to_anyhow)?;
+
+    Ok(())
+}
+
+pub async fn get_postgres_version_internal(pg_connection: &Client) -> Result<String> {
+    let row = pg_connection
+        .query_one("SHOW server_version;", &[])
         .await
-        .map_err(|e| {
-            tracing::debug!("Error fetching postgres_trigger: {:#?}", e);
-            windmill_common::error::Error::InternalErr("server error".to_string())
-        })?;
-    tx.commit().await.map_err(|e| {
-        tracing::debug!("Error commiting postgres_trigger: {:#?}", e);
-        windmill_common::error::Error::InternalErr("server error".to_string())
-    })?;
+        .map_err(to_anyhow)?;
+
+    let postgres_version: String = row.get(0);
+
+    Ok(postgres_version)
+}
+
+pub async fn get_postgres_version(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, postgres_resource_path)): Path<(String, String)>,
+) -> Result<String> {
+    let pg_connection = get_default_pg_connection(
+        authed.clone(),
+        Some(user_db),
+        &db,
+        &postgres_resource_path,
+        &w_id,
+    )
+    .await
+    .map_err(to_anyhow)?;
 
-    Ok(Json(rows))
+    let postgres_version = get_postgres_version_internal(&pg_connection).await?;
+
+    Ok(postgres_version)
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -1148,329 +1125,6 @@ pub async fn get_tracked_relations(
     Ok(table_to_track.into_values().collect_vec())
 }
 
-pub async fn get_postgres_trigger(
-    authed: ApiAuthed,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-) -> JsonResult<PostgresTrigger> {
-    let path = path.to_path();
-    check_scopes(&authed, || format!("postgres_triggers:read:{}", path))?;
-    let mut tx = user_db.begin(&authed).await?;
-    let trigger = sqlx::query_as!(
-        PostgresTrigger,
-        r#"
-        SELECT
-            workspace_id,
-            path,
-            script_path,
-            is_flow,
-            edited_by,
-            email,
-            edited_at,
-            server_id,
-            last_server_ping,
-            extra_perms,
-            error,
-            enabled,
-            replication_slot_name,
-            publication_name,
-            postgres_resource_path,
-            error_handler_path,
-            error_handler_args as "error_handler_args: _",
-            retry as "retry: _"
-        FROM 
-            postgres_trigger
-        WHERE 
-            workspace_id = $1 AND 
-            path = $2
-        "#,
-        &w_id,
-        &path
-    )
-    .fetch_optional(&mut *tx)
-    .await?;
-    tx.commit().await?;
-
-    let trigger = not_found_if_none(trigger, "Trigger", path)?;
-
-    Ok(Json(trigger))
-}
-
-pub async fn update_postgres_trigger(
-    authed: ApiAuthed,
-    Extension(user_db): Extension<UserDB>,
-    Extension(db): Extension<DB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-    Json(postgres_trigger): Json<EditPostgresTrigger>,
-) -> Result<String> {
-    let workspace_path = path.to_path();
-    check_scopes(&authed, || format!("postgres_triggers:write:{}", workspace_path))?;
-
-    let EditPostgresTrigger {
-        replication_slot_name,
-        publication_name,
-        script_path,
-        path,
-        is_flow,
-        postgres_resource_path,
-        publication,
-        error_handler_path,
-        error_handler_args,
-        retry,
-    } = postgres_trigger;
-
-    let mut pg_connection = get_default_pg_connection(
-        authed.clone(),
-        Some(user_db.clone()),
-        &db,
-        &postgres_resource_path,
-        &w_id,
-    )
-    .await
-    .map_err(to_anyhow)?;
-
-    let exists =
-        check_if_logical_replication_slot_exist(&mut pg_connection, &replication_slot_name).await?;
-
-    let tx = pg_connection.transaction().await.map_err(to_anyhow)?;
-
-    if !exists {
-        tracing::debug!(
-            "Logical replication slot named: {} does not exists creating it...",
-            &replication_slot_name
-        );
-        create_logical_replication_slot(tx.client(), &replication_slot_name)
-            .await
-            .map_err(to_anyhow)?;
-    }
-
-    if let Some(publication) = publication {
-        let publication_data =
-            get_publication_scope_and_transaction(tx.client(), &publication_name)
-                .await
-                .map_err(to_anyhow)?;
-
-        update_pg_publication(
-            tx.client(),
-            &publication_name,
-            publication,
-            publication_data.map(|publication| publication.0),
-        )
-        .await
-        .map_err(to_anyhow)?;
-    }
-
-    tx.commit().await.map_err(to_anyhow)?;
-
-    let mut tx = user_db.begin(&authed).await?;
-
-    sqlx::query!(
-        r#"
-            UPDATE postgres_trigger 
-            SET 
-                script_path = $1, 
-                path = $2, 
-                is_flow = $3, 
-                edited_by = $4, 
-                email = $5, 
-                postgres_resource_path = $6, 
-                replication_slot_name = $7,
-                publication_name = $8,
-                edited_at = now(), 
-                error = NULL,
-                server_id = NULL,
-                error_handler_path = $11,
-                error_handler_args = $12,
-                retry = $13
-            WHERE 
-                workspace_id = $9 AND 
-                path = $10
-            "#,
-        script_path,
-        path,
-        is_flow,
-        &authed.username,
-        &authed.email,
-        postgres_resource_path,
-        replication_slot_name,
-        publication_name,
-        w_id,
-        workspace_path,
-        error_handler_path,
-        error_handler_args as _,
-        retry as _,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "postgres_triggers.update",
-        ActionKind::Update,
-        &w_id,
-        Some(&path),
-        None,
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::PostgresTrigger { path: path.to_string() },
-        Some(format!("Postgres trigger '{}' updated", path)),
-        true,
-    )
-    .await?;
-
-    Ok(workspace_path.to_string())
-}
-
-pub async fn delete_postgres_trigger(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-) -> Result<String> {
-    let path = path.to_path();
-    check_scopes(&authed, || format!("postgres_triggers:write:{}", path))?;
-    let mut tx = user_db.begin(&authed).await?;
-    sqlx::query!(
-        r#"
-        DELETE FROM postgres_trigger 
-        WHERE 
-            workspace_id = $1 AND 
-            path = $2
-        "#,
-        w_id,
-        path,
-    )
-    .execute(&mut *tx)
-    .await?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "postgres_triggers.delete",
-        ActionKind::Delete,
-        &w_id,
-        Some(path),
-        None,
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::PostgresTrigger { path: path.to_string() },
-        Some(format!("Postgres trigger '{}' deleted", path)),
-        true,
-    )
-    .await?;
-
-    Ok(format!("Postgres trigger {path} deleted"))
-}
-
-pub async fn exists_postgres_trigger(
-    Extension(db): Extension<DB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-) -> JsonResult<bool> {
-    let path = path.to_path();
-    let exists = sqlx::query_scalar!(
-        r#"
-        SELECT EXISTS(
-            SELECT 1 
-            FROM postgres_trigger 
-            WHERE 
-                path = $1 AND 
-                workspace_id = $2
-        )"#,
-        path,
-        w_id,
-    )
-    .fetch_one(&db)
-    .await?
-    .unwrap_or(false);
-    Ok(Json(exists))
-}
-
-pub async fn set_enabled(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Extension(user_db): Extension<UserDB>,
-    Path((w_id, path)): Path<(String, StripPath)>,
-    Json(payload): Json<SetEnabled>,
-) -> Result<String> {
-    let path = path.to_path();
-    check_scopes(&authed, || format!("postgres_triggers:write:{}", path))?;
-    let mut tx = user_db.begin(&authed).await?;
-
-    // important to set server_id, last_server_ping and error to NULL to stop current postgres listener
-    let one_o = sqlx::query_scalar!(
-        r#"
-        UPDATE postgres_trigger 
-        SET 
-            enabled = $1, 
-            email = $2, 
-            edited_by = $3, 
-            edited_at = now(), 
-            server_id = NULL, 
-            error = NULL
-        WHERE 
-            path = $4 AND 
-            workspace_id = $5 
-        RETURNING 1
-        "#,
-        payload.enabled,
-        &authed.email,
-        &authed.username,
-        path,
-        w_id,
-    )
-    .fetch_optional(&mut *tx)
-    .await?
-    .flatten();
-
-    not_found_if_none(one_o, "Postgres trigger", path)?;
-
-    audit_log(
-        &mut *tx,
-        &authed,
-        "postgres_triggers.setenabled",
-        ActionKind::Update,
-        &w_id,
-        Some(path),
-        Some([("enabled", payload.enabled.to_string().as_ref())].into()),
-    )
-    .await?;
-
-    tx.commit().await?;
-
-    handle_deployment_metadata(
-        &authed.email,
-        &authed.username,
-        &db,
-        &w_id,
-        DeployedObject::PostgresTrigger { path: path.to_string() },
-        Some(format!("Postgres trigger '{}' updated", path)),
-        true,
-    )
-    .await?;
-
-    Ok(format!(
-        "succesfully updated postgres trigger at path {} to status {}",
-        path, payload.enabled
-    ))
-}
-
 pub async fn get_template_script(Path((_, id)): Path<(String, String)>) -> Result<String> {
     let template = if let Some((_, template)) = TEMPLATE.remove(&id) {
         template
@@ -1,8 +1,8 @@
 use crate::{
     db::{ApiAuthed, DB},
     resources::try_get_resource_from_db_as,
-    trigger_helpers::{trigger_runnable, TriggerJobArgs},
-    users::fetch_api_authed,
+    trigger_helpers::TriggerJobArgs,
+    triggers::{handler::trigger_routes, listener::trigger_job},
 };
 use chrono::Utc;
 use itertools::Itertools;
@@ -18,31 +18,31 @@ use axum::{
     routing::{delete, get, post},
     Router,
 };
-pub use handler::PostgresTrigger;
 use handler::{
-    alter_publication, create_postgres_trigger, create_publication, create_slot,
-    create_template_script, delete_postgres_trigger, delete_publication, drop_slot_name,
-    exists_postgres_trigger, get_postgres_trigger, get_postgres_version,
-    get_postgres_version_internal, get_publication_info, get_template_script,
-    is_database_in_logical_level, list_database_publication, list_postgres_triggers,
-    list_slot_name, set_enabled, test_postgres_connection, update_postgres_trigger, Postgres,
-    Relations,
+    alter_publication, create_publication, create_slot, create_template_script, delete_publication,
+    drop_slot_name, get_postgres_version, get_postgres_version_internal, get_publication_info,
+    get_template_script, is_database_in_logical_level, list_database_publication, list_slot_name,
+    test_postgres_connection, Postgres, Relations,
 };
+pub use handler::{PostgresTrigger, PostgresTriggerHandler};
 use windmill_common::{
     db::UserDB,
     error::{to_anyhow, Error, Result},
 };
+use windmill_queue::idempotency::IdempotencyKey;
 mod bool;
 mod converter;
+mod delivery;
+mod filter;
 mod handler;
 mod hex;
 mod mapper;
 mod relation;
 mod replication_message;
+mod snapshot;
 mod trigger;
 
 pub use handler::PublicationData;
-pub use trigger::start_database;
 
 const ERROR_REPLICATION_SLOT_NOT_EXISTS: &str = r#"The replication slot associated with this trigger no longer exists. Recreate a new replication slot or select an existing one in the advanced tab, or delete and recreate a new trigger"#;
 
@@ -349,15 +349,8 @@ fn postgres_service() -> Router {
 }
 
 pub fn workspaced_service() -> Router {
-    Router::new()
+    trigger_routes::<PostgresTriggerHandler>()
         .route("/test", post(test_postgres_connection))
-        .route("/create", post(create_postgres_trigger))
-        .route("/list", get(list_postgres_triggers))
-        .route("/get/*path", get(get_postgres_trigger))
-        .route("/update/*path", post(update_postgres_trigger))
-        .route("/delete/*path", delete(delete_postgres_trigger))
-        .route("/exists/*path", get(exists_postgres_trigger))
-        .route("/setenabled/*path", post(set_enabled))
         .route("/get_template_script/:id", get(get_template_script))
         .route("/create_template_script", post(create_template_script))
         .route(
@@ -373,40 +366,17 @@ async fn run_job(
     payload: HashMap<String, Box<RawValue>>,
     db: &DB,
     trigger: &PostgresTrigger,
-) -> anyhow::Result<()> {
-    let args = PostgresTrigger::build_job_args(
-        &trigger.script_path,
-        trigger.is_flow,
-        &trigger.workspace_id,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> Result<uuid::Uuid> {
+    let args = PostgresTriggerHandler::build_job_args(
+        &trigger.base.script_path,
+        trigger.base.is_flow,
+        &trigger.base.workspace_id,
         db,
         payload,
         HashMap::new(),
     )
     .await?;
 
-    let authed = fetch_api_authed(
-        trigger.edited_by.clone(),
-        trigger.email.clone(),
-        &trigger.workspace_id,
-        db,
-        Some(format!("postgres-{}", trigger.path)),
-    )
-    .await?;
-
-    trigger_runnable(
-        db,
-        None,
-        authed,
-        &trigger.workspace_id,
-        &trigger.script_path,
-        trigger.is_flow,
-        args,
-        trigger.retry.as_ref(),
-        trigger.error_handler_path.as_deref(),
-        trigger.error_handler_args.as_ref(),
-        format!("postgres_trigger/{}", trigger.path),
-    )
-    .await?;
-
-    Ok(())
+    trigger_job::<PostgresTriggerHandler>(db, &trigger.base, args, idempotency_key).await
 }
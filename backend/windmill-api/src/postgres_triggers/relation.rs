@@ -1,7 +1,10 @@
 use core::str;
 
 use serde_json::{Map, Value};
-use std::{collections::HashMap, str::Utf8Error};
+use std::{
+    collections::{HashMap, HashSet},
+    str::Utf8Error,
+};
 
 use super::{
     converter::{Converter, ConverterError},
@@ -47,15 +50,21 @@ impl RelationConverter {
             .ok_or(RelationConversionError::FailToFindMatchingTable)
     }
 
+    /// Decodes the row, keeping only the given columns if any
     pub fn row_to_json(
         &self,
         to_decode: (Oid, Vec<TupleData>),
+        columns_to_keep: Option<&HashSet<String>>,
     ) -> Result<Map<String, Value>, RelationConversionError> {
         let (o_id, tuple_data) = to_decode;
         let mut object: Map<String, Value> = Map::new();
         let columns = self.get_columns(o_id)?;
 
         for (i, column) in columns.iter().enumerate() {
+            if columns_to_keep.is_some_and(|columns_to_keep| !columns_to_keep.contains(&column.name)) {
+                continue;
+            }
+
             let value = match &tuple_data[i] {
                 TupleData::Null | TupleData::UnchangedToast => Value::Null,
                 TupleData::Binary(_) => {
@@ -159,6 +159,30 @@ impl DeleteBody {
     }
 }
 
+#[derive(Debug)]
+pub struct BeginBody {
+    /// lsn of the commit record of the transaction, identifies it across redeliveries
+    pub final_lsn: u64,
+}
+
+impl BeginBody {
+    pub fn new(final_lsn: u64) -> Self {
+        Self { final_lsn }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommitBody {
+    pub commit_lsn: u64,
+    pub end_lsn: u64,
+}
+
+impl CommitBody {
+    pub fn new(commit_lsn: u64, end_lsn: u64) -> Self {
+        Self { commit_lsn, end_lsn }
+    }
+}
+
 #[derive(Debug)]
 pub enum TupleData {
     Null,
@@ -213,8 +237,8 @@ pub enum TransactionBody {
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum LogicalReplicationMessage {
-    Begin,
-    Commit,
+    Begin(BeginBody),
+    Commit(CommitBody),
     Relation(RelationBody),
     Type,
     Insert(InsertBody),
@@ -296,18 +320,18 @@ impl XLogDataBody {
 
         let logical_replication_message = match byte {
             BEGIN_BYTE => {
-                buf.read_i64::<BigEndian>()?;
+                let final_lsn = buf.read_u64::<BigEndian>()?;
                 buf.read_i64::<BigEndian>()?;
                 buf.read_i32::<BigEndian>()?;
 
-                LogicalReplicationMessage::Begin
+                LogicalReplicationMessage::Begin(BeginBody::new(final_lsn))
             }
             COMMIT_BYTE => {
                 buf.read_i8()?;
-                buf.read_u64::<BigEndian>()?;
-                buf.read_u64::<BigEndian>()?;
+                let commit_lsn = buf.read_u64::<BigEndian>()?;
+                let end_lsn = buf.read_u64::<BigEndian>()?;
                 buf.read_i64::<BigEndian>()?;
-                LogicalReplicationMessage::Commit
+                LogicalReplicationMessage::Commit(CommitBody::new(commit_lsn, end_lsn))
             }
             RELATION_BYTE => {
                 let transaction_id = match logical_replication_settings.streaming {
@@ -0,0 +1,174 @@
+use pg_escape::{quote_identifier, quote_literal};
+use rust_postgres::Client;
+use serde_json::{Map, Value};
+use windmill_common::error::{to_anyhow, Error, Result};
+
+use super::{get_raw_postgres_connection, handler::Postgres};
+
+const SNAPSHOT_CURSOR_NAME: &str = "windmill_snapshot";
+pub const SNAPSHOT_BATCH_SIZE: i64 = 1000;
+
+pub struct PublishedTable {
+    pub schema_name: String,
+    pub table_name: String,
+    columns: Option<Vec<String>>,
+    row_filter: Option<String>,
+}
+
+impl PublishedTable {
+    fn select_query(&self) -> String {
+        let columns = self
+            .columns
+            .as_ref()
+            .map(|columns| {
+                columns
+                    .iter()
+                    .map(|column| quote_identifier(column))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+            .unwrap_or_else(|| "*".to_string());
+
+        let mut query = format!(
+            "SELECT to_jsonb(t)::text FROM (SELECT {} FROM ONLY {}.{}",
+            columns,
+            quote_identifier(&self.schema_name),
+            quote_identifier(&self.table_name)
+        );
+        if let Some(row_filter) = &self.row_filter {
+            query.push_str(" WHERE (");
+            query.push_str(row_filter);
+            query.push(')');
+        }
+        query.push_str(") t");
+        query
+    }
+}
+
+/// Reads every row of the tables of a publication as of a snapshot exported by
+/// `CREATE_REPLICATION_SLOT`, so that the rows that existed before the slot are seen exactly
+/// once before streaming the changes from the slot. Without an exported snapshot, the rows are
+/// read as of the start of the reader.
+///
+/// The snapshot is only valid as long as the replication connection that exported it does not
+/// run any other command, the reader must be finished before starting the replication.
+pub struct SnapshotReader {
+    client: Client,
+    tables: Vec<PublishedTable>,
+    current_table: usize,
+    cursor_open: bool,
+}
+
+impl SnapshotReader {
+    pub async fn new(
+        database: &Postgres,
+        snapshot_name: Option<&str>,
+        publication_name: &str,
+    ) -> Result<Self> {
+        let client = get_raw_postgres_connection(database, false).await?;
+
+        let mut begin = "BEGIN ISOLATION LEVEL REPEATABLE READ READ ONLY;".to_string();
+        if let Some(snapshot_name) = snapshot_name {
+            begin.push_str(&format!(
+                " SET TRANSACTION SNAPSHOT {};",
+                quote_literal(snapshot_name)
+            ));
+        }
+        client.batch_execute(&begin).await.map_err(to_anyhow)?;
+
+        let server_version_num: i32 = client
+            .query_one("SELECT current_setting('server_version_num')::int", &[])
+            .await
+            .map_err(to_anyhow)?
+            .get(0);
+
+        // column lists and row filters of publications are only available from postgres 15
+        let query = if server_version_num >= 150000 {
+            "SELECT schemaname::text, tablename::text, attnames::text[], rowfilter FROM pg_publication_tables WHERE pubname = $1"
+        } else {
+            "SELECT schemaname::text, tablename::text, NULL::text[], NULL::text FROM pg_publication_tables WHERE pubname = $1"
+        };
+
+        let tables = client
+            .query(query, &[&publication_name])
+            .await
+            .map_err(to_anyhow)?
+            .into_iter()
+            .map(|row| PublishedTable {
+                schema_name: row.get(0),
+                table_name: row.get(1),
+                columns: row.get(2),
+                row_filter: row.get(3),
+            })
+            .collect();
+
+        Ok(Self { client, tables, current_table: 0, cursor_open: false })
+    }
+
+    /// Returns the next batch of rows of the snapshot along with the table they belong to,
+    /// or `None` once all the tables have been read.
+    pub async fn next_batch(
+        &mut self,
+    ) -> Result<Option<(&PublishedTable, Vec<Map<String, Value>>)>> {
+        while self.current_table < self.tables.len() {
+            if !self.cursor_open {
+                let query = self.tables[self.current_table].select_query();
+                self.client
+                    .batch_execute(&format!(
+                        "DECLARE {} NO SCROLL CURSOR FOR {}",
+                        SNAPSHOT_CURSOR_NAME, query
+                    ))
+                    .await
+                    .map_err(to_anyhow)?;
+                self.cursor_open = true;
+            }
+
+            let rows = self
+                .client
+                .query(
+                    &format!(
+                        "FETCH FORWARD {} FROM {}",
+                        SNAPSHOT_BATCH_SIZE, SNAPSHOT_CURSOR_NAME
+                    ),
+                    &[],
+                )
+                .await
+                .map_err(to_anyhow)?;
+
+            if rows.is_empty() {
+                self.client
+                    .batch_execute(&format!("CLOSE {}", SNAPSHOT_CURSOR_NAME))
+                    .await
+                    .map_err(to_anyhow)?;
+                self.cursor_open = false;
+                self.current_table += 1;
+                continue;
+            }
+
+            let rows = rows
+                .into_iter()
+                .map(|row| {
+                    let row: String = row.get(0);
+                    match serde_json::from_str::<Value>(&row)? {
+                        Value::Object(row) => Ok(row),
+                        _ => Err(Error::InternalErr(
+                            "snapshot row is not a json object".to_string(),
+                        )),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            return Ok(Some((&self.tables[self.current_table], rows)));
+        }
+
+        Ok(None)
+    }
+
+    pub async fn finish(self) -> Result<()> {
+        self.client
+            .batch_execute("COMMIT")
+            .await
+            .map_err(to_anyhow)?;
+        Ok(())
+    }
+}
@@ -1,47 +1,52 @@
 use std::{collections::HashMap, pin::Pin};
 
 use crate::{
-    capture::{insert_capture_payload, PostgresTriggerConfig},
-    db::{ApiAuthed, DB},
+    capture::PostgresTriggerConfig,
+    db::DB,
     postgres_triggers::{
+        delivery::{
+            Batch, BatchBounds, Change, ChangeDelivery, PostgresTriggerBatching, Redelivery,
+        },
+        filter::RowSelector,
         relation::RelationConverter,
         replication_message::{
             LogicalReplicationMessage::{Begin, Commit, Delete, Insert, Relation, Type, Update},
             ReplicationMessage,
         },
         run_job,
+        snapshot::SnapshotReader,
     },
     resources::try_get_resource_from_db_as,
+    trigger_dead_letters::insert_job_failure,
     trigger_helpers::TriggerJobArgs,
-    users::fetch_api_authed,
+    triggers::{
+        handler::TriggerCrud,
+        listener::{Listener, ListeningConfig},
+    },
 };
 
+use axum::async_trait;
 use bytes::{BufMut, Bytes, BytesMut};
 use chrono::TimeZone;
 use futures::{pin_mut, SinkExt, StreamExt};
 use pg_escape::{quote_identifier, quote_literal};
-use rand::seq::SliceRandom;
 use rust_postgres::{Client, CopyBothDuplex, SimpleQueryMessage};
-use serde::Deserialize;
 use serde_json::value::RawValue;
 use sqlx::types::Json as SqlxJson;
+use tokio::time::Instant;
 
-use windmill_common::{
-    db::UserDB,
-    error::{self, to_anyhow},
-    triggers::TriggerKind,
-    utils::report_critical_error,
-    worker::to_raw_value,
-    INSTANCE_NAME,
-};
+use windmill_common::{db::UserDB, error::to_anyhow, triggers::TriggerKind};
+use windmill_queue::idempotency::IdempotencyKey;
 
 use super::{
     drop_publication, get_default_pg_connection, get_raw_postgres_connection,
-    handler::{drop_logical_replication_slot, Postgres, PostgresTrigger},
-    replication_message::PrimaryKeepAliveBody,
+    handler::{drop_logical_replication_slot, Postgres, PostgresTrigger, PostgresTriggerHandler},
     Error, ERROR_PUBLICATION_NAME_NOT_EXISTS, ERROR_REPLICATION_SLOT_NOT_EXISTS,
 };
 
+/// How often the flushed lsn is reported to postgres, outside of the replies to its keepalives
+const STATUS_UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
 pub struct LogicalReplicationSettings {
     pub streaming: bool,
 }
@@ -112,10 +117,34 @@ impl PostgresSimpleClient {
         ))
     }
 
-    async fn send_status_update(
-        primary_keep_alive: PrimaryKeepAliveBody,
-        copy_both_stream: &mut Pin<&mut CopyBothDuplex<Bytes>>,
-    ) {
+    /// Creates the slot from the replication connection and exports a snapshot of the database
+    /// consistent with the point from which the slot streams changes.
+    async fn create_logical_replication_slot_with_snapshot(
+        &self,
+        logical_replication_slot_name: &str,
+    ) -> Result<String, Error> {
+        let query = format!(
+            r#"CREATE_REPLICATION_SLOT {} LOGICAL pgoutput EXPORT_SNAPSHOT"#,
+            quote_identifier(logical_replication_slot_name)
+        );
+
+        self.execute_query(&query)
+            .await
+            .map_err(to_anyhow)?
+            .iter()
+            .find_map(|message| match message {
+                SimpleQueryMessage::Row(row) => row.get("snapshot_name").map(|s| s.to_string()),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                Error::InternalErr(format!(
+                    "No snapshot exported when creating replication slot {}",
+                    logical_replication_slot_name
+                ))
+            })
+    }
+
+    async fn send_status_update(lsn: u64, copy_both_stream: &mut Pin<&mut CopyBothDuplex<Bytes>>) {
         let mut buf = BytesMut::new();
         let ts = chrono::Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
         let ts = chrono::Utc::now()
@@ -124,9 +153,9 @@ impl PostgresSimpleClient {
             .unwrap_or(0);
 
         buf.put_u8(b'r');
-        buf.put_u64(primary_keep_alive.wal_end);
-        buf.put_u64(primary_keep_alive.wal_end);
-        buf.put_u64(primary_keep_alive.wal_end);
+        buf.put_u64(lsn);
+        buf.put_u64(lsn);
+        buf.put_u64(lsn);
         buf.put_i64(ts);
         buf.put_u8(0);
         copy_both_stream.send(buf.freeze()).await.unwrap();
@@ -134,209 +163,7 @@ impl PostgresSimpleClient {
     }
 }
 
-async fn loop_ping(db: &DB, pg: &PostgresConfig, error: Option<&str>) {
-    loop {
-        if pg.update_ping(db, error).await.is_none() {
-            return;
-        }
-
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-    }
-}
-
-enum PostgresConfig {
-    Trigger(PostgresTrigger),
-    Capture(CaptureConfigForPostgresTrigger),
-}
-
-impl PostgresTrigger {
-    async fn try_to_listen_to_database_transactions(
-        self,
-        db: DB,
-        killpill_rx: tokio::sync::broadcast::Receiver<()>,
-    ) -> () {
-        let postgres_trigger = sqlx::query_scalar!(
-            r#"
-            UPDATE postgres_trigger 
-            SET 
-                server_id = $1, 
-                last_server_ping = now(),
-                error = 'Connecting...'
-            WHERE 
-                enabled IS TRUE 
-                AND workspace_id = $2 
-                AND path = $3 
-                AND (last_server_ping IS NULL 
-                    OR last_server_ping < now() - INTERVAL '15 seconds'
-                ) 
-            RETURNING true
-            "#,
-            *INSTANCE_NAME,
-            self.workspace_id,
-            self.path,
-        )
-        .fetch_optional(&db)
-        .await;
-        match postgres_trigger {
-            Ok(has_lock) => {
-                if has_lock.flatten().unwrap_or(false) {
-                    tracing::info!("Spawning new task to listen_to_database_transaction");
-                    tokio::spawn(async move {
-                        listen_to_transactions(
-                            PostgresConfig::Trigger(self),
-                            db.clone(),
-                            killpill_rx,
-                        )
-                        .await;
-                    });
-                } else {
-                    tracing::info!("Postgres trigger {} already being listened to", self.path);
-                }
-            }
-            Err(err) => {
-                tracing::error!(
-                    "Error acquiring lock for postgres trigger {}: {:?}",
-                    self.path,
-                    err
-                );
-            }
-        };
-    }
-
-    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
-        let updated = sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                postgres_trigger
-            SET 
-                last_server_ping = now(),
-                error = $1
-            WHERE
-                workspace_id = $2
-                AND path = $3
-                AND server_id = $4 
-                AND enabled IS TRUE
-            RETURNING 1
-            "#,
-            error,
-            &self.workspace_id,
-            &self.path,
-            *INSTANCE_NAME
-        )
-        .fetch_optional(db)
-        .await;
-
-        match updated {
-            Ok(updated) => {
-                if updated.flatten().is_none() {
-                    // allow faster restart of database trigger
-                    sqlx::query!(
-                        r#"
-                    UPDATE 
-                        postgres_trigger 
-                    SET
-                        last_server_ping = NULL 
-                    WHERE 
-                        workspace_id = $1 
-                        AND path = $2 
-                        AND server_id IS NULL"#,
-                        &self.workspace_id,
-                        &self.path,
-                    )
-                    .execute(db)
-                    .await
-                    .ok();
-                    tracing::info!(
-                        "Postgres trigger {} changed, disabled, or deleted, stopping...",
-                        self.path
-                    );
-                    return None;
-                }
-            }
-            Err(err) => {
-                tracing::warn!(
-                    "Error updating ping of postgres trigger {}: {:?}",
-                    self.path,
-                    err
-                );
-            }
-        };
-
-        Some(())
-    }
-
-    async fn disable_with_error(&self, db: &DB, error: String) -> () {
-        match sqlx::query!(
-            r#"
-                UPDATE 
-                    postgres_trigger 
-                SET 
-                    enabled = FALSE, 
-                    error = $1, 
-                    server_id = NULL, 
-                    last_server_ping = NULL 
-                WHERE 
-                    workspace_id = $2 AND 
-                    path = $3
-            "#,
-            error,
-            self.workspace_id,
-            self.path,
-        )
-        .execute(db)
-        .await
-        {
-            Ok(_) => {
-                report_critical_error(
-                    format!(
-                        "Disabling postgres trigger {} because of error: {}",
-                        self.path, error
-                    ),
-                    db.clone(),
-                    Some(&self.workspace_id),
-                    None,
-                )
-                .await;
-            }
-            Err(disable_err) => {
-                report_critical_error(
-                    format!("Could not disable postgres trigger {} with err {}, disabling because of error {}", self.path, disable_err, error), 
-                    db.clone(),
-                    Some(&self.workspace_id),
-                    None,
-                ).await;
-            }
-        }
-    }
-
-    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
-        fetch_api_authed(
-            self.edited_by.clone(),
-            self.email.clone(),
-            &self.workspace_id,
-            db,
-            Some(format!("pg-{}", self.path)),
-        )
-        .await
-    }
-
-    async fn handle(&self, db: &DB, payload: HashMap<String, Box<RawValue>>) -> () {
-        if let Err(err) = run_job(payload, db, self).await {
-            report_critical_error(
-                format!(
-                    "Failed to trigger job from postgres {}: {:?}",
-                    self.path, err
-                ),
-                db.clone(),
-                Some(&self.workspace_id),
-                None,
-            )
-            .await;
-        };
-    }
-}
-
-impl TriggerJobArgs<HashMap<String, Box<RawValue>>> for PostgresTrigger {
+impl TriggerJobArgs<HashMap<String, Box<RawValue>>> for PostgresTriggerHandler {
     fn v1_payload_fn(payload: HashMap<String, Box<RawValue>>) -> HashMap<String, Box<RawValue>> {
         payload
     }
@@ -354,645 +181,664 @@ struct PgInfo<'a> {
     postgres_resource_path: &'a str,
     publication_name: &'a str,
     replication_slot_name: &'a str,
-    workspace_id: &'a str,
 }
 
-impl PostgresConfig {
-    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
-        match self {
-            PostgresConfig::Trigger(trigger) => trigger.update_ping(db, error).await,
-            PostgresConfig::Capture(capture) => capture.update_ping(db, error).await,
-        }
-    }
+fn retrieve_info(config: &ListeningConfig<PostgresTriggerHandler>) -> PgInfo<'_> {
+    let (postgres_resource_path, publication_name, replication_slot_name) = match config {
+        ListeningConfig::Trigger(trigger) => (
+            &trigger.config.postgres_resource_path,
+            &trigger.config.publication_name,
+            &trigger.config.replication_slot_name,
+        ),
+        ListeningConfig::Capture(capture) => (
+            &capture.trigger_config.postgres_resource_path,
+            &capture.trigger_config.publication_name,
+            &capture.trigger_config.replication_slot_name,
+        ),
+    };
 
-    async fn disable_with_error(&self, db: &DB, error: String) -> () {
-        match self {
-            PostgresConfig::Trigger(trigger) => trigger.disable_with_error(&db, error).await,
-            PostgresConfig::Capture(capture) => capture.disable_with_error(db, error).await,
-        }
+    PgInfo {
+        postgres_resource_path,
+        publication_name: publication_name.as_deref().unwrap_or_default(),
+        replication_slot_name: replication_slot_name.as_deref().unwrap_or_default(),
     }
+}
 
-    fn retrieve_info(&self) -> PgInfo {
-        let postgres_resource_path;
-        let publication_name;
-        let replication_slot_name;
-        let workspace_id;
-
-        match self {
-            PostgresConfig::Trigger(trigger) => {
-                postgres_resource_path = &trigger.postgres_resource_path;
-                publication_name = &trigger.publication_name;
-                replication_slot_name = &trigger.replication_slot_name;
-                workspace_id = &trigger.workspace_id;
-            }
-            PostgresConfig::Capture(capture) => {
-                postgres_resource_path = &capture.trigger_config.postgres_resource_path;
-                workspace_id = &capture.workspace_id;
-                publication_name = capture.trigger_config.publication_name.as_ref().unwrap();
-                replication_slot_name = capture
-                    .trigger_config
-                    .replication_slot_name
-                    .as_ref()
-                    .unwrap();
-            }
-        };
+async fn start_logical_replication_streaming(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+) -> std::result::Result<(CopyBothDuplex<Bytes>, LogicalReplicationSettings), Error> {
+    let PgInfo { publication_name, replication_slot_name, postgres_resource_path } =
+        retrieve_info(config);
+
+    let authed = config.fetch_authed(db).await?;
+
+    let database = try_get_resource_from_db_as::<Postgres>(
+        &authed,
+        Some(UserDB::new(db.clone())),
+        &db,
+        postgres_resource_path,
+        config.workspace_id(),
+    )
+    .await?;
 
-        PgInfo { postgres_resource_path, replication_slot_name, workspace_id, publication_name }
-    }
+    let client = PostgresSimpleClient::new(&database).await?;
 
-    async fn start_logical_replication_streaming(
-        &self,
-        db: &DB,
-    ) -> std::result::Result<(CopyBothDuplex<Bytes>, LogicalReplicationSettings), Error> {
-        let PgInfo {
-            publication_name,
-            replication_slot_name,
-            workspace_id,
-            postgres_resource_path,
-        } = self.retrieve_info();
+    let publication = client
+        .execute_query(&format!(
+            "SELECT pubname FROM pg_publication WHERE pubname = {}",
+            quote_literal(&publication_name)
+        ))
+        .await
+        .map_err(to_anyhow)?;
+
+    if !publication.row_exist() {
+        return Err(Error::BadConfig(
+            ERROR_PUBLICATION_NAME_NOT_EXISTS.to_string(),
+        ));
+    }
 
-        let authed = match self {
-            PostgresConfig::Trigger(trigger) => trigger.fetch_authed(db).await?,
-            PostgresConfig::Capture(capture) => capture.fetch_authed(db).await?,
+    let replication_slot = client
+        .execute_query(&format!(
+            "SELECT slot_name FROM pg_replication_slots WHERE slot_name = {}",
+            quote_literal(&replication_slot_name)
+        ))
+        .await
+        .map_err(to_anyhow)?;
+
+    if is_initial_snapshot_pending(config) {
+        // the slot is only created if absent so that streaming starts exactly where the
+        // snapshot ends. An existing slot, left by an interrupted snapshot or created by
+        // someone else, is kept: its snapshot can no longer be exported so the rows are read
+        // as of now and the changes it holds may also be part of the snapshot.
+        let snapshot_name = if replication_slot.row_exist() {
+            None
+        } else {
+            Some(
+                client
+                    .create_logical_replication_slot_with_snapshot(&replication_slot_name)
+                    .await?,
+            )
         };
 
-        let database = try_get_resource_from_db_as::<Postgres>(
-            &authed,
-            Some(UserDB::new(db.clone())),
-            &db,
-            postgres_resource_path,
-            workspace_id,
+        emit_initial_snapshot(
+            db,
+            config,
+            &database,
+            snapshot_name.as_deref(),
+            &publication_name,
         )
         .await?;
+    } else if !replication_slot.row_exist() {
+        return Err(Error::BadConfig(
+            ERROR_REPLICATION_SLOT_NOT_EXISTS.to_string(),
+        ));
+    }
 
-        let client = PostgresSimpleClient::new(&database).await?;
+    let (logical_replication_stream, logical_replication_settings) = client
+        .get_logical_replication_stream(&publication_name, &replication_slot_name)
+        .await
+        .map_err(to_anyhow)?;
 
-        let publication = client
-            .execute_query(&format!(
-                "SELECT pubname FROM pg_publication WHERE pubname = {}",
-                quote_literal(&publication_name)
-            ))
-            .await
-            .map_err(to_anyhow)?;
+    Ok((logical_replication_stream, logical_replication_settings))
+}
 
-        if !publication.row_exist() {
-            return Err(Error::BadConfig(
-                ERROR_PUBLICATION_NAME_NOT_EXISTS.to_string(),
-            ));
+fn is_initial_snapshot_pending(config: &ListeningConfig<PostgresTriggerHandler>) -> bool {
+    match config {
+        ListeningConfig::Trigger(trigger) => {
+            trigger.config.initial_snapshot && trigger.config.snapshot_completed_at.is_none()
         }
+        ListeningConfig::Capture(_) => false,
+    }
+}
 
-        let replication_slot = client
-            .execute_query(&format!(
-                "SELECT slot_name FROM pg_replication_slots WHERE slot_name = {}",
-                quote_literal(&replication_slot_name)
-            ))
-            .await
-            .map_err(to_anyhow)?;
+/// Emits every row of the tracked tables as of the snapshot as an insert event, batched like
+/// the streamed changes with each batch of rows read from a table as a transaction, and
+/// records its completion once all the jobs are pushed so that it is skipped when the listener
+/// restarts. If the listener stops before the end of the snapshot, it is taken again from the
+/// start while keeping the slot created by the first attempt, so rows may be delivered more
+/// than once.
+async fn emit_initial_snapshot(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    database: &Postgres,
+    snapshot_name: Option<&str>,
+    publication_name: &str,
+) -> Result<(), Error> {
+    let ListeningConfig::Trigger(trigger) = config else {
+        return Ok(());
+    };
 
-        if !replication_slot.row_exist() {
-            return Err(Error::BadConfig(
-                ERROR_REPLICATION_SLOT_NOT_EXISTS.to_string(),
-            ));
+    tracing::info!(
+        "Taking initial snapshot for postgres trigger {}",
+        trigger.base.path
+    );
+
+    let mut reader = SnapshotReader::new(database, snapshot_name, publication_name).await?;
+    let selector = row_selector(config);
+    // the jobs of the snapshot are not waited for: the rows are not held by the slot
+    let mut delivery = ChangeDelivery::new(
+        trigger
+            .config
+            .batching
+            .as_ref()
+            .map(|batching| batching.0.clone()),
+        false,
+    );
+    let mut rows_count = 0;
+
+    while let Some((table, rows)) = reader.next_batch().await? {
+        rows_count += rows.len();
+        delivery.on_begin(0);
+        for mut row in rows {
+            let position = delivery.next_position();
+            if !selector.matches(&table.schema_name, &table.table_name, &row) {
+                continue;
+            }
+            selector.project(&table.schema_name, &table.table_name, &mut row);
+            let change = Change {
+                schema_name: table.schema_name.clone(),
+                table_name: table.table_name.clone(),
+                transaction_type: "insert",
+                old_row: None,
+                row,
+            };
+            push_snapshot_batch(db, trigger, delivery.on_change(position, change)).await?;
+        }
+        push_snapshot_batch(db, trigger, delivery.on_commit(0)).await?;
+        if delivery
+            .deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            push_snapshot_batch(db, trigger, delivery.take()).await?;
         }
+    }
+    push_snapshot_batch(db, trigger, delivery.take()).await?;
 
-        let (logical_replication_stream, logical_replication_settings) = client
-            .get_logical_replication_stream(&publication_name, &replication_slot_name)
-            .await
-            .map_err(to_anyhow)?;
+    reader.finish().await?;
+
+    sqlx::query!(
+        "UPDATE postgres_trigger SET snapshot_completed_at = now() WHERE workspace_id = $1 AND path = $2",
+        &trigger.base.workspace_id,
+        &trigger.base.path
+    )
+    .execute(db)
+    .await?;
+
+    tracing::info!(
+        "Initial snapshot of postgres trigger {} completed with {} rows",
+        trigger.base.path,
+        rows_count
+    );
 
-        Ok((logical_replication_stream, logical_replication_settings))
+    Ok(())
+}
+
+/// Pushes the job of a batch of the initial snapshot, without idempotency key as the rows
+/// read by a new attempt of the snapshot may differ
+async fn push_snapshot_batch(
+    db: &DB,
+    trigger: &PostgresTrigger,
+    batch: Option<Batch>,
+) -> Result<(), Error> {
+    if let Some(batch) = batch {
+        run_job(batch.payload, db, trigger, None).await?;
     }
+    Ok(())
+}
 
-    fn get_path(&self) -> &str {
-        match self {
-            PostgresConfig::Trigger(trigger) => &trigger.path,
-            PostgresConfig::Capture(capture) => &capture.path,
-        }
+fn row_selector(config: &ListeningConfig<PostgresTriggerHandler>) -> RowSelector {
+    match config {
+        ListeningConfig::Trigger(trigger) => RowSelector::new(
+            trigger
+                .config
+                .row_filters
+                .as_ref()
+                .map(|row_filters| row_filters.0.clone())
+                .unwrap_or_default(),
+            trigger
+                .config
+                .column_projection
+                .as_ref()
+                .map(|column_projection| column_projection.0.clone())
+                .unwrap_or_default(),
+            trigger.config.include_old_row,
+        ),
+        ListeningConfig::Capture(_) => RowSelector::new(vec![], HashMap::new(), true),
     }
+}
 
-    async fn handle(&self, db: &DB, payload: HashMap<String, Box<RawValue>>) -> () {
-        match self {
-            PostgresConfig::Trigger(trigger) => trigger.handle(&db, payload).await,
-            PostgresConfig::Capture(capture) => capture.handle(&db, payload).await,
-        }
+fn change_delivery(config: &ListeningConfig<PostgresTriggerHandler>) -> ChangeDelivery {
+    match config {
+        ListeningConfig::Trigger(trigger) => ChangeDelivery::new(
+            trigger
+                .config
+                .batching
+                .as_ref()
+                .map(|batching| batching.0.clone()),
+            trigger.config.ack_after_success,
+        ),
+        ListeningConfig::Capture(_) => ChangeDelivery::new(None, false),
     }
+}
 
-    async fn cleanup(&self, db: &DB) -> Result<(), Error> {
-        match self {
-            PostgresConfig::Trigger(_) => Ok(()),
-            PostgresConfig::Capture(capture) => {
-                let publication_name = capture.trigger_config.publication_name.as_ref().unwrap();
-                let replication_slot_name = capture
-                    .trigger_config
-                    .replication_slot_name
-                    .as_ref()
-                    .unwrap();
-                let postgres_resource_path = &capture.trigger_config.postgres_resource_path;
-                let workspace_id = &capture.workspace_id;
-                let authed = capture.fetch_authed(&db).await?;
-
-                let user_db = UserDB::new(db.clone());
-
-                let mut pg_connection = get_default_pg_connection(
-                    authed.clone(),
-                    Some(user_db.clone()),
-                    &db,
-                    postgres_resource_path,
-                    workspace_id,
+/// Pushes the job of a batch of changes. Unless the changes are only acknowledged once their
+/// job succeeded, the position of the changes is used as idempotency key so that changes
+/// delivered again after a restart do not run twice. The bounds of a window are saved before
+/// pushing it so that a window cut short by a restart is replayed with the same bounds.
+async fn deliver(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    batch: &Batch,
+) -> Result<Option<uuid::Uuid>, Error> {
+    match config {
+        ListeningConfig::Trigger(trigger) => {
+            let idempotency_key = if trigger.config.ack_after_success {
+                None
+            } else {
+                IdempotencyKey::new(
+                    &trigger.base.workspace_id,
+                    format!(
+                        "{}/{}",
+                        PostgresTriggerHandler::TABLE_NAME,
+                        trigger.base.path
+                    ),
+                    &batch.idempotency_key(),
+                )?
+            };
+            if idempotency_key.is_some() && batches_by_window(config) {
+                sqlx::query!(
+                    "UPDATE postgres_trigger SET last_window_batch = $1 WHERE workspace_id = $2 AND path = $3",
+                    SqlxJson(&batch.bounds) as SqlxJson<&BatchBounds>,
+                    trigger.base.workspace_id,
+                    trigger.base.path,
                 )
+                .execute(db)
                 .await?;
-
-                if capture.trigger_config.basic_mode.unwrap_or(false) {
-                    drop_logical_replication_slot(&mut pg_connection, replication_slot_name)
-                        .await?;
-
-                    drop_publication(&mut pg_connection, publication_name).await?;
-                }
-
-                Ok(())
             }
+            let uuid =
+                run_job(batch.payload.clone(), db, trigger, idempotency_key.as_ref()).await?;
+            Ok(Some(uuid))
+        }
+        ListeningConfig::Capture(capture) => {
+            let (main_args, preprocessor_args) = PostgresTriggerHandler::build_capture_payloads(
+                batch.payload.clone(),
+                HashMap::new(),
+            );
+            capture
+                .insert_payload(db, &TriggerKind::Postgres, main_args, preprocessor_args)
+                .await;
+            Ok(None)
         }
     }
 }
 
-async fn listen_to_transactions(
-    pg: PostgresConfig,
-    db: DB,
-    mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
-) {
-    tokio::select! {
-        biased;
-        _ = killpill_rx.recv() => {
-            let _ = pg.cleanup(&db).await;
-            return;
-        }
-        _ = loop_ping(&db, &pg, Some("Connecting...")) => {
-            let _ = pg.cleanup(&db).await;
-            return;
-        }
-        result = pg.start_logical_replication_streaming(&db) => {
-            tokio::select! {
-                biased;
-                _ = killpill_rx.recv() => {
-                    let _ = pg.cleanup(&db).await;
-                    return;
-                }
-                _ = loop_ping(&db, &pg, None) => {
-                    let _ = pg.cleanup(&db).await;
-                    return;
-                }
-                _ = {
-                    async {
-                        match result {
-                            Ok((logical_replication_stream, logical_replication_settings)) => {
-                                pin_mut!(logical_replication_stream);
-                                let mut relations = RelationConverter::new();
-                                tracing::info!("Starting to listen for postgres trigger {}", pg.get_path());
-                                loop {
-                                    let message = logical_replication_stream.next().await;
-
-                                    let message = match message {
-                                        Some(message) => message,
-                                        None => {
-                                            tracing::error!("Stream for postgres trigger {} closed", pg.get_path());
-                                            if let None = pg.update_ping(&db, Some("Stream closed")).await {
-                                                return;
-                                            }
-                                            return;
-                                        }
-                                    };
-
-
-                                    let message = match message {
-                                        Ok(message) => message,
-                                        Err(err) => {
-                                            let err = format!("Postgres trigger named {} had an error while receiving a message : {}", pg.get_path(), err.to_string());
-                                            pg.disable_with_error(&db, err).await;
-                                            return;
-                                        }
-                                    };
-
-                                    let logical_message = match ReplicationMessage::parse(message) {
-                                        Ok(logical_message) => logical_message,
-                                        Err(err) => {
-                                            let err = format!("Postgres trigger named: {} had an error while parsing message: {}", pg.get_path(), err.to_string());
-                                            pg.disable_with_error(&db, err).await;
-                                            return;
-                                        }
-                                    };
-
-
-                                    match logical_message {
-                                        ReplicationMessage::PrimaryKeepAlive(primary_keep_alive) => {
-                                            if primary_keep_alive.reply {
-                                                PostgresSimpleClient::send_status_update(primary_keep_alive, &mut logical_replication_stream).await;
-                                            }
-                                        }
-                                        ReplicationMessage::XLogData(x_log_data) => {
-                                            let logical_replication_message = match x_log_data.parse(&logical_replication_settings) {
-                                                Ok(logical_replication_message) => logical_replication_message,
-                                                Err(err) => {
-                                                    tracing::error!("Postgres trigger named: {} had an error while trying to parse incomming stream message: {}", pg.get_path(), err.to_string());
-                                                    continue;
-                                                }
-                                            };
-
-                                            let json = match logical_replication_message {
-                                                Relation(relation_body) => {
-                                                    relations.add_relation(relation_body);
-                                                    None
-                                                }
-                                                Begin | Type | Commit => {
-                                                    None
-                                                }
-                                                Insert(insert) => {
-                                                    Some((insert.o_id, Ok(None), relations.row_to_json((insert.o_id, insert.tuple)), "insert"))
-                                                }
-                                                Update(update) => {
-                                                    let old_row = update.old_tuple.map(|old_tuple| relations.row_to_json((update.o_id, old_tuple))).transpose();
-                                                    let row = relations.row_to_json((update.o_id, update.new_tuple));
-                                                    Some((update.o_id, old_row, row, "update"))
-                                                }
-                                                Delete(delete) => {
-                                                    let row = delete.old_tuple.unwrap_or_else(|| delete.key_tuple.unwrap());
-                                                    Some((delete.o_id, Ok(None), relations.row_to_json((delete.o_id, row)), "delete"))
-                                                }
-                                            };
-                                            match json {
-                                                Some((o_id, Ok(old_row), Ok(row), transaction_type)) => {
-                                                    let relation = match relations.get_relation(o_id) {
-                                                        Ok(relation) => relation,
-                                                        Err(err) => {
-                                                            tracing::error!("Postgres trigger named: {}, error: {}", pg.get_path(), err.to_string());
-                                                            continue;
-                                                        }
-                                                    };
-                                                    let database_info = HashMap::from([
-                                                        ("schema_name".to_string(), to_raw_value(&relation.namespace)),
-                                                        ("table_name".to_string(), to_raw_value(&relation.name)),
-                                                        ("transaction_type".to_string(), to_raw_value(&transaction_type)),
-                                                        ("old_row".to_string(), to_raw_value(&old_row)),
-                                                        ("row".to_string(), to_raw_value(&row)),
-                                                    ]);
-
-
-                                                    let _ = pg.handle(&db, database_info).await;
-                                                }
-                                                Some((o_id, old_row, row, transaction_type)) => {
-                                                    let relation = match relations.get_relation(o_id) {
-                                                        Ok(relation) => relation,
-                                                        Err(err) => {
-                                                            tracing::error!("Postgres trigger named: {}, error: {}", pg.get_path(), err.to_string());
-                                                            continue;
-                                                        }
-                                                    };
-
-                                                    if let Err(err) = old_row {
-                                                        tracing::error!(
-                                                            transaction_type = ?transaction_type,
-                                                            schema = %relation.namespace,
-                                                            table = %relation.name,
-                                                            error = %err,
-                                                            "Failed to decode OLD row for {} transaction on {}.{}",
-                                                            transaction_type,
-                                                            relation.namespace,
-                                                            relation.name,
-                                                        );
-                                                    }
-
-                                                    if let Err(err) = row {
-                                                        tracing::error!(
-                                                            transaction_type = ?transaction_type,
-                                                            schema = %relation.namespace,
-                                                            table = %relation.name,
-                                                            error = %err,
-                                                            "Failed to decode NEW row for {} transaction on {}.{}",
-                                                            transaction_type,
-                                                            relation.namespace,
-                                                            relation.name,
-                                                        );
-                                                    }
-
-                                                }
-                                                _ => {}
-                                            }
-
-                                        }
-                                    }
-                                }
-                            }
-                            Err(err) => {
-                                tracing::error!("Postgres trigger error while trying to start logical replication streaming: {}", &err);
-                                pg.disable_with_error(&db, err.to_string()).await
-                            }
-                        }
-                    }
-                } => {
-                    let _ = pg.cleanup(&db).await;
-                    return;
-                }
-            }
-        }
+fn batches_by_window(config: &ListeningConfig<PostgresTriggerHandler>) -> bool {
+    match config {
+        ListeningConfig::Trigger(trigger) => matches!(
+            trigger.config.batching.as_ref().map(|batching| &batching.0),
+            Some(PostgresTriggerBatching::Window { .. })
+        ),
+        ListeningConfig::Capture(_) => false,
     }
 }
 
-#[derive(Deserialize)]
-struct CaptureConfigForPostgresTrigger {
-    trigger_config: SqlxJson<PostgresTriggerConfig>,
-    path: String,
-    is_flow: bool,
-    workspace_id: String,
-    owner: String,
-    email: String,
+async fn deliver_batch(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    delivery: &mut ChangeDelivery,
+    batch: Option<Batch>,
+) -> Result<(), Error> {
+    if let Some(batch) = batch {
+        let job_id = deliver(db, config, &batch).await?;
+        delivery.delivered(job_id, batch);
+    }
+    Ok(())
 }
 
-impl CaptureConfigForPostgresTrigger {
-    async fn try_to_listen_to_database_transactions(
-        self,
-        db: DB,
-        killpill_rx: tokio::sync::broadcast::Receiver<()>,
-    ) -> () {
-        match sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                capture_config 
-            SET 
-                server_id = $1,
-                last_server_ping = now(), 
-                error = 'Connecting...' 
-            WHERE 
-                last_client_ping > NOW() - INTERVAL '10 seconds' AND 
-                workspace_id = $2 AND 
-                path = $3 AND 
-                is_flow = $4 AND 
-                trigger_kind = 'postgres' AND 
-                (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds') 
-            RETURNING true
-            "#,
-            *INSTANCE_NAME,
-            self.workspace_id,
-            self.path,
-            self.is_flow,
-        )
-        .fetch_optional(&db)
-        .await
-        {
-            Ok(has_lock) => {
-                if has_lock.flatten().unwrap_or(false) {
-                    tokio::spawn(listen_to_transactions(
-                        PostgresConfig::Capture(self),
-                        db,
-                        killpill_rx,
-                    ));
-                } else {
-                    tracing::info!("Postgres {} already being listened to", self.path);
+/// Replays the last window pushed before a restart, in case its job was not pushed
+async fn resume_delivery(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    delivery: &mut ChangeDelivery,
+) -> Result<(), Error> {
+    let ListeningConfig::Trigger(trigger) = config else {
+        return Ok(());
+    };
+    if trigger.config.ack_after_success || !batches_by_window(config) {
+        return Ok(());
+    }
+    let bounds = sqlx::query_scalar!(
+        r#"SELECT last_window_batch AS "last_window_batch: SqlxJson<BatchBounds>" FROM postgres_trigger WHERE workspace_id = $1 AND path = $2"#,
+        trigger.base.workspace_id,
+        trigger.base.path,
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten();
+    if let Some(bounds) = bounds {
+        delivery.replay_window(bounds.0);
+    }
+    Ok(())
+}
+
+/// Pushes again the batches whose job failed once their backoff elapsed, and keeps the jobs
+/// that failed on every attempt as dead letters
+async fn refresh_delivery(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    delivery: &mut ChangeDelivery,
+) -> Result<(), Error> {
+    for redelivery in delivery.refresh(db).await? {
+        match redelivery {
+            Redelivery::Retry { failed_job, batch } => {
+                tracing::warn!(
+                    "Job {failed_job} triggered by postgres trigger {} failed, pushing changes {} again",
+                    config.path(),
+                    batch.idempotency_key()
+                );
+                if let Some(job_id) = deliver(db, config, &batch).await? {
+                    delivery.redelivered(failed_job, job_id);
                 }
             }
-            Err(err) => {
+            Redelivery::DeadLetter(job_id) => {
                 tracing::error!(
-                    "Error acquiring lock for capture postgres {}: {:?}",
-                    self.path,
-                    err
+                    "Job {job_id} triggered by postgres trigger {} failed on every attempt, its changes are acknowledged and the job is kept as a dead letter",
+                    config.path()
                 );
+                insert_job_failure(db, job_id).await?;
             }
-        };
+        }
     }
+    Ok(())
+}
 
-    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
-        match sqlx::query_scalar!(
-            r#"
-            UPDATE 
-                capture_config 
-            SET 
-                last_server_ping = now(), 
-                error = $1 
-            WHERE 
-                workspace_id = $2 AND 
-                path = $3 AND 
-                is_flow = $4 AND 
-                trigger_kind = 'postgres' AND 
-                server_id = $5 AND 
-                last_client_ping > NOW() - INTERVAL '10 seconds' 
-            RETURNING 1
-        "#,
-            error,
-            self.workspace_id,
-            self.path,
-            self.is_flow,
-            *INSTANCE_NAME
-        )
-        .fetch_optional(db)
-        .await
-        {
-            Ok(updated) => {
-                if updated.flatten().is_none() {
-                    // allow faster restart of postgres capture
-                    sqlx::query!(
-                        r#"UPDATE 
-                        capture_config 
-                    SET 
-                        last_server_ping = NULL 
-                    WHERE 
-                        workspace_id = $1 AND 
-                        path = $2 AND 
-                        is_flow = $3 AND 
-                        trigger_kind = 'postgres' AND 
-                        server_id IS NULL
-                    "#,
-                        self.workspace_id,
-                        self.path,
-                        self.is_flow,
-                    )
-                    .execute(db)
-                    .await
-                    .ok();
-                    tracing::info!(
-                        "Postgres capture {} changed, disabled, or deleted, stopping...",
-                        self.path
-                    );
-                    return None;
+/// Streams the changes until an error that disables the trigger. Returns without error when
+/// the changes could not be delivered or the stream closed, so that the listener restarts from
+/// the last acknowledged change.
+async fn stream_changes(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    logical_replication_stream: CopyBothDuplex<Bytes>,
+    logical_replication_settings: LogicalReplicationSettings,
+) -> Result<(), Error> {
+    pin_mut!(logical_replication_stream);
+    let mut relations = RelationConverter::new();
+    let mut delivery = change_delivery(config);
+    if let Err(err) = resume_delivery(db, config, &mut delivery).await {
+        stop_with_delivery_error(db, config, err).await;
+        return Ok(());
+    }
+    let selector = row_selector(config);
+    let mut wal_end = 0;
+    let mut status_interval = tokio::time::interval(STATUS_UPDATE_INTERVAL);
+    tracing::info!("Starting to listen for postgres trigger {}", config.path());
+    loop {
+        let deadline = delivery.deadline();
+        let message = tokio::select! {
+            message = logical_replication_stream.next() => message,
+            _ = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                let batch = delivery.take();
+                if let Err(err) = deliver_batch(db, config, &mut delivery, batch).await {
+                    stop_with_delivery_error(db, config, err).await;
+                    return Ok(());
                 }
+                continue;
             }
-            Err(err) => {
-                tracing::warn!(
-                    "Error updating ping of capture postgres {}: {:?}",
-                    self.path,
-                    err
-                );
+            _ = status_interval.tick() => {
+                if let Err(err) = refresh_delivery(db, config, &mut delivery).await {
+                    stop_with_delivery_error(db, config, err).await;
+                    return Ok(());
+                }
+                PostgresSimpleClient::send_status_update(delivery.flush_lsn(wal_end), &mut logical_replication_stream).await;
+                continue;
             }
         };
 
-        Some(())
-    }
+        let message = match message {
+            Some(message) => message,
+            None => {
+                tracing::error!("Stream for postgres trigger {} closed", config.path());
+                config.report_error(db, "Stream closed").await;
+                return Ok(());
+            }
+        };
 
-    async fn fetch_authed(&self, db: &DB) -> error::Result<ApiAuthed> {
-        fetch_api_authed(
-            self.owner.clone(),
-            self.email.clone(),
-            &self.workspace_id,
-            db,
-            Some(format!("postgres-{}", self.get_trigger_path())),
-        )
-        .await
-    }
+        let message = message.map_err(|err| {
+            Error::ExecutionErr(format!(
+                "Postgres trigger named {} had an error while receiving a message : {}",
+                config.path(),
+                err.to_string()
+            ))
+        })?;
 
-    fn get_trigger_path(&self) -> String {
-        format!(
-            "{}-{}",
-            if self.is_flow { "flow" } else { "script" },
-            self.path
-        )
-    }
+        let logical_message = ReplicationMessage::parse(message).map_err(|err| {
+            Error::ExecutionErr(format!(
+                "Postgres trigger named: {} had an error while parsing message: {}",
+                config.path(),
+                err.to_string()
+            ))
+        })?;
+
+        match logical_message {
+            ReplicationMessage::PrimaryKeepAlive(primary_keep_alive) => {
+                wal_end = wal_end.max(primary_keep_alive.wal_end);
+                if primary_keep_alive.reply {
+                    if let Err(err) = refresh_delivery(db, config, &mut delivery).await {
+                        stop_with_delivery_error(db, config, err).await;
+                        return Ok(());
+                    }
+                    PostgresSimpleClient::send_status_update(
+                        delivery.flush_lsn(wal_end),
+                        &mut logical_replication_stream,
+                    )
+                    .await;
+                }
+            }
+            ReplicationMessage::XLogData(x_log_data) => {
+                wal_end = wal_end.max(x_log_data.wal_end);
+                let logical_replication_message = match x_log_data
+                    .parse(&logical_replication_settings)
+                {
+                    Ok(logical_replication_message) => logical_replication_message,
+                    Err(err) => {
+                        tracing::error!("Postgres trigger named: {} had an error while trying to parse incomming stream message: {}", config.path(), err.to_string());
+                        continue;
+                    }
+                };
 
-    async fn disable_with_error(&self, db: &DB, error: String) -> () {
-        if let Err(err) = sqlx::query!(
-            r#"
-                UPDATE 
-                    capture_config 
-                SET 
-                    error = $1, 
-                    server_id = NULL, 
-                    last_server_ping = NULL 
-                WHERE 
-                    workspace_id = $2 AND 
-                    path = $3 AND 
-                    is_flow = $4 AND 
-                    trigger_kind = 'postgres'
-            "#,
-            error,
-            self.workspace_id,
-            self.path,
-            self.is_flow,
-        )
-        .execute(db)
-        .await
-        {
-            tracing::error!("Could not disable postgres capture {} ({}) with err {}, disabling because of error {}", self.path, self.workspace_id, err, error);
+                let tuples = match logical_replication_message {
+                    Relation(relation_body) => {
+                        relations.add_relation(relation_body);
+                        None
+                    }
+                    Begin(begin) => {
+                        delivery.on_begin(begin.final_lsn);
+                        None
+                    }
+                    Commit(commit) => {
+                        let batch = delivery.on_commit(commit.end_lsn);
+                        if let Err(err) = deliver_batch(db, config, &mut delivery, batch).await {
+                            stop_with_delivery_error(db, config, err).await;
+                            return Ok(());
+                        }
+                        None
+                    }
+                    Type => None,
+                    Insert(insert) => Some((insert.o_id, None, insert.tuple, "insert")),
+                    Update(update) => {
+                        let old_tuple = selector.old_row(update.old_tuple);
+                        Some((update.o_id, old_tuple, update.new_tuple, "update"))
+                    }
+                    Delete(delete) => {
+                        let row = delete
+                            .old_tuple
+                            .unwrap_or_else(|| delete.key_tuple.unwrap());
+                        Some((delete.o_id, None, row, "delete"))
+                    }
+                };
+
+                let Some((o_id, old_tuple, tuple, transaction_type)) = tuples else {
+                    continue;
+                };
+                let position = delivery.next_position();
+
+                let relation = match relations.get_relation(o_id) {
+                    Ok(relation) => relation,
+                    Err(err) => {
+                        tracing::error!(
+                            "Postgres trigger named: {}, error: {}",
+                            config.path(),
+                            err.to_string()
+                        );
+                        continue;
+                    }
+                };
+
+                let columns_to_decode =
+                    selector.columns_to_decode(&relation.namespace, &relation.name);
+                let old_row = old_tuple
+                    .map(|old_tuple| {
+                        relations.row_to_json((o_id, old_tuple), columns_to_decode.as_ref())
+                    })
+                    .transpose();
+                let row = relations.row_to_json((o_id, tuple), columns_to_decode.as_ref());
+
+                match (old_row, row) {
+                    (Ok(mut old_row), Ok(mut row)) => {
+                        if !selector.matches(&relation.namespace, &relation.name, &row) {
+                            continue;
+                        }
+
+                        selector.project(&relation.namespace, &relation.name, &mut row);
+                        if let Some(old_row) = old_row.as_mut() {
+                            selector.project(&relation.namespace, &relation.name, old_row);
+                        }
+
+                        let change = Change {
+                            schema_name: relation.namespace.clone(),
+                            table_name: relation.name.clone(),
+                            transaction_type,
+                            old_row,
+                            row,
+                        };
+
+                        let batch = delivery.on_change(position, change);
+                        if let Err(err) = deliver_batch(db, config, &mut delivery, batch).await {
+                            stop_with_delivery_error(db, config, err).await;
+                            return Ok(());
+                        }
+                    }
+                    (old_row, row) => {
+                        if let Err(err) = old_row {
+                            tracing::error!(
+                                transaction_type = ?transaction_type,
+                                schema = %relation.namespace,
+                                table = %relation.name,
+                                error = %err,
+                                "Failed to decode OLD row for {} transaction on {}.{}",
+                                transaction_type,
+                                relation.namespace,
+                                relation.name,
+                            );
+                        }
+
+                        if let Err(err) = row {
+                            tracing::error!(
+                                transaction_type = ?transaction_type,
+                                schema = %relation.namespace,
+                                table = %relation.name,
+                                error = %err,
+                                "Failed to decode NEW row for {} transaction on {}.{}",
+                                transaction_type,
+                                relation.namespace,
+                                relation.name,
+                            );
+                        }
+                    }
+                }
+            }
         }
     }
+}
+
+/// Stops listening without acknowledging the undelivered changes. The listener is restarted
+/// by the next server to pick up the trigger and postgres sends the changes again from the
+/// last acknowledged lsn.
+async fn stop_with_delivery_error(
+    db: &DB,
+    config: &ListeningConfig<PostgresTriggerHandler>,
+    err: Error,
+) {
+    let err = format!(
+        "Postgres trigger named {} failed to deliver changes, retrying from the last acknowledged change: {}",
+        config.path(),
+        err
+    );
+    tracing::error!("{}", err);
+    config.report_error(db, &err).await;
+}
 
-    async fn handle(&self, db: &DB, payload: HashMap<String, Box<RawValue>>) -> () {
-        let main_args = PostgresTrigger::build_job_args_v2(false, payload.clone(), HashMap::new());
-        let preprocessor_args = PostgresTrigger::build_job_args_v2(true, payload, HashMap::new());
-        if let Err(err) = insert_capture_payload(
+#[async_trait]
+impl Listener for PostgresTriggerHandler {
+    type CaptureConfig = PostgresTriggerConfig;
+    type Connection = (CopyBothDuplex<Bytes>, LogicalReplicationSettings);
+    type Error = Error;
+
+    async fn connect(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+    ) -> Result<(CopyBothDuplex<Bytes>, LogicalReplicationSettings), Error> {
+        start_logical_replication_streaming(db, config).await
+    }
+
+    async fn consume(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+        (logical_replication_stream, logical_replication_settings): (
+            CopyBothDuplex<Bytes>,
+            LogicalReplicationSettings,
+        ),
+    ) -> Result<(), Error> {
+        stream_changes(
             db,
-            &self.workspace_id,
-            &self.path,
-            self.is_flow,
-            &TriggerKind::Postgres,
-            main_args,
-            preprocessor_args,
-            &self.owner,
+            config,
+            logical_replication_stream,
+            logical_replication_settings,
         )
         .await
-        {
-            tracing::error!("Error inserting capture payload: {:?}", err);
-        }
     }
-}
 
-async fn listen_to_unlistened_database_events(
-    db: &DB,
-    killpill_rx: &tokio::sync::broadcast::Receiver<()>,
-) {
-    let postgres_triggers = sqlx::query_as!(
-        PostgresTrigger,
-        r#"
-            SELECT
-                workspace_id,
-                path,
-                script_path,
-                replication_slot_name,
-                publication_name,
-                is_flow,
-                edited_by,
-                email,
-                edited_at,
-                server_id,
-                last_server_ping,
-                extra_perms,
-                error,
-                enabled,
-                postgres_resource_path,
-                error_handler_path,
-                error_handler_args as "error_handler_args: _",
-                retry as "retry: _"
-            FROM
-                postgres_trigger
-            WHERE
-                enabled IS TRUE
-                AND (last_server_ping IS NULL OR
-                    last_server_ping < now() - interval '15 seconds'
-                )
-            "#
-    )
-    .fetch_all(db)
-    .await;
-
-    match postgres_triggers {
-        Ok(mut triggers) => {
-            triggers.shuffle(&mut rand::rng());
-            for trigger in triggers {
-                trigger
-                    .try_to_listen_to_database_transactions(db.clone(), killpill_rx.resubscribe())
-                    .await;
-            }
-        }
-        Err(err) => {
-            tracing::error!("Error fetching postgres triggers: {:?}", err);
+    /// The slot and publication of a capture in basic mode only live as long as the capture
+    async fn cleanup(db: &DB, config: &ListeningConfig<Self>) -> Result<(), Error> {
+        let ListeningConfig::Capture(capture) = config else {
+            return Ok(());
+        };
+        if !capture.trigger_config.basic_mode.unwrap_or(false) {
+            return Ok(());
         }
-    };
 
-    let postgres_triggers_capture = sqlx::query_as!(
-        CaptureConfigForPostgresTrigger,
-        r#"
-            SELECT
-                path,
-                is_flow,
-                workspace_id,
-                owner,
-                email,
-                trigger_config as "trigger_config!: _"
-            FROM
-                capture_config
-            WHERE
-                trigger_kind = 'postgres' AND
-                last_client_ping > NOW() - INTERVAL '10 seconds' AND
-                trigger_config IS NOT NULL AND
-                (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
-            "#
-    )
-    .fetch_all(db)
-    .await;
-
-    match postgres_triggers_capture {
-        Ok(mut captures) => {
-            captures.shuffle(&mut rand::rng());
-            for capture in captures {
-                capture
-                    .try_to_listen_to_database_transactions(db.clone(), killpill_rx.resubscribe())
-                    .await;
-            }
-        }
-        Err(err) => {
-            tracing::error!("Error fetching captures postgres triggers: {:?}", err);
-        }
-    };
-}
+        let PgInfo { publication_name, replication_slot_name, postgres_resource_path } =
+            retrieve_info(config);
 
-pub fn start_database(db: DB, mut killpill_rx: tokio::sync::broadcast::Receiver<()>) {
-    tokio::spawn(async move {
-        listen_to_unlistened_database_events(&db, &killpill_rx).await;
-        loop {
-            tokio::select! {
-                biased;
-                _ = killpill_rx.recv() => {
-                    return;
-                }
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(15)) => {
-                    listen_to_unlistened_database_events(&db,  &killpill_rx).await
-                }
-            }
-        }
-    });
+        let mut pg_connection = get_default_pg_connection(
+            config.fetch_authed(db).await?,
+            Some(UserDB::new(db.clone())),
+            &db,
+            postgres_resource_path,
+            &capture.workspace_id,
+        )
+        .await?;
+
+        drop_logical_replication_slot(&mut pg_connection, replication_slot_name).await?;
+
+        drop_publication(&mut pg_connection, publication_name).await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,969 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Promotions move a set of items from a workspace to another one, e.g. from dev to staging
+//! to prod. The diff between both workspaces is computed when the promotion is created, must
+//! be approved, and is applied in a single transaction only if it did not change since.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use axum::{
+    extract::{Extension, Path, Query},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use hyper::StatusCode;
+use magic_crypt::MagicCrypt256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json as SqlxJson;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    error::{Error, JsonResult, Result},
+    flows::Flow,
+    schedule::Schedule,
+    scripts::Script,
+    utils::{not_found_if_none, paginate, require_admin, Pagination},
+    variables::{build_crypt, decrypt, ExportableListableVariable},
+};
+
+use crate::{
+    apps::AppWithLastVersion,
+    db::{ApiAuthed, DB},
+    folders::Folder,
+    resources::{Resource, ResourceType},
+    workspaces_export::{script_archive_files, to_string_without_metadata},
+    workspaces_import::{
+        import_archive, ConflictStrategy, ImportArchive, ImportOptions, ImportReport,
+    },
+};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/create", post(create_promotion))
+        .route("/list", get(list_promotions))
+        .route("/get/:id", get(get_promotion))
+        .route("/approve/:id", post(approve_promotion))
+        .route("/reject/:id", post(reject_promotion))
+        .route("/apply/:id", post(apply_promotion))
+        .route("/delete/:id", delete(delete_promotion))
+}
+
+/// The kinds of `DeployedObject` that can be promoted. Folders and resource types are
+/// identified by their name.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PromotionKind {
+    Script,
+    Flow,
+    App,
+    Folder,
+    Resource,
+    Variable,
+    Schedule,
+    ResourceType,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PromotionItem {
+    pub kind: PromotionKind,
+    pub path: String,
+}
+
+/// References to `from` in the promoted items are rewritten to `to`, and an item at `from` is
+/// promoted to `to`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Remapping {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Added,
+    Modified,
+    Unchanged,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemDiff {
+    pub kind: PromotionKind,
+    pub path: String,
+    pub target_path: String,
+    pub status: DiffStatus,
+    /// JSON pointers of the changed fields, secret values are never part of the diff
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "PROMOTION_STATUS", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum PromotionStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Applied,
+}
+
+#[derive(Serialize)]
+struct Promotion {
+    id: i64,
+    workspace_id: String,
+    target_workspace_id: String,
+    description: Option<String>,
+    items: SqlxJson<Vec<PromotionItem>>,
+    remappings: SqlxJson<Vec<Remapping>>,
+    diff: SqlxJson<Vec<ItemDiff>>,
+    approver_group: Option<String>,
+    status: PromotionStatus,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    reviewed_by: Option<String>,
+    reviewed_at: Option<chrono::DateTime<chrono::Utc>>,
+    applied_by: Option<String>,
+    applied_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn remap_path(path: &str, remappings: &[Remapping]) -> String {
+    remappings
+        .iter()
+        .find(|r| r.from == path)
+        .map(|r| r.to.clone())
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn remap_value(value: &mut Value, remappings: &[Remapping]) {
+    match value {
+        Value::String(s) => {
+            for r in remappings {
+                if *s == r.from {
+                    *s = r.to.clone();
+                } else if let Some(prefix) = ["$res:", "$var:"]
+                    .into_iter()
+                    .find(|prefix| s.strip_prefix(prefix) == Some(r.from.as_str()))
+                {
+                    *s = format!("{prefix}{}", r.to);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| remap_value(v, remappings)),
+        Value::Object(map) => map.values_mut().for_each(|v| remap_value(v, remappings)),
+        _ => (),
+    }
+}
+
+/// Script contents only have their quoted and `$res:`/`$var:` references rewritten
+fn remap_content(content: &str, remappings: &[Remapping]) -> String {
+    remappings.iter().fold(content.to_string(), |content, r| {
+        ["\"", "'", "$res:", "$var:"]
+            .iter()
+            .fold(content, |content, delimiter| {
+                let (from, to) = if delimiter.starts_with('$') {
+                    (
+                        format!("{delimiter}{}", r.from),
+                        format!("{delimiter}{}", r.to),
+                    )
+                } else {
+                    (
+                        format!("{delimiter}{}{delimiter}", r.from),
+                        format!("{delimiter}{}{delimiter}", r.to),
+                    )
+                };
+                content.replace(&from, &to)
+            })
+    })
+}
+
+fn remap_files(files: Vec<(String, String)>, remappings: &[Remapping]) -> Vec<(String, String)> {
+    files
+        .into_iter()
+        .map(|(file, content)| {
+            let content = match serde_json::from_str::<Value>(&content) {
+                Ok(mut value) if file.ends_with(".json") => {
+                    remap_value(&mut value, remappings);
+                    serde_json::to_string_pretty(&value).unwrap_or(content)
+                }
+                _ => remap_content(&content, remappings),
+            };
+            (file, content)
+        })
+        .collect()
+}
+
+/// The archive files of an item, as written by the workspace export, named after `file_path`.
+/// Secret values are decrypted.
+pub(crate) async fn item_files<'e, E: sqlx::PgExecutor<'e>>(
+    db: E,
+    w_id: &str,
+    mc: &MagicCrypt256,
+    kind: PromotionKind,
+    path: &str,
+    file_path: &str,
+) -> Result<Vec<(String, String)>> {
+    let files = match kind {
+        PromotionKind::Script => sqlx::query_as::<_, Script>(
+            "SELECT * FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false
+             ORDER BY created_at DESC LIMIT 1",
+        )
+        .bind(path)
+        .bind(w_id)
+        .fetch_optional(db)
+        .await?
+        .map(|mut script| {
+            script.path = file_path.to_string();
+            script_archive_files(script, None).to_vec()
+        }),
+        PromotionKind::Flow => sqlx::query_as::<_, Flow>(
+            "SELECT flow.workspace_id, flow.path, flow.summary, flow.description, flow.archived, flow.extra_perms, flow.draft_only, flow.dedicated_worker, flow.tag, flow.ws_error_handler_muted, flow.timeout, flow.visible_to_runner_only, flow.on_behalf_of_email, flow_version.schema, flow_version.value, flow_version.created_at as edited_at, flow_version.created_by as edited_by
+             FROM flow
+             LEFT JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+             WHERE flow.path = $1 AND flow.workspace_id = $2 AND flow.archived = false",
+        )
+        .bind(path)
+        .bind(w_id)
+        .fetch_optional(db)
+        .await?
+        .map(|flow| to_string_without_metadata(&flow, false, None))
+        .transpose()?
+        .map(|content| vec![(format!("{file_path}.flow.json"), content)]),
+        PromotionKind::App => sqlx::query_as::<_, AppWithLastVersion>(
+            "SELECT app.id, app.path, app.summary, app.versions, app.policy, app.custom_path,
+             app.extra_perms, app_version.value,
+             app_version.created_at, app_version.created_by from app, app_version
+             WHERE app.path = $1 AND app.workspace_id = $2 AND app_version.id = app.versions[array_upper(app.versions, 1)] AND app_version.raw_app IS false",
+        )
+        .bind(path)
+        .bind(w_id)
+        .fetch_optional(db)
+        .await?
+        .map(|app| to_string_without_metadata(&app, false, None))
+        .transpose()?
+        .map(|content| vec![(format!("{file_path}.app.json"), content)]),
+        PromotionKind::Folder => {
+            sqlx::query_as::<_, Folder>("SELECT * FROM folder WHERE name = $1 AND workspace_id = $2")
+                .bind(path)
+                .bind(w_id)
+                .fetch_optional(db)
+                .await?
+                .map(|folder| to_string_without_metadata(&folder, true, None))
+                .transpose()?
+                .map(|content| vec![(format!("f/{file_path}/folder.meta.json"), content)])
+        }
+        PromotionKind::Resource => sqlx::query_as!(
+            Resource,
+            "SELECT * FROM resource WHERE path = $1 AND workspace_id = $2",
+            path,
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|resource| to_string_without_metadata(&resource, false, None))
+        .transpose()?
+        .map(|content| vec![(format!("{file_path}.resource.json"), content)]),
+        PromotionKind::Variable => {
+            let variable = sqlx::query_as::<_, ExportableListableVariable>(
+                "SELECT * FROM variable WHERE path = $1 AND workspace_id = $2 AND expires_at IS NULL",
+            )
+            .bind(path)
+            .bind(w_id)
+            .fetch_optional(db)
+            .await?;
+            match variable {
+                Some(mut variable) => {
+                    if variable.is_secret {
+                        variable.value = variable.value.map(|v| decrypt(mc, v)).transpose()?;
+                    }
+                    let content = to_string_without_metadata(&variable, false, None)?;
+                    Some(vec![(format!("{file_path}.variable.json"), content)])
+                }
+                None => None,
+            }
+        }
+        PromotionKind::Schedule => {
+            sqlx::query_as::<_, Schedule>("SELECT * FROM schedule WHERE path = $1 AND workspace_id = $2")
+                .bind(path)
+                .bind(w_id)
+                .fetch_optional(db)
+                .await?
+                .map(|schedule| to_string_without_metadata(&schedule, false, None))
+                .transpose()?
+                .map(|content| vec![(format!("{file_path}.schedule.json"), content)])
+        }
+        PromotionKind::ResourceType => sqlx::query_as!(
+            ResourceType,
+            "SELECT * FROM resource_type WHERE name = $1 AND workspace_id = $2",
+            path,
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|resource_type| to_string_without_metadata(&resource_type, false, None))
+        .transpose()?
+        .map(|content| vec![(format!("{file_path}.resource-type.json"), content)]),
+    };
+    Ok(files.unwrap_or_default())
+}
+
+/// Files keyed by what follows the item path, e.g. `flow.json`, parsed as JSON when they are
+pub(crate) fn files_value(files: &[(String, String)], file_path: &str) -> Value {
+    Value::Object(
+        files
+            .iter()
+            .map(|(file, content)| {
+                let key = file
+                    .strip_prefix(file_path)
+                    .unwrap_or(file)
+                    .trim_start_matches(['.', '/'])
+                    .to_string();
+                let value = serde_json::from_str(content)
+                    .unwrap_or_else(|_| Value::String(content.clone()));
+                (key, value)
+            })
+            .collect(),
+    )
+}
+
+pub(crate) fn diff_pointers(
+    pointer: String,
+    source: &Value,
+    target: &Value,
+    changes: &mut Vec<String>,
+) {
+    match (source, target) {
+        (Value::Object(source), Value::Object(target)) => {
+            let keys = source.keys().chain(target.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                match (source.get(key), target.get(key)) {
+                    (Some(s), Some(t)) => {
+                        diff_pointers(format!("{pointer}/{escaped}"), s, t, changes)
+                    }
+                    _ => changes.push(format!("{pointer}/{escaped}")),
+                }
+            }
+        }
+        (Value::Array(source), Value::Array(target)) if source.len() == target.len() => {
+            for (i, (s, t)) in source.iter().zip(target.iter()).enumerate() {
+                diff_pointers(format!("{pointer}/{i}"), s, t, changes);
+            }
+        }
+        (source, target) if source != target => changes.push(pointer),
+        _ => (),
+    }
+}
+
+/// The remapped source files of every item, and their diff with the target workspace
+async fn compute_diff(
+    db: &DB,
+    w_id: &str,
+    target_w_id: &str,
+    items: &[PromotionItem],
+    remappings: &[Remapping],
+) -> Result<(BTreeMap<String, String>, Vec<ItemDiff>)> {
+    let source_mc = build_crypt(db, w_id).await?;
+    let target_mc = build_crypt(db, target_w_id).await?;
+    let mut archive_files = BTreeMap::new();
+    let mut diff = vec![];
+    for item in items {
+        let target_path = remap_path(&item.path, remappings);
+        let source_files =
+            item_files(db, w_id, &source_mc, item.kind, &item.path, &target_path).await?;
+        if source_files.is_empty() {
+            return Err(Error::NotFound(format!(
+                "{:?} {} not found in workspace {w_id}",
+                item.kind, item.path
+            )));
+        }
+        let source_files = remap_files(source_files, remappings);
+        let target_files = item_files(
+            db,
+            target_w_id,
+            &target_mc,
+            item.kind,
+            &target_path,
+            &target_path,
+        )
+        .await?;
+
+        let (status, changes) = if target_files.is_empty() {
+            (DiffStatus::Added, vec![])
+        } else {
+            let mut changes = vec![];
+            diff_pointers(
+                String::new(),
+                &files_value(&source_files, &target_path),
+                &files_value(&target_files, &target_path),
+                &mut changes,
+            );
+            let status = if changes.is_empty() {
+                DiffStatus::Unchanged
+            } else {
+                DiffStatus::Modified
+            };
+            (status, changes)
+        };
+        diff.push(ItemDiff {
+            kind: item.kind,
+            path: item.path.clone(),
+            target_path,
+            status,
+            changes,
+        });
+        archive_files.extend(source_files);
+    }
+    Ok((archive_files, diff))
+}
+
+/// Username and admin status of the user in another workspace than the one of the request
+pub(crate) async fn workspace_user(db: &DB, email: &str, w_id: &str) -> Result<(String, bool)> {
+    let user = sqlx::query!(
+        "SELECT username, is_admin FROM usr WHERE email = $1 AND workspace_id = $2 AND disabled = false",
+        email,
+        w_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| {
+        Error::NotAuthorized(format!("{email} is not a member of workspace {w_id}"))
+    })?;
+    Ok((user.username, user.is_admin))
+}
+
+async fn fetch_promotion(db: &DB, w_id: &str, id: i64) -> Result<Promotion> {
+    let promotion = sqlx::query_as!(
+        Promotion,
+        r#"
+        SELECT
+            id,
+            workspace_id,
+            target_workspace_id,
+            description,
+            items AS "items!: _",
+            remappings AS "remappings!: _",
+            diff AS "diff!: _",
+            approver_group,
+            status AS "status!: _",
+            created_by,
+            created_at,
+            reviewed_by,
+            reviewed_at,
+            applied_by,
+            applied_at
+        FROM
+            promotion
+        WHERE
+            id = $1 AND (workspace_id = $2 OR target_workspace_id = $2)
+        "#,
+        id,
+        w_id,
+    )
+    .fetch_optional(db)
+    .await?;
+    not_found_if_none(promotion, "Promotion", id.to_string())
+}
+
+#[derive(Deserialize)]
+struct NewPromotion {
+    target_workspace_id: String,
+    description: Option<String>,
+    items: Vec<PromotionItem>,
+    #[serde(default)]
+    remappings: Vec<Remapping>,
+    /// group of the target workspace whose members can approve the promotion, target
+    /// workspace admins otherwise
+    approver_group: Option<String>,
+}
+
+async fn create_promotion(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(np): Json<NewPromotion>,
+) -> Result<(StatusCode, String)> {
+    require_admin(authed.is_admin, &authed.username)?;
+    if np.target_workspace_id == w_id {
+        return Err(Error::BadRequest(
+            "A workspace cannot be promoted to itself".to_string(),
+        ));
+    }
+    if np.items.is_empty() {
+        return Err(Error::BadRequest(
+            "A promotion needs at least one item".to_string(),
+        ));
+    }
+    workspace_user(&db, &authed.email, &np.target_workspace_id).await?;
+    if let Some(group) = np.approver_group.as_ref() {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM group_ WHERE name = $1 AND workspace_id = $2)",
+            group,
+            np.target_workspace_id
+        )
+        .fetch_one(&db)
+        .await?
+        .unwrap_or(false);
+        if !exists {
+            return Err(Error::BadRequest(format!(
+                "Group {group} does not exist in workspace {}",
+                np.target_workspace_id
+            )));
+        }
+    }
+
+    let (_, diff) = compute_diff(
+        &db,
+        &w_id,
+        &np.target_workspace_id,
+        &np.items,
+        &np.remappings,
+    )
+    .await?;
+
+    let mut tx = db.begin().await?;
+    let id = sqlx::query_scalar!(
+        "INSERT INTO promotion (workspace_id, target_workspace_id, description, items, remappings, diff, approver_group, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        w_id,
+        np.target_workspace_id,
+        np.description,
+        SqlxJson(&np.items) as SqlxJson<&Vec<PromotionItem>>,
+        SqlxJson(&np.remappings) as SqlxJson<&Vec<Remapping>>,
+        SqlxJson(&diff) as SqlxJson<&Vec<ItemDiff>>,
+        np.approver_group,
+        authed.email,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "promotions.create",
+        ActionKind::Create,
+        &w_id,
+        Some(&id.to_string()),
+        Some(HashMap::from([(
+            "target_workspace_id",
+            np.target_workspace_id.as_str(),
+        )])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, id.to_string()))
+}
+
+#[derive(Deserialize)]
+struct ListPromotionsQuery {
+    status: Option<PromotionStatus>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+/// Promotions from and to the workspace
+async fn list_promotions(
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<ListPromotionsQuery>,
+) -> JsonResult<Vec<Promotion>> {
+    let (per_page, offset) = paginate(Pagination { page: query.page, per_page: query.per_page });
+
+    let promotions = sqlx::query_as!(
+        Promotion,
+        r#"
+        SELECT
+            id,
+            workspace_id,
+            target_workspace_id,
+            description,
+            items AS "items!: _",
+            remappings AS "remappings!: _",
+            diff AS "diff!: _",
+            approver_group,
+            status AS "status!: _",
+            created_by,
+            created_at,
+            reviewed_by,
+            reviewed_at,
+            applied_by,
+            applied_at
+        FROM
+            promotion
+        WHERE
+            (workspace_id = $1 OR target_workspace_id = $1)
+            AND ($2::PROMOTION_STATUS IS NULL OR status = $2)
+        ORDER BY
+            id DESC
+        OFFSET $3
+        LIMIT $4
+        "#,
+        w_id,
+        query.status as Option<PromotionStatus>,
+        offset as i64,
+        per_page as i64,
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(promotions))
+}
+
+async fn get_promotion(
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> JsonResult<Promotion> {
+    Ok(Json(fetch_promotion(&db, &w_id, id).await?))
+}
+
+/// Members of the approver group can review a promotion they did not create, target workspace
+/// admins can review any promotion without approver group
+async fn review_promotion(
+    authed: ApiAuthed,
+    db: DB,
+    w_id: String,
+    id: i64,
+    status: PromotionStatus,
+) -> Result<String> {
+    let promotion = fetch_promotion(&db, &w_id, id).await?;
+    if promotion.status != PromotionStatus::Pending {
+        return Err(Error::BadRequest(format!(
+            "Promotion {id} is {:?}, only pending promotions can be reviewed",
+            promotion.status
+        )));
+    }
+    let (username, is_admin) =
+        workspace_user(&db, &authed.email, &promotion.target_workspace_id).await?;
+    match promotion.approver_group.as_ref() {
+        Some(group) => {
+            let is_member = sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM usr_to_group WHERE usr = $1 AND group_ = $2 AND workspace_id = $3)",
+                username,
+                group,
+                promotion.target_workspace_id
+            )
+            .fetch_one(&db)
+            .await?
+            .unwrap_or(false);
+            if !is_member {
+                return Err(Error::NotAuthorized(format!(
+                    "Only members of group {group} can review this promotion"
+                )));
+            }
+            if promotion.created_by == authed.email {
+                return Err(Error::NotAuthorized(
+                    "A promotion cannot be reviewed by its creator".to_string(),
+                ));
+            }
+        }
+        None => require_admin(is_admin, &username)?,
+    }
+
+    let mut tx = db.begin().await?;
+    let updated = sqlx::query_scalar!(
+        "UPDATE promotion SET status = $1, reviewed_by = $2, reviewed_at = now()
+         WHERE id = $3 AND status = 'pending' RETURNING id",
+        status as PromotionStatus,
+        authed.email,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if updated.is_none() {
+        return Err(Error::BadRequest(format!(
+            "Promotion {id} was reviewed concurrently"
+        )));
+    }
+    audit_log(
+        &mut *tx,
+        &authed,
+        if status == PromotionStatus::Approved {
+            "promotions.approve"
+        } else {
+            "promotions.reject"
+        },
+        ActionKind::Update,
+        &promotion.target_workspace_id,
+        Some(&id.to_string()),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Promotion {id} {status:?}").to_lowercase())
+}
+
+async fn approve_promotion(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> Result<String> {
+    review_promotion(authed, db, w_id, id, PromotionStatus::Approved).await
+}
+
+async fn reject_promotion(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> Result<String> {
+    review_promotion(authed, db, w_id, id, PromotionStatus::Rejected).await
+}
+
+/// Applies an approved promotion if its diff is still the one that was approved. Otherwise, the
+/// new diff is saved and the promotion goes back to pending.
+async fn apply_promotion(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> JsonResult<ImportReport> {
+    let promotion = fetch_promotion(&db, &w_id, id).await?;
+    let (username, is_admin) =
+        workspace_user(&db, &authed.email, &promotion.target_workspace_id).await?;
+    require_admin(is_admin, &username)?;
+
+    let mut tx = db.begin().await?;
+    let status = sqlx::query_scalar!(
+        r#"SELECT status AS "status: PromotionStatus" FROM promotion WHERE id = $1 FOR UPDATE"#,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if status != PromotionStatus::Approved {
+        return Err(Error::BadRequest(format!(
+            "Promotion {id} is {status:?}, only approved promotions can be applied"
+        )));
+    }
+
+    let (files, diff) = compute_diff(
+        &db,
+        &promotion.workspace_id,
+        &promotion.target_workspace_id,
+        &promotion.items,
+        &promotion.remappings,
+    )
+    .await?;
+    if diff != promotion.diff.0 {
+        sqlx::query!(
+            "UPDATE promotion SET diff = $1, status = 'pending', reviewed_by = NULL, reviewed_at = NULL
+             WHERE id = $2",
+            SqlxJson(&diff) as SqlxJson<&Vec<ItemDiff>>,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        return Err(Error::BadRequest(format!(
+            "The diff of promotion {id} changed since it was approved, it needs to be reviewed again"
+        )));
+    }
+
+    let target_authed = ApiAuthed { username, is_admin, ..authed.clone() };
+    let options = ImportOptions {
+        on_conflict: ConflictStrategy::Overwrite,
+        plain_secrets: true,
+        import_settings: false,
+    };
+    let (mut tx, report) = import_archive(
+        &db,
+        &target_authed,
+        tx,
+        &promotion.target_workspace_id,
+        ImportArchive::parse(files, false)?,
+        &options,
+    )
+    .await?;
+
+    sqlx::query!(
+        "UPDATE promotion SET status = 'applied', applied_by = $1, applied_at = now() WHERE id = $2",
+        authed.email,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit_log(
+        &mut *tx,
+        &target_authed,
+        "promotions.apply",
+        ActionKind::Execute,
+        &promotion.target_workspace_id,
+        Some(&id.to_string()),
+        Some(HashMap::from([(
+            "source_workspace_id",
+            promotion.workspace_id.as_str(),
+        )])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(report))
+}
+
+async fn delete_promotion(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let mut tx = db.begin().await?;
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM promotion WHERE id = $1 AND workspace_id = $2 RETURNING id",
+        id,
+        w_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(deleted, "Promotion", id.to_string())?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "promotions.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(&id.to_string()),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Promotion {id} deleted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn remappings() -> Vec<Remapping> {
+        vec![Remapping { from: "u/dev/db".to_string(), to: "f/prod/db".to_string() }]
+    }
+
+    #[test]
+    fn test_remap_path() {
+        assert_eq!(remap_path("u/dev/db", &remappings()), "f/prod/db");
+        assert_eq!(remap_path("u/dev/db2", &remappings()), "u/dev/db2");
+    }
+
+    #[test]
+    fn test_remap_value() {
+        let mut value = json!({
+            "resource": "$res:u/dev/db",
+            "variable": "$var:u/dev/db",
+            "path": "u/dev/db",
+            "nested": [{ "path": "u/dev/db" }, "u/dev/db/other"],
+            "count": 1
+        });
+        remap_value(&mut value, &remappings());
+        assert_eq!(
+            value,
+            json!({
+                "resource": "$res:f/prod/db",
+                "variable": "$var:f/prod/db",
+                "path": "f/prod/db",
+                "nested": [{ "path": "f/prod/db" }, "u/dev/db/other"],
+                "count": 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_remap_content() {
+        let content = r#"const a = wmill.getResource("u/dev/db");
+const b = wmill.getVariable('u/dev/db');
+// $res:u/dev/db, but not u/dev/db on its own or "u/dev/db2""#;
+        assert_eq!(
+            remap_content(content, &remappings()),
+            r#"const a = wmill.getResource("f/prod/db");
+const b = wmill.getVariable('f/prod/db');
+// $res:f/prod/db, but not u/dev/db on its own or "u/dev/db2""#
+        );
+    }
+
+    #[test]
+    fn test_remap_files() {
+        let files = vec![
+            (
+                "f/prod/flow.flow.json".to_string(),
+                r#"{"value": {"db": "$res:u/dev/db"}}"#.to_string(),
+            ),
+            (
+                "f/prod/script.py".to_string(),
+                r#"db = get_resource("u/dev/db")"#.to_string(),
+            ),
+        ];
+        let files = remap_files(files, &remappings());
+        assert_eq!(
+            serde_json::from_str::<Value>(&files[0].1).unwrap(),
+            json!({"value": {"db": "$res:f/prod/db"}})
+        );
+        assert_eq!(files[1].1, r#"db = get_resource("f/prod/db")"#);
+    }
+
+    #[test]
+    fn test_files_value() {
+        let files = vec![
+            (
+                "f/prod/script.script.json".to_string(),
+                r#"{"summary": "hello"}"#.to_string(),
+            ),
+            (
+                "f/prod/script.py".to_string(),
+                "def main(): pass".to_string(),
+            ),
+            (
+                "f/prod/folder.meta.json".to_string(),
+                r#"{"owners": []}"#.to_string(),
+            ),
+        ];
+        assert_eq!(
+            files_value(&files, "f/prod/script"),
+            json!({
+                "script.json": {"summary": "hello"},
+                "py": "def main(): pass",
+                "f/prod/folder.meta.json": {"owners": []}
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff_pointers() {
+        let source = json!({
+            "summary": "new",
+            "value": {"modules": [{"id": "a"}, {"id": "b"}], "a/b": 1, "a~b": 1},
+            "schema": {"required": ["x"]},
+            "added": true,
+            "same": {"x": [1, 2]}
+        });
+        let target = json!({
+            "summary": "old",
+            "value": {"modules": [{"id": "a"}, {"id": "c"}], "a/b": 2, "a~b": 2},
+            "schema": {"required": ["x", "y"]},
+            "removed": true,
+            "same": {"x": [1, 2]}
+        });
+        let mut changes = vec![];
+        diff_pointers(String::new(), &source, &target, &mut changes);
+        assert_eq!(
+            changes,
+            vec![
+                "/added",
+                "/removed",
+                "/schema/required",
+                "/summary",
+                "/value/a~1b",
+                "/value/a~0b",
+                "/value/modules/1/id",
+            ]
+        );
+
+        let mut changes = vec![];
+        diff_pointers(String::new(), &source, &source, &mut changes);
+        assert!(changes.is_empty());
+    }
+}
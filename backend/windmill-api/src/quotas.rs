@@ -0,0 +1,171 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use axum::{
+    extract::Path,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    error::{Error, JsonResult, Result},
+    quotas::{
+        get_workspace_consumption, invalidate_workspace_quotas, QuotaConsumption, WorkspaceQuotas,
+    },
+    utils::require_admin,
+};
+
+use crate::{
+    db::{ApiAuthed, DB},
+    utils::require_super_admin,
+};
+
+pub fn workspaced_service() -> Router {
+    let router = Router::new()
+        .route("/get", get(get_quotas))
+        .route("/update", post(update_quotas));
+
+    #[cfg(feature = "s3_trigger")]
+    let router = router.route("/refresh_object_storage", post(refresh_object_storage));
+
+    router
+}
+
+pub fn global_service() -> Router {
+    Router::new().route("/list", get(list_quotas))
+}
+
+#[derive(Serialize)]
+struct WorkspaceQuotaConsumption {
+    workspace_id: String,
+    quotas: Vec<QuotaConsumption>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object_storage_measured_at: Option<DateTime<Utc>>,
+}
+
+async fn get_workspace_quota_consumption(db: &DB, w_id: &str) -> Result<WorkspaceQuotaConsumption> {
+    let object_storage_measured_at = sqlx::query_scalar!(
+        "SELECT object_storage_measured_at FROM workspace_quota WHERE workspace_id = $1",
+        w_id
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten();
+    Ok(WorkspaceQuotaConsumption {
+        workspace_id: w_id.to_string(),
+        quotas: get_workspace_consumption(db, w_id).await?,
+        object_storage_measured_at,
+    })
+}
+
+async fn get_quotas(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<WorkspaceQuotaConsumption> {
+    require_admin(authed.is_admin, &authed.username)?;
+    Ok(Json(get_workspace_quota_consumption(&db, &w_id).await?))
+}
+
+/// Quotas are set by the superadmins only, as they govern what the workspace may take from the
+/// other workspaces of the instance
+async fn update_quotas(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(quotas): Json<WorkspaceQuotas>,
+) -> Result<String> {
+    require_super_admin(&db, &authed.email).await?;
+
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "INSERT INTO workspace_quota (workspace_id, quotas, updated_by, updated_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (workspace_id) DO UPDATE
+        SET quotas = EXCLUDED.quotas, updated_by = EXCLUDED.updated_by, updated_at = now()",
+        w_id,
+        serde_json::to_value(&quotas).map_err(|e| Error::internal_err(e.to_string()))?,
+        authed.email
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.update_quotas",
+        ActionKind::Update,
+        &w_id,
+        Some(&w_id),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    invalidate_workspace_quotas(&w_id);
+    Ok(format!("Updated quotas of workspace {w_id}"))
+}
+
+/// Measures the size of the objects of the primary storage of the workspace, which is what the
+/// object storage quota is checked against until the next measure
+#[cfg(feature = "s3_trigger")]
+async fn refresh_object_storage(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<windmill_common::db::UserDB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<i64> {
+    use futures::TryStreamExt;
+
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let store =
+        crate::s3_triggers::get_workspace_object_store(&authed, user_db, &db, &w_id, None).await?;
+    let size_f = store
+        .list(None)
+        .try_fold(0i64, |acc, meta| async move { Ok(acc + meta.size as i64) });
+    let size = tokio::time::timeout(std::time::Duration::from_secs(300), size_f)
+        .await
+        .map_err(|_| {
+            Error::BadConfig(
+                "Timeout occurred while measuring the object storage after 300 seconds".to_string(),
+            )
+        })?
+        .map_err(|e| Error::BadConfig(format!("Error listing the objects of the storage: {e}")))?;
+
+    sqlx::query!(
+        "INSERT INTO workspace_quota (workspace_id, object_storage_bytes, object_storage_measured_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (workspace_id) DO UPDATE
+        SET object_storage_bytes = EXCLUDED.object_storage_bytes, object_storage_measured_at = now()",
+        w_id,
+        size
+    )
+    .execute(&db)
+    .await?;
+    Ok(Json(size))
+}
+
+/// Consumption of the workspaces that have quotas
+async fn list_quotas(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+) -> JsonResult<Vec<WorkspaceQuotaConsumption>> {
+    require_super_admin(&db, &authed.email).await?;
+
+    let workspaces =
+        sqlx::query_scalar!("SELECT workspace_id FROM workspace_quota ORDER BY workspace_id")
+            .fetch_all(&db)
+            .await?;
+    let mut consumption = vec![];
+    for w_id in workspaces {
+        consumption.push(get_workspace_quota_consumption(&db, &w_id).await?);
+    }
+    Ok(Json(consumption))
+}
@@ -0,0 +1,460 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use axum::{
+    extract::{ConnectInfo, Path, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use http::HeaderMap;
+use quick_cache::sync::Cache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use windmill_common::{
+    error::{Error, Result},
+    DB,
+};
+
+use crate::{db::ApiAuthed, utils::ExpiringCacheEntry};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RateLimitPer {
+    /// a single counter shared by all the callers
+    Route,
+    /// one counter per token (api key) or per user if not called with a token
+    Principal,
+    /// one counter per client ip, see `RATE_LIMIT_TRUSTED_PROXY_HOPS`
+    Ip,
+    /// one counter per value of the given request header
+    Header { name: String },
+}
+
+impl Default for RateLimitPer {
+    fn default() -> Self {
+        Self::Route
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub window_s: u32,
+    #[serde(default)]
+    pub per: RateLimitPer,
+}
+
+impl RateLimit {
+    fn counter_key(&self, scope: &str, caller: &RateLimitCaller) -> String {
+        let (kind, value) = match &self.per {
+            RateLimitPer::Route => ("route", String::new()),
+            RateLimitPer::Principal => ("principal", caller.principal.clone()),
+            RateLimitPer::Ip => ("ip", caller.ip.clone().unwrap_or_default()),
+            // the value is hashed as it is chosen by the caller and can be of any length
+            RateLimitPer::Header { name } => (
+                "header",
+                format!(
+                    "{}={}",
+                    name.to_lowercase(),
+                    caller
+                        .headers
+                        .get(name.as_str())
+                        .map(|v| hex::encode(Sha256::digest(v.as_bytes())))
+                        .unwrap_or_default()
+                ),
+            ),
+        };
+        format!("{scope}:{}s:{kind}:{value}", self.window_s)
+    }
+}
+
+/// Rate limit of the webhooks of a workspace, applied to all of them or only to the ones of a
+/// script or flow
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebhookRateLimit {
+    #[serde(flatten)]
+    pub rate_limit: RateLimit,
+    /// path of the script or flow, webhooks called by hash are only limited by the rate limits
+    /// without a path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+pub fn validate_webhook_rate_limits(rate_limits: &[WebhookRateLimit]) -> Result<()> {
+    if rate_limits
+        .iter()
+        .any(|rate_limit| rate_limit.path.as_deref().is_some_and(str::is_empty))
+    {
+        return Err(Error::BadRequest(
+            "Webhook rate limits must have a non empty path or no path".to_string(),
+        ));
+    }
+    validate_rate_limits(
+        &rate_limits
+            .iter()
+            .map(|rate_limit| rate_limit.rate_limit.clone())
+            .collect::<Vec<_>>(),
+    )
+}
+
+pub fn validate_rate_limits(rate_limits: &[RateLimit]) -> Result<()> {
+    for rate_limit in rate_limits {
+        if rate_limit.max_requests == 0 || rate_limit.window_s == 0 {
+            return Err(Error::BadRequest(
+                "Rate limits must allow at least one request over a window of at least one second"
+                    .to_string(),
+            ));
+        }
+        if let RateLimitPer::Header { name } = &rate_limit.per {
+            if http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(Error::BadRequest(format!(
+                    "Invalid header name for rate limit: {name}"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub struct RateLimitCaller<'a> {
+    pub principal: String,
+    pub ip: Option<String>,
+    pub headers: &'a HeaderMap,
+}
+
+impl<'a> RateLimitCaller<'a> {
+    pub fn new(authed: &ApiAuthed, headers: &'a HeaderMap, peer: Option<SocketAddr>) -> Self {
+        let principal = authed
+            .token_prefix
+            .as_ref()
+            .map(|prefix| format!("token/{prefix}"))
+            .unwrap_or_else(|| format!("user/{}", authed.username));
+        if *RATE_LIMIT_TRUSTED_PROXY_HOPS == 0
+            && headers.contains_key("x-forwarded-for")
+            && !FORWARDED_FOR_WARNED.swap(true, Ordering::Relaxed)
+        {
+            tracing::warn!(
+                "Request received with x-forwarded-for while RATE_LIMIT_TRUSTED_PROXY_HOPS is not set, rate limits per ip use the address of the peer which is the one of the proxy. Set RATE_LIMIT_TRUSTED_PROXY_HOPS to the number of proxies in front of the servers."
+            );
+        }
+        let ip = client_ip(headers, peer, *RATE_LIMIT_TRUSTED_PROXY_HOPS);
+        Self { principal, ip, headers }
+    }
+}
+
+/// Each trusted proxy appends the address of its peer to `x-forwarded-for`, so the client is the
+/// address appended by the first of them, `trusted_hops` entries from the end. The entries before
+/// it are set by the client and cannot be trusted, nor can the header at all without a proxy.
+fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>, trusted_hops: usize) -> Option<String> {
+    if trusted_hops == 0 {
+        return peer.map(|peer| peer.ip().to_string());
+    }
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|ip| ip.trim())
+        .rev()
+        .nth(trusted_hops - 1)
+        .filter(|ip| !ip.is_empty())
+        .map(|ip| ip.to_string())
+}
+
+/// Increments the counters of all the rate limits and fails if one of them is exceeded.
+/// Counters are fixed windows stored in the database so that they are shared by all the api servers.
+/// The expired window of the counter is deleted when the next one starts, the counters that are
+/// not hit again are deleted by the monitor.
+pub async fn check_rate_limits(
+    db: &DB,
+    w_id: &str,
+    scope: &str,
+    rate_limits: &[RateLimit],
+    caller: &RateLimitCaller<'_>,
+) -> Result<()> {
+    for rate_limit in rate_limits {
+        let key = rate_limit.counter_key(scope, caller);
+        let counter = sqlx::query!(
+            r#"
+            WITH expired AS (
+                DELETE FROM rate_limit_counter
+                WHERE workspace_id = $1 AND key = $2 AND window_end <= now()
+            )
+            INSERT INTO rate_limit_counter (workspace_id, key, window_start, window_end, count)
+            SELECT $1, $2, w.start, w.start + make_interval(secs => $3), 1
+            FROM (SELECT to_timestamp(floor(extract(epoch FROM now()) / $3) * $3) AS start) w
+            ON CONFLICT (workspace_id, key, window_start)
+            DO UPDATE SET count = rate_limit_counter.count + 1
+            RETURNING count, ceil(extract(epoch FROM window_end - now()))::bigint AS "retry_after_s!"
+            "#,
+            w_id,
+            key,
+            rate_limit.window_s as f64,
+        )
+        .fetch_one(db)
+        .await?;
+
+        if counter.count > rate_limit.max_requests as i32 {
+            return Err(Error::TooManyRequests {
+                message: format!(
+                    "rate limit of {} requests per {}s exceeded for {scope}",
+                    rate_limit.max_requests, rate_limit.window_s
+                ),
+                retry_after_s: counter.retry_after_s.max(1) as u64,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+lazy_static::lazy_static! {
+    /// Number of reverse proxies in front of the api servers that append to `x-forwarded-for`.
+    /// Without any, the client ip is the address of the peer and the header is ignored, as it
+    /// could be set by the client. It is not set by default for that reason, a warning is logged
+    /// once if the header is received without it.
+    static ref RATE_LIMIT_TRUSTED_PROXY_HOPS: usize = std::env::var("RATE_LIMIT_TRUSTED_PROXY_HOPS")
+        .ok()
+        .and_then(|x| x.parse::<usize>().ok())
+        .unwrap_or(0);
+    static ref WEBHOOK_RATE_LIMITS_CACHE: Cache<String, ExpiringCacheEntry<Vec<WebhookRateLimit>>> = Cache::new(1000);
+}
+
+static FORWARDED_FOR_WARNED: AtomicBool = AtomicBool::new(false);
+
+async fn get_webhook_rate_limits(db: &DB, w_id: &str) -> Result<Vec<WebhookRateLimit>> {
+    match WEBHOOK_RATE_LIMITS_CACHE.get(w_id) {
+        Some(cache_entry) if cache_entry.expiry > std::time::Instant::now() => {
+            Ok(cache_entry.value)
+        }
+        _ => {
+            let rate_limits = sqlx::query_scalar!(
+                r#"SELECT webhook_rate_limits AS "webhook_rate_limits: sqlx::types::Json<Vec<WebhookRateLimit>>" FROM workspace_settings WHERE workspace_id = $1"#,
+                w_id
+            )
+            .fetch_optional(db)
+            .await?
+            .flatten()
+            .map(|rate_limits| rate_limits.0)
+            .unwrap_or_default();
+
+            WEBHOOK_RATE_LIMITS_CACHE.insert(
+                w_id.to_string(),
+                ExpiringCacheEntry {
+                    value: rate_limits.clone(),
+                    expiry: std::time::Instant::now() + std::time::Duration::from_secs(10),
+                },
+            );
+
+            Ok(rate_limits)
+        }
+    }
+}
+
+/// Middleware for the webhook endpoints that run scripts and flows, enforcing the
+/// rate limits set in the workspace settings before any job is pushed. The rate limits of a
+/// script or flow are counted separately from the ones applied to all the webhooks.
+pub async fn webhook_rate_limit(
+    Extension(db): Extension<DB>,
+    Path(params): Path<HashMap<String, String>>,
+    authed: ApiAuthed,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(w_id) = params.get("workspace_id") else {
+        return next.run(request).await;
+    };
+
+    let rate_limits = match get_webhook_rate_limits(&db, w_id).await {
+        Ok(rate_limits) => rate_limits,
+        Err(e) => return e.into_response(),
+    };
+
+    if !rate_limits.is_empty() {
+        let runnable = params
+            .get("script_path")
+            .or_else(|| params.get("hash"))
+            .map(|s| s.trim_start_matches('/'))
+            .unwrap_or_default();
+        let (runnable_rate_limits, workspace_rate_limits) =
+            split_webhook_rate_limits(rate_limits, params.get("script_path").map(|_| runnable));
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(peer)| *peer);
+        let caller = RateLimitCaller::new(&authed, request.headers(), peer);
+
+        for (scope, rate_limits) in [
+            (format!("webhook/{runnable}"), workspace_rate_limits),
+            (format!("webhook_path/{runnable}"), runnable_rate_limits),
+        ] {
+            if let Err(e) = check_rate_limits(&db, w_id, &scope, &rate_limits, &caller).await {
+                return e.into_response();
+            }
+        }
+    }
+
+    next.run(request).await
+}
+
+/// The rate limits of the script or flow at `path`, and the ones applied to all the webhooks
+fn split_webhook_rate_limits(
+    rate_limits: Vec<WebhookRateLimit>,
+    path: Option<&str>,
+) -> (Vec<RateLimit>, Vec<RateLimit>) {
+    let mut runnable_rate_limits = vec![];
+    let mut workspace_rate_limits = vec![];
+    for WebhookRateLimit { rate_limit, path: limited_path } in rate_limits {
+        match limited_path {
+            None => workspace_rate_limits.push(rate_limit),
+            Some(limited_path) if Some(limited_path.as_str()) == path => {
+                runnable_rate_limits.push(rate_limit)
+            }
+            Some(_) => {}
+        }
+    }
+    (runnable_rate_limits, workspace_rate_limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate_limit(per: RateLimitPer) -> RateLimit {
+        RateLimit { max_requests: 10, window_s: 60, per }
+    }
+
+    fn headers(entries: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_counter_key() {
+        let headers = headers(&[("x-tenant", "acme")]);
+        let caller = RateLimitCaller {
+            principal: "user/alice".to_string(),
+            ip: Some("10.0.0.1".to_string()),
+            headers: &headers,
+        };
+        let key = |per| rate_limit(per).counter_key("ws:u/alice/hook", &caller);
+
+        assert_eq!(key(RateLimitPer::Route), "ws:u/alice/hook:60s:route:");
+        assert_eq!(
+            key(RateLimitPer::Principal),
+            "ws:u/alice/hook:60s:principal:user/alice"
+        );
+        assert_eq!(key(RateLimitPer::Ip), "ws:u/alice/hook:60s:ip:10.0.0.1");
+        assert_eq!(
+            key(RateLimitPer::Header { name: "X-Tenant".to_string() }),
+            format!(
+                "ws:u/alice/hook:60s:header:x-tenant={}",
+                hex::encode(Sha256::digest(b"acme"))
+            )
+        );
+        assert_eq!(
+            key(RateLimitPer::Header { name: "x-missing".to_string() }),
+            "ws:u/alice/hook:60s:header:x-missing="
+        );
+    }
+
+    #[test]
+    fn test_validate_rate_limits() {
+        assert!(validate_rate_limits(&[
+            rate_limit(RateLimitPer::Route),
+            rate_limit(RateLimitPer::Header { name: "x-tenant".to_string() }),
+        ])
+        .is_ok());
+        assert!(validate_rate_limits(&[RateLimit {
+            max_requests: 0,
+            ..rate_limit(RateLimitPer::Route)
+        }])
+        .is_err());
+        assert!(validate_rate_limits(&[RateLimit {
+            window_s: 0,
+            ..rate_limit(RateLimitPer::Route)
+        }])
+        .is_err());
+        assert!(validate_rate_limits(&[rate_limit(RateLimitPer::Header {
+            name: "not a header".to_string()
+        })])
+        .is_err());
+    }
+
+    #[test]
+    fn test_split_webhook_rate_limits() {
+        let webhook_rate_limit = |max_requests, path: Option<&str>| WebhookRateLimit {
+            rate_limit: RateLimit { max_requests, ..rate_limit(RateLimitPer::Route) },
+            path: path.map(str::to_string),
+        };
+        let rate_limits = vec![
+            webhook_rate_limit(1, None),
+            webhook_rate_limit(2, Some("f/team/hook")),
+            webhook_rate_limit(3, Some("f/team/other")),
+        ];
+        let max_requests = |rate_limits: Vec<RateLimit>| {
+            rate_limits
+                .into_iter()
+                .map(|rate_limit| rate_limit.max_requests)
+                .collect::<Vec<_>>()
+        };
+
+        let (runnable, workspace) =
+            split_webhook_rate_limits(rate_limits.clone(), Some("f/team/hook"));
+        assert_eq!(max_requests(runnable), vec![2]);
+        assert_eq!(max_requests(workspace), vec![1]);
+
+        // webhooks called by hash are only limited by the rate limits without a path
+        let (runnable, workspace) = split_webhook_rate_limits(rate_limits, None);
+        assert!(runnable.is_empty());
+        assert_eq!(max_requests(workspace), vec![1]);
+
+        let parsed: WebhookRateLimit = serde_json::from_value(serde_json::json!({
+            "max_requests": 5,
+            "window_s": 60,
+            "per": { "kind": "ip" },
+            "path": "f/team/hook"
+        }))
+        .unwrap();
+        assert_eq!(parsed.path.as_deref(), Some("f/team/hook"));
+        assert_eq!(parsed.rate_limit.per, RateLimitPer::Ip);
+        assert!(validate_webhook_rate_limits(&[parsed]).is_ok());
+        assert!(validate_webhook_rate_limits(&[webhook_rate_limit(1, Some(""))]).is_err());
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let peer = Some("192.168.1.5:4321".parse().unwrap());
+        let forwarded = headers(&[
+            ("x-forwarded-for", "1.1.1.1, 2.2.2.2"),
+            ("x-forwarded-for", "3.3.3.3"),
+        ]);
+
+        // without a trusted proxy the header is set by the client
+        assert_eq!(
+            client_ip(&forwarded, peer, 0).as_deref(),
+            Some("192.168.1.5")
+        );
+        assert_eq!(client_ip(&forwarded, None, 0), None);
+        assert_eq!(client_ip(&forwarded, peer, 1).as_deref(), Some("3.3.3.3"));
+        assert_eq!(client_ip(&forwarded, peer, 2).as_deref(), Some("2.2.2.2"));
+        // fewer entries than trusted proxies, the request did not go through all of them
+        assert_eq!(client_ip(&forwarded, peer, 4), None);
+        assert_eq!(client_ip(&HeaderMap::new(), peer, 1), None);
+    }
+}
@@ -0,0 +1,600 @@
+use crate::{
+    capture::RedisTriggerConfig,
+    db::{ApiAuthed, DB},
+    resources::try_get_resource_from_db_as,
+    trigger_helpers::TriggerJobArgs,
+    triggers::{
+        handler::{trigger_routes, PgQuery, Trigger, TriggerCrud},
+        listener::{trigger_job, CaptureConfigForListener, Listener, ListeningConfig},
+    },
+};
+use windmill_git_sync::DeployedObject;
+
+use axum::{async_trait, extract::Path, routing::post, Extension, Json, Router};
+use base64::{engine, prelude::*};
+use redis::{
+    aio::MultiplexedConnection,
+    streams::{
+        StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamReadOptions, StreamReadReply,
+    },
+    AsyncCommands,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Map, Value};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use tokio::time::{Duration, Instant};
+use windmill_common::{
+    db::UserDB, error, triggers::TriggerKind, utils::report_critical_error, worker::to_raw_value,
+    INSTANCE_NAME,
+};
+
+pub fn workspaced_service() -> Router {
+    trigger_routes::<RedisTriggerHandler>().route("/test", post(test_redis_connection))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Common(#[from] windmill_common::error::Error),
+    #[error("{0}")]
+    Redis(#[from] redis::RedisError),
+}
+
+/// How long `XREADGROUP` blocks waiting for new entries
+const READ_BLOCK_MS: usize = 5000;
+/// Interval at which the pending entries of dead consumers are reclaimed
+const AUTOCLAIM_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_START_ID: &str = "$";
+const DEFAULT_BATCH_SIZE: i32 = 10;
+const DEFAULT_MIN_IDLE_MS: i64 = 60_000;
+
+/// Name of the consumer of this server in the consumer groups. Entries delivered to it and not
+/// acknowledged are reclaimed by the other servers once idle for `min_idle_ms`.
+fn consumer_name() -> String {
+    format!("windmill-{}", *INSTANCE_NAME)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedisResource {
+    host: String,
+    port: Option<u16>,
+    username: Option<String>,
+    password: Option<String>,
+    db: Option<i64>,
+    tls: Option<bool>,
+}
+
+impl RedisResource {
+    async fn connect(&self) -> Result<MultiplexedConnection, Error> {
+        let credentials = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => format!(
+                "{}:{}@",
+                urlencoding::encode(username),
+                urlencoding::encode(password)
+            ),
+            (None, Some(password)) => format!(":{}@", urlencoding::encode(password)),
+            (Some(username), None) => format!("{}@", urlencoding::encode(username)),
+            (None, None) => String::new(),
+        };
+        let url = format!(
+            "{}://{}{}:{}/{}",
+            if self.tls.unwrap_or(false) {
+                "rediss"
+            } else {
+                "redis"
+            },
+            credentials,
+            self.host,
+            self.port.unwrap_or(6379),
+            self.db.unwrap_or(0)
+        );
+
+        let client = redis::Client::open(url)?;
+        Ok(client.get_multiplexed_async_connection().await?)
+    }
+}
+
+/// Creates the consumer group on each stream, and the streams that do not exist yet
+async fn create_consumer_groups(
+    conn: &mut MultiplexedConnection,
+    streams: &[String],
+    consumer_group: &str,
+    start_id: &str,
+) -> Result<(), Error> {
+    for stream in streams {
+        let created: redis::RedisResult<()> = conn
+            .xgroup_create_mkstream(stream, consumer_group, start_id)
+            .await;
+        match created {
+            Ok(()) => {}
+            Err(err) if err.code() == Some("BUSYGROUP") => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+fn redis_value_to_json(value: &redis::Value) -> Value {
+    match redis::from_redis_value::<Vec<u8>>(value) {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(string) => Value::String(string),
+            Err(err) => Value::String(engine::general_purpose::STANDARD.encode(err.into_bytes())),
+        },
+        Err(_) => Value::Null,
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RedisMessage {
+    stream: String,
+    id: String,
+    fields: Map<String, Value>,
+}
+
+impl RedisMessage {
+    fn new(stream: &str, entry: &StreamId) -> Self {
+        Self {
+            stream: stream.to_string(),
+            id: entry.id.clone(),
+            fields: entry
+                .map
+                .iter()
+                .map(|(field, value)| (field.clone(), redis_value_to_json(value)))
+                .collect(),
+        }
+    }
+}
+
+fn default_start_id() -> String {
+    DEFAULT_START_ID.to_string()
+}
+
+fn default_batch_size() -> i32 {
+    DEFAULT_BATCH_SIZE
+}
+
+fn default_min_idle_ms() -> i64 {
+    DEFAULT_MIN_IDLE_MS
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct RedisConfig {
+    pub redis_resource_path: String,
+    pub streams: Vec<String>,
+    pub consumer_group: String,
+    #[serde(default = "default_start_id")]
+    pub start_id: String,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: i32,
+    #[serde(default = "default_min_idle_ms")]
+    pub min_idle_ms: i64,
+}
+
+pub type RedisTrigger = Trigger<RedisConfig>;
+
+pub struct RedisTriggerHandler;
+
+impl TriggerCrud for RedisTriggerHandler {
+    const TABLE_NAME: &'static str = "redis_trigger";
+    const ROUTE_PREFIX: &'static str = "redis_triggers";
+    const DISPLAY_NAME: &'static str = "Redis";
+    const KIND: TriggerKind = TriggerKind::Redis;
+    const CONFIG_COLUMNS: &'static [&'static str] = &[
+        "redis_resource_path",
+        "streams",
+        "consumer_group",
+        "start_id",
+        "batch_size",
+        "min_idle_ms",
+    ];
+
+    type Config = RedisConfig;
+
+    fn deployed_object(path: String) -> DeployedObject {
+        DeployedObject::RedisTrigger { path }
+    }
+
+    fn validate_config(config: &RedisConfig) -> error::Result<()> {
+        if config.streams.is_empty() || config.streams.iter().any(|stream| stream.trim().is_empty())
+        {
+            return Err(error::Error::BadRequest(
+                "Redis trigger must listen to at least one stream and stream names cannot be empty"
+                    .to_string(),
+            ));
+        }
+        if config.consumer_group.trim().is_empty() {
+            return Err(error::Error::BadRequest(
+                "Redis trigger must have a consumer group".to_string(),
+            ));
+        }
+        if config.batch_size < 1 {
+            return Err(error::Error::BadRequest(
+                "Batch size must be at least 1".to_string(),
+            ));
+        }
+        if config.min_idle_ms < 1000 {
+            return Err(error::Error::BadRequest(
+                "Min idle time of pending entries must be at least 1000 ms".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn bind_config<'q>(config: &'q RedisConfig, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&config.redis_resource_path)
+            .bind(&config.streams)
+            .bind(&config.consumer_group)
+            .bind(&config.start_id)
+            .bind(config.batch_size)
+            .bind(config.min_idle_ms)
+    }
+}
+
+async fn run_job(message: &RedisMessage, db: &DB, trigger: &RedisTrigger) -> anyhow::Result<()> {
+    let args = RedisTriggerHandler::build_job_args(
+        &trigger.base.script_path,
+        trigger.base.is_flow,
+        &trigger.base.workspace_id,
+        db,
+        message,
+        HashMap::new(),
+    )
+    .await?;
+
+    trigger_job::<RedisTriggerHandler>(db, &trigger.base, args, None).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestRedisConnection {
+    redis_resource_path: String,
+}
+
+pub async fn test_redis_connection(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(workspace_id): Path<String>,
+    Json(test_redis): Json<TestRedisConnection>,
+) -> error::Result<()> {
+    let redis_resource = try_get_resource_from_db_as::<RedisResource>(
+        &authed,
+        Some(user_db),
+        &db,
+        &test_redis.redis_resource_path,
+        &workspace_id,
+    )
+    .await?;
+
+    let connect_f = async {
+        let mut conn = redis_resource.connect().await?;
+        redis::cmd("PING").query_async::<String>(&mut conn).await?;
+        Ok::<_, Error>(())
+    };
+
+    tokio::time::timeout(tokio::time::Duration::from_secs(30), connect_f)
+        .await
+        .map_err(|_| {
+            error::Error::BadConfig(format!(
+                "Timeout occurred while trying to connect to redis after 30 seconds"
+            ))
+        })?
+        .map_err(|err| {
+            error::Error::BadConfig(format!("Error connecting to redis: {}", err.to_string()))
+        })?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Listener for RedisTriggerHandler {
+    type CaptureConfig = RedisTriggerConfig;
+    type Connection = MultiplexedConnection;
+    type Error = Error;
+
+    async fn connect(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+    ) -> Result<MultiplexedConnection, Error> {
+        let redis_resource_path = match config {
+            ListeningConfig::Trigger(trigger) => &trigger.config.redis_resource_path,
+            ListeningConfig::Capture(capture) => &capture.trigger_config.redis_resource_path,
+        };
+
+        let redis_resource = try_get_resource_from_db_as::<RedisResource>(
+            &config.fetch_authed(db).await?,
+            Some(UserDB::new(db.clone())),
+            db,
+            redis_resource_path,
+            config.workspace_id(),
+        )
+        .await?;
+
+        let mut conn = redis_resource.connect().await?;
+
+        if let ListeningConfig::Trigger(trigger) = config {
+            create_consumer_groups(
+                &mut conn,
+                &trigger.config.streams,
+                &trigger.config.consumer_group,
+                &trigger.config.start_id,
+            )
+            .await?;
+        }
+
+        Ok(conn)
+    }
+
+    async fn consume(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+        mut conn: MultiplexedConnection,
+    ) -> Result<(), Error> {
+        let trigger = match config {
+            ListeningConfig::Trigger(trigger) => trigger,
+            ListeningConfig::Capture(capture) => {
+                return read_capture_entries(db, &mut conn, capture).await
+            }
+        };
+
+        let consumer = consumer_name();
+        let new_entries_ids = vec![">"; trigger.config.streams.len()];
+        let read_options = StreamReadOptions::default()
+            .group(&trigger.config.consumer_group, &consumer)
+            .count(trigger.config.batch_size as usize)
+            .block(READ_BLOCK_MS);
+        let mut last_autoclaim: Option<Instant> = None;
+
+        loop {
+            if last_autoclaim.map_or(true, |at| at.elapsed() >= AUTOCLAIM_INTERVAL) {
+                for stream in &trigger.config.streams {
+                    reclaim_pending_entries(db, &mut conn, trigger, stream, &consumer).await?;
+                }
+                last_autoclaim = Some(Instant::now());
+            }
+
+            let reply: Option<StreamReadReply> = conn
+                .xread_options(&trigger.config.streams, &new_entries_ids, &read_options)
+                .await?;
+
+            for stream in reply.map(|reply| reply.keys).unwrap_or_default() {
+                for entry in &stream.ids {
+                    handle(
+                        db,
+                        &mut conn,
+                        trigger,
+                        &RedisMessage::new(&stream.key, entry),
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+}
+
+/// Captures read the new entries of the streams without a consumer group so that they do not
+/// take the entries meant for the trigger
+async fn read_capture_entries(
+    db: &DB,
+    conn: &mut MultiplexedConnection,
+    capture: &CaptureConfigForListener<RedisTriggerConfig>,
+) -> Result<(), Error> {
+    let streams = &capture.trigger_config.streams;
+    let mut last_ids = vec!["$".to_string(); streams.len()];
+    loop {
+        let reply: Option<StreamReadReply> = conn
+            .xread_options(
+                streams,
+                &last_ids,
+                &StreamReadOptions::default()
+                    .count(DEFAULT_BATCH_SIZE as usize)
+                    .block(READ_BLOCK_MS),
+            )
+            .await?;
+
+        let reply = reply.unwrap_or_default();
+        advance_last_ids(streams, &mut last_ids, &reply);
+        for stream in reply.keys {
+            for entry in &stream.ids {
+                let (main_args, preprocessor_args) = RedisTriggerHandler::build_capture_payloads(
+                    &RedisMessage::new(&stream.key, entry),
+                    HashMap::new(),
+                );
+                capture
+                    .insert_payload(db, &TriggerKind::Redis, main_args, preprocessor_args)
+                    .await;
+            }
+        }
+    }
+}
+
+/// Moves the id read from next on each stream of the reply past its last entry. The reply only
+/// holds the streams that had new entries, matched to the watched streams by key.
+fn advance_last_ids(streams: &[String], last_ids: &mut [String], reply: &StreamReadReply) {
+    for stream in &reply.keys {
+        if let Some(index) = streams.iter().position(|s| s == &stream.key) {
+            if let Some(last) = stream.ids.last() {
+                last_ids[index] = last.id.clone();
+            }
+        }
+    }
+}
+
+/// Claims the entries that were delivered to a consumer of the group, typically the one of a
+/// server that stopped, and were not acknowledged for at least `min_idle_ms`
+async fn reclaim_pending_entries(
+    db: &DB,
+    conn: &mut MultiplexedConnection,
+    trigger: &RedisTrigger,
+    stream: &str,
+    consumer: &str,
+) -> Result<(), Error> {
+    let mut start_id = "0-0".to_string();
+    loop {
+        let reply: StreamAutoClaimReply = conn
+            .xautoclaim_options(
+                stream,
+                &trigger.config.consumer_group,
+                consumer,
+                trigger.config.min_idle_ms,
+                &start_id,
+                StreamAutoClaimOptions::default().count(trigger.config.batch_size as usize),
+            )
+            .await?;
+
+        if !reply.claimed.is_empty() {
+            tracing::info!(
+                "Redis trigger {} reclaimed {} pending entries of stream {}",
+                trigger.base.path,
+                reply.claimed.len(),
+                stream
+            );
+        }
+        for entry in &reply.claimed {
+            handle(db, conn, trigger, &RedisMessage::new(stream, entry)).await?;
+        }
+
+        if reply.next_stream_id == "0-0" {
+            return Ok(());
+        }
+        start_id = reply.next_stream_id;
+    }
+}
+
+/// The entry is acknowledged only once its job is queued. Entries whose job could not be queued
+/// stay pending and are delivered again once reclaimed.
+async fn handle(
+    db: &DB,
+    conn: &mut MultiplexedConnection,
+    trigger: &RedisTrigger,
+    message: &RedisMessage,
+) -> Result<(), Error> {
+    match run_job(message, db, trigger).await {
+        Ok(()) => {
+            let _: i64 = conn
+                .xack(
+                    &message.stream,
+                    &trigger.config.consumer_group,
+                    &[&message.id],
+                )
+                .await?;
+        }
+        Err(err) => {
+            report_critical_error(
+                format!(
+                    "Failed to trigger job from redis trigger {} for entry {} of stream {}, it will be retried once reclaimed: {:?}",
+                    trigger.base.path, message.id, message.stream, err
+                ),
+                db.clone(),
+                Some(&trigger.base.workspace_id),
+                None,
+            )
+            .await;
+        }
+    }
+    Ok(())
+}
+
+impl TriggerJobArgs<&RedisMessage> for RedisTriggerHandler {
+    fn v1_payload_fn(message: &RedisMessage) -> HashMap<String, Box<RawValue>> {
+        HashMap::from([
+            ("stream".to_string(), to_raw_value(&message.stream)),
+            ("id".to_string(), to_raw_value(&message.id)),
+            ("fields".to_string(), to_raw_value(&message.fields)),
+        ])
+    }
+
+    fn trigger_kind() -> TriggerKind {
+        TriggerKind::Redis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::streams::StreamKey;
+    use serde_json::json;
+
+    fn entry(id: &str, fields: &[(&str, &[u8])]) -> StreamId {
+        StreamId {
+            id: id.to_string(),
+            map: fields
+                .iter()
+                .map(|(field, value)| (field.to_string(), redis::Value::BulkString(value.to_vec())))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_advance_last_ids() {
+        let streams = vec![
+            "orders".to_string(),
+            "payments".to_string(),
+            "audit".to_string(),
+        ];
+        let mut last_ids = vec!["$".to_string(); streams.len()];
+        let reply = StreamReadReply {
+            keys: vec![
+                StreamKey {
+                    key: "audit".to_string(),
+                    ids: vec![entry("5-0", &[]), entry("7-0", &[])],
+                },
+                StreamKey { key: "orders".to_string(), ids: vec![entry("3-1", &[])] },
+                // keys that are not watched and streams without entries are ignored
+                StreamKey { key: "other".to_string(), ids: vec![entry("9-0", &[])] },
+                StreamKey { key: "payments".to_string(), ids: vec![] },
+            ],
+        };
+
+        advance_last_ids(&streams, &mut last_ids, &reply);
+        assert_eq!(last_ids, vec!["3-1", "$", "7-0"]);
+
+        advance_last_ids(&streams, &mut last_ids, &StreamReadReply::default());
+        assert_eq!(last_ids, vec!["3-1", "$", "7-0"]);
+    }
+
+    #[test]
+    fn test_redis_message() {
+        let message = RedisMessage::new(
+            "orders",
+            &entry("1-0", &[("order", b"42"), ("raw", &[0xff, 0xfe])]),
+        );
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            json!({
+                "stream": "orders",
+                "id": "1-0",
+                "fields": {"order": "42", "raw": "//4="}
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let config = |streams: &[&str], consumer_group: &str| RedisConfig {
+            redis_resource_path: "f/orders/redis".to_string(),
+            streams: streams.iter().map(|stream| stream.to_string()).collect(),
+            consumer_group: consumer_group.to_string(),
+            start_id: default_start_id(),
+            batch_size: default_batch_size(),
+            min_idle_ms: default_min_idle_ms(),
+        };
+
+        assert!(RedisTriggerHandler::validate_config(&config(&["orders"], "windmill")).is_ok());
+        assert!(RedisTriggerHandler::validate_config(&config(&[], "windmill")).is_err());
+        assert!(
+            RedisTriggerHandler::validate_config(&config(&["orders", " "], "windmill")).is_err()
+        );
+        assert!(RedisTriggerHandler::validate_config(&config(&["orders"], "")).is_err());
+
+        let mut invalid = config(&["orders"], "windmill");
+        invalid.batch_size = 0;
+        assert!(RedisTriggerHandler::validate_config(&invalid).is_err());
+        let mut invalid = config(&["orders"], "windmill");
+        invalid.min_idle_ms = 999;
+        assert!(RedisTriggerHandler::validate_config(&invalid).is_err());
+    }
+}
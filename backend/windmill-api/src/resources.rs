@@ -10,6 +10,10 @@ use std::collections::HashMap;
 
 use crate::{
     db::{ApiAuthed, DB},
+    environments::{
+        delete_overrides, get_request_environment, get_resource_override, rename_overrides,
+        OverrideKind,
+    },
     users::{maybe_refresh_folders, require_owner_of_path, Tokened},
     utils::check_scopes,
     webhook_util::{WebhookMessage, WebhookShared},
@@ -35,6 +39,7 @@ use windmill_common::{
     utils::{not_found_if_none, paginate, require_admin, Pagination, StripPath},
     variables,
     worker::CLOUD_HOSTED,
+    workspaces::{fork_item_workspace, ForkedItemKind},
 };
 
 pub fn workspaced_service() -> Router {
@@ -282,6 +287,7 @@ async fn get_resource(
 ) -> JsonResult<ListableResource> {
     let path = path.to_path();
     check_scopes(&authed, || format!("resources:read:{}", path))?;
+    let w_id = fork_item_workspace(&db, &w_id, ForkedItemKind::Resource, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let resource_o = sqlx::query_as!(
@@ -334,6 +340,7 @@ async fn get_resource_value(
 ) -> JsonResult<Option<serde_json::Value>> {
     let path = path.to_path();
     check_scopes(&authed, || format!("resources:read:{}", path))?;
+    let w_id = fork_item_workspace(&db, &w_id, ForkedItemKind::Resource, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let value_o = sqlx::query_scalar!(
@@ -453,21 +460,28 @@ pub async fn get_resource_value_interpolated_internal(
     job_id: Option<Uuid>,
     token: &str,
 ) -> Result<Option<serde_json::Value>> {
+    // the variables and resources that the value refers to are resolved from the workspace
+    // itself, even when the resource is inherited from the parent of a fork
+    let resource_w_id = fork_item_workspace(db, workspace, ForkedItemKind::Resource, path).await?;
     let mut tx = authed_transaction_or_default(authed, user_db.clone(), db).await?;
 
     let value_o = sqlx::query_scalar!(
         "SELECT value from resource WHERE path = $1 AND workspace_id = $2",
         path,
-        workspace
+        resource_w_id
     )
     .fetch_optional(&mut *tx)
     .await?;
     tx.commit().await?;
     if value_o.is_none() {
-        explain_resource_perm_error(path, workspace, db, &authed).await?;
+        explain_resource_perm_error(path, &resource_w_id, db, &authed).await?;
     }
 
     let value = not_found_if_none(value_o, "Resource", path)?;
+    let environment = get_request_environment(db, workspace, job_id, token).await?;
+    let value = get_resource_override(db, workspace, path, environment.as_deref())
+        .await?
+        .or(value);
     if let Some(value) = value {
         Ok(Some(
             transform_json_value(
@@ -477,6 +491,7 @@ pub async fn get_resource_value_interpolated_internal(
                 workspace,
                 value,
                 &job_id,
+                &environment,
                 token,
             )
             .await?,
@@ -494,6 +509,7 @@ pub async fn transform_json_value<'c>(
     workspace: &str,
     v: Value,
     job_id: &Option<Uuid>,
+    environment: &Option<String>,
     token: &str,
 ) -> Result<Value> {
     match v {
@@ -516,6 +532,7 @@ pub async fn transform_json_value<'c>(
                         username_override: None,
                         token_prefix: None,
                     }),
+                environment.as_deref(),
             )
             .await?;
             Ok(Value::String(v))
@@ -538,8 +555,21 @@ pub async fn transform_json_value<'c>(
             .await?;
             tx.commit().await?;
             let v = not_found_if_none(v, "Resource", path)?;
+            let v = get_resource_override(db, workspace, path, environment.as_deref())
+                .await?
+                .or(v);
             if let Some(v) = v {
-                transform_json_value(authed, user_db.clone(), db, workspace, v, job_id, token).await
+                transform_json_value(
+                    authed,
+                    user_db.clone(),
+                    db,
+                    workspace,
+                    v,
+                    job_id,
+                    environment,
+                    token,
+                )
+                .await
             } else {
                 Ok(Value::Null)
             }
@@ -606,9 +636,17 @@ pub async fn transform_json_value<'c>(
         }
         Value::Object(mut m) => {
             for (a, b) in m.clone().into_iter() {
-                let v =
-                    transform_json_value(authed, user_db.clone(), db, workspace, b, job_id, token)
-                        .await?;
+                let v = transform_json_value(
+                    authed,
+                    user_db.clone(),
+                    db,
+                    workspace,
+                    b,
+                    job_id,
+                    environment,
+                    token,
+                )
+                .await?;
                 m.insert(a.clone(), v);
             }
             Ok(Value::Object(m))
@@ -764,6 +802,8 @@ async fn delete_resource(
     )
     .execute(&mut *tx)
     .await?;
+    delete_overrides(&mut tx, &w_id, OverrideKind::Resource, path).await?;
+    delete_overrides(&mut tx, &w_id, OverrideKind::Variable, path).await?;
     audit_log(
         &mut *tx,
         &authed,
@@ -843,6 +883,8 @@ async fn update_resource(
             )
             .execute(&mut *tx)
             .await?;
+            rename_overrides(&mut tx, &w_id, OverrideKind::Resource, path, &npath).await?;
+            rename_overrides(&mut tx, &w_id, OverrideKind::Variable, path, &npath).await?;
         }
     }
 
@@ -1069,6 +1111,18 @@ async fn create_resource_type(
     .execute(&mut *tx)
     .await?;
 
+    audit_log(
+        &mut *tx,
+        &authed,
+        "resource_types.create",
+        ActionKind::Create,
+        &w_id,
+        Some(&resource_type.name),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
     handle_deployment_metadata(
         &authed.email,
         &authed.username,
@@ -1083,18 +1137,6 @@ async fn create_resource_type(
     )
     .await?;
 
-    audit_log(
-        &mut *tx,
-        &authed,
-        "resource_types.create",
-        ActionKind::Create,
-        &w_id,
-        Some(&resource_type.name),
-        None,
-    )
-    .await?;
-    tx.commit().await?;
-
     webhook.send_message(
         w_id.clone(),
         WebhookMessage::CreateResourceType { name: resource_type.name.clone() },
@@ -1236,6 +1278,10 @@ async fn update_resource_type(
     feature = "http_trigger",
     feature = "postgres_trigger",
     feature = "mqtt_trigger",
+    feature = "mysql_trigger",
+    feature = "amqp_trigger",
+    feature = "redis_trigger",
+    feature = "s3_trigger",
     all(
         feature = "enterprise",
         any(feature = "sqs_trigger", feature = "gcp_trigger")
@@ -0,0 +1,718 @@
+use crate::{
+    capture::S3TriggerConfig,
+    db::{ApiAuthed, DB},
+    resources::try_get_resource_from_db_as,
+    trigger_helpers::TriggerJobArgs,
+    triggers::{
+        handler::{trigger_routes, PgQuery, Trigger, TriggerCrud},
+        listener::{trigger_job, Listener, ListeningConfig},
+    },
+};
+use windmill_git_sync::DeployedObject;
+
+use axum::{async_trait, extract::Path, routing::post, Extension, Json, Router};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use object_store::{path::Path as ObjectPath, ObjectMeta, ObjectStore};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use sqlx::FromRow;
+use std::{collections::HashMap, sync::Arc};
+use windmill_common::{
+    db::UserDB,
+    error,
+    s3_helpers::{
+        build_object_store_client, AzureBlobResource, FilesystemResource, GcsResource,
+        LargeFileStorage, ObjectStoreResource, S3Object, S3Resource,
+    },
+    triggers::TriggerKind,
+    utils::report_critical_error,
+    worker::to_raw_value,
+    INSTANCE_NAME,
+};
+use windmill_queue::idempotency::IdempotencyKey;
+
+pub fn workspaced_service() -> Router {
+    trigger_routes::<S3TriggerHandler>().route("/test", post(test_s3_storage))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Common(#[from] windmill_common::error::Error),
+    #[error("{0}")]
+    ObjectStore(#[from] object_store::Error),
+    #[error("Invalid glob pattern: {0}")]
+    Glob(#[from] globset::Error),
+}
+
+const DEFAULT_DEBOUNCE_MS: i32 = 5000;
+const DEFAULT_POLL_INTERVAL_S: i32 = 30;
+/// Captures only deliver the objects that land while they are on, with the default debounce
+const CAPTURE_POLL_INTERVAL_S: u64 = 5;
+
+/// Resolves the primary storage of the workspace, or the secondary storage `storage`, to an
+/// object store. Storages authenticated through OIDC or workload identity are not supported as
+/// they require a job token.
+pub(crate) async fn get_workspace_object_store(
+    authed: &ApiAuthed,
+    user_db: UserDB,
+    db: &DB,
+    w_id: &str,
+    storage: Option<&str>,
+) -> error::Result<Arc<dyn ObjectStore>> {
+    let raw_lfs = match storage {
+        Some(storage) => sqlx::query_scalar!(
+            "SELECT large_file_storage->'secondary_storage'->$2 FROM workspace_settings WHERE workspace_id = $1",
+            w_id,
+            storage
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+        None => sqlx::query_scalar!(
+            "SELECT large_file_storage FROM workspace_settings WHERE workspace_id = $1",
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+    };
+
+    let Some(raw_lfs) = raw_lfs else {
+        return Err(error::Error::BadConfig(match storage {
+            Some(storage) => format!("Secondary storage {storage} is not configured"),
+            None => "No object storage is configured for the workspace".to_string(),
+        }));
+    };
+    let lfs = serde_json::from_value::<LargeFileStorage>(raw_lfs).map_err(|e| {
+        error::Error::BadConfig(format!("Invalid object storage configuration: {e}"))
+    })?;
+
+    let user_db = Some(user_db);
+    let resource = match lfs {
+        LargeFileStorage::S3Storage(s3_storage) => ObjectStoreResource::S3(
+            try_get_resource_from_db_as::<S3Resource>(
+                authed,
+                user_db,
+                db,
+                s3_storage.s3_resource_path.trim_start_matches("$res:"),
+                w_id,
+            )
+            .await?,
+        ),
+        LargeFileStorage::AzureBlobStorage(azure_blob_storage) => ObjectStoreResource::Azure(
+            try_get_resource_from_db_as::<AzureBlobResource>(
+                authed,
+                user_db,
+                db,
+                azure_blob_storage
+                    .azure_blob_resource_path
+                    .trim_start_matches("$res:"),
+                w_id,
+            )
+            .await?,
+        ),
+        LargeFileStorage::GoogleCloudStorage(gcs) => ObjectStoreResource::Gcs(
+            try_get_resource_from_db_as::<GcsResource>(
+                authed,
+                user_db,
+                db,
+                gcs.gcs_resource_path.trim_start_matches("$res:"),
+                w_id,
+            )
+            .await?,
+        ),
+        LargeFileStorage::FilesystemStorage(filesystem) => {
+            ObjectStoreResource::Filesystem(FilesystemResource::from_storage(&filesystem)?)
+        }
+        LargeFileStorage::S3AwsOidc(_) | LargeFileStorage::AzureWorkloadIdentity(_) => {
+            return Err(error::Error::BadConfig(
+                "S3 triggers do not support storages authenticated with OIDC or workload identity"
+                    .to_string(),
+            ))
+        }
+    };
+
+    build_object_store_client(&resource).await
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+/// Selects the objects of the watched folder whose key matches one of the include patterns, if
+/// any, and none of the exclude patterns
+struct ObjectFilter {
+    prefix: Option<ObjectPath>,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl ObjectFilter {
+    fn new(
+        prefix: &str,
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            prefix: Some(ObjectPath::from(prefix)).filter(|prefix| !prefix.as_ref().is_empty()),
+            include: build_glob_set(include_patterns)?,
+            exclude: build_glob_set(exclude_patterns)?,
+        })
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        self.include.as_ref().map_or(true, |include| include.is_match(key))
+            && !self.exclude.as_ref().is_some_and(|exclude| exclude.is_match(key))
+    }
+}
+
+/// Lists the objects that were added or changed since the watermark, the last modification date
+/// of the objects already delivered. Objects modified at the watermark are told apart by key.
+pub struct ObjectWatcher {
+    store: Arc<dyn ObjectStore>,
+    filter: ObjectFilter,
+    debounce: chrono::Duration,
+    watermark: DateTime<Utc>,
+    watermark_keys: Vec<String>,
+}
+
+impl ObjectWatcher {
+    /// New or changed objects in modification order. Objects modified during the debounce are
+    /// left for a later poll so that an object written several times in a row is delivered once.
+    async fn new_objects(&self) -> Result<Vec<ObjectMeta>, Error> {
+        let settled_before = Utc::now() - self.debounce;
+        let mut objects: Vec<ObjectMeta> = self
+            .store
+            .list(self.filter.prefix.as_ref())
+            .try_filter(|meta| {
+                let is_new = meta.last_modified > self.watermark
+                    || (meta.last_modified == self.watermark
+                        && !self
+                            .watermark_keys
+                            .iter()
+                            .any(|key| key == meta.location.as_ref()));
+                futures::future::ready(
+                    is_new
+                        && meta.last_modified <= settled_before
+                        && self.filter.matches(meta.location.as_ref()),
+                )
+            })
+            .try_collect()
+            .await?;
+
+        objects.sort_by(|a, b| {
+            a.last_modified
+                .cmp(&b.last_modified)
+                .then_with(|| a.location.cmp(&b.location))
+        });
+        Ok(objects)
+    }
+
+    fn advance(&mut self, meta: &ObjectMeta) {
+        if meta.last_modified > self.watermark {
+            self.watermark = meta.last_modified;
+            self.watermark_keys.clear();
+        }
+        self.watermark_keys.push(meta.location.to_string());
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct S3ObjectEvent {
+    object: S3Object,
+    size: u64,
+    last_modified: DateTime<Utc>,
+    e_tag: Option<String>,
+    version: Option<String>,
+}
+
+impl S3ObjectEvent {
+    fn new(meta: &ObjectMeta, storage: Option<&String>) -> Self {
+        Self {
+            object: S3Object {
+                s3: meta.location.to_string(),
+                storage: storage.cloned(),
+                filename: meta.location.filename().map(|filename| filename.to_string()),
+                presigned: None,
+            },
+            size: meta.size as u64,
+            last_modified: meta.last_modified,
+            e_tag: meta.e_tag.clone(),
+            version: meta.version.clone(),
+        }
+    }
+}
+
+fn default_debounce_ms() -> i32 {
+    DEFAULT_DEBOUNCE_MS
+}
+
+fn default_poll_interval_s() -> i32 {
+    DEFAULT_POLL_INTERVAL_S
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct S3Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<String>,
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: i32,
+    #[serde(default = "default_poll_interval_s")]
+    pub poll_interval_s: i32,
+    /// Saved by the listener rather than written on create and update. It defaults to the
+    /// creation date so that only the objects landing after it are delivered.
+    #[serde(skip_deserializing, default = "Utc::now")]
+    pub watermark: DateTime<Utc>,
+    #[serde(skip_deserializing)]
+    pub watermark_keys: Vec<String>,
+}
+
+pub type S3Trigger = Trigger<S3Config>;
+
+pub struct S3TriggerHandler;
+
+impl TriggerCrud for S3TriggerHandler {
+    const TABLE_NAME: &'static str = "s3_trigger";
+    const ROUTE_PREFIX: &'static str = "s3_triggers";
+    const DISPLAY_NAME: &'static str = "S3";
+    const KIND: TriggerKind = TriggerKind::S3;
+    const CONFIG_COLUMNS: &'static [&'static str] = &[
+        "storage",
+        "prefix",
+        "include_patterns",
+        "exclude_patterns",
+        "debounce_ms",
+        "poll_interval_s",
+    ];
+
+    type Config = S3Config;
+
+    fn deployed_object(path: String) -> DeployedObject {
+        DeployedObject::S3Trigger { path }
+    }
+
+    fn validate_config(config: &S3Config) -> error::Result<()> {
+        ObjectFilter::new(
+            &config.prefix,
+            &config.include_patterns,
+            &config.exclude_patterns,
+        )
+        .map_err(|err| error::Error::BadRequest(err.to_string()))?;
+        if config.debounce_ms < 0 {
+            return Err(error::Error::BadRequest(
+                "Debounce cannot be negative".to_string(),
+            ));
+        }
+        if config.poll_interval_s < 5 {
+            return Err(error::Error::BadRequest(
+                "Poll interval must be at least 5 seconds".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn bind_config<'q>(config: &'q S3Config, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&config.storage)
+            .bind(&config.prefix)
+            .bind(&config.include_patterns)
+            .bind(&config.exclude_patterns)
+            .bind(config.debounce_ms)
+            .bind(config.poll_interval_s)
+    }
+}
+
+async fn run_job(
+    event: &S3ObjectEvent,
+    db: &DB,
+    trigger: &S3Trigger,
+) -> anyhow::Result<()> {
+    let trigger = &trigger.base;
+    let args = S3TriggerHandler::build_job_args(
+        &trigger.script_path,
+        trigger.is_flow,
+        &trigger.workspace_id,
+        db,
+        event,
+        HashMap::new(),
+    )
+    .await?;
+
+    // the watermark is saved after the job is pushed, the key prevents pushing it twice if the
+    // listener restarts in between
+    let mut hasher = Sha256::new();
+    hasher.update(event.object.s3.as_bytes());
+    hasher.update(event.last_modified.to_rfc3339().as_bytes());
+    hasher.update(event.e_tag.as_deref().unwrap_or_default().as_bytes());
+    let idempotency_key = IdempotencyKey::new(
+        &trigger.workspace_id,
+        format!("s3_trigger/{}", trigger.path),
+        &format!("{:x}", hasher.finalize()),
+    )?;
+
+    trigger_job::<S3TriggerHandler>(db, trigger, args, idempotency_key.as_ref()).await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TestS3Storage {
+    storage: Option<String>,
+    prefix: Option<String>,
+}
+
+pub async fn test_s3_storage(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(workspace_id): Path<String>,
+    Json(test_s3): Json<TestS3Storage>,
+) -> error::Result<()> {
+    let store = get_workspace_object_store(
+        &authed,
+        user_db,
+        &db,
+        &workspace_id,
+        test_s3.storage.as_deref(),
+    )
+    .await?;
+
+    let prefix = test_s3
+        .prefix
+        .as_deref()
+        .map(ObjectPath::from)
+        .filter(|prefix| !prefix.as_ref().is_empty());
+
+    let list_f = async { store.list(prefix.as_ref()).try_next().await };
+
+    tokio::time::timeout(tokio::time::Duration::from_secs(30), list_f)
+        .await
+        .map_err(|_| {
+            error::Error::BadConfig(format!(
+                "Timeout occurred while trying to list the objects of the storage after 30 seconds"
+            ))
+        })?
+        .map_err(|err| {
+            error::Error::BadConfig(format!(
+                "Error listing the objects of the storage: {}",
+                err.to_string()
+            ))
+        })?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl Listener for S3TriggerHandler {
+    type CaptureConfig = S3TriggerConfig;
+    type Connection = ObjectWatcher;
+    type Error = Error;
+
+    async fn connect(db: &DB, config: &ListeningConfig<Self>) -> Result<ObjectWatcher, Error> {
+        let store = get_workspace_object_store(
+            &config.fetch_authed(db).await?,
+            UserDB::new(db.clone()),
+            db,
+            config.workspace_id(),
+            storage(config).map(|storage| storage.as_str()),
+        )
+        .await?;
+
+        let watcher = match config {
+            ListeningConfig::Trigger(trigger) => {
+                let config = &trigger.config;
+                ObjectWatcher {
+                    store,
+                    filter: ObjectFilter::new(
+                        &config.prefix,
+                        &config.include_patterns,
+                        &config.exclude_patterns,
+                    )?,
+                    debounce: chrono::Duration::milliseconds(config.debounce_ms as i64),
+                    watermark: config.watermark,
+                    watermark_keys: config.watermark_keys.clone(),
+                }
+            }
+            ListeningConfig::Capture(capture) => {
+                let config = &capture.trigger_config;
+                ObjectWatcher {
+                    store,
+                    filter: ObjectFilter::new(
+                        &config.prefix,
+                        config.include_patterns.as_deref().unwrap_or_default(),
+                        config.exclude_patterns.as_deref().unwrap_or_default(),
+                    )?,
+                    debounce: chrono::Duration::milliseconds(DEFAULT_DEBOUNCE_MS as i64),
+                    watermark: Utc::now(),
+                    watermark_keys: vec![],
+                }
+            }
+        };
+
+        Ok(watcher)
+    }
+
+    async fn consume(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+        mut watcher: ObjectWatcher,
+    ) -> Result<(), Error> {
+        let poll_interval = match config {
+            ListeningConfig::Trigger(trigger) => {
+                tokio::time::Duration::from_secs(trigger.config.poll_interval_s as u64)
+            }
+            ListeningConfig::Capture(_) => tokio::time::Duration::from_secs(CAPTURE_POLL_INTERVAL_S),
+        };
+
+        loop {
+            for meta in watcher.new_objects().await? {
+                let event = S3ObjectEvent::new(&meta, storage(config));
+                match config {
+                    ListeningConfig::Trigger(trigger) => {
+                        if !handle(db, trigger, &event).await {
+                            // retried from the same object at the next poll to keep the delivery
+                            // order
+                            break;
+                        }
+                        watcher.advance(&meta);
+                        save_watermark(db, trigger, &watcher).await?;
+                    }
+                    ListeningConfig::Capture(capture) => {
+                        let (main_args, preprocessor_args) =
+                            S3TriggerHandler::build_capture_payloads(&event, HashMap::new());
+                        capture
+                            .insert_payload(db, &TriggerKind::S3, main_args, preprocessor_args)
+                            .await;
+                        watcher.advance(&meta);
+                    }
+                }
+            }
+
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+}
+
+fn storage(config: &ListeningConfig<S3TriggerHandler>) -> Option<&String> {
+    match config {
+        ListeningConfig::Trigger(trigger) => trigger.config.storage.as_ref(),
+        ListeningConfig::Capture(capture) => capture.trigger_config.storage.as_ref(),
+    }
+}
+
+async fn save_watermark(db: &DB, trigger: &S3Trigger, watcher: &ObjectWatcher) -> error::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE
+            s3_trigger
+        SET
+            watermark = $1,
+            watermark_keys = $2
+        WHERE
+            workspace_id = $3
+            AND path = $4
+            AND server_id = $5
+        "#,
+        watcher.watermark,
+        &watcher.watermark_keys,
+        &trigger.base.workspace_id,
+        &trigger.base.path,
+        *INSTANCE_NAME
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+async fn handle(db: &DB, trigger: &S3Trigger, event: &S3ObjectEvent) -> bool {
+    match run_job(event, db, trigger).await {
+        Ok(()) => true,
+        Err(err) => {
+            report_critical_error(
+                format!(
+                    "Failed to trigger job from s3 trigger {} for object {}, it will be retried at the next poll: {:?}",
+                    trigger.base.path, event.object.s3, err
+                ),
+                db.clone(),
+                Some(&trigger.base.workspace_id),
+                None,
+            )
+            .await;
+            false
+        }
+    }
+}
+
+impl TriggerJobArgs<&S3ObjectEvent> for S3TriggerHandler {
+    fn v1_payload_fn(event: &S3ObjectEvent) -> HashMap<String, Box<RawValue>> {
+        HashMap::from([
+            ("object".to_string(), to_raw_value(&event.object)),
+            ("size".to_string(), to_raw_value(&event.size)),
+            ("last_modified".to_string(), to_raw_value(&event.last_modified)),
+            ("e_tag".to_string(), to_raw_value(&event.e_tag)),
+            ("version".to_string(), to_raw_value(&event.version)),
+        ])
+    }
+
+    fn trigger_kind() -> TriggerKind {
+        TriggerKind::S3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::{memory::InMemory, PutPayload};
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn test_object_filter() {
+        let filter = ObjectFilter::new(
+            "incoming",
+            &patterns(&["**/*.csv", "**/*.json"]),
+            &patterns(&["**/tmp/**"]),
+        )
+        .unwrap();
+        assert_eq!(filter.prefix, Some(ObjectPath::from("incoming")));
+        assert!(filter.matches("incoming/a.csv"));
+        assert!(filter.matches("incoming/2025/01/b.json"));
+        assert!(!filter.matches("incoming/a.txt"));
+        assert!(!filter.matches("incoming/tmp/c.csv"));
+        assert!(!filter.matches("incoming/2025/tmp/d.json"));
+
+        // without include patterns every object is selected but the excluded ones
+        let filter = ObjectFilter::new("", &[], &patterns(&["*.tmp"])).unwrap();
+        assert_eq!(filter.prefix, None);
+        assert!(filter.matches("a.csv"));
+        assert!(!filter.matches("a.tmp"));
+        // `*` matches across folders, so nested objects are excluded too
+        assert!(!filter.matches("incoming/nested/a.tmp"));
+
+        let filter = ObjectFilter::new("", &patterns(&["incoming/{orders,refunds}/*.csv"]), &[])
+            .unwrap();
+        assert!(filter.matches("incoming/orders/a.csv"));
+        assert!(filter.matches("incoming/refunds/a.csv"));
+        assert!(!filter.matches("incoming/payments/a.csv"));
+
+        assert!(ObjectFilter::new("", &patterns(&["incoming/[a"]), &[]).is_err());
+        assert!(ObjectFilter::new("", &[], &patterns(&["{a,b"])).is_err());
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let config = |include_patterns: &[&str]| S3Config {
+            storage: None,
+            prefix: "incoming".to_string(),
+            include_patterns: patterns(include_patterns),
+            exclude_patterns: vec![],
+            debounce_ms: default_debounce_ms(),
+            poll_interval_s: default_poll_interval_s(),
+            watermark: Utc::now(),
+            watermark_keys: vec![],
+        };
+
+        assert!(S3TriggerHandler::validate_config(&config(&["**/*.csv"])).is_ok());
+        assert!(S3TriggerHandler::validate_config(&config(&["**/[*.csv"])).is_err());
+
+        let mut invalid = config(&[]);
+        invalid.debounce_ms = -1;
+        assert!(S3TriggerHandler::validate_config(&invalid).is_err());
+        let mut invalid = config(&[]);
+        invalid.poll_interval_s = 4;
+        assert!(S3TriggerHandler::validate_config(&invalid).is_err());
+    }
+
+    async fn put(store: &InMemory, key: &str) {
+        store
+            .put(&ObjectPath::from(key), PutPayload::from_static(b"data"))
+            .await
+            .unwrap();
+        // distinct modification dates keep the delivery order predictable
+        tokio::time::sleep(tokio::time::Duration::from_millis(5)).await;
+    }
+
+    async fn new_keys(watcher: &ObjectWatcher) -> Vec<String> {
+        watcher
+            .new_objects()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|meta| meta.location.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_object_watcher() {
+        let store = Arc::new(InMemory::new());
+        for key in [
+            "incoming/b.csv",
+            "incoming/a.csv",
+            "incoming/a.txt",
+            "incoming/tmp/c.csv",
+            "other/d.csv",
+        ] {
+            put(&store, key).await;
+        }
+
+        let mut watcher = ObjectWatcher {
+            store: store.clone(),
+            filter: ObjectFilter::new(
+                "incoming",
+                &patterns(&["**/*.csv"]),
+                &patterns(&["**/tmp/**"]),
+            )
+            .unwrap(),
+            debounce: chrono::Duration::zero(),
+            watermark: DateTime::<Utc>::MIN_UTC,
+            watermark_keys: vec![],
+        };
+
+        // in modification order rather than by key
+        assert_eq!(
+            new_keys(&watcher).await,
+            ["incoming/b.csv", "incoming/a.csv"]
+        );
+
+        let objects = watcher.new_objects().await.unwrap();
+        watcher.advance(&objects[0]);
+        assert_eq!(new_keys(&watcher).await, ["incoming/a.csv"]);
+        watcher.advance(&objects[1]);
+        assert!(new_keys(&watcher).await.is_empty());
+
+        // objects modified at the watermark are told apart by key
+        watcher.watermark_keys.clear();
+        assert_eq!(new_keys(&watcher).await, ["incoming/a.csv"]);
+        watcher.advance(&objects[1]);
+
+        // a rewritten object is delivered again once settled
+        watcher.debounce = chrono::Duration::hours(1);
+        put(&store, "incoming/b.csv").await;
+        put(&store, "incoming/e.csv").await;
+        assert!(new_keys(&watcher).await.is_empty());
+        watcher.debounce = chrono::Duration::zero();
+        assert_eq!(
+            new_keys(&watcher).await,
+            ["incoming/b.csv", "incoming/e.csv"]
+        );
+    }
+}
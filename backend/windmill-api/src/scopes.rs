@@ -252,6 +252,10 @@ pub enum ScopeDomain {
     KafkaTriggers,
     NatsTriggers,
     MqttTriggers,
+    MysqlTriggers,
+    AmqpTriggers,
+    RedisTriggers,
+    S3Triggers,
     SqsTriggers,
     GcpTriggers,
     PostgresTriggers,
@@ -271,6 +275,10 @@ pub enum ScopeDomain {
 
     // Special domains
     Capture,           // Webhook capture
+    TriggerDeadLetters, // Failed trigger deliveries
+    Promotions,        // Promotions between workspaces
+    ObjectHistory,     // History and restore of deployed objects
+    Environments,      // Environments and their overrides
     Drafts,            // Draft resources
     Favorites,         // User favorites
     Inputs,            // Input templates
@@ -304,6 +312,10 @@ impl ScopeDomain {
             Self::KafkaTriggers => "kafka_triggers",
             Self::NatsTriggers => "nats_triggers",
             Self::MqttTriggers => "mqtt_triggers",
+            Self::MysqlTriggers => "mysql_triggers",
+            Self::AmqpTriggers => "amqp_triggers",
+            Self::RedisTriggers => "redis_triggers",
+            Self::S3Triggers => "s3_triggers",
             Self::SqsTriggers => "sqs_triggers",
             Self::GcpTriggers => "gcp_triggers",
             Self::PostgresTriggers => "postgres_triggers",
@@ -315,6 +327,10 @@ impl ScopeDomain {
             Self::OAuth => "oauth",
             Self::AI => "ai",
             Self::Capture => "capture",
+            Self::TriggerDeadLetters => "trigger_dead_letters",
+            Self::Promotions => "promotions",
+            Self::ObjectHistory => "object_history",
+            Self::Environments => "environments",
             Self::Drafts => "drafts",
             Self::Favorites => "favorites",
             Self::Inputs => "inputs",
@@ -349,6 +365,10 @@ impl ScopeDomain {
             "kafka_triggers" => Some(Self::KafkaTriggers),
             "nats_triggers" => Some(Self::NatsTriggers),
             "mqtt_triggers" => Some(Self::MqttTriggers),
+            "mysql_triggers" => Some(Self::MysqlTriggers),
+            "amqp_triggers" => Some(Self::AmqpTriggers),
+            "redis_triggers" => Some(Self::RedisTriggers),
+            "s3_triggers" => Some(Self::S3Triggers),
             "sqs_triggers" => Some(Self::SqsTriggers),
             "gcp_triggers" => Some(Self::GcpTriggers),
             "postgres_triggers" => Some(Self::PostgresTriggers),
@@ -363,6 +383,10 @@ impl ScopeDomain {
             "teams" => Some(Self::Teams),
             "git_sync" | "github_app" => Some(Self::GitSync),
             "capture" => Some(Self::Capture),
+            "trigger_dead_letters" => Some(Self::TriggerDeadLetters),
+            "promotions" => Some(Self::Promotions),
+            "object_history" => Some(Self::ObjectHistory),
+            "environments" => Some(Self::Environments),
             "drafts" => Some(Self::Drafts),
             "favorites" => Some(Self::Favorites),
             "inputs" => Some(Self::Inputs),
@@ -63,6 +63,7 @@ use windmill_common::{
         not_found_if_none, paginate, query_elems_from_hub, require_admin, Pagination, StripPath,
     },
     worker::to_raw_value,
+    workspaces::{fork_item_workspace, ForkedItemKind},
     HUB_BASE_URL,
 };
 use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
@@ -1056,11 +1057,13 @@ pub async fn get_full_hub_script_by_path(
 async fn get_script_by_path(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
     Path((w_id, path)): Path<(String, StripPath)>,
     Query(query): Query<WithStarredInfoQuery>,
 ) -> JsonResult<ScriptWithStarred> {
     let path = path.to_path();
     check_scopes(&authed, || format!("scripts:read:{}", path))?;
+    let w_id = fork_item_workspace(&db, &w_id, ForkedItemKind::Script, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let script_o = if query.with_starred_info.unwrap_or(false) {
@@ -1378,6 +1381,7 @@ async fn raw_script_by_path_internal(
         .trim_end_matches(".ts")
         .trim_end_matches(".go")
         .trim_end_matches(".sh");
+    let w_id = fork_item_workspace(&db, &w_id, ForkedItemKind::Script, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let content_o = sqlx::query_scalar!(
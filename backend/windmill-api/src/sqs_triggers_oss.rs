@@ -3,12 +3,14 @@
 pub use crate::sqs_triggers_ee::*;
 
 #[cfg(not(feature = "private"))]
-use crate::db::DB;
+use crate::{db::DB, triggers::handler::Trigger};
 #[cfg(not(feature = "private"))]
 use axum::Router;
 #[cfg(not(feature = "private"))]
 use serde::{Deserialize, Serialize};
 #[cfg(not(feature = "private"))]
+use sqlx::FromRow;
+#[cfg(not(feature = "private"))]
 use windmill_common::auth::aws::AwsAuthResourceType;
 
 #[cfg(not(feature = "private"))]
@@ -21,29 +23,14 @@ pub fn start_sqs(_db: DB, mut _killpill_rx: tokio::sync::broadcast::Receiver<()>
     // implementation is not open source
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, FromRow)]
 #[cfg(not(feature = "private"))]
-pub struct SqsTrigger {
+pub struct SqsConfig {
     pub queue_url: String,
     pub aws_auth_resource_type: AwsAuthResourceType,
     pub aws_resource_path: String,
     pub message_attributes: Option<Vec<String>>,
-    pub path: String,
-    pub script_path: String,
-    pub is_flow: bool,
-    pub workspace_id: String,
-    pub edited_by: String,
-    pub email: String,
-    pub edited_at: chrono::DateTime<chrono::Utc>,
-    pub extra_perms: Option<serde_json::Value>,
-    pub error: Option<String>,
-    pub server_id: Option<String>,
-    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
-    pub enabled: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_path: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error_handler_args: Option<sqlx::types::Json<std::collections::HashMap<String, Box<serde_json::value::RawValue>>>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub retry: Option<sqlx::types::Json<windmill_common::flows::Retry>>,
 }
+
+#[cfg(not(feature = "private"))]
+pub type SqsTrigger = Trigger<SqsConfig>;
@@ -24,6 +24,10 @@ fn build_trigger_scope_domains() -> Vec<ScopeDomain> {
         ("kafka_triggers", "Kafka"),
         ("nats_triggers", "NATS"),
         ("mqtt_triggers", "MQTT"),
+        ("mysql_triggers", "MySQL"),
+        ("amqp_triggers", "AMQP"),
+        ("redis_triggers", "Redis"),
+        ("s3_triggers", "S3"),
         ("sqs_triggers", "AWS SQS"),
         ("gcp_triggers", "GCP Pub/Sub"),
         ("postgres_triggers", "PostgreSQL"),
@@ -107,6 +111,30 @@ fn build_standard_scope_domains() -> Vec<ScopeDomain> {
             false,
         ),
         ("capture", "Capture", "Request capture management", false),
+        (
+            "trigger_dead_letters",
+            "Trigger Dead Letters",
+            "Failed trigger deliveries inspection and replay",
+            false,
+        ),
+        (
+            "promotions",
+            "Promotions",
+            "Promotions of items between workspaces",
+            false,
+        ),
+        (
+            "object_history",
+            "Object History",
+            "History and restore of deployed objects",
+            false,
+        ),
+        (
+            "environments",
+            "Environments",
+            "Environments and their variable and resource overrides",
+            false,
+        ),
         (
             "concurrency_groups",
             "Concurrency Groups",
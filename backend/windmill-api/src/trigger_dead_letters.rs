@@ -0,0 +1,657 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Trigger events whose job could not be pushed, or whose job failed, are kept as dead letters
+//! so that they can be inspected, edited and replayed.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Extension, Path, Query},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sqlx::types::Json as SqlxJson;
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    triggers::TriggerKind,
+    utils::{not_found_if_none, paginate, Pagination, StripPath},
+};
+use windmill_queue::PushArgsOwned;
+
+use crate::{
+    db::{ApiAuthed, DB},
+    jobs::{run_flow_by_path_inner, run_script_by_path_inner, RunJobQuery},
+};
+
+/// Dead letters are kept for 30 days
+const DEAD_LETTER_RETENTION_DAYS: i32 = 30;
+
+/// Kinds of the triggers whose failed jobs are moved to the dead letters by the sweeper. Jobs run
+/// by webhooks, emails, schedules or apps report their failure to their caller or error handler.
+const SWEPT_TRIGGER_KINDS: &[TriggerKind] = &[
+    TriggerKind::Http,
+    TriggerKind::Websocket,
+    TriggerKind::Kafka,
+    TriggerKind::Nats,
+    TriggerKind::Mqtt,
+    TriggerKind::Sqs,
+    TriggerKind::Postgres,
+    TriggerKind::Gcp,
+    TriggerKind::Mysql,
+    TriggerKind::Amqp,
+    TriggerKind::Redis,
+    TriggerKind::S3,
+];
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/list", get(list_dead_letters))
+        .route("/get/:id", get(get_dead_letter))
+        .route("/update/:id", post(update_dead_letter))
+        .route("/delete/:id", delete(delete_dead_letter))
+        .route("/replay", post(replay_dead_letters))
+}
+
+/// The trigger a job was pushed by, parsed from the `<kind>_trigger/<path>` trigger path the
+/// triggers pass to `trigger_runnable`
+#[derive(Debug, Clone)]
+pub struct TriggerRef {
+    pub kind: TriggerKind,
+    pub path: String,
+}
+
+impl TriggerRef {
+    pub fn parse(trigger_path: &str) -> Option<Self> {
+        let (kind, path) = trigger_path.split_once("_trigger/")?;
+        let kind = TriggerKind::iter().find(|k| k.to_key() == kind)?;
+        Some(Self { kind, path: path.to_string() })
+    }
+}
+
+/// Marks the job as triggered by the trigger so that the dead letter sweeper picks it up
+/// if it fails
+pub async fn tag_triggered_job(db: &DB, job_id: Uuid, trigger: &TriggerRef) -> Result<()> {
+    sqlx::query!(
+        "UPDATE v2_job SET trigger = $1, trigger_kind = $2::TEXT::JOB_TRIGGER_KIND WHERE id = $3",
+        trigger.path,
+        trigger.kind.to_key(),
+        job_id,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn insert_push_failure(
+    db: &DB,
+    workspace_id: &str,
+    trigger: &TriggerRef,
+    runnable_path: &str,
+    is_flow: bool,
+    args: &RawValue,
+    error: &Error,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO trigger_dead_letter (
+            workspace_id,
+            trigger_kind,
+            trigger_path,
+            runnable_path,
+            is_flow,
+            args,
+            failure_kind,
+            error
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, 'push', $7)
+        "#,
+        workspace_id,
+        trigger.kind.clone() as TriggerKind,
+        trigger.path,
+        runnable_path,
+        is_flow,
+        SqlxJson(args) as SqlxJson<&RawValue>,
+        error.to_string(),
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Keeps a failed job as a dead letter right away, for the triggers that retry their failed jobs
+/// themselves and are left out by the sweeper
+pub async fn insert_job_failure(db: &DB, job_id: Uuid) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO trigger_dead_letter (
+            workspace_id,
+            trigger_kind,
+            trigger_path,
+            runnable_path,
+            is_flow,
+            args,
+            failure_kind,
+            error,
+            job_id,
+            created_at
+        )
+        SELECT
+            j.workspace_id,
+            j.trigger_kind::TEXT::TRIGGER_KIND,
+            j.trigger,
+            j.runnable_path,
+            j.kind = 'flow',
+            COALESCE(j.args, '{}'::jsonb),
+            'job',
+            COALESCE(jc.result->'error'->>'message', jc.result::TEXT, 'Job failed'),
+            j.id,
+            jc.completed_at
+        FROM
+            v2_job_completed jc
+            JOIN v2_job j ON j.id = jc.id
+        WHERE
+            jc.id = $1
+            AND j.trigger IS NOT NULL
+            AND j.runnable_path IS NOT NULL
+            AND j.trigger_kind IS NOT NULL
+        ON CONFLICT (job_id) WHERE job_id IS NOT NULL DO NOTHING
+        "#,
+        job_id,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DeadLetter {
+    id: i64,
+    trigger_kind: TriggerKind,
+    trigger_path: String,
+    runnable_path: String,
+    is_flow: bool,
+    args: SqlxJson<Box<RawValue>>,
+    failure_kind: String,
+    error: String,
+    job_id: Option<Uuid>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    edited_by: Option<String>,
+    edited_at: Option<chrono::DateTime<chrono::Utc>>,
+    replayed_by: Option<String>,
+    replayed_at: Option<chrono::DateTime<chrono::Utc>>,
+    replayed_job_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+struct ListDeadLettersQuery {
+    trigger_kind: Option<TriggerKind>,
+    trigger_path: Option<String>,
+    runnable_path: Option<String>,
+    replayed: Option<bool>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+async fn list_dead_letters(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(query): Query<ListDeadLettersQuery>,
+) -> JsonResult<Vec<DeadLetter>> {
+    let (per_page, offset) = paginate(Pagination { page: query.page, per_page: query.per_page });
+
+    let mut tx = user_db.begin(&authed).await?;
+    let dead_letters = sqlx::query_as!(
+        DeadLetter,
+        r#"
+        SELECT
+            id,
+            trigger_kind AS "trigger_kind: _",
+            trigger_path,
+            runnable_path,
+            is_flow,
+            CASE
+                WHEN pg_column_size(args) < 40000 THEN args
+                ELSE '"WINDMILL_TOO_BIG"'::jsonb
+            END AS "args!: _",
+            failure_kind,
+            error,
+            job_id,
+            created_at,
+            edited_by,
+            edited_at,
+            replayed_by,
+            replayed_at,
+            replayed_job_id
+        FROM
+            trigger_dead_letter
+        WHERE
+            workspace_id = $1
+            AND ($2::trigger_kind IS NULL OR trigger_kind = $2)
+            AND ($3::TEXT IS NULL OR trigger_path = $3)
+            AND ($4::TEXT IS NULL OR runnable_path = $4)
+            AND ($5::BOOLEAN IS NULL OR (replayed_at IS NOT NULL) = $5)
+        ORDER BY
+            created_at DESC
+        OFFSET $6
+        LIMIT $7
+        "#,
+        w_id,
+        query.trigger_kind as Option<TriggerKind>,
+        query.trigger_path,
+        query.runnable_path,
+        query.replayed,
+        offset as i64,
+        per_page as i64,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(dead_letters))
+}
+
+async fn get_dead_letter(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> JsonResult<DeadLetter> {
+    let mut tx = user_db.begin(&authed).await?;
+    let dead_letter = sqlx::query_as!(
+        DeadLetter,
+        r#"
+        SELECT
+            id,
+            trigger_kind AS "trigger_kind: _",
+            trigger_path,
+            runnable_path,
+            is_flow,
+            args AS "args!: _",
+            failure_kind,
+            error,
+            job_id,
+            created_at,
+            edited_by,
+            edited_at,
+            replayed_by,
+            replayed_at,
+            replayed_job_id
+        FROM
+            trigger_dead_letter
+        WHERE
+            workspace_id = $1 AND id = $2
+        "#,
+        w_id,
+        id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let dead_letter = not_found_if_none(dead_letter, "Dead letter", id.to_string())?;
+    Ok(Json(dead_letter))
+}
+
+#[derive(Deserialize)]
+struct EditDeadLetter {
+    args: Box<RawValue>,
+}
+
+async fn update_dead_letter(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, i64)>,
+    Json(edit): Json<EditDeadLetter>,
+) -> Result<String> {
+    if serde_json::from_str::<HashMap<String, Box<RawValue>>>(edit.args.get()).is_err() {
+        return Err(Error::BadRequest(
+            "Dead letter args must be an object".to_string(),
+        ));
+    }
+
+    let mut tx = user_db.begin(&authed).await?;
+    let trigger_path = sqlx::query_scalar!(
+        r#"
+        UPDATE
+            trigger_dead_letter
+        SET
+            args = $1,
+            edited_by = $2,
+            edited_at = now()
+        WHERE
+            workspace_id = $3 AND id = $4
+        RETURNING
+            trigger_path
+        "#,
+        SqlxJson(&edit.args) as SqlxJson<&Box<RawValue>>,
+        authed.username,
+        w_id,
+        id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let trigger_path = not_found_if_none(trigger_path, "Dead letter", id.to_string())?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "trigger_dead_letters.update",
+        ActionKind::Update,
+        &w_id,
+        Some(&trigger_path),
+        Some([("id", id.to_string().as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Dead letter {id} updated"))
+}
+
+async fn delete_dead_letter(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, i64)>,
+) -> Result<String> {
+    let mut tx = user_db.begin(&authed).await?;
+    let trigger_path = sqlx::query_scalar!(
+        "DELETE FROM trigger_dead_letter WHERE workspace_id = $1 AND id = $2 RETURNING trigger_path",
+        w_id,
+        id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let trigger_path = not_found_if_none(trigger_path, "Dead letter", id.to_string())?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "trigger_dead_letters.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(&trigger_path),
+        Some([("id", id.to_string().as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Dead letter {id} deleted"))
+}
+
+#[derive(Deserialize)]
+struct ReplayDeadLetters {
+    ids: Vec<i64>,
+    /// Replays into this runnable instead of the one the events were originally delivered to
+    runnable_path: Option<String>,
+    is_flow: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct ReplayResult {
+    id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+async fn replay_dead_letters(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(replay): Json<ReplayDeadLetters>,
+) -> JsonResult<Vec<ReplayResult>> {
+    if replay.ids.is_empty() {
+        return Err(Error::BadRequest(
+            "At least one dead letter id is required".to_string(),
+        ));
+    }
+    if replay.runnable_path.is_some() != replay.is_flow.is_some() {
+        return Err(Error::BadRequest(
+            "runnable_path and is_flow must be set together".to_string(),
+        ));
+    }
+
+    let mut tx = user_db.clone().begin(&authed).await?;
+    let dead_letters = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            trigger_kind AS "trigger_kind: TriggerKind",
+            trigger_path,
+            runnable_path,
+            is_flow,
+            args AS "args!: SqlxJson<HashMap<String, Box<RawValue>>>"
+        FROM
+            trigger_dead_letter
+        WHERE
+            workspace_id = $1 AND id = ANY($2)
+        ORDER BY
+            created_at
+        "#,
+        w_id,
+        &replay.ids,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    if let Some(missing) = replay
+        .ids
+        .iter()
+        .find(|id| !dead_letters.iter().any(|dead_letter| dead_letter.id == **id))
+    {
+        return Err(Error::NotFound(format!("Dead letter {missing} not found")));
+    }
+
+    let mut results = Vec::with_capacity(dead_letters.len());
+    for dead_letter in dead_letters {
+        let (runnable_path, is_flow) = match (&replay.runnable_path, replay.is_flow) {
+            (Some(runnable_path), Some(is_flow)) => (runnable_path.clone(), is_flow),
+            _ => (dead_letter.runnable_path, dead_letter.is_flow),
+        };
+        let args = PushArgsOwned { extra: None, args: dead_letter.args.0 };
+        let pushed = if is_flow {
+            run_flow_by_path_inner(
+                authed.clone(),
+                db.clone(),
+                user_db.clone(),
+                w_id.clone(),
+                StripPath(runnable_path.clone()),
+                RunJobQuery::default(),
+                args,
+            )
+            .await
+        } else {
+            run_script_by_path_inner(
+                authed.clone(),
+                db.clone(),
+                user_db.clone(),
+                w_id.clone(),
+                StripPath(runnable_path.clone()),
+                RunJobQuery::default(),
+                args,
+            )
+            .await
+            .map(|(uuid, _)| uuid)
+        };
+
+        let job_id = match pushed {
+            Ok(job_id) => job_id,
+            Err(err) => {
+                results.push(ReplayResult {
+                    id: dead_letter.id,
+                    job_id: None,
+                    error: Some(err.to_string()),
+                });
+                continue;
+            }
+        };
+
+        // a replayed job that fails again lands back in the dead letters
+        let trigger = TriggerRef { kind: dead_letter.trigger_kind, path: dead_letter.trigger_path };
+        if let Err(err) = tag_triggered_job(&db, job_id, &trigger).await {
+            tracing::error!("Could not tag replayed job {job_id} with its trigger: {err:#}");
+        }
+
+        let mut tx = user_db.clone().begin(&authed).await?;
+        sqlx::query!(
+            r#"
+            UPDATE
+                trigger_dead_letter
+            SET
+                replayed_by = $1,
+                replayed_at = now(),
+                replayed_job_id = $2
+            WHERE
+                workspace_id = $3 AND id = $4
+            "#,
+            authed.username,
+            job_id,
+            w_id,
+            dead_letter.id,
+        )
+        .execute(&mut *tx)
+        .await?;
+        audit_log(
+            &mut *tx,
+            &authed,
+            "trigger_dead_letters.replay",
+            ActionKind::Execute,
+            &w_id,
+            Some(&trigger.path),
+            Some(
+                [
+                    ("id", dead_letter.id.to_string().as_str()),
+                    ("job_id", job_id.to_string().as_str()),
+                    ("runnable_path", runnable_path.as_str()),
+                ]
+                .into(),
+            ),
+        )
+        .await?;
+        tx.commit().await?;
+
+        results.push(ReplayResult { id: dead_letter.id, job_id: Some(job_id), error: None });
+    }
+
+    Ok(Json(results))
+}
+
+/// Moves the failed root jobs pushed by triggers into the dead letters and drops the expired ones
+async fn sweep_dead_letters(db: &DB) -> Result<()> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO trigger_dead_letter (
+            workspace_id,
+            trigger_kind,
+            trigger_path,
+            runnable_path,
+            is_flow,
+            args,
+            failure_kind,
+            error,
+            job_id,
+            created_at
+        )
+        SELECT
+            j.workspace_id,
+            j.trigger_kind::TEXT::TRIGGER_KIND,
+            j.trigger,
+            j.runnable_path,
+            j.kind = 'flow',
+            COALESCE(j.args, '{}'::jsonb),
+            'job',
+            COALESCE(jc.result->'error'->>'message', jc.result::TEXT, 'Job failed'),
+            j.id,
+            jc.completed_at
+        FROM
+            v2_job_completed jc
+            JOIN v2_job j ON j.id = jc.id
+        WHERE
+            jc.status = 'failure'
+            AND jc.completed_at > now() - INTERVAL '10 minutes'
+            AND j.parent_job IS NULL
+            AND j.trigger IS NOT NULL
+            AND j.runnable_path IS NOT NULL
+            AND j.trigger_kind::TEXT = ANY($1)
+            AND NOT EXISTS (
+                SELECT 1 FROM postgres_trigger pt
+                WHERE j.trigger_kind = 'postgres'
+                    AND pt.workspace_id = j.workspace_id
+                    AND pt.path = j.trigger
+                    AND pt.ack_after_success
+            )
+        ON CONFLICT (job_id) WHERE job_id IS NOT NULL DO NOTHING
+        "#,
+        &SWEPT_TRIGGER_KINDS
+            .iter()
+            .map(TriggerKind::to_key)
+            .collect::<Vec<_>>(),
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    if inserted > 0 {
+        tracing::info!("Moved {inserted} failed trigger jobs to the dead letters");
+    }
+
+    sqlx::query!(
+        "DELETE FROM trigger_dead_letter WHERE created_at < now() - make_interval(days => $1)",
+        DEAD_LETTER_RETENTION_DAYS,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub fn start_dead_letter_sweeper(db: DB, mut killpill_rx: tokio::sync::broadcast::Receiver<()>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = sweep_dead_letters(&db).await {
+                tracing::error!("Error sweeping trigger dead letters: {err:#}");
+            }
+            tokio::select! {
+                biased;
+                _ = killpill_rx.recv() => {
+                    return;
+                }
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(30)) => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trigger_ref() {
+        let trigger = TriggerRef::parse("http_trigger/f/orders/hook").unwrap();
+        assert_eq!(trigger.kind, TriggerKind::Http);
+        assert_eq!(trigger.path, "f/orders/hook");
+
+        let trigger = TriggerRef::parse("postgres_trigger/u/alice/cdc").unwrap();
+        assert_eq!(trigger.kind, TriggerKind::Postgres);
+        assert_eq!(trigger.path, "u/alice/cdc");
+
+        // schedules and webhooks are not triggers whose events are kept
+        assert!(TriggerRef::parse("schedule/f/orders/daily").is_none());
+        assert!(TriggerRef::parse("unknown_trigger/f/orders/hook").is_none());
+        assert!(TriggerRef::parse("f/orders/hook").is_none());
+    }
+}
@@ -2,8 +2,9 @@ use anyhow::Context;
 use axum::response::IntoResponse;
 use http::StatusCode;
 use serde::Deserialize;
-use serde_json::value::RawValue;
-use std::collections::HashMap;
+use quick_cache::sync::Cache;
+use serde_json::{value::RawValue, Value};
+use std::{collections::HashMap, sync::Arc};
 use uuid::Uuid;
 use windmill_common::{
     db::UserDB,
@@ -11,6 +12,7 @@ use windmill_common::{
     flows::{FlowModuleValue, Retry},
     get_latest_deployed_hash_for_path, get_latest_flow_version_info_for_path,
     jobs::{get_has_preprocessor_from_content_and_lang, script_path_to_payload, JobPayload},
+    schema::SchemaValidator,
     scripts::{get_full_hub_script_by_path, ScriptHash, ScriptLang},
     triggers::{
         HubOrWorkspaceId, RunnableFormat, RunnableFormatVersion, TriggerKind,
@@ -21,7 +23,10 @@ use windmill_common::{
     worker::to_raw_value,
     FlowVersionInfo,
 };
-use windmill_queue::{push, PushArgs, PushArgsOwned, PushIsolationLevel};
+use windmill_queue::{
+    idempotency::{begin_idempotent_push, commit_idempotent_push, IdempotencyKey, IdempotentPush},
+    push, PushArgs, PushArgsOwned, PushIsolationLevel,
+};
 
 #[cfg(feature = "enterprise")]
 use crate::jobs::check_license_key_valid;
@@ -31,6 +36,7 @@ use crate::{
         check_tag_available_for_workspace, delete_job_metadata_after_use, result_to_response,
         run_flow_by_path_inner, run_script_by_path_inner, run_wait_result_internal, RunJobQuery,
     },
+    trigger_dead_letters::{insert_push_failure, tag_triggered_job, TriggerRef},
     HTTP_CLIENT,
 };
 
@@ -190,6 +196,109 @@ fn get_preprocessor_args_from_content_and_language(
     Ok(args)
 }
 
+fn get_hub_script_version(path: &str) -> Result<i64> {
+    let Some(version) = path.split("/").nth(1) else {
+        return Err(windmill_common::error::Error::internal_err(
+            "Invalid hub script path".to_string(),
+        ));
+    };
+
+    match version.parse::<i64>() {
+        Ok(version) => Ok(version),
+        Err(_) => Err(windmill_common::error::Error::internal_err(
+            "Invalid hub script version".to_string(),
+        )),
+    }
+}
+
+type RunnableSchemaCacheKey = (HubOrWorkspaceId, i64);
+
+lazy_static::lazy_static! {
+    static ref RUNNABLE_SCHEMA_VALIDATOR_CACHE: Cache<RunnableSchemaCacheKey, Arc<Option<SchemaValidator>>> = Cache::new(1000);
+}
+
+/// Returns the json schema of the main function of the runnable (latest deployed version)
+pub async fn get_runnable_schema(
+    runnable_id: &RunnableId,
+    workspace_id: &str,
+    db: &DB,
+) -> Result<Option<String>> {
+    let schema = match runnable_id {
+        RunnableId::HubScript(path) => {
+            let hub_script =
+                get_full_hub_script_by_path(StripPath(path.to_string()), &HTTP_CLIENT, Some(db))
+                    .await?;
+            Some(hub_script.schema.get().to_string())
+        }
+        RunnableId::FlowPath(path) => {
+            let FlowVersionInfo { version, .. } =
+                get_latest_flow_version_info_for_path(db, workspace_id, path, true).await?;
+            sqlx::query_scalar!(
+                "SELECT schema::text FROM flow_version WHERE id = $1 AND workspace_id = $2",
+                version,
+                workspace_id
+            )
+            .fetch_optional(db)
+            .await?
+            .flatten()
+        }
+        RunnableId::ScriptId(script_id) => {
+            let hash = script_id.clone().get_script_hash(workspace_id, db).await?;
+            sqlx::query_scalar!(
+                "SELECT schema::text FROM script WHERE hash = $1 AND workspace_id = $2",
+                hash,
+                workspace_id
+            )
+            .fetch_optional(db)
+            .await?
+            .flatten()
+        }
+    };
+
+    Ok(schema)
+}
+
+/// Builds (and caches per runnable version) a validator for the schema of the runnable.
+/// Returns None if the runnable has no schema, and an error if its schema is not supported
+/// by the validator so that requests are not let through unchecked.
+pub async fn get_runnable_schema_validator(
+    runnable_id: &RunnableId,
+    workspace_id: &str,
+    db: &DB,
+) -> Result<Arc<Option<SchemaValidator>>> {
+    let key = match runnable_id {
+        RunnableId::HubScript(path) => (HubOrWorkspaceId::Hub, get_hub_script_version(path)?),
+        RunnableId::FlowPath(path) => {
+            let FlowVersionInfo { version, .. } =
+                get_latest_flow_version_info_for_path(db, workspace_id, path, true).await?;
+            (HubOrWorkspaceId::WorkspaceId(workspace_id.to_string()), version)
+        }
+        RunnableId::ScriptId(script_id) => {
+            let hash = script_id.clone().get_script_hash(workspace_id, db).await?;
+            (HubOrWorkspaceId::WorkspaceId(workspace_id.to_string()), hash)
+        }
+    };
+
+    if let Some(validator) = RUNNABLE_SCHEMA_VALIDATOR_CACHE.get(&key) {
+        tracing::debug!("Using cached schema validator for {runnable_id:?}");
+        return Ok(validator);
+    }
+
+    let validator = match get_runnable_schema(runnable_id, workspace_id, db).await? {
+        Some(schema) => Some(SchemaValidator::from_schema(&schema).map_err(|err| {
+            windmill_common::error::Error::internal_err(format!(
+                "Schema of {runnable_id:?} cannot be used for request validation: {err}"
+            ))
+        })?),
+        None => None,
+    };
+
+    let validator = Arc::new(validator);
+    RUNNABLE_SCHEMA_VALIDATOR_CACHE.insert(key, validator.clone());
+
+    Ok(validator)
+}
+
 pub async fn get_runnable_format(
     runnable_id: RunnableId,
     workspace_id: &str,
@@ -198,20 +307,7 @@ pub async fn get_runnable_format(
 ) -> Result<RunnableFormat> {
     let (key, preprocessor_info) = match runnable_id {
         RunnableId::HubScript(path) => {
-            let Some(version) = path.split("/").nth(1) else {
-                return Err(windmill_common::error::Error::internal_err(
-                    "Invalid hub script path".to_string(),
-                ));
-            };
-
-            let version = match version.parse::<i64>() {
-                Ok(version) => version,
-                Err(_) => {
-                    return Err(windmill_common::error::Error::internal_err(
-                        "Invalid hub script version".to_string(),
-                    ));
-                }
-            };
+            let version = get_hub_script_version(&path)?;
 
             let key = (HubOrWorkspaceId::Hub, version, trigger_kind.clone());
 
@@ -471,10 +567,19 @@ async fn trigger_runnable_inner(
     error_handler_path: Option<&str>,
     error_handler_args: Option<&sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     trigger_path: String,
+    idempotency_key: Option<&IdempotencyKey>,
 ) -> Result<(Uuid, Option<bool>)> {
+    // the args are kept to be stored as a dead letter if the job can't be pushed
+    let trigger = TriggerRef::parse(&trigger_path).map(|trigger| {
+        let args = to_raw_value(&PushArgs { args: &args.args, extra: args.extra.clone() });
+        (trigger, args)
+    });
     let user_db = user_db.unwrap_or_else(|| UserDB::new(db.clone()));
-    let (uuid, delete_after_use) = if is_flow {
-        let run_query = RunJobQuery::default();
+    let pushed = if is_flow {
+        let run_query = RunJobQuery {
+            trigger_idempotency_key: idempotency_key.cloned(),
+            ..Default::default()
+        };
         let path = StripPath(runnable_path.to_string());
         let uuid = run_flow_by_path_inner(
             authed,
@@ -485,8 +590,8 @@ async fn trigger_runnable_inner(
             run_query,
             args,
         )
-        .await?;
-        (uuid, None)
+        .await;
+        uuid.map(|uuid| (uuid, None))
     } else {
         trigger_script_internal(
             db,
@@ -499,11 +604,39 @@ async fn trigger_runnable_inner(
             error_handler_path,
             error_handler_args,
             trigger_path,
+            idempotency_key,
         )
-        .await?
+        .await
     };
 
-    Ok((uuid, delete_after_use))
+    match (&pushed, trigger) {
+        (Ok((uuid, _)), Some((trigger, _))) => {
+            if let Err(err) = tag_triggered_job(db, *uuid, &trigger).await {
+                tracing::error!("Could not tag job {uuid} with its trigger: {err:#}");
+            }
+        }
+        (Err(err), Some((trigger, args))) => {
+            if let Err(insert_err) = insert_push_failure(
+                db,
+                workspace_id,
+                &trigger,
+                runnable_path,
+                is_flow,
+                &args,
+                err,
+            )
+            .await
+            {
+                tracing::error!(
+                    "Could not store dead letter for trigger {}: {insert_err:#}",
+                    trigger.path
+                );
+            }
+        }
+        _ => {}
+    }
+
+    pushed
 }
 
 #[allow(dead_code)]
@@ -532,6 +665,7 @@ pub async fn trigger_runnable(
         error_handler_path,
         error_handler_args,
         trigger_path,
+        None,
     )
     .await?;
     Ok((StatusCode::CREATED, uuid.to_string()).into_response())
@@ -551,6 +685,40 @@ pub async fn trigger_runnable_and_wait_for_result(
     error_handler_args: Option<&sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     trigger_path: String,
 ) -> Result<axum::response::Response> {
+    let (result, success) = trigger_runnable_and_wait_for_job_result(
+        db,
+        user_db,
+        authed,
+        workspace_id,
+        runnable_path,
+        is_flow,
+        args,
+        retry,
+        error_handler_path,
+        error_handler_args,
+        trigger_path,
+    )
+    .await?;
+
+    result_to_response(result, success)
+}
+
+/// Same as `trigger_runnable_and_wait_for_result` but returns the job result and whether
+/// the job succeeded instead of the http response built from them
+#[allow(dead_code)]
+pub async fn trigger_runnable_and_wait_for_job_result(
+    db: &DB,
+    user_db: Option<UserDB>,
+    authed: ApiAuthed,
+    workspace_id: &str,
+    runnable_path: &str,
+    is_flow: bool,
+    args: PushArgsOwned,
+    retry: Option<&sqlx::types::Json<Retry>>,
+    error_handler_path: Option<&str>,
+    error_handler_args: Option<&sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
+    trigger_path: String,
+) -> Result<(Box<RawValue>, bool)> {
     let username = authed.username.clone();
     let (uuid, delete_after_use) = trigger_runnable_inner(
         db,
@@ -564,16 +732,63 @@ pub async fn trigger_runnable_and_wait_for_result(
         error_handler_path,
         error_handler_args,
         trigger_path,
+        None,
     )
     .await?;
+
+    wait_for_job_result(db, uuid, workspace_id, &username, delete_after_use).await
+}
+
+/// Triggers the runnable unless a job was already triggered for it with the same idempotency key,
+/// in which case the id of that job is returned instead
+#[allow(dead_code)]
+pub async fn trigger_runnable_idempotent(
+    db: &DB,
+    user_db: Option<UserDB>,
+    authed: ApiAuthed,
+    workspace_id: &str,
+    runnable_path: &str,
+    is_flow: bool,
+    args: PushArgsOwned,
+    retry: Option<&sqlx::types::Json<Retry>>,
+    error_handler_path: Option<&str>,
+    error_handler_args: Option<&sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
+    trigger_path: String,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> Result<(Uuid, Option<bool>)> {
+    trigger_runnable_inner(
+        db,
+        user_db,
+        authed,
+        workspace_id,
+        runnable_path,
+        is_flow,
+        args,
+        retry,
+        error_handler_path,
+        error_handler_args,
+        trigger_path,
+        idempotency_key,
+    )
+    .await
+}
+
+#[allow(dead_code)]
+pub async fn wait_for_job_result(
+    db: &DB,
+    uuid: Uuid,
+    workspace_id: &str,
+    username: &str,
+    delete_after_use: Option<bool>,
+) -> Result<(Box<RawValue>, bool)> {
     let (result, success) =
-        run_wait_result_internal(db, uuid, workspace_id.to_string(), None, &username).await?;
+        run_wait_result_internal(db, uuid, workspace_id.to_string(), None, username).await?;
 
     if delete_after_use.unwrap_or(false) {
         delete_job_metadata_after_use(&db, uuid).await?;
     }
 
-    result_to_response(result, success)
+    Ok((result, success))
 }
 
 #[allow(dead_code)]
@@ -603,6 +818,7 @@ pub async fn trigger_runnable_and_wait_for_raw_result(
         error_handler_path,
         error_handler_args,
         trigger_path,
+        None,
     )
     .await?;
 
@@ -660,9 +876,13 @@ async fn trigger_script_internal(
     error_handler_path: Option<&str>,
     error_handler_args: Option<&sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     trigger_path: String,
+    idempotency_key: Option<&IdempotencyKey>,
 ) -> Result<(Uuid, Option<bool>)> {
     if retry.is_none() && error_handler_path.is_none() {
-        let run_query = RunJobQuery::default();
+        let run_query = RunJobQuery {
+            trigger_idempotency_key: idempotency_key.cloned(),
+            ..Default::default()
+        };
         let path = StripPath(script_path.to_string());
         run_script_by_path_inner(
             authed,
@@ -686,6 +906,7 @@ async fn trigger_script_internal(
             error_handler_path,
             error_handler_args,
             trigger_path,
+            idempotency_key,
         )
         .await
     }
@@ -702,6 +923,7 @@ async fn trigger_script_with_retry_and_error_handler(
     error_handler_path: Option<&str>,
     error_handler_args: Option<&sqlx::types::Json<HashMap<String, Box<RawValue>>>>,
     trigger_path: String,
+    idempotency_key: Option<&IdempotencyKey>,
 ) -> Result<(Uuid, Option<bool>)> {
     let retry = retry.map(|r| r.0.clone());
     let error_handler_path = error_handler_path.map(|p| p.to_string());
@@ -771,33 +993,63 @@ async fn trigger_script_with_retry_and_error_handler(
         }
     };
 
-    let (uuid, tx) = push(
-        &db,
-        tx,
-        &workspace_id,
-        retryable_job_payload,
-        push_args,
-        authed.display_username(),
-        email,
-        permissioned_as,
-        authed.token_prefix.as_deref(),
-        None,
-        None,
-        None,
-        None,
-        None,
-        false,
-        false,
-        None,
-        true,
-        tag,
-        timeout,
-        None,
-        None,
-        push_authed.as_ref(),
-    )
-    .await?;
-    tx.commit().await?;
+    let uuid = match begin_idempotent_push(tx, idempotency_key).await? {
+        IdempotentPush::Existing(uuid) => uuid,
+        IdempotentPush::Push(tx) => {
+            let (uuid, tx) = push(
+                &db,
+                tx,
+                &workspace_id,
+                retryable_job_payload,
+                push_args,
+                authed.display_username(),
+                email,
+                permissioned_as,
+                authed.token_prefix.as_deref(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                false,
+                None,
+                true,
+                tag,
+                timeout,
+                None,
+                None,
+                push_authed.as_ref(),
+            )
+            .await?;
+            commit_idempotent_push(tx, idempotency_key, uuid).await?;
+            uuid
+        }
+    };
 
     Ok((uuid, delete_after_use))
 }
+
+// Function to check if json_value is a superset of value_to_check
+#[allow(dead_code)]
+pub fn is_json_superset(json_value: &Value, value_to_check: &Value) -> bool {
+    match (json_value, value_to_check) {
+        (Value::Object(json_map), Value::Object(check_map)) => {
+            // Check that all keys and values in check_map exist and match in json_map
+            check_map.iter().all(|(k, v)| {
+                json_map
+                    .get(k)
+                    .map_or(false, |json_val| is_json_superset(json_val, v))
+            })
+        }
+        (Value::Array(json_array), Value::Array(check_array)) => {
+            // Check that all elements in check_array exist in json_array
+            check_array.iter().all(|check_item| {
+                json_array
+                    .iter()
+                    .any(|json_item| is_json_superset(json_item, check_item))
+            })
+        }
+        _ => json_value == value_to_check,
+    }
+}
@@ -0,0 +1,529 @@
+//! Generic CRUD routes of the triggers listening to an external service. Each trigger kind only
+//! declares its table, its config columns and how they are validated, see `TriggerCrud`.
+
+use std::{collections::HashMap, fmt::Debug};
+
+use axum::{
+    async_trait,
+    extract::{Path, Query},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use http::StatusCode;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sql_builder::{bind::Bind, SqlBuilder};
+use sqlx::{
+    postgres::{PgArguments, PgRow},
+    types::Json as SqlxJson,
+    FromRow, Postgres,
+};
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    flows::Retry,
+    triggers::TriggerKind,
+    utils::{not_found_if_none, paginate, Pagination, StripPath},
+    worker::CLOUD_HOSTED,
+};
+use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
+
+use crate::{
+    db::{ApiAuthed, DB},
+    utils::check_scopes,
+};
+
+pub type PgQuery<'q> = sqlx::query::Query<'q, Postgres, PgArguments>;
+
+/// Columns shared by all the trigger tables
+#[derive(Debug, Serialize, FromRow)]
+pub struct BaseTrigger {
+    pub path: String,
+    pub script_path: String,
+    pub is_flow: bool,
+    pub workspace_id: String,
+    pub edited_by: String,
+    pub email: String,
+    pub edited_at: chrono::DateTime<chrono::Utc>,
+    pub extra_perms: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub server_id: Option<String>,
+    pub last_server_ping: Option<chrono::DateTime<chrono::Utc>>,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_handler_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<SqlxJson<Retry>>,
+}
+
+/// A trigger row, serialized as a single object with the config columns next to the base ones
+#[derive(Debug, Serialize)]
+pub struct Trigger<C> {
+    #[serde(flatten)]
+    pub base: BaseTrigger,
+    #[serde(flatten)]
+    pub config: C,
+}
+
+impl<'r, C: FromRow<'r, PgRow>> FromRow<'r, PgRow> for Trigger<C> {
+    fn from_row(row: &'r PgRow) -> sqlx::Result<Self> {
+        Ok(Self { base: BaseTrigger::from_row(row)?, config: C::from_row(row)? })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BaseTriggerData {
+    pub path: String,
+    pub script_path: String,
+    pub is_flow: bool,
+    pub enabled: Option<bool>,
+    pub error_handler_path: Option<String>,
+    pub error_handler_args: Option<SqlxJson<HashMap<String, Box<RawValue>>>>,
+    pub retry: Option<SqlxJson<Retry>>,
+}
+
+/// Body of the create and update requests
+#[derive(Debug)]
+pub struct TriggerData<C> {
+    pub base: BaseTriggerData,
+    pub config: C,
+}
+
+impl<C: DeserializeOwned> TriggerData<C> {
+    /// The body is parsed twice rather than with `#[serde(flatten)]`, which cannot deserialize
+    /// the raw values of the error handler args
+    fn parse(body: &RawValue) -> Result<Self> {
+        let invalid = |e: serde_json::Error| Error::BadRequest(format!("Invalid trigger: {e}"));
+        Ok(Self {
+            base: serde_json::from_str(body.get()).map_err(invalid)?,
+            config: serde_json::from_str(body.get()).map_err(invalid)?,
+        })
+    }
+}
+
+/// A kind of trigger stored in its own `<kind>_trigger` table, whose rows are the columns of
+/// `BaseTrigger` and the `CONFIG_COLUMNS` of the kind
+#[async_trait]
+pub trait TriggerCrud: Send + Sync + Sized + 'static {
+    /// Table of the triggers, also the prefix of the trigger path of their jobs
+    const TABLE_NAME: &'static str;
+    /// Scope domain and audit log prefix of the routes, e.g. `amqp_triggers`
+    const ROUTE_PREFIX: &'static str;
+    /// Used in the messages, e.g. `Redis`
+    const DISPLAY_NAME: &'static str;
+    const KIND: TriggerKind;
+    const SUPPORTED_ON_CLOUD: bool = false;
+    /// Columns written on create and update, in the order they are bound by `bind_config`
+    const CONFIG_COLUMNS: &'static [&'static str];
+    /// Appended to the `SET` clause of the update, where the config columns are bound from `$11`
+    /// in the order of `CONFIG_COLUMNS`
+    const EXTRA_UPDATE_ASSIGNMENTS: &'static str = "";
+
+    /// The config columns, deserialized from the request with their defaults applied
+    type Config: for<'r> FromRow<'r, PgRow>
+        + Serialize
+        + DeserializeOwned
+        + Debug
+        + Send
+        + Sync
+        + Unpin
+        + 'static;
+
+    fn deployed_object(path: String) -> DeployedObject;
+
+    fn validate_config(config: &Self::Config) -> Result<()>;
+
+    /// Called once the config is validated, before the trigger is created or updated, e.g. to
+    /// set up what the trigger listens to on the service. `path` is the path of the updated
+    /// trigger, `None` on create.
+    async fn prepare_config(
+        _authed: &ApiAuthed,
+        _user_db: &UserDB,
+        _db: &DB,
+        _w_id: &str,
+        _path: Option<&str>,
+        _config: &mut Self::Config,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn bind_config<'q>(config: &'q Self::Config, query: PgQuery<'q>) -> PgQuery<'q>;
+}
+
+pub fn trigger_routes<T: TriggerCrud>() -> Router {
+    Router::new()
+        .route("/create", post(create_trigger::<T>))
+        .route("/list", get(list_triggers::<T>))
+        .route("/get/*path", get(get_trigger::<T>))
+        .route("/update/*path", post(update_trigger::<T>))
+        .route("/delete/*path", delete(delete_trigger::<T>))
+        .route("/exists/*path", get(exists_trigger::<T>))
+        .route("/setenabled/*path", post(set_enabled::<T>))
+}
+
+/// `, $<first>, $<first + 1>, ...` for each config column
+fn config_placeholders<T: TriggerCrud>(first: usize) -> String {
+    (0..T::CONFIG_COLUMNS.len())
+        .map(|i| format!(", ${}", first + i))
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct ListTriggerQuery {
+    page: Option<usize>,
+    per_page: Option<usize>,
+    path: Option<String>,
+    is_flow: Option<bool>,
+    path_start: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SetEnabled {
+    enabled: bool,
+}
+
+async fn create_trigger<T: TriggerCrud>(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(body): Json<Box<RawValue>>,
+) -> Result<(StatusCode, String)> {
+    let TriggerData { base, mut config } = TriggerData::<T::Config>::parse(&body)?;
+    check_scopes(&authed, || {
+        format!("{}:write:{}", T::ROUTE_PREFIX, &base.path)
+    })?;
+    if *CLOUD_HOSTED && !T::SUPPORTED_ON_CLOUD {
+        return Err(Error::BadRequest(format!(
+            "{} triggers are not supported on multi-tenant cloud, use dedicated cloud or self-host",
+            T::DISPLAY_NAME
+        )));
+    }
+    T::validate_config(&config)?;
+    T::prepare_config(&authed, &user_db, &db, &w_id, None, &mut config).await?;
+
+    let sql = format!(
+        "INSERT INTO {} (workspace_id, path, script_path, is_flow, email, enabled, edited_by, \
+         error_handler_path, error_handler_args, retry{}) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10{})",
+        T::TABLE_NAME,
+        T::CONFIG_COLUMNS
+            .iter()
+            .map(|column| format!(", {column}"))
+            .collect::<String>(),
+        config_placeholders::<T>(11)
+    );
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    let query = sqlx::query(&sql)
+        .bind(&w_id)
+        .bind(&base.path)
+        .bind(&base.script_path)
+        .bind(base.is_flow)
+        .bind(&authed.email)
+        .bind(base.enabled.unwrap_or(true))
+        .bind(&authed.username)
+        .bind(&base.error_handler_path)
+        .bind(&base.error_handler_args)
+        .bind(&base.retry);
+    T::bind_config(&config, query).execute(&mut *tx).await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        &format!("{}.create", T::ROUTE_PREFIX),
+        ActionKind::Create,
+        &w_id,
+        Some(base.path.as_str()),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        T::deployed_object(base.path.clone()),
+        Some(format!(
+            "{} trigger '{}' created",
+            T::DISPLAY_NAME,
+            base.path
+        )),
+        true,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, base.path))
+}
+
+async fn list_triggers<T: TriggerCrud>(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Query(lst): Query<ListTriggerQuery>,
+) -> JsonResult<Vec<Trigger<T::Config>>> {
+    let mut tx = user_db.begin(&authed).await?;
+    let (per_page, offset) = paginate(Pagination { per_page: lst.per_page, page: lst.page });
+    let mut sqlb = SqlBuilder::select_from(T::TABLE_NAME)
+        .field("*")
+        .order_by("edited_at", true)
+        .and_where("workspace_id = ?".bind(&w_id))
+        .offset(offset)
+        .limit(per_page)
+        .clone();
+    if let Some(path) = lst.path {
+        sqlb.and_where_eq("script_path", "?".bind(&path));
+    }
+    if let Some(is_flow) = lst.is_flow {
+        sqlb.and_where_eq("is_flow", "?".bind(&is_flow));
+    }
+    if let Some(path_start) = &lst.path_start {
+        sqlb.and_where_like_left("path", path_start);
+    }
+    let sql = sqlb.sql().map_err(|e| Error::InternalErr(e.to_string()))?;
+    let rows = sqlx::query_as::<_, Trigger<T::Config>>(&sql)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| {
+            tracing::debug!("Error fetching {}: {:#?}", T::TABLE_NAME, e);
+            Error::InternalErr("server error".to_string())
+        })?;
+    tx.commit().await?;
+
+    Ok(Json(rows))
+}
+
+async fn get_trigger<T: TriggerCrud>(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<Trigger<T::Config>> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("{}:read:{}", T::ROUTE_PREFIX, path))?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    let trigger = sqlx::query_as::<_, Trigger<T::Config>>(&format!(
+        "SELECT * FROM {} WHERE workspace_id = $1 AND path = $2",
+        T::TABLE_NAME
+    ))
+    .bind(&w_id)
+    .bind(path)
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let trigger = not_found_if_none(trigger, &format!("{} trigger", T::DISPLAY_NAME), path)?;
+
+    Ok(Json(trigger))
+}
+
+async fn update_trigger<T: TriggerCrud>(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(body): Json<Box<RawValue>>,
+) -> Result<String> {
+    let workspace_path = path.to_path();
+    check_scopes(&authed, || {
+        format!("{}:write:{}", T::ROUTE_PREFIX, workspace_path)
+    })?;
+    let TriggerData { base, mut config } = TriggerData::<T::Config>::parse(&body)?;
+    T::validate_config(&config)?;
+    T::prepare_config(
+        &authed,
+        &user_db,
+        &db,
+        &w_id,
+        Some(workspace_path),
+        &mut config,
+    )
+    .await?;
+
+    // important to set server_id to NULL to stop the current listener
+    let sql = format!(
+        "UPDATE {} SET script_path = $1, path = $2, is_flow = $3, edited_by = $4, email = $5, \
+         error_handler_path = $6, error_handler_args = $7, retry = $8, edited_at = now(), \
+         error = NULL, server_id = NULL{}{} WHERE workspace_id = $9 AND path = $10",
+        T::TABLE_NAME,
+        T::CONFIG_COLUMNS
+            .iter()
+            .enumerate()
+            .map(|(i, column)| format!(", {column} = ${}", i + 11))
+            .collect::<String>(),
+        T::EXTRA_UPDATE_ASSIGNMENTS
+    );
+
+    let mut tx = user_db.begin(&authed).await?;
+
+    let query = sqlx::query(&sql)
+        .bind(&base.script_path)
+        .bind(&base.path)
+        .bind(base.is_flow)
+        .bind(&authed.username)
+        .bind(&authed.email)
+        .bind(&base.error_handler_path)
+        .bind(&base.error_handler_args)
+        .bind(&base.retry)
+        .bind(&w_id)
+        .bind(workspace_path);
+    T::bind_config(&config, query).execute(&mut *tx).await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        &format!("{}.update", T::ROUTE_PREFIX),
+        ActionKind::Update,
+        &w_id,
+        Some(&base.path),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        T::deployed_object(base.path.clone()),
+        Some(format!(
+            "{} trigger '{}' updated",
+            T::DISPLAY_NAME,
+            base.path
+        )),
+        true,
+    )
+    .await?;
+
+    Ok(base.path)
+}
+
+async fn delete_trigger<T: TriggerCrud>(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> Result<String> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("{}:write:{}", T::ROUTE_PREFIX, path))?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    sqlx::query(&format!(
+        "DELETE FROM {} WHERE workspace_id = $1 AND path = $2",
+        T::TABLE_NAME
+    ))
+    .bind(&w_id)
+    .bind(path)
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        &format!("{}.delete", T::ROUTE_PREFIX),
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        T::deployed_object(path.to_string()),
+        Some(format!("{} trigger '{}' deleted", T::DISPLAY_NAME, path)),
+        true,
+    )
+    .await?;
+
+    Ok(format!("{} trigger {path} deleted", T::DISPLAY_NAME))
+}
+
+async fn exists_trigger<T: TriggerCrud>(
+    Extension(db): Extension<DB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<bool> {
+    let path = path.to_path();
+    let exists = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT EXISTS(SELECT 1 FROM {} WHERE path = $1 AND workspace_id = $2)",
+        T::TABLE_NAME
+    ))
+    .bind(path)
+    .bind(&w_id)
+    .fetch_one(&db)
+    .await?;
+    Ok(Json(exists))
+}
+
+async fn set_enabled<T: TriggerCrud>(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(payload): Json<SetEnabled>,
+) -> Result<String> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("{}:write:{}", T::ROUTE_PREFIX, path))?;
+    let mut tx = user_db.begin(&authed).await?;
+
+    // important to set server_id and error to NULL to stop the current listener
+    let one_o = sqlx::query_scalar::<_, i32>(&format!(
+        "UPDATE {} SET enabled = $1, email = $2, edited_by = $3, edited_at = now(), \
+         server_id = NULL, error = NULL WHERE path = $4 AND workspace_id = $5 RETURNING 1",
+        T::TABLE_NAME
+    ))
+    .bind(payload.enabled)
+    .bind(&authed.email)
+    .bind(&authed.username)
+    .bind(path)
+    .bind(&w_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    not_found_if_none(one_o, &format!("{} trigger", T::DISPLAY_NAME), path)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        &format!("{}.setenabled", T::ROUTE_PREFIX),
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some([("enabled", payload.enabled.to_string().as_ref())].into()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    handle_deployment_metadata(
+        &authed.email,
+        &authed.username,
+        &db,
+        &w_id,
+        T::deployed_object(path.to_string()),
+        Some(format!("{} trigger '{}' updated", T::DISPLAY_NAME, path)),
+        true,
+    )
+    .await?;
+
+    Ok(format!(
+        "successfully updated {} trigger at path {} to status {}",
+        T::DISPLAY_NAME.to_lowercase(),
+        path,
+        payload.enabled
+    ))
+}
@@ -0,0 +1,576 @@
+//! Lifecycle of the triggers and captures listening to an external service: leasing them to a
+//! single server, pinging while listening, disabling them on error and pushing their jobs.
+
+use std::fmt::Display;
+
+use axum::async_trait;
+use rand::seq::SliceRandom;
+use serde::de::DeserializeOwned;
+use sqlx::{postgres::PgRow, types::Json as SqlxJson, FromRow, Row};
+use uuid::Uuid;
+use windmill_common::{
+    error::Result, triggers::TriggerKind, utils::report_critical_error, INSTANCE_NAME,
+};
+use windmill_queue::{idempotency::IdempotencyKey, PushArgsOwned};
+
+use crate::{
+    capture::insert_capture_payload,
+    db::{ApiAuthed, DB},
+    trigger_helpers::trigger_runnable_idempotent,
+    triggers::handler::{BaseTrigger, Trigger, TriggerCrud},
+    users::fetch_api_authed,
+};
+
+/// A capture config of the kind of the listener, with its `trigger_config`
+#[derive(Debug)]
+pub struct CaptureConfigForListener<C> {
+    pub trigger_config: C,
+    pub path: String,
+    pub is_flow: bool,
+    pub workspace_id: String,
+    pub owner: String,
+    pub email: String,
+}
+
+impl<'r, C: DeserializeOwned> FromRow<'r, PgRow> for CaptureConfigForListener<C> {
+    fn from_row(row: &'r PgRow) -> sqlx::Result<Self> {
+        Ok(Self {
+            trigger_config: row.try_get::<SqlxJson<C>, _>("trigger_config")?.0,
+            path: row.try_get("path")?,
+            is_flow: row.try_get("is_flow")?,
+            workspace_id: row.try_get("workspace_id")?,
+            owner: row.try_get("owner")?,
+            email: row.try_get("email")?,
+        })
+    }
+}
+
+impl<C> CaptureConfigForListener<C> {
+    /// Errors are only logged, a capture is best effort
+    pub async fn insert_payload(
+        &self,
+        db: &DB,
+        kind: &TriggerKind,
+        main_args: PushArgsOwned,
+        preprocessor_args: PushArgsOwned,
+    ) {
+        if let Err(err) = insert_capture_payload(
+            db,
+            &self.workspace_id,
+            &self.path,
+            self.is_flow,
+            kind,
+            main_args,
+            preprocessor_args,
+            &self.owner,
+        )
+        .await
+        {
+            tracing::error!("Error inserting capture payload: {:?}", err);
+        }
+    }
+}
+
+pub enum ListeningConfig<T: Listener> {
+    Trigger(Trigger<T::Config>),
+    Capture(CaptureConfigForListener<T::CaptureConfig>),
+}
+
+/// A trigger kind whose triggers and captures each hold a long-lived connection to the service
+/// they listen to. Each of them is listened to by a single server at a time.
+#[async_trait]
+pub trait Listener: TriggerCrud {
+    /// The `trigger_config` of the capture configs of the kind
+    type CaptureConfig: DeserializeOwned + Send + Sync + Unpin + 'static;
+    /// What `connect` hands over to `consume`
+    type Connection: Send;
+    type Error: Display + Send + From<windmill_common::error::Error>;
+
+    /// Called while the error of the trigger is `Connecting...`
+    async fn connect(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+    ) -> std::result::Result<Self::Connection, Self::Error>;
+
+    /// Handles the events until an error, which disables the trigger or capture. Returning
+    /// `Ok(())` stops listening without an error, the trigger or capture is listened to again
+    /// once its lease expired.
+    async fn consume(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+        connection: Self::Connection,
+    ) -> std::result::Result<(), Self::Error>;
+
+    /// Called once the server stopped listening, for whatever reason
+    async fn cleanup(
+        _db: &DB,
+        _config: &ListeningConfig<Self>,
+    ) -> std::result::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Pushes the job of a trigger, with its retry and error handler, under the trigger path
+/// `<table>/<path>` used to tag the job and store its dead letters
+pub async fn trigger_job<T: TriggerCrud>(
+    db: &DB,
+    trigger: &BaseTrigger,
+    args: PushArgsOwned,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> Result<Uuid> {
+    let authed = fetch_trigger_authed::<T>(db, trigger).await?;
+    let (uuid, _) = trigger_runnable_idempotent(
+        db,
+        None,
+        authed,
+        &trigger.workspace_id,
+        &trigger.script_path,
+        trigger.is_flow,
+        args,
+        trigger.retry.as_ref(),
+        trigger.error_handler_path.as_deref(),
+        trigger.error_handler_args.as_ref(),
+        format!("{}/{}", T::TABLE_NAME, trigger.path),
+        idempotency_key,
+    )
+    .await?;
+    Ok(uuid)
+}
+
+pub async fn fetch_trigger_authed<T: TriggerCrud>(
+    db: &DB,
+    trigger: &BaseTrigger,
+) -> Result<ApiAuthed> {
+    fetch_api_authed(
+        trigger.edited_by.clone(),
+        trigger.email.clone(),
+        &trigger.workspace_id,
+        db,
+        Some(format!("{}-{}", T::KIND.to_key(), trigger.path)),
+    )
+    .await
+}
+
+impl<T: Listener> ListeningConfig<T> {
+    pub fn path(&self) -> &str {
+        match self {
+            ListeningConfig::Trigger(trigger) => &trigger.base.path,
+            ListeningConfig::Capture(capture) => &capture.path,
+        }
+    }
+
+    pub fn workspace_id(&self) -> &str {
+        match self {
+            ListeningConfig::Trigger(trigger) => &trigger.base.workspace_id,
+            ListeningConfig::Capture(capture) => &capture.workspace_id,
+        }
+    }
+
+    pub async fn fetch_authed(&self, db: &DB) -> Result<ApiAuthed> {
+        match self {
+            ListeningConfig::Trigger(trigger) => fetch_trigger_authed::<T>(db, &trigger.base).await,
+            ListeningConfig::Capture(capture) => {
+                fetch_api_authed(
+                    capture.owner.clone(),
+                    capture.email.clone(),
+                    &capture.workspace_id,
+                    db,
+                    Some(format!(
+                        "{}-{}-{}",
+                        T::KIND.to_key(),
+                        if capture.is_flow { "flow" } else { "script" },
+                        capture.path
+                    )),
+                )
+                .await
+            }
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            ListeningConfig::Trigger(trigger) => {
+                format!("{} trigger {}", T::DISPLAY_NAME, trigger.base.path)
+            }
+            ListeningConfig::Capture(capture) => {
+                format!("{} capture {}", T::DISPLAY_NAME, capture.path)
+            }
+        }
+    }
+
+    /// Takes the lease of the trigger or capture if no other server pinged it recently
+    async fn try_to_acquire(&self, db: &DB) -> sqlx::Result<bool> {
+        let acquired = match self {
+            ListeningConfig::Trigger(trigger) => {
+                sqlx::query_scalar::<_, bool>(&format!(
+                    "UPDATE {} SET server_id = $1, last_server_ping = now(), \
+                     error = 'Connecting...' WHERE enabled IS TRUE AND workspace_id = $2 \
+                     AND path = $3 AND (last_server_ping IS NULL \
+                     OR last_server_ping < now() - INTERVAL '15 seconds') RETURNING true",
+                    T::TABLE_NAME
+                ))
+                .bind(&*INSTANCE_NAME)
+                .bind(&trigger.base.workspace_id)
+                .bind(&trigger.base.path)
+                .fetch_optional(db)
+                .await?
+            }
+            ListeningConfig::Capture(capture) => {
+                sqlx::query_scalar::<_, bool>(
+                    r#"
+                    UPDATE
+                        capture_config
+                    SET
+                        server_id = $1,
+                        last_server_ping = now(),
+                        error = 'Connecting...'
+                    WHERE
+                        last_client_ping > NOW() - INTERVAL '10 seconds' AND
+                        workspace_id = $2 AND
+                        path = $3 AND
+                        is_flow = $4 AND
+                        trigger_kind = $5 AND
+                        (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
+                    RETURNING true
+                    "#,
+                )
+                .bind(&*INSTANCE_NAME)
+                .bind(&capture.workspace_id)
+                .bind(&capture.path)
+                .bind(capture.is_flow)
+                .bind(T::KIND)
+                .fetch_optional(db)
+                .await?
+            }
+        };
+        Ok(acquired.unwrap_or(false))
+    }
+
+    /// Returns `None` once the server lost the lease, i.e. the trigger was changed, disabled
+    /// or deleted, or the capture is no longer polled by a client
+    async fn update_ping(&self, db: &DB, error: Option<&str>) -> Option<()> {
+        let updated = match self {
+            ListeningConfig::Trigger(trigger) => {
+                sqlx::query_scalar::<_, i32>(&format!(
+                    "UPDATE {} SET last_server_ping = now(), error = $1 WHERE workspace_id = $2 \
+                     AND path = $3 AND server_id = $4 AND enabled IS TRUE RETURNING 1",
+                    T::TABLE_NAME
+                ))
+                .bind(error)
+                .bind(&trigger.base.workspace_id)
+                .bind(&trigger.base.path)
+                .bind(&*INSTANCE_NAME)
+                .fetch_optional(db)
+                .await
+            }
+            ListeningConfig::Capture(capture) => {
+                sqlx::query_scalar::<_, i32>(
+                    r#"
+                    UPDATE
+                        capture_config
+                    SET
+                        last_server_ping = now(),
+                        error = $1
+                    WHERE
+                        workspace_id = $2 AND
+                        path = $3 AND
+                        is_flow = $4 AND
+                        trigger_kind = $5 AND
+                        server_id = $6 AND
+                        last_client_ping > NOW() - INTERVAL '10 seconds'
+                    RETURNING 1
+                    "#,
+                )
+                .bind(error)
+                .bind(&capture.workspace_id)
+                .bind(&capture.path)
+                .bind(capture.is_flow)
+                .bind(T::KIND)
+                .bind(&*INSTANCE_NAME)
+                .fetch_optional(db)
+                .await
+            }
+        };
+
+        match updated {
+            Ok(Some(_)) => Some(()),
+            Ok(None) => {
+                // allow faster restart
+                let reset = match self {
+                    ListeningConfig::Trigger(trigger) => {
+                        sqlx::query(&format!(
+                            "UPDATE {} SET last_server_ping = NULL WHERE workspace_id = $1 \
+                             AND path = $2 AND server_id IS NULL",
+                            T::TABLE_NAME
+                        ))
+                        .bind(&trigger.base.workspace_id)
+                        .bind(&trigger.base.path)
+                        .execute(db)
+                        .await
+                    }
+                    ListeningConfig::Capture(capture) => {
+                        sqlx::query(
+                            r#"
+                            UPDATE
+                                capture_config
+                            SET
+                                last_server_ping = NULL
+                            WHERE
+                                workspace_id = $1 AND
+                                path = $2 AND
+                                is_flow = $3 AND
+                                trigger_kind = $4 AND
+                                server_id IS NULL
+                            "#,
+                        )
+                        .bind(&capture.workspace_id)
+                        .bind(&capture.path)
+                        .bind(capture.is_flow)
+                        .bind(T::KIND)
+                        .execute(db)
+                        .await
+                    }
+                };
+                reset.ok();
+                tracing::info!(
+                    "{} changed, disabled, or deleted, stopping...",
+                    self.description()
+                );
+                None
+            }
+            Err(err) => {
+                tracing::warn!("Error updating ping of {}: {:?}", self.description(), err);
+                Some(())
+            }
+        }
+    }
+
+    /// Records an error without disabling the trigger or capture, for the errors after which
+    /// `consume` returns `Ok(())` to listen again later
+    pub async fn report_error(&self, db: &DB, error: &str) {
+        self.update_ping(db, Some(error)).await;
+    }
+
+    async fn loop_ping(&self, db: &DB, error: Option<&str>) {
+        loop {
+            if self.update_ping(db, error).await.is_none() {
+                return;
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        }
+    }
+
+    pub async fn disable_with_error(&self, db: &DB, error: String) {
+        match self {
+            ListeningConfig::Trigger(trigger) => {
+                let disabled = sqlx::query(&format!(
+                    "UPDATE {} SET enabled = FALSE, error = $1, server_id = NULL, \
+                     last_server_ping = NULL WHERE workspace_id = $2 AND path = $3",
+                    T::TABLE_NAME
+                ))
+                .bind(&error)
+                .bind(&trigger.base.workspace_id)
+                .bind(&trigger.base.path)
+                .execute(db)
+                .await;
+                let message = match disabled {
+                    Ok(_) => format!(
+                        "Disabling {} because of error: {}",
+                        self.description(),
+                        error
+                    ),
+                    Err(disable_err) => format!(
+                        "Could not disable {} with err {}, disabling because of error {}",
+                        self.description(),
+                        disable_err,
+                        error
+                    ),
+                };
+                report_critical_error(message, db.clone(), Some(&trigger.base.workspace_id), None)
+                    .await;
+            }
+            ListeningConfig::Capture(capture) => {
+                if let Err(err) = sqlx::query(
+                    r#"
+                    UPDATE
+                        capture_config
+                    SET
+                        error = $1,
+                        server_id = NULL,
+                        last_server_ping = NULL
+                    WHERE
+                        workspace_id = $2 AND
+                        path = $3 AND
+                        is_flow = $4 AND
+                        trigger_kind = $5
+                    "#,
+                )
+                .bind(&error)
+                .bind(&capture.workspace_id)
+                .bind(&capture.path)
+                .bind(capture.is_flow)
+                .bind(T::KIND)
+                .execute(db)
+                .await
+                {
+                    tracing::error!(
+                        "Could not disable {} ({}) with err {}, disabling because of error {}",
+                        self.description(),
+                        capture.workspace_id,
+                        err,
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    async fn listen(self, db: DB, killpill_rx: tokio::sync::broadcast::Receiver<()>) {
+        self.listen_until_stopped(&db, killpill_rx).await;
+        if let Err(err) = T::cleanup(&db, &self).await {
+            tracing::error!("Error cleaning up {}: {}", self.description(), err);
+        }
+    }
+
+    /// Pings with `Connecting...` until connected, then without error while consuming
+    async fn listen_until_stopped(
+        &self,
+        db: &DB,
+        mut killpill_rx: tokio::sync::broadcast::Receiver<()>,
+    ) {
+        tokio::select! {
+            biased;
+
+            _ = killpill_rx.recv() => {
+                return;
+            }
+
+            _ = self.loop_ping(db, Some("Connecting...")) => {
+                return;
+            }
+
+            result = T::connect(db, self) => {
+                tokio::select! {
+                    biased;
+
+                    _ = killpill_rx.recv() => {
+                        return;
+                    }
+
+                    _ = self.loop_ping(db, None) => {
+                        return;
+                    }
+
+                    _ = async {
+                        let result = match result {
+                            Ok(connection) => T::consume(db, self, connection).await,
+                            Err(err) => Err(err),
+                        };
+                        if let Err(err) = result {
+                            tracing::error!("{} error while listening: {}", self.description(), &err);
+                            self.disable_with_error(db, err.to_string()).await
+                        }
+                    } => {}
+                }
+            }
+        }
+    }
+
+    async fn try_to_listen(self, db: DB, killpill_rx: tokio::sync::broadcast::Receiver<()>) {
+        match self.try_to_acquire(&db).await {
+            Ok(true) => {
+                tracing::info!("Spawning new task to listen to {}", self.description());
+                tokio::spawn(self.listen(db, killpill_rx));
+            }
+            Ok(false) => {
+                tracing::info!("{} already being listened to", self.description());
+            }
+            Err(err) => {
+                tracing::error!("Error acquiring lock for {}: {:?}", self.description(), err);
+            }
+        }
+    }
+}
+
+async fn listen_to_unlistened<T: Listener>(
+    db: &DB,
+    killpill_rx: &tokio::sync::broadcast::Receiver<()>,
+) {
+    let triggers = sqlx::query_as::<_, Trigger<T::Config>>(&format!(
+        "SELECT * FROM {} WHERE enabled IS TRUE AND (last_server_ping IS NULL \
+         OR last_server_ping < now() - interval '15 seconds')",
+        T::TABLE_NAME
+    ))
+    .fetch_all(db)
+    .await;
+
+    match triggers {
+        Ok(mut triggers) => {
+            triggers.shuffle(&mut rand::rng());
+            for trigger in triggers {
+                ListeningConfig::<T>::Trigger(trigger)
+                    .try_to_listen(db.clone(), killpill_rx.resubscribe())
+                    .await;
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error fetching {} triggers: {:?}", T::DISPLAY_NAME, err);
+        }
+    };
+
+    let captures = sqlx::query_as::<_, CaptureConfigForListener<T::CaptureConfig>>(
+        r#"
+        SELECT
+            path,
+            is_flow,
+            workspace_id,
+            owner,
+            email,
+            trigger_config
+        FROM
+            capture_config
+        WHERE
+            trigger_kind = $1 AND
+            last_client_ping > NOW() - INTERVAL '10 seconds' AND
+            trigger_config IS NOT NULL AND
+            (last_server_ping IS NULL OR last_server_ping < now() - interval '15 seconds')
+        "#,
+    )
+    .bind(T::KIND)
+    .fetch_all(db)
+    .await;
+
+    match captures {
+        Ok(mut captures) => {
+            captures.shuffle(&mut rand::rng());
+            for capture in captures {
+                ListeningConfig::<T>::Capture(capture)
+                    .try_to_listen(db.clone(), killpill_rx.resubscribe())
+                    .await;
+            }
+        }
+        Err(err) => {
+            tracing::error!("Error fetching {} captures: {:?}", T::DISPLAY_NAME, err);
+        }
+    };
+}
+
+/// Every 15 seconds, listens to the enabled triggers and the polled captures of the kind that
+/// no server is listening to
+pub fn start_listener<T: Listener>(db: DB, mut killpill_rx: tokio::sync::broadcast::Receiver<()>) {
+    tokio::spawn(async move {
+        listen_to_unlistened::<T>(&db, &killpill_rx).await;
+        loop {
+            tokio::select! {
+                biased;
+                _ = killpill_rx.recv() => {
+                    return;
+                }
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(15)) => {
+                    listen_to_unlistened::<T>(&db, &killpill_rx).await
+                }
+            }
+        }
+    });
+}
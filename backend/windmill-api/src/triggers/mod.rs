@@ -5,6 +5,42 @@ use windmill_common::error::JsonResult;
 
 use crate::db::DB;
 
+#[cfg(any(
+    feature = "websocket",
+    feature = "postgres_trigger",
+    feature = "mqtt_trigger",
+    feature = "amqp_trigger",
+    feature = "redis_trigger",
+    feature = "s3_trigger",
+    all(feature = "enterprise", feature = "kafka"),
+    all(feature = "enterprise", feature = "nats"),
+    all(feature = "enterprise", feature = "sqs_trigger"),
+    all(feature = "enterprise", feature = "gcp_trigger")
+))]
+// the open source stubs of the enterprise triggers only use `Trigger`
+#[cfg_attr(
+    not(any(
+        feature = "websocket",
+        feature = "postgres_trigger",
+        feature = "mqtt_trigger",
+        feature = "amqp_trigger",
+        feature = "redis_trigger",
+        feature = "s3_trigger",
+        feature = "private"
+    )),
+    allow(unused)
+)]
+pub mod handler;
+#[cfg(any(
+    feature = "websocket",
+    feature = "postgres_trigger",
+    feature = "mqtt_trigger",
+    feature = "amqp_trigger",
+    feature = "redis_trigger",
+    feature = "s3_trigger"
+))]
+pub mod listener;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TriggerPrimarySchedule {
     schedule: String,
@@ -22,6 +58,10 @@ pub struct TriggersCount {
     nats_count: i64,
     postgres_count: i64,
     mqtt_count: i64,
+    mysql_count: i64,
+    amqp_count: i64,
+    redis_count: i64,
+    s3_count: i64,
     sqs_count: i64,
     gcp_count: i64,
 }
@@ -110,6 +150,46 @@ pub(crate) async fn get_triggers_count_internal(
     .await?
     .unwrap_or(0);
 
+    let mysql_count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM mysql_trigger WHERE script_path = $1 AND is_flow = $2 AND workspace_id = $3",
+        path,
+        is_flow,
+        w_id
+    )
+    .fetch_one(db)
+    .await?
+    .unwrap_or(0);
+
+    let amqp_count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM amqp_trigger WHERE script_path = $1 AND is_flow = $2 AND workspace_id = $3",
+        path,
+        is_flow,
+        w_id
+    )
+    .fetch_one(db)
+    .await?
+    .unwrap_or(0);
+
+    let redis_count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM redis_trigger WHERE script_path = $1 AND is_flow = $2 AND workspace_id = $3",
+        path,
+        is_flow,
+        w_id
+    )
+    .fetch_one(db)
+    .await?
+    .unwrap_or(0);
+
+    let s3_count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM s3_trigger WHERE script_path = $1 AND is_flow = $2 AND workspace_id = $3",
+        path,
+        is_flow,
+        w_id
+    )
+    .fetch_one(db)
+    .await?
+    .unwrap_or(0);
+
     let sqs_count = sqlx::query_scalar!(
         "SELECT COUNT(*) FROM sqs_trigger WHERE script_path = $1 AND is_flow = $2 AND workspace_id = $3",
         path,
@@ -175,6 +255,10 @@ pub(crate) async fn get_triggers_count_internal(
         nats_count,
         postgres_count,
         mqtt_count,
+        mysql_count,
+        amqp_count,
+        redis_count,
+        s3_count,
         gcp_count,
         sqs_count,
     }))
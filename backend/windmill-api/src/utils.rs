@@ -439,7 +439,6 @@ pub async fn acknowledge_all_critical_alerts(
     Ok("All unacknowledged critical alerts acknowledged".to_string())
 }
 
-#[cfg(feature = "http_trigger")]
 #[derive(Clone)]
 pub struct ExpiringCacheEntry<T> {
     pub value: T,
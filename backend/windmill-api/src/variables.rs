@@ -8,7 +8,11 @@
 
 use crate::{
     db::{ApiAuthed, DB},
-    users::{maybe_refresh_folders, require_owner_of_path},
+    environments::{
+        delete_overrides, get_request_environment, rename_overrides, set_overrides_secret,
+        OverrideKind,
+    },
+    users::{maybe_refresh_folders, require_owner_of_path, Tokened},
     utils::check_scopes,
     webhook_util::{WebhookMessage, WebhookShared},
 };
@@ -24,10 +28,14 @@ use serde_json::Value;
 use windmill_audit::audit_oss::{audit_log, AuditAuthorable};
 use windmill_audit::ActionKind;
 use windmill_common::{
-    db::UserDB, error::{Error, JsonResult, Result}, utils::{not_found_if_none, paginate, Pagination, StripPath}, variables::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    utils::{not_found_if_none, paginate, Pagination, StripPath},
+    variables::{
         build_crypt, get_reserved_variables, ContextualVariable, CreateVariable, ListableVariable,
     },
     worker::CLOUD_HOSTED,
+    workspaces::{fork_item_workspace, get_fork_parent, ForkedItemKind},
 };
 
 use lazy_static::lazy_static;
@@ -139,6 +147,7 @@ async fn get_variable(
 ) -> JsonResult<ListableVariable> {
     let path = path.to_path();
     check_scopes(&authed, || format!("variables:read:{}", path))?;
+    let (w_id, masked) = variable_workspace(&db, &w_id, path).await?;
     let mut tx = user_db.begin(&authed).await?;
 
     let variable_o = sqlx::query_as::<_, ListableVariable>(
@@ -163,7 +172,7 @@ async fn get_variable(
         unreachable!()
     };
 
-    let decrypt_secret = q.decrypt_secret.unwrap_or(true);
+    let decrypt_secret = q.decrypt_secret.unwrap_or(true) && !masked;
 
     let r = if variable.is_secret {
         if decrypt_secret {
@@ -201,7 +210,7 @@ async fn get_variable(
                 let _ = tx.commit().await;
                 let mc = build_crypt(&db, &w_id).await?;
                 Some(decrypt(&mc, value)?)
-            } else if q.include_encrypted.unwrap_or(false) {
+            } else if q.include_encrypted.unwrap_or(false) && !masked {
                 Some(value)
             } else {
                 None
@@ -219,13 +228,14 @@ async fn get_value(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
     Extension(db): Extension<DB>,
-
+    Tokened { token }: Tokened,
     Path((w_id, path)): Path<(String, StripPath)>,
 ) -> JsonResult<String> {
     let path = path.to_path();
     check_scopes(&authed, || format!("variables:read:{}", path))?;
+    let environment = get_request_environment(&db, &w_id, None, &token).await?;
     let tx = user_db.begin(&authed).await?;
-    return get_value_internal(tx, &db, &w_id, &path, &authed)
+    return get_value_internal(tx, &db, &w_id, &path, &authed, environment.as_deref())
         .await
         .map(Json);
 }
@@ -428,6 +438,8 @@ async fn delete_variable(
     )
     .execute(&mut *tx)
     .await?;
+    delete_overrides(&mut tx, &w_id, OverrideKind::Variable, path).await?;
+    delete_overrides(&mut tx, &w_id, OverrideKind::Resource, path).await?;
     audit_log(
         &mut *tx,
         &authed,
@@ -528,6 +540,7 @@ async fn update_variable(
         sqlb.set_str("account", account_id);
     }
 
+    let mut secret_change = None;
     if let Some(nbool) = ns.is_secret {
         let old_secret = sqlx::query_scalar!(
             "SELECT is_secret from variable WHERE path = $1 AND workspace_id = $2",
@@ -542,6 +555,9 @@ async fn update_variable(
                 "cannot change is_secret without updating value too".to_string(),
             ));
         }
+        if old_secret != nbool {
+            secret_change = Some(nbool);
+        }
         sqlb.set_str("is_secret", nbool);
     }
     sqlb.returning("path");
@@ -593,6 +609,8 @@ async fn update_variable(
             )
             .execute(&mut *tx)
             .await?;
+            rename_overrides(&mut tx, &w_id, OverrideKind::Variable, path, &npath).await?;
+            rename_overrides(&mut tx, &w_id, OverrideKind::Resource, path, &npath).await?;
         }
     }
 
@@ -602,6 +620,10 @@ async fn update_variable(
 
     let npath = not_found_if_none(npath_o, "Variable", path)?;
 
+    if let Some(is_secret) = secret_change {
+        set_overrides_secret(&mut tx, &db, &w_id, &npath, is_secret).await?;
+    }
+
     audit_log(
         &mut *tx,
         &authed,
@@ -681,13 +703,27 @@ fn replace_path(v: serde_json::Value, path: &str, npath: &str) -> Value {
     }
 }
 
+/// The workspace to read the variable from, and whether it is inherited from the parent of a
+/// fork that masks its secrets
+async fn variable_workspace(db: &DB, w_id: &str, path: &str) -> Result<(String, bool)> {
+    let variable_w_id = fork_item_workspace(db, w_id, ForkedItemKind::Variable, path).await?;
+    let masked = variable_w_id != w_id
+        && get_fork_parent(db, w_id)
+            .await?
+            .is_some_and(|parent| parent.mask_secrets);
+    Ok((variable_w_id, masked))
+}
+
 pub async fn get_value_internal<'c>(
     mut tx: Transaction<'c, Postgres>,
     db: &DB,
     w_id: &str,
     path: &str,
     audit_author: &impl AuditAuthorable,
+    environment: Option<&str>,
 ) -> Result<String> {
+    let (w_id, masked) = variable_workspace(db, w_id, path).await?;
+    let w_id = w_id.as_str();
     let variable_o = sqlx::query!(
         "SELECT value, account, (now() > account.expires_at) as is_expired, is_secret, path from variable
         LEFT JOIN account ON variable.account = account.id WHERE variable.path = $1 AND variable.workspace_id = $2", path, w_id
@@ -702,6 +738,26 @@ pub async fn get_value_internal<'c>(
         unreachable!()
     };
 
+    // the value of the environment replaces the value of the variable, including its oauth token
+    let override_value = match environment {
+        Some(environment) => sqlx::query_scalar!(
+            "SELECT value FROM variable_override WHERE workspace_id = $1 AND path = $2 AND environment = $3",
+            w_id,
+            path,
+            environment
+        )
+        .fetch_optional(&mut *tx)
+        .await?,
+        None => None,
+    };
+    let overridden = override_value.is_some();
+
+    if variable.is_secret && masked {
+        return Err(Error::BadRequest(format!(
+            "Secret variable {path} is masked in this fork, set its value in the fork to use it"
+        )));
+    }
+
     let r = if variable.is_secret {
         audit_log(
             &mut *tx,
@@ -713,8 +769,8 @@ pub async fn get_value_internal<'c>(
             None,
         )
         .await?;
-        let value = variable.value;
-        if variable.is_expired.unwrap_or(false) && variable.account.is_some() {
+        let value = override_value.unwrap_or(variable.value);
+        if !overridden && variable.is_expired.unwrap_or(false) && variable.account.is_some() {
             #[cfg(feature = "oauth2")]
             {
                 crate::oauth2_oss::_refresh_token(
@@ -736,7 +792,7 @@ pub async fn get_value_internal<'c>(
             "".to_string()
         }
     } else {
-        variable.value
+        override_value.unwrap_or(variable.value)
     };
 
     Ok(r)
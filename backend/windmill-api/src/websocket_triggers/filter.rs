@@ -0,0 +1,520 @@
+use std::{cell::OnceCell, cmp::Ordering, collections::HashMap, fmt};
+
+use regex::Regex;
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_json::{value::RawValue, Map, Value};
+use sqlx::types::Json as SqlxJson;
+use windmill_common::{
+    error::{Error, Result},
+    worker::to_raw_value,
+};
+
+use crate::trigger_helpers::is_json_superset;
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Path to a value inside a JSON message, either `$.a.b[0]['c.d']` or `a.b.0`. `$` is the
+/// whole message.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct JsonPath(Vec<PathSegment>);
+
+impl TryFrom<String> for JsonPath {
+    type Error = String;
+
+    fn try_from(path: String) -> std::result::Result<Self, Self::Error> {
+        let invalid = |reason: &str| format!("Invalid JSON path `{path}`: {reason}");
+        let mut rest = path.trim();
+        rest = rest.strip_prefix('$').unwrap_or(rest);
+        let mut segments = vec![];
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix('[') {
+                let (inner, r) = r.split_once(']').ok_or_else(|| invalid("unclosed `[`"))?;
+                let segment = match inner.trim() {
+                    quoted
+                        if quoted.len() >= 2
+                            && (quoted.starts_with('\'') && quoted.ends_with('\'')
+                                || quoted.starts_with('"') && quoted.ends_with('"')) =>
+                    {
+                        PathSegment::Key(quoted[1..quoted.len() - 1].to_string())
+                    }
+                    index => PathSegment::Index(
+                        index
+                            .parse()
+                            .map_err(|_| invalid("expected an index or a quoted key in `[]`"))?,
+                    ),
+                };
+                segments.push(segment);
+                rest = r;
+            } else {
+                rest = rest.strip_prefix('.').unwrap_or(rest);
+                let end = rest.find(|c| c == '.' || c == '[').unwrap_or(rest.len());
+                let key = &rest[..end];
+                if key.is_empty() {
+                    return Err(invalid("empty key"));
+                }
+                segments.push(PathSegment::Key(key.to_string()));
+                rest = &rest[end..];
+            }
+        }
+        Ok(Self(segments))
+    }
+}
+
+impl JsonPath {
+    pub fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match (segment, value) {
+                (PathSegment::Key(key), Value::Object(map)) => map.get(key),
+                (PathSegment::Key(key), Value::Array(array)) => {
+                    key.parse::<usize>().ok().and_then(|i| array.get(i))
+                }
+                (PathSegment::Index(i), Value::Array(array)) => array.get(*i),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Deserialize, Default, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    /// The value is a superset of the filter value, like `JsonFilter`
+    #[default]
+    Contains,
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Exists,
+    Regex,
+}
+
+#[derive(Deserialize)]
+struct RawJsonPathFilter {
+    path: JsonPath,
+    #[serde(default)]
+    op: FilterOp,
+    #[serde(default)]
+    value: Value,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Value(FilterOp, Value),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawJsonPathFilter")]
+pub struct JsonPathFilter {
+    path: JsonPath,
+    condition: Condition,
+}
+
+impl TryFrom<RawJsonPathFilter> for JsonPathFilter {
+    type Error = String;
+
+    fn try_from(filter: RawJsonPathFilter) -> std::result::Result<Self, Self::Error> {
+        let condition = match filter.op {
+            FilterOp::Regex => {
+                let pattern = filter
+                    .value
+                    .as_str()
+                    .ok_or_else(|| "Regex filter value must be a string".to_string())?;
+                Condition::Regex(
+                    Regex::new(pattern).map_err(|e| format!("Invalid regex filter: {e}"))?,
+                )
+            }
+            op => Condition::Value(op, filter.value),
+        };
+        Ok(Self { path: filter.path, condition })
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+impl JsonPathFilter {
+    fn matches(&self, msg: &Value) -> bool {
+        let selected = self.path.select(msg);
+        match (&self.condition, selected) {
+            (Condition::Value(FilterOp::Exists, _), selected) => selected.is_some(),
+            (Condition::Value(FilterOp::Ne, value), selected) => selected != Some(value),
+            (_, None) => false,
+            (Condition::Regex(regex), Some(Value::String(s))) => regex.is_match(s),
+            (Condition::Regex(_), Some(_)) => false,
+            (Condition::Value(op, value), Some(selected)) => match op {
+                FilterOp::Contains => is_json_superset(selected, value),
+                FilterOp::Eq => selected == value,
+                FilterOp::Gt => compare(selected, value) == Some(Ordering::Greater),
+                FilterOp::Gte => {
+                    matches!(
+                        compare(selected, value),
+                        Some(Ordering::Greater | Ordering::Equal)
+                    )
+                }
+                FilterOp::Lt => compare(selected, value) == Some(Ordering::Less),
+                FilterOp::Lte => {
+                    matches!(
+                        compare(selected, value),
+                        Some(Ordering::Less | Ordering::Equal)
+                    )
+                }
+                FilterOp::Ne | FilterOp::Exists | FilterOp::Regex => unreachable!(),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JsonFilter {
+    key: String,
+    value: Value,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Filter {
+    JsonFilter(JsonFilter),
+    JsonPathFilter(JsonPathFilter),
+    And { and: Vec<Filter> },
+    Or { or: Vec<Filter> },
+    Not { not: Box<Filter> },
+}
+
+/// A received text message, only parsed once and only if a filter or the args transform needs it
+pub struct ReceivedMessage<'a> {
+    pub text: &'a str,
+    json: OnceCell<Option<Value>>,
+}
+
+impl<'a> ReceivedMessage<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text, json: OnceCell::new() }
+    }
+
+    pub fn json(&self) -> Option<&Value> {
+        self.json
+            .get_or_init(|| serde_json::from_str(self.text).ok())
+            .as_ref()
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, msg: &ReceivedMessage) -> bool {
+        match self {
+            Filter::JsonFilter(JsonFilter { key, value }) => {
+                let mut deserializer = serde_json::Deserializer::from_str(msg.text);
+                is_value_superset(&mut deserializer, key, value).unwrap_or(false)
+            }
+            Filter::JsonPathFilter(filter) => msg.json().map_or(false, |json| filter.matches(json)),
+            Filter::And { and } => and.iter().all(|filter| filter.matches(msg)),
+            Filter::Or { or } => or.iter().any(|filter| filter.matches(msg)),
+            Filter::Not { not } => !not.matches(msg),
+        }
+    }
+}
+
+pub fn parse_filters(filters: &[SqlxJson<Box<RawValue>>]) -> Result<Vec<Filter>> {
+    filters
+        .iter()
+        .map(|filter| {
+            serde_json::from_str(filter.get())
+                .map_err(|e| Error::BadRequest(format!("Invalid filter {}: {e}", filter.get())))
+        })
+        .collect()
+}
+
+struct SupersetVisitor<'a> {
+    key: &'a str,
+    value_to_check: &'a Value,
+}
+
+impl<'de, 'a> Visitor<'de> for SupersetVisitor<'a> {
+    type Value = bool;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON object with a specific key at the top level")
+    }
+
+    fn visit_map<V>(self, mut map: V) -> std::result::Result<Self::Value, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.key {
+                // Deserialize the value for the key and check if it's a superset
+                let json_value: Value = map.next_value()?;
+                return Ok(is_json_superset(&json_value, self.value_to_check));
+            } else {
+                // Skip the value if it's not the one we're interested in
+                let _ = map.next_value::<de::IgnoredAny>()?;
+            }
+        }
+        // If the key was not found, return false
+        Ok(false)
+    }
+}
+
+// A function to deserialize and check if the value at the given key is a superset of a passed value
+fn is_value_superset<'a, 'de, D>(
+    deserializer: D,
+    key: &'a str,
+    value_to_check: &'a Value,
+) -> std::result::Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(SupersetVisitor { key, value_to_check })
+}
+
+/// Job args extracted from the message, keyed by arg name
+pub type ArgsTransform = HashMap<String, JsonPath>;
+
+/// Args whose path is not in the message are left out so that the runnable defaults apply
+pub fn transform_args(
+    transform: &ArgsTransform,
+    msg: &ReceivedMessage,
+) -> Result<HashMap<String, Box<RawValue>>> {
+    let json = msg.json().ok_or_else(|| {
+        Error::BadRequest("Message must be JSON to extract the job args from it".to_string())
+    })?;
+    Ok(transform
+        .iter()
+        .filter_map(|(arg, path)| Some((arg.clone(), to_raw_value(path.select(json)?))))
+        .collect())
+}
+
+/// How the job result sent back over the websocket is matched to the message it answers
+#[derive(Deserialize, Debug, Clone)]
+pub struct Correlation {
+    /// Path to the correlation id in the received message
+    pub request_path: JsonPath,
+    /// Key the correlation id is set at in the response
+    pub response_key: String,
+}
+
+impl Correlation {
+    pub fn correlation_id(&self, msg: &ReceivedMessage) -> Option<Value> {
+        msg.json()
+            .and_then(|json| self.request_path.select(json))
+            .cloned()
+    }
+
+    /// Object results get the correlation id at the response key, other results are wrapped
+    /// as `{ <response_key>: <id>, "result": <result> }`
+    pub fn response(&self, correlation_id: Value, result: &RawValue) -> String {
+        let response = match serde_json::from_str::<Value>(result.get()) {
+            Ok(Value::Object(mut object)) => {
+                object.insert(self.response_key.clone(), correlation_id);
+                object
+            }
+            result => {
+                let mut object = Map::new();
+                object.insert(self.response_key.clone(), correlation_id);
+                object.insert("result".to_string(), result.unwrap_or(Value::Null));
+                object
+            }
+        };
+        Value::Object(response).to_string()
+    }
+
+    /// Sent instead of the result when the job could not be run or failed, so that the peer
+    /// is not left waiting for an answer
+    pub fn error_response(&self, correlation_id: Value, error: &str) -> String {
+        let mut object = Map::new();
+        object.insert(self.response_key.clone(), correlation_id);
+        object.insert("error".to_string(), serde_json::json!({ "message": error }));
+        Value::Object(object).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn path(path: &str) -> JsonPath {
+        JsonPath::try_from(path.to_string()).unwrap()
+    }
+
+    fn filter(filter: Value) -> Filter {
+        serde_json::from_value(filter).unwrap()
+    }
+
+    #[test]
+    fn test_json_path_parsing() {
+        assert_eq!(
+            path("$.a.b[0]['c.d'][\"e\"]").0,
+            vec![
+                PathSegment::Key("a".to_string()),
+                PathSegment::Key("b".to_string()),
+                PathSegment::Index(0),
+                PathSegment::Key("c.d".to_string()),
+                PathSegment::Key("e".to_string()),
+            ]
+        );
+        assert_eq!(path("a.b.0").0, path("$.a.b.0").0);
+        assert!(path("$").0.is_empty());
+
+        for invalid in ["$.a[0", "$.a..b", "$.a[x]", "$."] {
+            assert!(
+                JsonPath::try_from(invalid.to_string()).is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_json_path_select() {
+        let msg = json!({ "a": { "b": [{ "c.d": 1 }, 2] } });
+        assert_eq!(path("$.a.b[0]['c.d']").select(&msg), Some(&json!(1)));
+        assert_eq!(path("a.b.1").select(&msg), Some(&json!(2)));
+        assert_eq!(path("$").select(&msg), Some(&msg));
+        assert_eq!(path("$.a.b[2]").select(&msg), None);
+        assert_eq!(path("$.a.missing").select(&msg), None);
+        assert_eq!(path("$.a[0]").select(&msg), None);
+    }
+
+    #[test]
+    fn test_json_path_filter_ops() {
+        let msg = ReceivedMessage::new(r#"{"type": "trade", "price": 10, "tags": ["a", "b"]}"#);
+        let matches = |f: Value| filter(f).matches(&msg);
+
+        assert!(matches(json!({ "path": "$.tags", "value": ["a"] })));
+        assert!(!matches(json!({ "path": "$.tags", "value": ["c"] })));
+        assert!(matches(
+            json!({ "path": "$.type", "op": "eq", "value": "trade" })
+        ));
+        assert!(matches(
+            json!({ "path": "$.type", "op": "ne", "value": "quote" })
+        ));
+        assert!(matches(
+            json!({ "path": "$.missing", "op": "ne", "value": "quote" })
+        ));
+        assert!(matches(
+            json!({ "path": "$.price", "op": "gt", "value": 9.5 })
+        ));
+        assert!(matches(
+            json!({ "path": "$.price", "op": "gte", "value": 10 })
+        ));
+        assert!(!matches(
+            json!({ "path": "$.price", "op": "lt", "value": 10 })
+        ));
+        assert!(matches(
+            json!({ "path": "$.price", "op": "lte", "value": 10 })
+        ));
+        assert!(!matches(
+            json!({ "path": "$.type", "op": "gt", "value": 1 })
+        ));
+        assert!(matches(json!({ "path": "$.price", "op": "exists" })));
+        assert!(!matches(json!({ "path": "$.missing", "op": "exists" })));
+        assert!(matches(
+            json!({ "path": "$.type", "op": "regex", "value": "^tr" })
+        ));
+        assert!(!matches(
+            json!({ "path": "$.price", "op": "regex", "value": "10" })
+        ));
+
+        assert!(serde_json::from_value::<Filter>(
+            json!({ "path": "$.type", "op": "regex", "value": "(" })
+        )
+        .is_err());
+        assert!(
+            !filter(json!({ "path": "$.type", "op": "eq", "value": "trade" }))
+                .matches(&ReceivedMessage::new("not json"))
+        );
+    }
+
+    #[test]
+    fn test_filter_combinators() {
+        let msg = ReceivedMessage::new(r#"{"type": "trade", "data": {"side": "buy", "qty": 3}}"#);
+
+        assert!(filter(json!({ "key": "data", "value": { "side": "buy" } })).matches(&msg));
+        assert!(!filter(json!({ "key": "data", "value": { "side": "sell" } })).matches(&msg));
+        assert!(!filter(json!({ "key": "missing", "value": 1 })).matches(&msg));
+
+        let trade = json!({ "path": "$.type", "op": "eq", "value": "trade" });
+        let sell = json!({ "path": "$.data.side", "op": "eq", "value": "sell" });
+        assert!(filter(json!({ "and": [trade, { "not": sell }] })).matches(&msg));
+        assert!(!filter(json!({ "and": [trade, sell] })).matches(&msg));
+        assert!(filter(json!({ "or": [sell, trade] })).matches(&msg));
+        assert!(!filter(json!({ "or": [sell] })).matches(&msg));
+    }
+
+    #[test]
+    fn test_parse_filters_rejects_invalid_filters() {
+        let valid = SqlxJson(to_raw_value(&json!({ "key": "type", "value": "trade" })));
+        let invalid = SqlxJson(to_raw_value(&json!({ "path": "$.a[", "op": "exists" })));
+        assert_eq!(parse_filters(&[valid.clone()]).unwrap().len(), 1);
+        assert!(parse_filters(&[valid, invalid]).is_err());
+    }
+
+    #[test]
+    fn test_transform_args() {
+        let transform: ArgsTransform = serde_json::from_value(json!({
+            "symbol": "$.data.s",
+            "price": "$.data.p",
+            "missing": "$.data.missing",
+        }))
+        .unwrap();
+        let msg = ReceivedMessage::new(r#"{"data": {"s": "BTC", "p": 1.5}}"#);
+
+        let args = transform_args(&transform, &msg).unwrap();
+        assert_eq!(args.len(), 2);
+        assert_eq!(args["symbol"].get(), r#""BTC""#);
+        assert_eq!(args["price"].get(), "1.5");
+
+        assert!(transform_args(&transform, &ReceivedMessage::new("BTC 1.5")).is_err());
+    }
+
+    #[test]
+    fn test_correlation() {
+        let correlation: Correlation =
+            serde_json::from_value(json!({ "request_path": "$.req.id", "response_key": "id" }))
+                .unwrap();
+        let msg = ReceivedMessage::new(r#"{"req": {"id": 42}}"#);
+        let id = correlation.correlation_id(&msg).unwrap();
+        assert_eq!(id, json!(42));
+        assert_eq!(
+            correlation.correlation_id(&ReceivedMessage::new(r#"{"id": 42}"#)),
+            None
+        );
+
+        let response = |result: Value| -> Value {
+            serde_json::from_str(&correlation.response(id.clone(), &to_raw_value(&result))).unwrap()
+        };
+        assert_eq!(
+            response(json!({ "ok": true })),
+            json!({ "ok": true, "id": 42 })
+        );
+        assert_eq!(
+            response(json!([1, 2])),
+            json!({ "id": 42, "result": [1, 2] })
+        );
+        assert_eq!(response(Value::Null), json!({ "id": 42, "result": null }));
+
+        let error: Value =
+            serde_json::from_str(&correlation.error_response(id.clone(), "job failed")).unwrap();
+        assert_eq!(
+            error,
+            json!({ "id": 42, "error": { "message": "job failed" } })
+        );
+    }
+}
@@ -0,0 +1,720 @@
+use anyhow::Context;
+use axum::{async_trait, extract::Path, routing::post, Extension, Json, Router};
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Value};
+use sqlx::prelude::FromRow;
+use sqlx::types::Json as SqlxJson;
+use std::collections::HashMap;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use windmill_common::{
+    error::{self, to_anyhow},
+    triggers::TriggerKind,
+    utils::report_critical_error,
+    worker::to_raw_value,
+};
+use windmill_git_sync::DeployedObject;
+use windmill_queue::PushArgsOwned;
+
+use crate::{
+    capture::WebsocketTriggerConfig,
+    db::{ApiAuthed, DB},
+    trigger_helpers::{trigger_runnable_and_wait_for_raw_result, TriggerJobArgs},
+    triggers::{
+        handler::{trigger_routes, PgQuery, Trigger, TriggerCrud},
+        listener::{fetch_trigger_authed, trigger_job, Listener, ListeningConfig},
+    },
+};
+use filter::{parse_filters, transform_args, ArgsTransform, Correlation, Filter, ReceivedMessage};
+
+mod filter;
+
+use std::borrow::Cow;
+
+pub fn workspaced_service() -> Router {
+    trigger_routes::<WebsocketTriggerHandler>().route("/test", post(test_websocket_connection))
+}
+
+#[derive(Deserialize)]
+enum InitialMessage {
+    #[serde(rename = "raw_message")]
+    RawMessage(String),
+    #[serde(rename = "runnable_result")]
+    RunnableResult { path: String, args: Box<RawValue>, is_flow: bool },
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WebsocketConfig {
+    pub url: String,
+    pub filters: Vec<SqlxJson<Box<RawValue>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_messages: Option<Vec<SqlxJson<Box<RawValue>>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url_runnable_args: Option<SqlxJson<Box<RawValue>>>,
+    pub can_return_message: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args_transform: Option<SqlxJson<Box<RawValue>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation: Option<SqlxJson<Box<RawValue>>>,
+}
+
+pub type WebsocketTrigger = Trigger<WebsocketConfig>;
+
+pub struct WebsocketTriggerHandler;
+
+impl TriggerCrud for WebsocketTriggerHandler {
+    const TABLE_NAME: &'static str = "websocket_trigger";
+    const ROUTE_PREFIX: &'static str = "websocket_triggers";
+    const DISPLAY_NAME: &'static str = "WebSocket";
+    const KIND: TriggerKind = TriggerKind::Websocket;
+    const CONFIG_COLUMNS: &'static [&'static str] = &[
+        "url",
+        "filters",
+        "initial_messages",
+        "url_runnable_args",
+        "can_return_message",
+        "args_transform",
+        "correlation",
+    ];
+
+    type Config = WebsocketConfig;
+
+    fn deployed_object(path: String) -> DeployedObject {
+        DeployedObject::WebsocketTrigger { path }
+    }
+
+    fn validate_config(config: &WebsocketConfig) -> error::Result<()> {
+        validate_message_handling(config)
+    }
+
+    fn bind_config<'q>(config: &'q WebsocketConfig, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&config.url)
+            .bind(&config.filters)
+            .bind(&config.initial_messages)
+            .bind(&config.url_runnable_args)
+            .bind(config.can_return_message)
+            .bind(&config.args_transform)
+            .bind(&config.correlation)
+    }
+}
+
+/// Rejects the message handling options that could not be parsed when listening
+fn validate_message_handling(config: &WebsocketConfig) -> error::Result<()> {
+    parse_filters(&config.filters)?;
+    if let Some(args_transform) = &config.args_transform {
+        serde_json::from_str::<ArgsTransform>(args_transform.get())
+            .map_err(|e| error::Error::BadRequest(format!("Invalid args transform: {e}")))?;
+    }
+    if let Some(correlation) = &config.correlation {
+        if !config.can_return_message {
+            return Err(error::Error::BadRequest(
+                "Correlation requires the trigger to return the job result as a message"
+                    .to_string(),
+            ));
+        }
+        serde_json::from_str::<Correlation>(correlation.get())
+            .map_err(|e| error::Error::BadRequest(format!("Invalid correlation: {e}")))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct TestWebsocket {
+    url: String,
+    url_runnable_args: Option<Box<RawValue>>,
+}
+
+async fn test_websocket_connection(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(workspace_id): Path<String>,
+    Json(test_websocket): Json<TestWebsocket>,
+) -> error::Result<()> {
+    let url = test_websocket.url;
+
+    let connect_f = async {
+        let connect_url: Cow<str> = if url.starts_with("$") {
+            if url.starts_with("$flow:") || url.starts_with("$script:") {
+                let path = url.splitn(2, ':').nth(1).unwrap();
+                Cow::Owned(
+                    get_url_from_runnable(
+                        path,
+                        url.starts_with("$flow:"),
+                        &db,
+                        authed,
+                        test_websocket.url_runnable_args.as_ref(),
+                        &workspace_id,
+                    )
+                    .await?,
+                )
+            } else {
+                return Err(error::Error::BadConfig(format!(
+                    "Invalid WebSocket runnable path: {}",
+                    url
+                )));
+            }
+        } else {
+            Cow::Borrowed(&url)
+        };
+
+        connect_async(connect_url.as_ref()).await.map_err(|err| {
+            error::Error::BadConfig(format!(
+                "Error connecting to WebSocket: {}",
+                err.to_string()
+            ))
+        })?;
+
+        Ok(())
+    };
+
+    tokio::time::timeout(tokio::time::Duration::from_secs(30), connect_f)
+        .await
+        .map_err(|_| {
+            error::Error::BadConfig(format!("Timeout connecting to WebSocket after 30 seconds"))
+        })??;
+
+    Ok(())
+}
+
+fn raw_value_to_args_hashmap(
+    args: Option<&Box<RawValue>>,
+) -> error::Result<HashMap<String, Box<RawValue>>> {
+    let args = if let Some(args) = args {
+        serde_json::from_str::<Option<HashMap<String, Box<RawValue>>>>(args.get())
+            .map_err(|e| error::Error::BadRequest(format!("invalid json: {}", e)))?
+            .unwrap_or_else(HashMap::new)
+    } else {
+        HashMap::new()
+    };
+    Ok(args)
+}
+
+async fn get_url_from_runnable(
+    path: &str,
+    is_flow: bool,
+    db: &DB,
+    authed: ApiAuthed,
+    args: Option<&Box<RawValue>>,
+    workspace_id: &str,
+) -> error::Result<String> {
+    tracing::info!(
+        "Running {} {} to get WebSocket URL",
+        if is_flow { "flow" } else { "script" },
+        path
+    );
+
+    let args = raw_value_to_args_hashmap(args)?;
+
+    let result = trigger_runnable_and_wait_for_raw_result(
+        db,
+        None,
+        authed,
+        workspace_id,
+        path,
+        is_flow,
+        PushArgsOwned { args, extra: None },
+        None,
+        None,
+        None,
+        "".to_string(), // doesn't matter as no retry/error handler
+    )
+    .await?;
+
+    serde_json::from_str::<String>(result.get()).map_err(|_| {
+        error::Error::BadConfig(format!(
+            "{} {} did not return a string",
+            if is_flow { "Flow" } else { "Script" },
+            path,
+        ))
+    })
+}
+
+type WebsocketWriter = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+
+async fn send_initial_messages(
+    db: &DB,
+    trigger: &WebsocketTrigger,
+    url: &str,
+    writer: &mut WebsocketWriter,
+) -> error::Result<()> {
+    let initial_messages: Vec<InitialMessage> = trigger
+        .config
+        .initial_messages
+        .as_deref()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|m| serde_json::from_str(m.get()).ok())
+        .collect_vec();
+
+    let mut authed_o = None;
+    for start_message in initial_messages {
+        match start_message {
+            InitialMessage::RawMessage(msg) => {
+                let msg = if msg.starts_with("\"") && msg.ends_with("\"") {
+                    msg[1..msg.len() - 1].to_string()
+                } else {
+                    msg
+                };
+                tracing::info!(
+                    "Sending raw message initial message to WebSocket {}: {}",
+                    url,
+                    msg
+                );
+                writer
+                    .send(Message::Text(msg))
+                    .await
+                    .map_err(to_anyhow)
+                    .with_context(|| "failed to send raw message")?;
+            }
+            InitialMessage::RunnableResult { path, is_flow, args } => {
+                tracing::info!(
+                    "Running {} {} for initial message to WebSocket {}",
+                    if is_flow { "flow" } else { "script" },
+                    path,
+                    url,
+                );
+
+                let args = raw_value_to_args_hashmap(Some(&args))?;
+
+                if authed_o.is_none() {
+                    authed_o = Some(
+                        fetch_trigger_authed::<WebsocketTriggerHandler>(db, &trigger.base).await?,
+                    );
+                }
+                let authed = authed_o.clone().unwrap();
+
+                let result = trigger_runnable_and_wait_for_raw_result(
+                    db,
+                    None,
+                    authed,
+                    &trigger.base.workspace_id,
+                    &path,
+                    is_flow,
+                    PushArgsOwned { args, extra: None },
+                    None,
+                    None,
+                    None,
+                    "".to_string(), // doesn't matter as no retry/error handler
+                )
+                .await
+                .map(|r| r.get().to_owned())?;
+
+                tracing::info!(
+                    "Sending {} {} result to WebSocket {}",
+                    if is_flow { "flow" } else { "script" },
+                    path,
+                    url
+                );
+
+                // if the `result` was just a single string, the below removes the surrounding quotes by parsing it as a string.
+                // it falls back to the original serialized JSON if it doesn't work.
+                let result = serde_json::from_str::<String>(result.as_str()).unwrap_or(result);
+
+                writer
+                    .send(Message::Text(result))
+                    .await
+                    .map_err(to_anyhow)
+                    .with_context(|| {
+                        format!(
+                            "Failed to send {} {} result",
+                            if is_flow { "flow" } else { "script" },
+                            path
+                        )
+                    })?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl TriggerJobArgs<&str> for WebsocketTriggerHandler {
+    fn v1_payload_fn(payload: &str) -> HashMap<String, Box<RawValue>> {
+        HashMap::from([("msg".to_string(), to_raw_value(&payload))])
+    }
+
+    fn trigger_kind() -> TriggerKind {
+        TriggerKind::Websocket
+    }
+}
+
+/// The args and correlation id extracted from a received message that passed the filters
+struct MessageDelivery {
+    transformed_args: Option<error::Result<HashMap<String, Box<RawValue>>>>,
+    response_correlation: Option<(Correlation, Value)>,
+}
+
+pub struct WebsocketConnection {
+    url: String,
+    writer: WebsocketWriter,
+    reader: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+}
+
+#[async_trait]
+impl Listener for WebsocketTriggerHandler {
+    type CaptureConfig = WebsocketTriggerConfig;
+    type Connection = WebsocketConnection;
+    type Error = error::Error;
+
+    async fn connect(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+    ) -> error::Result<WebsocketConnection> {
+        let (url, url_runnable_args) = match config {
+            ListeningConfig::Trigger(trigger) => (
+                &trigger.config.url,
+                trigger
+                    .config
+                    .url_runnable_args
+                    .as_ref()
+                    .map(|args| args.0.clone()),
+            ),
+            ListeningConfig::Capture(capture) => (
+                &capture.trigger_config.url,
+                capture
+                    .trigger_config
+                    .url_runnable_args
+                    .as_ref()
+                    .map(to_raw_value),
+            ),
+        };
+
+        let connect_url: Cow<str> = if url.starts_with("$") {
+            if url.starts_with("$flow:") || url.starts_with("$script:") {
+                let path = url.splitn(2, ':').nth(1).unwrap();
+                let url = get_url_from_runnable(
+                    path,
+                    url.starts_with("$flow:"),
+                    db,
+                    config.fetch_authed(db).await?,
+                    url_runnable_args.as_ref(),
+                    config.workspace_id(),
+                )
+                .await
+                .map_err(|err| {
+                    error::Error::BadConfig(format!(
+                        "Error getting WebSocket URL from runnable: {}",
+                        err
+                    ))
+                })?;
+                Cow::Owned(url)
+            } else {
+                return Err(error::Error::BadConfig(format!(
+                    "Invalid WebSocket runnable path: {}",
+                    url
+                )));
+            }
+        } else {
+            Cow::Borrowed(url)
+        };
+
+        let (ws_stream, _) = connect_async(connect_url.as_ref()).await.map_err(|err| {
+            error::Error::BadConfig(format!("Error connecting to WebSocket {}: {}", url, err))
+        })?;
+        tracing::info!("Connected to WebSocket {} of {}", url, config.path());
+        let (mut writer, reader) = ws_stream.split();
+
+        if let ListeningConfig::Trigger(trigger) = config {
+            send_initial_messages(db, trigger, url, &mut writer)
+                .await
+                .map_err(|err| {
+                    error::Error::BadConfig(format!("Error sending initial messages: {:?}", err))
+                })?;
+            tracing::debug!("Initial messages sent successfully to WebSocket {}", url);
+        }
+
+        Ok(WebsocketConnection { url: url.clone(), writer, reader })
+    }
+
+    async fn consume(
+        db: &DB,
+        config: &ListeningConfig<Self>,
+        connection: WebsocketConnection,
+    ) -> error::Result<()> {
+        let WebsocketConnection { url, mut writer, mut reader } = connection;
+
+        let (filters, args_transform, correlation) = match config {
+            ListeningConfig::Trigger(trigger) => (
+                trigger
+                    .config
+                    .filters
+                    .iter()
+                    .filter_map(|m| serde_json::from_str::<Filter>(m.get()).ok())
+                    .collect_vec(),
+                trigger
+                    .config
+                    .args_transform
+                    .as_ref()
+                    .and_then(|t| serde_json::from_str::<ArgsTransform>(t.get()).ok()),
+                trigger
+                    .config
+                    .correlation
+                    .as_ref()
+                    .and_then(|c| serde_json::from_str::<Correlation>(c.get()).ok()),
+            ),
+            ListeningConfig::Capture(_) => (vec![], None, None),
+        };
+
+        // the job results sent back to the peer, written here so that the writer is dropped with
+        // the connection
+        let (send_message_tx, mut send_message_rx) = tokio::sync::mpsc::channel::<String>(100);
+        let return_message_tx = match config {
+            ListeningConfig::Trigger(trigger) if trigger.config.can_return_message => {
+                Some(&send_message_tx)
+            }
+            _ => None,
+        };
+
+        loop {
+            let msg = tokio::select! {
+                Some(message) = send_message_rx.recv() => {
+                    if let Err(err) = writer.send(Message::Text(message)).await {
+                        report_critical_error(
+                            format!(
+                                "Could not send runnable result to WebSocket {} because of error: {}",
+                                url, err
+                            ),
+                            db.clone(),
+                            Some(config.workspace_id()),
+                            None,
+                        )
+                        .await;
+                    }
+                    continue;
+                }
+                msg = reader.next() => msg,
+            };
+
+            match msg {
+                Some(Ok(Message::Text(text))) => {
+                    tracing::debug!("Received text message from WebSocket {}: {}", url, text);
+                    let delivery = {
+                        let received = ReceivedMessage::new(text.as_str());
+                        filters
+                            .iter()
+                            .all(|filter| filter.matches(&received))
+                            .then(|| MessageDelivery {
+                                transformed_args: args_transform
+                                    .as_ref()
+                                    .map(|transform| transform_args(transform, &received)),
+                                response_correlation: correlation.as_ref().and_then(
+                                    |correlation| {
+                                        Some((
+                                            correlation.clone(),
+                                            correlation.correlation_id(&received)?,
+                                        ))
+                                    },
+                                ),
+                            })
+                    };
+                    if let Some(delivery) = delivery {
+                        let trigger_info = HashMap::from([("url".to_string(), to_raw_value(&url))]);
+                        match config {
+                            ListeningConfig::Trigger(trigger) => {
+                                if let Err(err) = run_job(
+                                    db,
+                                    trigger,
+                                    &url,
+                                    &text,
+                                    trigger_info,
+                                    delivery,
+                                    return_message_tx,
+                                )
+                                .await
+                                {
+                                    report_critical_error(
+                                        format!(
+                                            "Failed to trigger job from WebSocket {}: {:?}",
+                                            trigger.base.path, err
+                                        ),
+                                        db.clone(),
+                                        Some(&trigger.base.workspace_id),
+                                        None,
+                                    )
+                                    .await;
+                                }
+                            }
+                            ListeningConfig::Capture(capture) => {
+                                let (main_args, preprocessor_args) =
+                                    WebsocketTriggerHandler::build_capture_payloads(
+                                        &text,
+                                        trigger_info,
+                                    );
+                                capture
+                                    .insert_payload(
+                                        db,
+                                        &TriggerKind::Websocket,
+                                        main_args,
+                                        preprocessor_args,
+                                    )
+                                    .await;
+                            }
+                        }
+                    }
+                }
+                Some(Ok(msg)) => {
+                    tracing::debug!(
+                        "Received non text-message from WebSocket {}: {:?}",
+                        url,
+                        msg
+                    );
+                }
+                Some(Err(err)) => {
+                    tracing::error!("Error reading from WebSocket {}: {:?}", url, err);
+                }
+                None => {
+                    tracing::error!("WebSocket {} closed", url);
+                    config.report_error(db, "WebSocket closed").await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+async fn job_args(
+    db: &DB,
+    trigger: &WebsocketTrigger,
+    msg: &str,
+    trigger_info: HashMap<String, Box<RawValue>>,
+    transformed_args: Option<error::Result<HashMap<String, Box<RawValue>>>>,
+) -> error::Result<PushArgsOwned> {
+    // the transformed args are passed as is, without the preprocessor event
+    match transformed_args {
+        Some(args) => Ok(PushArgsOwned { args: args?, extra: None }),
+        None => {
+            WebsocketTriggerHandler::build_job_args(
+                &trigger.base.script_path,
+                trigger.base.is_flow,
+                &trigger.base.workspace_id,
+                db,
+                msg,
+                trigger_info,
+            )
+            .await
+        }
+    }
+}
+
+async fn run_job(
+    db: &DB,
+    trigger: &WebsocketTrigger,
+    url: &str,
+    msg: &str,
+    trigger_info: HashMap<String, Box<RawValue>>,
+    delivery: MessageDelivery,
+    return_message_tx: Option<&tokio::sync::mpsc::Sender<String>>,
+) -> anyhow::Result<()> {
+    let MessageDelivery { transformed_args, response_correlation } = delivery;
+
+    let Some(send_message_tx) = return_message_tx else {
+        let args = job_args(db, trigger, msg, trigger_info, transformed_args).await?;
+        trigger_job::<WebsocketTriggerHandler>(db, &trigger.base, args, None).await?;
+        return Ok(());
+    };
+
+    let prepared = async {
+        let args = job_args(db, trigger, msg, trigger_info, transformed_args).await?;
+        let authed = fetch_trigger_authed::<WebsocketTriggerHandler>(db, &trigger.base).await?;
+        Ok::<_, error::Error>((args, authed))
+    }
+    .await;
+
+    let (args, authed) = match prepared {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            if let Some((correlation, correlation_id)) = &response_correlation {
+                let response = correlation.error_response(correlation_id.clone(), &err.to_string());
+                if let Err(send_err) = send_message_tx.send(response).await {
+                    tracing::error!(
+                        "Could not send error response to WebSocket {}: {}",
+                        url,
+                        send_err
+                    );
+                }
+            }
+            return Err(err.into());
+        }
+    };
+
+    let db_ = db.clone();
+    let url = url.to_string();
+    let send_message_tx = send_message_tx.clone();
+    let script_path = trigger.base.script_path.clone();
+    let is_flow = trigger.base.is_flow;
+    let w_id = trigger.base.workspace_id.clone();
+    let retry = trigger.base.retry.clone();
+    let error_handler_path = trigger.base.error_handler_path.clone();
+    let error_handler_args = trigger.base.error_handler_args.clone();
+    let trigger_path = format!(
+        "{}/{}",
+        WebsocketTriggerHandler::TABLE_NAME,
+        trigger.base.path
+    );
+    let handle_response_f = async move {
+        let result = trigger_runnable_and_wait_for_raw_result(
+            &db_,
+            None,
+            authed,
+            &w_id,
+            &script_path,
+            is_flow,
+            args,
+            retry.as_ref(),
+            error_handler_path.as_deref(),
+            error_handler_args.as_ref(),
+            trigger_path,
+        )
+        .await;
+        if let Some((correlation, correlation_id)) = response_correlation {
+            // correlated responses are always sent so that the peer is not left waiting
+            let response = match &result {
+                Ok(result) => correlation.response(correlation_id, result),
+                Err(err) => correlation.error_response(correlation_id, &err.to_string()),
+            };
+            tracing::info!("Sending correlated job result to WebSocket {}", url);
+            if let Err(err) = send_message_tx.send(response).await {
+                report_critical_error(
+                    format!(
+                        "Could not send runnable result to WebSocket {} because of error: {}",
+                        url, err
+                    ),
+                    db_.clone(),
+                    Some(&w_id),
+                    None,
+                )
+                .await;
+            }
+        } else if let Ok(result) = result.map(|r| r.get().to_owned()) {
+            // only send the result if it's not null
+            if result != "null" {
+                tracing::info!("Sending job result to WebSocket {}", url);
+                // if the `result` was just a single string, the below removes the surrounding quotes by parsing it as a string.
+                // it falls back to the original serialized JSON if it doesn't work.
+                let result = serde_json::from_str::<String>(result.as_str()).unwrap_or(result);
+                if let Err(err) = send_message_tx.send(result).await {
+                    report_critical_error(
+                        format!(
+                            "Could not send runnable result to WebSocket {} because of error: {}",
+                            url, err
+                        ),
+                        db_.clone(),
+                        Some(&w_id),
+                        None,
+                    )
+                    .await;
+                }
+            }
+        }
+    };
+
+    tokio::spawn(handle_response_f);
+
+    Ok(())
+}
@@ -0,0 +1,943 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Forks are workspaces created from a parent workspace to work on its items in isolation, and
+//! whose changes are merged back into the parent once done.
+//!
+//! A fork starts empty and shares the items of its parent: the scripts, flows, apps, resources
+//! and variables it does not hold are resolved from the parent (see
+//! `windmill_common::workspaces::fork_item_workspace`). An item is copied into the fork before it
+//! is first written to, which records the fingerprint of the item in the fork and in the parent.
+//! Those fingerprints, updated after every merge, are how a merge tells apart what changed in the
+//! fork, in the parent, or in both of them (a conflict). Schedules and triggers are never shared
+//! so that the fork does not run anything on its own.
+//!
+//! Secret values are never part of the fingerprints, and the value of a secret variable that
+//! exists in the parent is never merged back.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use axum::{
+    extract::{Extension, Path},
+    routing::{get, post},
+    Json, Router,
+};
+use magic_crypt::MagicCrypt256;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::PgConnection;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    utils::{not_found_if_none, require_admin},
+    variables::build_crypt,
+};
+
+use crate::{
+    db::{ApiAuthed, DB},
+    promotions::{diff_pointers, files_value, item_files, workspace_user, PromotionKind},
+    workspaces::{check_can_create_workspace, insert_workspace},
+    workspaces_import::{
+        import_archive, ConflictStrategy, ImportArchive, ImportOptions, ImportReport,
+    },
+};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/create", post(create_fork))
+        .route("/list", get(list_forks))
+        .route("/get", get(get_fork))
+        .route("/inherited", get(list_inherited))
+        .route("/copy", post(copy_item))
+        .route("/diff", get(diff_fork))
+        .route("/merge", post(merge_fork))
+}
+
+/// What the secret variables of the parent hold in the fork
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[sqlx(type_name = "FORK_SECRETS", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ForkSecrets {
+    /// the values of the parent, so the fork uses the same credentials
+    Reference,
+    /// no value, to be set in the fork
+    Mask,
+}
+
+#[derive(Serialize)]
+struct WorkspaceFork {
+    workspace_id: String,
+    parent_workspace_id: String,
+    secrets: ForkSecrets,
+    created_by: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    merged_by: Option<String>,
+    merged_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ForkItemStatus {
+    /// created in the fork
+    Added,
+    /// changed in the fork only
+    Modified,
+    /// changed in the fork and in the parent, merged only when conflicts are overwritten
+    Conflict,
+    /// deleted in the fork, deletions are not merged back
+    Deleted,
+}
+
+#[derive(Serialize)]
+struct ForkItemDiff {
+    kind: PromotionKind,
+    path: String,
+    status: ForkItemStatus,
+    /// JSON pointers of the fields that differ between the fork and the parent
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    changes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ForkMergeReport {
+    parent_workspace_id: String,
+    items: Vec<ForkItemDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    import: Option<ImportReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ForkItem {
+    kind: PromotionKind,
+    path: String,
+}
+
+const FORKED_KINDS: [PromotionKind; 7] = [
+    PromotionKind::Folder,
+    PromotionKind::ResourceType,
+    PromotionKind::Variable,
+    PromotionKind::Resource,
+    PromotionKind::Script,
+    PromotionKind::Flow,
+    PromotionKind::App,
+];
+
+fn kind_str(kind: PromotionKind) -> &'static str {
+    match kind {
+        PromotionKind::Script => "script",
+        PromotionKind::Flow => "flow",
+        PromotionKind::App => "app",
+        PromotionKind::Folder => "folder",
+        PromotionKind::Resource => "resource",
+        PromotionKind::Variable => "variable",
+        PromotionKind::Schedule => "schedule",
+        PromotionKind::ResourceType => "resource_type",
+    }
+}
+
+fn kind_from_str(kind: &str) -> Option<PromotionKind> {
+    FORKED_KINDS.into_iter().find(|k| kind_str(*k) == kind)
+}
+
+/// Paths of the items of a kind that forks are made of
+async fn list_paths(
+    conn: &mut PgConnection,
+    w_id: &str,
+    kind: PromotionKind,
+) -> Result<Vec<String>> {
+    let paths = match kind {
+        PromotionKind::Folder => {
+            sqlx::query_scalar!("SELECT name FROM folder WHERE workspace_id = $1", w_id)
+                .fetch_all(&mut *conn)
+                .await?
+        }
+        PromotionKind::ResourceType => {
+            sqlx::query_scalar!("SELECT name FROM resource_type WHERE workspace_id = $1", w_id)
+                .fetch_all(&mut *conn)
+                .await?
+        }
+        PromotionKind::Variable => sqlx::query_scalar!(
+            "SELECT path FROM variable WHERE workspace_id = $1 AND expires_at IS NULL",
+            w_id
+        )
+        .fetch_all(&mut *conn)
+        .await?,
+        PromotionKind::Resource => sqlx::query_scalar!(
+            "SELECT path FROM resource WHERE workspace_id = $1 AND resource_type != 'state' AND resource_type != 'cache'",
+            w_id
+        )
+        .fetch_all(&mut *conn)
+        .await?,
+        PromotionKind::Script => sqlx::query_scalar!(
+            "SELECT DISTINCT path FROM script WHERE workspace_id = $1 AND archived = false",
+            w_id
+        )
+        .fetch_all(&mut *conn)
+        .await?,
+        PromotionKind::Flow => sqlx::query_scalar!(
+            "SELECT path FROM flow WHERE workspace_id = $1 AND archived = false",
+            w_id
+        )
+        .fetch_all(&mut *conn)
+        .await?,
+        PromotionKind::App => sqlx::query_scalar!(
+            "SELECT app.path FROM app, app_version
+             WHERE app.workspace_id = $1 AND app_version.id = app.versions[array_upper(app.versions, 1)] AND app_version.raw_app IS false",
+            w_id
+        )
+        .fetch_all(&mut *conn)
+        .await?,
+        PromotionKind::Schedule => vec![],
+    };
+    Ok(paths)
+}
+
+fn is_secret_variable(kind: PromotionKind, files: &[(String, String)]) -> bool {
+    kind == PromotionKind::Variable
+        && files.iter().any(|(_, content)| {
+            serde_json::from_str::<Value>(content)
+                .ok()
+                .and_then(|v| v.get("is_secret").and_then(Value::as_bool))
+                .unwrap_or(false)
+        })
+}
+
+/// Files of the item with the value of the secret variable replaced
+fn with_secret_value(
+    kind: PromotionKind,
+    files: Vec<(String, String)>,
+    secret: &Value,
+) -> Result<Vec<(String, String)>> {
+    if !is_secret_variable(kind, &files) {
+        return Ok(files);
+    }
+    files
+        .into_iter()
+        .map(|(file, content)| {
+            let mut variable = serde_json::from_str::<Value>(&content)
+                .map_err(|e| Error::internal_err(format!("Invalid variable file {file}: {e}")))?;
+            variable["value"] = secret.clone();
+            Ok((file, variable.to_string()))
+        })
+        .collect()
+}
+
+fn secret_value(files: &[(String, String)]) -> Value {
+    files
+        .iter()
+        .find_map(|(_, content)| {
+            serde_json::from_str::<Value>(content)
+                .ok()
+                .and_then(|v| v.get("value").cloned())
+        })
+        .unwrap_or(Value::Null)
+}
+
+/// Value compared between the fork and the parent, without the secret values
+fn item_value(kind: PromotionKind, files: &[(String, String)], path: &str) -> Value {
+    let mut value = files_value(files, path);
+    if is_secret_variable(kind, files) {
+        if let Some(Value::Object(variable)) = value.get_mut("variable.json") {
+            variable.insert("value".to_string(), Value::Null);
+        }
+    }
+    value
+}
+
+/// `None` when the item does not exist
+fn fingerprint(kind: PromotionKind, files: &[(String, String)], path: &str) -> Option<String> {
+    (!files.is_empty()).then(|| {
+        hex::encode(Sha256::digest(
+            item_value(kind, files, path).to_string().as_bytes(),
+        ))
+    })
+}
+
+/// How an item of the fork compares to the parent, from its fingerprint in the fork and in the
+/// parent and the ones recorded when it was last copied from or merged into the parent. `None`
+/// when there is nothing to merge.
+fn item_status(
+    fork: Option<&str>,
+    base: Option<(&str, &str)>,
+    parent: Option<&str>,
+) -> Option<ForkItemStatus> {
+    match (fork, base) {
+        (None, None) => None,
+        (Some(fork), None) => match parent {
+            None => Some(ForkItemStatus::Added),
+            Some(parent) if parent == fork => None,
+            // created in both of them, instead of being copied into the fork
+            Some(_) => Some(ForkItemStatus::Conflict),
+        },
+        (Some(fork), Some((base_fork, base_parent))) => {
+            if fork == base_fork || parent == Some(fork) {
+                None
+            } else if parent == Some(base_parent) {
+                Some(ForkItemStatus::Modified)
+            } else {
+                Some(ForkItemStatus::Conflict)
+            }
+        }
+        (None, Some((_, base_parent))) => match parent {
+            None => None,
+            Some(parent) if parent == base_parent => Some(ForkItemStatus::Deleted),
+            Some(_) => Some(ForkItemStatus::Conflict),
+        },
+    }
+}
+
+/// Records the fingerprints of the item in the fork and in the parent as the base of the next
+/// merge. They are read through the transaction that copied or merged the item, so that they are
+/// the ones it committed.
+async fn record_fingerprints(
+    conn: &mut PgConnection,
+    fork: &WorkspaceFork,
+    kind: PromotionKind,
+    path: &str,
+    fork_mc: &MagicCrypt256,
+    parent_mc: &MagicCrypt256,
+) -> Result<()> {
+    let fork_files = item_files(&mut *conn, &fork.workspace_id, fork_mc, kind, path, path).await?;
+    let parent_files = item_files(
+        &mut *conn,
+        &fork.parent_workspace_id,
+        parent_mc,
+        kind,
+        path,
+        path,
+    )
+    .await?;
+    match (
+        fingerprint(kind, &fork_files, path),
+        fingerprint(kind, &parent_files, path),
+    ) {
+        (Some(fork_fingerprint), Some(parent_fingerprint)) => {
+            sqlx::query!(
+                "INSERT INTO workspace_fork_item (workspace_id, kind, path, fork_fingerprint, parent_fingerprint)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (workspace_id, kind, path) DO UPDATE
+                 SET fork_fingerprint = EXCLUDED.fork_fingerprint, parent_fingerprint = EXCLUDED.parent_fingerprint",
+                fork.workspace_id,
+                kind_str(kind),
+                path,
+                fork_fingerprint,
+                parent_fingerprint
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        _ => {
+            sqlx::query!(
+                "DELETE FROM workspace_fork_item WHERE workspace_id = $1 AND kind = $2 AND path = $3",
+                fork.workspace_id,
+                kind_str(kind),
+                path
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct NewFork {
+    id: String,
+    name: String,
+    #[serde(default = "default_secrets")]
+    secrets: ForkSecrets,
+    color: Option<String>,
+}
+
+fn default_secrets() -> ForkSecrets {
+    ForkSecrets::Mask
+}
+
+/// Forking is reserved to the admins of the workspace, as the fork resolves every item of the
+/// workspace that it does not hold itself
+async fn create_fork(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(nf): Json<NewFork>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    check_can_create_workspace(&db, &authed.email).await?;
+    if fetch_fork(&db, &w_id).await?.is_some() {
+        return Err(Error::BadRequest(format!(
+            "{w_id} is a fork, fork its parent instead"
+        )));
+    }
+
+    let mut tx = db.begin().await?;
+    insert_workspace(
+        &mut tx,
+        &authed.email,
+        &authed.username,
+        &nf.id,
+        &nf.name,
+        nf.color.as_deref(),
+    )
+    .await?;
+    sqlx::query!(
+        "INSERT INTO workspace_fork (workspace_id, parent_workspace_id, secrets, created_by)
+         VALUES ($1, $2, $3, $4)",
+        nf.id,
+        w_id,
+        nf.secrets as ForkSecrets,
+        authed.email
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.fork",
+        ActionKind::Create,
+        &w_id,
+        Some(&nf.id),
+        Some(HashMap::from([("secrets", secrets_str(nf.secrets))])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Created fork {} of workspace {w_id}", nf.id))
+}
+
+fn secrets_str(secrets: ForkSecrets) -> &'static str {
+    match secrets {
+        ForkSecrets::Reference => "reference",
+        ForkSecrets::Mask => "mask",
+    }
+}
+
+/// Forks of the workspace
+async fn list_forks(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Vec<WorkspaceFork>> {
+    if authed.is_operator {
+        return Err(Error::NotAuthorized(
+            "Operators cannot list forks".to_string(),
+        ));
+    }
+    let forks = sqlx::query_as!(
+        WorkspaceFork,
+        r#"SELECT workspace_id, parent_workspace_id, secrets AS "secrets: ForkSecrets", created_by,
+         created_at, merged_by, merged_at
+         FROM workspace_fork WHERE parent_workspace_id = $1 ORDER BY created_at DESC"#,
+        w_id
+    )
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(forks))
+}
+
+async fn fetch_fork(db: &DB, w_id: &str) -> Result<Option<WorkspaceFork>> {
+    let fork = sqlx::query_as!(
+        WorkspaceFork,
+        r#"SELECT workspace_id, parent_workspace_id, secrets AS "secrets: ForkSecrets", created_by,
+         created_at, merged_by, merged_at
+         FROM workspace_fork WHERE workspace_id = $1"#,
+        w_id
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(fork)
+}
+
+/// The parent of the workspace, if it is a fork
+async fn get_fork(
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Option<WorkspaceFork>> {
+    Ok(Json(fetch_fork(&db, &w_id).await?))
+}
+
+async fn require_fork(db: &DB, w_id: &str) -> Result<WorkspaceFork> {
+    not_found_if_none(fetch_fork(db, w_id).await?, "Workspace fork", w_id)
+}
+
+/// The items of the parent visible to the user that the fork shares, as it does not hold them
+async fn list_inherited(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Vec<ForkItem>> {
+    let fork = require_fork(&db, &w_id).await?;
+    let mut conn = db.acquire().await?;
+    let mut tx = user_db.begin(&authed).await?;
+    let mut items = vec![];
+    for kind in FORKED_KINDS {
+        let held = list_paths(&mut conn, &w_id, kind)
+            .await?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        items.extend(
+            list_paths(&mut tx, &fork.parent_workspace_id, kind)
+                .await?
+                .into_iter()
+                .filter(|path| !held.contains(path))
+                .map(|path| ForkItem { kind, path }),
+        );
+    }
+    tx.commit().await?;
+    Ok(Json(items))
+}
+
+/// Files of the item in the parent, read through `conn` with the permissions of the user, after
+/// the ones of the folder and resource type it depends on that the fork does not hold yet
+async fn parent_files(
+    conn: &mut PgConnection,
+    db: &DB,
+    fork: &WorkspaceFork,
+    mc: &MagicCrypt256,
+    item: &ForkItem,
+) -> Result<Vec<(PromotionKind, String, Vec<(String, String)>)>> {
+    let files = item_files(
+        &mut *conn,
+        &fork.parent_workspace_id,
+        mc,
+        item.kind,
+        &item.path,
+        &item.path,
+    )
+    .await?;
+    if files.is_empty() {
+        return Err(Error::NotFound(format!(
+            "{} {} not found in {}",
+            kind_str(item.kind),
+            item.path,
+            fork.parent_workspace_id
+        )));
+    }
+
+    let mut dependencies = vec![];
+    if item.kind != PromotionKind::Folder {
+        if let Some(folder) = item
+            .path
+            .strip_prefix("f/")
+            .and_then(|p| p.split('/').next())
+        {
+            dependencies.push((PromotionKind::Folder, folder.to_string()));
+        }
+    }
+    if item.kind == PromotionKind::Resource {
+        if let Some(resource_type) = files_value(&files, &item.path)
+            .pointer("/resource.json/resource_type")
+            .and_then(Value::as_str)
+        {
+            dependencies.push((PromotionKind::ResourceType, resource_type.to_string()));
+        }
+    }
+
+    let mut items = vec![];
+    for (kind, path) in dependencies {
+        if !item_files(db, &fork.workspace_id, mc, kind, &path, &path)
+            .await?
+            .is_empty()
+        {
+            continue;
+        }
+        let files = item_files(
+            &mut *conn,
+            &fork.parent_workspace_id,
+            mc,
+            kind,
+            &path,
+            &path,
+        )
+        .await?;
+        if !files.is_empty() {
+            items.push((kind, path, files));
+        }
+    }
+    items.push((item.kind, item.path.clone(), files));
+    Ok(items)
+}
+
+/// Copies an item that the fork shares with its parent into the fork, which is to be done before
+/// it is first written to in the fork
+async fn copy_item(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path(w_id): Path<String>,
+    Json(item): Json<ForkItem>,
+) -> Result<String> {
+    if authed.is_operator {
+        return Err(Error::NotAuthorized(
+            "Operators cannot write to a fork".to_string(),
+        ));
+    }
+    if kind_from_str(kind_str(item.kind)).is_none() {
+        return Err(Error::BadRequest(format!(
+            "Items of kind {} are not shared with forks",
+            kind_str(item.kind)
+        )));
+    }
+    let fork = require_fork(&db, &w_id).await?;
+    let fork_mc = build_crypt(&db, &w_id).await?;
+    let parent_mc = build_crypt(&db, &fork.parent_workspace_id).await?;
+    if !item_files(&db, &w_id, &fork_mc, item.kind, &item.path, &item.path)
+        .await?
+        .is_empty()
+    {
+        return Err(Error::BadRequest(format!(
+            "{} {} is already in the fork",
+            kind_str(item.kind),
+            item.path
+        )));
+    }
+
+    let mut user_tx = user_db.begin(&authed).await?;
+    let items = parent_files(&mut user_tx, &db, &fork, &parent_mc, &item).await?;
+    user_tx.commit().await?;
+
+    let mut files = BTreeMap::new();
+    for (kind, _, item_files) in &items {
+        let item_files = match fork.secrets {
+            ForkSecrets::Mask => {
+                with_secret_value(*kind, item_files.clone(), &Value::String(String::new()))?
+            }
+            ForkSecrets::Reference => item_files.clone(),
+        };
+        files.extend(item_files);
+    }
+
+    let fork_authed = ApiAuthed { is_admin: true, ..authed.clone() };
+    let options = ImportOptions {
+        on_conflict: ConflictStrategy::Overwrite,
+        plain_secrets: true,
+        import_settings: false,
+    };
+    let tx = db.begin().await?;
+    let (mut tx, _) = import_archive(
+        &db,
+        &fork_authed,
+        tx,
+        &w_id,
+        ImportArchive::parse(files, false)?,
+        &options,
+    )
+    .await?;
+    for (kind, path, _) in &items {
+        record_fingerprints(&mut tx, &fork, *kind, path, &fork_mc, &parent_mc).await?;
+    }
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.fork_copy",
+        ActionKind::Create,
+        &w_id,
+        Some(&item.path),
+        Some(HashMap::from([("kind", kind_str(item.kind))])),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!(
+        "Copied {} {} from {}",
+        kind_str(item.kind),
+        item.path,
+        fork.parent_workspace_id
+    ))
+}
+
+/// The items changed in the fork since they were copied or last merged, and the archive files of
+/// the ones to merge into the parent. Only the items that the fork holds are compared.
+async fn compute_fork_diff(
+    conn: &mut PgConnection,
+    db: &DB,
+    fork: &WorkspaceFork,
+    overwrite_conflicts: bool,
+) -> Result<(
+    BTreeMap<String, String>,
+    Vec<ForkItemDiff>,
+    Vec<(PromotionKind, String)>,
+)> {
+    let fork_mc = build_crypt(db, &fork.workspace_id).await?;
+    let parent_mc = build_crypt(db, &fork.parent_workspace_id).await?;
+    let base = sqlx::query!(
+        "SELECT kind, path, fork_fingerprint, parent_fingerprint FROM workspace_fork_item
+         WHERE workspace_id = $1",
+        fork.workspace_id
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .filter_map(|item| {
+        kind_from_str(&item.kind).map(|kind| {
+            (
+                (kind_str(kind), item.path),
+                (item.fork_fingerprint, item.parent_fingerprint),
+            )
+        })
+    })
+    .collect::<BTreeMap<_, _>>();
+
+    let mut keys = base.keys().cloned().collect::<BTreeSet<_>>();
+    for kind in FORKED_KINDS {
+        for path in list_paths(&mut *conn, &fork.workspace_id, kind).await? {
+            keys.insert((kind_str(kind), path));
+        }
+    }
+
+    let mut archive_files = BTreeMap::new();
+    let mut diff = vec![];
+    let mut merged = vec![];
+    for key in keys {
+        let Some(kind) = kind_from_str(key.0) else {
+            continue;
+        };
+        let path = &key.1;
+        let fork_files =
+            item_files(&mut *conn, &fork.workspace_id, &fork_mc, kind, path, path).await?;
+        let parent_files = item_files(
+            &mut *conn,
+            &fork.parent_workspace_id,
+            &parent_mc,
+            kind,
+            path,
+            path,
+        )
+        .await?;
+        let Some(status) = item_status(
+            fingerprint(kind, &fork_files, path).as_deref(),
+            base.get(&key)
+                .map(|(fork_fp, parent_fp)| (fork_fp.as_str(), parent_fp.as_str())),
+            fingerprint(kind, &parent_files, path).as_deref(),
+        ) else {
+            continue;
+        };
+
+        let mut changes = vec![];
+        if !fork_files.is_empty() && !parent_files.is_empty() {
+            diff_pointers(
+                String::new(),
+                &item_value(kind, &fork_files, path),
+                &item_value(kind, &parent_files, path),
+                &mut changes,
+            );
+        }
+        let merge = match status {
+            ForkItemStatus::Added | ForkItemStatus::Modified => true,
+            ForkItemStatus::Conflict => overwrite_conflicts && !fork_files.is_empty(),
+            ForkItemStatus::Deleted => false,
+        };
+        if merge {
+            let files = if parent_files.is_empty() {
+                fork_files
+            } else {
+                with_secret_value(kind, fork_files, &secret_value(&parent_files))?
+            };
+            archive_files.extend(files);
+            merged.push((kind, path.clone()));
+        }
+        diff.push(ForkItemDiff { kind, path: path.clone(), status, changes });
+    }
+    Ok((archive_files, diff, merged))
+}
+
+/// What a merge would apply to the parent
+async fn diff_fork(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<ForkMergeReport> {
+    if authed.is_operator {
+        return Err(Error::NotAuthorized(
+            "Operators cannot diff a fork".to_string(),
+        ));
+    }
+    let fork = require_fork(&db, &w_id).await?;
+    let mut conn = db.acquire().await?;
+    let (_, items, _) = compute_fork_diff(&mut conn, &db, &fork, false).await?;
+    Ok(Json(ForkMergeReport {
+        parent_workspace_id: fork.parent_workspace_id,
+        items,
+        import: None,
+    }))
+}
+
+#[derive(Deserialize)]
+struct MergeFork {
+    /// merge the items changed in both the fork and the parent, instead of reporting them only
+    #[serde(default)]
+    overwrite_conflicts: bool,
+}
+
+/// Merges the items changed in the fork into the parent, which requires being an admin of the
+/// parent. The conflicts are reported and not merged unless overwritten.
+///
+/// The diff is computed in the repeatable read transaction that applies it, so that a merge
+/// fails instead of overwriting an item that the parent changed in the meantime.
+async fn merge_fork(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(mf): Json<MergeFork>,
+) -> JsonResult<ForkMergeReport> {
+    let fork = require_fork(&db, &w_id).await?;
+    let (username, is_admin) =
+        workspace_user(&db, &authed.email, &fork.parent_workspace_id).await?;
+    require_admin(is_admin, &username)?;
+
+    let mut tx = db.begin().await?;
+    sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+        .execute(&mut *tx)
+        .await?;
+    let (files, items, merged) =
+        compute_fork_diff(&mut tx, &db, &fork, mf.overwrite_conflicts).await?;
+    if merged.is_empty() {
+        return Ok(Json(ForkMergeReport {
+            parent_workspace_id: fork.parent_workspace_id,
+            items,
+            import: None,
+        }));
+    }
+
+    let parent_authed = ApiAuthed { username, is_admin, ..authed.clone() };
+    let options = ImportOptions {
+        on_conflict: ConflictStrategy::Overwrite,
+        plain_secrets: true,
+        import_settings: false,
+    };
+    let (mut tx, report) = import_archive(
+        &db,
+        &parent_authed,
+        tx,
+        &fork.parent_workspace_id,
+        ImportArchive::parse(files, false)?,
+        &options,
+    )
+    .await?;
+    let fork_mc = build_crypt(&db, &fork.workspace_id).await?;
+    let parent_mc = build_crypt(&db, &fork.parent_workspace_id).await?;
+    for (kind, path) in &merged {
+        record_fingerprints(&mut tx, &fork, *kind, path, &fork_mc, &parent_mc).await?;
+    }
+    sqlx::query!(
+        "UPDATE workspace_fork SET merged_by = $1, merged_at = now() WHERE workspace_id = $2",
+        authed.email,
+        w_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit_log(
+        &mut *tx,
+        &parent_authed,
+        "workspaces.merge_fork",
+        ActionKind::Execute,
+        &fork.parent_workspace_id,
+        Some(&w_id),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(ForkMergeReport {
+        parent_workspace_id: fork.parent_workspace_id,
+        items,
+        import: Some(report),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "u/alice/db";
+
+    fn variable(value: &str, is_secret: bool) -> Vec<(String, String)> {
+        vec![(
+            format!("{PATH}.variable.json"),
+            serde_json::json!({ "value": value, "is_secret": is_secret, "description": "" })
+                .to_string(),
+        )]
+    }
+
+    #[test]
+    fn test_item_status() {
+        use ForkItemStatus::*;
+        // neither copied nor created in the fork
+        assert_eq!(item_status(None, None, Some("p")), None);
+        // created in the fork
+        assert_eq!(item_status(Some("f"), None, None), Some(Added));
+        assert_eq!(item_status(Some("f"), None, Some("f")), None);
+        assert_eq!(item_status(Some("f"), None, Some("p")), Some(Conflict));
+        // copied and left as is, whatever the parent did since
+        assert_eq!(item_status(Some("b"), Some(("b", "b")), Some("p")), None);
+        // copied and changed in the fork only
+        assert_eq!(
+            item_status(Some("f"), Some(("b", "b")), Some("b")),
+            Some(Modified)
+        );
+        // copied and changed in both, unless to the same
+        assert_eq!(
+            item_status(Some("f"), Some(("b", "b")), Some("p")),
+            Some(Conflict)
+        );
+        assert_eq!(item_status(Some("f"), Some(("b", "b")), Some("f")), None);
+        assert_eq!(
+            item_status(Some("f"), Some(("b", "b")), None),
+            Some(Conflict)
+        );
+        // copied and deleted in the fork
+        assert_eq!(
+            item_status(None, Some(("b", "b")), Some("b")),
+            Some(Deleted)
+        );
+        assert_eq!(
+            item_status(None, Some(("b", "b")), Some("p")),
+            Some(Conflict)
+        );
+        assert_eq!(item_status(None, Some(("b", "b")), None), None);
+    }
+
+    #[test]
+    fn test_secret_values_are_not_fingerprinted() {
+        let kind = PromotionKind::Variable;
+        assert_eq!(
+            fingerprint(kind, &variable("a", true), PATH),
+            fingerprint(kind, &variable("b", true), PATH)
+        );
+        assert_ne!(
+            fingerprint(kind, &variable("a", false), PATH),
+            fingerprint(kind, &variable("b", false), PATH)
+        );
+        assert_eq!(fingerprint(kind, &[], PATH), None);
+    }
+
+    #[test]
+    fn test_with_secret_value() {
+        let kind = PromotionKind::Variable;
+        let masked = with_secret_value(
+            kind,
+            variable("hunter2", true),
+            &Value::String(String::new()),
+        )
+        .unwrap();
+        assert_eq!(secret_value(&masked), Value::String(String::new()));
+
+        let restored = with_secret_value(kind, masked, &Value::from("hunter2")).unwrap();
+        assert_eq!(secret_value(&restored), Value::from("hunter2"));
+
+        let plain = with_secret_value(
+            kind,
+            variable("visible", false),
+            &Value::String(String::new()),
+        )
+        .unwrap();
+        assert_eq!(secret_value(&plain), Value::from("visible"));
+    }
+}
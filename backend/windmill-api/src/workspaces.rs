@@ -10,6 +10,7 @@ use std::collections::HashMap;
 
 use crate::ai::{AIConfig, AI_REQUEST_CACHE};
 use crate::db::ApiAuthed;
+use crate::rate_limits::{validate_webhook_rate_limits, WebhookRateLimit};
 use crate::users_oss::send_email_if_possible;
 use crate::utils::get_instance_username_or_create_pending;
 use crate::BASE_URL;
@@ -97,6 +98,8 @@ pub fn workspaced_service() -> Router {
             post(run_teams_message_test_job),
         )
         .route("/edit_webhook", post(edit_webhook))
+        .route("/edit_webhook_rate_limits", post(edit_webhook_rate_limits))
+        .route("/edit_idempotency_window", post(edit_idempotency_window))
         .route("/edit_auto_invite", post(edit_auto_invite))
         .route("/edit_deploy_to", post(edit_deploy_to))
         .route(
@@ -104,6 +107,11 @@ pub fn workspaced_service() -> Router {
             get(get_secondary_storage_names),
         )
         .route("/tarball", get(crate::workspaces_export::tarball_workspace))
+        .route(
+            "/import_tarball",
+            post(crate::workspaces_import::import_tarball)
+                .layer(axum::extract::DefaultBodyLimit::disable()),
+        )
         .route("/is_premium", get(is_premium))
         .route("/edit_copilot_config", post(edit_copilot_config))
         .route("/get_copilot_info", get(get_copilot_info))
@@ -161,6 +169,11 @@ pub fn global_service() -> Router {
         .route("/list", get(list_workspaces))
         .route("/users", get(user_workspaces))
         .route("/create", post(create_workspace))
+        .route(
+            "/create_from_tarball",
+            post(crate::workspaces_import::create_workspace_from_tarball)
+                .layer(axum::extract::DefaultBodyLimit::disable()),
+        )
         .route("/exists", post(exists_workspace))
         .route("/exists_username", post(exists_username))
         .route("/allowed_domain_auto_invite", get(is_allowed_auto_domain))
@@ -240,6 +253,10 @@ pub struct WorkspaceSettings {
     pub operator_settings: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub git_app_installations: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_rate_limits: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency_window_s: Option<i32>,
 }
 
 #[derive(FromRow, Serialize, Debug)]
@@ -293,6 +310,16 @@ struct EditWebhook {
     webhook: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct EditWebhookRateLimits {
+    rate_limits: Option<Vec<WebhookRateLimit>>,
+}
+
+#[derive(Deserialize)]
+struct EditIdempotencyWindow {
+    idempotency_window_s: Option<i32>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 struct LargeFileStorageWithSecondary {
     #[serde(flatten)]
@@ -307,11 +334,11 @@ struct EditLargeFileStorageConfig {
 }
 
 #[derive(Deserialize)]
-struct CreateWorkspace {
-    id: String,
-    name: String,
-    username: Option<String>,
-    color: Option<String>,
+pub(crate) struct CreateWorkspace {
+    pub id: String,
+    pub name: String,
+    pub username: Option<String>,
+    pub color: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -444,7 +471,7 @@ async fn get_settings(
     let mut tx = user_db.begin(&authed).await?;
     let settings = sqlx::query_as!(
         WorkspaceSettings,
-        "SELECT workspace_id, slack_team_id, teams_team_id, teams_team_name, slack_name, slack_command_script, teams_command_script, slack_email, auto_invite_domain, auto_invite_operator, auto_add, customer_id, plan, webhook, deploy_to, ai_config, error_handler, error_handler_extra_args, error_handler_muted_on_cancel, large_file_storage, git_sync, deploy_ui, default_app, default_scripts, mute_critical_alerts, color, operator_settings, git_app_installations FROM workspace_settings WHERE workspace_id = $1",
+        "SELECT workspace_id, slack_team_id, teams_team_id, teams_team_name, slack_name, slack_command_script, teams_command_script, slack_email, auto_invite_domain, auto_invite_operator, auto_add, customer_id, plan, webhook, deploy_to, ai_config, error_handler, error_handler_extra_args, error_handler_muted_on_cancel, large_file_storage, git_sync, deploy_ui, default_app, default_scripts, mute_critical_alerts, color, operator_settings, git_app_installations, webhook_rate_limits, idempotency_window_s FROM workspace_settings WHERE workspace_id = $1",
         &w_id
     )
     .fetch_optional(&mut *tx)
@@ -726,6 +753,85 @@ async fn edit_webhook(
     Ok(format!("Edit webhook for workspace {}", &w_id))
 }
 
+async fn edit_webhook_rate_limits(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    ApiAuthed { is_admin, username, .. }: ApiAuthed,
+    Json(er): Json<EditWebhookRateLimits>,
+) -> Result<String> {
+    require_admin(is_admin, &username)?;
+
+    let rate_limits = er.rate_limits.filter(|rate_limits| !rate_limits.is_empty());
+    if let Some(rate_limits) = &rate_limits {
+        validate_webhook_rate_limits(rate_limits)?;
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE workspace_settings SET webhook_rate_limits = $1 WHERE workspace_id = $2",
+        rate_limits.as_ref().map(sqlx::types::Json) as Option<sqlx::types::Json<&Vec<WebhookRateLimit>>>,
+        &w_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_webhook_rate_limits",
+        ActionKind::Update,
+        &w_id,
+        Some(&authed.email),
+        Some([("rate_limits", &format!("{:?}", rate_limits)[..])].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Edit webhook rate limits for workspace {}", &w_id))
+}
+
+async fn edit_idempotency_window(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    ApiAuthed { is_admin, username, .. }: ApiAuthed,
+    Json(ew): Json<EditIdempotencyWindow>,
+) -> Result<String> {
+    require_admin(is_admin, &username)?;
+
+    if ew.idempotency_window_s.is_some_and(|window_s| window_s <= 0) {
+        return Err(Error::BadRequest(
+            "Idempotency window must be greater than 0".to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE workspace_settings SET idempotency_window_s = $1 WHERE workspace_id = $2",
+        ew.idempotency_window_s,
+        &w_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.edit_idempotency_window",
+        ActionKind::Update,
+        &w_id,
+        Some(&authed.email),
+        Some([("idempotency_window_s", &format!("{:?}", ew.idempotency_window_s)[..])].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("Edit idempotency window for workspace {}", &w_id))
+}
+
 async fn edit_copilot_config(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
@@ -803,6 +909,7 @@ async fn get_copilot_info(
             providers: None,
             default_model: None,
             code_completion_model: None,
+            budgets: None,
         }))
     }
 }
@@ -1437,6 +1544,10 @@ struct UsedTriggers {
     pub nats_used: bool,
     pub postgres_used: bool,
     pub mqtt_used: bool,
+    pub mysql_used: bool,
+    pub amqp_used: bool,
+    pub redis_used: bool,
+    pub s3_used: bool,
     pub sqs_used: bool,
     pub gcp_used: bool,
 }
@@ -1457,6 +1568,10 @@ async fn get_used_triggers(
             EXISTS(SELECT 1 FROM nats_trigger WHERE workspace_id = $1) as "nats_used!",
             EXISTS(SELECT 1 FROM postgres_trigger WHERE workspace_id = $1) AS "postgres_used!",
             EXISTS(SELECT 1 FROM mqtt_trigger WHERE workspace_id = $1) AS "mqtt_used!",
+            EXISTS(SELECT 1 FROM mysql_trigger WHERE workspace_id = $1) AS "mysql_used!",
+            EXISTS(SELECT 1 FROM amqp_trigger WHERE workspace_id = $1) AS "amqp_used!",
+            EXISTS(SELECT 1 FROM redis_trigger WHERE workspace_id = $1) AS "redis_used!",
+            EXISTS(SELECT 1 FROM s3_trigger WHERE workspace_id = $1) AS "s3_used!",
             EXISTS(SELECT 1 FROM sqs_trigger WHERE workspace_id = $1) AS "sqs_used!",
             EXISTS(SELECT 1 FROM gcp_trigger WHERE workspace_id = $1) AS "gcp_used!"
         "#,
@@ -1571,25 +1686,21 @@ async fn _check_nb_of_workspaces(db: &DB) -> Result<()> {
     return Ok(());
 }
 
-async fn create_workspace(
-    authed: ApiAuthed,
-    Extension(db): Extension<DB>,
-    Json(nw): Json<CreateWorkspace>,
-) -> Result<String> {
+/// Rejects the creation of a workspace by `email` when it is reserved to superadmins, or once the
+/// instance or the user reached the maximum number of workspaces
+pub(crate) async fn check_can_create_workspace(db: &DB, email: &str) -> Result<()> {
     if *CREATE_WORKSPACE_REQUIRE_SUPERADMIN {
-        require_super_admin(&db, &authed.email).await?;
+        require_super_admin(db, email).await?;
     }
 
     #[cfg(not(feature = "enterprise"))]
-    _check_nb_of_workspaces(&db).await?;
+    _check_nb_of_workspaces(db).await?;
 
     if *CLOUD_HOSTED {
-        let nb_workspaces = sqlx::query_scalar!(
-            "SELECT COUNT(*) FROM workspace WHERE owner = $1",
-            authed.email
-        )
-        .fetch_one(&db)
-        .await?;
+        let nb_workspaces =
+            sqlx::query_scalar!("SELECT COUNT(*) FROM workspace WHERE owner = $1", email)
+                .fetch_one(db)
+                .await?;
         if nb_workspaces.unwrap_or(0) >= 10 {
             return Err(Error::BadRequest(
                 "You have reached the maximum number of workspaces (10) on cloud. Contact support@windmill.dev to increase the limit"
@@ -1597,135 +1708,170 @@ async fn create_workspace(
             ));
         }
     }
-
-    let mut tx: Transaction<'_, Postgres> = db.begin().await?;
-
-    check_w_id_conflict(&mut tx, &nw.id).await?;
+    Ok(())
+}
+
+/// Inserts the workspace with `email` as its admin `username`, along with its key and the group
+/// all
+pub(crate) async fn insert_workspace<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    email: &str,
+    username: &str,
+    w_id: &str,
+    name: &str,
+    color: Option<&str>,
+) -> Result<()> {
+    check_w_id_conflict(tx, w_id).await?;
     sqlx::query!(
         "INSERT INTO workspace
             (id, name, owner)
             VALUES ($1, $2, $3)",
-        nw.id,
-        nw.name,
-        authed.email,
+        w_id,
+        name,
+        email,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
     sqlx::query!(
         "INSERT INTO workspace_settings
             (workspace_id, color)
             VALUES ($1, $2)",
-        nw.id,
-        nw.color,
+        w_id,
+        color,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
     let key = rd_string(64);
     sqlx::query!(
         "INSERT INTO workspace_key
             (workspace_id, kind, key)
             VALUES ($1, 'cloud', $2)",
-        nw.id,
+        w_id,
         &key
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
-    // let mc = magic_crypt::new_magic_crypt!(key, 256);
-    // sqlx::query!(
-    //     "INSERT INTO variable
-    //         (workspace_id, path, value, is_secret, description)
-    //         VALUES ($1, 'g/all/pretty_secret', $2, true, 'This item is secret'),
-    //             ($3, 'g/all/not_secret', $4, false, 'This item is not secret')",
-    //     nw.id,
-    //     crate::variables::encrypt(&mc, "pretty secret value"),
-    //     nw.id,
-    //     "finland does not actually exist",
-    // )
-    // .execute(&mut *tx)
-    // .await?;
-
-    let automate_username_creation = sqlx::query_scalar!(
-        "SELECT value FROM global_settings WHERE name = $1",
-        AUTOMATE_USERNAME_CREATION_SETTING,
-    )
-    .fetch_optional(&mut *tx)
-    .await?
-    .map(|v| v.as_bool())
-    .flatten()
-    .unwrap_or(false);
-
-    let username = if automate_username_creation {
-        if nw.username.is_some() && nw.username.unwrap().len() > 0 {
-            return Err(Error::BadRequest(
-                "username is not allowed when username creation is automated".to_string(),
-            ));
-        }
-        get_instance_username_or_create_pending(&mut tx, &authed.email).await?
-    } else {
-        nw.username
-            .ok_or(Error::BadRequest("username is required".to_string()))?
-    };
-
     sqlx::query!(
         "INSERT INTO usr
             (workspace_id, email, username, is_admin)
             VALUES ($1, $2, $3, true)",
-        nw.id,
-        authed.email,
+        w_id,
+        email,
         username,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
     sqlx::query!(
         "INSERT INTO group_
             VALUES ($1, 'all', 'The group that always contains all users of this workspace')",
-        nw.id
+        w_id
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
     sqlx::query!(
         "INSERT INTO usr_to_group
             VALUES ($1, 'all', $2)",
-        nw.id,
+        w_id,
         username
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
+    Ok(())
+}
 
+/// Inserts the app folders and the default app theme that new workspaces start with
+pub(crate) async fn insert_workspace_defaults<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    w_id: &str,
+    username: &str,
+) -> Result<()> {
     sqlx::query!(
         "INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, created_by, edited_at) VALUES ($1, 'app_themes', 'App Themes', ARRAY[]::TEXT[], '{\"g/all\": false}', $2, now()) ON CONFLICT DO NOTHING",
-        nw.id,
+        w_id,
         username,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
     sqlx::query!(
         "INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, created_by, edited_at) VALUES ($1, 'app_custom', 'App Custom Components', ARRAY[]::TEXT[], '{\"g/all\": false}', $2, now()) ON CONFLICT DO NOTHING",
-        nw.id,
+        w_id,
         username,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
     sqlx::query!(
         "INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, created_by, edited_at) VALUES ($1, 'app_groups', 'App Groups', ARRAY[]::TEXT[], '{\"g/all\": false}', $2, now()) ON CONFLICT DO NOTHING",
-        nw.id,
+        w_id,
         username,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
     sqlx::query!(
         "INSERT INTO resource (workspace_id, path, value, description, resource_type, created_by, edited_at) VALUES ($1, 'f/app_themes/theme_0', '{\"name\": \"Default Theme\", \"value\": \"\"}', 'The default app theme', 'app_theme', $2, now()) ON CONFLICT DO NOTHING",
-        nw.id,
+        w_id,
         username,
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Username of the creator in a new workspace, the instance username when username creation is
+/// automated
+pub(crate) async fn creator_username<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    email: &str,
+    username: Option<String>,
+) -> Result<String> {
+    let automate_username_creation = sqlx::query_scalar!(
+        "SELECT value FROM global_settings WHERE name = $1",
+        AUTOMATE_USERNAME_CREATION_SETTING,
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .map(|v| v.as_bool())
+    .flatten()
+    .unwrap_or(false);
+
+    if automate_username_creation {
+        if username.is_some_and(|x| x.len() > 0) {
+            return Err(Error::BadRequest(
+                "username is not allowed when username creation is automated".to_string(),
+            ));
+        }
+        get_instance_username_or_create_pending(tx, email).await
+    } else {
+        username.ok_or(Error::BadRequest("username is required".to_string()))
+    }
+}
+
+async fn create_workspace(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Json(nw): Json<CreateWorkspace>,
+) -> Result<String> {
+    check_can_create_workspace(&db, &authed.email).await?;
+
+    let mut tx: Transaction<'_, Postgres> = db.begin().await?;
+
+    let username = creator_username(&mut tx, &authed.email, nw.username).await?;
+
+    insert_workspace(
+        &mut tx,
+        &authed.email,
+        &username,
+        &nw.id,
+        &nw.name,
+        nw.color.as_deref(),
+    )
     .await?;
+    insert_workspace_defaults(&mut tx, &nw.id, &username).await?;
 
     audit_log(
         &mut *tx,
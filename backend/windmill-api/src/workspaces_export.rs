@@ -268,6 +268,80 @@ struct SimplifiedSettings {
     operator_settings: Option<serde_json::Value>,
 }
 
+/// The content file and the metadata file of a script in a workspace archive
+pub(crate) fn script_archive_files(
+    script: Script,
+    default_ts: Option<&str>,
+) -> [(String, String); 2] {
+    let ext = match script.language {
+        ScriptLang::Python3 => "py",
+        ScriptLang::Deno => {
+            if default_ts == Some("bun") {
+                "deno.ts"
+            } else {
+                "ts"
+            }
+        }
+        ScriptLang::Go => "go",
+        ScriptLang::Bash => "sh",
+        ScriptLang::Powershell => "ps1",
+        ScriptLang::Postgresql => "pg.sql",
+        ScriptLang::Mysql => "my.sql",
+        ScriptLang::Bigquery => "bq.sql",
+        ScriptLang::Snowflake => "sf.sql",
+        ScriptLang::Mssql => "ms.sql",
+        ScriptLang::DuckDb => "duckdb.sql",
+        ScriptLang::Graphql => "gql",
+        ScriptLang::Nativets => "fetch.ts",
+        ScriptLang::Bun | ScriptLang::Bunnative => {
+            if default_ts == Some("bun") {
+                "ts"
+            } else {
+                "bun.ts"
+            }
+        }
+        ScriptLang::Php => "php",
+        ScriptLang::Rust => "rs",
+        ScriptLang::Ansible => "playbook.yml",
+        ScriptLang::CSharp => "cs",
+        ScriptLang::Nu => "nu",
+        ScriptLang::OracleDB => "odb.sql",
+        ScriptLang::Java => "java",
+        // for related places search: ADD_NEW_LANG
+    };
+    let content_file = (format!("{}.{}", script.path, ext), script.content);
+
+    let metadata = ScriptMetadata {
+        summary: script.summary,
+        description: script.description,
+        schema: script.schema,
+        kind: script.kind.to_string(),
+        lock: script.lock,
+        envs: script.envs,
+        concurrent_limit: script.concurrent_limit,
+        concurrency_time_window_s: script.concurrency_time_window_s,
+        cache_ttl: script.cache_ttl,
+        dedicated_worker: script.dedicated_worker,
+        ws_error_handler_muted: script.ws_error_handler_muted,
+        priority: script.priority,
+        tag: script.tag,
+        timeout: script.timeout,
+        delete_after_use: script.delete_after_use,
+        restart_unless_cancelled: script.restart_unless_cancelled,
+        visible_to_runner_only: script.visible_to_runner_only,
+        no_main_func: script.no_main_func,
+        codebase: script.codebase,
+        concurrency_key: script.concurrency_key,
+        has_preprocessor: script.has_preprocessor,
+        on_behalf_of_email: script.on_behalf_of_email,
+    };
+    let metadata_str = serde_json::to_string_pretty(&metadata).unwrap();
+    [
+        content_file,
+        (format!("{}.script.json", script.path), metadata_str),
+    ]
+}
+
 pub(crate) async fn tarball_workspace(
     authed: ApiAuthed,
     Extension(user_db): Extension<UserDB>,
@@ -343,74 +417,9 @@ pub(crate) async fn tarball_workspace(
         .await?;
 
         for script in scripts {
-            let ext = match script.language {
-                ScriptLang::Python3 => "py",
-                ScriptLang::Deno => {
-                    if default_ts.as_ref().is_some_and(|x| x == "bun") {
-                        "deno.ts"
-                    } else {
-                        "ts"
-                    }
-                }
-                ScriptLang::Go => "go",
-                ScriptLang::Bash => "sh",
-                ScriptLang::Powershell => "ps1",
-                ScriptLang::Postgresql => "pg.sql",
-                ScriptLang::Mysql => "my.sql",
-                ScriptLang::Bigquery => "bq.sql",
-                ScriptLang::Snowflake => "sf.sql",
-                ScriptLang::Mssql => "ms.sql",
-                ScriptLang::DuckDb => "duckdb.sql",
-                ScriptLang::Graphql => "gql",
-                ScriptLang::Nativets => "fetch.ts",
-                ScriptLang::Bun | ScriptLang::Bunnative => {
-                    if default_ts.as_ref().is_some_and(|x| x == "bun") {
-                        "ts"
-                    } else {
-                        "bun.ts"
-                    }
-                }
-                ScriptLang::Php => "php",
-                ScriptLang::Rust => "rs",
-                ScriptLang::Ansible => "playbook.yml",
-                ScriptLang::CSharp => "cs",
-                ScriptLang::Nu => "nu",
-                ScriptLang::OracleDB => "odb.sql",
-                ScriptLang::Java => "java",
-                // for related places search: ADD_NEW_LANG
-            };
-            archive
-                .write_to_archive(&script.content, &format!("{}.{}", script.path, ext))
-                .await?;
-
-            let metadata = ScriptMetadata {
-                summary: script.summary,
-                description: script.description,
-                schema: script.schema,
-                kind: script.kind.to_string(),
-                lock: script.lock,
-                envs: script.envs,
-                concurrent_limit: script.concurrent_limit,
-                concurrency_time_window_s: script.concurrency_time_window_s,
-                cache_ttl: script.cache_ttl,
-                dedicated_worker: script.dedicated_worker,
-                ws_error_handler_muted: script.ws_error_handler_muted,
-                priority: script.priority,
-                tag: script.tag,
-                timeout: script.timeout,
-                delete_after_use: script.delete_after_use,
-                restart_unless_cancelled: script.restart_unless_cancelled,
-                visible_to_runner_only: script.visible_to_runner_only,
-                no_main_func: script.no_main_func,
-                codebase: script.codebase,
-                concurrency_key: script.concurrency_key,
-                has_preprocessor: script.has_preprocessor,
-                on_behalf_of_email: script.on_behalf_of_email,
-            };
-            let metadata_str = serde_json::to_string_pretty(&metadata).unwrap();
-            archive
-                .write_to_archive(&metadata_str, &format!("{}.script.json", script.path))
-                .await?;
+            for (file, content) in script_archive_files(script, default_ts.as_deref()) {
+                archive.write_to_archive(&content, &file).await?;
+            }
         }
     }
 
@@ -563,7 +572,10 @@ pub(crate) async fn tarball_workspace(
                     raw_string,
                     error_handler_path,
                     error_handler_args as "error_handler_args: _",
-                    retry as "retry: _"
+                    retry as "retry: _",
+                    validate_request,
+                    cache_policy as "cache_policy: _",
+                    rate_limits as "rate_limits: _"
                 FROM http_trigger
                 WHERE workspace_id = $1
                 "#,
@@ -582,46 +594,20 @@ pub(crate) async fn tarball_workspace(
 
         #[cfg(feature = "websocket")]
         {
-            let websocket_triggers = sqlx::query_as!(
-                crate::websocket_triggers::WebsocketTrigger,
-                r#"
-                SELECT 
-                    workspace_id,
-                    path,
-                    url,
-                    script_path,
-                    is_flow,
-                    edited_by,
-                    email,
-                    edited_at,
-                    server_id,
-                    last_server_ping,
-                    extra_perms,
-                    error,
-                    enabled,
-                    filters AS "filters: _",
-                    initial_messages AS "initial_messages: _",
-                    url_runnable_args AS "url_runnable_args: _",
-                    can_return_message,
-                    error_handler_path,
-                    error_handler_args as "error_handler_args: _",
-                    retry as "retry: _"
-                FROM 
-                    websocket_trigger
-                WHERE 
-                    workspace_id = $1
-                "#,
-                &w_id
-            )
-            .fetch_all(&mut *tx)
-            .await?;
+            let websocket_triggers =
+                sqlx::query_as::<_, crate::websocket_triggers::WebsocketTrigger>(
+                    "SELECT * FROM websocket_trigger WHERE workspace_id = $1",
+                )
+                .bind(&w_id)
+                .fetch_all(&mut *tx)
+                .await?;
 
             for trigger in websocket_triggers {
                 let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
                 archive
                     .write_to_archive(
                         &trigger_str,
-                        &format!("{}.websocket_trigger.json", trigger.path),
+                        &format!("{}.websocket_trigger.json", trigger.base.path),
                     )
                     .await?;
             }
@@ -629,31 +615,10 @@ pub(crate) async fn tarball_workspace(
 
         #[cfg(all(feature = "enterprise", feature = "kafka"))]
         {
-            let kafka_triggers = sqlx::query_as!(
-                crate::kafka_triggers_oss::KafkaTrigger,
-                r#"SELECT 
-                    workspace_id,
-                    path,
-                    kafka_resource_path,
-                    group_id,
-                    topics,
-                    script_path,
-                    is_flow,
-                    edited_by,
-                    email,
-                    edited_at,
-                    server_id,
-                    last_server_ping,
-                    extra_perms,
-                    error,
-                    enabled,
-                    error_handler_path,
-                    error_handler_args as "error_handler_args: _",
-                    retry as "retry: _" 
-                 FROM kafka_trigger
-                 WHERE workspace_id = $1"#,
-                &w_id
+            let kafka_triggers = sqlx::query_as::<_, crate::kafka_triggers_oss::KafkaTrigger>(
+                "SELECT * FROM kafka_trigger WHERE workspace_id = $1",
             )
+            .bind(&w_id)
             .fetch_all(&mut *tx)
             .await?;
 
@@ -662,7 +627,7 @@ pub(crate) async fn tarball_workspace(
                 archive
                     .write_to_archive(
                         &trigger_str,
-                        &format!("{}.kafka_trigger.json", trigger.path),
+                        &format!("{}.kafka_trigger.json", trigger.base.path),
                     )
                     .await?;
             }
@@ -670,169 +635,84 @@ pub(crate) async fn tarball_workspace(
 
         #[cfg(all(feature = "enterprise", feature = "sqs_trigger"))]
         {
-            let sqs_triggers = sqlx::query_as!(
-                crate::sqs_triggers_oss::SqsTrigger,
-                r#"
-                SELECT
-                    aws_auth_resource_type AS "aws_auth_resource_type: _",
-                    aws_resource_path,
-                    message_attributes,
-                    queue_url,
-                    workspace_id,
-                    path,
-                    script_path,
-                    is_flow,
-                    edited_by,
-                    email,
-                    edited_at,
-                    server_id,
-                    last_server_ping,
-                    extra_perms,
-                    error,
-                    enabled,
-                    error_handler_path,
-                    error_handler_args as "error_handler_args: _",
-                    retry as "retry: _"
-                FROM 
-                    sqs_trigger
-                WHERE 
-                    workspace_id = $1
-                "#,
-                &w_id
+            let sqs_triggers = sqlx::query_as::<_, crate::sqs_triggers_oss::SqsTrigger>(
+                "SELECT * FROM sqs_trigger WHERE workspace_id = $1",
             )
+            .bind(&w_id)
             .fetch_all(&mut *tx)
             .await?;
 
             for trigger in sqs_triggers {
                 let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
                 archive
-                    .write_to_archive(&trigger_str, &format!("{}.sqs_trigger.json", trigger.path))
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.sqs_trigger.json", trigger.base.path),
+                    )
                     .await?;
             }
         }
 
         #[cfg(all(feature = "enterprise", feature = "gcp_trigger"))]
         {
-            let gcp_triggers = sqlx::query_as!(
-                crate::gcp_triggers_oss::GcpTrigger,
-                r#"
-                SELECT
-                    gcp_resource_path,
-                    subscription_id,
-                    topic_id,
-                    workspace_id,
-                    delivery_type AS "delivery_type: _",
-                    delivery_config AS "delivery_config: _",
-                    subscription_mode AS "subscription_mode: _",
-                    path,
-                    script_path,
-                    is_flow,
-                    edited_by,
-                    email,
-                    edited_at,
-                    server_id,
-                    last_server_ping,
-                    extra_perms,
-                    error,
-                    enabled,
-                    error_handler_path,
-                    error_handler_args as "error_handler_args: _",
-                    retry as "retry: _"
-                FROM 
-                    gcp_trigger
-                WHERE 
-                    workspace_id = $1
-                "#,
-                &w_id
+            let gcp_triggers = sqlx::query_as::<_, crate::gcp_triggers_oss::GcpTrigger>(
+                "SELECT * FROM gcp_trigger WHERE workspace_id = $1",
             )
+            .bind(&w_id)
             .fetch_all(&mut *tx)
             .await?;
 
             for trigger in gcp_triggers {
                 let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
                 archive
-                    .write_to_archive(&trigger_str, &format!("{}.gcp_trigger.json", trigger.path))
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.gcp_trigger.json", trigger.base.path),
+                    )
                     .await?;
             }
         }
 
         #[cfg(all(feature = "enterprise", feature = "nats"))]
         {
-            let nats_triggers = sqlx::query_as!(
-                crate::nats_triggers_oss::NatsTrigger,
-                r#"SELECT 
-                    workspace_id,
-                    path,
-                    nats_resource_path,
-                    subjects,
-                    stream_name,
-                    consumer_name,
-                    use_jetstream,
-                    script_path,
-                    is_flow,
-                    edited_by,
-                    email,
-                    edited_at,
-                    server_id,
-                    last_server_ping,
-                    extra_perms,
-                    error,
-                    enabled,
-                    error_handler_path,
-                    error_handler_args as "error_handler_args: _",
-                    retry as "retry: _"
-                 FROM nats_trigger
-                 WHERE workspace_id = $1"#,
-                &w_id
+            let nats_triggers = sqlx::query_as::<_, crate::nats_triggers_oss::NatsTrigger>(
+                "SELECT * FROM nats_trigger WHERE workspace_id = $1",
             )
+            .bind(&w_id)
             .fetch_all(&mut *tx)
             .await?;
 
             for trigger in nats_triggers {
-                let trigger_str: &String =
-                    &to_string_without_metadata(&trigger, false, None).unwrap();
+                let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
                 archive
-                    .write_to_archive(&trigger_str, &format!("{}.nats_trigger.json", trigger.path))
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.nats_trigger.json", trigger.base.path),
+                    )
                     .await?;
             }
         }
 
         #[cfg(feature = "postgres_trigger")]
         {
-            let postgres_triggers = sqlx::query_as!(
-                crate::postgres_triggers::PostgresTrigger,
-                r#"SELECT 
-                    workspace_id,
-                    path,
-                    script_path,
-                    is_flow,
-                    edited_by,
-                    email,
-                    edited_at,
-                    server_id,
-                    last_server_ping,
-                    extra_perms,
-                    error,
-                    enabled,
-                    replication_slot_name,
-                    publication_name,
-                    postgres_resource_path,
-                    error_handler_path,
-                    error_handler_args as "error_handler_args: _",
-                    retry as "retry: _" 
-                FROM postgres_trigger
-                WHERE workspace_id = $1"#,
-                &w_id
+            let postgres_triggers = sqlx::query_as::<_, crate::postgres_triggers::PostgresTrigger>(
+                "SELECT * FROM postgres_trigger WHERE workspace_id = $1",
             )
+            .bind(&w_id)
             .fetch_all(&mut *tx)
             .await?;
 
             for trigger in postgres_triggers {
-                let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
+                let trigger_str = &to_string_without_metadata(
+                    &trigger,
+                    false,
+                    Some(vec!["snapshot_completed_at"]),
+                )
+                .unwrap();
                 archive
                     .write_to_archive(
                         &trigger_str,
-                        &format!("{}.postgres_trigger.json", trigger.path),
+                        &format!("{}.postgres_trigger.json", trigger.base.path),
                     )
                     .await?;
             }
@@ -840,16 +720,34 @@ pub(crate) async fn tarball_workspace(
 
         #[cfg(all(feature = "enterprise", feature = "mqtt_trigger"))]
         {
-            let mqtt_triggers = sqlx::query_as!(
-                crate::mqtt_triggers::MqttTrigger,
+            let mqtt_triggers = sqlx::query_as::<_, crate::mqtt_triggers::MqttTrigger>(
+                "SELECT * FROM mqtt_trigger WHERE workspace_id = $1",
+            )
+            .bind(&w_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for trigger in mqtt_triggers {
+                let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
+                archive
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.mqtt_trigger.json", trigger.base.path),
+                    )
+                    .await?;
+            }
+        }
+
+        #[cfg(feature = "mysql_trigger")]
+        {
+            let mysql_triggers = sqlx::query_as!(
+                crate::mysql_triggers::MysqlTrigger,
                 r#"
                 SELECT
-                    mqtt_resource_path,
-                    subscribe_topics as "subscribe_topics: _",
-                    v3_config as "v3_config: _",
-                    v5_config as "v5_config: _",
-                    client_version AS "client_version: _",
-                    client_id,
+                    mysql_resource_path,
+                    include_tables,
+                    exclude_tables,
+                    gtid_set,
                     workspace_id,
                     path,
                     script_path,
@@ -865,17 +763,90 @@ pub(crate) async fn tarball_workspace(
                     error_handler_path,
                     error_handler_args as "error_handler_args: _",
                     retry as "retry: _"
-                FROM 
-                    mqtt_trigger
+                FROM
+                    mysql_trigger
+                WHERE
+                    workspace_id = $1
                 "#,
+                &w_id
             )
             .fetch_all(&mut *tx)
             .await?;
 
-            for trigger in mqtt_triggers {
+            for trigger in mysql_triggers {
+                // the binlog position is specific to the server the trigger was listening to
+                let trigger_str =
+                    &to_string_without_metadata(&trigger, false, Some(vec!["gtid_set"])).unwrap();
+                archive
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.mysql_trigger.json", trigger.path),
+                    )
+                    .await?;
+            }
+        }
+
+        #[cfg(feature = "amqp_trigger")]
+        {
+            let amqp_triggers = sqlx::query_as::<_, crate::amqp_triggers::AmqpTrigger>(
+                "SELECT * FROM amqp_trigger WHERE workspace_id = $1",
+            )
+            .bind(&w_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for trigger in amqp_triggers {
                 let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
                 archive
-                    .write_to_archive(&trigger_str, &format!("{}.mqtt_trigger.json", trigger.path))
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.amqp_trigger.json", trigger.base.path),
+                    )
+                    .await?;
+            }
+        }
+
+        #[cfg(feature = "redis_trigger")]
+        {
+            let redis_triggers = sqlx::query_as::<_, crate::redis_triggers::RedisTrigger>(
+                "SELECT * FROM redis_trigger WHERE workspace_id = $1",
+            )
+            .bind(&w_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for trigger in redis_triggers {
+                let trigger_str = &to_string_without_metadata(&trigger, false, None).unwrap();
+                archive
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.redis_trigger.json", trigger.base.path),
+                    )
+                    .await?;
+            }
+        }
+
+        #[cfg(feature = "s3_trigger")]
+        {
+            let s3_triggers = sqlx::query_as::<_, crate::s3_triggers::S3Trigger>(
+                "SELECT * FROM s3_trigger WHERE workspace_id = $1",
+            )
+            .bind(&w_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for trigger in s3_triggers {
+                let trigger_str = &to_string_without_metadata(
+                    &trigger,
+                    false,
+                    Some(vec!["watermark", "watermark_keys"]),
+                )
+                .unwrap();
+                archive
+                    .write_to_archive(
+                        &trigger_str,
+                        &format!("{}.s3_trigger.json", trigger.base.path),
+                    )
                     .await?;
             }
         }
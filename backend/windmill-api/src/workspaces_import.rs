@@ -0,0 +1,1523 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2022
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Restores an archive produced by `tarball_workspace` into an existing workspace, or into a new
+//! workspace created along with the import.
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Extension, Path, Query},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use magic_crypt::MagicCrypt256;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, value::RawValue, Value};
+use sqlx::{Postgres, Transaction};
+use tokio::io::AsyncReadExt;
+use windmill_audit::{audit_oss::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    schedule::Schedule,
+    scripts::{ScriptKind, ScriptLang},
+    users::username_to_permissioned_as,
+    utils::require_admin,
+    variables::{build_crypt_from_key, decrypt, encrypt},
+};
+use windmill_queue::schedule::push_scheduled_job;
+
+use crate::{
+    apps::Policy,
+    db::ApiAuthed,
+    db::DB,
+    schedule::clear_schedule,
+    workspaces::{
+        check_can_create_workspace, creator_username, insert_workspace, insert_workspace_defaults,
+        CreateWorkspace,
+    },
+};
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    #[default]
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQueryParams {
+    on_conflict: Option<ConflictStrategy>,
+    dry_run: Option<bool>,
+    /// secret variables of the archive are in clear, as exported with `plain_secrets`
+    plain_secrets: Option<bool>,
+    /// same as the export, `.ts` scripts are bun scripts when set to `bun`
+    default_ts: Option<String>,
+    import_settings: Option<bool>,
+}
+
+pub(crate) struct ImportOptions {
+    pub on_conflict: ConflictStrategy,
+    pub plain_secrets: bool,
+    pub import_settings: bool,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportKind {
+    User,
+    Group,
+    Folder,
+    ResourceType,
+    Variable,
+    Resource,
+    Script,
+    Flow,
+    App,
+    Schedule,
+    Trigger,
+    Settings,
+}
+
+impl ImportKind {
+    fn renamable(&self) -> bool {
+        matches!(
+            self,
+            ImportKind::Group
+                | ImportKind::Variable
+                | ImportKind::Resource
+                | ImportKind::Script
+                | ImportKind::Flow
+                | ImportKind::App
+                | ImportKind::Schedule
+        )
+    }
+
+    async fn exists(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        w_id: &str,
+        path: &str,
+    ) -> Result<bool> {
+        let exists = match self {
+            ImportKind::Group => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM group_ WHERE name = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::Folder => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM folder WHERE name = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::ResourceType => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM resource_type WHERE name = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::Variable => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM variable WHERE path = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::Resource => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM resource WHERE path = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::Script => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::Flow => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::App => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM app WHERE path = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::Schedule => sqlx::query_scalar!(
+                "SELECT EXISTS(SELECT 1 FROM schedule WHERE path = $1 AND workspace_id = $2)",
+                path,
+                w_id
+            )
+            .fetch_one(&mut **tx)
+            .await?,
+            ImportKind::User | ImportKind::Trigger | ImportKind::Settings => Some(false),
+        };
+        Ok(exists.unwrap_or(false))
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Overwrite,
+    Rename,
+    Skip,
+    Delete,
+}
+
+#[derive(Serialize)]
+pub struct ImportedItem {
+    kind: ImportKind,
+    path: String,
+    action: ImportAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    renamed_to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub(crate) dry_run: bool,
+    pub(crate) items: Vec<ImportedItem>,
+}
+
+impl ImportReport {
+    fn push(&mut self, kind: ImportKind, path: &str, target: &Target, reason: Option<String>) {
+        let (action, renamed_to) = match target {
+            Target::Create(_) => (ImportAction::Create, None),
+            Target::Overwrite(_) => (ImportAction::Overwrite, None),
+            Target::Rename(to) => (ImportAction::Rename, Some(to.clone())),
+            Target::Skip(_) => (ImportAction::Skip, None),
+        };
+        let reason = match target {
+            Target::Skip(skip_reason) => skip_reason.clone().or(reason),
+            _ => reason,
+        };
+        self.items
+            .push(ImportedItem { kind, path: path.to_string(), action, renamed_to, reason });
+    }
+
+    pub(crate) fn skip(&mut self, kind: ImportKind, path: &str, reason: impl Into<String>) {
+        self.push(kind, path, &Target::Skip(Some(reason.into())), None);
+    }
+
+    pub(crate) fn delete(&mut self, kind: ImportKind, path: &str) {
+        self.items.push(ImportedItem {
+            kind,
+            path: path.to_string(),
+            action: ImportAction::Delete,
+            renamed_to: None,
+            reason: None,
+        });
+    }
+}
+
+/// Where an archived item ends up in the target workspace
+enum Target {
+    Create(String),
+    Overwrite(String),
+    Rename(String),
+    Skip(Option<String>),
+}
+
+impl Target {
+    fn path(&self) -> Option<&str> {
+        match self {
+            Target::Create(p) | Target::Overwrite(p) | Target::Rename(p) => Some(p),
+            Target::Skip(_) => None,
+        }
+    }
+
+    fn is_overwrite(&self) -> bool {
+        matches!(self, Target::Overwrite(_))
+    }
+}
+
+async fn resolve_target(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: ImportKind,
+    w_id: &str,
+    path: &str,
+    on_conflict: ConflictStrategy,
+) -> Result<Target> {
+    if !kind.exists(tx, w_id, path).await? {
+        return Ok(Target::Create(path.to_string()));
+    }
+    match on_conflict {
+        ConflictStrategy::Skip => Ok(Target::Skip(Some("already exists".to_string()))),
+        ConflictStrategy::Overwrite => Ok(Target::Overwrite(path.to_string())),
+        ConflictStrategy::Rename if !kind.renamable() => Ok(Target::Skip(Some(
+            "already exists and cannot be renamed".to_string(),
+        ))),
+        ConflictStrategy::Rename => {
+            let mut candidate = format!("{path}_imported");
+            let mut i = 2;
+            while kind.exists(tx, w_id, &candidate).await? {
+                candidate = format!("{path}_imported_{i}");
+                i += 1;
+            }
+            Ok(Target::Rename(candidate))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ArchivedUser {
+    username: String,
+    role: String,
+    #[serde(default)]
+    disabled: bool,
+}
+
+#[derive(Deserialize)]
+struct ArchivedGroup {
+    summary: Option<String>,
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    admins: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedFolder {
+    display_name: Option<String>,
+    #[serde(default)]
+    owners: Vec<String>,
+    #[serde(default = "empty_object")]
+    extra_perms: Value,
+    summary: Option<String>,
+}
+
+fn empty_object() -> Value {
+    json!({})
+}
+
+#[derive(Deserialize)]
+struct ArchivedResourceType {
+    schema: Option<Value>,
+    description: Option<String>,
+    format_extension: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedVariable {
+    value: Option<String>,
+    #[serde(default)]
+    is_secret: bool,
+    #[serde(default)]
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct ArchivedResource {
+    value: Option<Value>,
+    description: Option<String>,
+    resource_type: String,
+}
+
+#[derive(Deserialize)]
+struct ArchivedScript {
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    description: String,
+    schema: Option<Box<RawValue>>,
+    lock: Option<String>,
+    kind: ScriptKind,
+    envs: Option<Vec<String>>,
+    concurrent_limit: Option<i32>,
+    concurrency_time_window_s: Option<i32>,
+    cache_ttl: Option<i32>,
+    dedicated_worker: Option<bool>,
+    ws_error_handler_muted: Option<bool>,
+    priority: Option<i16>,
+    tag: Option<String>,
+    timeout: Option<i32>,
+    delete_after_use: Option<bool>,
+    restart_unless_cancelled: Option<bool>,
+    visible_to_runner_only: Option<bool>,
+    no_main_func: Option<bool>,
+    codebase: Option<String>,
+    concurrency_key: Option<String>,
+    has_preprocessor: Option<bool>,
+    on_behalf_of_email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedFlow {
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    description: String,
+    value: Value,
+    schema: Option<Box<RawValue>>,
+    dedicated_worker: Option<bool>,
+    tag: Option<String>,
+    ws_error_handler_muted: Option<bool>,
+    timeout: Option<i32>,
+    visible_to_runner_only: Option<bool>,
+    on_behalf_of_email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedApp {
+    #[serde(default)]
+    summary: String,
+    policy: Policy,
+    value: Box<RawValue>,
+    custom_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedSchedule {
+    schedule: String,
+    timezone: String,
+    #[serde(default)]
+    enabled: bool,
+    script_path: String,
+    #[serde(default)]
+    is_flow: bool,
+    args: Option<Box<RawValue>>,
+    on_failure: Option<String>,
+    on_failure_times: Option<i32>,
+    on_failure_exact: Option<bool>,
+    on_failure_extra_args: Option<Box<RawValue>>,
+    on_recovery: Option<String>,
+    on_recovery_times: Option<i32>,
+    on_recovery_extra_args: Option<Box<RawValue>>,
+    on_success: Option<String>,
+    on_success_extra_args: Option<Box<RawValue>>,
+    #[serde(default)]
+    ws_error_handler_muted: bool,
+    retry: Option<Value>,
+    #[serde(default)]
+    no_flow_overlap: bool,
+    summary: Option<String>,
+    description: Option<String>,
+    tag: Option<String>,
+    paused_until: Option<DateTime<Utc>>,
+    cron_version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ArchivedSettings {
+    webhook: Option<String>,
+    deploy_to: Option<String>,
+    error_handler: Option<String>,
+    error_handler_extra_args: Option<Value>,
+    #[serde(default)]
+    error_handler_muted_on_cancel: bool,
+    ai_config: Option<Value>,
+    large_file_storage: Option<Value>,
+    git_sync: Option<Value>,
+    default_app: Option<String>,
+    default_scripts: Option<Value>,
+    mute_critical_alerts: Option<bool>,
+    color: Option<String>,
+    operator_settings: Option<Value>,
+}
+
+struct ScriptFile {
+    content: String,
+    language: ScriptLang,
+    metadata: ArchivedScript,
+}
+
+/// Content file extensions written by the export, most specific first so that `x.bun.ts` is not
+/// read as a `ts` script
+pub(crate) const SCRIPT_EXTENSIONS: &[&str] = &[
+    "deno.ts",
+    "bun.ts",
+    "fetch.ts",
+    "pg.sql",
+    "my.sql",
+    "bq.sql",
+    "sf.sql",
+    "ms.sql",
+    "duckdb.sql",
+    "odb.sql",
+    "playbook.yml",
+    "py",
+    "ts",
+    "go",
+    "sh",
+    "ps1",
+    "gql",
+    "php",
+    "rs",
+    "cs",
+    "nu",
+    "java",
+];
+
+fn language_of_extension(ext: &str, default_ts_is_bun: bool) -> ScriptLang {
+    match ext {
+        "py" => ScriptLang::Python3,
+        "ts" if default_ts_is_bun => ScriptLang::Bun,
+        "ts" | "deno.ts" => ScriptLang::Deno,
+        "bun.ts" => ScriptLang::Bun,
+        "fetch.ts" => ScriptLang::Nativets,
+        "go" => ScriptLang::Go,
+        "sh" => ScriptLang::Bash,
+        "ps1" => ScriptLang::Powershell,
+        "pg.sql" => ScriptLang::Postgresql,
+        "my.sql" => ScriptLang::Mysql,
+        "bq.sql" => ScriptLang::Bigquery,
+        "sf.sql" => ScriptLang::Snowflake,
+        "ms.sql" => ScriptLang::Mssql,
+        "duckdb.sql" => ScriptLang::DuckDb,
+        "odb.sql" => ScriptLang::OracleDB,
+        "gql" => ScriptLang::Graphql,
+        "php" => ScriptLang::Php,
+        "rs" => ScriptLang::Rust,
+        "playbook.yml" => ScriptLang::Ansible,
+        "cs" => ScriptLang::CSharp,
+        "nu" => ScriptLang::Nu,
+        "java" => ScriptLang::Java,
+        // for related places search: ADD_NEW_LANG
+        _ => unreachable!("extension {ext} is not in SCRIPT_EXTENSIONS"),
+    }
+}
+
+/// The archive parsed and validated before anything is written
+#[derive(Default)]
+pub(crate) struct ImportArchive {
+    key: Option<String>,
+    settings: Option<ArchivedSettings>,
+    users: Vec<(String, ArchivedUser)>,
+    groups: Vec<(String, ArchivedGroup)>,
+    folders: Vec<(String, ArchivedFolder)>,
+    resource_types: Vec<(String, ArchivedResourceType)>,
+    variables: Vec<(String, ArchivedVariable)>,
+    resources: Vec<(String, ArchivedResource)>,
+    scripts: Vec<(String, ScriptFile)>,
+    flows: Vec<(String, ArchivedFlow)>,
+    apps: Vec<(String, ArchivedApp)>,
+    schedules: Vec<(String, ArchivedSchedule)>,
+    triggers: Vec<(String, String)>,
+}
+
+fn parse_file<T: DeserializeOwned>(
+    file: &str,
+    content: &str,
+    errors: &mut Vec<String>,
+) -> Option<T> {
+    serde_json::from_str(content)
+        .map_err(|e| errors.push(format!("{file}: {e}")))
+        .ok()
+}
+
+fn check_path(file: &str, path: &str, errors: &mut Vec<String>) -> bool {
+    let valid = (path.starts_with("u/") || path.starts_with("f/")) && path.split('/').count() >= 3;
+    if !valid {
+        errors.push(format!("{file}: {path} is not a valid path"));
+    }
+    valid
+}
+
+impl ImportArchive {
+    /// Every invalid file is reported at once so that the archive can be fixed in one go
+    pub(crate) fn parse(
+        mut files: BTreeMap<String, String>,
+        default_ts_is_bun: bool,
+    ) -> Result<Self> {
+        let mut archive = ImportArchive::default();
+        let mut errors = vec![];
+
+        let script_paths = files
+            .keys()
+            .filter_map(|f| f.strip_suffix(".script.json").map(str::to_string))
+            .collect::<Vec<_>>();
+        for path in script_paths {
+            let file = format!("{path}.script.json");
+            let metadata = files.remove(&file).unwrap();
+            let content = SCRIPT_EXTENSIONS.iter().find_map(|ext| {
+                files
+                    .remove(&format!("{path}.{ext}"))
+                    .map(|content| (content, language_of_extension(ext, default_ts_is_bun)))
+            });
+            let Some((content, language)) = content else {
+                errors.push(format!(
+                    "{file}: no script content found next to the metadata"
+                ));
+                continue;
+            };
+            if let Some(metadata) = parse_file(&file, &metadata, &mut errors) {
+                if check_path(&file, &path, &mut errors) {
+                    archive
+                        .scripts
+                        .push((path, ScriptFile { content, language, metadata }));
+                }
+            }
+        }
+
+        for (file, content) in files {
+            let errors = &mut errors;
+            if file == "encryption_key.json" {
+                archive.key = parse_file(&file, &content, errors);
+            } else if file == "settings.json" {
+                archive.settings = parse_file(&file, &content, errors);
+            } else if let Some(email) = file
+                .strip_prefix("users/")
+                .and_then(|f| f.strip_suffix(".user.json"))
+            {
+                if let Some(user) = parse_file::<ArchivedUser>(&file, &content, errors) {
+                    if !["admin", "operator", "developer"].contains(&user.role.as_str()) {
+                        errors.push(format!("{file}: unknown role {}", user.role));
+                    } else {
+                        archive.users.push((email.to_string(), user));
+                    }
+                }
+            } else if let Some(name) = file
+                .strip_prefix("groups/")
+                .and_then(|f| f.strip_suffix(".group.json"))
+            {
+                if let Some(group) = parse_file(&file, &content, errors) {
+                    archive.groups.push((name.to_string(), group));
+                }
+            } else if let Some(name) = file
+                .strip_prefix("f/")
+                .and_then(|f| f.strip_suffix("/folder.meta.json"))
+            {
+                if let Some(folder) = parse_file(&file, &content, errors) {
+                    archive.folders.push((name.to_string(), folder));
+                }
+            } else if let Some(name) = file.strip_suffix(".resource-type.json") {
+                if let Some(resource_type) = parse_file(&file, &content, errors) {
+                    archive
+                        .resource_types
+                        .push((name.to_string(), resource_type));
+                }
+            } else if let Some(path) = file.strip_suffix(".variable.json") {
+                if let Some(variable) = parse_file(&file, &content, errors) {
+                    if check_path(&file, path, errors) {
+                        archive.variables.push((path.to_string(), variable));
+                    }
+                }
+            } else if let Some(path) = file.strip_suffix(".resource.json") {
+                if let Some(resource) = parse_file(&file, &content, errors) {
+                    if check_path(&file, path, errors) {
+                        archive.resources.push((path.to_string(), resource));
+                    }
+                }
+            } else if let Some(path) = file.strip_suffix(".flow.json") {
+                if let Some(flow) = parse_file(&file, &content, errors) {
+                    if check_path(&file, path, errors) {
+                        archive.flows.push((path.to_string(), flow));
+                    }
+                }
+            } else if let Some(path) = file.strip_suffix(".app.json") {
+                if let Some(app) = parse_file(&file, &content, errors) {
+                    if check_path(&file, path, errors) {
+                        archive.apps.push((path.to_string(), app));
+                    }
+                }
+            } else if let Some(path) = file.strip_suffix(".schedule.json") {
+                if let Some(schedule) = parse_file(&file, &content, errors) {
+                    if check_path(&file, path, errors) {
+                        archive.schedules.push((path.to_string(), schedule));
+                    }
+                }
+            } else if let Some((path, kind)) = file
+                .strip_suffix("_trigger.json")
+                .and_then(|f| f.rsplit_once('.'))
+            {
+                archive.triggers.push((path.to_string(), kind.to_string()));
+            } else {
+                errors.push(format!("{file}: not a file of a workspace archive"));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(archive)
+        } else {
+            Err(Error::BadRequest(format!(
+                "Invalid archive:\n{}",
+                errors.join("\n")
+            )))
+        }
+    }
+}
+
+async fn read_tarball(body: &[u8]) -> Result<BTreeMap<String, String>> {
+    let mut tar = tokio_tar::Archive::new(body);
+    let mut entries = tar
+        .entries()
+        .map_err(|e| Error::BadRequest(format!("Invalid tarball: {e}")))?;
+    let mut files = BTreeMap::new();
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry.map_err(|e| Error::BadRequest(format!("Invalid tarball: {e}")))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .map_err(|e| Error::BadRequest(format!("Invalid tarball entry path: {e}")))?
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .to_string();
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .await
+            .map_err(|e| Error::BadRequest(format!("{path} is not a valid UTF-8 file: {e}")))?;
+        files.insert(path, content);
+    }
+    Ok(files)
+}
+
+/// Decrypts secrets with the key of the archive and encrypts them with the key of the target
+/// workspace. Without a key in the archive, the secrets are assumed to come from the target
+/// workspace itself.
+struct SecretReencrypter {
+    source: Option<MagicCrypt256>,
+    target: MagicCrypt256,
+    plain_secrets: bool,
+}
+
+impl SecretReencrypter {
+    fn reencrypt(&self, path: &str, value: String) -> Result<String> {
+        let value = if self.plain_secrets {
+            value
+        } else {
+            decrypt(self.source.as_ref().unwrap_or(&self.target), value).map_err(|_| {
+                Error::BadRequest(format!(
+                    "Could not decrypt secret variable {path}, export the workspace with its \
+                     encryption key or with plain secrets"
+                ))
+            })?
+        };
+        Ok(encrypt(&self.target, &value))
+    }
+}
+
+fn script_hash(
+    w_id: &str,
+    path: &str,
+    content: &str,
+    lock: &Option<String>,
+    parent: Option<i64>,
+) -> i64 {
+    let mut dh = DefaultHasher::new();
+    (w_id, path, content, lock, parent).hash(&mut dh);
+    dh.finish() as i64
+}
+
+pub(crate) async fn import_tarball(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(params): Query<ImportQueryParams>,
+    body: Bytes,
+) -> JsonResult<ImportReport> {
+    require_admin(authed.is_admin, &authed.username)?;
+
+    let archive = ImportArchive::parse(
+        read_tarball(&body).await?,
+        params.default_ts.as_deref() == Some("bun"),
+    )?;
+    let tx = user_db.begin(&authed).await?;
+    run_import(&db, &authed, tx, &w_id, archive, &params).await
+}
+
+/// Creates the workspace and restores the archive into it in the same transaction, so that a
+/// failed import or a dry run leaves no workspace behind
+pub(crate) async fn create_workspace_from_tarball(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Query(nw): Query<CreateWorkspace>,
+    Query(params): Query<ImportQueryParams>,
+    body: Bytes,
+) -> JsonResult<ImportReport> {
+    check_can_create_workspace(&db, &authed.email).await?;
+
+    let archive = ImportArchive::parse(
+        read_tarball(&body).await?,
+        params.default_ts.as_deref() == Some("bun"),
+    )?;
+
+    let mut tx = db.begin().await?;
+    let username = creator_username(&mut tx, &authed.email, nw.username).await?;
+    insert_workspace(
+        &mut tx,
+        &authed.email,
+        &username,
+        &nw.id,
+        &nw.name,
+        nw.color.as_deref(),
+    )
+    .await?;
+    insert_workspace_defaults(&mut tx, &nw.id, &username).await?;
+    audit_log(
+        &mut *tx,
+        &authed,
+        "workspaces.create",
+        ActionKind::Create,
+        &nw.id,
+        Some(nw.name.as_str()),
+        None,
+    )
+    .await?;
+
+    // the creator is the admin of the new workspace
+    let authed = ApiAuthed { username, is_admin: true, ..authed };
+    run_import(&db, &authed, tx, &nw.id, archive, &params).await
+}
+
+async fn run_import<'c>(
+    db: &DB,
+    authed: &ApiAuthed,
+    tx: Transaction<'c, Postgres>,
+    w_id: &str,
+    archive: ImportArchive,
+    params: &ImportQueryParams,
+) -> JsonResult<ImportReport> {
+    let dry_run = params.dry_run.unwrap_or(false);
+    let options = ImportOptions {
+        on_conflict: params.on_conflict.unwrap_or_default(),
+        plain_secrets: params.plain_secrets.unwrap_or(false),
+        import_settings: params.import_settings.unwrap_or(false),
+    };
+    let (mut tx, mut report) = import_archive(db, authed, tx, w_id, archive, &options).await?;
+    report.dry_run = dry_run;
+
+    let count = |action: ImportAction| {
+        report
+            .items
+            .iter()
+            .filter(|item| item.action == action)
+            .count()
+            .to_string()
+    };
+    let (created, overwritten, renamed, skipped) = (
+        count(ImportAction::Create),
+        count(ImportAction::Overwrite),
+        count(ImportAction::Rename),
+        count(ImportAction::Skip),
+    );
+    audit_log(
+        &mut *tx,
+        authed,
+        "workspaces.import",
+        ActionKind::Create,
+        w_id,
+        None,
+        Some(HashMap::from([
+            ("created", created.as_str()),
+            ("overwritten", overwritten.as_str()),
+            ("renamed", renamed.as_str()),
+            ("skipped", skipped.as_str()),
+        ])),
+    )
+    .await?;
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    Ok(Json(report))
+}
+
+/// Writes the archive in the given transaction, items are written in dependency order
+pub(crate) async fn import_archive<'c>(
+    db: &DB,
+    authed: &ApiAuthed,
+    mut tx: Transaction<'c, Postgres>,
+    w_id: &str,
+    archive: ImportArchive,
+    options: &ImportOptions,
+) -> Result<(Transaction<'c, Postgres>, ImportReport)> {
+    let on_conflict = options.on_conflict;
+    // the key is read in the transaction as the workspace may have been created by it
+    let target_key = sqlx::query_scalar!(
+        "SELECT key FROM workspace_key WHERE workspace_id = $1 AND kind = 'cloud'",
+        w_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let crypt = SecretReencrypter {
+        source: archive.key.as_deref().map(build_crypt_from_key),
+        target: build_crypt_from_key(&target_key),
+        plain_secrets: options.plain_secrets,
+    };
+    let mut report = ImportReport { dry_run: false, items: vec![] };
+
+    for (email, user) in archive.users {
+        import_user(&mut tx, w_id, &email, user, on_conflict, &mut report).await?;
+    }
+
+    for (name, group) in archive.groups {
+        let target = resolve_target(&mut tx, ImportKind::Group, w_id, &name, on_conflict).await?;
+        let mut reason = None;
+        if let Some(to) = target.path() {
+            let mut admins = serde_json::Map::new();
+            let mut missing = vec![];
+            if target.is_overwrite() {
+                sqlx::query!(
+                    "DELETE FROM usr_to_group WHERE group_ = $1 AND workspace_id = $2",
+                    to,
+                    w_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            for member in group.members.iter().chain(group.admins.iter()) {
+                let username = member.strip_prefix("u/").unwrap_or(member);
+                let exists = sqlx::query_scalar!(
+                    "SELECT EXISTS(SELECT 1 FROM usr WHERE username = $1 AND workspace_id = $2)",
+                    username,
+                    w_id
+                )
+                .fetch_one(&mut *tx)
+                .await?
+                .unwrap_or(false);
+                if !exists {
+                    missing.push(username.to_string());
+                    continue;
+                }
+                if group.admins.contains(member) {
+                    admins.insert(format!("u/{username}"), Value::Bool(true));
+                }
+            }
+            sqlx::query!(
+                "INSERT INTO group_ (workspace_id, name, summary, extra_perms) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (workspace_id, name) DO UPDATE SET summary = EXCLUDED.summary, extra_perms = EXCLUDED.extra_perms",
+                w_id,
+                to,
+                group.summary,
+                Value::Object(admins),
+            )
+            .execute(&mut *tx)
+            .await?;
+            for member in group.members.iter().chain(group.admins.iter()) {
+                let username = member.strip_prefix("u/").unwrap_or(member);
+                if missing.iter().any(|m| m == username) {
+                    continue;
+                }
+                sqlx::query!(
+                    "INSERT INTO usr_to_group (workspace_id, usr, group_) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                    w_id,
+                    username,
+                    to,
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            if !missing.is_empty() {
+                reason = Some(format!(
+                    "members not in the workspace were left out: {}",
+                    missing.join(", ")
+                ));
+            }
+        }
+        report.push(ImportKind::Group, &name, &target, reason);
+    }
+
+    for (name, folder) in archive.folders {
+        let target = resolve_target(&mut tx, ImportKind::Folder, w_id, &name, on_conflict).await?;
+        if let Some(to) = target.path() {
+            sqlx::query!(
+                "INSERT INTO folder (workspace_id, name, display_name, owners, extra_perms, summary, created_by, edited_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, now())
+                 ON CONFLICT (workspace_id, name) DO UPDATE SET display_name = EXCLUDED.display_name,
+                 owners = EXCLUDED.owners, extra_perms = EXCLUDED.extra_perms, summary = EXCLUDED.summary, edited_at = now()",
+                w_id,
+                to,
+                folder.display_name.unwrap_or_else(|| name.clone()),
+                &folder.owners,
+                folder.extra_perms,
+                folder.summary,
+                &authed.username,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        report.push(ImportKind::Folder, &name, &target, None);
+    }
+
+    for (name, resource_type) in archive.resource_types {
+        let target =
+            resolve_target(&mut tx, ImportKind::ResourceType, w_id, &name, on_conflict).await?;
+        if let Some(to) = target.path() {
+            sqlx::query!(
+                "INSERT INTO resource_type (workspace_id, name, schema, description, created_by, format_extension, edited_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, now())
+                 ON CONFLICT (workspace_id, name) DO UPDATE SET schema = EXCLUDED.schema,
+                 description = EXCLUDED.description, format_extension = EXCLUDED.format_extension, edited_at = now()",
+                w_id,
+                to,
+                resource_type.schema,
+                resource_type.description,
+                &authed.username,
+                resource_type.format_extension,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        report.push(ImportKind::ResourceType, &name, &target, None);
+    }
+
+    for (path, variable) in archive.variables {
+        let target =
+            resolve_target(&mut tx, ImportKind::Variable, w_id, &path, on_conflict).await?;
+        if let Some(to) = target.path() {
+            let value = match variable.value {
+                Some(value) if variable.is_secret => crypt.reencrypt(&path, value)?,
+                value => value.unwrap_or_default(),
+            };
+            sqlx::query!(
+                "INSERT INTO variable (workspace_id, path, value, is_secret, description, account, is_oauth, expires_at)
+                 VALUES ($1, $2, $3, $4, $5, NULL, false, NULL)
+                 ON CONFLICT (workspace_id, path) DO UPDATE SET value = EXCLUDED.value, is_secret = EXCLUDED.is_secret,
+                 description = EXCLUDED.description, account = NULL, is_oauth = false, expires_at = NULL",
+                w_id,
+                to,
+                value,
+                variable.is_secret,
+                variable.description,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        report.push(ImportKind::Variable, &path, &target, None);
+    }
+
+    for (path, resource) in archive.resources {
+        let target =
+            resolve_target(&mut tx, ImportKind::Resource, w_id, &path, on_conflict).await?;
+        if let Some(to) = target.path() {
+            sqlx::query!(
+                "INSERT INTO resource (workspace_id, path, value, description, resource_type, created_by, edited_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, now())
+                 ON CONFLICT (workspace_id, path) DO UPDATE SET value = EXCLUDED.value,
+                 description = EXCLUDED.description, resource_type = EXCLUDED.resource_type, edited_at = now()",
+                w_id,
+                to,
+                resource.value,
+                resource.description,
+                resource.resource_type,
+                &authed.username,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        report.push(ImportKind::Resource, &path, &target, None);
+    }
+
+    for (path, script) in archive.scripts {
+        let target = resolve_target(&mut tx, ImportKind::Script, w_id, &path, on_conflict).await?;
+        if let Some(to) = target.path() {
+            import_script(&mut tx, authed, w_id, to, script).await?;
+        }
+        report.push(ImportKind::Script, &path, &target, None);
+    }
+
+    for (path, flow) in archive.flows {
+        let target = resolve_target(&mut tx, ImportKind::Flow, w_id, &path, on_conflict).await?;
+        if let Some(to) = target.path() {
+            let schema = flow.schema.map(|s| s.get().to_string());
+            sqlx::query!(
+                "INSERT INTO flow (workspace_id, path, summary, description, dependency_job, lock_error_logs, draft_only,
+                 tag, dedicated_worker, ws_error_handler_muted, timeout, visible_to_runner_only, on_behalf_of_email,
+                 value, schema, edited_by, edited_at)
+                 VALUES ($1, $2, $3, $4, NULL, '', NULL, $5, $6, $7, $8, $9, $10, $11, $12::text::json, $13, now())
+                 ON CONFLICT (workspace_id, path) DO UPDATE SET summary = EXCLUDED.summary,
+                 description = EXCLUDED.description, archived = false, draft_only = NULL, tag = EXCLUDED.tag,
+                 dedicated_worker = EXCLUDED.dedicated_worker, ws_error_handler_muted = EXCLUDED.ws_error_handler_muted,
+                 timeout = EXCLUDED.timeout, visible_to_runner_only = EXCLUDED.visible_to_runner_only,
+                 on_behalf_of_email = EXCLUDED.on_behalf_of_email, value = EXCLUDED.value, schema = EXCLUDED.schema,
+                 edited_by = EXCLUDED.edited_by, edited_at = now()",
+                w_id,
+                to,
+                flow.summary,
+                flow.description,
+                flow.tag,
+                flow.dedicated_worker,
+                flow.ws_error_handler_muted.unwrap_or(false),
+                flow.timeout,
+                flow.visible_to_runner_only.unwrap_or(false),
+                flow.on_behalf_of_email.as_ref().map(|_| &authed.email),
+                flow.value,
+                schema,
+                &authed.username,
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let version = sqlx::query_scalar!(
+                "INSERT INTO flow_version (workspace_id, path, value, schema, created_by)
+                 VALUES ($1, $2, $3, $4::text::json, $5)
+                 RETURNING id",
+                w_id,
+                to,
+                flow.value,
+                schema,
+                &authed.username,
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                "UPDATE flow SET versions = array_append(versions, $1) WHERE path = $2 AND workspace_id = $3",
+                version,
+                to,
+                w_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        report.push(ImportKind::Flow, &path, &target, None);
+    }
+
+    for (path, app) in archive.apps {
+        let target = resolve_target(&mut tx, ImportKind::App, w_id, &path, on_conflict).await?;
+        let mut reason = None;
+        if let Some(to) = target.path() {
+            reason = import_app(&mut tx, authed, w_id, to, app, target.is_overwrite()).await?;
+        }
+        report.push(ImportKind::App, &path, &target, reason);
+    }
+
+    for (path, schedule) in archive.schedules {
+        let target =
+            resolve_target(&mut tx, ImportKind::Schedule, w_id, &path, on_conflict).await?;
+        if let Some(to) = target.path() {
+            if target.is_overwrite() {
+                clear_schedule(&mut tx, to, w_id).await?;
+                sqlx::query!(
+                    "DELETE FROM schedule WHERE path = $1 AND workspace_id = $2",
+                    to,
+                    w_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            let schedule = insert_schedule(&mut tx, authed, w_id, to, schedule).await?;
+            if schedule.enabled {
+                tx = push_scheduled_job(db, tx, &schedule, Some(&authed.clone().into())).await?;
+            }
+        }
+        report.push(ImportKind::Schedule, &path, &target, None);
+    }
+
+    for (path, kind) in archive.triggers {
+        report.skip(
+            ImportKind::Trigger,
+            &path,
+            format!("{kind} triggers are not imported, recreate them in the target workspace"),
+        );
+    }
+
+    if let Some(settings) = archive.settings {
+        if options.import_settings {
+            sqlx::query!(
+                "UPDATE workspace_settings SET webhook = $1, deploy_to = $2, error_handler = $3,
+                 error_handler_extra_args = $4, error_handler_muted_on_cancel = $5, ai_config = $6,
+                 large_file_storage = $7, default_app = $8, default_scripts = $9,
+                 mute_critical_alerts = $10, color = $11, operator_settings = $12
+                 WHERE workspace_id = $13",
+                settings.webhook,
+                settings.deploy_to,
+                settings.error_handler,
+                settings.error_handler_extra_args,
+                settings.error_handler_muted_on_cancel,
+                settings.ai_config,
+                settings.large_file_storage,
+                settings.default_app,
+                settings.default_scripts,
+                settings.mute_critical_alerts,
+                settings.color,
+                settings.operator_settings,
+                w_id,
+            )
+            .execute(&mut *tx)
+            .await?;
+            report.push(
+                ImportKind::Settings,
+                "settings",
+                &Target::Overwrite("settings".to_string()),
+                settings
+                    .git_sync
+                    .map(|_| "git sync settings are not imported".to_string()),
+            );
+        } else {
+            report.skip(
+                ImportKind::Settings,
+                "settings",
+                "import_settings is not set",
+            );
+        }
+    }
+
+    Ok((tx, report))
+}
+
+/// Users can only be added if they already have an account on the instance
+async fn import_user(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    email: &str,
+    user: ArchivedUser,
+    on_conflict: ConflictStrategy,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let is_admin = user.role == "admin";
+    let operator = user.role == "operator";
+    let existing = sqlx::query_scalar!(
+        "SELECT username FROM usr WHERE email = $1 AND workspace_id = $2",
+        email,
+        w_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if existing.is_some() {
+        if on_conflict == ConflictStrategy::Overwrite {
+            sqlx::query!(
+                "UPDATE usr SET is_admin = $1, operator = $2, disabled = $3 WHERE email = $4 AND workspace_id = $5",
+                is_admin,
+                operator,
+                user.disabled,
+                email,
+                w_id
+            )
+            .execute(&mut **tx)
+            .await?;
+            report.push(
+                ImportKind::User,
+                email,
+                &Target::Overwrite(email.to_string()),
+                None,
+            );
+        } else {
+            report.skip(ImportKind::User, email, "already in the workspace");
+        }
+        return Ok(());
+    }
+
+    let has_account = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM password WHERE email = $1)",
+        email
+    )
+    .fetch_one(&mut **tx)
+    .await?
+    .unwrap_or(false);
+    if !has_account {
+        report.skip(ImportKind::User, email, "no account on this instance");
+        return Ok(());
+    }
+
+    let username_taken = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM usr WHERE username = $1 AND workspace_id = $2)",
+        user.username,
+        w_id
+    )
+    .fetch_one(&mut **tx)
+    .await?
+    .unwrap_or(false);
+    if username_taken {
+        report.skip(
+            ImportKind::User,
+            email,
+            format!("username {} is used by another user", user.username),
+        );
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "INSERT INTO usr (workspace_id, email, username, is_admin, operator, disabled)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+        w_id,
+        email,
+        user.username,
+        is_admin,
+        operator,
+        user.disabled,
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        "INSERT INTO usr_to_group (workspace_id, usr, group_) VALUES ($1, $2, 'all') ON CONFLICT DO NOTHING",
+        w_id,
+        user.username,
+    )
+    .execute(&mut **tx)
+    .await?;
+    report.push(
+        ImportKind::User,
+        email,
+        &Target::Create(email.to_string()),
+        None,
+    );
+    Ok(())
+}
+
+/// A new version is added on top of the deployed one, which is archived like on a script update
+async fn import_script(
+    tx: &mut Transaction<'_, Postgres>,
+    authed: &ApiAuthed,
+    w_id: &str,
+    path: &str,
+    script: ScriptFile,
+) -> Result<()> {
+    let ScriptFile { content, language, metadata: ns } = script;
+    let parent = sqlx::query_scalar!(
+        "SELECT hash FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false
+         ORDER BY created_at DESC LIMIT 1",
+        path,
+        w_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    if let Some(parent) = parent {
+        sqlx::query!(
+            "UPDATE script SET archived = true WHERE hash = $1 AND workspace_id = $2",
+            parent,
+            w_id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    let hash = script_hash(w_id, path, &content, &ns.lock, parent);
+    let parent_hashes = parent.map(|p| vec![p]);
+    sqlx::query!(
+        "INSERT INTO script (workspace_id, hash, path, parent_hashes, summary, description, \
+         content, created_by, schema, is_template, extra_perms, lock, language, kind, tag, \
+         draft_only, envs, concurrent_limit, concurrency_time_window_s, cache_ttl, \
+         dedicated_worker, ws_error_handler_muted, priority, restart_unless_cancelled, \
+         delete_after_use, timeout, concurrency_key, visible_to_runner_only, no_main_func, codebase, has_preprocessor, on_behalf_of_email) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::text::json, false, '{}', $10, $11, $12, $13, NULL, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29)",
+        w_id,
+        hash,
+        path,
+        parent_hashes.as_deref(),
+        ns.summary,
+        ns.description,
+        content,
+        &authed.username,
+        ns.schema.map(|s| s.get().to_string()),
+        ns.lock,
+        language as ScriptLang,
+        ns.kind as ScriptKind,
+        ns.tag,
+        ns.envs.as_deref(),
+        ns.concurrent_limit,
+        ns.concurrency_time_window_s,
+        ns.cache_ttl,
+        ns.dedicated_worker,
+        ns.ws_error_handler_muted.unwrap_or(false),
+        ns.priority,
+        ns.restart_unless_cancelled,
+        ns.delete_after_use,
+        ns.timeout,
+        ns.concurrency_key,
+        ns.visible_to_runner_only,
+        ns.no_main_func.filter(|x| *x),
+        ns.codebase,
+        ns.has_preprocessor.filter(|x| *x),
+        ns.on_behalf_of_email.as_ref().map(|_| &authed.email),
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Apps run on behalf of the importing user. Returns why the custom path was dropped, if it was.
+async fn import_app(
+    tx: &mut Transaction<'_, Postgres>,
+    authed: &ApiAuthed,
+    w_id: &str,
+    path: &str,
+    app: ArchivedApp,
+    overwrite: bool,
+) -> Result<Option<String>> {
+    let mut policy = app.policy;
+    policy.on_behalf_of = Some(username_to_permissioned_as(&authed.username));
+    policy.on_behalf_of_email = Some(authed.email.clone());
+
+    let mut reason = None;
+    let mut custom_path = app.custom_path.filter(|s| !s.is_empty());
+    if let Some(cp) = custom_path.as_ref() {
+        let taken = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM app WHERE custom_path = $1 AND path != $2)",
+            cp,
+            path
+        )
+        .fetch_one(&mut **tx)
+        .await?
+        .unwrap_or(false);
+        if taken {
+            reason = Some(format!("custom path {cp} is already used by another app"));
+            custom_path = None;
+        }
+    }
+
+    let id = if overwrite {
+        sqlx::query_scalar!(
+            "UPDATE app SET summary = $1, policy = $2, custom_path = $3, draft_only = NULL
+             WHERE path = $4 AND workspace_id = $5 RETURNING id",
+            app.summary,
+            json!(policy),
+            custom_path,
+            path,
+            w_id
+        )
+        .fetch_one(&mut **tx)
+        .await?
+    } else {
+        sqlx::query_scalar!(
+            "INSERT INTO app (workspace_id, path, summary, policy, versions, draft_only, custom_path)
+             VALUES ($1, $2, $3, $4, '{}', NULL, $5) RETURNING id",
+            w_id,
+            path,
+            app.summary,
+            json!(policy),
+            custom_path
+        )
+        .fetch_one(&mut **tx)
+        .await?
+    };
+
+    let v_id = sqlx::query_scalar!(
+        "INSERT INTO app_version (app_id, value, created_by, raw_app)
+         VALUES ($1, $2::text::json, $3, false) RETURNING id",
+        id,
+        //to preserve key orders
+        app.value.get(),
+        &authed.username,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE app SET versions = array_append(versions, $1::bigint) WHERE id = $2",
+        v_id,
+        id
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(reason)
+}
+
+async fn insert_schedule(
+    tx: &mut Transaction<'_, Postgres>,
+    authed: &ApiAuthed,
+    w_id: &str,
+    path: &str,
+    s: ArchivedSchedule,
+) -> Result<Schedule> {
+    let schedule = sqlx::query_as!(
+        Schedule,
+        r#"
+        INSERT INTO schedule (
+            workspace_id, path, schedule, timezone, edited_by, script_path,
+            is_flow, args, enabled, email,
+            on_failure, on_failure_times, on_failure_exact, on_failure_extra_args,
+            on_recovery, on_recovery_times, on_recovery_extra_args,
+            on_success, on_success_extra_args,
+            ws_error_handler_muted, retry, summary, no_flow_overlap,
+            tag, paused_until, cron_version, description
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6,
+            $7, $8, $9, $10,
+            $11, $12, $13, $14,
+            $15, $16, $17,
+            $18, $19,
+            $20, $21, $22, $23,
+            $24, $25, $26, $27
+        )
+        RETURNING
+            workspace_id,
+            path,
+            edited_by,
+            edited_at,
+            schedule,
+            timezone,
+            enabled,
+            script_path,
+            is_flow,
+            args AS "args: _",
+            extra_perms,
+            email,
+            error,
+            on_failure,
+            on_failure_times,
+            on_failure_exact,
+            on_failure_extra_args AS "on_failure_extra_args: _",
+            on_recovery,
+            on_recovery_times,
+            on_recovery_extra_args AS "on_recovery_extra_args: _",
+            on_success,
+            on_success_extra_args  AS "on_success_extra_args: _",
+            ws_error_handler_muted,
+            retry,
+            no_flow_overlap,
+            summary,
+            description,
+            tag,
+            paused_until,
+            cron_version
+        "#,
+        w_id,
+        path,
+        s.schedule,
+        s.timezone,
+        &authed.username,
+        s.script_path,
+        s.is_flow,
+        s.args.map(sqlx::types::Json) as Option<sqlx::types::Json<Box<RawValue>>>,
+        s.enabled,
+        &authed.email,
+        s.on_failure,
+        s.on_failure_times,
+        s.on_failure_exact,
+        s.on_failure_extra_args.map(sqlx::types::Json) as Option<sqlx::types::Json<Box<RawValue>>>,
+        s.on_recovery,
+        s.on_recovery_times,
+        s.on_recovery_extra_args.map(sqlx::types::Json) as Option<sqlx::types::Json<Box<RawValue>>>,
+        s.on_success,
+        s.on_success_extra_args.map(sqlx::types::Json) as Option<sqlx::types::Json<Box<RawValue>>>,
+        s.ws_error_handler_muted,
+        s.retry,
+        s.summary,
+        s.no_flow_overlap,
+        s.tag,
+        s.paused_until,
+        s.cron_version,
+        s.description,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(schedule)
+}
@@ -0,0 +1,380 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Conversion of scripts and flows to tools callable by language models, shared by the MCP
+//! server and the AI agent flow steps.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+
+use crate::scripts::Schema;
+
+#[derive(Serialize, FromRow, Deserialize, Debug, Clone)]
+pub struct SchemaType {
+    pub r#type: String,
+    pub properties: std::collections::HashMap<String, serde_json::Value>,
+    pub required: Vec<String>,
+}
+
+impl Default for SchemaType {
+    fn default() -> Self {
+        Self {
+            r#type: "object".to_string(),
+            properties: std::collections::HashMap::new(),
+            required: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct ResourceInfo {
+    pub path: String,
+    pub description: Option<String>,
+    pub resource_type: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct ResourceType {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Transforms the path for workspace scripts/flows.
+///
+/// This function takes a path and a type string.
+/// It then formats the transformed path with the type prefix.
+/// This is used when listing, because we can't have names with slashes.
+/// Because we replace slashes with underscores, we also need to escape underscores.
+///
+/// # Parameters
+/// - `path`: The path to transform.
+/// - `type_str`: The type of the item (script or flow).
+///
+/// # Returns
+/// - `String`: The transformed path.
+pub fn transform_path(path: &str, type_str: &str) -> String {
+    // Only apply special underscore escaping for paths starting with "f/"
+    let transformed = if path.starts_with("f/") {
+        let escaped_path = path.replace('_', "__");
+        escaped_path.replace('/', "_")
+    } else {
+        path.replace('/', "_")
+    };
+
+    // first letter of type_str is used as prefix, only one letter to avoid reaching 60 char name limit
+    format!("{}-{}", &type_str[..1], transformed)
+}
+
+pub fn convert_schema_to_schema_type(schema: Option<Schema>) -> SchemaType {
+    let schema_obj = if let Some(ref s) = schema {
+        match serde_json::from_str::<SchemaType>(s.0.get()) {
+            Ok(val) => val,
+            Err(_) => SchemaType::default(),
+        }
+    } else {
+        SchemaType::default()
+    };
+    schema_obj
+}
+
+/// Description of the tool of a script or flow, hub scripts also mention their app
+pub fn tool_description(
+    item_type: &str,
+    summary: &str,
+    description: &str,
+    hub_app: Option<&str>,
+) -> String {
+    format!(
+        "This is a {} named `{}` with the following description: `{}`.{}",
+        item_type,
+        summary,
+        description,
+        if let Some(app) = hub_app {
+            format!(" It is a tool used for the following app: {}", app)
+        } else {
+            "".to_string()
+        }
+    )
+}
+
+/// Applies a key transformation to a key.
+///
+/// This function takes a key and replaces spaces with underscores.
+/// It also removes any characters that are not alphanumeric or underscores.
+/// This is used when listing, because we can't have names with spaces or special characters in the schema properties.
+/// # Parameters
+/// - `key`: The key to transform.
+///
+/// # Returns
+/// - `String`: The transformed key.
+pub fn apply_key_transformation(key: &str) -> String {
+    key.replace(' ', "_")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_')
+        .collect::<String>()
+}
+
+/// Reverses the transformation of a key.
+///
+/// This function takes a transformed key and a schema object.
+/// It then reverses the transformation applied by `apply_key_transformation`. This can be subject to collisions, but it's unlikely and is ok for our use case.
+/// # Parameters
+/// - `transformed_key`: The transformed key to reverse.
+/// - `schema_obj`: The schema object.
+///
+/// # Returns
+/// - `String`: The original key.
+pub fn reverse_transform_key(transformed_key: &str, schema_obj: &Option<SchemaType>) -> String {
+    let schema_obj = match schema_obj {
+        Some(s) => s,
+        None => {
+            // No schema available, return the key as is (best guess)
+            return transformed_key.to_string();
+        }
+    };
+
+    for original_key_in_schema in schema_obj.properties.keys() {
+        // Apply the SAME forward transformation to the schema key
+        let potential_transformed_key = apply_key_transformation(original_key_in_schema);
+
+        // If it matches the key we received, we found the likely original
+        if potential_transformed_key == transformed_key {
+            return original_key_in_schema.clone();
+        }
+    }
+
+    transformed_key.to_string()
+}
+
+/// Transforms a value if it's an object.
+///
+/// This function takes a key and a value, and a schema object.
+/// If the value is a string that starts with "$res:", it returns the value as is.
+/// Otherwise, it checks if the key is defined in the schema and if it's an object type.
+/// If it is, it transforms the value to a string. This is because some clients do not support object types.
+/// # Parameters
+/// - `key`: The key of the value to transform.
+/// - `value`: The value to transform.
+/// - `schema_obj`: The schema object.
+///
+/// # Returns
+/// - `Value`: The transformed value.
+pub fn transform_value_if_object(
+    key: &str,
+    value: &Value,
+    schema_obj: &Option<SchemaType>,
+) -> Value {
+    if value.is_string() && value.as_str().unwrap().starts_with("$res:") {
+        return value.clone();
+    }
+
+    let schema_obj = match schema_obj {
+        Some(s) => s,
+        None => return value.clone(),
+    };
+
+    // Check if property is defined in schema and is an object type
+    let is_obj_type = match schema_obj.properties.get(key) {
+        Some(property) => {
+            let prop_type = property.get("type").and_then(|t| t.as_str());
+            prop_type == Some("object")
+        }
+        None => false,
+    };
+
+    // If it's an object type and we received a string, try to parse it
+    if is_obj_type && value.is_string() {
+        if let Some(str_val) = value.as_str() {
+            if let Ok(obj_val) = serde_json::from_str::<serde_json::Value>(str_val) {
+                return obj_val;
+            }
+        }
+    }
+
+    value.clone()
+}
+
+/// Resource types of the properties of the schema that expect a resource, the available
+/// resources of these types must be in the cache passed to `transform_schema_for_resources`
+pub fn schema_resource_types(schema: &SchemaType) -> Vec<String> {
+    schema
+        .properties
+        .values()
+        .filter_map(|prop| prop.get("format").and_then(|f| f.as_str()))
+        .filter(|format| format.starts_with("resource-"))
+        .map(|format| format.split("-").last().unwrap_or_default().to_string())
+        .collect()
+}
+
+/// Transforms the schema for resources.
+///
+/// This function replaces invalid char in property keys with underscores and converts object properties to strings.
+/// It also adds the resource type infos and the available resources of the cache to the description of resource properties.
+/// Resource properties whose type is missing from the cache are left as is.
+///
+/// # Parameters
+/// - `schema`: The schema to transform.
+/// - `resources_cache`: The available resources by resource type.
+/// - `resources_types`: The resource types of the workspace.
+///
+/// # Returns
+/// - `SchemaType`: The transformed schema.
+pub fn transform_schema_for_resources(
+    schema: &SchemaType,
+    resources_cache: &HashMap<String, Vec<ResourceInfo>>,
+    resources_types: &[ResourceType],
+) -> SchemaType {
+    let mut schema_obj: SchemaType = schema.clone();
+
+    // replace invalid char in property key with underscore
+    let replacements: Vec<(String, String, serde_json::Value)> = schema_obj
+        .properties
+        .iter()
+        .filter_map(|(key, value)| {
+            if key.chars().any(|c| !c.is_alphanumeric() && c != '_') {
+                let new_key = apply_key_transformation(key);
+                Some((key.clone(), new_key, value.clone()))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for (old_key, new_key, value) in replacements {
+        schema_obj.properties.remove(&old_key);
+        schema_obj.properties.insert(new_key, value);
+    }
+
+    for (_key, prop_value) in schema_obj.properties.iter_mut() {
+        if let serde_json::Value::Object(prop_map) = prop_value {
+            // transform object properties to string because some client does not support object, might change in the future
+            if let Some(type_value) = prop_map.get("type") {
+                if let serde_json::Value::String(type_str) = type_value {
+                    if type_str == "object" {
+                        prop_map.insert(
+                            "type".to_string(),
+                            serde_json::Value::String("string".to_string()),
+                        );
+                    }
+                }
+            }
+            // if property is a resource, add each available resource to the description
+            if let Some(format_value) = prop_map.get("format") {
+                if let serde_json::Value::String(format_str) = format_value {
+                    if format_str.starts_with("resource-") {
+                        let resource_type_key =
+                            format_str.split("-").last().unwrap_or_default().to_string();
+                        let resource_type = resources_types
+                            .iter()
+                            .find(|rt| rt.name == resource_type_key);
+                        let resource_type_obj = resource_type.cloned().unwrap_or_else(|| {
+                            tracing::info!("Resource type not found: {}", resource_type_key);
+                            ResourceType { name: resource_type_key.clone(), description: None }
+                        });
+
+                        if let Some(resource_cache) = resources_cache.get(&resource_type_key) {
+                            let resources_count = resource_cache.len();
+                            let description = format!(
+                                "This is a resource named `{}` with the following description: `{}`.\nThe path of the resource should be used to specify the resource.\n{}",
+                                resource_type_obj.name,
+                                resource_type_obj.description.as_deref().unwrap_or("No description"),
+                                if resources_count == 0 {
+                                    "This resource does not have any available instances, you should create one from your windmill workspace."
+                                } else if resources_count > 1 {
+                                    "This resource has multiple available instances, you should precisely select the one you want to use."
+                                } else {
+                                    "There is 1 resource available."
+                                }
+                            );
+                            prop_map.insert(
+                                "type".to_string(),
+                                serde_json::Value::String("string".to_string()),
+                            );
+                            prop_map.insert(
+                                "description".to_string(),
+                                serde_json::Value::String(description),
+                            );
+                            if resources_count > 0 {
+                                let resources_description = resource_cache
+                                    .iter()
+                                    .map(|resource| {
+                                        format!(
+                                            "{}: $res:{}",
+                                            resource.description.as_deref().unwrap_or("No title"),
+                                            resource.path
+                                        )
+                                    })
+                                    .collect::<Vec<String>>()
+                                    .join("\n");
+
+                                prop_map.insert(
+                                    "description".to_string(),
+                                    serde_json::Value::String(format!(
+                                        "{}\nHere are the available resources, in the format title:path. Title can be empty. Path should be used to specify the resource:\n{}",
+                                        prop_map.get("description").unwrap_or(&serde_json::Value::String("No description".to_string())),
+                                        resources_description
+                                    )),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        } else {
+            tracing::warn!(
+                "Schema property value is not a JSON object: {:?}",
+                prop_value
+            );
+        }
+    }
+
+    schema_obj
+}
+
+/// Input schema of the tool as a JSON object, empty if the schema cannot be serialized
+pub fn tool_input_schema(schema_obj: SchemaType, path: &str) -> serde_json::Map<String, Value> {
+    match serde_json::to_value(schema_obj) {
+        Ok(Value::Object(map)) => map,
+        Ok(_) => {
+            tracing::warn!(
+                "Schema object for tool '{}' did not serialize to a JSON object, using empty schema.",
+                path
+            );
+            serde_json::Map::new()
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed to serialize schema object for tool '{}': {}. Using empty schema.",
+                path,
+                e
+            );
+            serde_json::Map::new()
+        }
+    }
+}
+
+/// Arguments of the runnable from the arguments of a tool call, with the original property keys
+/// and the object properties parsed back from their string form
+pub fn tool_args_to_runnable_args(
+    args: serde_json::Map<String, Value>,
+    schema_obj: &Option<SchemaType>,
+) -> HashMap<String, Box<serde_json::value::RawValue>> {
+    let mut args_hash = HashMap::new();
+    for (k, v) in args {
+        // need to transform back the key without invalid characters to the original key
+        let original_key = reverse_transform_key(&k, schema_obj);
+
+        // object properties are transformed to string because some client does not support object, might change in the future
+        let transformed_v = transform_value_if_object(&k, &v, schema_obj);
+        args_hash.insert(original_key, crate::worker::to_raw_value(&transformed_v));
+    }
+    args_hash
+}
@@ -250,4 +250,49 @@ impl AuthedClient {
             _ => Err(anyhow::anyhow!(response.text().await.unwrap_or_default()))?,
         }
     }
+
+    /// Sends a request to the workspace AI proxy using the given provider and resource
+    pub async fn ai_proxy_request(
+        &self,
+        provider: &str,
+        resource_path: &str,
+        ai_path: &str,
+        body: &serde_json::Value,
+    ) -> error::Result<serde_json::Value> {
+        let url = format!(
+            "{}/api/w/{}/ai/proxy/{}",
+            self.base_internal_url, &self.workspace, ai_path
+        );
+        let response = self
+            .force_client
+            .as_ref()
+            .unwrap_or(&HTTP_CLIENT)
+            .post(url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                reqwest::header::HeaderValue::from_static("application/json"),
+            )
+            .header(
+                reqwest::header::AUTHORIZATION,
+                reqwest::header::HeaderValue::from_str(&format!("Bearer {}", self.token))
+                    .map_err(|e| error::Error::BadConfig(e.to_string()))?,
+            )
+            .header("X-Provider", provider)
+            .header("X-Resource-Path", resource_path)
+            .body(serde_json::to_string(body).map_err(to_anyhow)?)
+            .send()
+            .await
+            .context(format!("Sent ai proxy request to {ai_path}"))
+            .map_err(error::Error::from)?;
+        if response.status().is_success() {
+            Ok(response
+                .json::<serde_json::Value>()
+                .await
+                .context("decoding ai proxy response as json")?)
+        } else {
+            Err(error::Error::AIError(
+                response.text().await.unwrap_or_default(),
+            ))
+        }
+    }
 }
@@ -0,0 +1,48 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Environments are named overlays of a workspace: variables and resources can carry a value
+//! per environment that replaces their own value for the jobs running in that environment.
+
+use uuid::Uuid;
+
+use crate::{error::Result, jobs::ENVIRONMENT_ARG, DB};
+
+/// Environment a job runs in, by order of precedence:
+/// - the `_ENVIRONMENT` arg of the job, then of its root flow
+/// - the environment bound to the schedule or trigger that started the job, then its root flow
+/// - the environment of the worker tag of the job
+///
+/// Names that are not environments of the workspace are skipped for the next source.
+pub async fn get_job_environment(db: &DB, w_id: &str, job_id: &Uuid) -> Result<Option<String>> {
+    let environment = sqlx::query_scalar!(
+        "SELECT e.name FROM v2_job j
+        LEFT JOIN v2_job r ON r.id = COALESCE(j.root_job, j.flow_innermost_root_job, j.parent_job)
+        CROSS JOIN LATERAL (VALUES
+            (1, j.args->>$3),
+            (2, r.args->>$3),
+            (3, (SELECT b.environment FROM environment_binding b
+                WHERE b.workspace_id = j.workspace_id AND b.kind = j.trigger_kind AND b.path = j.trigger)),
+            (4, (SELECT b.environment FROM environment_binding b
+                WHERE b.workspace_id = r.workspace_id AND b.kind = r.trigger_kind AND b.path = r.trigger)),
+            (5, (SELECT t.name FROM environment t
+                WHERE t.workspace_id = j.workspace_id AND j.tag = ANY(t.worker_tags)
+                ORDER BY t.name LIMIT 1))
+        ) AS c(precedence, name)
+        JOIN environment e ON e.workspace_id = j.workspace_id AND e.name = c.name
+        WHERE j.id = $1 AND j.workspace_id = $2
+        ORDER BY c.precedence
+        LIMIT 1",
+        job_id,
+        w_id,
+        ENVIRONMENT_ARG
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(environment)
+}
@@ -54,6 +54,8 @@ pub enum Error {
     BadRequest(String),
     #[error("Quota exceeded: {0}")]
     QuotaExceeded(String),
+    #[error("Too many requests: {message}")]
+    TooManyRequests { message: String, retry_after_s: u64 },
     #[error("Internal: {0}")]
     InternalErr(String),
     #[error("Internal: {message} @{location}")]
@@ -188,13 +190,17 @@ impl IntoResponse for Error {
             | Self::AIError(_)
             | Self::QuotaExceeded(_) => axum::http::StatusCode::BAD_REQUEST,
             Self::BadGateway(_) => axum::http::StatusCode::BAD_GATEWAY,
+            Self::TooManyRequests { .. } => axum::http::StatusCode::TOO_MANY_REQUESTS,
             Self::Generic(status_code, _) => status_code,
             _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
         };
 
         let e = &self;
 
-        if matches!(status, axum::http::StatusCode::NOT_FOUND) {
+        if matches!(
+            status,
+            axum::http::StatusCode::NOT_FOUND | axum::http::StatusCode::TOO_MANY_REQUESTS
+        ) {
             tracing::warn!(message = e.to_string());
         } else {
             tracing::error!(message = e.to_string(), error = ?e);
@@ -202,11 +208,15 @@ impl IntoResponse for Error {
 
         let body = Body::from(e.to_string());
 
-        axum::response::Response::builder()
+        let mut response = axum::response::Response::builder()
             .header("Content-Type", "text/plain")
-            .status(status)
-            .body(body)
-            .unwrap()
+            .status(status);
+
+        if let Self::TooManyRequests { retry_after_s, .. } = e {
+            response = response.header("Retry-After", retry_after_s.to_string());
+        }
+
+        response.body(body).unwrap()
     }
 }
 
@@ -12,7 +12,7 @@ use std::time::Duration;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::flows::FlowValue;
+use crate::flows::{AgentToolKind, FlowValue};
 
 const MINUTES: Duration = Duration::from_secs(60);
 const HOURS: Duration = MINUTES.saturating_mul(60);
@@ -34,6 +34,10 @@ pub struct FlowStatus {
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     #[serde(default)]
     pub user_states: HashMap<String, serde_json::Value>,
+    /// tool calls of the AI agent steps, by job of the step
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default)]
+    pub agent_actions: HashMap<Uuid, Vec<AgentAction>>,
     #[serde(default)]
     pub cleanup_module: FlowCleanupModule,
     #[serde(default)]
@@ -45,6 +49,14 @@ pub struct FlowStatus {
     pub restarted_from: Option<RestartedFrom>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AgentAction {
+    pub job_id: Uuid,
+    pub tool: String,
+    pub kind: AgentToolKind,
+    pub path: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(default)]
 pub struct RetryStatus {
@@ -391,6 +403,7 @@ impl FlowStatus {
             retry: RetryStatus { fail_count: 0, failed_jobs: vec![] },
             restarted_from: None,
             user_states: HashMap::new(),
+            agent_actions: HashMap::new(),
         }
     }
 
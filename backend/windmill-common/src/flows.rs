@@ -508,6 +508,20 @@ pub enum FlowModuleValue {
         #[serde(skip_serializing_if = "Option::is_none")]
         assets: Option<Vec<AssetWithAltAccessType>>,
     },
+    AIAgent {
+        #[serde(default)]
+        #[serde(alias = "input_transform", serialize_with = "ordered_map")]
+        input_transforms: HashMap<String, InputTransform>,
+        tools: Vec<AgentTool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_iterations: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_tool_calls: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tool_timeout: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output_schema: Option<serde_json::Value>,
+    },
     Identity,
     // Internal only, never exposed to the frontend.
     FlowScript {
@@ -531,6 +545,38 @@ pub enum FlowModuleValue {
     },
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentToolKind {
+    Script,
+    Flow,
+}
+
+/// Workspace script or flow the model of an AI agent step can call
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AgentTool {
+    pub kind: AgentToolKind,
+    pub path: String,
+    /// replaces the summary and description of the runnable in the description of the tool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Static settings of an AI agent step, passed to its job along with the evaluated inputs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AIAgentConfig {
+    pub tools: Vec<AgentTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tool_calls: Option<u32>,
+    /// seconds after which a tool job is canceled and reported to the model as failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_timeout: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+}
+
 fn is_none_or_empty(expr: &Option<String>) -> bool {
     expr.is_none() || expr.as_ref().unwrap().is_empty()
 }
@@ -563,6 +609,11 @@ struct UntaggedFlowModuleValue {
     default_node: Option<FlowNodeId>,
     modules_node: Option<FlowNodeId>,
     assets: Option<Vec<AssetWithAltAccessType>>,
+    tools: Option<Vec<AgentTool>>,
+    max_iterations: Option<u32>,
+    max_tool_calls: Option<u32>,
+    tool_timeout: Option<u32>,
+    output_schema: Option<serde_json::Value>,
 }
 
 impl<'de> Deserialize<'de> for FlowModuleValue {
@@ -654,6 +705,14 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                 is_trigger: untagged.is_trigger,
                 assets: untagged.assets,
             }),
+            "aiagent" => Ok(FlowModuleValue::AIAgent {
+                input_transforms: untagged.input_transforms.unwrap_or_default(),
+                tools: untagged.tools.unwrap_or_default(),
+                max_iterations: untagged.max_iterations,
+                max_tool_calls: untagged.max_tool_calls,
+                tool_timeout: untagged.tool_timeout,
+                output_schema: untagged.output_schema,
+            }),
             "identity" => Ok(FlowModuleValue::Identity),
             other => Err(serde::de::Error::unknown_variant(
                 other,
@@ -665,6 +724,7 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                     "branchone",
                     "branchall",
                     "rawscript",
+                    "aiagent",
                     "identity",
                 ],
             )),
@@ -873,3 +933,69 @@ pub async fn resolve_modules(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ai_agent_module_round_trip() {
+        let value = serde_json::json!({
+            "type": "aiagent",
+            "input_transforms": {
+                "user_message": { "type": "javascript", "expr": "flow_input.question" }
+            },
+            "tools": [
+                { "kind": "script", "path": "f/tools/search", "description": "Search the docs" },
+                { "kind": "flow", "path": "f/tools/summarize" }
+            ],
+            "max_iterations": 5,
+            "tool_timeout": 60,
+            "output_schema": { "type": "object" }
+        });
+
+        let module = serde_json::from_value::<FlowModuleValue>(value.clone()).unwrap();
+        let FlowModuleValue::AIAgent {
+            input_transforms,
+            tools,
+            max_iterations,
+            max_tool_calls,
+            tool_timeout,
+            output_schema,
+        } = &module
+        else {
+            panic!("expected an AI agent module, got {module:?}");
+        };
+        assert!(matches!(
+            input_transforms.get("user_message"),
+            Some(InputTransform::Javascript { expr }) if expr == "flow_input.question"
+        ));
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].kind, AgentToolKind::Script);
+        assert_eq!(tools[0].description.as_deref(), Some("Search the docs"));
+        assert_eq!(tools[1].kind, AgentToolKind::Flow);
+        assert_eq!(tools[1].path, "f/tools/summarize");
+        assert_eq!(*max_iterations, Some(5));
+        assert_eq!(*max_tool_calls, None);
+        assert_eq!(*tool_timeout, Some(60));
+        assert_eq!(
+            output_schema,
+            &Some(serde_json::json!({ "type": "object" }))
+        );
+
+        // unset settings are not serialized and the module reads back the same
+        assert_eq!(serde_json::to_value(&module).unwrap(), value);
+    }
+
+    #[test]
+    fn test_ai_agent_module_defaults() {
+        let module =
+            serde_json::from_value::<FlowModuleValue>(serde_json::json!({ "type": "aiagent" }))
+                .unwrap();
+        assert!(matches!(
+            module,
+            FlowModuleValue::AIAgent { ref input_transforms, ref tools, max_iterations: None, .. }
+                if input_transforms.is_empty() && tools.is_empty()
+        ));
+    }
+}
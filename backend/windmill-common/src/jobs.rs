@@ -10,6 +10,8 @@ use tokio::io::AsyncReadExt;
 use uuid::Uuid;
 
 pub const ENTRYPOINT_OVERRIDE: &str = "_ENTRYPOINT_OVERRIDE";
+pub const ENVIRONMENT_ARG: &str = "_ENVIRONMENT";
+pub const AI_AGENT_ARG: &str = "_AI_AGENT";
 pub const LARGE_LOG_THRESHOLD_SIZE: usize = 9000;
 
 use crate::{
@@ -46,6 +48,7 @@ pub enum JobKind {
     FlowScript,
     FlowNode,
     AppScript,
+    AIAgent,
 }
 
 impl JobKind {
@@ -371,6 +374,9 @@ pub enum JobPayload {
     DeploymentCallback {
         path: String,
     },
+    AIAgent {
+        path: String, // flow step path (e.g. `outer/a`).
+    },
     Identity,
     Noop,
 }
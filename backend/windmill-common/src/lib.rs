@@ -25,6 +25,7 @@ use scripts::ScriptLang;
 use sqlx::{Pool, Postgres};
 
 pub mod agent_workers;
+pub mod ai_tools;
 pub mod apps;
 pub mod assets;
 pub mod auth;
@@ -39,6 +40,7 @@ pub mod ee_oss;
 #[cfg(feature = "private")]
 pub mod email_ee;
 pub mod email_oss;
+pub mod environments;
 pub mod error;
 pub mod external_ip;
 pub mod flow_status;
@@ -64,6 +66,7 @@ pub mod oauth2;
 pub mod otel_ee;
 pub mod otel_oss;
 pub mod queue;
+pub mod quotas;
 pub mod s3_helpers;
 pub mod schedule;
 pub mod schema;
@@ -433,7 +436,7 @@ pub fn get_latest_deployed_hash_for_path<
             _ => {
                 tracing::debug!("Fetching script hash for {script_path}");
                 let hash = sqlx::query_scalar!( 
-                    "select hash from script where path = $1 AND workspace_id = $2 AND deleted = false AND lock IS not NULL AND lock_error_logs IS NULL ORDER BY created_at DESC LIMIT 1",
+                    "select hash from script where path = $1 AND workspace_id = ANY(ARRAY[$2::varchar, (SELECT parent_workspace_id FROM workspace_fork WHERE workspace_id = $2)]) AND deleted = false AND lock IS not NULL AND lock_error_logs IS NULL ORDER BY workspace_id = $2 DESC, created_at DESC LIMIT 1",
                     script_path,
                     w_id
                 )
@@ -474,7 +477,7 @@ pub async fn get_script_info_for_hash<'e, E: sqlx::PgExecutor<'e>>(
             tracing::debug!("Fetching deployed script info for {hash}");
             let info = sqlx::query_as!(
                     ScriptHashInfo,
-                    "select hash, tag, concurrency_key, concurrent_limit, concurrency_time_window_s, cache_ttl, language as \"language: ScriptLang\", dedicated_worker, priority, delete_after_use, timeout, has_preprocessor, on_behalf_of_email, created_by, path from script where hash = $1 AND workspace_id = $2",
+                    "select hash, tag, concurrency_key, concurrent_limit, concurrency_time_window_s, cache_ttl, language as \"language: ScriptLang\", dedicated_worker, priority, delete_after_use, timeout, has_preprocessor, on_behalf_of_email, created_by, path from script where hash = $1 AND workspace_id = ANY(ARRAY[$2::varchar, (SELECT parent_workspace_id FROM workspace_fork WHERE workspace_id = $2)])",
                     hash,
                     w_id
                 )
@@ -533,7 +536,8 @@ pub fn get_latest_flow_version_info_for_path<
                     "SELECT flow_version.id from flow
                     INNER JOIN flow_version
                     ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
-                    WHERE flow.path = $1 and flow.workspace_id = $2",
+                    WHERE flow.path = $1 and flow.workspace_id = ANY(ARRAY[$2::varchar, (SELECT parent_workspace_id FROM workspace_fork WHERE workspace_id = $2)])
+                    ORDER BY flow.workspace_id = $2 DESC LIMIT 1",
                     path,
                     w_id
                 )
@@ -568,8 +572,8 @@ pub fn get_latest_flow_version_info_for_path<
                     "SELECT tag, dedicated_worker, flow_version.value->>'early_return' as early_return, flow_version.value->>'preprocessor_module' IS NOT NULL as has_preprocessor, on_behalf_of_email, edited_by, flow_version.id AS version
                     FROM flow
                     INNER JOIN flow_version
-                        ON flow_version.id = $3
-                    WHERE flow.path = $1 and flow.workspace_id = $2",
+                        ON flow_version.id = $3 AND flow_version.workspace_id = flow.workspace_id
+                    WHERE flow.path = $1 and flow.workspace_id = ANY(ARRAY[$2::varchar, (SELECT parent_workspace_id FROM workspace_fork WHERE workspace_id = $2)])",
                 path,
                 w_id,
                 version
@@ -606,9 +610,8 @@ pub async fn get_latest_hash_for_path<'c>(
     String,
 )> {
     let r_o = sqlx::query!(
-        "select hash, tag, concurrency_key, concurrent_limit, concurrency_time_window_s, cache_ttl, language as \"language: ScriptLang\", dedicated_worker, priority, timeout, on_behalf_of_email, created_by FROM script where path = $1 AND workspace_id = $2 AND
-    created_at = (SELECT max(created_at) FROM script WHERE path = $1 AND workspace_id = $2 AND
-    deleted = false AND archived = false)",
+        "select hash, tag, concurrency_key, concurrent_limit, concurrency_time_window_s, cache_ttl, language as \"language: ScriptLang\", dedicated_worker, priority, timeout, on_behalf_of_email, created_by FROM script where path = $1 AND workspace_id = ANY(ARRAY[$2::varchar, (SELECT parent_workspace_id FROM workspace_fork WHERE workspace_id = $2)]) AND
+    deleted = false AND archived = false ORDER BY workspace_id = $2 DESC, created_at DESC LIMIT 1",
         script_path,
         w_id
     )
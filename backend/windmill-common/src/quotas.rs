@@ -0,0 +1,297 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Quotas on what a workspace consumes of the shared resources of the instance, set by the
+//! superadmins. Reaching the soft limit of a quota warns the workspace through a critical alert,
+//! reaching the hard limit rejects the jobs and objects that would consume more of it, except for
+//! the concurrent jobs which are held in the queue until the running ones complete.
+//!
+//! Consumption is cached for up to a minute, so it can go over a hard limit by what is consumed
+//! during that duration.
+
+use std::time::{Duration, Instant};
+
+use quick_cache::sync::Cache;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    DB,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct QuotaLimit {
+    /// a critical alert is sent to the workspace once a day while it is reached
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soft: Option<i64>,
+    /// what would consume more once it is reached is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hard: Option<i64>,
+}
+
+impl QuotaLimit {
+    /// Status of the quota given what the workspace already consumes of it
+    pub fn status(&self, w_id: &str, kind: QuotaKind, used: i64) -> QuotaStatus {
+        if let Some(hard) = self.hard.filter(|hard| used >= *hard) {
+            return QuotaStatus::Exceeded(format!(
+                "Workspace {w_id} has reached its {} quota of {hard} ({used} used), ask an instance admin to raise it",
+                kind.as_str()
+            ));
+        }
+        if let Some(soft) = self.soft.filter(|soft| used >= *soft) {
+            return QuotaStatus::Warning(format!(
+                "Workspace {w_id} has reached the warning threshold of {soft} of its {} quota ({used} used{})",
+                kind.as_str(),
+                self.hard
+                    .map(|hard| format!(", hard limit is {hard}"))
+                    .unwrap_or_default()
+            ));
+        }
+        QuotaStatus::Ok
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WorkspaceQuotas {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrent_jobs: Option<QuotaLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_seconds_per_day: Option<QuotaLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_bytes: Option<QuotaLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_bytes: Option<QuotaLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedules: Option<QuotaLimit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_storage_bytes: Option<QuotaLimit>,
+}
+
+impl WorkspaceQuotas {
+    pub fn get(&self, kind: QuotaKind) -> Option<&QuotaLimit> {
+        match kind {
+            QuotaKind::ConcurrentJobs => self.concurrent_jobs.as_ref(),
+            QuotaKind::JobSecondsPerDay => self.job_seconds_per_day.as_ref(),
+            QuotaKind::ResultBytes => self.result_bytes.as_ref(),
+            QuotaKind::LogBytes => self.log_bytes.as_ref(),
+            QuotaKind::Schedules => self.schedules.as_ref(),
+            QuotaKind::ObjectStorageBytes => self.object_storage_bytes.as_ref(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    /// jobs of the workspace running at the same time, flows excluded as their steps are counted
+    ConcurrentJobs,
+    /// seconds spent running the jobs of the workspace completed today (UTC), flows excluded
+    /// as their steps are counted
+    JobSecondsPerDay,
+    /// size of the stored results of the completed jobs
+    ResultBytes,
+    /// size of the logs of the jobs stored in the database
+    LogBytes,
+    Schedules,
+    /// size of the objects of the workspace storage, as of its last measure
+    ObjectStorageBytes,
+}
+
+impl QuotaKind {
+    pub const ALL: [QuotaKind; 6] = [
+        QuotaKind::ConcurrentJobs,
+        QuotaKind::JobSecondsPerDay,
+        QuotaKind::ResultBytes,
+        QuotaKind::LogBytes,
+        QuotaKind::Schedules,
+        QuotaKind::ObjectStorageBytes,
+    ];
+
+    /// Quotas a new job consumes from, the concurrent jobs are checked when the job is pulled
+    pub const JOB: [QuotaKind; 4] = [
+        QuotaKind::JobSecondsPerDay,
+        QuotaKind::ResultBytes,
+        QuotaKind::LogBytes,
+        QuotaKind::ObjectStorageBytes,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaKind::ConcurrentJobs => "concurrent_jobs",
+            QuotaKind::JobSecondsPerDay => "job_seconds_per_day",
+            QuotaKind::ResultBytes => "result_bytes",
+            QuotaKind::LogBytes => "log_bytes",
+            QuotaKind::Schedules => "schedules",
+            QuotaKind::ObjectStorageBytes => "object_storage_bytes",
+        }
+    }
+
+    fn cache_ttl(&self) -> Duration {
+        match self {
+            QuotaKind::JobSecondsPerDay => Duration::from_secs(60),
+            QuotaKind::ResultBytes | QuotaKind::LogBytes => Duration::from_secs(5),
+            QuotaKind::ConcurrentJobs | QuotaKind::Schedules | QuotaKind::ObjectStorageBytes => {
+                Duration::ZERO
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct QuotaConsumption {
+    pub quota: QuotaKind,
+    pub used: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soft: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hard: Option<i64>,
+}
+
+pub enum QuotaStatus {
+    Ok,
+    /// the soft limit is reached, with the message of the warning
+    Warning(String),
+    /// the hard limit is reached, with the message of the rejection
+    Exceeded(String),
+}
+
+lazy_static::lazy_static! {
+    static ref QUOTAS_CACHE: Cache<String, (Option<WorkspaceQuotas>, Instant)> = Cache::new(1000);
+    static ref CONSUMPTION_CACHE: Cache<(String, QuotaKind), (i64, Instant)> = Cache::new(5000);
+}
+
+const QUOTAS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Quotas of the workspace, updates take up to a minute to apply on every server and worker
+pub async fn get_workspace_quotas(db: &DB, w_id: &str) -> Result<Option<WorkspaceQuotas>> {
+    if let Some((quotas, at)) = QUOTAS_CACHE.get(w_id) {
+        if at.elapsed() < QUOTAS_CACHE_TTL {
+            return Ok(quotas);
+        }
+    }
+    let quotas = sqlx::query_scalar!(
+        "SELECT quotas FROM workspace_quota WHERE workspace_id = $1",
+        w_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(serde_json::from_value::<WorkspaceQuotas>)
+    .transpose()
+    .map_err(|e| Error::BadConfig(format!("Invalid quotas for workspace {w_id}: {e}")))?;
+    QUOTAS_CACHE.insert(w_id.to_string(), (quotas.clone(), Instant::now()));
+    Ok(quotas)
+}
+
+pub fn invalidate_workspace_quotas(w_id: &str) {
+    QUOTAS_CACHE.remove(w_id);
+}
+
+/// Workspaces that reached the hard limit of their concurrent jobs quota, with the running jobs
+/// counted as in `get_quota_consumption`. Their jobs are left in the queue by the workers.
+pub const OVER_CONCURRENT_JOBS_QUOTA_QUERY: &str = "SELECT wq.workspace_id FROM workspace_quota wq
+    WHERE (wq.quotas->'concurrent_jobs'->>'hard')::BIGINT <= (
+        SELECT COUNT(*) FROM v2_job_queue rq JOIN v2_job rj ON rj.id = rq.id
+        WHERE rq.workspace_id = wq.workspace_id AND rq.running = true
+        AND rj.kind NOT IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow'))";
+
+pub async fn get_quota_consumption(db: &DB, w_id: &str, kind: QuotaKind) -> Result<i64> {
+    let key = (w_id.to_string(), kind);
+    if let Some((used, at)) = CONSUMPTION_CACHE.get(&key) {
+        if at.elapsed() < kind.cache_ttl() {
+            return Ok(used);
+        }
+    }
+    let used = match kind {
+        QuotaKind::ConcurrentJobs => sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM v2_job_queue q
+            JOIN v2_job j ON j.id = q.id
+            WHERE q.workspace_id = $1 AND q.running = true
+            AND j.kind NOT IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow')",
+            w_id
+        )
+        .fetch_one(db)
+        .await?
+        .unwrap_or(0),
+        QuotaKind::JobSecondsPerDay => sqlx::query_scalar!(
+            "SELECT (COALESCE(SUM(c.duration_ms), 0) / 1000)::BIGINT FROM v2_job_completed c
+            JOIN v2_job j ON j.id = c.id
+            WHERE c.workspace_id = $1 AND c.completed_at >= date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+            AND j.kind NOT IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow')",
+            w_id
+        )
+        .fetch_one(db)
+        .await?
+        .unwrap_or(0),
+        QuotaKind::ResultBytes => sqlx::query_scalar!(
+            "SELECT result_bytes FROM workspace_quota WHERE workspace_id = $1",
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .unwrap_or(0),
+        QuotaKind::LogBytes => sqlx::query_scalar!(
+            "SELECT log_bytes FROM workspace_quota WHERE workspace_id = $1",
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .unwrap_or(0),
+        QuotaKind::Schedules => sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM schedule WHERE workspace_id = $1",
+            w_id
+        )
+        .fetch_one(db)
+        .await?
+        .unwrap_or(0),
+        QuotaKind::ObjectStorageBytes => sqlx::query_scalar!(
+            "SELECT object_storage_bytes FROM workspace_quota WHERE workspace_id = $1",
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten()
+        .unwrap_or(0),
+    };
+    if !kind.cache_ttl().is_zero() {
+        CONSUMPTION_CACHE.insert(key, (used, Instant::now()));
+    }
+    Ok(used)
+}
+
+/// Status of the quota of the workspace against its current consumption
+pub async fn get_quota_status(
+    db: &DB,
+    w_id: &str,
+    quotas: &WorkspaceQuotas,
+    kind: QuotaKind,
+) -> Result<QuotaStatus> {
+    let Some(limit) = quotas.get(kind) else {
+        return Ok(QuotaStatus::Ok);
+    };
+    if limit.soft.is_none() && limit.hard.is_none() {
+        return Ok(QuotaStatus::Ok);
+    }
+    let used = get_quota_consumption(db, w_id, kind).await?;
+    Ok(limit.status(w_id, kind, used))
+}
+
+/// Consumption of every quota of the workspace, set or not
+pub async fn get_workspace_consumption(db: &DB, w_id: &str) -> Result<Vec<QuotaConsumption>> {
+    let quotas = get_workspace_quotas(db, w_id).await?.unwrap_or_default();
+    let mut consumption = vec![];
+    for kind in QuotaKind::ALL {
+        let limit = quotas.get(kind).cloned().unwrap_or_default();
+        consumption.push(QuotaConsumption {
+            quota: kind,
+            used: get_quota_consumption(db, w_id, kind).await?,
+            soft: limit.soft,
+            hard: limit.hard,
+        });
+    }
+    Ok(consumption)
+}
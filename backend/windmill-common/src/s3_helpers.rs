@@ -224,7 +224,7 @@ pub enum LargeFileStorage {
     S3AwsOidc(S3Storage),
     AzureWorkloadIdentity(AzureBlobStorage),
     GoogleCloudStorage(GoogleCloudStorage),
-    // TODO: Add a filesystem type here in the future if needed
+    FilesystemStorage(FilesystemStorage),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -248,11 +248,26 @@ pub struct GoogleCloudStorage {
     pub public_resource: Option<bool>,
 }
 
+/// Directory of the server under which the filesystem storages of the workspaces are stored
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FilesystemStorage {
+    pub root_path: String,
+}
+
+lazy_static::lazy_static! {
+    /// Filesystem storages are disabled unless this is set
+    pub static ref FILESYSTEM_STORAGE_ROOT: Option<std::path::PathBuf> = std::env::var("FILESYSTEM_STORAGE_ROOT")
+        .ok()
+        .filter(|root| !root.is_empty())
+        .map(std::path::PathBuf::from);
+}
+
 #[derive(Clone, Debug)]
 pub enum ObjectStoreResource {
     S3(S3Resource),
     Azure(AzureBlobResource),
     Gcs(GcsResource),
+    Filesystem(FilesystemResource),
 }
 
 impl ObjectStoreResource {
@@ -320,6 +335,33 @@ where
     serde_json::to_string(&v).map_err(serde::de::Error::custom)
 }
 
+#[derive(Debug, Clone)]
+pub struct FilesystemResource {
+    pub root_path: std::path::PathBuf,
+}
+
+impl FilesystemResource {
+    /// Resolves the root path of a filesystem storage inside `FILESYSTEM_STORAGE_ROOT`
+    pub fn from_storage(storage: &FilesystemStorage) -> crate::error::Result<Self> {
+        let Some(storage_root) = FILESYSTEM_STORAGE_ROOT.as_ref() else {
+            return Err(crate::error::Error::BadConfig(
+                "Filesystem storage is disabled, set FILESYSTEM_STORAGE_ROOT on the servers and workers to enable it".to_string(),
+            ));
+        };
+        let root_path = std::path::Path::new(storage.root_path.trim_start_matches('/'));
+        if root_path
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(crate::error::Error::BadConfig(format!(
+                "Invalid filesystem storage root path {}, it must be relative to FILESYSTEM_STORAGE_ROOT",
+                storage.root_path
+            )));
+        }
+        Ok(Self { root_path: storage_root.join(root_path) })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct GcsResource {
     pub bucket: String,
@@ -409,6 +451,9 @@ pub async fn build_object_store_client(
             build_azure_blob_client(&azure_blob_resource_ref)
         }
         ObjectStoreResource::Gcs(gcs_resource_ref) => build_gcs_client(&gcs_resource_ref).await,
+        ObjectStoreResource::Filesystem(filesystem_resource_ref) => {
+            build_filesystem_client(&filesystem_resource_ref)
+        }
     }
 }
 
@@ -644,6 +689,33 @@ async fn build_gcs_client(gcs_resource_ref: &GcsResource) -> error::Result<Arc<d
     return Ok(Arc::new(store));
 }
 
+#[cfg(feature = "parquet")]
+fn build_filesystem_client(
+    filesystem_resource_ref: &FilesystemResource,
+) -> error::Result<Arc<dyn ObjectStore>> {
+    std::fs::create_dir_all(&filesystem_resource_ref.root_path).map_err(|err| {
+        error::Error::internal_err(format!(
+            "Error creating filesystem storage directory {}: {}",
+            filesystem_resource_ref.root_path.display(),
+            err
+        ))
+    })?;
+
+    let store = object_store::local::LocalFileSystem::new_with_prefix(
+        &filesystem_resource_ref.root_path,
+    )
+    .map_err(|err| {
+        tracing::error!("Error building filesystem object store client: {:?}", err);
+        error::Error::internal_err(format!(
+            "Error building filesystem object store client: {}",
+            err.to_string()
+        ))
+    })?
+    .with_automatic_cleanup(true);
+
+    return Ok(Arc::new(store));
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "typ", content = "value")]
 pub enum ObjectStoreSettings {
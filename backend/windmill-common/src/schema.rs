@@ -362,7 +362,68 @@ pub struct SchemaValidator {
     pub rules: Vec<(String, Vec<SchemaValidationRule>)>,
 }
 
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SchemaFieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl SchemaFieldError {
+    fn new(field: &str, error: Error) -> Self {
+        let message = match error {
+            Error::ArgumentErr(message) => message,
+            e => e.to_string(),
+        };
+        Self { field: field.to_string(), message }
+    }
+}
+
 impl SchemaValidator {
+    /// Same checks as `validate` but keeps going after the first failure so that
+    /// callers can report every invalid field at once.
+    pub fn validate_all(&self, args: &HashMap<String, Box<RawValue>>) -> Vec<SchemaFieldError> {
+        let mut errors = vec![];
+
+        for key in &self.required {
+            if !args.contains_key(key) {
+                errors.push(SchemaFieldError::new(
+                    key,
+                    Error::ArgumentErr(format!("Argument {key} is required")),
+                ));
+            }
+        }
+
+        for (key, raw_val) in args {
+            let parsed_val = match Value::from_str(raw_val.get()) {
+                Ok(v) => v,
+                Err(e) => {
+                    errors.push(SchemaFieldError::new(
+                        key,
+                        Error::ArgumentErr(format!("Failed to parse `{key}` argument: {e}")),
+                    ));
+                    continue;
+                }
+            };
+            if let Err(e) = self.validate_field(key, &parsed_val, self.required.contains(key)) {
+                errors.push(SchemaFieldError::new(key, e));
+            }
+        }
+
+        errors
+    }
+
+    /// Validates a single value against the rules of the property `key`.
+    /// Keys that are not part of the schema are accepted as is.
+    pub fn validate_field(&self, key: &str, val: &Value, required: bool) -> Result<(), Error> {
+        if let Some((_, rules)) = self.rules.iter().find(|(k, _)| k == key) {
+            for rule in rules {
+                rule.apply_rule(key, val, required)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn validate(&self, args: &HashMap<String, Box<RawValue>>) -> Result<(), Error> {
         for key in &self.required {
             if !args.contains_key(key) {
@@ -638,4 +699,38 @@ mod tests {
             .validate(&value_to_rawvalue_map(args).unwrap())
             .expect("Validation should work for this");
     }
+
+    #[test]
+    fn test_validate_all_collects_field_errors() {
+        let schema = r#"{
+    "$schema": "https://json-schema.org/draft/2020-12/schema",
+    "properties": {
+        "name": { "type": "string" },
+        "age": { "type": "integer" },
+        "kind": { "type": "string", "enum": ["a", "b"] }
+    },
+    "required": ["name", "age"],
+    "type": "object"
+}"#;
+
+        let validator = SchemaValidator::from_schema(schema).unwrap();
+
+        let args = json!({ "age": "not a number", "kind": "c", "extra": 1 });
+        let mut errors = validator.validate_all(&value_to_rawvalue_map(args).unwrap());
+        errors.sort_by(|a, b| a.field.cmp(&b.field));
+
+        assert_eq!(
+            errors.iter().map(|e| e.field.as_str()).collect::<Vec<_>>(),
+            vec!["age", "kind", "name"]
+        );
+
+        let args = json!({ "name": "foo", "age": 3 });
+        assert!(validator
+            .validate_all(&value_to_rawvalue_map(args).unwrap())
+            .is_empty());
+
+        assert!(validator.validate_field("age", &json!(3), true).is_ok());
+        assert!(validator.validate_field("age", &json!("3"), true).is_err());
+        assert!(validator.validate_field("unknown", &json!("3"), true).is_ok());
+    }
 }
@@ -41,6 +41,10 @@ pub enum TriggerKind {
     Sqs,
     Postgres,
     Gcp,
+    Mysql,
+    Amqp,
+    Redis,
+    S3,
 }
 
 impl TriggerKind {
@@ -56,6 +60,10 @@ impl TriggerKind {
             TriggerKind::Sqs => "sqs".to_string(),
             TriggerKind::Postgres => "postgres".to_string(),
             TriggerKind::Gcp => "gcp".to_string(),
+            TriggerKind::Mysql => "mysql".to_string(),
+            TriggerKind::Amqp => "amqp".to_string(),
+            TriggerKind::Redis => "redis".to_string(),
+            TriggerKind::S3 => "s3".to_string(),
         }
     }
 }
@@ -73,6 +81,10 @@ impl fmt::Display for TriggerKind {
             TriggerKind::Sqs => "sqs",
             TriggerKind::Postgres => "postgres",
             TriggerKind::Gcp => "gcp",
+            TriggerKind::Mysql => "mysql",
+            TriggerKind::Amqp => "amqp",
+            TriggerKind::Redis => "redis",
+            TriggerKind::S3 => "s3",
         };
         write!(f, "{}", s)
     }
@@ -6,6 +6,7 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
+use crate::environments::get_job_environment;
 use crate::error;
 use crate::worker::Connection;
 use crate::{worker::WORKER_GROUP, BASE_URL, DB};
@@ -78,12 +79,17 @@ pub struct CreateVariable {
 
 pub async fn build_crypt(db: &DB, w_id: &str) -> crate::error::Result<MagicCrypt256> {
     let key = get_workspace_key(w_id, db).await?;
+    Ok(build_crypt_from_key(&key))
+}
+
+/// Crypt of a workspace key that is not stored, e.g. the key of an exported workspace
+pub fn build_crypt_from_key(key: &str) -> MagicCrypt256 {
     let crypt_key = if let Some(ref salt) = SECRET_SALT.as_ref() {
         format!("{}{}", key, salt)
     } else {
-        key
+        key.to_string()
     };
-    Ok(magic_crypt::new_magic_crypt!(crypt_key, 256))
+    magic_crypt::new_magic_crypt!(crypt_key, 256)
 }
 
 pub async fn build_crypt_with_key_suffix(
@@ -208,6 +214,16 @@ pub async fn get_reserved_variables(
 
     let custom_envs = get_cached_workspace_envs(conn, w_id).await;
 
+    let environment = match (conn, uuid::Uuid::parse_str(job_id)) {
+        (Connection::Sql(db), Ok(job_id)) => get_job_environment(db, w_id, &job_id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Could not resolve the environment of job {job_id}: {e:#}");
+                None
+            }),
+        _ => None,
+    };
+
     let joined_schedule_path = schedule_path
         .clone()
         .unwrap_or("manual".to_string())
@@ -339,6 +355,12 @@ pub async fn get_reserved_variables(
         description: "name of the worker group the job is running on".to_string(),
         is_custom: false,
     },
+    ContextualVariable {
+        name: "WM_ENVIRONMENT".to_string(),
+        value: environment.unwrap_or_else(|| "".to_string()),
+        description: "Environment the job runs in, its variables and resources use the values of that environment".to_string(),
+        is_custom: false,
+    },
 ].into_iter().chain(custom_envs.into_iter().map(|(name, value)| ContextualVariable {
     name,
     value,
@@ -320,15 +320,22 @@ pub async fn store_suspended_pull_query(wc: &WorkerConfig) {
     *l = query;
 }
 
+/// Jobs of the workspaces that reached the hard limit of their concurrent jobs quota are not
+/// pulled, except flows as their steps are counted instead
 pub fn make_pull_query(tags: &[String]) -> String {
     let query = format_pull_query(format!(
         "SELECT id
         FROM v2_job_queue
         WHERE running = false AND tag IN ({}) AND scheduled_for <= now()
+        AND (workspace_id NOT IN ({}) OR EXISTS (
+            SELECT 1 FROM v2_job WHERE v2_job.id = v2_job_queue.id
+            AND v2_job.kind IN ('flow', 'flowpreview', 'flownode', 'singlescriptflow')
+        ))
         ORDER BY priority DESC NULLS LAST, scheduled_for
         FOR UPDATE SKIP LOCKED
         LIMIT 1",
-        tags.iter().map(|x| format!("'{x}'")).join(", ")
+        tags.iter().map(|x| format!("'{x}'")).join(", "),
+        crate::quotas::OVER_CONCURRENT_JOBS_QUOTA_QUERY,
     ));
     query
 }
@@ -49,6 +49,19 @@ pub struct GitRepositorySettings {
     pub group_by_folder: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub settings: Option<GitSyncSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull: Option<GitSyncPullSettings>,
+}
+
+/// Pulling makes the workspace follow a branch of the repository: commits pushed to it are
+/// applied to the workspace
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GitSyncPullSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Defaults to the branch of the git repository resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -74,6 +87,125 @@ impl Default for GitSyncSettings {
 
 lazy_static::lazy_static! {
     pub static ref IS_PREMIUM_CACHE: Cache<String, bool> = Cache::new(5000);
+    static ref FORK_PARENT_CACHE: Cache<String, ExpiringForkParent> = Cache::new(5000);
+}
+
+const FORK_PARENT_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The parent of a fork, from which the fork resolves the items it does not hold itself
+#[derive(Clone, Debug)]
+pub struct ForkParent {
+    pub workspace_id: String,
+    /// the secret variables of the parent have no value in the fork
+    pub mask_secrets: bool,
+}
+
+#[derive(Clone)]
+struct ExpiringForkParent {
+    parent: Option<ForkParent>,
+    expires_at: std::time::Instant,
+}
+
+pub async fn get_fork_parent(
+    db: &crate::DB,
+    w_id: &str,
+) -> crate::error::Result<Option<ForkParent>> {
+    if let Some(cached) = FORK_PARENT_CACHE.get(w_id) {
+        if cached.expires_at > std::time::Instant::now() {
+            return Ok(cached.parent);
+        }
+    }
+    let parent = sqlx::query!(
+        "SELECT parent_workspace_id, secrets = 'mask' AS \"mask_secrets!\" FROM workspace_fork
+         WHERE workspace_id = $1",
+        w_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|r| ForkParent { workspace_id: r.parent_workspace_id, mask_secrets: r.mask_secrets });
+    FORK_PARENT_CACHE.insert(
+        w_id.to_string(),
+        ExpiringForkParent {
+            parent: parent.clone(),
+            expires_at: std::time::Instant::now() + FORK_PARENT_CACHE_TTL,
+        },
+    );
+    Ok(parent)
+}
+
+/// Items that a fork shares with its parent until they are copied into it
+#[derive(Clone, Copy, Debug)]
+pub enum ForkedItemKind {
+    Script,
+    Flow,
+    App,
+    Resource,
+    Variable,
+}
+
+async fn item_exists(
+    db: &crate::DB,
+    w_id: &str,
+    kind: ForkedItemKind,
+    path: &str,
+) -> crate::error::Result<bool> {
+    let exists = match kind {
+        ForkedItemKind::Script => sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM script WHERE path = $1 AND workspace_id = $2 AND archived = false AND deleted = false)",
+            path,
+            w_id
+        )
+        .fetch_one(db)
+        .await?,
+        ForkedItemKind::Flow => sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+            path,
+            w_id
+        )
+        .fetch_one(db)
+        .await?,
+        ForkedItemKind::App => sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM app WHERE path = $1 AND workspace_id = $2)",
+            path,
+            w_id
+        )
+        .fetch_one(db)
+        .await?,
+        ForkedItemKind::Resource => sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM resource WHERE path = $1 AND workspace_id = $2)",
+            path,
+            w_id
+        )
+        .fetch_one(db)
+        .await?,
+        ForkedItemKind::Variable => sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM variable WHERE path = $1 AND workspace_id = $2)",
+            path,
+            w_id
+        )
+        .fetch_one(db)
+        .await?,
+    };
+    Ok(exists.unwrap_or(false))
+}
+
+/// The workspace to read the item at `path` from: the workspace itself, or its parent when it
+/// is a fork that does not hold the item yet
+pub async fn fork_item_workspace(
+    db: &crate::DB,
+    w_id: &str,
+    kind: ForkedItemKind,
+    path: &str,
+) -> crate::error::Result<String> {
+    let Some(parent) = get_fork_parent(db, w_id).await? else {
+        return Ok(w_id.to_string());
+    };
+    if !item_exists(db, w_id, kind, path).await?
+        && item_exists(db, &parent.workspace_id, kind, path).await?
+    {
+        return Ok(parent.workspace_id);
+    }
+    Ok(w_id.to_string())
 }
 
 #[cfg(feature = "cloud")]
@@ -0,0 +1,53 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Scripts, flows, apps and resources are searchable by the embedding of their path, summary and
+//! description. They are queued in `workspace_embedding_queue` when deployed, and embedded from
+//! there by the servers running the embedding model.
+
+use windmill_common::error::Result;
+
+use crate::{DeployedObject, DB};
+
+/// Kind under which the object is embedded, if it is searchable by embedding
+pub fn embedding_kind(obj: &DeployedObject) -> Option<&'static str> {
+    let kind = match obj {
+        DeployedObject::Script { .. } => "script",
+        DeployedObject::Flow { .. } => "flow",
+        DeployedObject::App { .. } => "app",
+        DeployedObject::Resource { .. } => "resource",
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Queues the object to be embedded again, along with its previous path when it was renamed so
+/// that the embedding of the previous path is removed
+pub async fn queue_embedding_update(db: &DB, w_id: &str, obj: &DeployedObject) -> Result<()> {
+    let Some(kind) = embedding_kind(obj) else {
+        return Ok(());
+    };
+    // the parent path of an object updated in place is its own path, queuing it twice would
+    // make the upsert fail
+    let path = obj.get_path();
+    let parent_path = obj
+        .get_parent_path()
+        .filter(|parent_path| parent_path != &path);
+    let paths = std::iter::once(path).chain(parent_path).collect::<Vec<_>>();
+    sqlx::query!(
+        "INSERT INTO workspace_embedding_queue (workspace_id, kind, path)
+        SELECT $1, $2, unnest($3::text[])
+        ON CONFLICT (workspace_id, kind, path) DO UPDATE SET queued_at = now()",
+        w_id,
+        kind,
+        &paths
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -10,13 +10,49 @@ use sqlx::{Pool, Postgres};
 
 use windmill_common::scripts::ScriptHash;
 
+pub mod embeddings;
 #[cfg(feature = "private")]
 pub mod git_sync_ee;
 pub mod git_sync_oss;
+pub mod object_history;
 
-pub use git_sync_oss::handle_deployment_metadata;
 pub type DB = Pool<Postgres>;
 
+/// Called after every change of a deployed object: records it in the object history, queues it
+/// to be embedded again and pushes it to the git sync repositories
+pub async fn handle_deployment_metadata<'c>(
+    email: &str,
+    created_by: &str,
+    db: &DB,
+    w_id: &str,
+    obj: DeployedObject,
+    deployment_message: Option<String>,
+    skip_db_insert: bool,
+) -> windmill_common::error::Result<()> {
+    if let Err(err) = object_history::record_object_history(db, w_id, &obj, created_by).await {
+        tracing::error!(
+            "Could not record the history of {} in workspace {w_id}: {err:#}",
+            obj.get_path()
+        );
+    }
+    if let Err(err) = embeddings::queue_embedding_update(db, w_id, &obj).await {
+        tracing::error!(
+            "Could not queue the embedding of {} in workspace {w_id}: {err:#}",
+            obj.get_path()
+        );
+    }
+    git_sync_oss::handle_deployment_metadata(
+        email,
+        created_by,
+        db,
+        w_id,
+        obj,
+        deployment_message,
+        skip_db_insert,
+    )
+    .await
+}
+
 #[derive(Clone, Debug)]
 pub enum DeployedObject {
     Script { hash: ScriptHash, path: String, parent_path: Option<String> },
@@ -37,6 +73,10 @@ pub enum DeployedObject {
     MqttTrigger { path: String },
     SqsTrigger { path: String },
     GcpTrigger { path: String },
+    MysqlTrigger { path: String },
+    AmqpTrigger { path: String },
+    RedisTrigger { path: String },
+    S3Trigger { path: String },
     Settings { setting_type: String },
     Key { key_type: String },
 }
@@ -62,6 +102,10 @@ impl DeployedObject {
             DeployedObject::MqttTrigger { path } => path.to_owned(),
             DeployedObject::SqsTrigger { path } => path.to_owned(),
             DeployedObject::GcpTrigger { path } => path.to_owned(),
+            DeployedObject::MysqlTrigger { path } => path.to_owned(),
+            DeployedObject::AmqpTrigger { path } => path.to_owned(),
+            DeployedObject::RedisTrigger { path } => path.to_owned(),
+            DeployedObject::S3Trigger { path } => path.to_owned(),
             DeployedObject::Settings { .. } => "settings.yaml".to_string(),
             DeployedObject::Key { .. } => "encryption_key.yaml".to_string(),
         }
@@ -94,6 +138,10 @@ impl DeployedObject {
             DeployedObject::MqttTrigger { .. } => None,
             DeployedObject::SqsTrigger { .. } => None,
             DeployedObject::GcpTrigger { .. } => None,
+            DeployedObject::MysqlTrigger { .. } => None,
+            DeployedObject::AmqpTrigger { .. } => None,
+            DeployedObject::RedisTrigger { .. } => None,
+            DeployedObject::S3Trigger { .. } => None,
             DeployedObject::Settings { .. } => None,
             DeployedObject::Key { .. } => None,
         }
@@ -0,0 +1,243 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Every change of a deployed object is recorded in `object_history` with the value of the
+//! object after the change and before it. Scripts, flows and apps are versioned on their own,
+//! their history rows only make their changes visible next to the rest of the workspace.
+
+use serde_json::Value;
+use sqlx::{Executor, Postgres};
+use windmill_common::error::Result;
+
+use crate::{DeployedObject, DB};
+
+/// Columns updated by the listeners of the triggers and by the schedules runs, not by edits
+const VOLATILE_COLUMNS: &[&str] = &["server_id", "last_server_ping", "error"];
+
+/// Kinds stored as a single row that can be written back as is, with their key column
+pub fn history_table(kind: &str) -> Option<(&'static str, &'static str)> {
+    let table = match kind {
+        "resource" => ("resource", "path"),
+        "variable" => ("variable", "path"),
+        "schedule" => ("schedule", "path"),
+        "folder" => ("folder", "name"),
+        "resource_type" => ("resource_type", "name"),
+        "user" => ("usr", "email"),
+        "http_trigger" => ("http_trigger", "path"),
+        "websocket_trigger" => ("websocket_trigger", "path"),
+        "kafka_trigger" => ("kafka_trigger", "path"),
+        "nats_trigger" => ("nats_trigger", "path"),
+        "postgres_trigger" => ("postgres_trigger", "path"),
+        "mqtt_trigger" => ("mqtt_trigger", "path"),
+        "sqs_trigger" => ("sqs_trigger", "path"),
+        "gcp_trigger" => ("gcp_trigger", "path"),
+        "mysql_trigger" => ("mysql_trigger", "path"),
+        "amqp_trigger" => ("amqp_trigger", "path"),
+        "redis_trigger" => ("redis_trigger", "path"),
+        "s3_trigger" => ("s3_trigger", "path"),
+        _ => return None,
+    };
+    Some(table)
+}
+
+/// Kind and path under which the changes of the object are recorded, folders by their name. The
+/// encryption key is never recorded.
+pub fn history_kind(obj: &DeployedObject) -> Option<(&'static str, String)> {
+    let kind = match obj {
+        DeployedObject::Script { .. } => "script",
+        DeployedObject::Flow { .. } => "flow",
+        DeployedObject::App { .. } => "app",
+        DeployedObject::Folder { path } => {
+            let name = path.strip_prefix("f/").unwrap_or(path);
+            return Some(("folder", name.to_string()));
+        }
+        DeployedObject::Resource { .. } => "resource",
+        DeployedObject::Variable { .. } => "variable",
+        DeployedObject::Schedule { .. } => "schedule",
+        DeployedObject::ResourceType { .. } => "resource_type",
+        DeployedObject::User { email } => return Some(("user", email.clone())),
+        DeployedObject::Group { name } => return Some(("group", name.clone())),
+        DeployedObject::HttpTrigger { .. } => "http_trigger",
+        DeployedObject::WebsocketTrigger { .. } => "websocket_trigger",
+        DeployedObject::KafkaTrigger { .. } => "kafka_trigger",
+        DeployedObject::NatsTrigger { .. } => "nats_trigger",
+        DeployedObject::PostgresTrigger { .. } => "postgres_trigger",
+        DeployedObject::MqttTrigger { .. } => "mqtt_trigger",
+        DeployedObject::SqsTrigger { .. } => "sqs_trigger",
+        DeployedObject::GcpTrigger { .. } => "gcp_trigger",
+        DeployedObject::MysqlTrigger { .. } => "mysql_trigger",
+        DeployedObject::AmqpTrigger { .. } => "amqp_trigger",
+        DeployedObject::RedisTrigger { .. } => "redis_trigger",
+        DeployedObject::S3Trigger { .. } => "s3_trigger",
+        DeployedObject::Settings { .. } => return Some(("settings", "settings".to_string())),
+        DeployedObject::Key { .. } => return None,
+    };
+    Some((kind, obj.get_path()))
+}
+
+/// The object to notify once a recorded kind has been written back, `None` for the kinds that
+/// are not restored from their history
+pub fn deployed_object(kind: &str, path: &str) -> Option<DeployedObject> {
+    let path = path.to_string();
+    let obj = match kind {
+        "folder" => DeployedObject::Folder { path: format!("f/{path}") },
+        "resource" => DeployedObject::Resource { path, parent_path: None },
+        "variable" => DeployedObject::Variable { path, parent_path: None },
+        "schedule" => DeployedObject::Schedule { path },
+        "resource_type" => DeployedObject::ResourceType { path },
+        "user" => DeployedObject::User { email: path },
+        "group" => DeployedObject::Group { name: path },
+        "http_trigger" => DeployedObject::HttpTrigger { path },
+        "websocket_trigger" => DeployedObject::WebsocketTrigger { path },
+        "kafka_trigger" => DeployedObject::KafkaTrigger { path },
+        "nats_trigger" => DeployedObject::NatsTrigger { path },
+        "postgres_trigger" => DeployedObject::PostgresTrigger { path },
+        "mqtt_trigger" => DeployedObject::MqttTrigger { path },
+        "sqs_trigger" => DeployedObject::SqsTrigger { path },
+        "gcp_trigger" => DeployedObject::GcpTrigger { path },
+        "mysql_trigger" => DeployedObject::MysqlTrigger { path },
+        "amqp_trigger" => DeployedObject::AmqpTrigger { path },
+        "redis_trigger" => DeployedObject::RedisTrigger { path },
+        "s3_trigger" => DeployedObject::S3Trigger { path },
+        "settings" => DeployedObject::Settings { setting_type: "all".to_string() },
+        _ => return None,
+    };
+    Some(obj)
+}
+
+/// Current value of the object as recorded in its history, `None` if it does not exist
+pub async fn snapshot<'e, E: Executor<'e, Database = Postgres>>(
+    db: E,
+    w_id: &str,
+    kind: &str,
+    path: &str,
+) -> Result<Option<Value>> {
+    let value = match kind {
+        "script" => sqlx::query_scalar!(
+            "SELECT to_jsonb(s) - 'content' - 'lock' FROM script s
+            WHERE s.workspace_id = $1 AND s.path = $2 AND s.archived = false
+            ORDER BY s.created_at DESC LIMIT 1",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+        "flow" => sqlx::query_scalar!(
+            "SELECT to_jsonb(f) FROM flow f WHERE f.workspace_id = $1 AND f.path = $2",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+        "app" => sqlx::query_scalar!(
+            "SELECT to_jsonb(a) || jsonb_build_object('value', v.value) FROM app a
+            JOIN app_version v ON v.id = a.versions[array_upper(a.versions, 1)]
+            WHERE a.workspace_id = $1 AND a.path = $2",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+        "group" => sqlx::query_scalar!(
+            "SELECT to_jsonb(g) || jsonb_build_object('members', (
+                SELECT COALESCE(jsonb_agg(m.usr ORDER BY m.usr), '[]'::jsonb) FROM usr_to_group m
+                WHERE m.workspace_id = g.workspace_id AND m.group_ = g.name
+            )) FROM group_ g WHERE g.workspace_id = $1 AND g.name = $2",
+            w_id,
+            path
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+        "settings" => sqlx::query_scalar!(
+            "SELECT to_jsonb(s) FROM workspace_settings s WHERE s.workspace_id = $1",
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .flatten(),
+        kind => match history_table(kind) {
+            Some((table, key)) => {
+                sqlx::query_scalar::<_, Value>(&format!(
+                    "SELECT to_jsonb(t) FROM {table} t WHERE t.workspace_id = $1 AND t.{key} = $2"
+                ))
+                .bind(w_id)
+                .bind(path)
+                .fetch_optional(db)
+                .await?
+            }
+            None => None,
+        },
+    };
+
+    Ok(value.map(|mut value| {
+        if let Some(o) = value.as_object_mut() {
+            for column in VOLATILE_COLUMNS {
+                o.remove(*column);
+            }
+        }
+        value
+    }))
+}
+
+async fn record_path(db: &DB, w_id: &str, kind: &str, path: &str, edited_by: &str) -> Result<()> {
+    let value = snapshot(db, w_id, kind, path).await?;
+    let previous = sqlx::query_scalar!(
+        "SELECT value FROM object_history WHERE workspace_id = $1 AND kind = $2 AND path = $3
+        ORDER BY id DESC LIMIT 1",
+        w_id,
+        kind,
+        path
+    )
+    .fetch_optional(db)
+    .await?;
+
+    let action = match (&previous, &value) {
+        (Some(previous), value) if previous == value => return Ok(()),
+        (Some(None) | None, None) => return Ok(()),
+        // existing scripts, flows and apps have no baseline, their first change is one
+        (None, Some(_)) if matches!(kind, "script" | "flow" | "app") => "baseline",
+        (Some(None) | None, Some(_)) => "create",
+        (Some(Some(_)), Some(_)) => "update",
+        (Some(Some(_)), None) => "delete",
+    };
+    sqlx::query!(
+        "INSERT INTO object_history (workspace_id, kind, path, action, value, previous_value, edited_by)
+        VALUES ($1, $2, $3, $4::text::OBJECT_HISTORY_ACTION, $5, $6, $7)",
+        w_id,
+        kind,
+        path,
+        action,
+        value,
+        previous.flatten(),
+        edited_by
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Records the change of the object, and the removal of its previous path if it was renamed
+pub async fn record_object_history(
+    db: &DB,
+    w_id: &str,
+    obj: &DeployedObject,
+    edited_by: &str,
+) -> Result<()> {
+    let Some((kind, path)) = history_kind(obj) else {
+        return Ok(());
+    };
+    record_path(db, w_id, kind, &path, edited_by).await?;
+    if let Some(parent_path) = obj.get_parent_path().filter(|p| p != &path) {
+        record_path(db, w_id, kind, &parent_path, edited_by).await?;
+    }
+    Ok(())
+}
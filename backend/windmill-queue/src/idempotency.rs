@@ -0,0 +1,193 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+use windmill_common::error::{Error, Result};
+
+use crate::PushIsolationLevel;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Used when the workspace does not set its own idempotency window
+pub const DEFAULT_IDEMPOTENCY_WINDOW_S: i32 = 24 * 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyKey {
+    pub workspace_id: String,
+    /// who submitted the key and for what, keys are only deduplicated within the same scope so
+    /// that a key submitted by someone cannot be used to read the job of someone else, and a key
+    /// reused for another script or flow does not return the job of the first one
+    pub scope: String,
+    pub key: String,
+}
+
+impl IdempotencyKey {
+    pub fn new(workspace_id: &str, scope: String, key: &str) -> Result<Option<Self>> {
+        let key = key.trim();
+        if key.is_empty() {
+            return Ok(None);
+        }
+        if key.len() > 255 {
+            return Err(Error::BadRequest(
+                "Idempotency key must be at most 255 characters long".to_string(),
+            ));
+        }
+        Ok(Some(Self {
+            workspace_id: workspace_id.to_string(),
+            scope,
+            key: key.to_string(),
+        }))
+    }
+
+    /// Scope of the keys submitted by a user or with one of their tokens to run the script or
+    /// flow at `path`
+    pub fn user_scope(email: &str, is_flow: bool, path: &str) -> String {
+        let kind = if is_flow { "flow" } else { "script" };
+        format!("user/{email}/{kind}/{path}")
+    }
+
+    /// Claims the key for the window of the workspace, or returns the job already pushed with
+    /// it. A concurrent claim of the same key waits for the transaction of the first one to end.
+    async fn claim(&self, conn: &mut PgConnection) -> Result<Option<Uuid>> {
+        let claimed = sqlx::query_scalar!(
+            "INSERT INTO job_idempotency_key (workspace_id, scope, key, job_id, expires_at)
+            VALUES ($1, $2, $3, NULL, now() + make_interval(secs => COALESCE(
+                (SELECT idempotency_window_s FROM workspace_settings WHERE workspace_id = $1::VARCHAR), $4
+            )))
+            ON CONFLICT (workspace_id, scope, key)
+            DO UPDATE SET job_id = NULL, expires_at = EXCLUDED.expires_at
+            WHERE job_idempotency_key.expires_at <= now()
+            RETURNING key",
+            self.workspace_id,
+            self.scope,
+            self.key,
+            DEFAULT_IDEMPOTENCY_WINDOW_S,
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let job_id = sqlx::query_scalar!(
+            "SELECT job_id FROM job_idempotency_key
+            WHERE workspace_id = $1 AND scope = $2 AND key = $3 AND expires_at > now()",
+            self.workspace_id,
+            self.scope,
+            self.key,
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten();
+
+        match job_id {
+            Some(job_id) => Ok(Some(job_id)),
+            None => Err(Error::Generic(
+                axum::http::StatusCode::CONFLICT,
+                format!(
+                    "A request with idempotency key {} is already being processed",
+                    self.key
+                ),
+            )),
+        }
+    }
+
+    async fn set_job(&self, conn: &mut PgConnection, job_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            "UPDATE job_idempotency_key SET job_id = $4
+            WHERE workspace_id = $1 AND scope = $2 AND key = $3",
+            self.workspace_id,
+            self.scope,
+            self.key,
+            job_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+}
+
+pub enum IdempotentPush<'c> {
+    /// a job was already pushed with the key within the window
+    Existing(Uuid),
+    /// the job is to be pushed in this transaction and committed with `commit_idempotent_push`
+    Push(PushIsolationLevel<'c>),
+}
+
+/// Claims the idempotency key in the transaction the job is to be pushed in, so that the key is
+/// only bound if the job is pushed and a job is never pushed twice for the same key
+pub async fn begin_idempotent_push<'c>(
+    tx: PushIsolationLevel<'c>,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> Result<IdempotentPush<'c>> {
+    let Some(idempotency_key) = idempotency_key else {
+        return Ok(IdempotentPush::Push(tx));
+    };
+
+    let mut tx = tx.into_tx().await?;
+    match idempotency_key.claim(&mut *tx).await? {
+        Some(job_id) => {
+            tracing::info!(
+                "Idempotency key {} already used by {} in workspace {}, returning job {job_id}",
+                idempotency_key.key,
+                idempotency_key.scope,
+                idempotency_key.workspace_id
+            );
+            Ok(IdempotentPush::Existing(job_id))
+        }
+        None => Ok(IdempotentPush::Push(PushIsolationLevel::Transaction(tx))),
+    }
+}
+
+/// Binds the claimed key to the pushed job and commits the transaction of the push
+pub async fn commit_idempotent_push(
+    mut tx: Transaction<'_, Postgres>,
+    idempotency_key: Option<&IdempotencyKey>,
+    job_id: Uuid,
+) -> Result<()> {
+    if let Some(idempotency_key) = idempotency_key {
+        idempotency_key.set_job(&mut *tx, job_id).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_scope() {
+        let scope = IdempotencyKey::user_scope("alice@windmill.dev", false, "u/alice/a");
+        assert_eq!(scope, "user/alice@windmill.dev/script/u/alice/a");
+
+        for (email, is_flow, path) in [
+            ("alice@windmill.dev", false, "u/alice/b"),
+            ("alice@windmill.dev", true, "u/alice/a"),
+            ("bob@windmill.dev", false, "u/alice/a"),
+        ] {
+            assert_ne!(scope, IdempotencyKey::user_scope(email, is_flow, path));
+        }
+    }
+
+    #[test]
+    fn test_new_key() {
+        let scope = IdempotencyKey::user_scope("alice@windmill.dev", true, "f/flows/a");
+        let key = IdempotencyKey::new("test", scope.clone(), "  ").unwrap();
+        assert!(key.is_none());
+        assert!(IdempotencyKey::new("test", scope.clone(), &"k".repeat(256)).is_err());
+
+        let key = IdempotencyKey::new("test", scope, " order-1 ")
+            .unwrap()
+            .unwrap();
+        assert_eq!(key.key, "order-1");
+        assert_eq!(key.scope, "user/alice@windmill.dev/flow/f/flows/a");
+    }
+}
@@ -41,14 +41,15 @@ use windmill_common::{
     db::{Authed, UserDB},
     error::{self, to_anyhow, Error},
     flow_status::{
-        BranchAllStatus, FlowCleanupModule, FlowStatus, FlowStatusModule, FlowStatusModuleWParent,
-        Iterator as FlowIterator, JobResult, RestartedFrom, RetryStatus, MAX_RETRY_ATTEMPTS,
-        MAX_RETRY_INTERVAL,
+        AgentAction, BranchAllStatus, FlowCleanupModule, FlowStatus, FlowStatusModule,
+        FlowStatusModuleWParent, Iterator as FlowIterator, JobResult, RestartedFrom, RetryStatus,
+        MAX_RETRY_ATTEMPTS, MAX_RETRY_INTERVAL,
     },
     flows::{
         add_virtual_items_if_necessary, FlowModule, FlowModuleValue, FlowValue, InputTransform,
     },
     jobs::{get_payload_tag_from_prefixed_path, JobKind, JobPayload, QueuedJob, RawCode},
+    quotas::QuotaKind,
     schedule::Schedule,
     scripts::{get_full_hub_script_by_path, ScriptHash, ScriptLang},
     users::{SUPERADMIN_NOTIFICATION_EMAIL, SUPERADMIN_SECRET_EMAIL},
@@ -840,6 +841,17 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
                     "Could not update parent job `duration_ms` in workflow as code status: {}",
                     e,
                 ));
+
+                // resume the AI agent job suspended until its tool jobs complete
+                sqlx::query!(
+                    "UPDATE v2_job_queue q SET suspend = q.suspend - 1
+                    FROM v2_job j
+                    WHERE q.id = $1 AND j.id = q.id AND j.kind = 'aiagent'
+                        AND q.suspend_until IS NOT NULL",
+                    parent_job
+                )
+                .execute(&mut *tx)
+                .await?;
             }
         }
         // tracing::error!("Added completed job {:#?}", queued_job);
@@ -2030,7 +2042,11 @@ pub enum JobTriggerKind {
     Sqs,
     Postgres,
     Schedule,
-    Gcp
+    Gcp,
+    Mysql,
+    Amqp,
+    Redis,
+    S3,
 }
 
 
@@ -2413,6 +2429,9 @@ pub async fn pull(
                     sqlx::query!("UPDATE v2_job_queue SET tag = $1, running = false WHERE id = $2", tag, job.id).execute(db).await?;
                     continue;
                 }
+                if requeue_if_over_concurrent_jobs_quota(db, job).await? {
+                    continue;
+                }
             }
             return Ok(njob);
         };
@@ -2428,6 +2447,9 @@ pub async fn pull(
             return Ok(PulledJobResult { job: None, suspended });
         };
 
+        if requeue_if_over_concurrent_jobs_quota(db, &job).await? {
+            continue;
+        }
 
         let has_concurent_limit = job.concurrent_limit.is_some();
 
@@ -2623,6 +2645,37 @@ pub async fn pull(
     }
 }
 
+/// Sends the pulled job back to the queue for a few seconds if its workspace is already running as
+/// many jobs as its concurrent jobs quota allows. Flows are not held back as they only orchestrate
+/// their steps, which are.
+/// The pull query skips the workspaces over their concurrent jobs quota, this puts back in the
+/// queue the jobs pulled by several workers at the same time past the quota
+pub async fn requeue_if_over_concurrent_jobs_quota(
+    db: &Pool<Postgres>,
+    job: &PulledJob,
+) -> windmill_common::error::Result<bool> {
+    if job.is_flow()
+        || job.canceled_by.is_some()
+        || !crate::quotas::is_over_concurrent_jobs_quota(db, &job.workspace_id).await?
+    {
+        return Ok(false);
+    }
+    sqlx::query!(
+        "WITH ping AS (
+            UPDATE v2_job_runtime SET ping = null WHERE id = $1
+        )
+        UPDATE v2_job_queue SET
+            running = false,
+            started_at = null,
+            scheduled_for = now() + interval '3 seconds'
+        WHERE id = $1",
+        job.id,
+    )
+    .execute(db)
+    .await?;
+    Ok(true)
+}
+
 async fn pull_single_job_and_mark_as_running_no_concurrency_limit<'c>(
     db: &Pool<Postgres>,
     suspend_first: bool,
@@ -3230,7 +3283,7 @@ pub enum PushIsolationLevel<'c> {
 }
 
 impl<'c> PushIsolationLevel<'c> {
-    async fn into_tx(self) -> error::Result<Transaction<'c, Postgres>> {
+    pub(crate) async fn into_tx(self) -> error::Result<Transaction<'c, Postgres>> {
         match self {
             PushIsolationLevel::Isolated(db, authed) => Ok((db.begin(&authed).await?).into()),
             PushIsolationLevel::IsolatedRoot(db) => Ok(db.begin().await?),
@@ -3538,6 +3591,23 @@ pub async fn push<'c, 'd>(
         }
     }
 
+    // the steps of flows and the scheduled runs are checked too, a flow whose step is rejected
+    // fails and a schedule whose next run is rejected is disabled
+    if !matches!(
+        job_payload,
+        JobPayload::Dependencies { .. }
+            | JobPayload::FlowDependencies { .. }
+            | JobPayload::AppDependencies { .. }
+            | JobPayload::RawFlowDependencies { .. }
+            | JobPayload::RawScriptDependencies { .. }
+            | JobPayload::Noop
+    ) && email != "worker@windmill.dev"
+        && email != SUPERADMIN_SECRET_EMAIL
+        && email != SUPERADMIN_NOTIFICATION_EMAIL
+    {
+        crate::quotas::check_workspace_quotas(_db, workspace_id, &QuotaKind::JOB).await?;
+    }
+
     let mut preprocessed = None;
     let (
         script_hash,
@@ -3812,15 +3882,23 @@ pub async fn push<'c, 'd>(
 
             let flow_status: FlowStatus = match restarted_from {
                 Some(restarted_from_val) => {
-                    let (_, _, _, step_n, truncated_modules, user_states, cleanup_module) =
-                        restarted_flows_resolution(
-                            _db,
-                            workspace_id,
-                            restarted_from_val.flow_job_id,
-                            restarted_from_val.step_id.as_str(),
-                            restarted_from_val.branch_or_iteration_n,
-                        )
-                        .await?;
+                    let (
+                        _,
+                        _,
+                        _,
+                        step_n,
+                        truncated_modules,
+                        user_states,
+                        agent_actions,
+                        cleanup_module,
+                    ) = restarted_flows_resolution(
+                        _db,
+                        workspace_id,
+                        restarted_from_val.flow_job_id,
+                        restarted_from_val.step_id.as_str(),
+                        restarted_from_val.branch_or_iteration_n,
+                    )
+                    .await?;
                     FlowStatus {
                         step: step_n,
                         modules: truncated_modules,
@@ -3842,6 +3920,7 @@ pub async fn push<'c, 'd>(
                             branch_or_iteration_n: restarted_from_val.branch_or_iteration_n,
                         }),
                         user_states,
+                        agent_actions,
                         preprocessor_module: None,
                     }
                 }
@@ -4041,6 +4120,7 @@ pub async fn push<'c, 'd>(
                 step_n,
                 truncated_modules,
                 user_states,
+                agent_actions,
                 cleanup_module,
             ) = restarted_flows_resolution(
                 _db,
@@ -4071,6 +4151,7 @@ pub async fn push<'c, 'd>(
                     branch_or_iteration_n,
                 }),
                 user_states,
+                agent_actions,
                 preprocessor_module: None,
             };
             let value = flow_data.value();
@@ -4118,6 +4199,21 @@ pub async fn push<'c, 'd>(
             None,
             None,
         ),
+        JobPayload::AIAgent { path } => (
+            None,
+            Some(path),
+            None,
+            JobKind::AIAgent,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ),
         JobPayload::Identity => (
             None,
             None,
@@ -4222,7 +4318,10 @@ pub async fn push<'c, 'd>(
         let per_workspace = per_workspace_tag(&workspace_id).await;
 
         let default = || {
-            let ntag = if job_kind.is_flow() || job_kind == JobKind::Identity {
+            let ntag = if job_kind.is_flow()
+                || job_kind == JobKind::Identity
+                || job_kind == JobKind::AIAgent
+            {
                 "flow".to_string()
             } else if job_kind == JobKind::Dependencies
                 || job_kind == JobKind::FlowDependencies
@@ -4485,6 +4584,7 @@ pub async fn push<'c, 'd>(
             JobKind::FlowScript => "jobs.run.flow_script",
             JobKind::FlowNode => "jobs.run.flow_node",
             JobKind::AppScript => "jobs.run.app_script",
+            JobKind::AIAgent => "jobs.run.ai_agent",
         };
 
         let audit_author = if format!("u/{user}") != permissioned_as && user != permissioned_as {
@@ -4571,6 +4671,7 @@ async fn restarted_flows_resolution(
         i32,
         Vec<FlowStatusModule>,
         HashMap<String, serde_json::Value>,
+        HashMap<Uuid, Vec<AgentAction>>,
         FlowCleanupModule,
     ),
     Error,
@@ -4745,6 +4846,7 @@ async fn restarted_flows_resolution(
         step_n,
         truncated_modules,
         flow_status.user_states,
+        flow_status.agent_actions,
         flow_status.cleanup_module,
     ))
 }
@@ -13,4 +13,6 @@ pub mod jobs_oss;
 pub mod schedule;
 pub use jobs::*;
 pub mod flow_status;
+pub mod idempotency;
+pub mod quotas;
 pub mod tags;
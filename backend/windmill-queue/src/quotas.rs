@@ -0,0 +1,75 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use windmill_common::{
+    error::{Error, Result},
+    quotas::{
+        get_quota_consumption, get_quota_status, get_workspace_quotas, QuotaKind, QuotaStatus,
+    },
+    utils::report_critical_error,
+    DB,
+};
+
+/// Rejects what would consume from the quotas if the hard limit of one of them is reached, and
+/// warns the workspace of the soft limits reached
+pub async fn check_workspace_quotas(db: &DB, w_id: &str, kinds: &[QuotaKind]) -> Result<()> {
+    let Some(quotas) = get_workspace_quotas(db, w_id).await? else {
+        return Ok(());
+    };
+    for kind in kinds {
+        match get_quota_status(db, w_id, &quotas, *kind).await? {
+            QuotaStatus::Ok => {}
+            QuotaStatus::Warning(message) => send_quota_warning(db, w_id, *kind, message).await,
+            QuotaStatus::Exceeded(message) => return Err(Error::QuotaExceeded(message)),
+        }
+    }
+    Ok(())
+}
+
+/// Whether a job pulled by a worker has to go back to the queue, its workspace already running
+/// as many jobs as its concurrent jobs quota allows
+pub async fn is_over_concurrent_jobs_quota(db: &DB, w_id: &str) -> Result<bool> {
+    let Some(limit) = get_workspace_quotas(db, w_id)
+        .await?
+        .and_then(|quotas| quotas.concurrent_jobs)
+    else {
+        return Ok(false);
+    };
+    // the pulled job is already marked as running
+    let used = get_quota_consumption(db, w_id, QuotaKind::ConcurrentJobs).await? - 1;
+    match limit.status(w_id, QuotaKind::ConcurrentJobs, used) {
+        QuotaStatus::Ok => Ok(false),
+        QuotaStatus::Warning(message) => {
+            send_quota_warning(db, w_id, QuotaKind::ConcurrentJobs, message).await;
+            Ok(false)
+        }
+        QuotaStatus::Exceeded(message) => {
+            tracing::debug!("{message}");
+            Ok(true)
+        }
+    }
+}
+
+/// Sends the warning as a critical alert of the workspace, once a day per quota
+async fn send_quota_warning(db: &DB, w_id: &str, kind: QuotaKind, message: String) {
+    let first_today = sqlx::query_scalar!(
+        "INSERT INTO workspace_quota_warning (workspace_id, quota, day)
+        VALUES ($1, $2, (now() AT TIME ZONE 'UTC')::date)
+        ON CONFLICT DO NOTHING
+        RETURNING true",
+        w_id,
+        kind.as_str()
+    )
+    .fetch_optional(db)
+    .await;
+    match first_today {
+        Ok(Some(_)) => report_critical_error(message, db.clone(), Some(w_id), None).await,
+        Ok(None) => {}
+        Err(e) => tracing::error!("Could not record quota warning for workspace {w_id}: {e:#}"),
+    }
+}
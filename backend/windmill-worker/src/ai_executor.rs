@@ -0,0 +1,896 @@
+use std::{collections::HashMap, time::Duration};
+
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, value::RawValue, Value};
+use sqlx::types::Json;
+use uuid::Uuid;
+use windmill_common::{
+    ai_tools::{
+        convert_schema_to_schema_type, schema_resource_types, tool_args_to_runnable_args,
+        tool_description, tool_input_schema, transform_path, transform_schema_for_resources,
+        ResourceInfo, ResourceType, SchemaType,
+    },
+    auth::JobPerms,
+    client::AuthedClient,
+    error::{Error, Result},
+    flow_status::AgentAction,
+    flows::{AIAgentConfig, AgentTool, AgentToolKind},
+    jobs::{get_payload_tag_from_prefixed_path, AI_AGENT_ARG},
+    scripts::{get_full_hub_script_by_path, Schema},
+    utils::{StripPath, HTTP_CLIENT},
+    worker::{to_raw_value, Connection},
+    DB,
+};
+use windmill_queue::{
+    append_logs, cancel_job, push, CanceledBy, MiniPulledJob, PushArgs, PushIsolationLevel,
+};
+
+use crate::{common::OccupancyMetrics, handle_child::run_future_with_polling_update_job_poller};
+
+const DEFAULT_MAX_ITERATIONS: u32 = 10;
+const DEFAULT_MAX_TOOL_CALLS: u32 = 20;
+const DEFAULT_TOOL_TIMEOUT_S: u32 = 300;
+
+#[derive(Deserialize)]
+struct AIAgentArgs {
+    provider: String,
+    resource: String,
+    model: String,
+    #[serde(default)]
+    system_prompt: Option<String>,
+    user_message: String,
+}
+
+struct AgentToolDef {
+    tool: AgentTool,
+    description: String,
+    schema: SchemaType,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct ToolCall {
+    id: String,
+    function: ToolCallFunction,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct ToolCallFunction {
+    name: String,
+    #[serde(default)]
+    arguments: String,
+}
+
+/// Conversation of an agent job suspended while its tool jobs run, restored when it is pulled
+/// again
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct AgentState {
+    messages: Vec<Value>,
+    iteration: u32,
+    tool_calls_count: u32,
+    tool_calls: Vec<PendingToolCall>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PendingToolCall {
+    id: String,
+    answer: ToolCallAnswer,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum ToolCallAnswer {
+    Content(String),
+    Job(Uuid),
+}
+
+pub async fn handle_ai_agent_job(
+    job: &MiniPulledJob,
+    db: &DB,
+    client: &AuthedClient,
+    conn: &Connection,
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    worker_name: &str,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> Result<Option<Box<RawValue>>> {
+    let job_args = job
+        .args
+        .as_ref()
+        .ok_or_else(|| Error::BadRequest("Missing arguments for AI agent step".to_string()))?;
+
+    let config = job_args
+        .get(AI_AGENT_ARG)
+        .map(|x| serde_json::from_str::<AIAgentConfig>(x.get()))
+        .transpose()
+        .map_err(|e| Error::BadRequest(format!("Invalid AI agent configuration: {e}")))?
+        .ok_or_else(|| Error::BadRequest("Missing AI agent configuration".to_string()))?;
+
+    let args = serde_json::from_value::<AIAgentArgs>(
+        serde_json::to_value(
+            job_args
+                .iter()
+                .filter(|(k, _)| k.as_str() != AI_AGENT_ARG)
+                .collect::<HashMap<_, _>>(),
+        )
+        .map_err(|e| Error::internal_err(e.to_string()))?,
+    )
+    .map_err(|e| Error::BadRequest(format!("Invalid AI agent inputs: {e}")))?;
+
+    let resource_path = args
+        .resource
+        .strip_prefix("$res:")
+        .unwrap_or(&args.resource)
+        .to_string();
+
+    let tools = build_tools(&config.tools, db, client, &job.workspace_id).await?;
+
+    let state = sqlx::query_scalar!(
+        "SELECT state AS \"state: Json<AgentState>\" FROM ai_agent_state WHERE id = $1",
+        job.id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|x| x.0);
+
+    let result_f = run_agent(
+        job,
+        db,
+        client,
+        conn,
+        &args,
+        &resource_path,
+        &config,
+        &tools,
+        state,
+    );
+
+    let r = run_future_with_polling_update_job_poller(
+        job.id,
+        job.timeout,
+        conn,
+        mem_peak,
+        canceled_by,
+        result_f,
+        worker_name,
+        &job.workspace_id,
+        &mut Some(occupancy_metrics),
+        Box::pin(stream::once(async { 0 })),
+    )
+    .await;
+
+    // the tool jobs still running when the agent is canceled, times out or fails are not waited
+    // for anymore
+    if r.is_err() {
+        if let Err(e) = cancel_tool_jobs(db, job).await {
+            tracing::error!(
+                "Could not cancel the tool jobs of AI agent job {}: {e}",
+                job.id
+            );
+        }
+    }
+
+    r
+}
+
+async fn cancel_tool_jobs(db: &DB, job: &MiniPulledJob) -> Result<()> {
+    let tool_jobs = sqlx::query_scalar!(
+        "SELECT v2_job_queue.id FROM v2_job_queue INNER JOIN v2_job USING (id)
+         WHERE v2_job.parent_job = $1 AND v2_job.workspace_id = $2",
+        job.id,
+        job.workspace_id
+    )
+    .fetch_all(db)
+    .await?;
+    for tool_job in tool_jobs {
+        cancel_tool_job(db, &job.workspace_id, tool_job, "the AI agent job ended").await?;
+    }
+    Ok(())
+}
+
+async fn cancel_tool_job(db: &DB, w_id: &str, job_id: Uuid, reason: &str) -> Result<()> {
+    let (tx, _) = cancel_job(
+        "ai_agent",
+        Some(reason.to_string()),
+        job_id,
+        w_id,
+        db.begin().await?,
+        db,
+        false,
+        false,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn run_agent(
+    job: &MiniPulledJob,
+    db: &DB,
+    client: &AuthedClient,
+    conn: &Connection,
+    args: &AIAgentArgs,
+    resource_path: &str,
+    config: &AIAgentConfig,
+    tools: &HashMap<String, AgentToolDef>,
+    state: Option<AgentState>,
+) -> Result<Option<Box<RawValue>>> {
+    let max_iterations = config.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS);
+    let max_tool_calls = config.max_tool_calls.unwrap_or(DEFAULT_MAX_TOOL_CALLS);
+
+    let (mut messages, first_iteration, mut tool_calls_count) = match state {
+        Some(state) => {
+            let mut messages = state.messages;
+            for tool_call in state.tool_calls {
+                let content = tool_call_content(job, db, config, tool_call.answer).await?;
+                messages.push(tool_message(&tool_call.id, content));
+            }
+            (messages, state.iteration + 1, state.tool_calls_count)
+        }
+        None => (initial_messages(args), 0, 0),
+    };
+
+    let tools_defs = tools
+        .iter()
+        .map(|(name, def)| {
+            json!({
+                "type": "function",
+                "function": {
+                    "name": name,
+                    "description": def.description,
+                    "parameters": tool_input_schema(def.schema.clone(), &def.tool.path),
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for iteration in first_iteration..max_iterations {
+        let mut body = json!({ "model": args.model, "messages": messages });
+        if !tools_defs.is_empty() && tool_calls_count < max_tool_calls {
+            body["tools"] = json!(tools_defs);
+        }
+        if let Some(schema) = config.output_schema.as_ref() {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": "output", "schema": schema, "strict": false }
+            });
+        }
+
+        append_logs(
+            &job.id,
+            &job.workspace_id,
+            format!(
+                "\nCalling model {} (iteration {})",
+                args.model,
+                iteration + 1
+            ),
+            conn,
+        )
+        .await;
+
+        let response = client
+            .ai_proxy_request(&args.provider, resource_path, "chat/completions", &body)
+            .await?;
+
+        let message = response
+            .get("choices")
+            .and_then(|x| x.get(0))
+            .and_then(|x| x.get("message"))
+            .cloned()
+            .ok_or_else(|| {
+                Error::AIError(format!("Unexpected response from the model: {response}"))
+            })?;
+        messages.push(message.clone());
+
+        let tool_calls = match parse_model_message(&message, config.output_schema.is_some())? {
+            ModelTurn::Answer(output) => {
+                return Ok(Some(to_raw_value(
+                    &json!({ "output": output, "messages": messages }),
+                )));
+            }
+            ModelTurn::ToolCalls(tool_calls) => tool_calls,
+        };
+
+        let planned = plan_tool_calls(tool_calls, tools, &mut tool_calls_count, max_tool_calls);
+        if planned
+            .iter()
+            .any(|x| matches!(x, PlannedToolCall::Run { .. }))
+        {
+            suspend_on_tool_jobs(
+                job,
+                db,
+                conn,
+                config,
+                tools,
+                planned,
+                AgentState { messages, iteration, tool_calls_count, tool_calls: vec![] },
+            )
+            .await?;
+            return Ok(None);
+        }
+        for planned in planned {
+            if let PlannedToolCall::Answer { id, content } = planned {
+                messages.push(tool_message(&id, content));
+            }
+        }
+    }
+
+    Err(Error::ExecutionErr(format!(
+        "AI agent reached the maximum number of iterations ({max_iterations}) without a final answer"
+    )))
+}
+
+fn initial_messages(args: &AIAgentArgs) -> Vec<Value> {
+    let mut messages = vec![];
+    if let Some(system_prompt) = args.system_prompt.as_ref().filter(|x| !x.is_empty()) {
+        messages.push(json!({ "role": "system", "content": system_prompt }));
+    }
+    messages.push(json!({ "role": "user", "content": args.user_message }));
+    messages
+}
+
+fn tool_message(tool_call_id: &str, content: String) -> Value {
+    json!({ "role": "tool", "tool_call_id": tool_call_id, "content": content })
+}
+
+#[derive(Debug, PartialEq)]
+enum ModelTurn {
+    Answer(Value),
+    ToolCalls(Vec<ToolCall>),
+}
+
+/// The final answer of the model, parsed against the output schema when there is one, or the
+/// tools it asks to call
+fn parse_model_message(message: &Value, has_output_schema: bool) -> Result<ModelTurn> {
+    let tool_calls = message
+        .get("tool_calls")
+        .cloned()
+        .map(serde_json::from_value::<Vec<ToolCall>>)
+        .transpose()
+        .map_err(|e| Error::AIError(format!("Invalid tool calls from the model: {e}")))?
+        .unwrap_or_default();
+    if !tool_calls.is_empty() {
+        return Ok(ModelTurn::ToolCalls(tool_calls));
+    }
+
+    let content = message
+        .get("content")
+        .and_then(|x| x.as_str())
+        .unwrap_or_default();
+    let output = if has_output_schema {
+        serde_json::from_str::<Value>(content).map_err(|e| {
+            Error::AIError(format!(
+                "Output of the model does not match the output schema: {e}"
+            ))
+        })?
+    } else {
+        json!(content)
+    };
+    Ok(ModelTurn::Answer(output))
+}
+
+#[derive(Debug, PartialEq)]
+enum PlannedToolCall {
+    Run { call: ToolCall, args: serde_json::Map<String, Value> },
+    Answer { id: String, content: String },
+}
+
+/// Tool calls run as jobs and the ones answered right away, every tool call of the model must get
+/// an answer
+fn plan_tool_calls<T>(
+    tool_calls: Vec<ToolCall>,
+    tools: &HashMap<String, T>,
+    tool_calls_count: &mut u32,
+    max_tool_calls: u32,
+) -> Vec<PlannedToolCall> {
+    tool_calls
+        .into_iter()
+        .map(|call| {
+            let answer = |content: String| PlannedToolCall::Answer { id: call.id.clone(), content };
+            if *tool_calls_count >= max_tool_calls {
+                return answer(
+                    "Tool calls limit reached, answer without calling any more tools".to_string(),
+                );
+            }
+            if !tools.contains_key(&call.function.name) {
+                return answer(format!("Unknown tool: {}", call.function.name));
+            }
+            let args = if call.function.arguments.trim().is_empty() {
+                serde_json::Map::new()
+            } else {
+                match serde_json::from_str(&call.function.arguments) {
+                    Ok(args) => args,
+                    Err(e) => return answer(format!("Invalid arguments for the tool: {e}")),
+                }
+            };
+            *tool_calls_count += 1;
+            PlannedToolCall::Run { call, args }
+        })
+        .collect()
+}
+
+fn tool_kind_str(kind: &AgentToolKind) -> &'static str {
+    match kind {
+        AgentToolKind::Script => "script",
+        AgentToolKind::Flow => "flow",
+    }
+}
+
+async fn build_tools(
+    agent_tools: &[AgentTool],
+    db: &DB,
+    client: &AuthedClient,
+    w_id: &str,
+) -> Result<HashMap<String, AgentToolDef>> {
+    let mut schemas = vec![];
+    for tool in agent_tools {
+        let (summary, description, schema) = get_tool_runnable(tool, db, w_id).await?;
+        let kind = tool_kind_str(&tool.kind);
+        let description = tool.description.clone().unwrap_or_else(|| {
+            tool_description(
+                kind,
+                summary.as_deref().unwrap_or_default(),
+                description.as_deref().unwrap_or_default(),
+                None,
+            )
+        });
+        schemas.push((
+            transform_path(&tool.path, kind),
+            tool.clone(),
+            description,
+            convert_schema_to_schema_type(schema),
+        ));
+    }
+
+    let resource_types_needed = schemas
+        .iter()
+        .flat_map(|(_, _, _, schema)| schema_resource_types(schema))
+        .collect::<Vec<_>>();
+    let (resources_cache, resources_types) = if resource_types_needed.is_empty() {
+        (HashMap::new(), vec![])
+    } else {
+        list_resources(client, &resource_types_needed).await
+    };
+
+    Ok(schemas
+        .into_iter()
+        .map(|(name, tool, description, schema)| {
+            let schema =
+                transform_schema_for_resources(&schema, &resources_cache, &resources_types);
+            (name, AgentToolDef { tool, description, schema })
+        })
+        .collect())
+}
+
+async fn get_tool_runnable(
+    tool: &AgentTool,
+    db: &DB,
+    w_id: &str,
+) -> Result<(Option<String>, Option<String>, Option<Schema>)> {
+    match tool.kind {
+        AgentToolKind::Script if tool.path.starts_with("hub/") => {
+            let res =
+                get_full_hub_script_by_path(StripPath(tool.path.clone()), &HTTP_CLIENT, Some(db))
+                    .await?;
+            let schema = serde_json::from_str::<Schema>(res.schema.get()).ok();
+            Ok((res.summary, None, schema))
+        }
+        AgentToolKind::Script => sqlx::query_as!(
+            ToolRunnable,
+            "SELECT summary, description, schema AS \"schema: _\" FROM script
+             WHERE path = $1 AND workspace_id = $2 AND archived = false AND deleted = false
+             ORDER BY created_at DESC LIMIT 1",
+            tool.path,
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|x| (Some(x.summary), Some(x.description), x.schema))
+        .ok_or_else(|| Error::NotFound(format!("Tool script not found: {}", tool.path))),
+        AgentToolKind::Flow => sqlx::query_as!(
+            ToolRunnable,
+            "SELECT summary, description, schema AS \"schema: _\" FROM flow
+             WHERE path = $1 AND workspace_id = $2",
+            tool.path,
+            w_id
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|x| (Some(x.summary), Some(x.description), x.schema))
+        .ok_or_else(|| Error::NotFound(format!("Tool flow not found: {}", tool.path))),
+    }
+}
+
+struct ToolRunnable {
+    summary: String,
+    description: String,
+    schema: Option<Schema>,
+}
+
+/// Resources of the given types and the resource types visible with the permissions of the job
+async fn list_resources(
+    client: &AuthedClient,
+    resource_types: &[String],
+) -> (HashMap<String, Vec<ResourceInfo>>, Vec<ResourceType>) {
+    let base_url = format!(
+        "{}/api/w/{}/resources",
+        client.base_internal_url, client.workspace
+    );
+    let resources = match client
+        .get(
+            &format!("{base_url}/list"),
+            vec![("resource_type", resource_types.join(","))],
+        )
+        .await
+    {
+        Ok(response) => response
+            .json::<Vec<ResourceInfo>>()
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to decode resources for AI agent tools: {e}");
+                vec![]
+            }),
+        Err(e) => {
+            tracing::error!("Failed to list resources for AI agent tools: {e}");
+            vec![]
+        }
+    };
+    let resources_types = match client.get(&format!("{base_url}/type/list"), vec![]).await {
+        Ok(response) => response
+            .json::<Vec<ResourceType>>()
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to decode resource types for AI agent tools: {e}");
+                vec![]
+            }),
+        Err(e) => {
+            tracing::error!("Failed to list resource types for AI agent tools: {e}");
+            vec![]
+        }
+    };
+
+    let mut resources_cache: HashMap<String, Vec<ResourceInfo>> = resource_types
+        .iter()
+        .map(|rt| (rt.clone(), vec![]))
+        .collect();
+    for resource in resources {
+        if let Some(cache) = resources_cache.get_mut(&resource.resource_type) {
+            cache.push(resource);
+        }
+    }
+    (resources_cache, resources_types)
+}
+
+/// Runs the tools as child jobs of the agent job, records them in the status of the parent flow
+/// and suspends the agent job until they complete, in a single transaction so that no tool job
+/// completes before the agent is suspended
+async fn suspend_on_tool_jobs(
+    job: &MiniPulledJob,
+    db: &DB,
+    conn: &Connection,
+    config: &AIAgentConfig,
+    tools: &HashMap<String, AgentToolDef>,
+    planned: Vec<PlannedToolCall>,
+    mut state: AgentState,
+) -> Result<()> {
+    let root_job = job.flow_innermost_root_job.or(job.parent_job);
+    let job_perms = if let Some(root_job) = root_job {
+        sqlx::query_as!(
+            JobPerms,
+            "SELECT email, username, is_admin, is_operator, groups, folders FROM job_perms WHERE job_id = $1 AND workspace_id = $2",
+            root_job,
+            job.workspace_id,
+        )
+        .fetch_optional(db)
+        .await?
+        .map(|x| x.into())
+    } else {
+        None
+    };
+
+    let mut tx = db.begin().await?;
+    let mut actions = vec![];
+    for planned in planned {
+        let (id, answer) = match planned {
+            PlannedToolCall::Answer { id, content } => (id, ToolCallAnswer::Content(content)),
+            PlannedToolCall::Run { call, args } => {
+                let def = &tools[&call.function.name];
+                let args = tool_args_to_runnable_args(args, &Some(def.schema.clone()));
+                let (payload, tag, on_behalf_of) = get_payload_tag_from_prefixed_path(
+                    &format!("{}/{}", tool_kind_str(&def.tool.kind), def.tool.path),
+                    db,
+                    &job.workspace_id,
+                )
+                .await?;
+                let (email, permissioned_as) = if let Some(on_behalf_of) = on_behalf_of.as_ref() {
+                    (&on_behalf_of.email, on_behalf_of.permissioned_as.clone())
+                } else {
+                    (&job.permissioned_as_email, job.permissioned_as.clone())
+                };
+
+                let (uuid, ntx) = push(
+                    db,
+                    PushIsolationLevel::Transaction(tx),
+                    &job.workspace_id,
+                    payload,
+                    PushArgs::from(&args),
+                    &job.created_by,
+                    email,
+                    permissioned_as,
+                    Some(&format!("job-span-{}", root_job.unwrap_or(job.id))),
+                    None,
+                    None,
+                    Some(job.id),
+                    root_job,
+                    None,
+                    false,
+                    false,
+                    None,
+                    job.visible_to_owner,
+                    tag,
+                    None,
+                    None,
+                    job.priority,
+                    job_perms.as_ref(),
+                )
+                .await?;
+                tx = ntx;
+
+                let action = AgentAction {
+                    job_id: uuid,
+                    tool: call.function.name,
+                    kind: def.tool.kind.clone(),
+                    path: def.tool.path.clone(),
+                };
+                if let Some(parent_job) = job.parent_job {
+                    sqlx::query!(
+                        "UPDATE v2_job_status SET flow_status = flow_status || jsonb_build_object(
+                            'agent_actions',
+                            COALESCE(flow_status->'agent_actions', '{}'::jsonb) || jsonb_build_object(
+                                $2::TEXT,
+                                COALESCE(flow_status->'agent_actions'->$2::TEXT, '[]'::jsonb) || jsonb_build_array($3::jsonb)
+                            )
+                        )
+                        WHERE id = $1",
+                        parent_job,
+                        job.id.to_string(),
+                        Json(&action) as Json<&AgentAction>,
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                actions.push(action);
+                (call.id, ToolCallAnswer::Job(uuid))
+            }
+        };
+        state.tool_calls.push(PendingToolCall { id, answer });
+    }
+
+    sqlx::query!(
+        "INSERT INTO ai_agent_state (id, state) VALUES ($1, $2)
+         ON CONFLICT (id) DO UPDATE SET state = EXCLUDED.state",
+        job.id,
+        Json(&state) as Json<&AgentState>,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // the job is pulled again by the suspended pull query once every tool job completed or when
+    // the tool timeout is reached
+    let timeout = config.tool_timeout.unwrap_or(DEFAULT_TOOL_TIMEOUT_S);
+    sqlx::query!(
+        "UPDATE v2_job_queue SET suspend = $2, suspend_until = now() + $3 WHERE id = $1",
+        job.id,
+        actions.len() as i32,
+        Duration::from_secs(timeout as u64) as Duration,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    for action in actions {
+        append_logs(
+            &job.id,
+            &job.workspace_id,
+            format!(
+                "\nCalling tool {} ({} {}) in job {}",
+                action.tool,
+                tool_kind_str(&action.kind),
+                action.path,
+                action.job_id
+            ),
+            conn,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// The answer passed back to the model for a tool call, tool jobs that did not complete by the
+/// time the agent is resumed reached the tool timeout and are canceled
+async fn tool_call_content(
+    job: &MiniPulledJob,
+    db: &DB,
+    config: &AIAgentConfig,
+    answer: ToolCallAnswer,
+) -> Result<String> {
+    let tool_job = match answer {
+        ToolCallAnswer::Content(content) => return Ok(content),
+        ToolCallAnswer::Job(tool_job) => tool_job,
+    };
+    let completed = sqlx::query!(
+        "SELECT result AS \"result: Json<Box<RawValue>>\", status = 'success' AS \"success!\"
+         FROM v2_job_completed WHERE id = $1 AND workspace_id = $2",
+        tool_job,
+        job.workspace_id
+    )
+    .fetch_optional(db)
+    .await?;
+    if let Some(completed) = completed {
+        return Ok(tool_result_content(
+            completed.result.as_ref().map(|x| x.0.get()),
+            completed.success,
+        ));
+    }
+
+    let timeout = config.tool_timeout.unwrap_or(DEFAULT_TOOL_TIMEOUT_S);
+    cancel_tool_job(
+        db,
+        &job.workspace_id,
+        tool_job,
+        &format!("the tool did not complete within {timeout}s"),
+    )
+    .await?;
+    Ok(format!(
+        "Tool failed with error: it did not complete within {timeout}s and was canceled"
+    ))
+}
+
+fn tool_result_content(result: Option<&str>, success: bool) -> String {
+    let result = result.unwrap_or("null");
+    if success {
+        result.to_string()
+    } else {
+        format!("Tool failed with error: {result}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_call(id: &str, name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            function: ToolCallFunction { name: name.to_string(), arguments: arguments.to_string() },
+        }
+    }
+
+    #[test]
+    fn test_parse_model_message() {
+        let message = json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": "s_f_tools_search", "arguments": "{\"query\":\"x\"}" }
+            }]
+        });
+        assert_eq!(
+            parse_model_message(&message, false).unwrap(),
+            ModelTurn::ToolCalls(vec![tool_call(
+                "call_1",
+                "s_f_tools_search",
+                "{\"query\":\"x\"}"
+            )])
+        );
+
+        let message =
+            json!({ "role": "assistant", "content": "{\"answer\":42}", "tool_calls": [] });
+        assert_eq!(
+            parse_model_message(&message, false).unwrap(),
+            ModelTurn::Answer(json!("{\"answer\":42}"))
+        );
+        assert_eq!(
+            parse_model_message(&message, true).unwrap(),
+            ModelTurn::Answer(json!({ "answer": 42 }))
+        );
+
+        let message = json!({ "role": "assistant", "content": "not json" });
+        assert!(parse_model_message(&message, true).is_err());
+    }
+
+    #[test]
+    fn test_plan_tool_calls() {
+        let tools = HashMap::from([("s_f_tools_search".to_string(), ())]);
+        let mut tool_calls_count = 0;
+        let planned = plan_tool_calls(
+            vec![
+                tool_call("call_1", "s_f_tools_search", ""),
+                tool_call("call_2", "s_f_tools_unknown", "{}"),
+                tool_call("call_3", "s_f_tools_search", "not json"),
+                tool_call("call_4", "s_f_tools_search", "{\"query\":\"x\"}"),
+                tool_call("call_5", "s_f_tools_search", "{}"),
+            ],
+            &tools,
+            &mut tool_calls_count,
+            2,
+        );
+        assert_eq!(tool_calls_count, 2);
+        assert_eq!(planned.len(), 5);
+
+        assert!(matches!(
+            &planned[0],
+            PlannedToolCall::Run { call, args } if call.id == "call_1" && args.is_empty()
+        ));
+        assert_eq!(
+            planned[1],
+            PlannedToolCall::Answer {
+                id: "call_2".to_string(),
+                content: "Unknown tool: s_f_tools_unknown".to_string()
+            }
+        );
+        // invalid arguments do not count as a tool call
+        assert!(matches!(
+            &planned[2],
+            PlannedToolCall::Answer { id, content }
+                if id == "call_3" && content.starts_with("Invalid arguments for the tool")
+        ));
+        assert!(matches!(
+            &planned[3],
+            PlannedToolCall::Run { call, args } if call.id == "call_4" && args["query"] == "x"
+        ));
+        assert_eq!(
+            planned[4],
+            PlannedToolCall::Answer {
+                id: "call_5".to_string(),
+                content: "Tool calls limit reached, answer without calling any more tools"
+                    .to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_agent_state_round_trip() {
+        let job_id = Uuid::new_v4();
+        let state = AgentState {
+            messages: vec![
+                json!({ "role": "user", "content": "hello" }),
+                json!({ "role": "assistant", "tool_calls": [] }),
+            ],
+            iteration: 1,
+            tool_calls_count: 1,
+            tool_calls: vec![
+                PendingToolCall { id: "call_1".to_string(), answer: ToolCallAnswer::Job(job_id) },
+                PendingToolCall {
+                    id: "call_2".to_string(),
+                    answer: ToolCallAnswer::Content("Unknown tool: x".to_string()),
+                },
+            ],
+        };
+        let value = serde_json::to_value(&state).unwrap();
+        assert_eq!(
+            value["tool_calls"],
+            json!([
+                { "id": "call_1", "answer": { "job": job_id } },
+                { "id": "call_2", "answer": { "content": "Unknown tool: x" } }
+            ])
+        );
+        assert_eq!(serde_json::from_value::<AgentState>(value).unwrap(), state);
+    }
+
+    #[test]
+    fn test_tool_result_content() {
+        assert_eq!(tool_result_content(Some("{\"a\":1}"), true), "{\"a\":1}");
+        assert_eq!(tool_result_content(None, true), "null");
+        assert_eq!(
+            tool_result_content(Some("{\"error\":\"boom\"}"), false),
+            "Tool failed with error: {\"error\":\"boom\"}"
+        );
+        assert_eq!(
+            tool_message("call_1", "null".to_string()),
+            json!({ "role": "tool", "tool_call_id": "call_1", "content": "null" })
+        );
+    }
+}
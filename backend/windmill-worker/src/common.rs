@@ -744,7 +744,8 @@ async fn get_workspace_s3_resource_path(
     storage: Option<&String>,
 ) -> windmill_common::error::Result<Option<ObjectStoreResource>> {
     use windmill_common::{
-        job_s3_helpers_oss::get_s3_resource_internal, s3_helpers::StorageResourceType,
+        job_s3_helpers_oss::get_s3_resource_internal,
+        s3_helpers::{FilesystemResource, StorageResourceType},
     };
 
     let raw_lfs_opt = if let Some(storage) = storage {
@@ -798,6 +799,10 @@ async fn get_workspace_s3_resource_path(
                 resource_path.to_string(),
             )
         }
+        Some(LargeFileStorage::FilesystemStorage(filesystem)) => {
+            return FilesystemResource::from_storage(&filesystem)
+                .map(|resource| Some(ObjectStoreResource::Filesystem(resource)));
+        }
         None => {
             return Ok(None);
         }
@@ -432,6 +432,7 @@ async fn spawn_dedicated_workers_for_flow(
                     }
                 }
                 FlowModuleValue::Flow { .. } => (),
+                FlowModuleValue::AIAgent { .. } => (),
                 FlowModuleValue::Identity => (),
             }
         } else {
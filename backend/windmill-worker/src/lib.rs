@@ -6,6 +6,7 @@ mod mssql_executor;
 mod snowflake_executor;
 
 mod agent_workers;
+mod ai_executor;
 #[cfg(feature = "python")]
 mod ansible_executor;
 mod bash_executor;
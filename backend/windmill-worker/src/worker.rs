@@ -101,6 +101,7 @@ use rand::Rng;
 
 use crate::{
     agent_workers::{queue_init_job, queue_periodic_job},
+    ai_executor::handle_ai_agent_job,
     bash_executor::{handle_bash_job, handle_powershell_job},
     bun_executor::handle_bun_job,
     common::{
@@ -2529,6 +2530,32 @@ pub async fn handle_queued_job(
                     ));
                 }
             },
+            JobKind::AIAgent => match conn {
+                Connection::Sql(db) => {
+                    let result = handle_ai_agent_job(
+                        &job,
+                        db,
+                        client,
+                        conn,
+                        &mut mem_peak,
+                        &mut canceled_by,
+                        worker_name,
+                        occupancy_metrics,
+                    )
+                    .await
+                    .transpose();
+                    match result {
+                        // suspended until its tool jobs complete, it is pulled again then
+                        None => return Ok(true),
+                        Some(result) => result,
+                    }
+                }
+                Connection::Http(_) => {
+                    return Err(Error::internal_err(
+                        "Could not handle AI agent job with agent worker".to_string(),
+                    ));
+                }
+            },
             JobKind::Identity => Ok(job
                 .args
                 .as_ref()
@@ -2720,6 +2747,7 @@ async fn try_validate_schema(
                 JobKind::AppDependencies => 12,
                 JobKind::Noop => 13,
                 JobKind::FlowNode => 14,
+                JobKind::AIAgent => 15,
             };
 
             let sv = match job.runnable_id {
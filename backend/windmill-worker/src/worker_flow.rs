@@ -38,9 +38,12 @@ use windmill_common::db::Authed;
 use windmill_common::flow_status::{
     ApprovalConditions, FlowStatusModuleWParent, Iterator as FlowIterator, JobResult,
 };
-use windmill_common::flows::{add_virtual_items_if_necessary, Branch, FlowNodeId, StopAfterIf};
+use windmill_common::flows::{
+    add_virtual_items_if_necessary, AIAgentConfig, Branch, FlowNodeId, StopAfterIf,
+};
 use windmill_common::jobs::{
-    script_path_to_payload, JobKind, JobPayload, OnBehalfOf, RawCode, ENTRYPOINT_OVERRIDE,
+    script_path_to_payload, JobKind, JobPayload, OnBehalfOf, RawCode, AI_AGENT_ARG,
+    ENTRYPOINT_OVERRIDE,
 };
 use windmill_common::scripts::ScriptHash;
 use windmill_common::users::username_to_permissioned_as;
@@ -2483,7 +2486,8 @@ async fn push_next_flow_job(
                 FlowModuleValue::Script { input_transforms, .. }
                 | FlowModuleValue::RawScript { input_transforms, .. }
                 | FlowModuleValue::FlowScript { input_transforms, .. }
-                | FlowModuleValue::Flow { input_transforms, .. },
+                | FlowModuleValue::Flow { input_transforms, .. }
+                | FlowModuleValue::AIAgent { input_transforms, .. },
             ) => {
                 let ctx = get_transform_context(&flow_job, &previous_id, &status)
                     .warn_after_seconds(3)
@@ -2512,6 +2516,28 @@ async fn push_next_flow_job(
                 )
                 .warn_after_seconds(3)
                 .await
+                .map(|mut args| {
+                    // the tools and limits of the agent are not inputs, they are passed along
+                    if let Ok(FlowModuleValue::AIAgent {
+                        tools,
+                        max_iterations,
+                        max_tool_calls,
+                        tool_timeout,
+                        output_schema,
+                        ..
+                    }) = &value
+                    {
+                        let config = AIAgentConfig {
+                            tools: tools.clone(),
+                            max_iterations: *max_iterations,
+                            max_tool_calls: *max_tool_calls,
+                            tool_timeout: *tool_timeout,
+                            output_schema: output_schema.clone(),
+                        };
+                        args.insert(AI_AGENT_ARG.to_string(), to_raw_value(&config));
+                    }
+                    args
+                })
                 .map(Marc::new)
             }
             Ok(_) => Ok(arc_flow_job_args.clone()),
@@ -3348,6 +3374,19 @@ async fn compute_next_flow_transform(
 
     match module.get_value()? {
         FlowModuleValue::Identity => trivial_next_job(JobPayload::Identity),
+        FlowModuleValue::AIAgent { .. } => {
+            let payload = JobPayloadWithTag {
+                payload: JobPayload::AIAgent { path: get_path(flow_job, status, module) },
+                tag: None,
+                delete_after_use,
+                timeout: module.timeout,
+                on_behalf_of: None,
+            };
+            Ok(NextFlowTransform::Continue(
+                ContinuePayload::SingleJob(payload),
+                NextStatus::NextStep,
+            ))
+        }
         FlowModuleValue::Flow { path, .. } => {
             let payload =
                 flow_to_payload(path, delete_after_use, &flow_job.workspace_id, db).await?;
@@ -14,7 +14,7 @@ use uuid::Uuid;
 use windmill_common::assets::{clear_asset_usage, insert_asset_usage, AssetUsageKind};
 use windmill_common::error::Error;
 use windmill_common::error::Result;
-use windmill_common::flows::{FlowModule, FlowModuleValue, FlowNodeId};
+use windmill_common::flows::{AgentToolKind, FlowModule, FlowModuleValue, FlowNodeId};
 use windmill_common::get_latest_deployed_hash_for_path;
 use windmill_common::jobs::JobPayload;
 use windmill_common::scripts::ScriptHash;
@@ -1130,6 +1130,19 @@ async fn lock_modules<'c>(
                     .execute(&mut *tx)
                     .await?;
                 }
+                FlowModuleValue::AIAgent { tools, .. } if !skip_flow_update => {
+                    for tool in tools.iter().filter(|t| !t.path.starts_with("hub/")) {
+                        sqlx::query!(
+                            "INSERT INTO workspace_runnable_dependencies (flow_path, runnable_path, runnable_is_flow, workspace_id) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+                            job_path,
+                            tool.path,
+                            tool.kind == AgentToolKind::Flow,
+                            job.workspace_id,
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                }
                 _ => (),
             };
             modified_ids.extend(nmodified_ids);
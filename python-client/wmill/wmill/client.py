@@ -950,6 +950,40 @@ class Windmill:
             },
         )
 
+    def publish_amqp_message(
+        self,
+        amqp_resource_path: str,
+        routing_key: str,
+        payload: Any,
+        exchange: str = "",
+        content_type: str | None = None,
+        headers: dict | None = None,
+        persistent: bool = True,
+        message_id: str | None = None,
+        correlation_id: str | None = None,
+        reply_to: str | None = None,
+    ) -> None:
+        """
+        Publish a message to an AMQP broker (e.g. RabbitMQ) and wait for the broker to confirm it.
+        Strings are published as is, any other payload as json.
+        """
+        amqp_resource_path = parse_resource_syntax(amqp_resource_path) or amqp_resource_path
+        self.post(
+            f"/w/{self.workspace}/amqp_triggers/publish",
+            json={
+                "amqp_resource_path": amqp_resource_path,
+                "routing_key": routing_key,
+                "payload": payload,
+                "exchange": exchange,
+                "content_type": content_type,
+                "headers": headers,
+                "persistent": persistent,
+                "message_id": message_id,
+                "correlation_id": correlation_id,
+                "reply_to": reply_to,
+            },
+        )
+
 
 def init_global_client(f):
     @functools.wraps(f)
@@ -1363,6 +1397,20 @@ def send_teams_message(
     return _client.send_teams_message(conversation_id, text, success, card_block)
 
 
+@init_global_client
+def publish_amqp_message(
+    amqp_resource_path: str,
+    routing_key: str,
+    payload: Any,
+    exchange: str = "",
+    **kwargs,
+) -> None:
+    """Publish a message to an AMQP broker and wait for the broker to confirm it."""
+    return _client.publish_amqp_message(
+        amqp_resource_path, routing_key, payload, exchange=exchange, **kwargs
+    )
+
+
 @init_global_client
 def cancel_running() -> dict:
     """Cancel currently running executions of the same script."""
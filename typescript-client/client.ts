@@ -8,6 +8,7 @@ import {
   OidcService,
   UserService,
   TeamsService,
+  AmqpTriggerService,
 } from "./index";
 import { OpenAPI } from "./index";
 // import type { DenoS3LightClientSettings } from "./index";
@@ -682,6 +683,47 @@ export async function setVariable(
   }
 }
 
+interface AmqpPublishOptions {
+  exchange?: string;
+  contentType?: string;
+  headers?: Record<string, any>;
+  persistent?: boolean;
+  messageId?: string;
+  correlationId?: string;
+  replyTo?: string;
+}
+
+/**
+ * Publish a message to an AMQP broker (e.g. RabbitMQ) and wait for the broker to confirm it
+ * @param amqpResourcePath path of the amqp resource
+ * @param routingKey routing key of the message, the queue name when using the default exchange
+ * @param payload strings are published as is, any other value as json
+ * @param options exchange (default exchange if not set) and properties of the message
+ */
+export async function publishAmqpMessage(
+  amqpResourcePath: string,
+  routingKey: string,
+  payload: any,
+  options?: AmqpPublishOptions
+): Promise<void> {
+  const workspace = getWorkspace();
+  await AmqpTriggerService.publishAmqpMessage({
+    workspace,
+    requestBody: {
+      amqp_resource_path: parseResourceSyntax(amqpResourcePath) ?? amqpResourcePath,
+      routing_key: routingKey,
+      payload,
+      exchange: options?.exchange,
+      content_type: options?.contentType,
+      headers: options?.headers,
+      persistent: options?.persistent,
+      message_id: options?.messageId,
+      correlation_id: options?.correlationId,
+      reply_to: options?.replyTo,
+    },
+  });
+}
+
 export async function databaseUrlFromResource(path: string): Promise<string> {
   const resource = await getResource(path);
   return `postgresql://${resource.user}:${resource.password}@${resource.host}:${resource.port}/${resource.dbname}?sslmode=${resource.sslmode}`;